@@ -6,11 +6,13 @@ use axum::{
 use serde_json::json;
 use thiserror::Error;
 use tracing::error;
+use yoloeats_domain::{ErrorBody, ErrorCode};
+use yoloeats_tracing::current_request_id;
 
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("HTTP request failed: {0}")]
-    ReqwestError(#[from] reqwest::Error),
+    ReqwestError(#[from] reqwest_middleware::Error),
 
     #[error("Neo4j database error: {0}")]
     Neo4jError(#[from] neo4rs::Error),
@@ -21,12 +23,18 @@ pub enum AppError {
     #[error("Configuration error: Missing environment variable '{0}'")]
     MissingEnvVar(String),
 
-    #[error("Resource not found: {0}")]
-    NotFoundError(String),
+    #[error("Profile not found: {0}")]
+    ProfileNotFoundError(String),
+
+    #[error("Product not found: {0}")]
+    ProductNotFoundError(String),
 
     #[error("Error response from upstream service '{service}': Status {status}")]
     UpstreamServiceError { service: String, status: u16 },
 
+    #[error("Error response from upstream service '{service}': gRPC status {code}")]
+    UpstreamRpcError { service: String, code: tonic::Code },
+
     #[error("Failed to process user profile: {0}")]
     ProfileProcessingError(String),
 
@@ -42,21 +50,53 @@ pub enum AppError {
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, error_message) = match &self {
-            AppError::NotFoundError(msg) => (StatusCode::NOT_FOUND, msg.clone()),
-            AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
+        let mut details = None;
+        let (status, code, error_message) = match &self {
+            AppError::ProfileNotFoundError(msg) => (
+                StatusCode::NOT_FOUND,
+                ErrorCode::ProfileNotFound,
+                msg.clone(),
+            ),
+            AppError::ProductNotFoundError(msg) => (
+                StatusCode::NOT_FOUND,
+                ErrorCode::ProductNotFound,
+                msg.clone(),
+            ),
+            AppError::BadRequest(msg) => (
+                StatusCode::BAD_REQUEST,
+                ErrorCode::InvalidRequest,
+                msg.clone(),
+            ),
             AppError::SerializationError(e) => {
                 error!("Serialization error: {}", e);
-                (StatusCode::BAD_REQUEST, "Invalid data format".to_string())
+                (
+                    StatusCode::BAD_REQUEST,
+                    ErrorCode::InvalidData,
+                    "Invalid data format".to_string(),
+                )
             }
             AppError::UpstreamServiceError { service, status } => {
                 error!(
                     "Upstream service '{}' failed with status {}",
                     service, status
                 );
+                details = Some(json!({ "service": service }));
                 // Bad Gateway seems appropriate
                 (
                     StatusCode::BAD_GATEWAY,
+                    ErrorCode::UpstreamUnavailable,
+                    format!("Error communicating with {}", service),
+                )
+            }
+            AppError::UpstreamRpcError { service, code } => {
+                error!(
+                    "Upstream service '{}' failed with gRPC status {}",
+                    service, code
+                );
+                details = Some(json!({ "service": service }));
+                (
+                    StatusCode::BAD_GATEWAY,
+                    ErrorCode::UpstreamUnavailable,
                     format!("Error communicating with {}", service),
                 )
             }
@@ -64,6 +104,7 @@ impl IntoResponse for AppError {
                 error!("Data processing error: {}", msg);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::InternalError,
                     "Failed to process data".to_string(),
                 )
             }
@@ -71,6 +112,7 @@ impl IntoResponse for AppError {
                 error!("HTTP client error: {}", e);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::UpstreamUnavailable,
                     "Internal network error".to_string(),
                 )
             }
@@ -78,6 +120,7 @@ impl IntoResponse for AppError {
                 error!("Neo4j error: {}", e);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::DatabaseError,
                     "Database error".to_string(),
                 )
             }
@@ -85,18 +128,130 @@ impl IntoResponse for AppError {
                 error!("Missing configuration: {}", var);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::ConfigurationError,
                     "Internal server configuration error".to_string(),
                 )
             }
             AppError::InternalServerError => (
                 StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorCode::InternalError,
                 "An internal server error occurred".to_string(),
             ),
         };
 
-        let body = Json(json!({ "error": error_message }));
-        (status, body).into_response()
+        let mut body = ErrorBody::new(code, error_message)
+            .with_request_id(current_request_id().map(|id| id.to_string()));
+        if let Some(details) = details {
+            body = body.with_details(details);
+        }
+        (status, Json(body)).into_response()
     }
 }
 
 pub type Result<T, E = AppError> = std::result::Result<T, E>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use yoloeats_tracing::{RequestId, with_request_id};
+
+    async fn render(err: AppError) -> (StatusCode, Value) {
+        let id = RequestId::parse("req-1").unwrap();
+        let response = with_request_id(id, async move { err.into_response() }).await;
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    fn envelope(code: &str, message: &str) -> Value {
+        json!({
+            "code": code,
+            "message": message,
+            "error": message,
+            "requestId": "req-1"
+        })
+    }
+
+    #[tokio::test]
+    async fn error_envelope_json_snapshots() {
+        let mut upstream_http = envelope(
+            "upstream_unavailable",
+            "Error communicating with user-profile-service",
+        );
+        upstream_http["details"] = json!({ "service": "user-profile-service" });
+        let mut upstream_rpc = envelope(
+            "upstream_unavailable",
+            "Error communicating with product-catalog-service",
+        );
+        upstream_rpc["details"] = json!({ "service": "product-catalog-service" });
+
+        let cases = [
+            (
+                AppError::SerializationError(serde_json::from_str::<Value>("{").unwrap_err()),
+                StatusCode::BAD_REQUEST,
+                envelope("invalid_data", "Invalid data format"),
+            ),
+            (
+                AppError::MissingEnvVar("NEO4J_URI".to_string()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                envelope("configuration_error", "Internal server configuration error"),
+            ),
+            (
+                AppError::ProfileNotFoundError("User profile not found for user u1".to_string()),
+                StatusCode::NOT_FOUND,
+                envelope("profile_not_found", "User profile not found for user u1"),
+            ),
+            (
+                AppError::ProductNotFoundError("Product not found for identifier 123".to_string()),
+                StatusCode::NOT_FOUND,
+                envelope("product_not_found", "Product not found for identifier 123"),
+            ),
+            (
+                AppError::UpstreamServiceError {
+                    service: "user-profile-service".to_string(),
+                    status: 503,
+                },
+                StatusCode::BAD_GATEWAY,
+                upstream_http,
+            ),
+            (
+                AppError::UpstreamRpcError {
+                    service: "product-catalog-service".to_string(),
+                    code: tonic::Code::Unavailable,
+                },
+                StatusCode::BAD_GATEWAY,
+                upstream_rpc,
+            ),
+            (
+                AppError::ProfileProcessingError("bad json".to_string()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                envelope("internal_error", "Failed to process data"),
+            ),
+            (
+                AppError::ProductProcessingError("bad json".to_string()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                envelope("internal_error", "Failed to process data"),
+            ),
+            (
+                AppError::BadRequest("productIdentifier must not be empty".to_string()),
+                StatusCode::BAD_REQUEST,
+                envelope("invalid_request", "productIdentifier must not be empty"),
+            ),
+            (
+                AppError::InternalServerError,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                envelope("internal_error", "An internal server error occurred"),
+            ),
+        ];
+
+        for (err, expected_status, expected_body) in cases {
+            let label = format!("{:?}", err);
+            let (status, body) = render(err).await;
+            assert_eq!(status, expected_status, "{}", label);
+            assert_eq!(body, expected_body, "{}", label);
+        }
+    }
+}
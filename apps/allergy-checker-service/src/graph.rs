@@ -0,0 +1,226 @@
+//! The conflict lookup behind `/api/v1/check`: which of a product's ingredients are the
+//! user's allergens, may carry traces of them, or go against their diets.
+//!
+//! [`Neo4jGraph`] queries the ingredient graph; [`MemoryGraph`] answers from a list of
+//! triples for `STORAGE_MODE=memory`.
+
+use crate::errors::{AppError, Result};
+use async_trait::async_trait;
+use neo4rs::{Error as Neo4jError, Graph, query};
+use std::collections::HashSet;
+use tracing::error;
+use yoloeats_domain::ingredient_graph::{
+    INGREDIENT_ALLERGENS, INGREDIENT_DIET_CONFLICTS, TRACE_ALLERGENS,
+};
+
+/// Matches found for one check, by the user's allergen and diet names.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct GraphConflicts {
+    pub allergens: HashSet<String>,
+    pub traces: HashSet<String>,
+    pub diets: HashSet<String>,
+}
+
+#[async_trait]
+pub trait ConflictGraph: Send + Sync {
+    async fn conflicts(
+        &self,
+        ingredients: Vec<String>,
+        user_allergens: Vec<String>,
+        user_diets: Vec<String>,
+    ) -> Result<GraphConflicts>;
+}
+
+pub struct Neo4jGraph {
+    graph: Graph,
+}
+
+impl Neo4jGraph {
+    pub fn new(graph: Graph) -> Self {
+        Neo4jGraph { graph }
+    }
+}
+
+#[async_trait]
+impl ConflictGraph for Neo4jGraph {
+    async fn conflicts(
+        &self,
+        ingredients: Vec<String>,
+        user_allergens: Vec<String>,
+        user_diets: Vec<String>,
+    ) -> Result<GraphConflicts> {
+        let cypher_query = query(
+            r#"
+        UNWIND $ingredients AS ingredientName
+        MATCH (i:Ingredient {name: ingredientName}) // Match ingredients from the input list
+        OPTIONAL MATCH (i)-[:IS_ALLERGEN]->(a:Allergen) WHERE a.name IN $userAllergens
+        OPTIONAL MATCH (i)-[:MAY_CONTAIN_TRACE]->(ta:Allergen) WHERE ta.name IN $userAllergens
+        OPTIONAL MATCH (i)-[:CONFLICTS_WITH_DIET]->(d:DietaryPreference) WHERE d.name IN $userDiets
+        RETURN ingredientName,
+               collect(DISTINCT a.name) AS conflictingAllergens,
+               collect(DISTINCT ta.name) AS traceAllergens,
+               collect(DISTINCT d.name) AS conflictingDiets
+    "#,
+        )
+        .param("ingredients", ingredients)
+        .param("userAllergens", user_allergens)
+        .param("userDiets", user_diets);
+
+        let mut result_stream = self.graph.execute(cypher_query).await?;
+
+        let mut conflicts = GraphConflicts::default();
+        loop {
+            match result_stream.next().await {
+                Ok(Some(row)) => {
+                    let allergens: Vec<String> = row
+                        .get("conflictingAllergens")
+                        .map_err(|e| AppError::Neo4jError(Neo4jError::DeserializationError(e)))?;
+                    let traces: Vec<String> = row
+                        .get("traceAllergens")
+                        .map_err(|e| AppError::Neo4jError(Neo4jError::DeserializationError(e)))?;
+                    let diets: Vec<String> = row
+                        .get("conflictingDiets")
+                        .map_err(|e| AppError::Neo4jError(Neo4jError::DeserializationError(e)))?;
+
+                    conflicts.allergens.extend(allergens);
+                    conflicts.traces.extend(traces);
+                    conflicts.diets.extend(diets);
+                }
+                Ok(None) => {
+                    break;
+                }
+                Err(e) => {
+                    error!("Error fetching row from Neo4j stream: {}", e);
+                    return Err(AppError::Neo4jError(e));
+                }
+            }
+        }
+        Ok(conflicts)
+    }
+}
+
+/// The relationships the checker walks, from an ingredient to an allergen or a diet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relationship {
+    IsAllergen,
+    MayContainTrace,
+    ConflictsWithDiet,
+}
+
+/// `(ingredient)-[relationship]->(target)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Triple {
+    pub ingredient: String,
+    pub relationship: Relationship,
+    pub target: String,
+}
+
+/// The ingredient graph as a list of triples, matched the way the Cypher query does.
+pub struct MemoryGraph {
+    triples: Vec<Triple>,
+}
+
+impl MemoryGraph {
+    pub fn new(triples: Vec<Triple>) -> Self {
+        MemoryGraph { triples }
+    }
+
+    /// The same links the seed CLI writes to Neo4j.
+    pub fn seeded() -> Self {
+        let triples = triples(INGREDIENT_ALLERGENS, Relationship::IsAllergen)
+            .chain(triples(TRACE_ALLERGENS, Relationship::MayContainTrace))
+            .chain(triples(
+                INGREDIENT_DIET_CONFLICTS,
+                Relationship::ConflictsWithDiet,
+            ))
+            .collect();
+        MemoryGraph::new(triples)
+    }
+}
+
+fn triples(
+    pairs: &'static [(&'static str, &'static str)],
+    relationship: Relationship,
+) -> impl Iterator<Item = Triple> {
+    pairs.iter().map(move |&(ingredient, target)| Triple {
+        ingredient: ingredient.to_string(),
+        relationship,
+        target: target.to_string(),
+    })
+}
+
+#[async_trait]
+impl ConflictGraph for MemoryGraph {
+    async fn conflicts(
+        &self,
+        ingredients: Vec<String>,
+        user_allergens: Vec<String>,
+        user_diets: Vec<String>,
+    ) -> Result<GraphConflicts> {
+        let ingredients: HashSet<String> = ingredients.into_iter().collect();
+        let mut conflicts = GraphConflicts::default();
+        for triple in self
+            .triples
+            .iter()
+            .filter(|t| ingredients.contains(&t.ingredient))
+        {
+            let (found, wanted) = match triple.relationship {
+                Relationship::IsAllergen => (&mut conflicts.allergens, &user_allergens),
+                Relationship::MayContainTrace => (&mut conflicts.traces, &user_allergens),
+                Relationship::ConflictsWithDiet => (&mut conflicts.diets, &user_diets),
+            };
+            if wanted.contains(&triple.target) {
+                found.insert(triple.target.clone());
+            }
+        }
+        Ok(conflicts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    fn set(values: &[&str]) -> HashSet<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn seeded_graph_finds_allergens_traces_and_diets() {
+        let graph = MemoryGraph::seeded();
+        let conflicts = graph
+            .conflicts(
+                strings(&["sugar", "whole milk powder", "hazelnuts", "en:peanuts"]),
+                strings(&["milk", "peanuts"]),
+                strings(&["vegan"]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            conflicts,
+            GraphConflicts {
+                allergens: set(&["milk"]),
+                traces: set(&["peanuts"]),
+                diets: set(&["vegan"]),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn only_the_users_allergens_and_diets_count() {
+        let graph = MemoryGraph::seeded();
+        let conflicts = graph
+            .conflicts(
+                strings(&["milk", "wheat"]),
+                strings(&["fish"]),
+                strings(&["vegetarian"]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(conflicts, GraphConflicts::default());
+    }
+}
@@ -1,107 +1,93 @@
 use crate::{
-    errors::{AppError, Result},
-    models::{CheckRequest, CheckResult, ProductData, SafetyStatus, UserProfileData},
+    errors::Result,
+    graph::GraphConflicts,
+    models::{CheckRequest, CheckResult, SafetyStatus},
     state::AppState,
+    tunables::{TRACE_POLICY, TracePolicy},
+};
+use axum::{
+    Json,
+    extract::State,
+    http::{HeaderMap, header},
 };
-use axum::{Json, extract::State};
-use neo4rs::{Error as Neo4jError, query};
-use reqwest::StatusCode;
 use std::{collections::HashSet, sync::Arc};
 use tracing::{debug, info, instrument, warn};
+use yoloeats_domain::{ErrorBody, IngredientEntry};
+use yoloeats_taxonomy::canonical_ingredient;
 
 // TODO: Replace with a more robust NLP or rule-based parser
+/// Comma-separated ingredients, lowercased and folded onto ingredient-graph names.
 fn parse_ingredients(text: Option<String>) -> HashSet<String> {
     text.map(|s| {
         s.split(',')
             .map(|item| item.trim().to_lowercase())
             .filter(|item| !item.is_empty())
+            .map(|item| canonical_ingredient(&item).to_string())
             .collect::<HashSet<String>>()
     })
     .unwrap_or_default()
 }
 
-#[instrument(skip(state, payload), fields(user_id = %payload.user_id, product = %payload.product_identifier))]
+/// OpenFoodFacts' parsed ingredients, by name, folded onto ingredient-graph names.
+fn structured_ingredients(entries: &[IngredientEntry]) -> HashSet<String> {
+    entries
+        .iter()
+        .filter_map(IngredientEntry::name)
+        .map(|name| canonical_ingredient(&name).to_string())
+        .collect()
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/check",
+    tag = "v1",
+    request_body = CheckRequest,
+    responses(
+        (status = 200, description = "The product's safety for the user.", body = CheckResult),
+        (status = 400, description = "The profile or product could not be read.", body = ErrorBody),
+        (status = 404, description = "No such profile or product.", body = ErrorBody),
+        (status = 500, description = "The graph or a peer call failed.", body = ErrorBody),
+        (status = 502, description = "The profile or catalog service answered an error.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, headers, payload), fields(user_id = %payload.user_id, product = %payload.product_identifier))]
 pub async fn check_product_safety(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Json(payload): Json<CheckRequest>,
 ) -> Result<Json<CheckResult>> {
-    info!("Received safety check request");
-
-    // TODO: Use actual user_id from payload or auth context
-    let profile_url = format!("{}/api/v1/profile", state.user_profile_service_url);
-    debug!("Fetching user profile from: {}", profile_url);
-
-    let profile_resp = state.http_client.get(&profile_url).send().await?;
+    info!(
+        transport = ?state.upstreams.transport(),
+        "Received safety check request"
+    );
 
-    let user_profile: UserProfileData = match profile_resp.status() {
-        StatusCode::OK => profile_resp.json::<UserProfileData>().await.map_err(|e| {
-            tracing::error!("Failed to deserialize user profile JSON: {}", e);
-            AppError::ProfileProcessingError(format!("Failed to parse profile data: {}", e))
-        })?,
-        StatusCode::NOT_FOUND => {
-            warn!("User profile not found at {}", profile_url);
-            return Err(AppError::NotFoundError(format!(
-                "User profile not found for user {}",
-                payload.user_id
-            )));
-        }
-        other_status => {
-            let body = profile_resp.text().await.unwrap_or_default();
-            tracing::error!(
-                "User profile service failed with status {}: {}",
-                other_status,
-                body
-            );
-            return Err(AppError::UpstreamServiceError {
-                service: "user-profile-service".to_string(),
-                status: other_status.as_u16(),
-            });
-        }
-    };
+    let authorization = headers.get(header::AUTHORIZATION);
+    let user_profile = state
+        .upstreams
+        .fetch_profile(&payload.user_id, authorization)
+        .await?;
     debug!(
         "User profile fetched. Allergens: {}, Diets: {}",
         user_profile.allergens.len(),
         user_profile.dietary_prefs.len()
     );
 
-    let product_url = format!(
-        "{}/api/v1/products/barcode/{}",
-        state.product_catalog_service_url, payload.product_identifier
-    );
-    debug!("Fetching product data from: {}", product_url);
-    let product_resp = state.http_client.get(&product_url).send().await?;
-    let product_data: ProductData = match product_resp.status() {
-        StatusCode::OK => product_resp.json::<ProductData>().await.map_err(|e| {
-            tracing::error!("Failed to deserialize product data JSON: {}", e);
-            AppError::ProductProcessingError(format!("Failed to parse product data: {}", e))
-        })?,
-        StatusCode::NOT_FOUND => {
-            warn!("Product not found at {}", product_url);
-            return Err(AppError::NotFoundError(format!(
-                "Product not found for identifier {}",
-                payload.product_identifier
-            )));
-        }
-        other_status => {
-            let body = product_resp.text().await.unwrap_or_default();
-            tracing::error!(
-                "Product catalog service failed with status {}: {}",
-                other_status,
-                body
-            );
-            return Err(AppError::UpstreamServiceError {
-                service: "product-catalog-service".to_string(),
-                status: other_status.as_u16(),
-            });
-        }
-    };
+    let product_data = state
+        .upstreams
+        .fetch_product(&payload.product_identifier)
+        .await?;
     debug!(
-        "Product data fetched. Ingredients present: {}, Traces: {}",
+        "Product data fetched. Ingredients present: {}, structured: {}, Traces: {}",
         product_data.ingredients_text.is_some(),
+        product_data.ingredients.is_some(),
         product_data.traces_tags.len()
     );
 
-    let ingredients = parse_ingredients(product_data.ingredients_text);
+    // The parsed list beats splitting the text on commas, which sub-ingredients defeat.
+    let ingredients = match product_data.ingredients.as_deref() {
+        Some(entries) if !entries.is_empty() => structured_ingredients(entries),
+        _ => parse_ingredients(product_data.ingredients_text),
+    };
     let trace_ingredients: HashSet<String> = product_data
         .traces_tags
         .into_iter()
@@ -126,62 +112,22 @@ pub async fn check_product_safety(
         }));
     }
 
-    debug!("Querying Neo4j for conflicts...");
-    let user_allergens: Vec<String> = user_profile.allergens.into_iter().collect();
-    let user_diets: Vec<String> = user_profile.dietary_prefs.into_iter().collect();
-
-    let cypher_query = query(
-        r#"
-        UNWIND $ingredients AS ingredientName
-        MATCH (i:Ingredient {name: ingredientName}) // Match ingredients from the input list
-        OPTIONAL MATCH (i)-[:IS_ALLERGEN]->(a:Allergen) WHERE a.name IN $userAllergens
-        OPTIONAL MATCH (i)-[:MAY_CONTAIN_TRACE]->(ta:Allergen) WHERE ta.name IN $userAllergens
-        OPTIONAL MATCH (i)-[:CONFLICTS_WITH_DIET]->(d:DietaryPreference) WHERE d.name IN $userDiets
-        RETURN ingredientName,
-               collect(DISTINCT a.name) AS conflictingAllergens,
-               collect(DISTINCT ta.name) AS traceAllergens,
-               collect(DISTINCT d.name) AS conflictingDiets
-    "#,
-    )
-    .param("ingredients", all_potential_ingredients)
-    .param("userAllergens", user_allergens)
-    .param("userDiets", user_diets);
-
-    let mut result_stream = state.neo4j_client.execute(cypher_query).await?;
-
-    let mut conflicting_allergens_set = HashSet::new();
-    let mut trace_allergens_set = HashSet::new();
-    let mut conflicting_diets_set = HashSet::new();
-
-    loop {
-        match result_stream.next().await {
-            Ok(Some(row)) => {
-                let conflicts: Vec<String> = row
-                    .get("conflictingAllergens")
-                    .map_err(|e| AppError::Neo4jError(Neo4jError::DeserializationError(e)))?;
-                let traces: Vec<String> = row
-                    .get("traceAllergens")
-                    .map_err(|e| AppError::Neo4jError(Neo4jError::DeserializationError(e)))?;
-                let diets: Vec<String> = row
-                    .get("conflictingDiets")
-                    .map_err(|e| AppError::Neo4jError(Neo4jError::DeserializationError(e)))?;
-
-                conflicting_allergens_set.extend(conflicts);
-                trace_allergens_set.extend(traces);
-                conflicting_diets_set.extend(diets);
-            }
-            Ok(None) => {
-                break;
-            }
-            Err(e) => {
-                tracing::error!("Error fetching row from Neo4j stream: {}", e);
-                return Err(AppError::Neo4jError(e));
-            }
-        }
-    }
+    debug!("Querying the ingredient graph for conflicts...");
+    let GraphConflicts {
+        allergens: conflicting_allergens_set,
+        traces: trace_allergens_set,
+        diets: conflicting_diets_set,
+    } = state
+        .graph
+        .conflicts(
+            all_potential_ingredients,
+            user_profile.allergens,
+            user_profile.dietary_prefs,
+        )
+        .await?;
 
     debug!(
-        "Neo4j conflicts found - Allergens: {:?}, Traces: {:?}, Diets: {:?}",
+        "Graph conflicts found - Allergens: {:?}, Traces: {:?}, Diets: {:?}",
         conflicting_allergens_set, trace_allergens_set, conflicting_diets_set
     );
 
@@ -190,8 +136,13 @@ pub async fn check_product_safety(
         SafetyStatus::Unsafe
     } else if !trace_allergens_set.is_empty() {
         // TODO: Factor in user_profile.risk_tolerance here
-        warn!("Trace allergens found, setting status to Caution (risk tolerance not implemented)");
-        SafetyStatus::Caution
+        let policy = state.config.get(TRACE_POLICY);
+        warn!("Trace allergens found, applying trace policy {}", policy);
+        match policy {
+            TracePolicy::Caution => SafetyStatus::Caution,
+            TracePolicy::Unsafe => SafetyStatus::Unsafe,
+            TracePolicy::Ignore => SafetyStatus::Safe,
+        }
     } else {
         SafetyStatus::Safe
     };
@@ -207,3 +158,41 @@ pub async fn check_product_safety(
 
     Ok(Json(check_result))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ingredients_are_split_lowercased_and_folded_onto_synonyms() {
+        let parsed = parse_ingredients(Some(
+            "Sugar, Skimmed Milk,  Whole milk powder, , Soya beans".to_string(),
+        ));
+        let expected: HashSet<String> = ["sugar", "milk", "whole milk powder", "soybeans"]
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        assert_eq!(parsed, expected);
+        assert!(parse_ingredients(None).is_empty());
+    }
+
+    #[test]
+    fn structured_ingredients_are_read_by_name() {
+        let entry = |id: Option<&str>, text: &str| IngredientEntry {
+            id: id.map(str::to_string),
+            text: Some(text.to_string()),
+            ..Default::default()
+        };
+        let parsed = structured_ingredients(&[
+            entry(Some("en:skimmed-milk"), "Magermilch"),
+            entry(Some("en:whole-milk-powder"), "Vollmilchpulver"),
+            entry(None, " Soya Beans "),
+            entry(None, ""),
+        ]);
+        let expected: HashSet<String> = ["milk", "whole milk powder", "soybeans"]
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        assert_eq!(parsed, expected);
+    }
+}
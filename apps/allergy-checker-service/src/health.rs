@@ -0,0 +1,52 @@
+//! Dependency checks behind `/health/ready` and `/health/details`.
+
+use crate::state::AppState;
+use crate::upstream::{PRODUCT_CATALOG_SERVICE, USER_PROFILE_SERVICE, Upstreams};
+use yoloeats_health::{
+    Criticality, HealthRegistry,
+    checks::{HttpCheck, Neo4jCheck, RedisCheck},
+};
+
+/// The peers are informational: a checker whose upstreams are down can't answer, but
+/// restarting it or pulling it from rotation wouldn't help either. gRPC channels connect
+/// lazily and have no liveness route, so only the HTTP transport reports on them.
+pub fn registry(state: &AppState) -> HealthRegistry {
+    let mut registry = HealthRegistry::new("allergy-checker-service");
+    // Nothing external to check on in-memory storage.
+    if let Some(clients) = &state.clients {
+        // Redis only holds runtime config overrides; without it the defaults keep working.
+        registry = registry
+            .register(
+                "neo4j",
+                Criticality::Critical,
+                Neo4jCheck(clients.neo4j_client.clone()),
+            )
+            .register(
+                "redis",
+                Criticality::Informational,
+                RedisCheck(clients.redis_client.clone()),
+            );
+    }
+    match &state.upstreams {
+        Upstreams::Http {
+            user_profile_service_url,
+            product_catalog_service_url,
+            ..
+        } => {
+            // Not the upstream client: its retries would stretch a probe past its timeout.
+            let client = reqwest::Client::new();
+            registry
+                .register(
+                    USER_PROFILE_SERVICE,
+                    Criticality::Informational,
+                    HttpCheck::live(client.clone(), user_profile_service_url),
+                )
+                .register(
+                    PRODUCT_CATALOG_SERVICE,
+                    Criticality::Informational,
+                    HttpCheck::live(client, product_catalog_service_url),
+                )
+        }
+        Upstreams::Grpc { .. } => registry,
+    }
+}
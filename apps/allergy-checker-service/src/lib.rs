@@ -0,0 +1,63 @@
+//! Allergy checker service: matches a product's ingredients against a user's allergens
+//! and diets using the Neo4j ingredient graph.
+//!
+//! `main.rs` loads configuration and connects the clients; [`router`] is public so the
+//! integration harness can serve the same routes in-process with injected state. With
+//! `STORAGE_MODE=memory` the graph is [`graph::MemoryGraph`] and nothing external is needed
+//! besides the other two services.
+//!
+//! The API is documented in [`openapi::ApiDoc`], served at `/api-docs/openapi.json` with
+//! Swagger UI at `/docs`.
+
+use axum::{
+    Router,
+    routing::{get, post},
+};
+use handlers::check_product_safety;
+use state::AppState;
+use std::sync::Arc;
+use tower_http::cors::{Any, CorsLayer};
+use utoipa::OpenApi;
+use yoloeats_auth::InternalTokenLayer;
+use yoloeats_dynamic_config::config_router;
+use yoloeats_health::health_router;
+use yoloeats_metrics::{BodyLimitLayer, HttpMetricsLayer, LoadShedLayer, compression_layer};
+use yoloeats_openapi::openapi_router;
+use yoloeats_tracing::RequestIdLayer;
+
+pub mod errors;
+pub mod graph;
+pub mod handlers;
+pub mod health;
+pub mod models;
+pub mod openapi;
+pub mod state;
+pub mod tunables;
+pub mod upstream;
+
+async fn health_check() -> &'static str {
+    "Allergy Checker Service OK"
+}
+
+pub fn router(app_state: Arc<AppState>) -> Router {
+    // Permissive for development.
+    let cors = CorsLayer::new()
+        .allow_origin(Any)
+        .allow_methods(Any)
+        .allow_headers(Any);
+
+    Router::new()
+        .route("/", get(health_check))
+        .route("/api/v1/check", post(check_product_safety))
+        .merge(health_router(Arc::new(health::registry(&app_state))))
+        .merge(config_router(app_state.config.clone()))
+        .merge(openapi_router(openapi::ApiDoc::openapi()))
+        .layer(compression_layer())
+        .layer(InternalTokenLayer::new(app_state.internal_tokens.clone()))
+        .layer(BodyLimitLayer::new(app_state.body_limits.clone()))
+        .layer(LoadShedLayer::new("allergy-checker-service", app_state.load_shed))
+        .layer(HttpMetricsLayer::new("allergy-checker-service"))
+        .layer(RequestIdLayer)
+        .layer(cors)
+        .with_state(app_state)
+}
@@ -1,93 +1,132 @@
-use axum::extract::State;
-use axum::routing::get;
-use axum::{Json, Router, routing::post};
+use allergy_checker_service::{
+    graph::{ConflictGraph, MemoryGraph, Neo4jGraph},
+    router,
+    state::{AppState, Clients},
+    tunables,
+    upstream::{InternalTransport, Upstreams},
+};
 use dotenvy::dotenv;
 use neo4rs::Graph;
-use reqwest::Client;
+use rust_database_clients::{StorageMode, http_client::HttpClientConfig};
 use std::{env, net::SocketAddr, sync::Arc};
-use tower_http::cors::{Any, CorsLayer};
 use tracing::{info, warn};
-use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt};
-
-mod errors;
-mod handlers;
-mod models;
-mod state;
-
-use errors::Result;
-use state::AppState;
-
-async fn check_product_safety(
-    State(_state): State<Arc<AppState>>,
-    Json(_payload): Json<models::CheckRequest>,
-) -> Result<Json<models::CheckResult>> {
-    warn!("'/api/v1/check' endpoint hit, but handler logic not implemented yet.");
-    Ok(Json(models::CheckResult {
-        status: models::SafetyStatus::Caution,
-        conflicting_allergens: vec!["Not Implemented".to_string()],
-        conflicting_diets: vec![],
-        trace_allergens: vec![],
-        is_offline_result: true,
-    }))
-}
-
-async fn health_check() -> &'static str {
-    "Allergy Checker Service OK"
-}
+use yoloeats_auth::InternalTokens;
+use yoloeats_dynamic_config::{DEFAULT_REFRESH_INTERVAL, MemoryStore, RedisStore};
+use yoloeats_metrics::{BodyLimits, LoadShedConfig, install_recorder, metrics_router};
+use yoloeats_shutdown::{Shutdown, drain_timeout_from_env, shutdown_signal};
+use yoloeats_tracing::init_tracing;
 
 #[tokio::main]
 async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
     dotenv().ok();
 
-    tracing_subscriber::registry()
-        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()))
-        .with(fmt::layer())
-        .init();
+    let _telemetry = init_tracing("allergy-checker-service", env!("CARGO_PKG_VERSION"))?;
 
     info!("Starting Allergy Checker Service...");
 
-    let neo4j_uri = env::var("NEO4J_URI").expect("NEO4J_URI must be set");
-    let neo4j_user = env::var("NEO4J_USER").unwrap_or_else(|_| "neo4j".to_string());
-    let neo4j_password = env::var("NEO4J_PASSWORD").expect("NEO4J_PASSWORD must be set");
+    let storage_mode = StorageMode::from_env()?;
     let user_profile_service_url = env::var("USER_PROFILE_SERVICE_URL")
         .unwrap_or_else(|_| "http://user-profile-service:8001".to_string());
     let product_catalog_service_url = env::var("PRODUCT_CATALOG_SERVICE_URL")
         .unwrap_or_else(|_| "http://product-catalog-service:8002".to_string());
+    let internal_transport = env::var("INTERNAL_TRANSPORT")
+        .unwrap_or_else(|_| "http".to_string())
+        .parse::<InternalTransport>()?;
     let port_str = env::var("ALLERGY_CHECKER_SERVICE_PORT").unwrap_or_else(|_| "8003".to_string());
     let port = port_str.parse::<u16>().unwrap_or(8003);
 
-    info!("Neo4j URI: {}", neo4j_uri);
     info!("User Profile Service URL: {}", user_profile_service_url);
     info!(
         "Product Catalog Service URL: {}",
         product_catalog_service_url
     );
 
-    let http_client = Client::new();
-    info!("Reqwest HTTP client created.");
+    let upstreams = match internal_transport {
+        InternalTransport::Http => {
+            let http_config = HttpClientConfig::from_env()?;
+            info!("Outbound HTTP: {:?}", http_config);
+            Upstreams::Http {
+                client: yoloeats_tracing::http_client(http_config.build()?),
+                user_profile_service_url,
+                product_catalog_service_url,
+            }
+        }
+        InternalTransport::Grpc => {
+            let user_profile_grpc_url = env::var("USER_PROFILE_GRPC_URL")
+                .unwrap_or_else(|_| "http://user-profile-service:50051".to_string());
+            let product_catalog_grpc_url = env::var("PRODUCT_CATALOG_GRPC_URL")
+                .unwrap_or_else(|_| "http://product-catalog-service:50052".to_string());
+            info!("User Profile gRPC URL: {}", user_profile_grpc_url);
+            info!("Product Catalog gRPC URL: {}", product_catalog_grpc_url);
+            Upstreams::grpc(&user_profile_grpc_url, &product_catalog_grpc_url)?
+        }
+    };
+    info!(
+        "Internal fetches use {:?} transport.",
+        upstreams.transport()
+    );
+
+    let (graph, clients, config) = match storage_mode {
+        StorageMode::External => {
+            let neo4j_uri = env::var("NEO4J_URI").expect("NEO4J_URI must be set");
+            let neo4j_user = env::var("NEO4J_USER").unwrap_or_else(|_| "neo4j".to_string());
+            let neo4j_password = env::var("NEO4J_PASSWORD").expect("NEO4J_PASSWORD must be set");
+            let redis_uri = env::var("REDIS_URI").expect("REDIS_URI must be set");
+            info!("Neo4j URI: {}", neo4j_uri);
+
+            let neo4j_client = Graph::new(&neo4j_uri, &neo4j_user, &neo4j_password).await?;
+            info!("Neo4j client connected successfully.");
+
+            // Only holds the runtime config overrides; the checker caches nothing.
+            let redis_client = redis::Client::open(redis_uri.as_str())?;
+            let config =
+                tunables::config(RedisStore::new(redis_client.clone(), tunables::SERVICE))?;
+            (
+                Arc::new(Neo4jGraph::new(neo4j_client.clone())) as Arc<dyn ConflictGraph>,
+                Some(Clients {
+                    neo4j_client,
+                    redis_client,
+                }),
+                config,
+            )
+        }
+        StorageMode::Memory => {
+            warn!("STORAGE_MODE=memory: using the seed ingredient graph, held in process.");
+            (
+                Arc::new(MemoryGraph::seeded()) as Arc<dyn ConflictGraph>,
+                None,
+                tunables::config(MemoryStore::default())?,
+            )
+        }
+    };
+    config.spawn_refresh(DEFAULT_REFRESH_INTERVAL);
+    info!(
+        "Runtime config loaded; overrides refresh every {:?}.",
+        DEFAULT_REFRESH_INTERVAL
+    );
 
-    let neo4j_client = Graph::new(&neo4j_uri, &neo4j_user, &neo4j_password).await?;
-    info!("Neo4j client connected successfully.");
+    let load_shed = LoadShedConfig::from_env()?;
+    info!("Load shedding: {:?}", load_shed);
+    let body_limits = BodyLimits::from_env()?;
+    info!("Request body limits: {:?}", body_limits);
+    yoloeats_taxonomy::load_from_env()?;
+    info!("Taxonomy loaded.");
+    let drain_timeout = drain_timeout_from_env()?;
+    info!("Shutdown drains requests for up to {:?}.", drain_timeout);
 
     let app_state = Arc::new(AppState {
-        neo4j_client,
-        http_client,
-        user_profile_service_url,
-        product_catalog_service_url,
+        graph,
+        upstreams,
+        clients,
+        internal_tokens: InternalTokens::from_env(),
+        config,
+        load_shed,
+        body_limits,
     });
     info!("Application state created.");
 
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
-    info!("CORS layer configured (permissive).");
-
-    let app = Router::new()
-        .route("/", get(health_check))
-        .route("/api/v1/check", post(check_product_safety))
-        .layer(cors)
-        .with_state(app_state);
+    let metrics_handle = install_recorder()?;
+    let app = router(app_state).merge(metrics_router(metrics_handle));
     info!("Axum router configured.");
 
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
@@ -100,6 +139,13 @@ async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
         addr
     );
 
-    axum::serve(listener, app.into_make_service()).await?;
+    let shutdown = Shutdown::new(drain_timeout);
+    shutdown.trigger_on(shutdown_signal());
+    shutdown
+        .run(
+            axum::serve(listener, app.into_make_service())
+                .with_graceful_shutdown(shutdown.signalled()),
+        )
+        .await?;
     Ok(())
 }
@@ -0,0 +1,57 @@
+//! The service's OpenAPI document, served by [`yoloeats_openapi::openapi_router`].
+
+use crate::handlers;
+use utoipa::OpenApi;
+use yoloeats_openapi::MiddlewareResponses;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Allergy Checker Service",
+        description = "Matches a product's ingredients against a user's allergens and diets."
+    ),
+    paths(handlers::check_product_safety),
+    modifiers(&MiddlewareResponses)
+)]
+pub struct ApiDoc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+
+    #[test]
+    fn the_check_is_documented_with_camel_case_bodies() {
+        let spec: Value = serde_json::from_str(&ApiDoc::openapi().to_json().unwrap()).unwrap();
+
+        let check = &spec["paths"]["/api/v1/check"]["post"];
+        assert_eq!(
+            check["requestBody"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/CheckRequest"
+        );
+        for status in ["200", "404", "413", "502", "503"] {
+            assert!(check["responses"][status].is_object(), "{}", status);
+        }
+
+        let schemas = &spec["components"]["schemas"];
+        assert_eq!(
+            schemas["CheckRequest"]["required"],
+            json!(["productIdentifier", "userId"])
+        );
+        let result = &schemas["CheckResult"]["properties"];
+        for field in [
+            "status",
+            "conflictingAllergens",
+            "conflictingDiets",
+            "traceAllergens",
+            "isOfflineResult",
+        ] {
+            assert!(result[field].is_object(), "{}", field);
+        }
+        assert_eq!(
+            schemas["SafetyStatus"]["enum"],
+            json!(["safe", "unsafe", "caution"])
+        );
+        assert!(schemas["ErrorBody"]["properties"]["requestId"].is_object());
+    }
+}
@@ -1,10 +1,27 @@
+use crate::{graph::ConflictGraph, upstream::Upstreams};
 use neo4rs::Graph;
-use reqwest::Client;
+use redis::Client as RedisClient;
+use std::sync::Arc;
+use yoloeats_auth::InternalTokens;
+use yoloeats_dynamic_config::DynamicConfig;
+use yoloeats_metrics::{BodyLimits, LoadShedConfig};
 
 #[derive(Clone)]
 pub struct AppState {
+    pub graph: Arc<dyn ConflictGraph>,
+    pub upstreams: Upstreams,
+    /// `None` with `STORAGE_MODE=memory`.
+    pub clients: Option<Clients>,
+    pub internal_tokens: InternalTokens,
+    pub config: DynamicConfig,
+    pub load_shed: LoadShedConfig,
+    pub body_limits: BodyLimits,
+}
+
+/// The external clients behind [`AppState::graph`] and the runtime config, kept for the
+/// health checks.
+#[derive(Clone)]
+pub struct Clients {
     pub neo4j_client: Graph,
-    pub http_client: Client,
-    pub user_profile_service_url: String,
-    pub product_catalog_service_url: String,
+    pub redis_client: RedisClient,
 }
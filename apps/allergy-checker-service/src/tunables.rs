@@ -0,0 +1,90 @@
+//! Settings that can be changed at runtime through `/internal/v1/config`; see
+//! `yoloeats_dynamic_config`. Defaults come from the environment variable in each comment.
+
+use std::{fmt, str::FromStr};
+use yoloeats_dynamic_config::{ConfigError, DynamicConfig, OverrideStore, Tunable, env_default};
+
+pub const SERVICE: &str = "allergy-checker-service";
+
+/// How a "may contain traces of" match on one of the user's allergens is rated when
+/// nothing in the ingredients conflicts outright.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TracePolicy {
+    Caution,
+    Unsafe,
+    /// Still reported in `traceAllergens`, but the product is rated safe.
+    Ignore,
+}
+
+impl FromStr for TracePolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "caution" => Ok(TracePolicy::Caution),
+            "unsafe" => Ok(TracePolicy::Unsafe),
+            "ignore" => Ok(TracePolicy::Ignore),
+            other => Err(format!(
+                "expected 'caution', 'unsafe' or 'ignore', got '{}'",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for TracePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TracePolicy::Caution => "caution",
+            TracePolicy::Unsafe => "unsafe",
+            TracePolicy::Ignore => "ignore",
+        })
+    }
+}
+
+/// `TRACE_POLICY`, default `caution`.
+pub const TRACE_POLICY: Tunable<TracePolicy> = Tunable::new("trace_policy");
+
+pub fn config(store: impl OverrideStore + 'static) -> Result<DynamicConfig, ConfigError> {
+    DynamicConfig::builder(SERVICE)
+        .register(
+            TRACE_POLICY,
+            env_default("TRACE_POLICY", TracePolicy::Caution),
+            "Rating for trace-only allergen matches: caution, unsafe or ignore",
+        )
+        .build(store)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yoloeats_dynamic_config::MemoryStore;
+
+    #[test]
+    fn trace_policy_round_trips() {
+        for policy in [
+            TracePolicy::Caution,
+            TracePolicy::Unsafe,
+            TracePolicy::Ignore,
+        ] {
+            assert_eq!(policy.to_string().parse::<TracePolicy>(), Ok(policy));
+        }
+        assert_eq!(" Unsafe ".parse::<TracePolicy>(), Ok(TracePolicy::Unsafe));
+        assert!("strict".parse::<TracePolicy>().is_err());
+    }
+
+    #[tokio::test]
+    async fn trace_policy_is_tunable() {
+        let store = MemoryStore::default();
+        let config = config(store.clone()).unwrap();
+        assert_eq!(config.get(TRACE_POLICY), TracePolicy::Caution);
+
+        store.set("trace_policy", "unsafe");
+        config.refresh().await.unwrap();
+        assert_eq!(config.get(TRACE_POLICY), TracePolicy::Unsafe);
+
+        store.set("trace_policy", "strict");
+        config.refresh().await.unwrap();
+        assert_eq!(config.get(TRACE_POLICY), TracePolicy::Unsafe, "ignored");
+    }
+}
@@ -0,0 +1,411 @@
+use crate::{
+    errors::{AppError, Result},
+    models::{ProductSummary, SafetyProfile},
+};
+use axum::http::{HeaderValue, header};
+use reqwest::StatusCode;
+use reqwest_middleware::ClientWithMiddleware;
+use std::str::FromStr;
+use tonic::transport::{Channel, Endpoint};
+use tracing::{debug, error, warn};
+use yoloeats_tracing::{REQUEST_ID_HEADER, current_request_id};
+use yoloeats_proto::v1::{
+    GetProductSummaryRequest, GetSafetyProfileRequest, product_service_client::ProductServiceClient,
+    profile_service_client::ProfileServiceClient,
+};
+
+pub const USER_PROFILE_SERVICE: &str = "user-profile-service";
+pub const PRODUCT_CATALOG_SERVICE: &str = "product-catalog-service";
+
+/// Which wire protocol the checker uses for its internal fetches (`INTERNAL_TRANSPORT`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InternalTransport {
+    Http,
+    Grpc,
+}
+
+impl FromStr for InternalTransport {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "http" => Ok(InternalTransport::Http),
+            "grpc" => Ok(InternalTransport::Grpc),
+            other => Err(format!(
+                "INTERNAL_TRANSPORT must be 'http' or 'grpc', got '{}'",
+                other
+            )),
+        }
+    }
+}
+
+/// Fetches profiles and products from the other services over the configured transport.
+/// Both variants return the same domain types, so the check logic doesn't care which one runs.
+#[derive(Clone)]
+pub enum Upstreams {
+    Http {
+        client: ClientWithMiddleware,
+        user_profile_service_url: String,
+        product_catalog_service_url: String,
+    },
+    Grpc {
+        profiles: Box<ProfileServiceClient<Channel>>,
+        products: Box<ProductServiceClient<Channel>>,
+    },
+}
+
+/// gRPC counterpart of the HTTP client's request-id propagation.
+fn grpc_request<T>(message: T) -> tonic::Request<T> {
+    let mut request = tonic::Request::new(message);
+    if let Some(value) = current_request_id().and_then(|id| id.as_str().parse().ok()) {
+        request.metadata_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    request
+}
+
+impl Upstreams {
+    /// Channels connect lazily, so a peer that is still starting up doesn't fail our startup.
+    pub fn grpc(
+        user_profile_grpc_url: &str,
+        product_catalog_grpc_url: &str,
+    ) -> std::result::Result<Self, tonic::transport::Error> {
+        let profiles = Endpoint::from_shared(user_profile_grpc_url.to_string())?.connect_lazy();
+        let products = Endpoint::from_shared(product_catalog_grpc_url.to_string())?.connect_lazy();
+        Ok(Upstreams::Grpc {
+            profiles: Box::new(ProfileServiceClient::new(profiles)),
+            products: Box::new(ProductServiceClient::new(products)),
+        })
+    }
+
+    pub fn transport(&self) -> InternalTransport {
+        match self {
+            Upstreams::Http { .. } => InternalTransport::Http,
+            Upstreams::Grpc { .. } => InternalTransport::Grpc,
+        }
+    }
+
+    /// `authorization` is the caller's header; the HTTP profile route requires it, while the
+    /// internal gRPC port does not.
+    pub async fn fetch_profile(
+        &self,
+        user_id: &str,
+        authorization: Option<&HeaderValue>,
+    ) -> Result<SafetyProfile> {
+        match self {
+            Upstreams::Http {
+                client,
+                user_profile_service_url,
+                ..
+            } => {
+                let profile_url = format!(
+                    "{}/api/v1/users/{}/profile",
+                    user_profile_service_url, user_id
+                );
+                debug!("Fetching user profile from: {}", profile_url);
+                let mut request = client.get(&profile_url);
+                if let Some(value) = authorization {
+                    request = request.header(header::AUTHORIZATION, value);
+                }
+                let response = request.send().await?;
+                match response.status() {
+                    StatusCode::OK => response.json::<SafetyProfile>().await.map_err(|e| {
+                        error!("Failed to deserialize user profile JSON: {}", e);
+                        AppError::ProfileProcessingError(format!(
+                            "Failed to parse profile data: {}",
+                            e
+                        ))
+                    }),
+                    StatusCode::NOT_FOUND => {
+                        warn!("User profile not found at {}", profile_url);
+                        Err(AppError::ProfileNotFoundError(format!(
+                            "User profile not found for user {}",
+                            user_id
+                        )))
+                    }
+                    other_status => {
+                        let body = response.text().await.unwrap_or_default();
+                        error!(
+                            "User profile service failed with status {}: {}",
+                            other_status, body
+                        );
+                        Err(AppError::UpstreamServiceError {
+                            service: USER_PROFILE_SERVICE.to_string(),
+                            status: other_status.as_u16(),
+                        })
+                    }
+                }
+            }
+            Upstreams::Grpc { profiles, .. } => {
+                debug!("Fetching user profile over gRPC for user {}", user_id);
+                let request = grpc_request(GetSafetyProfileRequest {
+                    user_id: user_id.to_string(),
+                });
+                match profiles.clone().get_safety_profile(request).await {
+                    Ok(response) => Ok(response.into_inner().into()),
+                    Err(status) if status.code() == tonic::Code::NotFound => {
+                        warn!("User profile not found over gRPC: {}", status.message());
+                        Err(AppError::ProfileNotFoundError(format!(
+                            "User profile not found for user {}",
+                            user_id
+                        )))
+                    }
+                    Err(status) => Err(AppError::UpstreamRpcError {
+                        service: USER_PROFILE_SERVICE.to_string(),
+                        code: status.code(),
+                    }),
+                }
+            }
+        }
+    }
+
+    pub async fn fetch_product(&self, code: &str) -> Result<ProductSummary> {
+        match self {
+            Upstreams::Http {
+                client,
+                product_catalog_service_url,
+                ..
+            } => {
+                let product_url = format!(
+                    "{}/api/v1/products/barcode/{}",
+                    product_catalog_service_url, code
+                );
+                debug!("Fetching product data from: {}", product_url);
+                let response = client.get(&product_url).send().await?;
+                match response.status() {
+                    StatusCode::OK => response.json::<ProductSummary>().await.map_err(|e| {
+                        error!("Failed to deserialize product data JSON: {}", e);
+                        AppError::ProductProcessingError(format!(
+                            "Failed to parse product data: {}",
+                            e
+                        ))
+                    }),
+                    StatusCode::NOT_FOUND => {
+                        warn!("Product not found at {}", product_url);
+                        Err(AppError::ProductNotFoundError(format!(
+                            "Product not found for identifier {}",
+                            code
+                        )))
+                    }
+                    other_status => {
+                        let body = response.text().await.unwrap_or_default();
+                        error!(
+                            "Product catalog service failed with status {}: {}",
+                            other_status, body
+                        );
+                        Err(AppError::UpstreamServiceError {
+                            service: PRODUCT_CATALOG_SERVICE.to_string(),
+                            status: other_status.as_u16(),
+                        })
+                    }
+                }
+            }
+            Upstreams::Grpc { products, .. } => {
+                debug!("Fetching product data over gRPC for {}", code);
+                let request = grpc_request(GetProductSummaryRequest {
+                    code: code.to_string(),
+                });
+                match products.clone().get_product_summary(request).await {
+                    Ok(response) => Ok(response.into_inner().into()),
+                    Err(status) if status.code() == tonic::Code::NotFound => {
+                        warn!("Product not found over gRPC: {}", status.message());
+                        Err(AppError::ProductNotFoundError(format!(
+                            "Product not found for identifier {}",
+                            code
+                        )))
+                    }
+                    Err(status) => Err(AppError::UpstreamRpcError {
+                        service: PRODUCT_CATALOG_SERVICE.to_string(),
+                        code: status.code(),
+                    }),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::{Request, Response, Status};
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{header as header_matcher, method, path},
+    };
+    use yoloeats_domain::{
+        RiskLevel,
+        fixtures::{ProductFixture, UserProfileFixture},
+    };
+    use yoloeats_proto::v1::{
+        self,
+        product_service_server::{ProductService, ProductServiceServer},
+        profile_service_server::{ProfileService, ProfileServiceServer},
+    };
+    use yoloeats_tracing::{RequestId, with_request_id};
+
+    const USER_ID: &str = "user-1";
+    const CODE: &str = "4000417025005";
+
+    fn profile_fixture() -> UserProfileFixture {
+        UserProfileFixture::new(USER_ID)
+            .named("alice")
+            .allergic_to(["milk", "peanuts"])
+            .following(["vegetarian"])
+            .risk(RiskLevel::High)
+    }
+
+    fn product_fixture() -> ProductFixture {
+        ProductFixture::new(CODE)
+            .named("Ritter Sport Alpenmilch")
+            .with_brands(["ritter-sport"])
+            .with_allergens(["en:milk"])
+            .with_traces(["en:nuts"])
+    }
+
+    /// What the HTTP services actually send: full persistence models with extra fields.
+    async fn http_upstreams() -> (MockServer, Upstreams) {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(format!("/api/v1/users/{}/profile", USER_ID)))
+            .and(header_matcher("authorization", "Bearer abc"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(profile_fixture().json()))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/api/v1/products/barcode/{}", CODE)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(product_fixture().json()))
+            .mount(&server)
+            .await;
+
+        let upstreams = Upstreams::Http {
+            client: yoloeats_tracing::http_client(reqwest::Client::new()),
+            user_profile_service_url: server.uri(),
+            product_catalog_service_url: server.uri(),
+        };
+        (server, upstreams)
+    }
+
+    struct FixtureServices;
+
+    #[tonic::async_trait]
+    impl ProfileService for FixtureServices {
+        async fn get_safety_profile(
+            &self,
+            request: Request<GetSafetyProfileRequest>,
+        ) -> std::result::Result<Response<v1::SafetyProfile>, Status> {
+            match request.into_inner().user_id.as_str() {
+                USER_ID => Ok(Response::new(profile_fixture().safety_profile().into())),
+                other => Err(Status::not_found(format!("Profile for user {} not found", other))),
+            }
+        }
+    }
+
+    #[tonic::async_trait]
+    impl ProductService for FixtureServices {
+        async fn get_product_summary(
+            &self,
+            request: Request<GetProductSummaryRequest>,
+        ) -> std::result::Result<Response<v1::ProductSummary>, Status> {
+            match request.into_inner().code.as_str() {
+                CODE => Ok(Response::new(product_fixture().summary().into())),
+                "500" => Err(Status::internal("boom")),
+                other => Err(Status::not_found(format!("Product with barcode {} not found", other))),
+            }
+        }
+    }
+
+    async fn grpc_upstreams() -> Upstreams {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(ProfileServiceServer::new(FixtureServices))
+                .add_service(ProductServiceServer::new(FixtureServices))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        Upstreams::grpc(&url, &url).unwrap()
+    }
+
+    #[test]
+    fn parses_transport_flag() {
+        assert_eq!("grpc".parse(), Ok(InternalTransport::Grpc));
+        assert_eq!(" HTTP ".parse(), Ok(InternalTransport::Http));
+        assert!("rest".parse::<InternalTransport>().is_err());
+    }
+
+    #[tokio::test]
+    async fn http_and_grpc_return_the_same_profile() {
+        let (_server, http) = http_upstreams().await;
+        let grpc = grpc_upstreams().await;
+        let auth = HeaderValue::from_static("Bearer abc");
+
+        let via_http = http.fetch_profile(USER_ID, Some(&auth)).await.unwrap();
+        let via_grpc = grpc.fetch_profile(USER_ID, None).await.unwrap();
+        assert_eq!(via_http, profile_fixture().safety_profile());
+        assert_eq!(via_grpc, via_http);
+    }
+
+    #[tokio::test]
+    async fn http_and_grpc_return_the_same_product() {
+        let (_server, http) = http_upstreams().await;
+        let grpc = grpc_upstreams().await;
+
+        let via_http = http.fetch_product(CODE).await.unwrap();
+        let via_grpc = grpc.fetch_product(CODE).await.unwrap();
+        assert_eq!(via_http, product_fixture().summary());
+        assert_eq!(via_grpc, via_http);
+    }
+
+    #[tokio::test]
+    async fn http_and_grpc_agree_on_not_found() {
+        let (_server, http) = http_upstreams().await;
+        let grpc = grpc_upstreams().await;
+
+        for upstreams in [&http, &grpc] {
+            assert!(matches!(
+                upstreams.fetch_product("0000").await,
+                Err(AppError::ProductNotFoundError(_))
+            ));
+        }
+        assert!(matches!(
+            grpc.fetch_profile("someone-else", None).await,
+            Err(AppError::ProfileNotFoundError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn http_fetches_forward_the_request_id() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(header_matcher(REQUEST_ID_HEADER, "check-42"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(2)
+            .mount(&server)
+            .await;
+        let upstreams = Upstreams::Http {
+            client: yoloeats_tracing::http_client(reqwest::Client::new()),
+            user_profile_service_url: server.uri(),
+            product_catalog_service_url: server.uri(),
+        };
+
+        let id = RequestId::parse("check-42").unwrap();
+        with_request_id(id, async {
+            assert!(upstreams.fetch_profile(USER_ID, None).await.is_err());
+            assert!(upstreams.fetch_product(CODE).await.is_err());
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn grpc_failures_name_the_service() {
+        let grpc = grpc_upstreams().await;
+        match grpc.fetch_product("500").await {
+            Err(AppError::UpstreamRpcError { service, code }) => {
+                assert_eq!(service, PRODUCT_CATALOG_SERVICE);
+                assert_eq!(code, tonic::Code::Internal);
+            }
+            other => panic!("expected UpstreamRpcError, got {:?}", other),
+        }
+    }
+}
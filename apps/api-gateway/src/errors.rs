@@ -0,0 +1,83 @@
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+use thiserror::Error;
+use tracing::error;
+
+#[derive(Error, Debug)]
+pub enum GatewayError {
+    #[error("Invalid input: {0}")]
+    BadRequest(String),
+
+    /// The upstream answered with a non-success status.
+    #[error("Upstream service '{service}' responded with status {status}")]
+    Upstream {
+        service: &'static str,
+        status: u16,
+        message: Option<String>,
+    },
+
+    /// The upstream could not be reached at all.
+    #[error("Upstream service '{service}' unavailable: {source}")]
+    Unavailable {
+        service: &'static str,
+        #[source]
+        source: reqwest_middleware::Error,
+    },
+}
+
+impl IntoResponse for GatewayError {
+    fn into_response(self) -> Response {
+        let (status, error_message, service) = match &self {
+            GatewayError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg.clone(), None),
+            GatewayError::Upstream {
+                service,
+                status,
+                message,
+            } => {
+                let upstream_status =
+                    StatusCode::from_u16(*status).unwrap_or(StatusCode::BAD_GATEWAY);
+                if upstream_status.is_client_error() {
+                    // Client errors are the caller's to fix; pass them through unchanged.
+                    (
+                        upstream_status,
+                        message
+                            .clone()
+                            .unwrap_or_else(|| "Request rejected by upstream".to_string()),
+                        Some(*service),
+                    )
+                } else {
+                    error!("Upstream service '{}' failed with status {}", service, status);
+                    (
+                        StatusCode::BAD_GATEWAY,
+                        format!("Error communicating with {}", service),
+                        Some(*service),
+                    )
+                }
+            }
+            GatewayError::Unavailable { service, source } => {
+                error!("Upstream service '{}' unreachable: {}", service, source);
+                let timed_out =
+                    matches!(source, reqwest_middleware::Error::Reqwest(e) if e.is_timeout());
+                let status = if timed_out {
+                    StatusCode::GATEWAY_TIMEOUT
+                } else {
+                    StatusCode::BAD_GATEWAY
+                };
+                (
+                    status,
+                    format!("{} is unavailable", service),
+                    Some(*service),
+                )
+            }
+        };
+
+        let body = Json(json!({ "error": error_message, "service": service }));
+        (status, body).into_response()
+    }
+}
+
+pub type Result<T> = std::result::Result<T, GatewayError>;
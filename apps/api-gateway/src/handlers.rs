@@ -0,0 +1,261 @@
+use crate::{
+    errors::{GatewayError, Result},
+    models::{CheckRequest, ScanRequest, ScanResponse},
+    state::AppState,
+};
+use axum::{
+    Extension, Json,
+    body::Body,
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue, header},
+    response::Response,
+};
+use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
+use serde::de::DeserializeOwned;
+use std::{net::SocketAddr, sync::Arc};
+use tracing::{debug, info, instrument, warn};
+use yoloeats_domain::CheckResult;
+
+pub const USER_PROFILE_SERVICE: &str = "user-profile-service";
+pub const PRODUCT_CATALOG_SERVICE: &str = "product-catalog-service";
+pub const ALLERGY_CHECKER_SERVICE: &str = "allergy-checker-service";
+
+/// Headers that describe a single hop and must not be forwarded by a proxy.
+const HOP_BY_HOP_HEADERS: [HeaderName; 8] = [
+    header::CONNECTION,
+    header::HOST,
+    header::PROXY_AUTHENTICATE,
+    header::PROXY_AUTHORIZATION,
+    header::TE,
+    header::TRAILER,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+];
+
+/// Names the clients a request was forwarded for, the gateway's own client last. The
+/// services trust it from the gateway's address only (`TRUSTED_PROXIES`), to tell the
+/// gateway's clients apart in their rate limits.
+pub const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// Upstream error bodies are small JSON envelopes; anything bigger is not worth reading.
+const MAX_ERROR_BODY_BYTES: usize = 16 * 1024;
+
+fn strip_hop_by_hop(headers: &mut HeaderMap) {
+    for name in HOP_BY_HOP_HEADERS.iter() {
+        headers.remove(name);
+    }
+    headers.remove("keep-alive");
+}
+
+/// Adds `peer`, the address the request came from, to its `X-Forwarded-For`. Without
+/// one (the server wasn't started with connect info) the header is left as it is.
+fn forward_for(headers: &mut HeaderMap, peer: Option<SocketAddr>) {
+    let Some(peer) = peer else {
+        return;
+    };
+    let chain: Vec<&str> = headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect();
+    let forwarded = if chain.is_empty() {
+        peer.ip().to_string()
+    } else {
+        format!("{}, {}", chain.join(", "), peer.ip())
+    };
+    match HeaderValue::from_str(&forwarded) {
+        Ok(value) => {
+            headers.insert(X_FORWARDED_FOR, value);
+        }
+        Err(e) => warn!("Not forwarding unrepresentable X-Forwarded-For: {}", e),
+    }
+}
+
+/// The headers composite calls forward to every upstream. The request id is stamped
+/// by the HTTP client itself.
+fn forwarded_headers(incoming: &HeaderMap, peer: Option<SocketAddr>) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Some(value) = incoming.get(header::AUTHORIZATION) {
+        headers.insert(header::AUTHORIZATION, value.clone());
+    }
+    for value in incoming.get_all(X_FORWARDED_FOR) {
+        headers.append(X_FORWARDED_FOR, value.clone());
+    }
+    forward_for(&mut headers, peer);
+    headers
+}
+
+async fn upstream_error(service: &'static str, response: reqwest::Response) -> GatewayError {
+    let status = response.status().as_u16();
+    let message = match response.bytes().await {
+        Ok(bytes) if bytes.len() <= MAX_ERROR_BODY_BYTES => {
+            serde_json::from_slice::<serde_json::Value>(&bytes)
+                .ok()
+                .and_then(|v| {
+                    // `error` is the pre-envelope field; services still send both for now.
+                    v.get("message")
+                        .or_else(|| v.get("error"))
+                        .and_then(|e| e.as_str())
+                        .map(String::from)
+                })
+        }
+        _ => None,
+    };
+    GatewayError::Upstream {
+        service,
+        status,
+        message,
+    }
+}
+
+async fn fetch_json<T: DeserializeOwned>(
+    service: &'static str,
+    request: RequestBuilder,
+) -> Result<T> {
+    let response = request
+        .send()
+        .await
+        .map_err(|source| GatewayError::Unavailable { service, source })?;
+    if !response.status().is_success() {
+        return Err(upstream_error(service, response).await);
+    }
+    response.json::<T>().await.map_err(|e| {
+        warn!(service, "Failed to decode upstream response: {}", e);
+        GatewayError::Upstream {
+            service,
+            status: 502,
+            message: None,
+        }
+    })
+}
+
+async fn proxy(
+    client: &ClientWithMiddleware,
+    service: &'static str,
+    base_url: &str,
+    request: Request,
+) -> Result<Response> {
+    let (parts, body) = request.into_parts();
+    let path_and_query = parts
+        .uri
+        .path_and_query()
+        .map(|pq| pq.as_str())
+        .unwrap_or("/");
+    let url = format!("{}{}", base_url.trim_end_matches('/'), path_and_query);
+    debug!(service, method = %parts.method, url = %url, "Proxying request");
+
+    let mut headers = parts.headers;
+    strip_hop_by_hop(&mut headers);
+    let peer = parts
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(peer)| *peer);
+    forward_for(&mut headers, peer);
+
+    let upstream = client
+        .request(parts.method, &url)
+        .headers(headers)
+        .body(reqwest::Body::wrap_stream(body.into_data_stream()))
+        .send()
+        .await
+        .map_err(|source| GatewayError::Unavailable { service, source })?;
+
+    if upstream.status().is_client_error() || upstream.status().is_server_error() {
+        return Err(upstream_error(service, upstream).await);
+    }
+
+    let mut response_headers = upstream.headers().clone();
+    strip_hop_by_hop(&mut response_headers);
+
+    let status = upstream.status();
+    let mut response = Response::new(Body::from_stream(upstream.bytes_stream()));
+    *response.status_mut() = status;
+    response.headers_mut().extend(response_headers);
+    Ok(response)
+}
+
+pub async fn proxy_user_profile(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+) -> Result<Response> {
+    proxy(
+        &state.http_client,
+        USER_PROFILE_SERVICE,
+        &state.user_profile_service_url,
+        request,
+    )
+    .await
+}
+
+pub async fn proxy_product_catalog(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+) -> Result<Response> {
+    proxy(
+        &state.http_client,
+        PRODUCT_CATALOG_SERVICE,
+        &state.product_catalog_service_url,
+        request,
+    )
+    .await
+}
+
+pub async fn proxy_allergy_checker(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+) -> Result<Response> {
+    proxy(
+        &state.http_client,
+        ALLERGY_CHECKER_SERVICE,
+        &state.allergy_checker_service_url,
+        request,
+    )
+    .await
+}
+
+/// Barcode lookup and safety check in one round trip for the app's scan flow. Both
+/// upstream calls run concurrently; the checker resolves the product on its own.
+#[instrument(skip(state, peer, headers, payload), fields(barcode = %payload.barcode, user_id = %payload.user_id))]
+pub async fn scan(
+    State(state): State<Arc<AppState>>,
+    peer: Option<Extension<ConnectInfo<SocketAddr>>>,
+    headers: HeaderMap,
+    Json(payload): Json<ScanRequest>,
+) -> Result<Json<ScanResponse>> {
+    let barcode = payload.barcode.trim();
+    if barcode.is_empty() || !barcode.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(GatewayError::BadRequest(
+            "barcode must be a non-empty alphanumeric string".to_string(),
+        ));
+    }
+    if payload.user_id.trim().is_empty() {
+        return Err(GatewayError::BadRequest("userId must not be empty".to_string()));
+    }
+    info!("Processing scan");
+
+    let peer = peer.map(|Extension(ConnectInfo(peer))| peer);
+    let forwarded = forwarded_headers(&headers, peer);
+    let product_request = state
+        .http_client
+        .get(format!(
+            "{}/api/v1/products/barcode/{}",
+            state.product_catalog_service_url, barcode
+        ))
+        .headers(forwarded.clone());
+    let check_request = state
+        .http_client
+        .post(format!("{}/api/v1/check", state.allergy_checker_service_url))
+        .headers(forwarded)
+        .json(&CheckRequest {
+            product_identifier: barcode,
+            user_id: &payload.user_id,
+        });
+
+    let (product, check) = tokio::try_join!(
+        fetch_json::<serde_json::Value>(PRODUCT_CATALOG_SERVICE, product_request),
+        fetch_json::<CheckResult>(ALLERGY_CHECKER_SERVICE, check_request),
+    )?;
+    info!(status = ?check.status, "Scan completed");
+
+    Ok(Json(ScanResponse { product, check }))
+}
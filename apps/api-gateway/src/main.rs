@@ -0,0 +1,401 @@
+use axum::{
+    Router,
+    http::HeaderName,
+    routing::{any, get, post},
+};
+use dotenvy::dotenv;
+use handlers::{proxy_allergy_checker, proxy_product_catalog, proxy_user_profile, scan};
+use reqwest::Client as HttpClient;
+use state::AppState;
+use std::{env, net::SocketAddr, sync::Arc};
+use tower_http::cors::{Any, CorsLayer};
+use tracing::{info, warn};
+use yoloeats_tracing::{REQUEST_ID_HEADER, RequestIdLayer, init_tracing};
+
+mod errors;
+mod handlers;
+mod models;
+mod state;
+
+async fn health_check() -> &'static str {
+    "API Gateway OK"
+}
+
+fn app(app_state: Arc<AppState>) -> Router {
+    let cors = CorsLayer::new()
+        .allow_origin(Any)
+        .allow_methods(Any)
+        .allow_headers(Any)
+        .expose_headers([
+            HeaderName::from_static(REQUEST_ID_HEADER),
+            HeaderName::from_static("deprecation"),
+            HeaderName::from_static("sunset"),
+        ]);
+
+    Router::new()
+        .route("/", get(health_check))
+        .route("/health", get(health_check))
+        .route("/api/v1/scan", post(scan))
+        .route("/api/v1/users", any(proxy_user_profile))
+        .route("/api/v1/users/{*rest}", any(proxy_user_profile))
+        .route("/api/v1/allergens", any(proxy_user_profile))
+        .route("/api/v1/allergens/{*rest}", any(proxy_user_profile))
+        .route("/api/v1/products", any(proxy_product_catalog))
+        .route("/api/v1/products/{*rest}", any(proxy_product_catalog))
+        .route("/api/v1/categories", any(proxy_product_catalog))
+        .route("/api/v1/check", any(proxy_allergy_checker))
+        .route("/api/v2/users/{*rest}", any(proxy_user_profile))
+        .route("/api/v2/allergens", any(proxy_user_profile))
+        .route("/api/v2/products", any(proxy_product_catalog))
+        .route("/api/v2/products/{*rest}", any(proxy_product_catalog))
+        .layer(RequestIdLayer)
+        .layer(cors)
+        .with_state(app_state)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    dotenv().ok();
+
+    let _telemetry = init_tracing("api-gateway", env!("CARGO_PKG_VERSION"))?;
+
+    info!("Starting API Gateway...");
+
+    let user_profile_service_url = env::var("USER_PROFILE_SERVICE_URL")
+        .unwrap_or_else(|_| "http://user-profile-service:8001".to_string());
+    let product_catalog_service_url = env::var("PRODUCT_CATALOG_SERVICE_URL")
+        .unwrap_or_else(|_| "http://product-catalog-service:8002".to_string());
+    let allergy_checker_service_url = env::var("ALLERGY_CHECKER_SERVICE_URL")
+        .unwrap_or_else(|_| "http://allergy-checker-service:8003".to_string());
+    let port_str = env::var("API_GATEWAY_PORT").unwrap_or_else(|_| "8000".to_string());
+    let port = port_str.parse::<u16>().unwrap_or(8000);
+
+    info!("User Profile Service URL: {}", user_profile_service_url);
+    info!(
+        "Product Catalog Service URL: {}",
+        product_catalog_service_url
+    );
+    info!(
+        "Allergy Checker Service URL: {}",
+        allergy_checker_service_url
+    );
+
+    let app_state = Arc::new(AppState {
+        http_client: yoloeats_tracing::http_client(HttpClient::new()),
+        user_profile_service_url,
+        product_catalog_service_url,
+        allergy_checker_service_url,
+    });
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    warn!("Warning: The gateway forwards credentials but does not validate them itself.");
+    info!("API Gateway successfully started, listening on {}", addr);
+
+    axum::serve(
+        listener,
+        app(app_state).into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::{Body, to_bytes},
+        extract::ConnectInfo,
+        http::{Request as HttpRequest, StatusCode, header},
+        response::Response,
+    };
+    use serde_json::{Value, json};
+    use tower::ServiceExt;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{body_json, header as header_matcher, method, path},
+    };
+
+    struct Backends {
+        profile: MockServer,
+        catalog: MockServer,
+        checker: MockServer,
+    }
+
+    async fn backends() -> Backends {
+        Backends {
+            profile: MockServer::start().await,
+            catalog: MockServer::start().await,
+            checker: MockServer::start().await,
+        }
+    }
+
+    fn gateway(backends: &Backends) -> Router {
+        app(Arc::new(AppState {
+            http_client: yoloeats_tracing::http_client(HttpClient::new()),
+            user_profile_service_url: backends.profile.uri(),
+            product_catalog_service_url: backends.catalog.uri(),
+            allergy_checker_service_url: backends.checker.uri(),
+        }))
+    }
+
+    async fn json_body(response: Response) -> Value {
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    fn scan_request() -> HttpRequest<Body> {
+        HttpRequest::post("/api/v1/scan")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, "Bearer abc")
+            .header(REQUEST_ID_HEADER, "req-123")
+            .body(Body::from(
+                json!({"barcode": "4000417025005", "userId": "user-1"}).to_string(),
+            ))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn scan_combines_product_and_check() {
+        let backends = backends().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/products/barcode/4000417025005"))
+            .and(header_matcher(REQUEST_ID_HEADER, "req-123"))
+            .and(header_matcher("authorization", "Bearer abc"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "code": "4000417025005",
+                "product_name": "Ritter Sport"
+            })))
+            .expect(1)
+            .mount(&backends.catalog)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/check"))
+            .and(header_matcher(REQUEST_ID_HEADER, "req-123"))
+            .and(body_json(
+                json!({"productIdentifier": "4000417025005", "userId": "user-1"}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "status": "unsafe",
+                "conflictingAllergens": ["milk"],
+                "isOfflineResult": false
+            })))
+            .expect(1)
+            .mount(&backends.checker)
+            .await;
+
+        let response = gateway(&backends).oneshot(scan_request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "req-123");
+        let body = json_body(response).await;
+        assert_eq!(body["product"]["product_name"], "Ritter Sport");
+        assert_eq!(body["check"]["status"], "unsafe");
+        assert_eq!(body["check"]["conflictingAllergens"], json!(["milk"]));
+    }
+
+    #[tokio::test]
+    async fn scan_names_failing_upstream() {
+        let backends = backends().await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(404)
+                    .set_body_json(json!({"error": "Product with barcode 4000417025005 not found"})),
+            )
+            .mount(&backends.catalog)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&backends.checker)
+            .await;
+
+        let response = gateway(&backends).oneshot(scan_request()).await.unwrap();
+        let status = response.status();
+        let body = json_body(response).await;
+        // Whichever upstream fails first is reported, and it is always named.
+        match body["service"].as_str().unwrap() {
+            "product-catalog-service" => {
+                assert_eq!(status, StatusCode::NOT_FOUND);
+                assert_eq!(body["error"], "Product with barcode 4000417025005 not found");
+            }
+            "allergy-checker-service" => assert_eq!(status, StatusCode::BAD_GATEWAY),
+            other => panic!("unexpected service {}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn proxies_requests_and_generates_request_id() {
+        let backends = backends().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/products/search"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([{"code": "1"}])))
+            .expect(1)
+            .mount(&backends.catalog)
+            .await;
+
+        let response = gateway(&backends)
+            .oneshot(
+                HttpRequest::get("/api/v1/products/search?q=milk")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().contains_key(REQUEST_ID_HEADER));
+        assert_eq!(json_body(response).await, json!([{"code": "1"}]));
+
+        let received = backends.catalog.received_requests().await.unwrap();
+        assert_eq!(received[0].url.query(), Some("q=milk"));
+        assert!(received[0].headers.contains_key(REQUEST_ID_HEADER));
+    }
+
+    #[tokio::test]
+    async fn categories_are_proxied_to_the_catalog() {
+        let backends = backends().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/categories"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({"items": [{"tag": "en:spreads", "count": 3}]})),
+            )
+            .expect(1)
+            .mount(&backends.catalog)
+            .await;
+
+        let response = gateway(&backends)
+            .oneshot(
+                HttpRequest::get("/api/v1/categories?prefix=en:sp")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            json_body(response).await,
+            json!({"items": [{"tag": "en:spreads", "count": 3}]})
+        );
+        let received = backends.catalog.received_requests().await.unwrap();
+        assert_eq!(received[0].url.query(), Some("prefix=en:sp"));
+    }
+
+    #[tokio::test]
+    async fn proxied_and_composite_calls_name_the_client() {
+        let backends = backends().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"code": "1"})))
+            .mount(&backends.catalog)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "status": "safe",
+                "conflictingAllergens": [],
+                "isOfflineResult": false
+            })))
+            .mount(&backends.checker)
+            .await;
+        let from_client = |mut request: HttpRequest<Body>| {
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(SocketAddr::from(([203, 0, 113, 7], 40000))));
+            request
+        };
+
+        let search = HttpRequest::get("/api/v1/products/search")
+            .header(handlers::X_FORWARDED_FOR, "198.51.100.9")
+            .body(Body::empty())
+            .unwrap();
+        let response = gateway(&backends)
+            .oneshot(from_client(search))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = gateway(&backends)
+            .oneshot(from_client(scan_request()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let received = backends.catalog.received_requests().await.unwrap();
+        assert_eq!(
+            received[0].headers[handlers::X_FORWARDED_FOR],
+            "198.51.100.9, 203.0.113.7"
+        );
+        assert_eq!(
+            received[1].headers[handlers::X_FORWARDED_FOR],
+            "203.0.113.7"
+        );
+        let received = backends.checker.received_requests().await.unwrap();
+        assert_eq!(
+            received[0].headers[handlers::X_FORWARDED_FOR],
+            "203.0.113.7"
+        );
+    }
+
+    #[tokio::test]
+    async fn proxies_v2_and_passes_deprecation_headers_through() {
+        let backends = backends().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v2/products/search"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"items": []})))
+            .mount(&backends.catalog)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/products/search"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("deprecation", "@1751328000")
+                    .insert_header("sunset", "Sat, 31 Jan 2026 23:59:59 GMT")
+                    .set_body_json(json!({"items": []})),
+            )
+            .mount(&backends.catalog)
+            .await;
+
+        let response = gateway(&backends)
+            .oneshot(
+                HttpRequest::get("/api/v2/products/search")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get("deprecation").is_none());
+
+        let response = gateway(&backends)
+            .oneshot(
+                HttpRequest::get("/api/v1/products/search")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["deprecation"], "@1751328000");
+        assert_eq!(
+            response.headers()["sunset"],
+            "Sat, 31 Jan 2026 23:59:59 GMT"
+        );
+    }
+
+    #[tokio::test]
+    async fn unreachable_upstream_maps_to_envelope() {
+        let backends = backends().await;
+        let router_state = AppState {
+            http_client: yoloeats_tracing::http_client(HttpClient::new()),
+            user_profile_service_url: "http://127.0.0.1:9".to_string(),
+            product_catalog_service_url: backends.catalog.uri(),
+            allergy_checker_service_url: backends.checker.uri(),
+        };
+        let response = app(Arc::new(router_state))
+            .oneshot(
+                HttpRequest::get("/api/v1/users/user-1/profile")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        let body = json_body(response).await;
+        assert_eq!(body["service"], "user-profile-service");
+    }
+}
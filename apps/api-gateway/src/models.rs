@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+use yoloeats_domain::CheckResult;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanRequest {
+    pub barcode: String,
+    pub user_id: String,
+}
+
+/// Payload forwarded to the allergy-checker's `/api/v1/check`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckRequest<'a> {
+    pub product_identifier: &'a str,
+    pub user_id: &'a str,
+}
+
+/// Combined scan result. `product` is passed through as served by the catalog so
+/// the gateway never drops fields the app relies on.
+#[derive(Debug, Serialize)]
+pub struct ScanResponse {
+    pub product: serde_json::Value,
+    pub check: CheckResult,
+}
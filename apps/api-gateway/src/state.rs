@@ -0,0 +1,9 @@
+use reqwest_middleware::ClientWithMiddleware;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub http_client: ClientWithMiddleware,
+    pub user_profile_service_url: String,
+    pub product_catalog_service_url: String,
+    pub allergy_checker_service_url: String,
+}
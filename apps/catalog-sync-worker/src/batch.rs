@@ -0,0 +1,85 @@
+use crate::point::ProductDoc;
+use bson::oid::ObjectId;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Upsert(Box<ProductDoc>),
+    Delete,
+}
+
+impl Change {
+    pub fn operation(&self) -> &'static str {
+        match self {
+            Change::Upsert(_) => "upsert",
+            Change::Delete => "delete",
+        }
+    }
+}
+
+/// Pending changes keyed by product id. Only a product's latest state matters to Qdrant,
+/// so a later change replaces an earlier one instead of queueing behind it.
+#[derive(Debug, Default)]
+pub struct Batch {
+    changes: HashMap<ObjectId, Change>,
+}
+
+impl Batch {
+    pub fn push(&mut self, id: ObjectId, change: Change) {
+        self.changes.insert(id, change);
+    }
+
+    pub fn discard(&mut self, id: &ObjectId) {
+        self.changes.remove(id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn take(&mut self) -> Vec<(ObjectId, Change)> {
+        self.changes.drain().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::doc;
+
+    fn product(id: ObjectId, name: &str) -> Change {
+        Change::Upsert(Box::new(
+            ProductDoc::from_document(doc! { "_id": id, "code": "1", "product_name": name })
+                .unwrap(),
+        ))
+    }
+
+    #[test]
+    fn latest_change_per_product_wins() {
+        let (a, b) = (ObjectId::new(), ObjectId::new());
+        let mut batch = Batch::default();
+        batch.push(a, product(a, "first"));
+        batch.push(b, product(b, "other"));
+        batch.push(a, product(a, "second"));
+        assert_eq!(batch.len(), 2);
+
+        batch.push(a, Change::Delete);
+        let mut changes = batch.take();
+        changes.sort_by_key(|(id, _)| *id);
+        assert_eq!(changes, vec![(a, Change::Delete), (b, product(b, "other"))]);
+        assert!(batch.is_empty());
+    }
+
+    #[test]
+    fn discard_drops_the_pending_change() {
+        let id = ObjectId::new();
+        let mut batch = Batch::default();
+        batch.push(id, product(id, "stale"));
+        batch.discard(&id);
+        assert!(batch.is_empty());
+    }
+}
@@ -0,0 +1,125 @@
+use crate::errors::{Result, SyncError};
+use chrono::{DateTime, Utc};
+use mongodb::change_stream::event::ResumeToken;
+use redis::{AsyncCommands, Client as RedisClient};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+pub const DEFAULT_KEY_PREFIX: &str = "catalog-sync";
+
+/// A change the worker gave up on. Operators inspect the list, fix the product and
+/// touch it in Mongo to have it synced again.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeadLetter {
+    pub product_id: String,
+    pub operation: String,
+    pub error: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+/// BSON needs a document at the top level; the token itself is a nested value.
+#[derive(Serialize, Deserialize)]
+struct StoredToken {
+    token: ResumeToken,
+}
+
+/// Worker state kept in Redis: the change-stream resume token and the dead-letter list.
+#[derive(Clone, Debug)]
+pub struct Checkpoints {
+    redis: RedisClient,
+    resume_token_key: String,
+    dead_letter_key: String,
+}
+
+impl Checkpoints {
+    pub fn new(redis: RedisClient, key_prefix: &str) -> Self {
+        Checkpoints {
+            redis,
+            resume_token_key: format!("{}:resume-token", key_prefix),
+            dead_letter_key: format!("{}:dead-letter", key_prefix),
+        }
+    }
+
+    pub async fn load_resume_token(&self) -> Result<Option<ResumeToken>> {
+        let mut conn = self.redis.get_multiplexed_async_connection().await?;
+        let Some(bytes) = conn
+            .get::<_, Option<Vec<u8>>>(&self.resume_token_key)
+            .await?
+        else {
+            return Ok(None);
+        };
+        // Falling back to "now" would silently skip changes; refuse and let the operator
+        // decide between fixing the key and a backfill.
+        bson::from_slice::<StoredToken>(&bytes)
+            .map(|stored| Some(stored.token))
+            .map_err(|e| {
+                SyncError::Checkpoint(format!(
+                    "stored resume token under '{}' is unreadable: {}",
+                    self.resume_token_key, e
+                ))
+            })
+    }
+
+    pub async fn save_resume_token(&self, token: &ResumeToken) -> Result<()> {
+        let bytes = bson::to_vec(&StoredToken {
+            token: token.clone(),
+        })
+        .map_err(|e| SyncError::Checkpoint(e.to_string()))?;
+        let mut conn = self.redis.get_multiplexed_async_connection().await?;
+        conn.set::<_, _, ()>(&self.resume_token_key, bytes).await?;
+        debug!("Saved resume token to '{}'", self.resume_token_key);
+        Ok(())
+    }
+
+    pub async fn dead_letter(&self, entry: &DeadLetter) -> Result<()> {
+        warn!(
+            product_id = %entry.product_id,
+            operation = %entry.operation,
+            "Dead-lettering change: {}",
+            entry.error
+        );
+        let json = serde_json::to_string(entry).expect("dead letter serializes");
+        let mut conn = self.redis.get_multiplexed_async_connection().await?;
+        conn.rpush::<_, _, ()>(&self.dead_letter_key, json).await?;
+        Ok(())
+    }
+
+    /// Oldest first.
+    pub async fn dead_letters(&self) -> Result<Vec<DeadLetter>> {
+        let mut conn = self.redis.get_multiplexed_async_connection().await?;
+        let entries: Vec<String> = conn.lrange(&self.dead_letter_key, 0, -1).await?;
+        Ok(entries
+            .iter()
+            .filter_map(|entry| serde_json::from_str(entry).ok())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn dead_letter_json_snapshot() {
+        let entry = DeadLetter {
+            product_id: "663a1f2e9b1e8a3f4c5d6e7f".to_string(),
+            operation: "upsert".to_string(),
+            error: "Invalid product document 663a1f2e9b1e8a3f4c5d6e7f: missing field `code`"
+                .to_string(),
+            failed_at: DateTime::parse_from_rfc3339("2024-06-01T08:00:00Z")
+                .unwrap()
+                .with_timezone(&Utc),
+        };
+        assert_eq!(
+            serde_json::to_value(&entry).unwrap(),
+            json!({
+                "productId": "663a1f2e9b1e8a3f4c5d6e7f",
+                "operation": "upsert",
+                "error": "Invalid product document 663a1f2e9b1e8a3f4c5d6e7f: missing field `code`",
+                "failedAt": "2024-06-01T08:00:00Z"
+            })
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use crate::errors::{Result, SyncError};
+use std::{env, str::FromStr, time::Duration};
+
+#[derive(Debug, Clone)]
+pub struct WorkerConfig {
+    pub qdrant_collection: String,
+    /// Must match the embedding model and the existing collection.
+    pub vector_dimension: u64,
+    /// Pending changes that trigger a flush before `flush_interval` is up.
+    pub batch_size: usize,
+    /// Upper bound on how long a change waits in a partial batch; also how often the
+    /// resume token is saved while the collection is idle.
+    pub flush_interval: Duration,
+    /// Attempts per write before a transient failure stops the worker.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for every following one.
+    pub retry_base_delay: Duration,
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        WorkerConfig {
+            qdrant_collection: "product_vectors".to_string(),
+            vector_dimension: 384,
+            batch_size: 100,
+            flush_interval: Duration::from_secs(1),
+            max_attempts: 5,
+            retry_base_delay: Duration::from_millis(200),
+        }
+    }
+}
+
+impl WorkerConfig {
+    /// Every setting is optional; unset variables keep the defaults.
+    pub fn from_env() -> Result<Self> {
+        Self::from_lookup(|name| env::var(name).ok())
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let defaults = WorkerConfig::default();
+        Ok(WorkerConfig {
+            qdrant_collection: lookup("QDRANT_COLLECTION_NAME")
+                .filter(|v| !v.trim().is_empty())
+                .unwrap_or(defaults.qdrant_collection),
+            vector_dimension: parse_var(&lookup, "VECTOR_DIMENSION")?
+                .unwrap_or(defaults.vector_dimension),
+            batch_size: parse_var(&lookup, "SYNC_BATCH_SIZE")?.unwrap_or(defaults.batch_size),
+            flush_interval: parse_var(&lookup, "SYNC_FLUSH_INTERVAL_MS")?
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.flush_interval),
+            max_attempts: parse_var(&lookup, "SYNC_MAX_ATTEMPTS")?.unwrap_or(defaults.max_attempts),
+            retry_base_delay: parse_var(&lookup, "SYNC_RETRY_BASE_DELAY_MS")?
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.retry_base_delay),
+        })
+    }
+}
+
+fn parse_var<T: FromStr + PartialOrd + Default>(
+    lookup: &impl Fn(&str) -> Option<String>,
+    name: &str,
+) -> Result<Option<T>> {
+    let Some(raw) = lookup(name) else {
+        return Ok(None);
+    };
+    match raw.trim().parse::<T>() {
+        Ok(value) if value > T::default() => Ok(Some(value)),
+        _ => Err(SyncError::Config(format!(
+            "{} must be a positive integer, got '{}'",
+            name, raw
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config(vars: &[(&str, &str)]) -> Result<WorkerConfig> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        WorkerConfig::from_lookup(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn unset_variables_keep_the_defaults() {
+        let config = config(&[]).unwrap();
+        assert_eq!(config.qdrant_collection, "product_vectors");
+        assert_eq!(config.vector_dimension, 384);
+        assert_eq!(config.batch_size, 100);
+        assert_eq!(config.flush_interval, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn variables_override_the_defaults() {
+        let config = config(&[
+            ("QDRANT_COLLECTION_NAME", "vectors_v2"),
+            ("VECTOR_DIMENSION", "768"),
+            ("SYNC_BATCH_SIZE", "25"),
+            ("SYNC_FLUSH_INTERVAL_MS", "250"),
+            ("SYNC_MAX_ATTEMPTS", "2"),
+        ])
+        .unwrap();
+        assert_eq!(config.qdrant_collection, "vectors_v2");
+        assert_eq!(config.vector_dimension, 768);
+        assert_eq!(config.batch_size, 25);
+        assert_eq!(config.flush_interval, Duration::from_millis(250));
+        assert_eq!(config.max_attempts, 2);
+    }
+
+    #[test]
+    fn zero_and_garbage_are_rejected() {
+        assert!(config(&[("SYNC_BATCH_SIZE", "0")]).is_err());
+        assert!(config(&[("VECTOR_DIMENSION", "large")]).is_err());
+        assert!(config(&[("SYNC_MAX_ATTEMPTS", "-1")]).is_err());
+    }
+}
@@ -0,0 +1,148 @@
+use crate::errors::{Result, SyncError};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use tracing::debug;
+
+/// Turns product texts into vectors, one per input, in order.
+pub trait Embedder: Send + Sync {
+    fn embed(&self, texts: &[String]) -> impl Future<Output = Result<Vec<Vec<f32>>>> + Send;
+}
+
+#[derive(Serialize)]
+struct EmbedRequest<'a> {
+    texts: &'a [String],
+}
+
+#[derive(Deserialize)]
+struct EmbedResponse {
+    vectors: Vec<Vec<f32>>,
+}
+
+/// Client for the embedding service: `POST {base_url}/embed` with `{"texts": [...]}`,
+/// answered by `{"vectors": [[...], ...]}`.
+///
+/// A 4xx means the service refused this input and is reported as
+/// [`SyncError::EmbeddingRejected`]; anything else counts as the service being down.
+#[derive(Clone, Debug)]
+pub struct HttpEmbedder {
+    client: Client,
+    endpoint: String,
+}
+
+impl HttpEmbedder {
+    pub fn new(client: Client, base_url: &str) -> Self {
+        HttpEmbedder {
+            client,
+            endpoint: format!("{}/embed", base_url.trim_end_matches('/')),
+        }
+    }
+}
+
+impl Embedder for HttpEmbedder {
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        debug!(
+            "Requesting {} embeddings from {}",
+            texts.len(),
+            self.endpoint
+        );
+        let response = self
+            .client
+            .post(&self.endpoint)
+            .json(&EmbedRequest { texts })
+            .send()
+            .await
+            .map_err(|e| SyncError::EmbeddingUnavailable(e.to_string()))?;
+
+        let status = response.status();
+        if status.is_client_error() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(SyncError::EmbeddingRejected(format!(
+                "{}: {}",
+                status, body
+            )));
+        }
+        if !status.is_success() {
+            return Err(SyncError::EmbeddingUnavailable(status.to_string()));
+        }
+
+        let body: EmbedResponse = response
+            .json()
+            .await
+            .map_err(|e| SyncError::EmbeddingUnavailable(e.to_string()))?;
+        if body.vectors.len() != texts.len() {
+            return Err(SyncError::EmbeddingUnavailable(format!(
+                "expected {} vectors, got {}",
+                texts.len(),
+                body.vectors.len()
+            )));
+        }
+        Ok(body.vectors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{body_json, method, path},
+    };
+
+    fn texts() -> Vec<String> {
+        vec!["Product: a".to_string(), "Product: b".to_string()]
+    }
+
+    #[tokio::test]
+    async fn returns_one_vector_per_text() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/embed"))
+            .and(body_json(json!({ "texts": ["Product: a", "Product: b"] })))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({ "vectors": [[0.1, 0.2], [0.3, 0.4]] })),
+            )
+            .mount(&server)
+            .await;
+
+        let embedder = HttpEmbedder::new(Client::new(), &format!("{}/", server.uri()));
+        let vectors = embedder.embed(&texts()).await.unwrap();
+        assert_eq!(vectors, vec![vec![0.1, 0.2], vec![0.3, 0.4]]);
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_permanent_and_server_errors_are_not() {
+        let server = MockServer::start().await;
+        let embedder = HttpEmbedder::new(Client::new(), &server.uri());
+
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(422).set_body_string("text too long"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        let err = embedder.embed(&texts()).await.unwrap_err();
+        assert!(err.is_permanent(), "{}", err);
+
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+        let err = embedder.embed(&texts()).await.unwrap_err();
+        assert!(!err.is_permanent(), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn short_responses_are_an_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "vectors": [[0.1]] })))
+            .mount(&server)
+            .await;
+
+        let embedder = HttpEmbedder::new(Client::new(), &server.uri());
+        let err = embedder.embed(&texts()).await.unwrap_err();
+        assert!(matches!(err, SyncError::EmbeddingUnavailable(_)));
+    }
+}
@@ -0,0 +1,44 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SyncError {
+    #[error("MongoDB error: {0}")]
+    Mongo(#[from] mongodb::error::Error),
+
+    #[error("Redis error: {0}")]
+    Redis(#[from] redis::RedisError),
+
+    #[error("Qdrant error: {0}")]
+    Qdrant(#[from] qdrant_client::QdrantError),
+
+    #[error("Embedding service unavailable: {0}")]
+    EmbeddingUnavailable(String),
+
+    #[error("Embedding service rejected the input: {0}")]
+    EmbeddingRejected(String),
+
+    #[error("Invalid product document {id}: {reason}")]
+    InvalidDocument { id: String, reason: String },
+
+    #[error("Failed to encode resume token: {0}")]
+    Checkpoint(String),
+
+    #[error("Change stream invalidated ({0}); rerun with --backfill")]
+    Invalidated(String),
+
+    #[error("Configuration error: {0}")]
+    Config(String),
+}
+
+impl SyncError {
+    /// Errors that will fail the same way on every retry, so the document is
+    /// dead-lettered instead of retried. Everything else is assumed to be an outage.
+    pub fn is_permanent(&self) -> bool {
+        matches!(
+            self,
+            SyncError::InvalidDocument { .. } | SyncError::EmbeddingRejected(_)
+        )
+    }
+}
+
+pub type Result<T, E = SyncError> = std::result::Result<T, E>;
@@ -0,0 +1,22 @@
+//! Keeps the Qdrant product vectors in sync with the MongoDB catalog.
+//!
+//! [`Worker`] tails a change stream on the products collection, re-embeds inserted and
+//! updated products through an [`Embedder`] and upserts their points, and deletes the
+//! points of deleted products. The resume token and a dead-letter list live in Redis
+//! ([`Checkpoints`]). `main.rs` wires it to the real services; the library is public so
+//! the integration harness can drive it with a fake embedder.
+
+pub mod batch;
+pub mod checkpoint;
+pub mod config;
+pub mod embedding;
+pub mod errors;
+pub mod point;
+pub mod worker;
+
+pub use checkpoint::{Checkpoints, DEFAULT_KEY_PREFIX, DeadLetter};
+pub use config::WorkerConfig;
+pub use embedding::{Embedder, HttpEmbedder};
+pub use errors::SyncError;
+pub use point::point_id;
+pub use worker::{RunMode, Worker};
@@ -0,0 +1,60 @@
+use catalog_sync_worker::{
+    Checkpoints, DEFAULT_KEY_PREFIX, HttpEmbedder, RunMode, Worker, WorkerConfig,
+};
+use dotenvy::dotenv;
+use qdrant_client::Qdrant;
+use reqwest::Client;
+use rust_database_clients::{
+    MongoSettings, create_mongo_client_with_settings, create_redis_client, load_config,
+    validate_qdrant_uri,
+};
+use std::{env, sync::Arc, time::Duration};
+use tracing::{error, info};
+use yoloeats_shutdown::shutdown_signal;
+use yoloeats_tracing::init_tracing;
+
+#[tokio::main]
+async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    dotenv().ok();
+
+    let _telemetry = init_tracing("catalog-sync-worker", env!("CARGO_PKG_VERSION"))?;
+
+    let mode = RunMode::from_args(env::args().skip(1))?;
+    info!("Starting Catalog Sync Worker ({:?} mode)...", mode);
+
+    let (mongo_uri, redis_uri) = load_config()?;
+    let qdrant_uri = env::var("QDRANT_URI").expect("QDRANT_URI must be set");
+    let embedding_service_url =
+        env::var("EMBEDDING_SERVICE_URL").expect("EMBEDDING_SERVICE_URL must be set");
+    let database = env::var("MONGO_DB_NAME").unwrap_or_else(|_| "openfoods".to_string());
+    let config = WorkerConfig::from_env()?;
+    validate_qdrant_uri(&qdrant_uri)?;
+
+    info!("Qdrant URI: {}", qdrant_uri);
+    info!("Embedding Service URL: {}", embedding_service_url);
+    info!("Worker configuration: {:?}", config);
+
+    let mongo_settings = MongoSettings::from_env("catalog-sync-worker")?;
+    let mongo_client = create_mongo_client_with_settings(&mongo_uri, &mongo_settings).await?;
+    let products = mongo_client.database(&database).collection("products");
+    let redis_client = create_redis_client(&redis_uri)?;
+    let qdrant = Arc::new(Qdrant::from_url(&qdrant_uri).build()?);
+    let embedder = HttpEmbedder::new(
+        Client::builder().timeout(Duration::from_secs(30)).build()?,
+        &embedding_service_url,
+    );
+    info!("Clients connected. Syncing '{}.products'.", database);
+
+    let worker = Worker::new(
+        products,
+        qdrant,
+        Checkpoints::new(redis_client, DEFAULT_KEY_PREFIX),
+        embedder,
+        config,
+    );
+    worker.run(mode, shutdown_signal()).await.map_err(|e| {
+        error!("Catalog sync stopped: {}", e);
+        e
+    })?;
+    Ok(())
+}
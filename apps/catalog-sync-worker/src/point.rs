@@ -0,0 +1,31 @@
+//! The product points the worker writes, built by [`yoloeats_domain::point`] as the
+//! catalog builds its own.
+
+use crate::errors::{Result, SyncError};
+use bson::{Document, oid::ObjectId};
+
+pub use yoloeats_domain::point::{ProductDoc, point_id};
+
+/// Reads the stored product `id`, a document that doesn't fit a [`ProductDoc`] being a
+/// permanent [`SyncError::InvalidDocument`].
+pub fn product_doc(id: &ObjectId, document: Document) -> Result<ProductDoc> {
+    ProductDoc::from_document(document).map_err(|e| SyncError::InvalidDocument {
+        id: id.to_hex(),
+        reason: e.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::doc;
+
+    #[test]
+    fn malformed_documents_are_invalid() {
+        let id = ObjectId::parse_str("663a1f2e9b1e8a3f4c5d6e7f").unwrap();
+        let err = product_doc(&id, doc! { "_id": id, "code": 42 }).unwrap_err();
+        assert!(err.is_permanent(), "{}", err);
+        let err = product_doc(&id, doc! { "_id": id }).unwrap_err();
+        assert!(matches!(err, SyncError::InvalidDocument { .. }));
+    }
+}
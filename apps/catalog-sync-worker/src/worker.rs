@@ -0,0 +1,480 @@
+use crate::{
+    batch::{Batch, Change},
+    checkpoint::{Checkpoints, DeadLetter},
+    config::WorkerConfig,
+    embedding::Embedder,
+    errors::{Result, SyncError},
+    point::{point_id, product_doc},
+};
+use bson::{Document, doc, oid::ObjectId};
+use chrono::Utc;
+use futures::{StreamExt, TryStreamExt};
+use mongodb::{
+    Collection,
+    change_stream::{
+        ChangeStream,
+        event::{ChangeStreamEvent, OperationType, ResumeToken},
+    },
+    options::FullDocumentType,
+};
+use qdrant_client::{
+    Qdrant,
+    qdrant::{
+        CreateCollectionBuilder, CreateFieldIndexCollectionBuilder, DeletePointsBuilder, Distance,
+        FieldType, PointId, PointStruct, PointsIdsList, UpsertPointsBuilder, VectorParamsBuilder,
+    },
+};
+use std::{future::Future, sync::Arc};
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, info, instrument, warn};
+
+/// Payload fields the catalog filters on.
+const INDEXED_PAYLOAD_FIELDS: [&str; 4] =
+    ["code", "labels_tags", "allergens_tags", "countries_tags"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunMode {
+    /// Continue from the saved resume token, or from now if there is none.
+    Stream,
+    /// Re-index every product, then continue streaming from when the scan started.
+    Backfill,
+}
+
+impl RunMode {
+    /// Parses the command line (without the program name): nothing or `--backfill`.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Self> {
+        let mut mode = RunMode::Stream;
+        for arg in args {
+            match arg.as_str() {
+                "--backfill" => mode = RunMode::Backfill,
+                other => {
+                    return Err(SyncError::Config(format!(
+                        "unknown argument '{}' (usage: catalog-sync-worker [--backfill])",
+                        other
+                    )));
+                }
+            }
+        }
+        Ok(mode)
+    }
+}
+
+/// Mirrors the products collection into Qdrant.
+///
+/// Delivery is at-least-once: the resume token is saved only after the changes before
+/// it are in Qdrant, so a crash replays a few changes rather than losing them. Writes are
+/// idempotent upserts and deletes keyed by [`point_id`], so replays are harmless.
+///
+/// Documents that can never succeed (malformed, or refused by the embedding service) go
+/// to the dead-letter list. Transient failures are retried with backoff; once retries
+/// run out, [`Worker::run`] returns the error without saving the token so a restart
+/// picks up from the last good position.
+pub struct Worker<E> {
+    products: Collection<Document>,
+    qdrant: Arc<Qdrant>,
+    checkpoints: Checkpoints,
+    embedder: E,
+    config: WorkerConfig,
+}
+
+impl<E: Embedder> Worker<E> {
+    pub fn new(
+        products: Collection<Document>,
+        qdrant: Arc<Qdrant>,
+        checkpoints: Checkpoints,
+        embedder: E,
+        config: WorkerConfig,
+    ) -> Self {
+        Worker {
+            products,
+            qdrant,
+            checkpoints,
+            embedder,
+            config,
+        }
+    }
+
+    pub fn checkpoints(&self) -> &Checkpoints {
+        &self.checkpoints
+    }
+
+    /// Runs until `shutdown` resolves, then flushes pending changes, saves the resume
+    /// token and returns.
+    pub async fn run(&self, mode: RunMode, shutdown: impl Future<Output = ()>) -> Result<()> {
+        self.ensure_collection().await?;
+
+        let resume_after = match mode {
+            RunMode::Backfill => None,
+            RunMode::Stream => self.checkpoints.load_resume_token().await?,
+        };
+        // Opened before the backfill scan so writes racing the scan are replayed after it.
+        let mut stream = self.open_stream(resume_after).await?;
+        if mode == RunMode::Backfill {
+            self.backfill().await?;
+            self.save_position(stream.resume_token()).await?;
+        }
+
+        let mut batch = Batch::default();
+        let mut ticker = tokio::time::interval(self.config.flush_interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        tokio::pin!(shutdown);
+
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => {
+                    info!("Shutdown requested, flushing {} pending changes", batch.len());
+                    self.flush(&mut batch).await?;
+                    self.save_position(stream.resume_token()).await?;
+                    info!("Resume token saved, catalog sync stopped.");
+                    return Ok(());
+                }
+                _ = ticker.tick() => {
+                    self.flush(&mut batch).await?;
+                    self.save_position(stream.resume_token()).await?;
+                }
+                event = stream.next() => {
+                    let Some(event) = event else {
+                        return Err(SyncError::Invalidated("change stream closed".to_string()));
+                    };
+                    self.stage(&mut batch, event?).await?;
+                    if batch.len() >= self.config.batch_size {
+                        self.flush(&mut batch).await?;
+                        self.save_position(stream.resume_token()).await?;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn ensure_collection(&self) -> Result<()> {
+        let name = &self.config.qdrant_collection;
+        if self.qdrant.collection_exists(name).await? {
+            debug!("Qdrant collection '{}' exists", name);
+            return Ok(());
+        }
+
+        info!(
+            "Creating Qdrant collection '{}' ({} dimensions)",
+            name, self.config.vector_dimension
+        );
+        self.qdrant
+            .create_collection(CreateCollectionBuilder::new(name).vectors_config(
+                VectorParamsBuilder::new(self.config.vector_dimension, Distance::Cosine),
+            ))
+            .await?;
+        for field in INDEXED_PAYLOAD_FIELDS {
+            self.qdrant
+                .create_field_index(
+                    CreateFieldIndexCollectionBuilder::new(name, field, FieldType::Keyword)
+                        .wait(true),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn open_stream(
+        &self,
+        resume_after: Option<ResumeToken>,
+    ) -> Result<ChangeStream<ChangeStreamEvent<Document>>> {
+        let mut watch = self
+            .products
+            .watch()
+            .full_document(FullDocumentType::UpdateLookup);
+        match resume_after {
+            Some(token) => {
+                info!("Resuming change stream on '{}'", self.products.name());
+                watch = watch.resume_after(token);
+            }
+            None => info!(
+                "Starting change stream on '{}' from the current time",
+                self.products.name()
+            ),
+        }
+        Ok(watch.await?)
+    }
+
+    #[instrument(skip(self))]
+    async fn backfill(&self) -> Result<()> {
+        info!(
+            "Backfilling '{}' into Qdrant collection '{}'",
+            self.products.name(),
+            self.config.qdrant_collection
+        );
+        let mut cursor = self.products.find(doc! {}).await?;
+        let mut batch = Batch::default();
+        let mut scanned: u64 = 0;
+
+        while let Some(document) = cursor.try_next().await? {
+            scanned += 1;
+            match document.get_object_id("_id") {
+                Ok(id) => self.stage_upsert(&mut batch, id, document).await?,
+                Err(_) => warn!(
+                    "Skipping product without an ObjectId _id: {:?}",
+                    document.get("_id")
+                ),
+            }
+            if batch.len() >= self.config.batch_size {
+                self.flush(&mut batch).await?;
+                info!("Backfill progress: {} products scanned", scanned);
+            }
+        }
+        self.flush(&mut batch).await?;
+        info!("Backfill complete: {} products scanned", scanned);
+        Ok(())
+    }
+
+    async fn stage(&self, batch: &mut Batch, event: ChangeStreamEvent<Document>) -> Result<()> {
+        match &event.operation_type {
+            OperationType::Insert
+            | OperationType::Update
+            | OperationType::Replace
+            | OperationType::Delete => {}
+            OperationType::Drop
+            | OperationType::DropDatabase
+            | OperationType::Rename
+            | OperationType::Invalidate => {
+                return Err(SyncError::Invalidated(format!(
+                    "{:?} on '{}'",
+                    event.operation_type,
+                    self.products.name()
+                )));
+            }
+            other => {
+                debug!("Ignoring {:?} event", other);
+                return Ok(());
+            }
+        }
+
+        let Some(id) = event
+            .document_key
+            .as_ref()
+            .and_then(|key| key.get_object_id("_id").ok())
+        else {
+            warn!(
+                "Skipping change without an ObjectId _id: {:?}",
+                event.document_key
+            );
+            return Ok(());
+        };
+
+        match (event.operation_type, event.full_document) {
+            // No full document on an update means it was deleted before the lookup ran.
+            (OperationType::Delete, _) | (_, None) => {
+                debug!("Staging delete for product {}", id);
+                batch.push(id, Change::Delete);
+                Ok(())
+            }
+            (_, Some(document)) => self.stage_upsert(batch, id, document).await,
+        }
+    }
+
+    async fn stage_upsert(
+        &self,
+        batch: &mut Batch,
+        id: ObjectId,
+        document: Document,
+    ) -> Result<()> {
+        match product_doc(&id, document) {
+            Ok(product) => {
+                debug!("Staging upsert for product {}", id);
+                batch.push(id, Change::Upsert(Box::new(product)));
+                Ok(())
+            }
+            Err(e) => {
+                batch.discard(&id);
+                self.dead_letter(&id, "upsert", &e).await
+            }
+        }
+    }
+
+    /// Writes the whole batch at once. If a poison document fails it, the changes are
+    /// replayed one by one so only the offenders are dead-lettered.
+    async fn flush(&self, batch: &mut Batch) -> Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let changes = batch.take();
+        match retry(&self.config, || self.apply(&changes)).await {
+            Ok(()) => {
+                debug!("Synced {} changes to Qdrant", changes.len());
+                Ok(())
+            }
+            Err(e) if e.is_permanent() => {
+                warn!(
+                    "Batch of {} changes rejected ({}), isolating the failing documents",
+                    changes.len(),
+                    e
+                );
+                for change in &changes {
+                    let single = std::slice::from_ref(change);
+                    if let Err(e) = retry(&self.config, || self.apply(single)).await {
+                        if !e.is_permanent() {
+                            return Err(e);
+                        }
+                        self.dead_letter(&change.0, change.1.operation(), &e)
+                            .await?;
+                    }
+                }
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn apply(&self, changes: &[(ObjectId, Change)]) -> Result<()> {
+        let collection = &self.config.qdrant_collection;
+        let mut upserts = Vec::new();
+        let mut deletes: Vec<PointId> = Vec::new();
+        for (id, change) in changes {
+            match change {
+                Change::Upsert(product) => upserts.push((id, product)),
+                Change::Delete => deletes.push(point_id(id).into()),
+            }
+        }
+
+        if !upserts.is_empty() {
+            let texts: Vec<String> = upserts
+                .iter()
+                .map(|(_, product)| product.embedding_text())
+                .collect();
+            let vectors = self.embedder.embed(&texts).await?;
+            let points = upserts
+                .iter()
+                .zip(vectors)
+                .map(|((id, product), vector)| {
+                    if vector.len() as u64 != self.config.vector_dimension {
+                        // A model/collection mismatch, not a bad document: stop, don't dead-letter.
+                        return Err(SyncError::EmbeddingUnavailable(format!(
+                            "got a {}-dimensional vector, collection '{}' expects {}",
+                            vector.len(),
+                            collection,
+                            self.config.vector_dimension
+                        )));
+                    }
+                    Ok(PointStruct::new(point_id(id), vector, product.payload()))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            self.qdrant
+                .upsert_points(UpsertPointsBuilder::new(collection, points).wait(true))
+                .await?;
+        }
+
+        if !deletes.is_empty() {
+            self.qdrant
+                .delete_points(
+                    DeletePointsBuilder::new(collection)
+                        .points(PointsIdsList { ids: deletes })
+                        .wait(true),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn dead_letter(&self, id: &ObjectId, operation: &str, error: &SyncError) -> Result<()> {
+        self.checkpoints
+            .dead_letter(&DeadLetter {
+                product_id: id.to_hex(),
+                operation: operation.to_string(),
+                error: error.to_string(),
+                failed_at: Utc::now(),
+            })
+            .await
+    }
+
+    async fn save_position(&self, resume_token: Option<ResumeToken>) -> Result<()> {
+        match resume_token {
+            Some(token) => self.checkpoints.save_resume_token(&token).await,
+            None => Ok(()),
+        }
+    }
+}
+
+/// Runs `op` up to `max_attempts` times with exponential backoff. Permanent errors are
+/// returned immediately: retrying a malformed document only delays the dead letter.
+async fn retry<T, F, Fut>(config: &WorkerConfig, mut op: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 1;
+    loop {
+        match op().await {
+            Ok(value) => return Ok(value),
+            Err(e) if e.is_permanent() || attempt >= config.max_attempts => return Err(e),
+            Err(e) => {
+                let delay = config.retry_base_delay * 2u32.saturating_pow(attempt - 1);
+                warn!(
+                    "Attempt {}/{} failed: {}. Retrying in {:?}",
+                    attempt, config.max_attempts, e, delay
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::atomic::{AtomicU32, Ordering},
+        time::Duration,
+    };
+
+    fn config(max_attempts: u32) -> WorkerConfig {
+        WorkerConfig {
+            max_attempts,
+            retry_base_delay: Duration::ZERO,
+            ..WorkerConfig::default()
+        }
+    }
+
+    #[test]
+    fn backfill_is_opt_in() {
+        let args = |args: &[&str]| RunMode::from_args(args.iter().map(|a| a.to_string()));
+        assert_eq!(args(&[]).unwrap(), RunMode::Stream);
+        assert_eq!(args(&["--backfill"]).unwrap(), RunMode::Backfill);
+        assert!(args(&["--backfil"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn transient_errors_are_retried_up_to_the_limit() {
+        let calls = &AtomicU32::new(0);
+        let result: Result<()> = retry(&config(3), move || async move {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(SyncError::EmbeddingUnavailable("503".to_string()))
+        })
+        .await;
+        assert!(matches!(result, Err(SyncError::EmbeddingUnavailable(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn recovers_when_a_retry_succeeds() {
+        let calls = &AtomicU32::new(0);
+        let result = retry(&config(3), move || async move {
+            match calls.fetch_add(1, Ordering::SeqCst) {
+                0 => Err(SyncError::EmbeddingUnavailable("timeout".to_string())),
+                _ => Ok("synced"),
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), "synced");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn permanent_errors_are_not_retried() {
+        let calls = &AtomicU32::new(0);
+        let result: Result<()> = retry(&config(5), move || async move {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(SyncError::EmbeddingRejected("422".to_string()))
+        })
+        .await;
+        assert!(result.unwrap_err().is_permanent());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,21 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Failed to download dump: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("MongoDB error: {0}")]
+    Mongo(#[from] mongodb::error::Error),
+
+    #[error("Malformed CSV header: {0}")]
+    Header(String),
+
+    #[error("Corrupt offset file {path}: {reason}")]
+    Offset { path: String, reason: String },
+}
+
+pub type Result<T, E = ImportError> = std::result::Result<T, E>;
@@ -0,0 +1,252 @@
+use crate::errors::{ImportError, Result};
+use crate::mapping::{Filter, Mapped, SkipReason, map_row};
+use crate::offset::OffsetFile;
+use crate::rejects::{Reject, RejectsWriter};
+use crate::row::RowParser;
+use crate::source::{DumpReader, Format};
+use bson::doc;
+use chrono::Utc;
+use futures::{StreamExt, TryStreamExt, stream};
+use mongodb::Collection;
+use product_catalog_service::models::Product;
+use std::{collections::HashSet, fmt, time::Instant};
+use tokio::io::AsyncBufReadExt;
+use tracing::{debug, info};
+
+#[derive(Debug, Clone)]
+pub struct ImportConfig {
+    pub filter: Filter,
+    /// Products upserted together; the offset is saved after each batch.
+    pub batch_size: usize,
+    /// Upserts in flight at once within a batch.
+    pub concurrency: usize,
+    /// Lines between progress log lines.
+    pub progress_every: u64,
+}
+
+impl Default for ImportConfig {
+    fn default() -> Self {
+        ImportConfig {
+            filter: Filter::default(),
+            batch_size: 500,
+            concurrency: 16,
+            progress_every: 10_000,
+        }
+    }
+}
+
+/// What one run did. Lines skipped because of a saved offset are not counted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ImportStats {
+    pub lines: u64,
+    pub inserted: u64,
+    pub updated: u64,
+    pub unchanged: u64,
+    pub skipped_country: u64,
+    pub skipped_incomplete: u64,
+    pub rejected: u64,
+}
+
+impl ImportStats {
+    pub fn imported(&self) -> u64 {
+        self.inserted + self.updated + self.unchanged
+    }
+
+    pub fn skipped(&self) -> u64 {
+        self.skipped_country + self.skipped_incomplete
+    }
+}
+
+impl fmt::Display for ImportStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows = [
+            ("lines read", self.lines),
+            ("inserted", self.inserted),
+            ("updated", self.updated),
+            ("unchanged", self.unchanged),
+            ("skipped (country)", self.skipped_country),
+            ("skipped (incomplete)", self.skipped_incomplete),
+            ("rejected", self.rejected),
+        ];
+        for (label, value) in rows {
+            writeln!(f, "{:<22} {:>10}", label, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Upserts `batch` by barcode. A dump can list a code twice; only the last row wins,
+/// since two concurrent upserts of one code would race.
+async fn flush(
+    products: &Collection<Product>,
+    batch: &mut Vec<Product>,
+    concurrency: usize,
+    stats: &mut ImportStats,
+) -> Result<()> {
+    let mut seen = HashSet::new();
+    let mut unique: Vec<Product> = batch
+        .drain(..)
+        .rev()
+        .filter(|p| seen.insert(p.code.clone()))
+        .collect();
+    unique.reverse();
+    debug!("Upserting batch of {} products", unique.len());
+
+    let results: Vec<_> = stream::iter(unique)
+        .map(|product| async move {
+            products
+                .replace_one(doc! { "code": &product.code }, &product)
+                .upsert(true)
+                .await
+        })
+        .buffer_unordered(concurrency.max(1))
+        .try_collect()
+        .await?;
+    for result in results {
+        if result.upserted_id.is_some() {
+            stats.inserted += 1;
+        } else if result.modified_count > 0 {
+            stats.updated += 1;
+        } else {
+            stats.unchanged += 1;
+        }
+    }
+    Ok(())
+}
+
+fn log_progress(stats: &ImportStats, started: Instant) {
+    let rate = stats.lines as f64 / started.elapsed().as_secs_f64().max(0.001);
+    info!(
+        "{} lines read: {} imported, {} skipped, {} rejected ({:.0} lines/s)",
+        stats.lines,
+        stats.imported(),
+        stats.skipped(),
+        stats.rejected,
+        rate
+    );
+}
+
+/// Streams `reader` into `products`, resuming after the line count in `offsets` and
+/// writing unparseable or unmappable rows to `rejects`. Mongo failures abort the run
+/// without advancing the offset, so rerunning retries the failed batch.
+pub async fn import(
+    mut reader: DumpReader,
+    format: Format,
+    products: &Collection<Product>,
+    offsets: &OffsetFile,
+    rejects: &mut RejectsWriter,
+    config: &ImportConfig,
+) -> Result<ImportStats> {
+    let resume_after = offsets.load().await?;
+    if resume_after > 0 {
+        info!(
+            "Resuming after line {} (from {})",
+            resume_after,
+            offsets.path().display()
+        );
+    }
+
+    let imported_at = Utc::now();
+    let started = Instant::now();
+    let mut parser = RowParser::new(format);
+    let mut stats = ImportStats::default();
+    let mut batch: Vec<Product> = Vec::with_capacity(config.batch_size);
+    let mut buf = Vec::new();
+    let mut line_no = 0u64;
+
+    loop {
+        if reader.read_until(b'\n', &mut buf).await? == 0 {
+            break;
+        }
+        line_no += 1;
+        let line = String::from_utf8(std::mem::take(&mut buf));
+        // The CSV header is needed even when resuming past it.
+        if line_no == 1 && parser.has_header() {
+            let header =
+                line.map_err(|_| ImportError::Header("header is not UTF-8".to_string()))?;
+            parser.read_header(header.trim_end_matches(['\r', '\n']))?;
+            continue;
+        }
+        if line_no <= resume_after {
+            continue;
+        }
+        stats.lines += 1;
+
+        let outcome = match &line {
+            Ok(line) if line.trim().is_empty() => None,
+            Ok(line) => Some(
+                parser
+                    .parse(line.trim_end_matches(['\r', '\n']))
+                    .map(|row| map_row(&row, &config.filter, imported_at))
+                    .unwrap_or_else(Mapped::Rejected),
+            ),
+            Err(_) => Some(Mapped::Rejected("line is not valid UTF-8".to_string())),
+        };
+        match outcome {
+            None => {}
+            Some(Mapped::Product(product)) => batch.push(*product),
+            Some(Mapped::Skipped(SkipReason::Country)) => stats.skipped_country += 1,
+            Some(Mapped::Skipped(SkipReason::Incomplete)) => stats.skipped_incomplete += 1,
+            Some(Mapped::Rejected(reason)) => {
+                let raw = match &line {
+                    Ok(line) => line.trim_end_matches(['\r', '\n']).to_string(),
+                    Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+                };
+                debug!("Rejecting line {}: {}", line_no, reason);
+                rejects
+                    .write(&Reject {
+                        line: line_no,
+                        reason: &reason,
+                        raw: &raw,
+                    })
+                    .await?;
+                stats.rejected += 1;
+            }
+        }
+
+        if batch.len() >= config.batch_size {
+            flush(products, &mut batch, config.concurrency, &mut stats).await?;
+            rejects.flush().await?;
+            offsets.save(line_no).await?;
+        }
+        if stats.lines % config.progress_every.max(1) == 0 {
+            log_progress(&stats, started);
+        }
+    }
+
+    flush(products, &mut batch, config.concurrency, &mut stats).await?;
+    rejects.flush().await?;
+    offsets.save(line_no.max(resume_after)).await?;
+    log_progress(&stats, started);
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_render_a_summary() {
+        let stats = ImportStats {
+            lines: 1000,
+            inserted: 900,
+            skipped_country: 60,
+            skipped_incomplete: 20,
+            rejected: 20,
+            ..ImportStats::default()
+        };
+        assert_eq!(stats.imported(), 900);
+        assert_eq!(stats.skipped(), 80);
+        let table = stats.to_string();
+        assert!(
+            table.starts_with("lines read                   1000\n"),
+            "{}",
+            table
+        );
+        assert!(
+            table.contains("rejected                       20\n"),
+            "{}",
+            table
+        );
+    }
+}
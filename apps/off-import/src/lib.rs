@@ -0,0 +1,21 @@
+//! Loads an OpenFoodFacts dump into the catalog's `products` collection.
+//!
+//! [`source::open`] streams a JSONL or CSV dump from disk or HTTP (gunzipping on the
+//! fly), [`mapping::map_row`] filters rows and maps them onto the catalog's `Product`
+//! using the shared tag normalization, and [`import`] upserts them by barcode in
+//! batches, recording progress in an [`OffsetFile`] and bad rows in a rejects file.
+
+pub mod errors;
+pub mod importer;
+pub mod mapping;
+pub mod offset;
+pub mod rejects;
+pub mod row;
+pub mod source;
+
+pub use errors::ImportError;
+pub use importer::{ImportConfig, ImportStats, import};
+pub use mapping::Filter;
+pub use offset::OffsetFile;
+pub use rejects::RejectsWriter;
+pub use source::Format;
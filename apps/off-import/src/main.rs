@@ -0,0 +1,93 @@
+use clap::Parser;
+use dotenvy::dotenv;
+use off_import::{Filter, Format, ImportConfig, OffsetFile, RejectsWriter, import, source};
+use product_catalog_service::models::Product;
+use rust_database_clients::{MongoSettings, create_mongo_client_with_settings, validate_mongo_uri};
+use std::{env, path::PathBuf};
+use tracing::info;
+use yoloeats_tracing::init_tracing;
+
+/// Imports an OpenFoodFacts dump into the catalog. Rerunning resumes from the offset
+/// file; every product is upserted by barcode, so overlapping runs are harmless.
+#[derive(Parser, Debug)]
+#[command(name = "off-import", version)]
+struct Cli {
+    /// Dump path or http(s) URL; `.gz` is decompressed while streaming.
+    input: String,
+
+    /// Dump format; guessed from the file name when omitted.
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+
+    /// Keep products sold in this country tag (repeatable).
+    #[arg(long = "country", default_values_t = ["en:germany".to_string()])]
+    countries: Vec<String>,
+
+    /// Import every country, ignoring --country.
+    #[arg(long, conflicts_with = "countries")]
+    all_countries: bool,
+
+    /// Minimum OFF completeness score, 0.0 to 1.0.
+    #[arg(long, default_value_t = 0.0)]
+    min_completeness: f64,
+
+    #[arg(long, default_value_t = 500, value_parser = clap::value_parser!(u32).range(1..))]
+    batch_size: u32,
+
+    /// Where progress is saved; delete it (or pass --restart) to import from the top.
+    #[arg(long, default_value = "off-import.offset")]
+    offset_file: PathBuf,
+
+    /// Rejected rows are appended here as JSON lines, with the reason.
+    #[arg(long, default_value = "off-import.rejects.jsonl")]
+    rejects: PathBuf,
+
+    /// Ignore the saved offset and start from the first line.
+    #[arg(long)]
+    restart: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    dotenv().ok();
+
+    let _telemetry = init_tracing("off-import", env!("CARGO_PKG_VERSION"))?;
+    let cli = Cli::parse();
+
+    let mongo_uri = env::var("MONGO_URI").map_err(|_| "MONGO_URI must be set")?;
+    validate_mongo_uri(&mongo_uri)?;
+    let database = env::var("MONGO_DB_NAME").unwrap_or_else(|_| "openfoods".to_string());
+
+    let format = cli.format.unwrap_or_else(|| Format::detect(&cli.input));
+    let config = ImportConfig {
+        filter: Filter {
+            countries: if cli.all_countries {
+                Vec::new()
+            } else {
+                yoloeats_domain::tags::normalize_tags(&cli.countries)
+            },
+            min_completeness: cli.min_completeness,
+        },
+        batch_size: cli.batch_size as usize,
+        ..ImportConfig::default()
+    };
+    info!("Importing {} as {:?} with {:?}", cli.input, format, config);
+
+    let offsets = OffsetFile::new(&cli.offset_file);
+    if cli.restart {
+        offsets.clear().await?;
+    }
+    let mongo_settings = MongoSettings::from_env("off-import")?;
+    let mongo = create_mongo_client_with_settings(&mongo_uri, &mongo_settings).await?;
+    let products = mongo.database(&database).collection::<Product>("products");
+    let reader = source::open(&cli.input, &reqwest::Client::new()).await?;
+    let mut rejects = RejectsWriter::open(&cli.rejects).await?;
+
+    let stats = import(reader, format, &products, &offsets, &mut rejects, &config).await?;
+
+    print!("{}", stats);
+    if stats.rejected > 0 {
+        println!("Rejected rows: {}", cli.rejects.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,58 @@
+//! OpenFoodFacts row -> catalog `Product`: the catalog's [`product_catalog_service::off`]
+//! mapping, which its import endpoint shares.
+
+pub use product_catalog_service::off::{Filter, Mapped, SOURCE, SkipReason, map_row};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::row::{RawRow, RowParser};
+    use crate::source::Format;
+    use chrono::{DateTime, TimeZone, Utc};
+    use product_catalog_service::models::Product;
+
+    /// The same rows as the catalog's JSONL sample, as OFF's CSV export writes them.
+    const SAMPLE_CSV: &str = include_str!("../fixtures/off_sample.csv");
+    const SAMPLE_JSONL: &str =
+        include_str!("../../product-catalog-service/fixtures/off_sample.jsonl");
+
+    fn imported_at() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()
+    }
+
+    fn rows(format: Format, sample: &str) -> Vec<RawRow> {
+        let mut lines = sample.lines();
+        let mut parser = RowParser::new(format);
+        if parser.has_header() {
+            parser.read_header(lines.next().unwrap()).unwrap();
+        }
+        lines.map(|line| parser.parse(line).unwrap()).collect()
+    }
+
+    fn product(mapped: Mapped) -> Product {
+        match mapped {
+            Mapped::Product(product) => *product,
+            other => panic!("expected a product, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn csv_rows_map_like_jsonl_rows() {
+        let csv = rows(Format::Csv, SAMPLE_CSV);
+        let jsonl = rows(Format::Jsonl, SAMPLE_JSONL);
+        let from_csv = product(map_row(&csv[0], &Filter::default(), imported_at()));
+        let from_jsonl = product(map_row(&jsonl[0], &Filter::default(), imported_at()));
+
+        assert_eq!(from_csv.code, from_jsonl.code);
+        assert_eq!(from_csv.brands, from_jsonl.brands);
+        assert_eq!(from_csv.allergens_tags, from_jsonl.allergens_tags);
+        assert_eq!(from_csv.countries, from_jsonl.countries);
+        assert_eq!(from_csv.nutrition_grade_fr, from_jsonl.nutrition_grade_fr);
+        assert_eq!(from_csv.created_at, from_jsonl.created_at);
+
+        let bread = product(map_row(&csv[1], &Filter::default(), imported_at()));
+        assert_eq!(bread.main_category.as_deref(), Some("en:wholemeal-breads"));
+        assert_eq!(bread.allergens_tags, vec!["en:gluten", "en:sesame-seeds"]);
+        assert_eq!(bread.traces_tags, None);
+    }
+}
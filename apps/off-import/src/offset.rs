@@ -0,0 +1,82 @@
+use crate::errors::{ImportError, Result};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// How many dump lines (header included) are already imported, kept in a small text
+/// file so an interrupted import resumes where it stopped instead of starting over.
+#[derive(Debug, Clone)]
+pub struct OffsetFile {
+    path: PathBuf,
+}
+
+impl OffsetFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        OffsetFile { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 0 when the file doesn't exist yet.
+    pub async fn load(&self) -> Result<u64> {
+        match fs::read_to_string(&self.path).await {
+            Ok(contents) => contents.trim().parse().map_err(|_| ImportError::Offset {
+                path: self.path.display().to_string(),
+                reason: format!("expected a line count, found '{}'", contents.trim()),
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Written to a temporary file and renamed over the old one, so a crash mid-write
+    /// never leaves a truncated offset behind.
+    pub async fn save(&self, lines: u64) -> Result<()> {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        fs::write(&tmp, lines.to_string()).await?;
+        fs::rename(&tmp, &self.path).await?;
+        Ok(())
+    }
+
+    pub async fn clear(&self) -> Result<()> {
+        match fs::remove_file(&self.path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("off-import-{}-{}", std::process::id(), name))
+    }
+
+    #[tokio::test]
+    async fn round_trips_and_starts_at_zero() {
+        let offsets = OffsetFile::new(temp_path("offset"));
+        offsets.clear().await.unwrap();
+        assert_eq!(offsets.load().await.unwrap(), 0);
+
+        offsets.save(1500).await.unwrap();
+        assert_eq!(offsets.load().await.unwrap(), 1500);
+        offsets.save(2000).await.unwrap();
+        assert_eq!(offsets.load().await.unwrap(), 2000);
+
+        offsets.clear().await.unwrap();
+        assert_eq!(offsets.load().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn garbage_is_an_error_not_a_restart() {
+        let path = temp_path("garbage");
+        fs::write(&path, "lots").await.unwrap();
+        let err = OffsetFile::new(&path).load().await.unwrap_err();
+        assert!(matches!(err, ImportError::Offset { .. }), "{}", err);
+        fs::remove_file(&path).await.unwrap();
+    }
+}
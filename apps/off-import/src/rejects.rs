@@ -0,0 +1,95 @@
+use crate::errors::Result;
+use serde::Serialize;
+use std::path::Path;
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncWriteExt, BufWriter},
+};
+
+/// One line of the rejects file.
+#[derive(Debug, Serialize)]
+pub struct Reject<'a> {
+    pub line: u64,
+    pub reason: &'a str,
+    pub raw: &'a str,
+}
+
+/// Appends rejected rows as JSON lines. Appending keeps rejects from earlier runs of a
+/// resumed import; rows between the last saved offset and a crash can appear twice.
+pub struct RejectsWriter {
+    out: BufWriter<File>,
+    count: u64,
+}
+
+impl RejectsWriter {
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        Ok(RejectsWriter {
+            out: BufWriter::new(file),
+            count: 0,
+        })
+    }
+
+    pub async fn write(&mut self, reject: &Reject<'_>) -> Result<()> {
+        let mut line = serde_json::to_vec(reject).expect("reject serializes");
+        line.push(b'\n');
+        self.out.write_all(&line).await?;
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Rejects written by this writer.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Called with every offset save, so the file never lags the offset.
+    pub async fn flush(&mut self) -> Result<()> {
+        self.out.flush().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn appends_json_lines() {
+        let path = std::env::temp_dir().join(format!("off-import-{}-rejects", std::process::id()));
+        let _ = tokio::fs::remove_file(&path).await;
+
+        for line in [3, 7] {
+            let mut rejects = RejectsWriter::open(&path).await.unwrap();
+            rejects
+                .write(&Reject {
+                    line,
+                    reason: "missing code",
+                    raw: "{\"product_name\": \"x\"}",
+                })
+                .await
+                .unwrap();
+            rejects.flush().await.unwrap();
+            assert_eq!(rejects.count(), 1);
+        }
+
+        let contents = tokio::fs::read_to_string(&path).await.unwrap();
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                json!({"line": 3, "reason": "missing code", "raw": "{\"product_name\": \"x\"}"}),
+                json!({"line": 7, "reason": "missing code", "raw": "{\"product_name\": \"x\"}"}),
+            ]
+        );
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+}
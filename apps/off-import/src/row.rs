@@ -0,0 +1,111 @@
+use crate::errors::{ImportError, Result};
+use crate::source::Format;
+pub use product_catalog_service::off::RawRow;
+use product_catalog_service::off::parse_jsonl;
+use serde_json::Value;
+
+/// Splits dump lines into [`RawRow`]s. CSV needs its header first.
+#[derive(Debug, Clone)]
+pub struct RowParser {
+    format: Format,
+    columns: Vec<String>,
+}
+
+impl RowParser {
+    pub fn new(format: Format) -> Self {
+        RowParser {
+            format,
+            columns: Vec::new(),
+        }
+    }
+
+    /// Whether the first line is a header to hand to [`RowParser::read_header`].
+    pub fn has_header(&self) -> bool {
+        self.format == Format::Csv
+    }
+
+    pub fn read_header(&mut self, line: &str) -> Result<()> {
+        let columns: Vec<String> = line.split('\t').map(|c| c.trim().to_string()).collect();
+        if !columns.iter().any(|c| c == "code") {
+            return Err(ImportError::Header(format!(
+                "no 'code' column among {} columns",
+                columns.len()
+            )));
+        }
+        self.columns = columns;
+        Ok(())
+    }
+
+    /// Parses one data line; the error is the reject reason.
+    pub fn parse(&self, line: &str) -> std::result::Result<RawRow, String> {
+        match self.format {
+            Format::Jsonl => parse_jsonl(line),
+            Format::Csv => {
+                let cells: Vec<&str> = line.split('\t').collect();
+                if cells.len() != self.columns.len() {
+                    return Err(format!(
+                        "expected {} columns, got {}",
+                        self.columns.len(),
+                        cells.len()
+                    ));
+                }
+                Ok(self
+                    .columns
+                    .iter()
+                    .zip(cells)
+                    .filter(|(_, cell)| !cell.trim().is_empty())
+                    .map(|(column, cell)| (column.clone(), Value::String(cell.to_string())))
+                    .collect())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jsonl_lines_must_be_objects() {
+        let parser = RowParser::new(Format::Jsonl);
+        assert_eq!(parser.parse(r#"{"code": "123"}"#).unwrap()["code"], "123");
+        assert_eq!(
+            parser.parse("[1, 2]").unwrap_err(),
+            "line is not a JSON object"
+        );
+        assert!(
+            parser
+                .parse(r#"{"code": "#)
+                .unwrap_err()
+                .starts_with("invalid JSON")
+        );
+    }
+
+    #[test]
+    fn csv_cells_are_keyed_by_header_and_blanks_dropped() {
+        let mut parser = RowParser::new(Format::Csv);
+        assert!(parser.has_header());
+        parser
+            .read_header("code\tproduct_name\tbrands_tags")
+            .unwrap();
+
+        let row = parser.parse("4000417025005\t\tritter-sport").unwrap();
+        assert_eq!(row["code"], "4000417025005");
+        assert_eq!(row["brands_tags"], "ritter-sport");
+        assert!(!row.contains_key("product_name"));
+
+        assert_eq!(
+            parser.parse("4000417025005\tonly two").unwrap_err(),
+            "expected 3 columns, got 2"
+        );
+    }
+
+    #[test]
+    fn csv_header_needs_a_code_column() {
+        let mut parser = RowParser::new(Format::Csv);
+        assert!(matches!(
+            parser.read_header("barcode\tname"),
+            Err(ImportError::Header(_))
+        ));
+    }
+}
@@ -0,0 +1,79 @@
+use crate::errors::Result;
+use async_compression::tokio::bufread::GzipDecoder;
+use futures::TryStreamExt;
+use std::{io, path::Path, pin::Pin};
+use tokio::{
+    fs::File,
+    io::{AsyncBufRead, BufReader},
+};
+use tokio_util::io::StreamReader;
+use tracing::info;
+
+pub type DumpReader = Pin<Box<dyn AsyncBufRead + Send>>;
+
+/// The two dump flavours OpenFoodFacts publishes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// `openfoodfacts-products.jsonl`: one product object per line.
+    Jsonl,
+    /// `en.openfoodfacts.org.products.csv`: tab-separated despite the name, with a
+    /// header row and comma-separated tag columns.
+    Csv,
+}
+
+impl Format {
+    /// Guesses from the file name, ignoring a trailing `.gz`; JSONL unless it says CSV.
+    pub fn detect(input: &str) -> Self {
+        let name = input.trim_end_matches(".gz").to_ascii_lowercase();
+        if name.ends_with(".csv") || name.ends_with(".tsv") {
+            Format::Csv
+        } else {
+            Format::Jsonl
+        }
+    }
+}
+
+fn is_url(input: &str) -> bool {
+    input.starts_with("http://") || input.starts_with("https://")
+}
+
+/// Opens `input` (a path or an http(s) URL) as a line reader, gunzipping `.gz` on the
+/// fly. Nothing is buffered beyond the current chunk, so multi-gigabyte dumps stream.
+pub async fn open(input: &str, client: &reqwest::Client) -> Result<DumpReader> {
+    let raw: DumpReader = if is_url(input) {
+        info!("Streaming dump from {}", input);
+        let response = client.get(input).send().await?.error_for_status()?;
+        let body = response.bytes_stream().map_err(io::Error::other);
+        Box::pin(StreamReader::new(body))
+    } else {
+        info!("Reading dump from {}", Path::new(input).display());
+        Box::pin(BufReader::new(File::open(input).await?))
+    };
+
+    if input.ends_with(".gz") {
+        let mut decoder = GzipDecoder::new(raw);
+        decoder.multiple_members(true);
+        Ok(Box::pin(BufReader::new(decoder)))
+    } else {
+        Ok(raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_follows_the_extension() {
+        assert_eq!(
+            Format::detect("openfoodfacts-products.jsonl.gz"),
+            Format::Jsonl
+        );
+        assert_eq!(
+            Format::detect("en.openfoodfacts.org.products.csv.gz"),
+            Format::Csv
+        );
+        assert_eq!(Format::detect("https://example.test/dump.TSV"), Format::Csv);
+        assert_eq!(Format::detect("dump"), Format::Jsonl);
+    }
+}
@@ -0,0 +1,644 @@
+//! Finds the declarable allergens a free-text ingredient list names, for products that
+//! come with `ingredients_text` but no `allergens_tags`.
+//!
+//! The text is read as OpenFoodFacts and labels write it, in English or German:
+//! `Zutaten: Weizenmehl (45 %), _Milch_, Emulgator: Sojalecithin. Kann Spuren von
+//! Nüssen enthalten.` Percentages and the brackets, colons and underscores around words
+//! are dropped, and sentences that only warn of traces are skipped, since traces are
+//! not ingredients.
+//!
+//! Matching is by whole word against [`RULES`], one entry per allergen. A keyword is a
+//! word or a phrase of words; `*` at either end of a word lets it be part of a longer
+//! word, which German compounds (`Vollmilchpulver`, `Hartweizengrieß`) need. Each rule
+//! also lists the phrases that contain a keyword without meaning the allergen, such as
+//! `coconut milk` or `cocoa butter` for milk.
+
+/// One allergen's keywords, and the phrases that contain a keyword without the
+/// product containing the allergen. Words are lowercase, with `ss` for `ß`.
+struct Rule {
+    allergen: &'static str,
+    keywords: &'static [&'static str],
+    except: &'static [&'static str],
+}
+
+/// What [`extract_allergens`] looks for, in [`KNOWN_ALLERGENS`] order.
+///
+/// Coconut is not a tree nut for labelling, so `coconut milk` is neither milk nor nuts,
+/// and plant drinks (`almond milk`, `Hafermilch`) count as their plant only. Cocoa,
+/// shea and nut butters are not dairy; `Milchsäure` (lactic acid) and its bacteria are
+/// not milk either. A bare `Eiweiß` is egg white, but `Milcheiweiß` is milk protein.
+///
+/// [`KNOWN_ALLERGENS`]: yoloeats_domain::tags::KNOWN_ALLERGENS
+const RULES: &[Rule] = &[
+    Rule {
+        allergen: "en:celery",
+        keywords: &["celery", "celeriac", "*sellerie*"],
+        except: &[],
+    },
+    Rule {
+        allergen: "en:crustaceans",
+        keywords: &[
+            "crustacean",
+            "crustaceans",
+            "shrimp",
+            "shrimps",
+            "prawn",
+            "prawns",
+            "crab",
+            "crabs",
+            "lobster",
+            "crayfish",
+            "langoustine",
+            "langoustines",
+            "scampi",
+            "krill",
+            "*garnele*",
+            "*krabbe*",
+            "*krebs*",
+            "hummer*",
+            "languste*",
+            "krebstiere",
+        ],
+        except: &[],
+    },
+    Rule {
+        allergen: "en:eggs",
+        keywords: &[
+            "egg",
+            "eggs",
+            "albumen",
+            "ei",
+            "eier*",
+            "*vollei*",
+            "*eigelb*",
+            "*eiklar*",
+            "*eidotter*",
+            "*hühnerei*",
+            "eiweiss",
+        ],
+        except: &[],
+    },
+    Rule {
+        allergen: "en:fish",
+        keywords: &[
+            "fish",
+            "salmon",
+            "tuna",
+            "cod",
+            "anchovy",
+            "anchovies",
+            "mackerel",
+            "herring",
+            "trout",
+            "pollock",
+            "haddock",
+            "*fisch*",
+            "*lachs*",
+            "sardelle*",
+            "sardine*",
+            "kabeljau*",
+            "*hering*",
+            "makrele*",
+            "forelle*",
+        ],
+        except: &["*tintenfisch*", "lachsschinken*"],
+    },
+    Rule {
+        allergen: "en:gluten",
+        keywords: &[
+            "*wheat", "rye", "barley", "oat", "oats", "oatmeal", "spelt", "kamut", "semolina",
+            "couscous", "bulgur", "malt", "*weizen*", "*roggen*", "*gerste*", "*hafer*",
+            "*dinkel*", "malz*", "*gluten",
+        ],
+        except: &["buckwheat", "*buchweizen*", "gluten free", "glutenfrei"],
+    },
+    Rule {
+        allergen: "en:lupin",
+        keywords: &["*lupin*"],
+        except: &[],
+    },
+    Rule {
+        allergen: "en:milk",
+        keywords: &[
+            "milk",
+            "milkfat",
+            "lactose",
+            "whey",
+            "casein",
+            "caseinate",
+            "caseinates",
+            "cream",
+            "ghee",
+            "cheese",
+            "yogurt",
+            "yoghurt",
+            "kefir",
+            "skyr",
+            "curd",
+            "*milch*",
+            "laktose",
+            "*molke*",
+            "*sahne*",
+            "*rahm*",
+            "butter*",
+            "*käse*",
+            "*joghurt*",
+            "*quark*",
+            "*kasein*",
+        ],
+        except: &[
+            "coconut milk",
+            "coconut cream",
+            "almond milk",
+            "oat milk",
+            "rice milk",
+            "soy milk",
+            "soya milk",
+            "cocoa butter",
+            "shea butter",
+            "peanut butter",
+            "nut butter",
+            "almond butter",
+            "cream of tartar",
+            "bean curd",
+            "kokosmilch*",
+            "kokosnussmilch*",
+            "mandelmilch*",
+            "hafermilch*",
+            "reismilch*",
+            "butternut*",
+            "sojamilch*",
+            "milchsäure*",
+            "kokosrahm*",
+        ],
+    },
+    Rule {
+        allergen: "en:molluscs",
+        keywords: &[
+            "mollusc",
+            "molluscs",
+            "squid",
+            "octopus",
+            "mussel",
+            "mussels",
+            "oyster",
+            "oysters",
+            "clam",
+            "clams",
+            "scallop",
+            "scallops",
+            "*muschel*",
+            "*tintenfisch*",
+            "auster*",
+            "kalmar*",
+            "oktopus*",
+            "weichtiere",
+        ],
+        except: &[],
+    },
+    Rule {
+        allergen: "en:mustard",
+        keywords: &["mustard", "*senf*"],
+        except: &[],
+    },
+    Rule {
+        allergen: "en:nuts",
+        keywords: &[
+            "nuts",
+            "almond",
+            "almonds",
+            "hazelnut",
+            "hazelnuts",
+            "walnut",
+            "walnuts",
+            "pecan",
+            "pecans",
+            "pistachio",
+            "pistachios",
+            "brazil nut",
+            "brazil nuts",
+            "*mandel*",
+            "*haselnuss*",
+            "*haselnüsse*",
+            "*walnuss*",
+            "*walnüsse*",
+            "*cashew*",
+            "*pistazie*",
+            "*pekannuss*",
+            "*pekannüsse*",
+            "*macadamia*",
+            "*paranuss*",
+            "*paranüsse*",
+            "schalenfrüchte",
+        ],
+        except: &["tiger nuts"],
+    },
+    Rule {
+        allergen: "en:peanuts",
+        keywords: &[
+            "peanut",
+            "peanuts",
+            "groundnut",
+            "groundnuts",
+            "*erdnuss*",
+            "*erdnüsse*",
+        ],
+        except: &[],
+    },
+    Rule {
+        allergen: "en:sesame-seeds",
+        keywords: &["sesame", "tahini", "tahina", "*sesam*"],
+        except: &[],
+    },
+    Rule {
+        allergen: "en:soybeans",
+        keywords: &[
+            "soy",
+            "soya",
+            "soybean",
+            "soybeans",
+            "soyabean",
+            "soyabeans",
+            "tofu",
+            "bean curd",
+            "edamame",
+            "*soja*",
+        ],
+        except: &[],
+    },
+    Rule {
+        allergen: "en:sulphur-dioxide-and-sulphites",
+        keywords: &[
+            "*sulfit*",
+            "*sulphit*",
+            "sulfur dioxide",
+            "sulphur dioxide",
+            "*schwefeldioxid*",
+            "e220",
+            "e221",
+            "e222",
+            "e223",
+            "e224",
+            "e226",
+            "e227",
+            "e228",
+            "e 220",
+            "e 221",
+            "e 222",
+            "e 223",
+            "e 224",
+            "e 226",
+            "e 227",
+            "e 228",
+        ],
+        except: &[],
+    },
+];
+
+/// Phrases that mark a sentence as a warning of traces rather than ingredients.
+const TRACE_MARKERS: &[&str] = &[
+    "may contain",
+    "may also contain",
+    "can contain",
+    "trace",
+    "traces",
+    "spuren",
+];
+
+/// The `allergens_tags` the ingredients in `text` stand for, in [`RULES`] order. Empty
+/// for a text naming none.
+pub fn extract_allergens(text: &str) -> Vec<String> {
+    let runs: Vec<Vec<String>> = sentences(text)
+        .iter()
+        .map(|sentence| segments(sentence))
+        .filter(|runs| {
+            !runs.iter().any(|words| {
+                TRACE_MARKERS
+                    .iter()
+                    .any(|marker| !phrase_matches(words, marker).is_empty())
+            })
+        })
+        .flatten()
+        .collect();
+    RULES
+        .iter()
+        .filter(|rule| runs.iter().any(|words| names(rule, words)))
+        .map(|rule| rule.allergen.to_string())
+        .collect()
+}
+
+/// `text` lowercased, with `ß` as `ss` and percentages removed, split after every `.`,
+/// `!` or `?` that ends a sentence.
+fn sentences(text: &str) -> Vec<String> {
+    let text = without_percentages(&text.to_lowercase().replace('ß', "ss"));
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let ends =
+            matches!(c, '.' | '!' | '?') && chars.peek().is_none_or(|next| next.is_whitespace());
+        if ends {
+            sentences.push(std::mem::take(&mut current));
+        } else {
+            current.push(c);
+        }
+    }
+    sentences.push(current);
+    sentences
+}
+
+/// `text` without amounts such as `45%`, `4,5 %` or `12.5%`.
+fn without_percentages(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut kept = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i].is_ascii_digit() {
+            let mut end = i;
+            while end < chars.len()
+                && (chars[end].is_ascii_digit() || matches!(chars[end], '.' | ','))
+            {
+                end += 1;
+            }
+            let mut percent = end;
+            while percent < chars.len() && chars[percent] == ' ' {
+                percent += 1;
+            }
+            if chars.get(percent) == Some(&'%') {
+                i = percent + 1;
+                continue;
+            }
+            kept.extend(&chars[i..end]);
+            i = end;
+        } else {
+            kept.push(chars[i]);
+            i += 1;
+        }
+    }
+    kept
+}
+
+/// The runs of words in a sentence that phrases may span: brackets, commas, colons and
+/// other punctuation end a run, while hyphens, underscores, asterisks and apostrophes
+/// only separate words.
+fn segments(sentence: &str) -> Vec<Vec<String>> {
+    sentence
+        .split(|c: char| {
+            !c.is_alphanumeric() && !c.is_whitespace() && !matches!(c, '-' | '_' | '*' | '\'' | '’')
+        })
+        .map(|segment| {
+            segment
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|word| !word.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })
+        .filter(|words| !words.is_empty())
+        .collect()
+}
+
+/// Whether `words` hold one of the rule's keywords outside all of its exceptions.
+fn names(rule: &Rule, words: &[String]) -> bool {
+    let mut excepted = vec![false; words.len()];
+    for phrase in rule.except {
+        for (start, len) in phrase_matches(words, phrase) {
+            excepted[start..start + len].fill(true);
+        }
+    }
+    rule.keywords.iter().any(|keyword| {
+        phrase_matches(words, keyword)
+            .into_iter()
+            .any(|(start, len)| !excepted[start..start + len].iter().all(|&e| e))
+    })
+}
+
+/// Where `phrase` occurs in `words`, as start and length.
+fn phrase_matches(words: &[String], phrase: &str) -> Vec<(usize, usize)> {
+    let patterns: Vec<&str> = phrase.split(' ').collect();
+    if patterns.len() > words.len() {
+        return Vec::new();
+    }
+    (0..=words.len() - patterns.len())
+        .filter(|&start| {
+            patterns
+                .iter()
+                .zip(&words[start..])
+                .all(|(pattern, word)| word_matches(pattern, word))
+        })
+        .map(|start| (start, patterns.len()))
+        .collect()
+}
+
+/// Whether `word` is `pattern`, or contains, starts or ends with it where `pattern` has
+/// a `*` on the other side.
+fn word_matches(pattern: &str, word: &str) -> bool {
+    match (pattern.strip_prefix('*'), pattern.strip_suffix('*')) {
+        (Some(_), Some(_)) => word.contains(&pattern[1..pattern.len() - 1]),
+        (Some(suffix), None) => word.ends_with(suffix),
+        (None, Some(prefix)) => word.starts_with(prefix),
+        (None, None) => word == pattern,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yoloeats_domain::tags::KNOWN_ALLERGENS;
+
+    fn allergens(text: &str) -> Vec<String> {
+        extract_allergens(text)
+    }
+
+    #[test]
+    fn english_ingredient_lists() {
+        assert_eq!(
+            allergens(
+                "Sugar, palm oil, hazelnuts (13%), skimmed milk powder (8.7%), fat-reduced \
+                 cocoa (7.4%), emulsifier: lecithins (soya), vanillin."
+            ),
+            ["en:milk", "en:nuts", "en:soybeans"]
+        );
+        assert_eq!(
+            allergens("Wheat flour, water, whole egg, salt, mustard seeds, celery."),
+            ["en:celery", "en:eggs", "en:gluten", "en:mustard"]
+        );
+        assert_eq!(allergens("Tuna, sunflower oil, salt"), ["en:fish"]);
+    }
+
+    #[test]
+    fn german_ingredient_lists_and_compounds() {
+        assert_eq!(
+            allergens(
+                "Zutaten: Zucker, Kakaobutter, Vollmilchpulver, Haselnussmasse, \
+                 Süßmolkenpulver, Emulgator Sojalecithine."
+            ),
+            ["en:milk", "en:nuts", "en:soybeans"]
+        );
+        assert_eq!(
+            allergens("Hartweizengrieß, Vollei, Speisesalz, Senfsaat, Knollensellerie"),
+            ["en:celery", "en:eggs", "en:gluten", "en:mustard"]
+        );
+        assert_eq!(
+            allergens("Roggenmehl, Gerstenmalzextrakt, Sesam, Erdnüsse, Garnelen"),
+            [
+                "en:crustaceans",
+                "en:gluten",
+                "en:peanuts",
+                "en:sesame-seeds"
+            ]
+        );
+        assert_eq!(
+            allergens("Trockenobst, Konservierungsstoff: Natriummetabisulfit"),
+            ["en:sulphur-dioxide-and-sulphites"]
+        );
+    }
+
+    #[test]
+    fn brackets_percentages_and_emphasis_are_noise() {
+        for text in [
+            "Weizenmehl (45 %)",
+            "Weizenmehl(45%)",
+            "WEIZENMEHL [4,5%]",
+            "_Weizen_mehl",
+            "*wheat* flour 12.5%",
+            "flour (wheat)",
+        ] {
+            assert_eq!(allergens(text), ["en:gluten"], "{}", text);
+        }
+        assert_eq!(allergens("_MILK_ chocolate"), ["en:milk"]);
+        assert_eq!(allergens("sugar 3.5%, water"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn words_only_match_whole() {
+        for text in [
+            "eggplant, butternut squash",
+            "maltodextrin, dextrose",
+            "nutmeg, coconut, tiger nuts",
+            "cocoa mass, cornflour, cottonseed oil",
+            "rice, lactic acid, sulphate",
+            "Muskatnuss, Kokosnuss, Eisen",
+            "Reis, Meersalz, Speiseeisbasis",
+        ] {
+            assert_eq!(allergens(text), Vec::<String>::new(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn coconut_milk_and_other_dairy_free_look_alikes() {
+        for text in [
+            "coconut milk (60%), water",
+            "Kokosmilch, Wasser",
+            "coconut cream",
+            "cocoa butter, sugar",
+            "Kakaobutter, Sheabutter",
+            "rice milk",
+            "Säuerungsmittel: Milchsäure",
+            "Milchsäurebakterien",
+            "cream of tartar",
+        ] {
+            assert!(
+                !allergens(text).contains(&"en:milk".to_string()),
+                "{}: {:?}",
+                text,
+                allergens(text)
+            );
+        }
+        assert_eq!(allergens("coconut milk, milk"), ["en:milk"]);
+        assert_eq!(allergens("coconut, milk"), ["en:milk"]);
+        assert_eq!(allergens("Kokosmilch, Buttermilch"), ["en:milk"]);
+        assert_eq!(allergens("milk chocolate (cocoa butter)"), ["en:milk"]);
+    }
+
+    #[test]
+    fn plant_drinks_count_as_their_plant() {
+        assert_eq!(allergens("almond milk"), ["en:nuts"]);
+        assert_eq!(allergens("Mandelmilch"), ["en:nuts"]);
+        assert_eq!(allergens("soy milk"), ["en:soybeans"]);
+        assert_eq!(allergens("Hafermilch"), ["en:gluten"]);
+        assert_eq!(allergens("oat milk"), ["en:gluten"]);
+        assert_eq!(allergens("peanut butter"), ["en:peanuts"]);
+        assert_eq!(allergens("bean curd"), ["en:soybeans"]);
+    }
+
+    #[test]
+    fn look_alikes_of_other_allergens() {
+        assert_eq!(allergens("buckwheat flour"), Vec::<String>::new());
+        assert_eq!(allergens("Buchweizenmehl"), Vec::<String>::new());
+        assert_eq!(allergens("gluten-free oats"), ["en:gluten"]);
+        assert_eq!(allergens("glutenfrei, Maisstärke"), Vec::<String>::new());
+        assert_eq!(allergens("Weizengluten"), ["en:gluten"]);
+        assert_eq!(allergens("Tintenfisch"), ["en:molluscs"]);
+        assert_eq!(allergens("Tintenfisch, Lachs"), ["en:fish", "en:molluscs"]);
+        assert_eq!(allergens("Lachsschinken"), Vec::<String>::new());
+        assert_eq!(allergens("Erdnussbutter"), ["en:peanuts"]);
+        assert_eq!(allergens("Milcheiweiß"), ["en:milk"]);
+        assert_eq!(allergens("Hühnereiweiß"), ["en:eggs"]);
+        assert_eq!(allergens("Eiweiß, Zucker"), ["en:eggs"]);
+        assert_eq!(allergens("Sojaeiweiß"), ["en:soybeans"]);
+    }
+
+    #[test]
+    fn warnings_of_traces_are_not_ingredients() {
+        assert_eq!(
+            allergens("Sugar, cocoa mass, milk. May contain traces of nuts and peanuts."),
+            ["en:milk"]
+        );
+        assert_eq!(
+            allergens("Zucker, Kakaomasse. Kann Spuren von Haselnüssen und Milch enthalten."),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            allergens("Wheat flour, sugar. Can contain sesame"),
+            ["en:gluten"]
+        );
+    }
+
+    #[test]
+    fn e_numbers_name_sulphites() {
+        assert_eq!(
+            allergens("grape must, preservative: E220"),
+            ["en:sulphur-dioxide-and-sulphites"]
+        );
+        assert_eq!(
+            allergens("Wein, Schwefeldioxid"),
+            ["en:sulphur-dioxide-and-sulphites"]
+        );
+        assert_eq!(
+            allergens("antioxidant (e 224)"),
+            ["en:sulphur-dioxide-and-sulphites"]
+        );
+        assert_eq!(allergens("E202, E330"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn nothing_to_find() {
+        assert!(allergens("").is_empty());
+        assert!(allergens("  .,;()% ").is_empty());
+        assert!(allergens("Water, sugar, citric acid, natural flavouring").is_empty());
+    }
+
+    #[test]
+    fn rules_name_known_allergens_in_words_the_text_can_have() {
+        let allergens: Vec<&str> = RULES.iter().map(|rule| rule.allergen).collect();
+        assert_eq!(allergens, KNOWN_ALLERGENS);
+        for rule in RULES {
+            for phrase in rule.keywords.iter().chain(rule.except) {
+                assert_eq!(
+                    *phrase,
+                    phrase.to_lowercase().replace('ß', "ss"),
+                    "{}",
+                    phrase
+                );
+                for word in phrase.split(' ') {
+                    let word = word.trim_matches('*');
+                    assert!(
+                        !word.is_empty() && word.chars().all(char::is_alphanumeric),
+                        "{}",
+                        phrase
+                    );
+                }
+            }
+        }
+    }
+}
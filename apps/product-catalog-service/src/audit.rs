@@ -0,0 +1,508 @@
+//! Who changed which product how: an entry in the `product_audit` collection for every
+//! create, update, patch and delete through the API, served newest first by
+//! `/api/v1/products/{id}/history`.
+//!
+//! The actor is the subject of the caller's bearer token, which the catalog checks on
+//! every write; see [`crate::auth`]. Recording is best effort: a failed write is logged
+//! and the product change still answers with success. Bulk imports are not audited.
+
+use crate::{
+    errors::{Result, ServiceError},
+    models::Product,
+    state::AppState,
+};
+use async_trait::async_trait;
+use axum::{
+    Json,
+    extract::{FromRequestParts, Path, State},
+    http::request::Parts,
+};
+use bson::{doc, oid::ObjectId};
+use chrono::{DateTime, Utc};
+use futures::stream::TryStreamExt;
+use mongodb::{Collection, Database, options::FindOptions};
+use rust_database_clients::serde_helpers::chrono_datetime_as_rfc3339_or_bson;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::BTreeSet,
+    convert::Infallible,
+    sync::{Arc, Mutex},
+};
+use tracing::{debug, error, info, instrument, warn};
+use utoipa::ToSchema;
+use yoloeats_auth::AuthContext;
+use yoloeats_domain::ErrorBody;
+use yoloeats_pagination::{Page, PageLimit, PageParams};
+
+/// Bookkeeping every write touches; left out of the diff.
+const UNAUDITED_FIELDS: [&str; 3] = ["_id", "created_datetime", "last_modified_datetime"];
+
+/// Page sizes for [`get_product_history`].
+pub struct HistoryPageLimit;
+
+impl PageLimit for HistoryPageLimit {
+    const DEFAULT: u64 = 20;
+    const MAX: u64 = 100;
+}
+
+/// Where the next history page starts: after the oldest entry of the previous one.
+#[derive(Debug, Serialize, Deserialize)]
+struct HistoryCursor {
+    before_id: ObjectId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditAction {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// One changed field, by its stored name. A field the product did not have is `null`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntry {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub id: Option<ObjectId>,
+    #[schema(value_type = String)]
+    pub product_id: ObjectId,
+    pub code: String,
+    pub action: AuditAction,
+    pub changes: Vec<FieldChange>,
+    /// `None` when the request was not authenticated.
+    pub actor: Option<String>,
+    #[serde(with = "chrono_datetime_as_rfc3339_or_bson")]
+    pub at: DateTime<Utc>,
+}
+
+/// The subject of the [`AuthContext`] the auth layer handed the request, if it has one.
+/// Nothing the caller says about itself is taken.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Actor(pub Option<String>);
+
+impl<S> FromRequestParts<S> for Actor
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        Ok(Actor(
+            parts
+                .extensions
+                .get::<AuthContext>()
+                .map(|context| context.subject.clone()),
+        ))
+    }
+}
+
+/// The fields that differ between two versions of a product, in name order. `None` is
+/// the side of a create or delete, on which every field is `null`.
+pub fn diff(before: Option<&Product>, after: Option<&Product>) -> Vec<FieldChange> {
+    let before = fields(before);
+    let after = fields(after);
+    let names: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    names
+        .into_iter()
+        .filter(|name| !UNAUDITED_FIELDS.contains(&name.as_str()))
+        .filter_map(|name| {
+            let old = before.get(name).cloned().unwrap_or(Value::Null);
+            let new = after.get(name).cloned().unwrap_or(Value::Null);
+            (old != new).then(|| FieldChange {
+                field: name.clone(),
+                old,
+                new,
+            })
+        })
+        .collect()
+}
+
+fn fields(product: Option<&Product>) -> Map<String, Value> {
+    match product.map(serde_json::to_value) {
+        Some(Ok(Value::Object(fields))) => fields,
+        Some(Ok(_)) | None => Map::new(),
+        Some(Err(e)) => {
+            error!("Failed to serialize product for its audit diff: {}", e);
+            Map::new()
+        }
+    }
+}
+
+/// What a change from `before` to `after` did, and to which product: the one after it,
+/// or the one deleted.
+pub(crate) fn action<'a>(
+    before: Option<&'a Product>,
+    after: Option<&'a Product>,
+) -> Option<(AuditAction, &'a Product)> {
+    match (before, after) {
+        (None, Some(product)) => Some((AuditAction::Created, product)),
+        (Some(_), Some(product)) => Some((AuditAction::Updated, product)),
+        (Some(product), None) => Some((AuditAction::Deleted, product)),
+        (None, None) => None,
+    }
+}
+
+/// Records a change from `before` to `after`, at least one of which is the product. An
+/// update that changed nothing is not recorded, and a failed write is only logged.
+pub(crate) async fn record(
+    state: &AppState,
+    actor: &Actor,
+    before: Option<&Product>,
+    after: Option<&Product>,
+) {
+    let Some((action, product)) = action(before, after) else {
+        return;
+    };
+    let Some(product_id) = product.id else {
+        warn!(code = %product.code, "Not auditing a product without an id");
+        return;
+    };
+    let changes = diff(before, after);
+    if action == AuditAction::Updated && changes.is_empty() {
+        debug!(id = %product_id, "Update changed no audited field; not recording it");
+        return;
+    }
+    let entry = AuditEntry {
+        id: None,
+        product_id,
+        code: product.code.clone(),
+        action,
+        changes,
+        actor: actor.0.clone(),
+        at: Utc::now(),
+    };
+    if let Err(e) = state.audit.record(entry).await {
+        warn!(id = %product_id, ?action, "Failed to record product audit entry: {}", e);
+    }
+}
+
+/// Where a history page starts. Entries are ordered newest first by `_id`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HistoryFrom {
+    Offset(u64),
+    /// At the newest entry older than this one.
+    Before(ObjectId),
+}
+
+#[async_trait]
+pub trait AuditLog: Send + Sync {
+    async fn record(&self, entry: AuditEntry) -> Result<()>;
+
+    /// Up to `limit` of the product's entries starting at `from`, newest first.
+    async fn history(
+        &self,
+        product_id: ObjectId,
+        from: HistoryFrom,
+        limit: u64,
+    ) -> Result<Vec<AuditEntry>>;
+
+    /// How many entries the product has in all.
+    async fn count(&self, product_id: ObjectId) -> Result<u64>;
+}
+
+pub struct MongoAuditLog {
+    collection: Collection<AuditEntry>,
+}
+
+impl MongoAuditLog {
+    pub fn new(db: &Database) -> Self {
+        MongoAuditLog {
+            collection: db.collection("product_audit"),
+        }
+    }
+}
+
+#[async_trait]
+impl AuditLog for MongoAuditLog {
+    async fn record(&self, entry: AuditEntry) -> Result<()> {
+        self.collection.insert_one(&entry).await?;
+        Ok(())
+    }
+
+    async fn history(
+        &self,
+        product_id: ObjectId,
+        from: HistoryFrom,
+        limit: u64,
+    ) -> Result<Vec<AuditEntry>> {
+        let mut filter = doc! { "product_id": product_id };
+        let mut find_options = FindOptions::builder()
+            .limit(limit as i64)
+            .sort(doc! { "_id": -1 })
+            .build();
+        match from {
+            HistoryFrom::Offset(skip) => find_options.skip = Some(skip),
+            HistoryFrom::Before(before_id) => {
+                filter.insert("_id", doc! { "$lt": before_id });
+            }
+        }
+        let cursor = self
+            .collection
+            .find(filter)
+            .with_options(find_options)
+            .await
+            .map_err(|e| {
+                error!(product_id = %product_id, "MongoDB find on product_audit failed: {}", e);
+                ServiceError::MongoDb(e)
+            })?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn count(&self, product_id: ObjectId) -> Result<u64> {
+        Ok(self
+            .collection
+            .count_documents(doc! { "product_id": product_id })
+            .await?)
+    }
+}
+
+/// Entries in recording order. Clones share the same list.
+#[derive(Clone, Default)]
+pub struct MemoryAuditLog {
+    entries: Arc<Mutex<Vec<AuditEntry>>>,
+}
+
+#[async_trait]
+impl AuditLog for MemoryAuditLog {
+    async fn record(&self, mut entry: AuditEntry) -> Result<()> {
+        entry.id = Some(ObjectId::new());
+        self.entries.lock().unwrap().push(entry);
+        Ok(())
+    }
+
+    async fn history(
+        &self,
+        product_id: ObjectId,
+        from: HistoryFrom,
+        limit: u64,
+    ) -> Result<Vec<AuditEntry>> {
+        let entries = self.entries.lock().unwrap();
+        let newest_first = entries
+            .iter()
+            .rev()
+            .filter(|entry| entry.product_id == product_id);
+        let page: Vec<AuditEntry> = match from {
+            HistoryFrom::Offset(skip) => newest_first
+                .skip(skip as usize)
+                .take(limit as usize)
+                .cloned()
+                .collect(),
+            HistoryFrom::Before(before_id) => newest_first
+                .filter(|entry| entry.id.is_some_and(|id| id < before_id))
+                .take(limit as usize)
+                .cloned()
+                .collect(),
+        };
+        Ok(page)
+    }
+
+    async fn count(&self, product_id: ObjectId) -> Result<u64> {
+        let entries = self.entries.lock().unwrap();
+        Ok(entries
+            .iter()
+            .filter(|entry| entry.product_id == product_id)
+            .count() as u64)
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/products/{id}/history",
+    tag = "v1",
+    params(
+        ("id" = String, Path, description = "The product's ObjectId."),
+        PageParams<HistoryPageLimit>,
+    ),
+    responses(
+        (status = 200, description = "One page of the product's changes, newest first.", body = Page<AuditEntry>),
+        (status = 400, description = "An invalid id or cursor.", body = ErrorBody),
+        (status = 500, description = "MongoDB failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state), fields(id = %id_str))]
+pub async fn get_product_history(
+    State(state): State<Arc<AppState>>,
+    Path(id_str): Path<String>,
+    page: PageParams<HistoryPageLimit>,
+) -> Result<Json<Page<AuditEntry>>> {
+    find_history(&state, &id_str, &page).await.map(Json)
+}
+
+/// One page of the history of the product `id_str` names, shared by every API version.
+/// Deleted products keep their history; an id that never had any gets an empty page.
+pub async fn find_history(
+    state: &AppState,
+    id_str: &str,
+    page: &PageParams<HistoryPageLimit>,
+) -> Result<Page<AuditEntry>> {
+    let product_id = ObjectId::parse_str(id_str).map_err(|e| {
+        error!("Invalid ObjectId format '{}': {}", id_str, e);
+        ServiceError::InvalidProductId(format!("Invalid product ID format: {}", id_str))
+    })?;
+    let from = page
+        .position::<HistoryCursor>(&state.cursor_codec)?
+        .map_or(HistoryFrom::Offset(page.offset), |cursor| {
+            HistoryFrom::Before(cursor.before_id)
+        });
+    debug!(
+        "Product history page: limit={}, from={:?}",
+        page.limit, from
+    );
+
+    let entries = state
+        .audit
+        .history(product_id, from, page.limit + 1)
+        .await?;
+    let entries = Page::overfetched(entries, page.limit, |oldest| {
+        let before_id = oldest.id?;
+        Some(state.cursor_codec.encode(&HistoryCursor { before_id }))
+    });
+    info!(id = %product_id, "Returning {} history entries", entries.items.len());
+
+    let total = state.audit.count(product_id).await?;
+    Ok(entries.with_total(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use yoloeats_domain::fixtures::ProductFixture;
+
+    fn entry(product_id: ObjectId, action: AuditAction) -> AuditEntry {
+        AuditEntry {
+            id: None,
+            product_id,
+            code: "123".to_string(),
+            action,
+            changes: Vec::new(),
+            actor: None,
+            at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn diff_lists_changed_fields_in_name_order() {
+        let before: Product = ProductFixture::new("123")
+            .named("Crisps")
+            .with_allergens(["en:milk"])
+            .build();
+        let mut after = before.clone();
+        after.product_name = Some("Salted crisps".to_string());
+        after.allergens_tags = Vec::new();
+        after.quantity = Some("150 g".to_string());
+        after.last_modified_at = before.last_modified_at + chrono::Duration::seconds(5);
+
+        assert_eq!(
+            diff(Some(&before), Some(&after)),
+            [
+                FieldChange {
+                    field: "allergens_tags".to_string(),
+                    old: json!(["en:milk"]),
+                    new: json!([]),
+                },
+                FieldChange {
+                    field: "product_name".to_string(),
+                    old: json!("Crisps"),
+                    new: json!("Salted crisps"),
+                },
+                FieldChange {
+                    field: "quantity".to_string(),
+                    old: Value::Null,
+                    new: json!("150 g"),
+                },
+            ]
+        );
+        assert!(diff(Some(&before), Some(&before)).is_empty());
+    }
+
+    #[test]
+    fn diff_of_a_create_or_delete_has_every_set_field() {
+        let product: Product = ProductFixture::new("123").named("Crisps").build();
+        let created = diff(None, Some(&product));
+        let fields: Vec<&str> = created.iter().map(|c| c.field.as_str()).collect();
+        assert!(fields.contains(&"code"));
+        assert!(fields.contains(&"product_name"));
+        assert!(!fields.iter().any(|f| UNAUDITED_FIELDS.contains(f)));
+        assert!(created.iter().all(|c| c.old.is_null() && !c.new.is_null()));
+
+        let deleted = diff(Some(&product), None);
+        assert_eq!(deleted.len(), created.len());
+        assert!(deleted.iter().all(|c| c.new.is_null()));
+    }
+
+    #[tokio::test]
+    async fn memory_history_is_newest_first_and_pages() {
+        let log = MemoryAuditLog::default();
+        let (product, other) = (ObjectId::new(), ObjectId::new());
+        for action in [
+            AuditAction::Created,
+            AuditAction::Updated,
+            AuditAction::Deleted,
+        ] {
+            log.record(entry(product, action)).await.unwrap();
+        }
+        log.record(entry(other, AuditAction::Created))
+            .await
+            .unwrap();
+
+        let actions = |entries: Vec<AuditEntry>| -> Vec<AuditAction> {
+            entries.into_iter().map(|e| e.action).collect()
+        };
+        let first = log
+            .history(product, HistoryFrom::Offset(0), 2)
+            .await
+            .unwrap();
+        let before_id = first[1].id.unwrap();
+        assert_eq!(actions(first), [AuditAction::Deleted, AuditAction::Updated]);
+        assert_eq!(
+            actions(
+                log.history(product, HistoryFrom::Before(before_id), 2)
+                    .await
+                    .unwrap()
+            ),
+            [AuditAction::Created]
+        );
+        assert_eq!(
+            actions(
+                log.history(product, HistoryFrom::Offset(1), 5)
+                    .await
+                    .unwrap()
+            ),
+            [AuditAction::Updated, AuditAction::Created]
+        );
+        assert_eq!(log.count(product).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn actor_comes_from_the_authenticated_subject() {
+        let actor = |subject: Option<&'static str>| async move {
+            let mut request = axum::http::Request::builder().header("x-user-id", "someone-else");
+            if let Some(subject) = subject {
+                request = request.extension(AuthContext {
+                    subject: subject.to_string(),
+                    roles: Vec::new(),
+                });
+            }
+            let (mut parts, ()) = request.body(()).unwrap().into_parts();
+            Actor::from_request_parts(&mut parts, &()).await.unwrap()
+        };
+        assert_eq!(
+            actor(Some("moderator-7")).await.0.as_deref(),
+            Some("moderator-7")
+        );
+        assert_eq!(actor(None).await, Actor(None));
+    }
+}
@@ -0,0 +1,194 @@
+//! Who may call which catalog route. Writes (create, update, patch, delete and import)
+//! need a valid bearer token, checked by [`AuthLayer`]: it answers 401 in the shared
+//! error shape otherwise, and hands the handler the caller as an
+//! [`AuthContext`](yoloeats_auth::AuthContext). Reads, including the `POST` lookups, are
+//! public unless [`PUBLIC_READS_ENV`] turns that off.
+
+use crate::errors::{Result, ServiceError};
+use axum::routing::MethodRouter;
+use std::env;
+use yoloeats_auth::{AuthLayer, Authenticator};
+
+/// `true` (the default) serves the reads without a token.
+pub const PUBLIC_READS_ENV: &str = "CATALOG_PUBLIC_READS";
+
+pub fn public_reads_from_env() -> Result<bool> {
+    match env::var(PUBLIC_READS_ENV) {
+        Ok(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse::<bool>()
+            .map_err(|_| ServiceError::InvalidVariable(PUBLIC_READS_ENV.to_string())),
+        _ => Ok(true),
+    }
+}
+
+/// Puts routes behind [`AuthLayer`] as they are read or write routes.
+#[derive(Clone)]
+pub struct RouteAuth {
+    layer: AuthLayer,
+    public_reads: bool,
+}
+
+impl RouteAuth {
+    pub fn new(authenticator: Authenticator, public_reads: bool) -> Self {
+        RouteAuth {
+            layer: AuthLayer::new(authenticator),
+            public_reads,
+        }
+    }
+
+    pub fn write<S>(&self, route: MethodRouter<S>) -> MethodRouter<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        route.route_layer(self.layer.clone())
+    }
+
+    pub fn read<S>(&self, route: MethodRouter<S>) -> MethodRouter<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        if self.public_reads {
+            route
+        } else {
+            self.write(route)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Router,
+        body::{Body, to_bytes},
+        http::{Request, StatusCode, header::AUTHORIZATION},
+        routing::{get, post, put},
+    };
+    use jsonwebtoken::{EncodingKey, Header, encode};
+    use serde_json::{Value, json};
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use tower::ServiceExt;
+    use yoloeats_auth::{AuthConfig, AuthMode, Authenticated};
+
+    const SECRET: &str = "catalog-test-secret";
+    const AUDIENCE: &str = "yoloeats-catalog";
+
+    fn token(audience: &str, expires_in: i64) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        encode(
+            &Header::default(),
+            &json!({
+                "sub": "editor-1",
+                "roles": ["editor"],
+                "aud": audience,
+                "exp": now + expires_in,
+            }),
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    /// A product route shaped like the real ones: a read and the writes on one path, and a
+    /// read done by `POST`.
+    fn app(public_reads: bool) -> Router {
+        let auth = RouteAuth::new(
+            Authenticator::new(AuthConfig {
+                mode: AuthMode::Hs256 {
+                    secret: SECRET.to_string(),
+                },
+                audience: Some(AUDIENCE.to_string()),
+                issuer: None,
+            }),
+            public_reads,
+        );
+        Router::new()
+            .route(
+                "/products/{id}",
+                auth.read(get(|| async { "product" })).merge(
+                    auth.write(
+                        put(|Authenticated(ctx): Authenticated| async move { ctx.subject })
+                            .delete(|| async { StatusCode::NO_CONTENT }),
+                    ),
+                ),
+            )
+            .route("/products/batch", auth.read(post(|| async { "{}" })))
+    }
+
+    async fn call(
+        app: Router,
+        method: &str,
+        uri: &str,
+        token: Option<String>,
+    ) -> (StatusCode, String) {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            request = request.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let response = app
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn writes_need_a_valid_token_for_the_audience() {
+        let (status, body) =
+            call(app(true), "PUT", "/products/1", Some(token(AUDIENCE, 600))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "editor-1");
+        let (status, _) = call(
+            app(true),
+            "DELETE",
+            "/products/1",
+            Some(token(AUDIENCE, 600)),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        for bearer in [
+            None,
+            Some(token(AUDIENCE, -3600)),
+            Some(token("another-service", 600)),
+            Some("not-a-jwt".to_string()),
+        ] {
+            let (status, body) = call(app(true), "DELETE", "/products/1", bearer.clone()).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{:?}", bearer);
+            let body: Value = serde_json::from_str(&body).unwrap();
+            assert_eq!(body["code"], "unauthorized", "{:?}", bearer);
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_are_public_unless_turned_off() {
+        assert_eq!(
+            call(app(true), "GET", "/products/1", None).await.0,
+            StatusCode::OK
+        );
+        assert_eq!(
+            call(app(true), "POST", "/products/batch", None).await.0,
+            StatusCode::OK
+        );
+
+        assert_eq!(
+            call(app(false), "GET", "/products/1", None).await.0,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            call(app(false), "POST", "/products/batch", None).await.0,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            call(app(false), "GET", "/products/1", Some(token(AUDIENCE, 600)))
+                .await
+                .0,
+            StatusCode::OK
+        );
+    }
+}
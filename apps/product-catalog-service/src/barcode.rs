@@ -0,0 +1,168 @@
+//! Product codes as scanned: EAN-8, UPC-A and EAN-13, all GTINs whose last digit checks
+//! the others.
+//!
+//! A code of the wrong length or with a wrong check digit is almost always a typo, and
+//! a product stored under it is never found by a scan. Stores also print their own
+//! codes, which GS1 reserves the prefix 2 for, and tests use short made-up ones; the
+//! `allow_internal_codes` tunable lets those through.
+
+use thiserror::Error;
+
+/// What a valid product code looks like, for error messages.
+pub const EXPECTED_FORMATS: &str =
+    "an EAN-8, UPC-A or EAN-13 barcode: 8, 12 or 13 digits, the last one a valid check digit";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BarcodeFormat {
+    Ean8,
+    UpcA,
+    Ean13,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum BarcodeError {
+    #[error("a barcode has digits only")]
+    NotDigits,
+    #[error("a barcode has 8, 12 or 13 digits, not {0}")]
+    Length(usize),
+    #[error("the check digit should be {expected}, not {found}")]
+    CheckDigit { expected: u32, found: u32 },
+}
+
+/// The format of `code`, if it is a GTIN with the right check digit.
+pub fn parse(code: &str) -> Result<BarcodeFormat, BarcodeError> {
+    let digits = code
+        .chars()
+        .map(|c| c.to_digit(10))
+        .collect::<Option<Vec<u32>>>()
+        .filter(|digits| !digits.is_empty())
+        .ok_or(BarcodeError::NotDigits)?;
+    let format = match digits.len() {
+        8 => BarcodeFormat::Ean8,
+        12 => BarcodeFormat::UpcA,
+        13 => BarcodeFormat::Ean13,
+        other => return Err(BarcodeError::Length(other)),
+    };
+    let (found, body) = digits.split_last().expect("not empty");
+    let expected = check_digit(body);
+    if *found != expected {
+        return Err(BarcodeError::CheckDigit {
+            expected,
+            found: *found,
+        });
+    }
+    Ok(format)
+}
+
+/// The GS1 check digit of `body`: its digits weighted 3 and 1 alternately from the
+/// right, and what brings their sum to a multiple of ten.
+pub fn check_digit(body: &[u32]) -> u32 {
+    let sum: u32 = body
+        .iter()
+        .rev()
+        .zip([3, 1].into_iter().cycle())
+        .map(|(digit, weight)| digit * weight)
+        .sum();
+    (10 - sum % 10) % 10
+}
+
+/// Whether `code` may be stored and looked up. With `allow_internal`, store-internal
+/// codes (prefix 2) pass whatever their check digit, and so does anything not shaped
+/// like a GTIN; a GTIN-shaped code with a wrong check digit is still refused.
+pub fn validate(code: &str, allow_internal: bool) -> Result<(), BarcodeError> {
+    match parse(code) {
+        Ok(_) => Ok(()),
+        Err(_) if allow_internal && code.starts_with('2') => Ok(()),
+        Err(BarcodeError::NotDigits | BarcodeError::Length(_)) if allow_internal => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_codes_of_each_format() {
+        assert_eq!(parse("96385074"), Ok(BarcodeFormat::Ean8));
+        assert_eq!(parse("036000291452"), Ok(BarcodeFormat::UpcA));
+        assert_eq!(parse("4006381333931"), Ok(BarcodeFormat::Ean13));
+        assert_eq!(parse("4000417025005"), Ok(BarcodeFormat::Ean13));
+        // A check digit of zero, where the sum is already a multiple of ten.
+        assert_eq!(parse("1000000000030"), Ok(BarcodeFormat::Ean13));
+    }
+
+    #[test]
+    fn wrong_check_digits_are_named() {
+        assert_eq!(
+            parse("4006381333932"),
+            Err(BarcodeError::CheckDigit {
+                expected: 1,
+                found: 2
+            })
+        );
+        assert_eq!(
+            parse("96385075"),
+            Err(BarcodeError::CheckDigit {
+                expected: 4,
+                found: 5
+            })
+        );
+        // Two digits swapped, the typo a check digit is for.
+        assert!(matches!(
+            parse("036000921452"),
+            Err(BarcodeError::CheckDigit { .. })
+        ));
+    }
+
+    #[test]
+    fn lengths_around_the_formats_are_refused() {
+        for (code, length) in [
+            ("9638507", 7),
+            ("963850745", 9),
+            ("03600029145", 11),
+            ("40063813339310", 14),
+            ("1", 1),
+        ] {
+            assert_eq!(parse(code), Err(BarcodeError::Length(length)), "{}", code);
+        }
+    }
+
+    #[test]
+    fn only_ascii_digits_are_read() {
+        for code in [
+            "",
+            "40063813339a1",
+            " 4006381333931",
+            "4006381333931 ",
+            "-96385074",
+        ] {
+            assert_eq!(parse(code), Err(BarcodeError::NotDigits), "{:?}", code);
+        }
+        assert_eq!(parse("٩٦٣٨٥٠٧٤"), Err(BarcodeError::NotDigits));
+    }
+
+    #[test]
+    fn check_digits_weigh_from_the_right() {
+        assert_eq!(check_digit(&[9, 6, 3, 8, 5, 0, 7]), 4);
+        assert_eq!(check_digit(&[0, 3, 6, 0, 0, 0, 2, 9, 1, 4, 5]), 2);
+        assert_eq!(check_digit(&[]), 0);
+    }
+
+    #[test]
+    fn internal_codes_need_the_escape_hatch() {
+        // Prefix 2 with a wrong check digit, a test code, and a mistyped GTIN.
+        assert!(validate("2012345678900", false).is_err());
+        assert!(validate("123", false).is_err());
+        assert!(validate("4006381333932", false).is_err());
+
+        assert!(validate("2012345678900", true).is_ok());
+        assert!(validate("20123", true).is_ok());
+        assert!(validate("123", true).is_ok());
+        assert!(validate("test-product", true).is_ok());
+        assert!(validate("4006381333932", true).is_err());
+
+        assert!(validate("4006381333931", false).is_ok());
+        assert!(validate("2012345678903", false).is_ok());
+    }
+}
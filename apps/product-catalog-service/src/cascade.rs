@@ -0,0 +1,165 @@
+//! Removes a deleted product's copies outside MongoDB: its Qdrant point, which would keep
+//! it in recommendations and semantic search, and its node in the Neo4j ingredient graph.
+//!
+//! Both are best effort. The product is gone once MongoDB says so; a copy that could not
+//! be removed is logged and counted in [`ORPHANED_COPIES_TOTAL`] for reconciliation.
+
+use crate::{
+    catalog_metrics::{ORPHANED_COPIES_TOTAL, observe_qdrant},
+    errors::Result,
+    handlers::QDRANT_COLLECTION_NAME,
+};
+use async_trait::async_trait;
+use bson::oid::ObjectId;
+use neo4rs::{Graph, query};
+use qdrant_client::{
+    Qdrant,
+    qdrant::{DeletePointsBuilder, PointsIdsList},
+};
+use std::sync::Arc;
+use tracing::{debug, warn};
+use yoloeats_domain::point::point_id;
+
+/// Where products are copied besides MongoDB.
+#[async_trait]
+pub trait ProductCopies: Send + Sync {
+    /// Deletes the Qdrant point with this id.
+    async fn delete_vector(&self, point_id: &str) -> Result<()>;
+
+    /// Deletes the graph node of the product with this barcode, and its relationships.
+    async fn delete_graph_node(&self, code: &str) -> Result<()>;
+}
+
+pub struct ExternalCopies {
+    qdrant: Arc<Qdrant>,
+    neo4j: Graph,
+}
+
+impl ExternalCopies {
+    pub fn new(qdrant: Arc<Qdrant>, neo4j: Graph) -> Self {
+        ExternalCopies { qdrant, neo4j }
+    }
+}
+
+#[async_trait]
+impl ProductCopies for ExternalCopies {
+    async fn delete_vector(&self, point_id: &str) -> Result<()> {
+        let delete = DeletePointsBuilder::new(QDRANT_COLLECTION_NAME)
+            .points(PointsIdsList {
+                ids: vec![point_id.to_string().into()],
+            })
+            .wait(true);
+        observe_qdrant("delete_points", self.qdrant.delete_points(delete)).await?;
+        Ok(())
+    }
+
+    async fn delete_graph_node(&self, code: &str) -> Result<()> {
+        self.neo4j
+            .run(query("MATCH (p:Product {code: $code}) DETACH DELETE p").param("code", code))
+            .await?;
+        Ok(())
+    }
+}
+
+/// In-memory storage has no copies.
+#[derive(Clone, Copy, Default)]
+pub struct NoCopies;
+
+#[async_trait]
+impl ProductCopies for NoCopies {
+    async fn delete_vector(&self, _point_id: &str) -> Result<()> {
+        Ok(())
+    }
+
+    async fn delete_graph_node(&self, _code: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Deletes the copies of the product deleted from MongoDB, logging and counting those
+/// that stay behind.
+pub(crate) async fn delete_copies(copies: &dyn ProductCopies, id: &ObjectId, code: &str) {
+    let point_id = point_id(id);
+    match copies.delete_vector(&point_id).await {
+        Ok(()) => debug!(id = %id, point_id, "Deleted the product's Qdrant point"),
+        Err(e) => orphaned("qdrant", id, code, e),
+    }
+    match copies.delete_graph_node(code).await {
+        Ok(()) => debug!(id = %id, code, "Deleted the product's graph node"),
+        Err(e) => orphaned("neo4j", id, code, e),
+    }
+}
+
+fn orphaned(store: &'static str, id: &ObjectId, code: &str, error: impl std::fmt::Display) {
+    warn!(
+        id = %id,
+        code,
+        store,
+        "Deleted product left a copy behind; reconcile it later: {}",
+        error
+    );
+    metrics::counter!(ORPHANED_COPIES_TOTAL, "store" => store).increment(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ServiceError;
+    use std::sync::Mutex;
+    use uuid::Uuid;
+
+    #[derive(Default)]
+    struct RecordingCopies {
+        deleted: Mutex<Vec<String>>,
+        fail: bool,
+    }
+
+    impl RecordingCopies {
+        fn delete(&self, what: String) -> Result<()> {
+            self.deleted.lock().unwrap().push(what);
+            if self.fail {
+                Err(ServiceError::Internal("store is down".to_string()))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[async_trait]
+    impl ProductCopies for RecordingCopies {
+        async fn delete_vector(&self, point_id: &str) -> Result<()> {
+            self.delete(format!("point {}", point_id))
+        }
+
+        async fn delete_graph_node(&self, code: &str) -> Result<()> {
+            self.delete(format!("node {}", code))
+        }
+    }
+
+    #[tokio::test]
+    async fn copies_are_deleted_by_point_id_and_barcode() {
+        let id = ObjectId::parse_str("663a1f2e9b1e8a3f4c5d6e7f").unwrap();
+        // As the recommendations look the point up.
+        let point_id = Uuid::new_v5(&Uuid::NAMESPACE_DNS, id.to_hex().as_bytes());
+        let copies = RecordingCopies::default();
+
+        delete_copies(&copies, &id, "4000417025005").await;
+        assert_eq!(
+            *copies.deleted.lock().unwrap(),
+            [
+                format!("point {}", point_id),
+                "node 4000417025005".to_string()
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn a_failed_deletion_does_not_stop_the_other() {
+        let copies = RecordingCopies {
+            fail: true,
+            ..RecordingCopies::default()
+        };
+        delete_copies(&copies, &ObjectId::new(), "4000417025005").await;
+        assert_eq!(copies.deleted.lock().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,52 @@
+//! Catalog-specific counters. They go through the same process-wide recorder as the HTTP
+//! metrics from `yoloeats-metrics`, so they are exported on the same `/metrics` endpoint.
+
+use std::{future::Future, time::Instant};
+
+pub const CACHE_LOOKUPS_TOTAL: &str = "catalog_cache_lookups_total";
+pub const QDRANT_REQUESTS_TOTAL: &str = "catalog_qdrant_requests_total";
+pub const QDRANT_REQUEST_DURATION_SECONDS: &str = "catalog_qdrant_request_duration_seconds";
+/// Copies of deleted products left in Qdrant or Neo4j, by `store`; see [`crate::cascade`].
+pub const ORPHANED_COPIES_TOTAL: &str = "catalog_orphaned_copies_total";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheOutcome {
+    Hit,
+    /// A cached "not found": answered 404 without asking Mongo.
+    NegativeHit,
+    Miss,
+    /// Redis failed or held an unreadable entry; the lookup fell through to Mongo.
+    Error,
+}
+
+impl CacheOutcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            CacheOutcome::Hit => "hit",
+            CacheOutcome::NegativeHit => "negative_hit",
+            CacheOutcome::Miss => "miss",
+            CacheOutcome::Error => "error",
+        }
+    }
+}
+
+/// `key_kind` is the lookup key (`id`, `code`), `count` or `search`, never the key itself.
+pub fn record_cache_lookup(key_kind: &'static str, outcome: CacheOutcome) {
+    metrics::counter!(CACHE_LOOKUPS_TOTAL, "key" => key_kind, "outcome" => outcome.as_str())
+        .increment(1);
+}
+
+/// Awaits a Qdrant call, counting it by operation and outcome and recording its latency.
+pub async fn observe_qdrant<T, E>(
+    operation: &'static str,
+    call: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    let start = Instant::now();
+    let result = call.await;
+    let outcome = if result.is_ok() { "ok" } else { "error" };
+    metrics::counter!(QDRANT_REQUESTS_TOTAL, "operation" => operation, "outcome" => outcome)
+        .increment(1);
+    metrics::histogram!(QDRANT_REQUEST_DURATION_SECONDS, "operation" => operation)
+        .record(start.elapsed().as_secs_f64());
+    result
+}
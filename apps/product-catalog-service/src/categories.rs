@@ -0,0 +1,667 @@
+//! `/api/v1/categories`: the categories products are in and how many products each, for
+//! the app's category browser.
+//!
+//! MongoDB counts the `categories_tags` of the matching products in an aggregation.
+//! Where Neo4j holds the category taxonomy, as `(:Category {tag, name})` nodes linked
+//! `(child)-[:CHILD_OF]->(parent)`, each entry also gets its display name and parent,
+//! and `parent` lists the children of a category. The taxonomy is optional: without it,
+//! or with Neo4j down or slow, entries have their tag and count only. Only `parent`
+//! can't do without the graph and answers 503 then.
+//!
+//! Listings are cached in Redis for [`CATEGORY_CACHE_TTL_SECS`] under a hash of the
+//! query, except those made without a taxonomy that should have been there. Writes
+//! don't clear them, so counts may lag behind for that long.
+
+use crate::{
+    catalog_metrics::{CacheOutcome, record_cache_lookup},
+    default_country::{DefaultCountry, scope_country},
+    errors::{Result, ServiceError},
+    handlers::connect_cache,
+    repository::{CategoryQuery, ProductFilter, ProductRepository},
+    state::AppState,
+    taxonomy::normalize_tags,
+    tunables::{CATEGORY_CACHE_TTL_SECS, cache_ttl},
+};
+use async_trait::async_trait;
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use neo4rs::{Graph, query};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, future::Future, sync::Arc, time::Duration};
+use tracing::{debug, error, info, instrument, warn};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+use yoloeats_domain::ErrorBody;
+
+const DEFAULT_LIMIT: u64 = 100;
+const MAX_LIMIT: u64 = 500;
+
+/// How long a taxonomy lookup may take before the listing goes on without it.
+const TAXONOMY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// What the taxonomy knows of a category.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CategoryNode {
+    pub name: Option<String>,
+    /// The parent's tag; the first by tag for a category with several.
+    pub parent: Option<String>,
+}
+
+/// Where category names and their hierarchy come from.
+#[async_trait]
+pub trait CategoryTaxonomy: Send + Sync {
+    /// Those of `tags` the taxonomy has, by tag.
+    async fn describe(&self, tags: &[String]) -> Result<HashMap<String, CategoryNode>>;
+
+    /// The tags of the categories directly under `parent`.
+    async fn children(&self, parent: &str) -> Result<Vec<String>>;
+}
+
+/// The taxonomy in the Neo4j graph.
+pub struct GraphTaxonomy {
+    graph: Graph,
+}
+
+impl GraphTaxonomy {
+    pub fn new(graph: Graph) -> Self {
+        GraphTaxonomy { graph }
+    }
+}
+
+fn unreadable(e: neo4rs::DeError) -> ServiceError {
+    ServiceError::Neo4j(neo4rs::Error::DeserializationError(e))
+}
+
+#[async_trait]
+impl CategoryTaxonomy for GraphTaxonomy {
+    async fn describe(&self, tags: &[String]) -> Result<HashMap<String, CategoryNode>> {
+        let mut rows = self
+            .graph
+            .execute(
+                query(
+                    "MATCH (c:Category) WHERE c.tag IN $tags \
+                     OPTIONAL MATCH (c)-[:CHILD_OF]->(p:Category) \
+                     RETURN c.tag AS tag, c.name AS name, min(p.tag) AS parent",
+                )
+                .param("tags", tags.to_vec()),
+            )
+            .await?;
+        let mut nodes = HashMap::new();
+        while let Some(row) = rows.next().await? {
+            let tag: String = row.get("tag").map_err(unreadable)?;
+            let node = CategoryNode {
+                name: row.get("name").map_err(unreadable)?,
+                parent: row.get("parent").map_err(unreadable)?,
+            };
+            nodes.insert(tag, node);
+        }
+        Ok(nodes)
+    }
+
+    async fn children(&self, parent: &str) -> Result<Vec<String>> {
+        let mut rows = self
+            .graph
+            .execute(
+                query(
+                    "MATCH (c:Category)-[:CHILD_OF]->(:Category {tag: $parent}) \
+                     RETURN c.tag AS tag",
+                )
+                .param("parent", parent),
+            )
+            .await?;
+        let mut children = Vec::new();
+        while let Some(row) = rows.next().await? {
+            children.push(row.get("tag").map_err(unreadable)?);
+        }
+        Ok(children)
+    }
+}
+
+/// In-memory storage has no taxonomy: categories have no names and no children.
+#[derive(Clone, Copy, Default)]
+pub struct NoTaxonomy;
+
+#[async_trait]
+impl CategoryTaxonomy for NoTaxonomy {
+    async fn describe(&self, _tags: &[String]) -> Result<HashMap<String, CategoryNode>> {
+        Ok(HashMap::new())
+    }
+
+    async fn children(&self, _parent: &str) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+}
+
+/// Query of `GET /api/v1/categories`. Tags are read as search reads them, so `Snacks`
+/// and `en:snacks` are the same category.
+#[derive(Debug, Default, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CategoryParams {
+    /// Only the categories directly under this one in the taxonomy.
+    pub parent: Option<String>,
+    /// Only categories whose tag starts with this: `choc` finds `en:chocolates`.
+    pub prefix: Option<String>,
+    /// Only count products sold in this country; `all` counts every country even with a
+    /// `DEFAULT_COUNTRY_TAG`.
+    pub country: Option<String>,
+    /// Leaves out categories with fewer products; 1 when absent.
+    #[validate(range(min = 1, message = "min_count must be at least 1"))]
+    pub min_count: Option<u64>,
+    /// 1 to 500 categories; 100 when absent.
+    #[validate(range(min = 1, max = 500, message = "limit must be between 1 and 500"))]
+    pub limit: Option<u64>,
+}
+
+/// [`CategoryParams`] read: tags normalized, blanks dropped and defaults filled in.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CategoryRequest {
+    pub parent: Option<String>,
+    pub prefix: Option<String>,
+    pub country: Option<String>,
+    pub min_count: u64,
+    pub limit: u64,
+}
+
+impl CategoryParams {
+    pub fn request(&self) -> CategoryRequest {
+        let tag = |value: &Option<String>| normalize_tags(value.as_deref()).pop();
+        CategoryRequest {
+            parent: tag(&self.parent),
+            prefix: tag(&self.prefix),
+            country: tag(&self.country),
+            min_count: self.min_count.unwrap_or(1),
+            limit: self.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT),
+        }
+    }
+}
+
+/// A category and how many products are in it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct CategoryEntry {
+    pub tag: String,
+    pub count: u64,
+    /// The display name in the taxonomy; `null` without one.
+    pub name: Option<String>,
+    /// The tag of the category above this one in the taxonomy; `null` for a top-level
+    /// category or without a taxonomy.
+    pub parent: Option<String>,
+}
+
+/// A listing, and whether the taxonomy was read for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CategoryListing {
+    pub entries: Vec<CategoryEntry>,
+    pub with_taxonomy: bool,
+}
+
+/// Where the listing for `request` is cached.
+pub fn categories_cache_key(request: &CategoryRequest) -> String {
+    let canonical = serde_json::to_string(request).unwrap_or_default();
+    let digest = Sha256::digest(canonical.as_bytes());
+    let hash: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("categories:{}", hash)
+}
+
+/// `lookup`, given up on after [`TAXONOMY_TIMEOUT`]. Any failure is
+/// [`ServiceError::TaxonomyUnavailable`].
+async fn ask_taxonomy<T>(lookup: impl Future<Output = Result<T>>) -> Result<T> {
+    match tokio::time::timeout(TAXONOMY_TIMEOUT, lookup).await {
+        Ok(Ok(answer)) => Ok(answer),
+        Ok(Err(e)) => Err(ServiceError::TaxonomyUnavailable(e.to_string())),
+        Err(_) => Err(ServiceError::TaxonomyUnavailable(format!(
+            "no answer within {:?}",
+            TAXONOMY_TIMEOUT
+        ))),
+    }
+}
+
+/// Counts the categories `request` asks for and describes them from `taxonomy` where it
+/// can. A taxonomy that fails leaves the entries without names and parents, unless
+/// `parent` needs it to pick the categories at all.
+pub async fn find_categories(
+    products: &dyn ProductRepository,
+    taxonomy: &dyn CategoryTaxonomy,
+    request: &CategoryRequest,
+) -> Result<CategoryListing> {
+    let within = match &request.parent {
+        Some(parent) => Some(ask_taxonomy(taxonomy.children(parent)).await?),
+        None => None,
+    };
+    let filter = ProductFilter {
+        countries: request.country.iter().cloned().collect(),
+        ..Default::default()
+    };
+    let query = CategoryQuery {
+        prefix: request.prefix.clone(),
+        within,
+        min_count: request.min_count,
+        limit: request.limit,
+    };
+    let counts = products.category_counts(&filter, &query).await?;
+
+    let tags: Vec<String> = counts.iter().map(|count| count.tag.clone()).collect();
+    let (mut nodes, with_taxonomy) = match ask_taxonomy(taxonomy.describe(&tags)).await {
+        Ok(nodes) => (nodes, true),
+        Err(e) => {
+            warn!("Listing categories without the taxonomy: {}", e);
+            (HashMap::new(), false)
+        }
+    };
+    let entries = counts
+        .into_iter()
+        .map(|count| {
+            let node = nodes.remove(&count.tag).unwrap_or_default();
+            CategoryEntry {
+                tag: count.tag,
+                count: count.count,
+                name: node.name,
+                parent: node.parent,
+            }
+        })
+        .collect();
+    Ok(CategoryListing {
+        entries,
+        with_taxonomy,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/categories",
+    tag = "v1",
+    params(CategoryParams),
+    responses(
+        (status = 200, description = "The categories with the most products first, then by tag.", body = Vec<CategoryEntry>, headers(("X-Default-Country" = String, description = "The `DEFAULT_COUNTRY_TAG` the counts were scoped to, as no `country` was named."))),
+        (status = 400, description = "An invalid `min_count` or `limit`.", body = ErrorBody),
+        (status = 500, description = "MongoDB failed.", body = ErrorBody),
+        (status = 503, description = "`parent` was given and the taxonomy can't be read.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, params), fields(query = ?params))]
+pub async fn list_categories(
+    State(state): State<Arc<AppState>>,
+    Query(mut params): Query<CategoryParams>,
+) -> Result<(DefaultCountry, Json<Vec<CategoryEntry>>)> {
+    params.validate()?;
+    let default_country = scope_country(&mut params.country, state.default_country.as_deref());
+    let request = params.request();
+    let cache_key = categories_cache_key(&request);
+
+    let mut cache_conn = connect_cache(&state, "categories").await;
+    if let Some(conn) = cache_conn.as_mut() {
+        match conn.get(&cache_key).await {
+            Ok(Some(cached)) => match serde_json::from_str(&cached) {
+                Ok(entries) => {
+                    debug!(key = %cache_key, "Cache hit for categories");
+                    record_cache_lookup("categories", CacheOutcome::Hit);
+                    return Ok((default_country, Json(entries)));
+                }
+                Err(e) => {
+                    error!(key = %cache_key, "Failed to deserialize cached categories: {}", e);
+                    record_cache_lookup("categories", CacheOutcome::Error);
+                }
+            },
+            Ok(None) => record_cache_lookup("categories", CacheOutcome::Miss),
+            Err(e) => {
+                warn!(key = %cache_key, "Redis GET command failed (categories): {}", e);
+                record_cache_lookup("categories", CacheOutcome::Error);
+            }
+        }
+    }
+
+    let listing =
+        find_categories(state.products.as_ref(), state.categories.as_ref(), &request).await?;
+    info!(
+        "Listed {} categories (taxonomy: {})",
+        listing.entries.len(),
+        listing.with_taxonomy
+    );
+    // A listing without names would stay that way for the whole TTL.
+    if let (Some(conn), true) = (cache_conn.as_mut(), listing.with_taxonomy) {
+        match serde_json::to_string(&listing.entries) {
+            Ok(json) => {
+                let ttl = cache_ttl(&state.config, CATEGORY_CACHE_TTL_SECS);
+                if let Err(e) = conn.set_ex(&cache_key, &json, ttl).await {
+                    warn!(key = %cache_key, "Failed to cache categories in Redis: {}", e);
+                }
+            }
+            Err(e) => error!(key = %cache_key, "Failed to serialize categories: {}", e),
+        }
+    }
+    Ok((default_country, Json(listing.entries)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::MemoryProducts;
+    use std::sync::Mutex;
+    use yoloeats_domain::fixtures::ProductFixture;
+
+    /// A taxonomy with a few chocolate categories, or one that is down.
+    #[derive(Default)]
+    struct FakeTaxonomy {
+        down: bool,
+        asked: Mutex<Vec<String>>,
+    }
+
+    impl FakeTaxonomy {
+        fn down() -> Self {
+            FakeTaxonomy {
+                down: true,
+                ..Default::default()
+            }
+        }
+
+        fn answer<T>(&self, question: String, answer: T) -> Result<T> {
+            self.asked.lock().unwrap().push(question);
+            if self.down {
+                Err(ServiceError::Internal("connection refused".to_string()))
+            } else {
+                Ok(answer)
+            }
+        }
+    }
+
+    #[async_trait]
+    impl CategoryTaxonomy for FakeTaxonomy {
+        async fn describe(&self, tags: &[String]) -> Result<HashMap<String, CategoryNode>> {
+            let known = [
+                ("en:snacks", "Snacks", None),
+                ("en:chocolates", "Chocolates", Some("en:snacks")),
+                ("en:crisps", "Crisps", Some("en:snacks")),
+            ];
+            let nodes = known
+                .into_iter()
+                .filter(|(tag, _, _)| tags.iter().any(|t| t == *tag))
+                .map(|(tag, name, parent)| {
+                    let node = CategoryNode {
+                        name: Some(name.to_string()),
+                        parent: parent.map(str::to_string),
+                    };
+                    (tag.to_string(), node)
+                })
+                .collect();
+            self.answer(format!("describe {}", tags.join(",")), nodes)
+        }
+
+        async fn children(&self, parent: &str) -> Result<Vec<String>> {
+            let children = match parent {
+                "en:snacks" => vec!["en:chocolates".to_string(), "en:crisps".to_string()],
+                _ => Vec::new(),
+            };
+            self.answer(format!("children {}", parent), children)
+        }
+    }
+
+    async fn products() -> MemoryProducts {
+        let products = MemoryProducts::default();
+        for (code, categories, country) in [
+            ("101", &["en:snacks", "en:chocolates"][..], "en:germany"),
+            ("102", &["en:snacks", "en:chocolates"], "en:germany"),
+            ("103", &["en:snacks", "en:crisps"], "en:france"),
+            ("104", &["en:spreads", "en:chocolate-spreads"], "en:germany"),
+        ] {
+            products
+                .insert(
+                    ProductFixture::new(code)
+                        .with_categories(categories)
+                        .with_countries([country])
+                        .build(),
+                )
+                .await
+                .unwrap();
+        }
+        products
+    }
+
+    fn request(params: CategoryParams) -> CategoryRequest {
+        params.validate().unwrap();
+        params.request()
+    }
+
+    fn tags(listing: &CategoryListing) -> Vec<(&str, u64)> {
+        listing
+            .entries
+            .iter()
+            .map(|entry| (entry.tag.as_str(), entry.count))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn categories_are_counted_and_described() {
+        let (products, taxonomy) = (products().await, FakeTaxonomy::default());
+        let listing = find_categories(&products, &taxonomy, &request(CategoryParams::default()))
+            .await
+            .unwrap();
+        assert!(listing.with_taxonomy);
+        assert_eq!(
+            tags(&listing),
+            [
+                ("en:snacks", 3),
+                ("en:chocolates", 2),
+                ("en:chocolate-spreads", 1),
+                ("en:crisps", 1),
+                ("en:spreads", 1),
+            ]
+        );
+        assert_eq!(
+            listing.entries[1],
+            CategoryEntry {
+                tag: "en:chocolates".to_string(),
+                count: 2,
+                name: Some("Chocolates".to_string()),
+                parent: Some("en:snacks".to_string()),
+            }
+        );
+        assert_eq!(listing.entries[0].parent, None);
+        assert_eq!(listing.entries[2].name, None, "not in the taxonomy");
+    }
+
+    #[tokio::test]
+    async fn prefixes_are_read_as_tags() {
+        let (products, taxonomy) = (products().await, FakeTaxonomy::default());
+        for prefix in ["choc", "en:choc", " CHOC"] {
+            let params = CategoryParams {
+                prefix: Some(prefix.to_string()),
+                ..Default::default()
+            };
+            let listing = find_categories(&products, &taxonomy, &request(params))
+                .await
+                .unwrap();
+            assert_eq!(
+                tags(&listing),
+                [("en:chocolates", 2), ("en:chocolate-spreads", 1)],
+                "{}",
+                prefix
+            );
+        }
+        let params = CategoryParams {
+            prefix: Some("fr:choc".to_string()),
+            ..Default::default()
+        };
+        let listing = find_categories(&products, &taxonomy, &request(params))
+            .await
+            .unwrap();
+        assert!(listing.entries.is_empty());
+    }
+
+    #[tokio::test]
+    async fn min_count_limit_and_country_narrow_the_listing() {
+        let (products, taxonomy) = (products().await, FakeTaxonomy::default());
+        let listed = |params: CategoryParams| {
+            let (products, taxonomy) = (&products, &taxonomy);
+            async move {
+                let listing = find_categories(products, taxonomy, &request(params))
+                    .await
+                    .unwrap();
+                tags(&listing)
+                    .into_iter()
+                    .map(|(tag, count)| (tag.to_string(), count))
+                    .collect::<Vec<_>>()
+            }
+        };
+        let pairs = |expected: &[(&str, u64)]| -> Vec<(String, u64)> {
+            expected
+                .iter()
+                .map(|(tag, count)| (tag.to_string(), *count))
+                .collect()
+        };
+
+        assert_eq!(
+            listed(CategoryParams {
+                min_count: Some(2),
+                ..Default::default()
+            })
+            .await,
+            pairs(&[("en:snacks", 3), ("en:chocolates", 2)])
+        );
+        assert_eq!(
+            listed(CategoryParams {
+                min_count: Some(4),
+                ..Default::default()
+            })
+            .await,
+            pairs(&[])
+        );
+        assert_eq!(
+            listed(CategoryParams {
+                limit: Some(1),
+                ..Default::default()
+            })
+            .await,
+            pairs(&[("en:snacks", 3)])
+        );
+        assert_eq!(
+            listed(CategoryParams {
+                country: Some("Germany".to_string()),
+                min_count: Some(2),
+                ..Default::default()
+            })
+            .await,
+            pairs(&[("en:chocolates", 2), ("en:snacks", 2)])
+        );
+        assert_eq!(
+            listed(CategoryParams {
+                parent: Some("Snacks".to_string()),
+                ..Default::default()
+            })
+            .await,
+            pairs(&[("en:chocolates", 2), ("en:crisps", 1)])
+        );
+        assert_eq!(
+            listed(CategoryParams {
+                parent: Some("en:spreads".to_string()),
+                ..Default::default()
+            })
+            .await,
+            pairs(&[]),
+            "a category without children"
+        );
+    }
+
+    #[tokio::test]
+    async fn a_taxonomy_that_is_down_leaves_tags_and_counts() {
+        let (products, taxonomy) = (products().await, FakeTaxonomy::down());
+        let listing = find_categories(&products, &taxonomy, &request(CategoryParams::default()))
+            .await
+            .unwrap();
+        assert!(!listing.with_taxonomy);
+        assert_eq!(listing.entries.len(), 5);
+        assert!(
+            listing
+                .entries
+                .iter()
+                .all(|entry| entry.name.is_none() && entry.parent.is_none())
+        );
+        assert_eq!(
+            listing.entries[0],
+            CategoryEntry {
+                tag: "en:snacks".to_string(),
+                count: 3,
+                name: None,
+                parent: None,
+            }
+        );
+
+        let params = CategoryParams {
+            parent: Some("en:snacks".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            find_categories(&products, &taxonomy, &request(params)).await,
+            Err(ServiceError::TaxonomyUnavailable(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn no_taxonomy_lists_tags_only() {
+        let products = products().await;
+        let listing = find_categories(&products, &NoTaxonomy, &request(CategoryParams::default()))
+            .await
+            .unwrap();
+        assert!(
+            listing.with_taxonomy,
+            "nothing was missed, so it may be cached"
+        );
+        assert!(listing.entries.iter().all(|entry| entry.name.is_none()));
+    }
+
+    #[test]
+    fn params_are_normalized_and_bounded() {
+        let params = CategoryParams {
+            parent: Some(" Snacks ".to_string()),
+            prefix: Some("  ".to_string()),
+            country: Some("Germany".to_string()),
+            min_count: None,
+            limit: None,
+        };
+        assert_eq!(
+            params.request(),
+            CategoryRequest {
+                parent: Some("en:snacks".to_string()),
+                prefix: None,
+                country: Some("en:germany".to_string()),
+                min_count: 1,
+                limit: DEFAULT_LIMIT,
+            }
+        );
+        for (min_count, limit) in [(Some(0), None), (None, Some(0)), (None, Some(501))] {
+            let params = CategoryParams {
+                min_count,
+                limit,
+                ..Default::default()
+            };
+            assert!(params.validate().is_err(), "{:?}", params);
+        }
+    }
+
+    #[test]
+    fn equal_requests_share_a_cache_key() {
+        let key = |params: CategoryParams| categories_cache_key(&params.request());
+        let snacks = key(CategoryParams {
+            parent: Some("en:snacks".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(
+            snacks,
+            key(CategoryParams {
+                parent: Some("Snacks".to_string()),
+                limit: Some(DEFAULT_LIMIT),
+                ..Default::default()
+            })
+        );
+        assert!(snacks.starts_with("categories:"));
+        assert_ne!(snacks, key(CategoryParams::default()));
+        assert_ne!(
+            key(CategoryParams::default()),
+            key(CategoryParams {
+                min_count: Some(2),
+                ..Default::default()
+            })
+        );
+    }
+}
@@ -0,0 +1,184 @@
+//! `/api/v1/products/changes`: what changed in the catalog after a time, for the
+//! offline-first app to keep its products fresh without downloading them all again.
+//!
+//! The feed lists the products modified after `since` as they now are, and the
+//! [tombstones](crate::models::Tombstone) of those deleted after it, ordered by that time
+//! and then by `_id`. The cursor resumes after the last entry listed, so a product
+//! changed while a client pages moves to the end of the feed instead of shifting the
+//! pages. Deletes are still hard deletes; the repository writes the tombstone next to
+//! them. A `since` older than
+//! [`CHANGES_RETENTION_DAYS`] answers 410 `resync_required`: the client downloads the
+//! catalog again and syncs from then on.
+
+use crate::{
+    errors::{Result, ServiceError},
+    models::ChangeEntry,
+    repository::ChangesFrom,
+    state::AppState,
+    tunables::CHANGES_RETENTION_DAYS,
+};
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use bson::oid::ObjectId;
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{debug, info, instrument};
+use utoipa::IntoParams;
+use yoloeats_domain::ErrorBody;
+use yoloeats_pagination::{Page, PageLimit, PageParams, PaginationError};
+
+/// Page sizes for [`list_changes`].
+pub struct ChangesPageLimit;
+
+impl PageLimit for ChangesPageLimit {
+    const DEFAULT: u64 = 100;
+    const MAX: u64 = 500;
+}
+
+/// Where the next changes page starts: after the last entry of the previous one. The
+/// time is RFC 3339 to the nanosecond, as in-memory products are stamped.
+#[derive(Debug, Serialize, Deserialize)]
+struct ChangesCursor {
+    at: String,
+    after_id: Option<ObjectId>,
+}
+
+/// Query of `GET /api/v1/products/changes` besides the page.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ChangesParams {
+    /// Lists what changed after this RFC 3339 time. Required, and no older than the
+    /// `changes_retention_days` tunable.
+    #[param(example = "2024-06-01T00:00:00Z")]
+    pub since: Option<String>,
+}
+
+/// The `since` of a changes request. Taken as a string so a bad value gets our message,
+/// not serde's.
+pub fn parse_since(since: Option<&str>) -> Result<DateTime<Utc>> {
+    let since = since.map(str::trim).filter(|since| !since.is_empty());
+    let Some(since) = since else {
+        return Err(ServiceError::BadRequest(
+            "since is required: the RFC 3339 time of the last sync, such as 2024-06-01T00:00:00Z"
+                .to_string(),
+        ));
+    };
+    DateTime::parse_from_rfc3339(since)
+        .map(|since| since.with_timezone(&Utc))
+        .map_err(|_| {
+            ServiceError::BadRequest(format!(
+                "Invalid since '{}': expected an RFC 3339 time, such as 2024-06-01T00:00:00Z",
+                since
+            ))
+        })
+}
+
+/// Refuses a `since` further back than `retention_days` before `now`.
+pub fn check_retention(
+    since: DateTime<Utc>,
+    now: DateTime<Utc>,
+    retention_days: u64,
+) -> Result<()> {
+    let oldest = now - Duration::days(retention_days as i64);
+    if since < oldest {
+        return Err(ServiceError::ResyncRequired(format!(
+            "since is older than the {} days changes are kept for; download the catalog again and sync from then on",
+            retention_days
+        )));
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/products/changes",
+    tag = "v1",
+    params(
+        ChangesParams,
+        PageParams<ChangesPageLimit>,
+    ),
+    responses(
+        (status = 200, description = "One page of changes, oldest first: each a product as it now is or the tombstone of a deleted one, told apart by `change`.", body = Page<ChangeEntry>),
+        (status = 400, description = "A missing or invalid `since`, or an invalid cursor.", body = ErrorBody),
+        (status = 410, description = "`since` is older than the retention window; resync in full.", body = ErrorBody),
+        (status = 500, description = "MongoDB failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, params), fields(since = ?params.since))]
+pub async fn list_changes(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ChangesParams>,
+    page: PageParams<ChangesPageLimit>,
+) -> Result<Json<Page<ChangeEntry>>> {
+    let since = parse_since(params.since.as_deref())?;
+    check_retention(since, Utc::now(), state.config.get(CHANGES_RETENTION_DAYS))?;
+    let from = match page.position::<ChangesCursor>(&state.cursor_codec)? {
+        Some(cursor) => ChangesFrom {
+            at: DateTime::parse_from_rfc3339(&cursor.at)
+                .map_err(|_| PaginationError::InvalidCursor)?
+                .with_timezone(&Utc),
+            after_id: cursor.after_id,
+        },
+        None => ChangesFrom {
+            at: since,
+            after_id: None,
+        },
+    };
+    debug!("Changes page: limit={}, from={:?}", page.limit, from);
+
+    let changes = state.products.changes(from, page.limit + 1).await?;
+    let changes = Page::overfetched(changes, page.limit, |last| {
+        let (at, after_id) = last.position();
+        Some(state.cursor_codec.encode(&ChangesCursor {
+            at: at.to_rfc3339_opts(SecondsFormat::Nanos, true),
+            after_id,
+        }))
+    });
+    info!("Returning {} changes", changes.items.len());
+    Ok(Json(changes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn since_must_be_an_rfc3339_time() {
+        assert_eq!(
+            parse_since(Some("2024-06-01T00:00:00Z")).unwrap(),
+            time("2024-06-01T00:00:00Z")
+        );
+        assert_eq!(
+            parse_since(Some(" 2024-06-01T02:00:00+02:00 ")).unwrap(),
+            time("2024-06-01T00:00:00Z")
+        );
+        for since in [None, Some(""), Some("2024-06-01"), Some("yesterday")] {
+            assert!(
+                matches!(parse_since(since), Err(ServiceError::BadRequest(_))),
+                "{:?}",
+                since
+            );
+        }
+    }
+
+    #[test]
+    fn since_must_be_within_the_retention_window() {
+        let now = time("2024-07-01T00:00:00Z");
+        assert!(check_retention(time("2024-06-01T00:00:00Z"), now, 30).is_ok());
+        assert!(check_retention(time("2024-06-30T00:00:00Z"), now, 1).is_ok());
+        assert!(check_retention(now + Duration::days(1), now, 1).is_ok());
+
+        let err = check_retention(time("2024-05-31T23:59:59Z"), now, 30).unwrap_err();
+        assert!(matches!(err, ServiceError::ResyncRequired(_)));
+        assert!(err.to_string().contains("download the catalog again"));
+    }
+}
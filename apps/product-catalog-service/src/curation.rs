@@ -0,0 +1,95 @@
+//! `/api/v1/products/curation/incomplete`: the products with the least complete data,
+//! for curators to fix first.
+//!
+//! Completeness is [`completeness`](crate::models::completeness), derived on every read
+//! and never stored: MongoDB scores the matching products in an aggregation and returns
+//! one page, least complete first.
+
+use crate::{
+    errors::Result, models::IncompleteProduct, repository::ProductFilter, state::AppState,
+    taxonomy::normalize_tags,
+};
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{debug, info, instrument};
+use utoipa::IntoParams;
+use validator::Validate;
+use yoloeats_domain::ErrorBody;
+use yoloeats_pagination::{Page, PageLimit, PageParams};
+
+/// Page sizes for [`list_incomplete_products`].
+pub struct CurationPageLimit;
+
+impl PageLimit for CurationPageLimit {
+    const DEFAULT: u64 = 50;
+    const MAX: u64 = 100;
+}
+
+/// Where the next curation page starts: past the products already listed. Scores tie
+/// too often to resume after a product.
+#[derive(Debug, Serialize, Deserialize)]
+struct CurationCursor {
+    skip: u64,
+}
+
+/// Query of `GET /api/v1/products/curation/incomplete`.
+#[derive(Debug, Default, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CurationParams {
+    /// Lists products scoring at most this, 0 to 100; all of them when absent.
+    #[validate(range(max = 100, message = "max_score must be between 0 and 100"))]
+    pub max_score: Option<u8>,
+    /// Products sold in any of these countries, comma-separated.
+    pub country: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/products/curation/incomplete",
+    tag = "v1",
+    params(
+        CurationParams,
+        PageParams<CurationPageLimit>,
+    ),
+    responses(
+        (status = 200, description = "One page of products, least complete first, each with its `completeness`.", body = Page<IncompleteProduct>),
+        (status = 400, description = "An invalid parameter or cursor.", body = ErrorBody),
+        (status = 500, description = "MongoDB failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, params), fields(query = ?params))]
+pub async fn list_incomplete_products(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<CurationParams>,
+    page: PageParams<CurationPageLimit>,
+) -> Result<Json<Page<IncompleteProduct>>> {
+    params.validate()?;
+    let filter = ProductFilter {
+        countries: normalize_tags(params.country.as_deref().unwrap_or_default().split(',')),
+        ..Default::default()
+    };
+    let max_score = params.max_score.unwrap_or(100);
+    let skip = page
+        .position::<CurationCursor>(&state.cursor_codec)?
+        .map_or(page.offset, |cursor| cursor.skip);
+    debug!(
+        "Curation page: max_score={}, limit={}, skip={}",
+        max_score, page.limit, skip
+    );
+
+    let products = state
+        .products
+        .least_complete(&filter, max_score, skip, page.limit + 1)
+        .await?;
+    let products = Page::overfetched(products, page.limit, |_| {
+        Some(state.cursor_codec.encode(&CurationCursor {
+            skip: skip + page.limit,
+        }))
+    });
+    info!("Returning {} incomplete products", products.items.len());
+    Ok(Json(products))
+}
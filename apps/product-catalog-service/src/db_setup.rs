@@ -1,65 +1,146 @@
-use crate::models::Product;
-use mongodb::{Database, IndexModel, bson::doc, options::IndexOptions};
-use tracing::{error, info};
+use crate::{
+    audit::AuditEntry,
+    models::{Product, Tombstone},
+    reports::{ProductReport, REPORTS_COLLECTION},
+    repository::{PRODUCTS_COLLECTION, TOMBSTONES_COLLECTION},
+};
+use mongodb::{
+    Collection, Database, IndexModel,
+    bson::{Document, doc},
+    error::ErrorKind,
+    options::IndexOptions,
+};
+use tracing::{error, info, warn};
 
-pub async fn create_indexes(db: &Database) -> Result<(), mongodb::error::Error> {
-    let collection = db.collection::<Product>("openfoodfacts_products");
-    info!("Attempting to create indexes for 'products' collection...");
+/// `IndexOptionsConflict` and `IndexKeySpecsConflict`: an index of that name or those
+/// keys already exists, built differently.
+const INDEX_CONFLICT_CODES: [i32; 2] = [85, 86];
 
-    let code_options = IndexOptions::builder().unique(true).build();
-    let code_index = IndexModel::builder()
-        .keys(doc! { "code": 1 }) // 1 for ascending order
-        .options(code_options)
-        .build();
+/// The indexes of the products collection, by MongoDB's default names.
+pub fn product_indexes() -> Vec<IndexModel> {
+    let ascending = |field: &str| IndexModel::builder().keys(doc! { field: 1 }).build();
+    let sparse = |field: &str| {
+        IndexModel::builder()
+            .keys(doc! { field: 1 })
+            .options(IndexOptions::builder().sparse(true).build())
+            .build()
+    };
+    vec![
+        IndexModel::builder()
+            .keys(doc! { "code": 1 }) // 1 for ascending order
+            .options(IndexOptions::builder().unique(true).build())
+            .build(),
+        IndexModel::builder()
+            .keys(doc! {
+                "product_name": "text",
+                "generic_name": "text",
+                "ingredients_text": "text",
+                "brands_tags": "text"
+            })
+            .build(),
+        ascending("categories_tags"),
+        ascending("labels_tags"),
+        ascending("brands_tags"),
+        ascending("countries_tags"),
+        ascending("allergens_tags"),
+        ascending("traces_tags"),
+        ascending("nutrition_grade_fr"),
+        ascending("ecoscore_grade"),
+        // Most products lack some nutriments; search bounds only ever match present values.
+        sparse("nutriments.sugars_100g"),
+        sparse("nutriments.salt_100g"),
+        sparse("nutriments.fat_100g"),
+        sparse("nutriments.proteins_100g"),
+        // The changes feed walks products in this order.
+        IndexModel::builder()
+            .keys(doc! { "last_modified_datetime": 1, "_id": 1 })
+            .build(),
+        // The recent feeds list products newest first.
+        IndexModel::builder()
+            .keys(doc! { "created_datetime": -1, "_id": -1 })
+            .build(),
+        IndexModel::builder()
+            .keys(doc! { "last_modified_datetime": -1, "_id": -1 })
+            .build(),
+    ]
+}
 
-    let text_index = IndexModel::builder()
-        .keys(doc! {
-            "product_name": "text",
-            "generic_name": "text",
-            "ingredients_text": "text",
-            "brands_tags": "text"
-        })
-        .build();
+/// Creates the catalog's indexes one by one. An index that already exists with other
+/// options is left as it is, with a warning; any other failure is returned.
+pub async fn create_indexes(db: &Database) -> Result<(), mongodb::error::Error> {
+    let products = db.collection::<Product>(PRODUCTS_COLLECTION);
+    info!(
+        "Attempting to create indexes for '{}' collection...",
+        PRODUCTS_COLLECTION
+    );
+    for index in product_indexes() {
+        create_index(&products, index).await?;
+    }
 
-    let categories_index = IndexModel::builder()
-        .keys(doc! { "categories_tags": 1 })
-        .build();
-    let labels_index = IndexModel::builder()
-        .keys(doc! { "labels_tags": 1 })
-        .build();
-    let brands_idx = IndexModel::builder()
-        .keys(doc! { "brands_tags": 1 })
-        .build();
-    let countries_index = IndexModel::builder()
-        .keys(doc! { "countries_tags": 1 })
+    // History pages are one product's entries, newest first.
+    let audit_index = IndexModel::builder()
+        .keys(doc! { "product_id": 1, "_id": -1 })
         .build();
+    create_index(&db.collection::<AuditEntry>("product_audit"), audit_index).await?;
 
-    let nutriscore_index = IndexModel::builder()
-        .keys(doc! { "nutrition_grade_fr": 1 })
+    // Report pages are one product's reports, or all in one status, newest first.
+    let reports = db.collection::<ProductReport>(REPORTS_COLLECTION);
+    for keys in [
+        doc! { "product_id": 1, "_id": -1 },
+        doc! { "status": 1, "_id": -1 },
+    ] {
+        create_index(&reports, IndexModel::builder().keys(keys).build()).await?;
+    }
+
+    // The changes feed walks tombstones in deletion order too.
+    let tombstone_index = IndexModel::builder()
+        .keys(doc! { "deleted_datetime": 1, "_id": 1 })
         .build();
+    create_index(
+        &db.collection::<Tombstone>(TOMBSTONES_COLLECTION),
+        tombstone_index,
+    )
+    .await
+}
 
-    match collection
-        .create_indexes(vec![
-            code_index,
-            text_index,
-            categories_index,
-            labels_index,
-            brands_idx,
-            countries_index,
-            nutriscore_index,
-        ])
-        .await
-    {
+async fn create_index<T: Send + Sync>(
+    collection: &Collection<T>,
+    index: IndexModel,
+) -> Result<(), mongodb::error::Error> {
+    let keys: Document = index.keys.clone();
+    match collection.create_index(index).await {
         Ok(result) => {
             info!(
-                "Successfully created MongoDB indexes for 'openfoodfacts_products' collection: {:?}",
-                result.index_names
+                "Index '{}' on '{}' is in place",
+                result.index_name,
+                collection.name()
+            );
+            Ok(())
+        }
+        Err(e) if is_index_conflict(&e) => {
+            warn!(
+                "Keeping the existing index on '{}' for {}, which differs from ours: {}",
+                collection.name(),
+                keys,
+                e
             );
             Ok(())
         }
         Err(e) => {
-            error!("Failed to create MongoDB indexes: {}", e);
-            Err(e) // Propagate the error for handling in main.rs
+            error!(
+                "Failed to create the index on '{}' for {}: {}",
+                collection.name(),
+                keys,
+                e
+            );
+            Err(e)
         }
     }
 }
+
+fn is_index_conflict(e: &mongodb::error::Error) -> bool {
+    matches!(
+        e.kind.as_ref(),
+        ErrorKind::Command(command_error) if INDEX_CONFLICT_CODES.contains(&command_error.code)
+    )
+}
@@ -0,0 +1,208 @@
+//! The country requests are scoped to when they name none. YoloEats serves Germany
+//! first: with `DEFAULT_COUNTRY_TAG=en:germany`, the searches, counts, category listing
+//! and popular and recent feeds that name no `country` only see the products sold in
+//! Germany. `country=all` sees every country again, and any other `country` is taken as
+//! it is. Responses the default was applied to name it in [`DEFAULT_COUNTRY_HEADER`].
+
+use crate::{
+    errors::{Result, ServiceError},
+    taxonomy::normalize_tags,
+};
+use axum::{
+    http::{HeaderName, HeaderValue},
+    response::{IntoResponseParts, ResponseParts},
+};
+use std::{convert::Infallible, env};
+use tracing::error;
+
+/// A country tag like `en:germany`; unset or blank scopes nothing.
+pub const DEFAULT_COUNTRY_TAG_ENV: &str = "DEFAULT_COUNTRY_TAG";
+
+/// The `country` that asks for every country, whatever the default.
+pub const ALL_COUNTRIES: &str = "all";
+
+/// Carries the country a response was scoped to because its request named none.
+pub const DEFAULT_COUNTRY_HEADER: HeaderName = HeaderName::from_static("x-default-country");
+
+/// The [`DEFAULT_COUNTRY_TAG_ENV`] country, none when unset. A value that isn't a tag as
+/// products store it, like `Germany` or `en:Germany`, stops the service from starting
+/// rather than scoping every request to a country no product is sold in.
+pub fn default_country_from_env() -> Result<Option<String>> {
+    match env::var(DEFAULT_COUNTRY_TAG_ENV) {
+        Ok(value) if !value.trim().is_empty() => {
+            let tag = value.trim();
+            if is_country_tag(tag) {
+                Ok(Some(tag.to_string()))
+            } else {
+                error!(
+                    "{} must be a country tag like en:germany, got '{}'",
+                    DEFAULT_COUNTRY_TAG_ENV, tag
+                );
+                Err(ServiceError::InvalidVariable(
+                    DEFAULT_COUNTRY_TAG_ENV.to_string(),
+                ))
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Whether `tag` is a country tag in the catalog's form: a lowercase language code, `:`
+/// and a name of lowercase letters and digits, maybe joined by dashes.
+pub fn is_country_tag(tag: &str) -> bool {
+    let Some((language, name)) = tag.split_once(':') else {
+        return false;
+    };
+    (2..=3).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_lowercase())
+        && name.split('-').all(|part| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        })
+}
+
+/// The [`DEFAULT_COUNTRY_HEADER`] of a response, none when the request named its own
+/// countries or `all`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DefaultCountry(pub Option<String>);
+
+impl IntoResponseParts for DefaultCountry {
+    type Error = Infallible;
+
+    fn into_response_parts(
+        self,
+        mut res: ResponseParts,
+    ) -> std::result::Result<ResponseParts, Self::Error> {
+        if let Some(value) = self
+            .0
+            .and_then(|country| HeaderValue::try_from(country).ok())
+        {
+            res.headers_mut().insert(DEFAULT_COUNTRY_HEADER, value);
+        }
+        Ok(res)
+    }
+}
+
+/// Scopes the `countries` a request named: to `default` when they are none or blank, to
+/// every country when one is `all`, and otherwise leaves them be.
+pub fn scope_countries(countries: &mut Vec<String>, default: Option<&str>) -> DefaultCountry {
+    if countries
+        .iter()
+        .any(|country| country.trim().eq_ignore_ascii_case(ALL_COUNTRIES))
+    {
+        countries.clear();
+        return DefaultCountry(None);
+    }
+    match default {
+        Some(default) if normalize_tags(countries.iter()).is_empty() => {
+            *countries = vec![default.to_string()];
+            DefaultCountry(Some(default.to_string()))
+        }
+        _ => DefaultCountry(None),
+    }
+}
+
+/// [`scope_countries`] for the requests that name one country at most.
+pub fn scope_country(country: &mut Option<String>, default: Option<&str>) -> DefaultCountry {
+    let mut countries: Vec<String> = country.take().into_iter().collect();
+    let scoped = scope_countries(&mut countries, default);
+    *country = countries.pop();
+    scoped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn countries(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn default_countries_are_tags_in_the_catalogs_form() {
+        for tag in ["en:germany", "en:united-kingdom", "fr:allemagne", "en:b2"] {
+            assert!(is_country_tag(tag), "{}", tag);
+        }
+        for tag in [
+            "germany",
+            "Germany",
+            "en:Germany",
+            "en:",
+            ":germany",
+            "english:germany",
+            "en:united--kingdom",
+            "en:germany-",
+            "en:germany ",
+            "en:deutschland:de",
+            "all",
+        ] {
+            assert!(!is_country_tag(tag), "{}", tag);
+        }
+    }
+
+    #[test]
+    fn requests_without_countries_get_the_default() {
+        for requested in [countries(&[]), countries(&[""]), countries(&[" ", ""])] {
+            let mut scoped = requested.clone();
+            assert_eq!(
+                scope_countries(&mut scoped, Some("en:germany")),
+                DefaultCountry(Some("en:germany".to_string())),
+                "{:?}",
+                requested
+            );
+            assert_eq!(scoped, ["en:germany"]);
+        }
+
+        let mut scoped = countries(&[]);
+        assert_eq!(scope_countries(&mut scoped, None), DefaultCountry(None));
+        assert!(scoped.is_empty());
+    }
+
+    #[test]
+    fn named_countries_override_the_default() {
+        let mut scoped = countries(&["France", "en:spain"]);
+        assert_eq!(
+            scope_countries(&mut scoped, Some("en:germany")),
+            DefaultCountry(None)
+        );
+        assert_eq!(scoped, ["France", "en:spain"]);
+
+        let mut country = Some("en:france".to_string());
+        assert_eq!(
+            scope_country(&mut country, Some("en:germany")),
+            DefaultCountry(None)
+        );
+        assert_eq!(country.as_deref(), Some("en:france"));
+    }
+
+    #[test]
+    fn all_opts_out_of_the_default() {
+        for default in [Some("en:germany"), None] {
+            for requested in [
+                countries(&["all"]),
+                countries(&[" ALL "]),
+                countries(&["en:france", "all"]),
+            ] {
+                let mut scoped = requested.clone();
+                assert_eq!(scope_countries(&mut scoped, default), DefaultCountry(None));
+                assert!(scoped.is_empty(), "{:?}", requested);
+            }
+        }
+
+        let mut country = Some("all".to_string());
+        assert_eq!(
+            scope_country(&mut country, Some("en:germany")),
+            DefaultCountry(None)
+        );
+        assert_eq!(country, None);
+
+        let mut country = None;
+        assert_eq!(
+            scope_country(&mut country, Some("en:germany")),
+            DefaultCountry(Some("en:germany".to_string()))
+        );
+        assert_eq!(country.as_deref(), Some("en:germany"));
+    }
+}
@@ -0,0 +1,121 @@
+//! `/api/v1/products/random`: products picked at random, for the app's "discover
+//! something new" card.
+//!
+//! MongoDB picks them with `$sample` after matching the filters, so no ids are loaded
+//! to choose from. The filters are those of product search, built the same way, so the
+//! `allergens` and `diets` exclusions leave out the same products. Products with neither
+//! a name nor an image are never picked.
+
+use crate::{
+    errors::Result,
+    handlers::search_filter,
+    models::{Product, SearchParams},
+    repository::ProductFilter,
+    state::AppState,
+};
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::{info, instrument};
+use utoipa::IntoParams;
+use validator::Validate;
+use yoloeats_domain::ErrorBody;
+
+/// Query of `GET /api/v1/products/random` besides the search filters it takes.
+#[derive(Debug, Default, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RandomParams {
+    /// How many products to pick, 1 to 10; one when absent.
+    #[validate(range(min = 1, max = 10, message = "count must be between 1 and 10"))]
+    pub count: Option<u64>,
+}
+
+/// The filter of a random pick: the search filter of `params`' categories, countries and
+/// exclusions. Other search parameters, such as `q`, are ignored.
+pub fn discovery_filter(params: SearchParams) -> ProductFilter {
+    search_filter(&SearchParams {
+        category: params.category,
+        category_match: params.category_match,
+        country: params.country,
+        user_allergens: params.user_allergens,
+        user_diets: params.user_diets,
+        ..Default::default()
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/products/random",
+    tag = "v1",
+    params(
+        ("country" = Option<Vec<String>>, Query, description = "Countries, repeated or comma-separated."),
+        ("category" = Option<Vec<String>>, Query, description = "Categories, repeated or comma-separated."),
+        ("allergens" = Option<Vec<String>>, Query, description = "Leaves out products with any of these allergens."),
+        ("diets" = Option<Vec<String>>, Query, description = "Leaves out products conflicting with any of these diets."),
+        RandomParams,
+    ),
+    responses(
+        (status = 200, description = "Up to `count` matching products in random order; fewer if fewer match.", body = Vec<Product>),
+        (status = 400, description = "An invalid parameter.", body = ErrorBody),
+        (status = 500, description = "MongoDB failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, params), fields(query = ?params, count = ?random.count))]
+pub async fn get_random_products(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SearchParams>,
+    Query(random): Query<RandomParams>,
+) -> Result<Json<Vec<Product>>> {
+    params.validate()?;
+    random.validate()?;
+    let count = random.count.unwrap_or(1);
+    let filter = discovery_filter(params);
+    let products = state.products.sample(&filter, count).await?;
+    info!("Picked {} random products", products.len());
+    Ok(Json(products))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TagMatch;
+
+    #[test]
+    fn random_picks_take_only_the_discovery_filters() {
+        let params = SearchParams {
+            q: Some("nutella".to_string()),
+            brand: vec!["ferrero".to_string()],
+            category: vec!["EN:Spreads".to_string()],
+            category_match: TagMatch::All,
+            country: vec!["en:germany".to_string()],
+            user_allergens: Some(vec!["peanuts".to_string()]),
+            max_sugar: Some(5.0),
+            ..Default::default()
+        };
+        let filter = discovery_filter(params);
+        assert_eq!(filter.text, None);
+        assert!(filter.brands.is_empty());
+        assert_eq!(filter.max_sugar, None);
+        assert_eq!(filter.categories, ["en:spreads"]);
+        assert_eq!(filter.category_match, TagMatch::All);
+        assert_eq!(filter.countries, ["en:germany"]);
+        assert!(
+            filter
+                .excluded_allergens
+                .contains(&"en:peanuts".to_string())
+        );
+    }
+
+    #[test]
+    fn count_is_one_to_ten() {
+        let params = |count| RandomParams { count };
+        assert!(params(None).validate().is_ok());
+        assert!(params(Some(1)).validate().is_ok());
+        assert!(params(Some(10)).validate().is_ok());
+        assert!(params(Some(0)).validate().is_err());
+        assert!(params(Some(11)).validate().is_err());
+    }
+}
@@ -0,0 +1,256 @@
+//! `/api/v1/products/{id}/duplicates`: products that are likely the same as this one,
+//! for curators to merge by hand.
+//!
+//! Candidates are the products whose vectors in the Qdrant index are at least
+//! `min_score` similar to this one's, found like the recommendations. A product that has
+//! not been indexed, and every product on in-memory storage, is matched by name instead:
+//! the products whose names have the same [`name_tokens`]. Either way each candidate
+//! carries how many of its name's words it shares with the product.
+
+use crate::{
+    catalog_metrics::observe_qdrant,
+    errors::Result,
+    handlers::{
+        QDRANT_COLLECTION_NAME, find_product_by_id, product_point_id, product_vector,
+        rank_by_score, scored_barcodes,
+    },
+    models::{Product, name_tokens},
+    state::{AppState, Clients},
+};
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use qdrant_client::qdrant::{Condition, Filter, SearchPointsBuilder};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, sync::Arc};
+use tracing::{debug, info, instrument};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+use yoloeats_domain::ErrorBody;
+
+/// How similar a vector must be to count as a duplicate without `min_score`.
+pub const DEFAULT_MIN_SCORE: f32 = 0.97;
+
+const DEFAULT_LIMIT: u64 = 10;
+const MAX_LIMIT: u64 = 50;
+
+/// Candidates fetched from Qdrant per duplicate asked for, so that products missing from
+/// MongoDB still leave enough to fill the list.
+const OVERFETCH: u64 = 2;
+
+/// Query of `GET /api/v1/products/{id}/duplicates`.
+#[derive(Debug, Default, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DuplicateParams {
+    /// The least vector similarity of a duplicate, 0 to 1; 0.97 when absent.
+    #[validate(range(min = 0.0, max = 1.0, message = "min_score must be between 0 and 1"))]
+    pub min_score: Option<f32>,
+    /// Most candidates to return, 10 when absent and capped at 50.
+    #[validate(range(min = 1, message = "limit must be at least 1"))]
+    pub limit: Option<u64>,
+}
+
+/// How the candidates were found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateMatch {
+    /// Their vectors are at least `min_score` similar to the product's.
+    Vector,
+    /// The product has no vector; their names have the same words as its name.
+    Name,
+}
+
+/// A product that may be a duplicate.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DuplicateCandidate {
+    #[serde(flatten)]
+    pub product: Product,
+    /// Vector similarity to the product, `null` for name matches.
+    pub score: Option<f32>,
+    /// The share of the words in either name that are in both, 0 to 1.
+    pub name_overlap: f32,
+}
+
+/// Response of `GET /api/v1/products/{id}/duplicates`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Duplicates {
+    pub matched_by: DuplicateMatch,
+    /// Most similar first for vector matches, in `_id` order for name matches.
+    pub candidates: Vec<DuplicateCandidate>,
+}
+
+/// The share of the [`name_tokens`] of either name that both have: 1 for names with the
+/// same words, 0 when they share none or either has no name.
+pub fn name_overlap(a: Option<&str>, b: Option<&str>) -> f32 {
+    let words = |name: Option<&str>| -> BTreeSet<String> {
+        name.map(name_tokens)
+            .unwrap_or_default()
+            .into_iter()
+            .collect()
+    };
+    let (a, b) = (words(a), words(b));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f32 / union as f32
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/products/{id}/duplicates",
+    tag = "v1",
+    params(
+        ("id" = String, Path, description = "The product's ObjectId."),
+        DuplicateParams,
+    ),
+    responses(
+        (status = 200, description = "Products that may be duplicates of this one, and how they were found.", body = Duplicates),
+        (status = 400, description = "An invalid id, `min_score` or `limit`.", body = ErrorBody),
+        (status = 404, description = "No product with this id.", body = ErrorBody),
+        (status = 500, description = "Qdrant or MongoDB failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state), fields(product_id = %id_str))]
+pub async fn get_duplicates(
+    State(state): State<Arc<AppState>>,
+    Path(id_str): Path<String>,
+    Query(params): Query<DuplicateParams>,
+) -> Result<Json<Duplicates>> {
+    params.validate()?;
+    let min_score = params.min_score.unwrap_or(DEFAULT_MIN_SCORE);
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+    let product = find_product_by_id(&state, &id_str).await?;
+
+    let indexed = match &state.clients {
+        Some(clients) => product_vector(clients, &id_str)
+            .await?
+            .map(|vector| (clients, vector)),
+        None => None,
+    };
+    let duplicates = match indexed {
+        Some((clients, vector)) => {
+            similar_vectors(&state, clients, &product, &id_str, vector, min_score, limit).await?
+        }
+        None => same_name(&state, &product, limit).await?,
+    };
+    info!(
+        "Returning {} duplicate candidates matched by {:?}",
+        duplicates.candidates.len(),
+        duplicates.matched_by
+    );
+    Ok(Json(duplicates))
+}
+
+/// The products whose vectors are at least `min_score` similar to `vector`, the source
+/// product's.
+async fn similar_vectors(
+    state: &AppState,
+    clients: &Clients,
+    product: &Product,
+    id_str: &str,
+    vector: Vec<f32>,
+    min_score: f32,
+    limit: u64,
+) -> Result<Duplicates> {
+    let source = Condition::has_id([product_point_id(id_str)]);
+    let search = SearchPointsBuilder::new(QDRANT_COLLECTION_NAME, vector, limit * OVERFETCH)
+        .filter(Filter::must_not([source]))
+        .score_threshold(min_score)
+        .with_payload(true);
+    let search_result =
+        observe_qdrant("search_points", clients.qdrant_client.search_points(search)).await?;
+    let candidates: Vec<(String, f32)> = scored_barcodes(search_result.result)
+        .into_iter()
+        .filter(|(code, _)| *code != product.code)
+        .collect();
+    debug!("Duplicate candidates from Qdrant: {:?}", candidates);
+
+    let codes = candidates.iter().map(|(code, _)| code.clone()).collect();
+    let found = state
+        .products
+        .find_by_codes(codes, candidates.len())
+        .await?;
+    let candidates = rank_by_score(&candidates, found, limit as usize)
+        .into_iter()
+        .map(|similar| candidate(product, similar.product, Some(similar.score)))
+        .collect();
+    Ok(Duplicates {
+        matched_by: DuplicateMatch::Vector,
+        candidates,
+    })
+}
+
+/// The products whose names have the same words as the source product's name.
+async fn same_name(state: &AppState, product: &Product, limit: u64) -> Result<Duplicates> {
+    let tokens = product
+        .product_name
+        .as_deref()
+        .map(name_tokens)
+        .unwrap_or_default();
+    debug!("Matching duplicates by name tokens: {:?}", tokens);
+    let candidates = match product.id {
+        Some(id) => {
+            state
+                .products
+                .find_by_name_tokens(&tokens, id, limit)
+                .await?
+        }
+        None => Vec::new(),
+    };
+    Ok(Duplicates {
+        matched_by: DuplicateMatch::Name,
+        candidates: candidates
+            .into_iter()
+            .map(|found| candidate(product, found, None))
+            .collect(),
+    })
+}
+
+fn candidate(source: &Product, product: Product, score: Option<f32>) -> DuplicateCandidate {
+    DuplicateCandidate {
+        name_overlap: name_overlap(
+            source.product_name.as_deref(),
+            product.product_name.as_deref(),
+        ),
+        product,
+        score,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_overlap_is_the_share_of_shared_words() {
+        assert_eq!(
+            name_overlap(
+                Some("Ritter Sport Alpenmilch"),
+                Some("ritter sport - ALPENMILCH")
+            ),
+            1.0
+        );
+        assert_eq!(
+            name_overlap(Some("Ritter Sport Alpenmilch"), Some("Ritter Sport Nuss")),
+            0.5
+        );
+        assert_eq!(name_overlap(Some("Nutella"), Some("Milka")), 0.0);
+        assert_eq!(name_overlap(Some("Nutella"), None), 0.0);
+        assert_eq!(name_overlap(None, None), 0.0);
+        // Repeated words count once.
+        assert_eq!(name_overlap(Some("Choco choco"), Some("Choco")), 1.0);
+    }
+
+    #[test]
+    fn duplicate_params_reject_nonsense() {
+        let params = |min_score, limit| DuplicateParams { min_score, limit };
+        assert!(params(None, None).validate().is_ok());
+        assert!(params(Some(0.0), Some(500)).validate().is_ok());
+        assert!(params(Some(1.0), Some(1)).validate().is_ok());
+        assert!(params(Some(1.01), None).validate().is_err());
+        assert!(params(Some(-0.5), None).validate().is_err());
+        assert!(params(None, Some(0)).validate().is_err());
+    }
+}
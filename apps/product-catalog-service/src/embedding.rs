@@ -0,0 +1,325 @@
+//! How texts become vectors of the `product_vectors` index: semantic and hybrid search
+//! embed their queries, [`crate::vector_sync`] and [`crate::reindex`] the products, all
+//! through the [`EmbeddingClient`] in [`AppState::embeddings`](crate::state::AppState).
+//!
+//! [`HttpEmbeddings`] calls the service the sync worker embeds with, `POST
+//! {EMBEDDING_SERVICE_URL}/embed` with `{"texts": [...]}`, [`BATCH_SIZE`] texts at a
+//! time. The model takes its time, so each call waits [`EMBEDDING_TIMEOUT_MS`] rather
+//! than the upstream client's usual timeout. The upstream client never retries a POST,
+//! but embedding the same texts again is harmless, so timeouts, transport errors and
+//! 5xx are tried [`RETRY_POLICY`]`.attempts` times here. [`FakeEmbeddings`] stands in for
+//! the service in tests.
+//!
+//! Every vector has the client's [`EmbeddingClient::dimensions`], `QDRANT_VECTOR_SIZE`,
+//! which startup checks against the collection's (see [`crate::qdrant_setup`]). Anything
+//! else, like a vector of the wrong length or a service that keeps failing, is a
+//! [`ServiceError::Embedding`], which callers that can do without vectors fall back on.
+
+use crate::{
+    errors::{Result, ServiceError},
+    models::name_tokens,
+    tunables::EMBEDDING_TIMEOUT_MS,
+};
+use async_trait::async_trait;
+use rust_database_clients::{
+    RetryPolicy,
+    http_resilience::{ResilientClient, UpstreamError, UpstreamErrorKind},
+    retry,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::debug;
+use yoloeats_dynamic_config::DynamicConfig;
+
+/// Base URL of the embedding service; optional, as only text queries and indexing need
+/// it.
+pub const EMBEDDING_SERVICE_URL_ENV: &str = "EMBEDDING_SERVICE_URL";
+
+/// The most texts [`HttpEmbeddings`] sends in one request.
+pub const BATCH_SIZE: usize = 32;
+
+/// How often [`HttpEmbeddings`] tries a batch, and how far apart.
+pub const RETRY_POLICY: RetryPolicy = RetryPolicy {
+    attempts: 3,
+    base_delay: Duration::from_millis(200),
+};
+
+/// Turns texts into vectors of the index's model.
+#[async_trait]
+pub trait EmbeddingClient: Send + Sync {
+    /// One vector per text, in their order, each of [`dimensions`](Self::dimensions).
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>>;
+
+    /// The length of every vector.
+    fn dimensions(&self) -> u64;
+}
+
+#[derive(Serialize)]
+struct EmbedRequest<'a> {
+    texts: &'a [String],
+}
+
+#[derive(Deserialize)]
+struct EmbedResponse {
+    vectors: Vec<Vec<f32>>,
+}
+
+/// The embedding service at [`EMBEDDING_SERVICE_URL_ENV`].
+pub struct HttpEmbeddings {
+    url: String,
+    client: ResilientClient,
+    config: DynamicConfig,
+    dimensions: u64,
+}
+
+impl HttpEmbeddings {
+    /// The service at `base_url`, called through `client` and waiting the
+    /// [`EMBEDDING_TIMEOUT_MS`] of `config`, whose vectors have `dimensions`.
+    pub fn new(
+        base_url: &str,
+        client: ResilientClient,
+        config: DynamicConfig,
+        dimensions: u64,
+    ) -> Self {
+        HttpEmbeddings {
+            url: format!("{}/embed", base_url.trim_end_matches('/')),
+            client,
+            config,
+            dimensions,
+        }
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let timeout = Duration::from_millis(self.config.get(EMBEDDING_TIMEOUT_MS));
+        let request = EmbedRequest { texts };
+        let response: EmbedResponse = retry(RETRY_POLICY, is_transient, |attempt| {
+            if attempt > 1 {
+                debug!("Embedding {} texts, attempt {}", texts.len(), attempt);
+            }
+            self.client
+                .post_json_with_timeout(&self.url, &request, timeout)
+        })
+        .await
+        .map_err(|e| ServiceError::Embedding(format!("The embedding service failed: {}", e)))?;
+        checked_vectors(response.vectors, texts.len(), self.dimensions)
+    }
+}
+
+#[async_trait]
+impl EmbeddingClient for HttpEmbeddings {
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let mut vectors = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(BATCH_SIZE) {
+            vectors.extend(self.embed_batch(batch).await?);
+        }
+        Ok(vectors)
+    }
+
+    fn dimensions(&self) -> u64 {
+        self.dimensions
+    }
+}
+
+/// Timeouts, transport errors and 5xx; an open breaker or a 4xx won't pass on a retry.
+fn is_transient(error: &UpstreamError) -> bool {
+    match error.kind {
+        UpstreamErrorKind::Timeout | UpstreamErrorKind::Transport => true,
+        UpstreamErrorKind::Status(status) => status >= 500,
+        UpstreamErrorKind::Open => false,
+    }
+}
+
+/// `vectors` if there are `texts` of them, each of `dimensions`.
+fn checked_vectors(vectors: Vec<Vec<f32>>, texts: usize, dimensions: u64) -> Result<Vec<Vec<f32>>> {
+    if vectors.len() != texts {
+        return Err(ServiceError::Embedding(format!(
+            "The embedding service returned {} vectors for {} texts",
+            vectors.len(),
+            texts
+        )));
+    }
+    if let Some(vector) = vectors.iter().find(|v| v.len() as u64 != dimensions) {
+        return Err(ServiceError::Embedding(format!(
+            "The embedding service returned a vector of {} dimensions, not {}",
+            vector.len(),
+            dimensions
+        )));
+    }
+    Ok(vectors)
+}
+
+/// Vectors made up on the spot for tests: each word of a text, as [`name_tokens`] splits
+/// it, adds 1 to a component picked by its hash, so equal texts get equal vectors and
+/// texts sharing words similar ones. A text without words gets the first unit vector.
+pub struct FakeEmbeddings {
+    dimensions: u64,
+}
+
+impl FakeEmbeddings {
+    pub fn new(dimensions: u64) -> Self {
+        FakeEmbeddings {
+            dimensions: dimensions.max(1),
+        }
+    }
+
+    fn vector(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0; self.dimensions as usize];
+        let words = name_tokens(text);
+        if words.is_empty() {
+            vector[0] = 1.0;
+        }
+        for word in words {
+            // FNV-1a, which unlike the standard library's hasher is the same in every run.
+            let hash = word.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+            });
+            vector[(hash % self.dimensions) as usize] += 1.0;
+        }
+        vector
+    }
+}
+
+#[async_trait]
+impl EmbeddingClient for FakeEmbeddings {
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        Ok(texts.iter().map(|text| self.vector(text)).collect())
+    }
+
+    fn dimensions(&self) -> u64 {
+        self.dimensions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tunables;
+    use rust_database_clients::http_resilience::ResilienceConfig;
+    use serde_json::{Value, json};
+    use wiremock::{
+        Mock, MockServer, Request, ResponseTemplate,
+        matchers::{method, path},
+    };
+    use yoloeats_dynamic_config::MemoryStore;
+
+    const DIMENSIONS: u64 = 4;
+
+    fn texts(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("text {}", i)).collect()
+    }
+
+    fn client(server: &MockServer) -> HttpEmbeddings {
+        HttpEmbeddings::new(
+            &format!("{}/", server.uri()),
+            ResilientClient::new(reqwest::Client::new(), ResilienceConfig::default()),
+            tunables::config(MemoryStore::default()).unwrap(),
+            DIMENSIONS,
+        )
+    }
+
+    /// Answers each request with a vector per text, the text's index in the request first.
+    fn vectors_for(request: &Request) -> ResponseTemplate {
+        let body: Value = serde_json::from_slice(&request.body).unwrap();
+        let vectors: Vec<Vec<f32>> = body["texts"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .map(|(i, _)| vec![i as f32, 0.0, 0.0, 1.0])
+            .collect();
+        ResponseTemplate::new(200).set_body_json(json!({ "vectors": vectors }))
+    }
+
+    #[tokio::test]
+    async fn texts_are_embedded_in_batches_and_in_order() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/embed"))
+            .respond_with(vectors_for)
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let vectors = client(&server).embed(&texts(BATCH_SIZE + 3)).await.unwrap();
+        assert_eq!(vectors.len(), BATCH_SIZE + 3);
+        assert_eq!(vectors[BATCH_SIZE - 1][0], (BATCH_SIZE - 1) as f32);
+        assert_eq!(vectors[BATCH_SIZE + 2][0], 2.0);
+    }
+
+    #[tokio::test]
+    async fn failing_batches_are_retried() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(vectors_for)
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let vectors = client(&server).embed(&texts(2)).await.unwrap();
+        assert_eq!(vectors.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn failures_and_unfit_vectors_are_embedding_errors() {
+        let answers = [
+            (ResponseTemplate::new(503), RETRY_POLICY.attempts as u64),
+            (ResponseTemplate::new(400), 1),
+            (
+                ResponseTemplate::new(200).set_body_json(json!({ "vectors": [[1.0, 0.0]] })),
+                1,
+            ),
+            (
+                ResponseTemplate::new(200).set_body_json(json!({ "vectors": [] })),
+                1,
+            ),
+            (
+                ResponseTemplate::new(200).set_body_string("busy"),
+                RETRY_POLICY.attempts as u64,
+            ),
+        ];
+        for (answer, calls) in answers {
+            let server = MockServer::start().await;
+            Mock::given(method("POST"))
+                .respond_with(answer)
+                .expect(calls)
+                .mount(&server)
+                .await;
+            let result = client(&server).embed(&texts(1)).await;
+            assert!(
+                matches!(result, Err(ServiceError::Embedding(_))),
+                "{:?}",
+                result
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn fake_vectors_are_deterministic_and_near_for_shared_words() {
+        let fake = FakeEmbeddings::new(64);
+        let vectors = fake
+            .embed(&[
+                "Milk chocolate".to_string(),
+                "milk CHOCOLATE!".to_string(),
+                "Dark chocolate".to_string(),
+                String::new(),
+            ])
+            .await
+            .unwrap();
+        assert!(vectors.iter().all(|vector| vector.len() == 64));
+        assert_eq!(vectors[0], vectors[1]);
+        assert_ne!(vectors[0], vectors[2]);
+        assert_eq!(vectors[0].iter().sum::<f32>(), 2.0);
+        assert_eq!(vectors[3][0], 1.0);
+        assert_eq!(fake.dimensions(), 64);
+        assert_eq!(
+            fake.embed(&["Milk chocolate".to_string()]).await.unwrap()[0],
+            vectors[0]
+        );
+    }
+}
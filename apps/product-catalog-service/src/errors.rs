@@ -1,11 +1,20 @@
 use axum::{
-    http::StatusCode,
+    http::{HeaderValue, StatusCode, header::RETRY_AFTER},
     response::{IntoResponse, Json, Response},
 };
 use qdrant_client::QdrantError;
+use rust_database_clients::http_resilience::UpstreamError;
 use serde_json::json;
+use std::time::Duration;
 use thiserror::Error;
 use tracing::error;
+use yoloeats_domain::{
+    ErrorBody, ErrorCode,
+    validation::{validation_details, validation_summary},
+};
+use yoloeats_metrics::{RouteLimit, retry_after_secs};
+use yoloeats_pagination::PaginationError;
+use yoloeats_tracing::current_request_id;
 
 #[derive(Error, Debug)]
 pub enum ServiceError {
@@ -27,6 +36,21 @@ pub enum ServiceError {
     #[error("HTTP request error: {0}")]
     Reqwest(#[from] reqwest::Error),
 
+    #[error("Upstream service error: {0}")]
+    Upstream(#[from] UpstreamError),
+
+    #[error("Semantic search unavailable: {0}")]
+    SemanticSearchUnavailable(String),
+
+    #[error("Embedding failed: {0}")]
+    Embedding(String),
+
+    #[error("Category taxonomy unavailable: {0}")]
+    TaxonomyUnavailable(String),
+
+    #[error("Text search failed: the products text index is missing")]
+    TextIndexMissing,
+
     #[error("BSON serialization error: {0}")]
     BsonSerialize(#[from] mongodb::bson::ser::Error),
 
@@ -48,9 +72,30 @@ pub enum ServiceError {
     #[error("Invalid input: {0}")]
     BadRequest(String),
 
+    #[error("Input validation failed: {}", validation_summary(.0))]
+    Validation(#[from] validator::ValidationErrors),
+
+    #[error("Invalid product ID: {0}")]
+    InvalidProductId(String),
+
+    #[error("Invalid barcode: {0}")]
+    InvalidBarcode(String),
+
+    #[error("Invalid pagination: {0}")]
+    Pagination(#[from] PaginationError),
+
     #[error("Resource not found: {0}")]
     NotFound(String),
 
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Too many requests to {}: at most {} per minute", .route.group, .route.per_minute)]
+    RateLimited { route: RouteLimit, wait: Duration },
+
+    #[error("Resync required: {0}")]
+    ResyncRequired(String),
+
     #[error("Internal server error: {0}")]
     Internal(String),
 }
@@ -61,6 +106,9 @@ impl From<rust_database_clients::ConfigError> for ServiceError {
             rust_database_clients::ConfigError::MissingVariable(var) => {
                 ServiceError::MissingVariable(var)
             }
+            rust_database_clients::ConfigError::InvalidVariable(msg) => {
+                ServiceError::InvalidVariable(msg)
+            }
             rust_database_clients::ConfigError::Dotenv(e) => ServiceError::Dotenv(e),
         }
     }
@@ -68,11 +116,12 @@ impl From<rust_database_clients::ConfigError> for ServiceError {
 
 impl IntoResponse for ServiceError {
     fn into_response(self) -> Response {
-        let (status, error_message) = match &self {
+        let (status, code, error_message) = match &self {
             ServiceError::Io(e) => {
                 error!("IO error: {}", e);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::InternalError,
                     "Internal I/O error".to_string(),
                 )
             }
@@ -80,6 +129,7 @@ impl IntoResponse for ServiceError {
                 error!("MongoDB error: {}", e);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::DatabaseError,
                     "Database operation failed".to_string(),
                 )
             }
@@ -87,6 +137,7 @@ impl IntoResponse for ServiceError {
                 error!("Redis error: {}", e);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::CacheError,
                     "Cache operation failed".to_string(),
                 )
             }
@@ -94,6 +145,7 @@ impl IntoResponse for ServiceError {
                 error!("Qdrant client error: {}", e);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::DatabaseError,
                     "Vector DB operation failed".to_string(),
                 )
             }
@@ -101,6 +153,7 @@ impl IntoResponse for ServiceError {
                 error!("Neo4j client error: {}", e);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::DatabaseError,
                     "Graph DB operation failed".to_string(),
                 )
             }
@@ -108,13 +161,52 @@ impl IntoResponse for ServiceError {
                 error!("Reqwest HTTP client error: {}", e);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::UpstreamUnavailable,
                     "Internal network communication error".to_string(),
                 )
             }
+            ServiceError::Upstream(e) => {
+                error!("Upstream service error: {}", e);
+                (
+                    StatusCode::BAD_GATEWAY,
+                    ErrorCode::UpstreamUnavailable,
+                    "Upstream service unavailable".to_string(),
+                )
+            }
+            ServiceError::SemanticSearchUnavailable(msg) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                ErrorCode::UpstreamUnavailable,
+                msg.clone(),
+            ),
+            ServiceError::Embedding(msg) => {
+                error!("Embedding failed: {}", msg);
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    ErrorCode::UpstreamUnavailable,
+                    "The embedding service is unavailable".to_string(),
+                )
+            }
+            ServiceError::TaxonomyUnavailable(msg) => {
+                error!("Category taxonomy unavailable: {}", msg);
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    ErrorCode::UpstreamUnavailable,
+                    "The category taxonomy is unavailable".to_string(),
+                )
+            }
+            ServiceError::TextIndexMissing => {
+                error!("Text search failed: the products text index is missing");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::DatabaseError,
+                    "Text search is unavailable: text index missing".to_string(),
+                )
+            }
             ServiceError::BsonSerialize(e) => {
                 error!("BSON serialization error: {}", e);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::InternalError,
                     "Failed to serialize data".to_string(),
                 )
             }
@@ -122,6 +214,7 @@ impl IntoResponse for ServiceError {
                 error!("BSON deserialization error: {}", e);
                 (
                     StatusCode::BAD_REQUEST,
+                    ErrorCode::InvalidData,
                     "Failed to deserialize data".to_string(),
                 )
             }
@@ -129,6 +222,7 @@ impl IntoResponse for ServiceError {
                 error!("Configuration error: Problem with env var {}", var);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::ConfigurationError,
                     "Internal server configuration error".to_string(),
                 )
             }
@@ -136,6 +230,7 @@ impl IntoResponse for ServiceError {
                 error!("Configuration error: Dotenv error {}", e);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::ConfigurationError,
                     "Internal configuration error".to_string(),
                 )
             }
@@ -143,23 +238,324 @@ impl IntoResponse for ServiceError {
                 error!("Configuration error: Env var read error {}", e);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::ConfigurationError,
                     "Internal server configuration error".to_string(),
                 )
             }
-            ServiceError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
-            ServiceError::NotFound(msg) => (StatusCode::NOT_FOUND, msg.clone()),
+            ServiceError::BadRequest(msg) => (
+                StatusCode::BAD_REQUEST,
+                ErrorCode::InvalidRequest,
+                msg.clone(),
+            ),
+            // Query parameters; request bodies are checked by `ValidJson` and answer 422.
+            ServiceError::Validation(errors) => (
+                StatusCode::BAD_REQUEST,
+                ErrorCode::InvalidRequest,
+                format!("Input validation failed: {}", validation_summary(errors)),
+            ),
+            ServiceError::InvalidProductId(msg) => (
+                StatusCode::BAD_REQUEST,
+                ErrorCode::InvalidProductId,
+                msg.clone(),
+            ),
+            ServiceError::InvalidBarcode(msg) => (
+                StatusCode::BAD_REQUEST,
+                ErrorCode::InvalidBarcode,
+                msg.clone(),
+            ),
+            ServiceError::Pagination(e) => (
+                StatusCode::BAD_REQUEST,
+                ErrorCode::InvalidRequest,
+                e.to_string(),
+            ),
+            ServiceError::NotFound(msg) => (
+                StatusCode::NOT_FOUND,
+                ErrorCode::ProductNotFound,
+                msg.clone(),
+            ),
+            ServiceError::Conflict(msg) => (
+                StatusCode::CONFLICT,
+                ErrorCode::ProductConflict,
+                msg.clone(),
+            ),
+            // As `RateLimitLayer` answers, for limits checked in handlers.
+            ServiceError::RateLimited { route, wait } => (
+                StatusCode::TOO_MANY_REQUESTS,
+                ErrorCode::RateLimited,
+                format!(
+                    "Too many requests: at most {} per minute, retry in {} s",
+                    route.per_minute,
+                    retry_after_secs(*wait)
+                ),
+            ),
+            ServiceError::ResyncRequired(msg) => {
+                (StatusCode::GONE, ErrorCode::ResyncRequired, msg.clone())
+            }
             ServiceError::Internal(msg) => {
                 error!("Internal server error: {}", msg);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::InternalError,
                     "An internal error occurred".to_string(),
                 )
             }
         };
 
-        let body = Json(json!({ "error": error_message }));
-        (status, body).into_response()
+        let mut body = ErrorBody::new(code, error_message)
+            .with_request_id(current_request_id().map(|id| id.to_string()));
+        match &self {
+            ServiceError::Validation(errors) => {
+                body = body.with_details(validation_details(errors));
+            }
+            ServiceError::RateLimited { route, wait } => {
+                body = body.with_details(json!({ "limitPerMinute": route.per_minute }));
+                let retry_after = HeaderValue::from(retry_after_secs(*wait));
+                return (status, [(RETRY_AFTER, retry_after)], Json(body)).into_response();
+            }
+            _ => {}
+        }
+        (status, Json(body)).into_response()
+    }
+}
+
+impl From<ServiceError> for tonic::Status {
+    fn from(err: ServiceError) -> Self {
+        match err {
+            ServiceError::NotFound(msg) => tonic::Status::not_found(msg),
+            ServiceError::BadRequest(msg)
+            | ServiceError::InvalidProductId(msg)
+            | ServiceError::InvalidBarcode(msg) => tonic::Status::invalid_argument(msg),
+            ServiceError::Validation(errors) => {
+                tonic::Status::invalid_argument(validation_summary(&errors))
+            }
+            ServiceError::Conflict(msg) => tonic::Status::already_exists(msg),
+            ServiceError::RateLimited { .. } => tonic::Status::resource_exhausted(err.to_string()),
+            ServiceError::ResyncRequired(msg) => tonic::Status::failed_precondition(msg),
+            other => {
+                error!("Internal gRPC request failed: {}", other);
+                tonic::Status::internal("An internal error occurred")
+            }
+        }
     }
 }
 
 pub type Result<T> = std::result::Result<T, ServiceError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_database_clients::http_resilience::UpstreamErrorKind;
+    use serde_json::{Value, json};
+    use yoloeats_tracing::{RequestId, with_request_id};
+
+    async fn render(err: ServiceError) -> (StatusCode, Value) {
+        let id = RequestId::parse("req-1").unwrap();
+        let response = with_request_id(id, async move { err.into_response() }).await;
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    fn envelope(code: &str, message: &str) -> Value {
+        json!({
+            "code": code,
+            "message": message,
+            "error": message,
+            "requestId": "req-1"
+        })
+    }
+
+    #[tokio::test]
+    async fn error_envelope_json_snapshots() {
+        let cases = [
+            (
+                ServiceError::Io(std::io::Error::other("disk")),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                envelope("internal_error", "Internal I/O error"),
+            ),
+            (
+                ServiceError::MongoDb(std::io::Error::other("socket").into()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                envelope("database_error", "Database operation failed"),
+            ),
+            (
+                ServiceError::Redis((redis::ErrorKind::IoError, "refused").into()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                envelope("cache_error", "Cache operation failed"),
+            ),
+            (
+                ServiceError::Upstream(UpstreamError {
+                    kind: UpstreamErrorKind::Open,
+                    host: "user-profile-service".to_string(),
+                }),
+                StatusCode::BAD_GATEWAY,
+                envelope("upstream_unavailable", "Upstream service unavailable"),
+            ),
+            (
+                ServiceError::Embedding("timed out".to_string()),
+                StatusCode::SERVICE_UNAVAILABLE,
+                envelope(
+                    "upstream_unavailable",
+                    "The embedding service is unavailable",
+                ),
+            ),
+            (
+                ServiceError::TaxonomyUnavailable("Neo4j is down".to_string()),
+                StatusCode::SERVICE_UNAVAILABLE,
+                envelope(
+                    "upstream_unavailable",
+                    "The category taxonomy is unavailable",
+                ),
+            ),
+            (
+                ServiceError::TextIndexMissing,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                envelope(
+                    "database_error",
+                    "Text search is unavailable: text index missing",
+                ),
+            ),
+            (
+                ServiceError::BsonSerialize(serde::ser::Error::custom("bad")),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                envelope("internal_error", "Failed to serialize data"),
+            ),
+            (
+                ServiceError::BsonDeserialize(serde::de::Error::custom("bad")),
+                StatusCode::BAD_REQUEST,
+                envelope("invalid_data", "Failed to deserialize data"),
+            ),
+            (
+                ServiceError::MissingVariable("MONGO_URI".to_string()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                envelope("configuration_error", "Internal server configuration error"),
+            ),
+            (
+                ServiceError::InvalidVariable("PORT".to_string()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                envelope("configuration_error", "Internal server configuration error"),
+            ),
+            (
+                ServiceError::Dotenv(dotenvy::Error::EnvVar(std::env::VarError::NotPresent)),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                envelope("configuration_error", "Internal configuration error"),
+            ),
+            (
+                ServiceError::VarError(std::env::VarError::NotPresent),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                envelope("configuration_error", "Internal server configuration error"),
+            ),
+            (
+                ServiceError::BadRequest("limit must be positive".to_string()),
+                StatusCode::BAD_REQUEST,
+                envelope("invalid_request", "limit must be positive"),
+            ),
+            (
+                ServiceError::InvalidProductId("Invalid product ID format: xyz".to_string()),
+                StatusCode::BAD_REQUEST,
+                envelope("invalid_product_id", "Invalid product ID format: xyz"),
+            ),
+            (
+                ServiceError::InvalidBarcode("Invalid barcode '123'".to_string()),
+                StatusCode::BAD_REQUEST,
+                envelope("invalid_barcode", "Invalid barcode '123'"),
+            ),
+            (
+                ServiceError::NotFound("Product with barcode 123 not found".to_string()),
+                StatusCode::NOT_FOUND,
+                envelope("product_not_found", "Product with barcode 123 not found"),
+            ),
+            (
+                ServiceError::Conflict("Product with this code already exists.".to_string()),
+                StatusCode::CONFLICT,
+                envelope("product_conflict", "Product with this code already exists."),
+            ),
+            (
+                ServiceError::ResyncRequired("since is too old".to_string()),
+                StatusCode::GONE,
+                envelope("resync_required", "since is too old"),
+            ),
+            (
+                ServiceError::Internal("empty vector".to_string()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                envelope("internal_error", "An internal error occurred"),
+            ),
+        ];
+
+        for (err, expected_status, expected_body) in cases {
+            let label = format!("{:?}", err);
+            let (status, body) = render(err).await;
+            assert_eq!(status, expected_status, "{}", label);
+            assert_eq!(body, expected_body, "{}", label);
+        }
+    }
+
+    #[tokio::test]
+    async fn request_id_is_omitted_outside_a_request() {
+        let response = ServiceError::NotFound("gone".to_string()).into_response();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            body,
+            json!({ "code": "product_not_found", "message": "gone", "error": "gone" })
+        );
+    }
+
+    #[tokio::test]
+    async fn rate_limited_requests_get_retry_after_and_the_limit() {
+        let err = ServiceError::RateLimited {
+            route: RouteLimit {
+                group: "reports",
+                prefix: "/api/v1/products",
+                per_minute: 5,
+            },
+            wait: Duration::from_millis(41_200),
+        };
+        let response = err.into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[RETRY_AFTER], "42");
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["code"], "rate_limited");
+        assert_eq!(
+            body["message"],
+            "Too many requests: at most 5 per minute, retry in 42 s"
+        );
+        assert_eq!(body["details"], json!({ "limitPerMinute": 5 }));
+    }
+
+    #[tokio::test]
+    async fn validation_errors_carry_field_details() {
+        use validator::Validate;
+
+        let payload = crate::models::CreateProductPayload {
+            code: String::new(),
+            product_name: None,
+            ingredients_text: None,
+            ingredients: None,
+            brands: Some(vec!["b".to_string(); 51]),
+            categories: None,
+            image_url: None,
+            ecoscore_grade: None,
+            ecoscore_score: None,
+            nutriments: None,
+        };
+        let (status, body) = render(payload.validate().unwrap_err().into()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "invalid_request");
+        assert_eq!(
+            body["message"],
+            "Input validation failed: brands: At most 50 brands; code: Product code must be 1-64 characters"
+        );
+        assert_eq!(body["details"][0]["field"], "brands");
+        assert_eq!(body["details"][0]["params"], json!({ "max": 50 }));
+        assert_eq!(body["details"][1]["field"], "code");
+        assert_eq!(body["details"][1]["code"], "length");
+    }
+}
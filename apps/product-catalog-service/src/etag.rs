@@ -0,0 +1,247 @@
+//! Conditional GETs of a single product, by id or barcode.
+//!
+//! A product's ETag is weak, made of its id and `last_modified_datetime`, so every
+//! update changes it. It is cached in front of the product's JSON, which lets a request
+//! whose `If-None-Match` still matches get its `304 Not Modified` from the cache alone,
+//! without parsing the JSON. Entries cached before ETags were are read as plain JSON.
+//!
+//! A v2 product is named in the reader's language (see [`crate::language`]), so the ETag
+//! a v2 response carries has the languages the reader prefers folded in by
+//! [`localized_etag`], and [`IfNoneMatch::for_languages`] reads the client's tags back
+//! into the cached ones. A copy made for one language never passes for another. v1
+//! responses carry the stored ETag.
+
+use crate::models::Product;
+use axum::{
+    Json,
+    extract::FromRequestParts,
+    http::{
+        HeaderValue, StatusCode,
+        header::{ETAG, IF_NONE_MATCH},
+        request::Parts,
+    },
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::convert::Infallible;
+use tracing::warn;
+
+/// The weak ETag of the product as stored now.
+pub fn product_etag(product: &Product) -> String {
+    format!(
+        "W/\"{}-{}\"",
+        product.id.map(|id| id.to_hex()).unwrap_or_default(),
+        product.last_modified_at.timestamp_millis()
+    )
+}
+
+/// The ETag of a product shown to a reader who prefers `languages`: `etag` with the
+/// languages appended, `W/"{id}-{millis}-de-fr"`. Unchanged without languages.
+pub fn localized_etag(etag: &str, languages: &[String]) -> String {
+    match etag.strip_suffix('"') {
+        Some(open) if !languages.is_empty() => format!("{}-{}\"", open, languages.join("-")),
+        _ => etag.to_string(),
+    }
+}
+
+/// The stored ETag a [`localized_etag`] was made from: its id and time, with any
+/// languages dropped.
+fn stored_etag(tag: &str) -> Option<String> {
+    let opaque = tag.trim().trim_start_matches("W/");
+    let mut parts = opaque.strip_prefix('"')?.strip_suffix('"')?.splitn(3, '-');
+    let (id, millis) = (parts.next()?, parts.next()?);
+    Some(format!("W/\"{}-{}\"", id, millis))
+}
+
+/// The cache value for a product: its ETag and JSON on one line each. Compact JSON has
+/// no raw newlines, so the first one ends the ETag.
+pub(crate) fn encode_cached(etag: &str, json: &str) -> String {
+    format!("{}\n{}", etag, json)
+}
+
+/// The ETag and JSON of a cache value; no ETag for a value cached as plain JSON.
+pub(crate) fn decode_cached(value: &str) -> (Option<&str>, &str) {
+    match value.split_once('\n') {
+        Some((etag, json)) if etag.starts_with("W/\"") => (Some(etag), json),
+        _ => (None, value),
+    }
+}
+
+/// The request's `If-None-Match`, if any.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IfNoneMatch(Option<String>);
+
+impl IfNoneMatch {
+    pub fn new(value: &str) -> Self {
+        IfNoneMatch(Some(value.to_string()))
+    }
+
+    /// Whether `etag` is one of those listed, compared weakly as GET requires.
+    pub fn matches(&self, etag: &str) -> bool {
+        let Some(listed) = &self.0 else {
+            return false;
+        };
+        let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+        let etag = opaque(etag);
+        listed
+            .split(',')
+            .any(|tag| tag.trim() == "*" || opaque(tag) == etag)
+    }
+
+    /// The stored ETags of the tags listed that were made for `languages`; the rest,
+    /// made for other languages, can't match. `*` is kept.
+    pub fn for_languages(&self, languages: &[String]) -> IfNoneMatch {
+        let Some(listed) = &self.0 else {
+            return IfNoneMatch(None);
+        };
+        let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+        let kept: Vec<String> = listed
+            .split(',')
+            .filter_map(|tag| {
+                if tag.trim() == "*" {
+                    return Some("*".to_string());
+                }
+                let stored = stored_etag(tag)?;
+                (opaque(&localized_etag(&stored, languages)) == opaque(tag)).then_some(stored)
+            })
+            .collect();
+        IfNoneMatch(Some(kept.join(", ")).filter(|kept| !kept.is_empty()))
+    }
+}
+
+impl<S> FromRequestParts<S> for IfNoneMatch
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        Ok(IfNoneMatch(
+            parts
+                .headers
+                .get(IF_NONE_MATCH)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+        ))
+    }
+}
+
+/// A lookup answered under an `If-None-Match`: either the client's copy is current, or
+/// here is the value and its ETag.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Conditional<T> {
+    NotModified { etag: String },
+    Modified { etag: String, value: T },
+}
+
+impl<T> Conditional<T> {
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Conditional<U> {
+        match self {
+            Conditional::NotModified { etag } => Conditional::NotModified { etag },
+            Conditional::Modified { etag, value } => Conditional::Modified {
+                etag,
+                value: f(value),
+            },
+        }
+    }
+}
+
+/// `304` with just the ETag, or `200` with the value as JSON and its ETag.
+impl<T: Serialize> IntoResponse for Conditional<T> {
+    fn into_response(self) -> Response {
+        let (status, etag, body) = match self {
+            Conditional::NotModified { etag } => (StatusCode::NOT_MODIFIED, etag, None),
+            Conditional::Modified { etag, value } => (StatusCode::OK, etag, Some(Json(value))),
+        };
+        let mut response = match body {
+            Some(body) => (status, body).into_response(),
+            None => status.into_response(),
+        };
+        match HeaderValue::from_str(&etag) {
+            Ok(value) => {
+                response.headers_mut().insert(ETAG, value);
+            }
+            Err(e) => warn!("Not sending unrepresentable ETag {:?}: {}", etag, e),
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::header::VARY;
+    use yoloeats_domain::fixtures::ProductFixture;
+
+    #[test]
+    fn etag_changes_with_the_modification_time() {
+        let product: Product = ProductFixture::new("123").build();
+        let etag = product_etag(&product);
+        assert!(etag.starts_with("W/\""));
+        assert!(etag.contains(&product.id.unwrap().to_hex()));
+
+        let mut updated = product.clone();
+        updated.last_modified_at += chrono::Duration::milliseconds(1);
+        assert_ne!(product_etag(&updated), etag);
+    }
+
+    #[test]
+    fn cache_values_carry_the_etag_before_the_json() {
+        let json = r#"{"code":"123","ingredients_text":"a\nb"}"#;
+        let value = encode_cached("W/\"abc-1\"", json);
+        assert_eq!(decode_cached(&value), (Some("W/\"abc-1\""), json));
+        // Cached before ETags were.
+        assert_eq!(decode_cached(json), (None, json));
+    }
+
+    #[test]
+    fn if_none_match_compares_weakly_and_takes_lists() {
+        let etag = "W/\"abc-1\"";
+        assert!(IfNoneMatch::new("W/\"abc-1\"").matches(etag));
+        assert!(IfNoneMatch::new("\"abc-1\"").matches(etag));
+        assert!(IfNoneMatch::new("W/\"old-0\", W/\"abc-1\"").matches(etag));
+        assert!(IfNoneMatch::new("*").matches(etag));
+        assert!(!IfNoneMatch::new("W/\"abc-2\"").matches(etag));
+        assert!(!IfNoneMatch::default().matches(etag));
+    }
+
+    #[test]
+    fn etags_fold_in_the_languages() {
+        let etag = "W/\"abc-1\"";
+        let german = ["de".to_string()];
+        let german_then_french = ["de".to_string(), "fr".to_string()];
+        assert_eq!(localized_etag(etag, &[]), etag);
+        assert_eq!(localized_etag(etag, &german), "W/\"abc-1-de\"");
+        assert_eq!(
+            localized_etag(etag, &german_then_french),
+            "W/\"abc-1-de-fr\""
+        );
+
+        let sent = IfNoneMatch::new("W/\"abc-1-de\"");
+        assert!(sent.for_languages(&german).matches(etag));
+        assert!(!sent.for_languages(&german_then_french).matches(etag));
+        assert!(!sent.for_languages(&[]).matches(etag));
+        assert!(IfNoneMatch::new(etag).for_languages(&[]).matches(etag));
+        assert!(!IfNoneMatch::new(etag).for_languages(&german).matches(etag));
+        assert!(IfNoneMatch::new("*").for_languages(&german).matches(etag));
+        assert_eq!(
+            IfNoneMatch::default().for_languages(&german),
+            IfNoneMatch::default()
+        );
+    }
+
+    #[test]
+    fn not_modified_has_the_etag_and_no_body() {
+        let response = Conditional::<Product>::NotModified {
+            etag: "W/\"abc-1\"".to_string(),
+        }
+        .into_response();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[ETAG], "W/\"abc-1\"");
+        assert!(!response.headers().contains_key(VARY));
+        assert!(!response.headers().contains_key("content-type"));
+    }
+}
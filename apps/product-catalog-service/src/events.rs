@@ -0,0 +1,171 @@
+//! Product change events on the Redis channel [`PRODUCT_EVENTS_CHANNEL`], for the
+//! services that hold product data of their own, like the allergy checker's caches, to
+//! drop it when it changes.
+//!
+//! Every create, update, patch and delete through the API publishes a compact
+//! [`ProductEvent`] naming the changed fields as the [history](crate::audit) does. It is
+//! pub/sub: only subscribers listening at the time get the event, and nothing is kept
+//! for later. Publishing is best effort; a failure is logged and the product change
+//! still answers with success. Bulk imports publish nothing, and with
+//! `STORAGE_MODE=memory` there is no Redis to publish to.
+
+use crate::{
+    audit::{self, AuditAction},
+    errors::Result,
+    models::Product,
+    state::AppState,
+};
+use chrono::{DateTime, Utc};
+use futures::stream::{BoxStream, StreamExt};
+use redis::{AsyncCommands, Client as RedisClient};
+use rust_database_clients::serde_helpers::chrono_datetime_as_rfc3339_or_bson;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+pub const PRODUCT_EVENTS_CHANNEL: &str = "yoloeats.products.events";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProductEvent {
+    pub event: AuditAction,
+    /// The product's ObjectId.
+    pub id: String,
+    pub code: String,
+    /// The stored names of the fields that changed; all the product has for creates and
+    /// deletes.
+    pub changed_fields: Vec<String>,
+    #[serde(with = "chrono_datetime_as_rfc3339_or_bson")]
+    pub ts: DateTime<Utc>,
+}
+
+/// The event of a change from `before` to `after`, at least one of which is the product.
+/// `None` for an update that changed nothing and for a product without an id.
+pub fn product_event(
+    before: Option<&Product>,
+    after: Option<&Product>,
+    ts: DateTime<Utc>,
+) -> Option<ProductEvent> {
+    let (event, product) = audit::action(before, after)?;
+    let id = product.id?;
+    let changed_fields: Vec<String> = audit::diff(before, after)
+        .into_iter()
+        .map(|change| change.field)
+        .collect();
+    if event == AuditAction::Updated && changed_fields.is_empty() {
+        return None;
+    }
+    Some(ProductEvent {
+        event,
+        id: id.to_hex(),
+        code: product.code.clone(),
+        changed_fields,
+        ts,
+    })
+}
+
+/// Publishes the change from `before` to `after`. A failure is only logged.
+pub(crate) async fn publish(state: &AppState, before: Option<&Product>, after: Option<&Product>) {
+    let Some(clients) = &state.clients else {
+        return;
+    };
+    let Some(event) = product_event(before, after, Utc::now()) else {
+        debug!("No product event to publish");
+        return;
+    };
+    if let Err(e) = send(&clients.redis_client, &event).await {
+        warn!(id = %event.id, event = ?event.event, "Failed to publish product event: {}", e);
+    }
+}
+
+async fn send(redis: &RedisClient, event: &ProductEvent) -> Result<()> {
+    let payload = serde_json::to_string(event).expect("product events serialize");
+    let mut conn = redis.get_multiplexed_async_connection().await?;
+    let receivers: i64 = conn.publish(PRODUCT_EVENTS_CHANNEL, payload).await?;
+    debug!(id = %event.id, "Published product event to {} subscribers", receivers);
+    Ok(())
+}
+
+/// The events published from now on, for consumers of the channel. A message that is
+/// not an event is logged and skipped.
+pub async fn subscribe(redis: &RedisClient) -> Result<BoxStream<'static, ProductEvent>> {
+    let mut pubsub = redis.get_async_pubsub().await?;
+    pubsub.subscribe(PRODUCT_EVENTS_CHANNEL).await?;
+    Ok(pubsub
+        .into_on_message()
+        .filter_map(|message| async move {
+            let payload: String = message.get_payload().ok()?;
+            serde_json::from_str(&payload)
+                .inspect_err(|e| warn!("Skipping a malformed product event: {}", e))
+                .ok()
+        })
+        .boxed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use yoloeats_domain::fixtures::ProductFixture;
+
+    fn stored() -> Product {
+        ProductFixture::new("4000417025005")
+            .named("Alpine milk chocolate")
+            .build()
+    }
+
+    #[test]
+    fn events_name_the_changed_fields() {
+        let before = stored();
+        let mut after = before.clone();
+        after.product_name = Some("Alpine dark chocolate".to_string());
+        after.last_modified_at = before.last_modified_at + chrono::Duration::seconds(5);
+
+        let event = product_event(Some(&before), Some(&after), Utc::now()).unwrap();
+        assert_eq!(event.event, AuditAction::Updated);
+        assert_eq!(event.id, before.id.unwrap().to_hex());
+        assert_eq!(event.code, "4000417025005");
+        assert_eq!(event.changed_fields, ["product_name"]);
+
+        let created = product_event(None, Some(&before), Utc::now()).unwrap();
+        assert_eq!(created.event, AuditAction::Created);
+        assert!(created.changed_fields.contains(&"product_name".to_string()));
+        let deleted = product_event(Some(&before), None, Utc::now()).unwrap();
+        assert_eq!(deleted.event, AuditAction::Deleted);
+        assert_eq!(deleted.changed_fields, created.changed_fields);
+    }
+
+    #[test]
+    fn unchanged_updates_and_unsaved_products_have_no_event() {
+        let product = stored();
+        assert_eq!(
+            product_event(Some(&product), Some(&product), Utc::now()),
+            None
+        );
+        let unsaved = Product {
+            id: None,
+            ..product
+        };
+        assert_eq!(product_event(None, Some(&unsaved), Utc::now()), None);
+        assert_eq!(product_event(None, None, Utc::now()), None);
+    }
+
+    #[test]
+    fn events_are_compact_json() {
+        let product = stored();
+        let ts = DateTime::parse_from_rfc3339("2024-06-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let event = product_event(Some(&product), None, ts).unwrap();
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "event": "deleted",
+                "id": product.id.unwrap().to_hex(),
+                "code": "4000417025005",
+                "changed_fields": event.changed_fields,
+                "ts": "2024-06-01T12:00:00.000Z",
+            })
+        );
+        assert_eq!(serde_json::from_value::<ProductEvent>(json).unwrap(), event);
+    }
+}
@@ -0,0 +1,36 @@
+use crate::{handlers::find_product_by_barcode, state::AppState};
+use std::sync::Arc;
+use tonic::{Request, Response, Status};
+use tracing::instrument;
+use yoloeats_domain::ProductSummary;
+use yoloeats_proto::v1::{
+    self, GetProductSummaryRequest,
+    product_service_server::{ProductService, ProductServiceServer},
+};
+
+/// Internal gRPC view of the catalog for other services, served on its own port.
+pub struct ProductGrpc {
+    state: Arc<AppState>,
+}
+
+impl ProductGrpc {
+    pub fn server(state: Arc<AppState>) -> ProductServiceServer<Self> {
+        ProductServiceServer::new(ProductGrpc { state })
+    }
+}
+
+#[tonic::async_trait]
+impl ProductService for ProductGrpc {
+    #[instrument(skip(self, request), fields(code = %request.get_ref().code))]
+    async fn get_product_summary(
+        &self,
+        request: Request<GetProductSummaryRequest>,
+    ) -> Result<Response<v1::ProductSummary>, Status> {
+        let code = request.into_inner().code;
+        if code.trim().is_empty() {
+            return Err(Status::invalid_argument("code must not be empty"));
+        }
+        let product = find_product_by_barcode(&self.state, &code).await?;
+        Ok(Response::new(ProductSummary::from(product).into()))
+    }
+}
@@ -1,571 +1,1568 @@
 use crate::{
+    allergen_extraction::extract_allergens,
+    audit::{self, Actor},
+    barcode, cascade,
+    catalog_metrics::{CacheOutcome, observe_qdrant, record_cache_lookup},
+    default_country::{DefaultCountry, scope_countries},
     errors::{Result, ServiceError},
-    models::{CreateProductPayload, Product, SearchParams, UpdateProductPayload},
-    state::AppState,
+    etag::{Conditional, IfNoneMatch, decode_cached, encode_cached, product_etag},
+    events, images,
+    models::{
+        AllergenMode, BatchLookupPayload, BatchLookupResponse, CreateProductPayload,
+        PatchProductPayload, Product, RecommendationParams, SearchHit, SearchParams, SearchSort,
+        SearchSummary, SearchView, UpdateProductPayload, ecoscore_grade,
+    },
+    nutriscore::{self, COMPUTED_GRADE_SOURCE},
+    off_fallback::{self, FetchedRemotely},
+    popularity,
+    projection::{ProductResponseParams, ProductShape, schema_fields},
+    repository::{ProductChanges, ProductField, ProductFilter, SearchFrom},
+    search_cache::{cached_hits, search_cache_key},
+    state::{AppState, Clients},
+    suggestions,
+    taxonomy::{
+        allergen_tags, declared_allergen_tags, diet_exclusion_tags, ingredient_hints,
+        normalize_tags,
+    },
+    tunables::{
+        ALLOW_INTERNAL_CODES, BARCODE_CACHE_TTL_SECS, COUNT_CACHE_TTL_SECS,
+        NEGATIVE_CACHE_TTL_SECS, PRODUCT_CACHE_TTL_SECS, RECOMMENDATION_LIMIT, cache_ttl,
+    },
+    vector_sync,
+    webhooks::{self, EventType},
 };
 use axum::{
     Json,
-    extract::{Path, Query, State},
-    http::StatusCode,
+    extract::{FromRequestParts, Path, Query, State},
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode, header::AUTHORIZATION, request::Parts},
+    response::{IntoResponseParts, ResponseParts},
 };
-use bson::{doc, oid::ObjectId};
+use bson::oid::ObjectId;
 use chrono::Utc;
-use futures::stream::TryStreamExt;
-use mongodb::{
-    error::ErrorKind,
-    options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument},
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::{
+    Arc, LazyLock,
+    atomic::{AtomicBool, Ordering},
 };
-use redis::AsyncCommands;
-use std::collections::HashSet;
-use std::sync::Arc;
 use tracing::{debug, error, info, instrument, warn};
+use utoipa::ToSchema;
 
 use qdrant_client::qdrant::{
-    Condition, FieldCondition, Filter, GetPointsBuilder, HasIdCondition, Match, PointId,
-    RepeatedStrings, SearchPoints, WithPayloadSelector, condition::ConditionOneOf,
-    r#match::MatchValue, value::Kind, vectors_output,
+    Condition, FieldCondition, Filter, GetPointsBuilder, HasIdCondition, IsEmptyCondition,
+    IsNullCondition, PointId, RepeatedStrings, RetrievedPoint, ScoredPoint, SearchPoints,
+    WithPayloadSelector, condition::ConditionOneOf, r#match::MatchValue, point_id::PointIdOptions,
+    value::Kind, vector_output, vectors_output,
+};
+use rust_database_clients::{
+    CacheConnection,
+    http_resilience::{ResilientClient, UpstreamError, UpstreamErrorKind},
 };
-use reqwest::StatusCode as HttpStatus;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use uuid::Uuid;
+use validator::Validate;
+use yoloeats_domain::{ErrorBody, SafetyProfile, tags};
+use yoloeats_dynamic_config::Tunable;
+use yoloeats_metrics::ValidJson;
+use yoloeats_pagination::{Page, PageLimit, PageParams};
+
+/// Page sizes for [`search_products`].
+pub struct SearchPageLimit;
+
+impl PageLimit for SearchPageLimit {
+    const DEFAULT: u64 = 20;
+    const MAX: u64 = 100;
+}
 
-const CACHE_EXPIRATION_SECONDS: u64 = 300;
-const DEFAULT_SEARCH_LIMIT: u64 = 20;
-const MAX_SEARCH_LIMIT: u64 = 100;
+/// A search page in the view it was asked for.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum SearchResults {
+    Full(Page<SearchHit>),
+    Summary(Page<SearchSummary>),
+}
 
-const QDRANT_COLLECTION_NAME: &str = "product_vectors";
-const QDRANT_CODE_PAYLOAD_KEY: &str = "code";
+impl SearchResults {
+    /// Whether the page lists no products.
+    pub fn is_empty(&self) -> bool {
+        match self {
+            SearchResults::Full(page) => page.items.is_empty(),
+            SearchResults::Summary(page) => page.items.is_empty(),
+        }
+    }
+
+    /// The page's `total`, if it was counted.
+    pub fn total(&self) -> Option<u64> {
+        match self {
+            SearchResults::Full(page) => page.total,
+            SearchResults::Summary(page) => page.total,
+        }
+    }
+
+    /// The page with a display name for each product, in the first of `preferred` it
+    /// has a name in; see [`crate::language`].
+    pub fn named_for(self, preferred: &[String]) -> Self {
+        match self {
+            SearchResults::Full(page) => {
+                SearchResults::Full(page.map(|hit| hit.named_for(preferred)))
+            }
+            SearchResults::Summary(page) => {
+                SearchResults::Summary(page.map(|summary| summary.named_for(preferred)))
+            }
+        }
+    }
+
+    /// The page with the highlights of the search's `q` when `highlight=true` asks for
+    /// them, and as it is otherwise; see [`crate::highlight`].
+    pub fn highlighted_for(self, params: &SearchParams) -> Self {
+        let q = match params.q.as_deref().map(str::trim) {
+            Some(q) if params.highlight && !q.is_empty() => q,
+            _ => return self,
+        };
+        match self {
+            SearchResults::Full(page) => SearchResults::Full(page.map(|hit| hit.highlighted(q))),
+            SearchResults::Summary(page) => {
+                SearchResults::Summary(page.map(|summary| summary.highlighted(q)))
+            }
+        }
+    }
+}
+
+/// A search page, with the queries the search may have meant when its `q` found nothing.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SearchResponse {
+    #[serde(flatten)]
+    pub results: SearchResults,
+    /// Up to three queries like `q` with its words spelled as in the catalog, only when
+    /// the search found nothing; see [`crate::suggestions`].
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub did_you_mean: Vec<String>,
+}
+
+/// The [`suggestions::did_you_mean`] for a search for `params` whose first page is
+/// `results`: none unless it has a `q` and found nothing at all.
+pub async fn search_suggestions(
+    state: &AppState,
+    params: &SearchParams,
+    page: &PageParams<SearchPageLimit>,
+    results: &SearchResults,
+) -> Vec<String> {
+    let first_page = page.offset == 0 && page.cursor.is_none();
+    match params.q.as_deref().map(str::trim) {
+        Some(q) if !q.is_empty() && first_page && results.is_empty() => {
+            suggestions::did_you_mean(state, q).await
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Carries the match count of a search asked for with `include_count=true`, leaving the
+/// body as it is.
+pub const TOTAL_COUNT_HEADER: HeaderName = HeaderName::from_static("x-total-count");
+
+/// The [`TOTAL_COUNT_HEADER`] of a search response, none when not asked for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TotalCount(pub Option<u64>);
+
+impl IntoResponseParts for TotalCount {
+    type Error = Infallible;
+
+    fn into_response_parts(
+        self,
+        mut res: ResponseParts,
+    ) -> std::result::Result<ResponseParts, Self::Error> {
+        if let Some(count) = self.0 {
+            res.headers_mut().insert(TOTAL_COUNT_HEADER, count.into());
+        }
+        Ok(res)
+    }
+}
 
-#[derive(Deserialize, Debug, Default)]
-struct UserProfileResponse {
-    #[serde(default)]
-    allergens: Vec<String>,
-    #[serde(default, rename = "dietaryPrefs")]
-    dietary_prefs: Vec<String>,
+/// Response of `GET /api/v1/products/count`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ProductCount {
+    pub count: u64,
 }
-fn product_id_cache_key(id: &ObjectId) -> String {
+
+/// What a search page lists, in either view.
+trait Listed {
+    fn id(&self) -> Option<ObjectId>;
+    fn clear_score(&mut self);
+}
+
+impl Listed for SearchHit {
+    fn id(&self) -> Option<ObjectId> {
+        self.product.id
+    }
+
+    fn clear_score(&mut self) {
+        self.score = None;
+    }
+}
+
+impl Listed for SearchSummary {
+    fn id(&self) -> Option<ObjectId> {
+        self.id
+    }
+
+    fn clear_score(&mut self) {
+        self.score = None;
+    }
+}
+
+/// Route groups rate limited together as `search`, semantic search and counts included.
+pub const SEARCH_PATHS: [&str; 3] = [
+    "/api/v1/products/search",
+    "/api/v2/products/search",
+    "/api/v1/products/count",
+];
+/// Requests per client and minute on [`SEARCH_PATHS`]; default
+/// [`DEFAULT_RATE_LIMIT_SEARCH_PER_MIN`].
+pub const RATE_LIMIT_SEARCH_PER_MIN_ENV: &str = "RATE_LIMIT_SEARCH_PER_MIN";
+pub const DEFAULT_RATE_LIMIT_SEARCH_PER_MIN: u64 = 120;
+/// Route groups rate limited together as `barcode`.
+pub const BARCODE_PATHS: [&str; 2] = ["/api/v1/products/barcode", "/api/v2/products/barcode"];
+/// Requests per client and minute on [`BARCODE_PATHS`]; default
+/// [`DEFAULT_RATE_LIMIT_BARCODE_PER_MIN`].
+pub const RATE_LIMIT_BARCODE_PER_MIN_ENV: &str = "RATE_LIMIT_BARCODE_PER_MIN";
+pub const DEFAULT_RATE_LIMIT_BARCODE_PER_MIN: u64 = 600;
+
+/// Where the next search page starts: after the last product of the previous one, or,
+/// in relevance order, which has no key to resume after, past the matches already seen.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum SearchCursor {
+    After { last_id: ObjectId },
+    Skip { skip: u64 },
+}
+
+pub(crate) const QDRANT_COLLECTION_NAME: &str = "product_vectors";
+const QDRANT_CODE_PAYLOAD_KEY: &str = "code";
+
+pub(crate) fn product_id_cache_key(id: &ObjectId) -> String {
     format!("product:id:{}", id)
 }
 
-fn product_code_cache_key(code: &str) -> String {
+pub(crate) fn product_code_cache_key(code: &str) -> String {
     format!("product:code:{}", code)
 }
 
-#[instrument(skip(state), fields(id = %id_str))]
+/// Where the match count of a search is cached, for the searches worth it: those of
+/// everything and of a single country, the most common and the most expensive to count.
+/// `None` for any other filter.
+fn count_cache_key(filter: &ProductFilter) -> Option<String> {
+    let unfiltered = ProductFilter {
+        countries: filter.countries.clone(),
+        category_match: filter.category_match,
+        sort: filter.sort,
+        ..Default::default()
+    };
+    if *filter != unfiltered {
+        return None;
+    }
+    match filter.countries.as_slice() {
+        [] => Some("product:count:all".to_string()),
+        [country] => Some(format!("product:count:country:{}", country)),
+        _ => None,
+    }
+}
+
+/// Cached under a product's id or code key for [`NEGATIVE_CACHE_TTL_SECS`] after a
+/// lookup found nothing there, so repeated misses don't reach the database. Whatever
+/// stores the product deletes the key.
+pub(crate) const NOT_FOUND_SENTINEL: &str = "__nf__";
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/products/{id}",
+    tag = "v1",
+    params(
+        ("id" = String, Path, description = "The product's ObjectId."),
+        ProductResponseParams,
+        ("If-None-Match" = Option<String>, Header, description = "An ETag from an earlier answer."),
+    ),
+    responses(
+        (status = 200, description = "The product.", body = Product, headers(("ETag" = String, description = "Weak; changes with every update."))),
+        (status = 304, description = "The `If-None-Match` ETag is still current."),
+        (status = 400, description = "The id is not an ObjectId, or `fields` names an unknown field.", body = ErrorBody),
+        (status = 404, description = "No product has the id.", body = ErrorBody),
+        (status = 500, description = "MongoDB or Redis failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, if_none_match), fields(id = %id_str))]
 pub async fn get_product_by_id(
     State(state): State<Arc<AppState>>,
     Path(id_str): Path<String>,
-) -> Result<Json<Product>> {
+    Query(params): Query<ProductResponseParams>,
+    if_none_match: IfNoneMatch,
+) -> Result<Conditional<Value>> {
+    let shape = ProductShape::parse(&params, &PRODUCT_FIELDS)?;
+    let if_none_match = shape.if_none_match(&if_none_match);
+    let product = find_product_by_id_if_none_match(&state, &id_str, &if_none_match).await?;
+    shape.apply(product, |product| product)
+}
+
+/// What `fields` may name on the v1 product GETs; see [`crate::projection`].
+static PRODUCT_FIELDS: LazyLock<BTreeSet<String>> = LazyLock::new(schema_fields::<Product>);
+
+/// Cache-then-database lookup by ObjectId string, shared by every API version.
+pub async fn find_product_by_id(state: &AppState, id_str: &str) -> Result<Product> {
+    find_product_by_id_if_none_match(state, id_str, &IfNoneMatch::default())
+        .await
+        .map(unconditional)
+}
+
+/// [`find_product_by_id`], or `NotModified` if `if_none_match` names the current ETag.
+pub async fn find_product_by_id_if_none_match(
+    state: &AppState,
+    id_str: &str,
+    if_none_match: &IfNoneMatch,
+) -> Result<Conditional<Product>> {
     info!("Attempting to get product by ID: {}", id_str);
 
-    let object_id = ObjectId::parse_str(&id_str).map_err(|e| {
+    let object_id = ObjectId::parse_str(id_str).map_err(|e| {
         error!("Invalid ObjectId format '{}': {}", id_str, e);
-        ServiceError::BadRequest(format!("Invalid product ID format: {}", id_str))
+        ServiceError::InvalidProductId(format!("Invalid product ID format: {}", id_str))
     })?;
     debug!("Parsed ObjectId: {}", object_id);
 
-    let cache_key = product_id_cache_key(&object_id);
+    let lookup = ProductLookup {
+        cache_key: product_id_cache_key(&object_id),
+        kind: "id",
+        ttl: PRODUCT_CACHE_TTL_SECS,
+    };
+    match cached_product(
+        state,
+        &lookup,
+        if_none_match,
+        state.products.find_by_id(object_id),
+    )
+    .await?
+    {
+        Some(found) => Ok(found),
+        None => {
+            info!(id = %object_id, "Product not found by ID");
+            Err(ServiceError::NotFound(format!(
+                "Product with ID {} not found",
+                object_id
+            )))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/products/barcode/{code}",
+    tag = "v1",
+    params(
+        ("code" = String, Path, description = "An EAN-8, UPC-A or EAN-13 barcode."),
+        ProductResponseParams,
+        ("If-None-Match" = Option<String>, Header, description = "An ETag from an earlier answer."),
+    ),
+    responses(
+        (status = 200, description = "The product.", body = Product, headers(("ETag" = String, description = "Weak; changes with every update."), ("X-Fetched-From" = String, description = "`openfoodfacts` when the product was just fetched from there."))),
+        (status = 304, description = "The `If-None-Match` ETag is still current."),
+        (status = 400, description = "The code is not a valid barcode, or `fields` names an unknown field.", body = ErrorBody),
+        (status = 404, description = "No product has the barcode.", body = ErrorBody),
+        (status = 429, description = "Over the client's rate limit; retry after `Retry-After`.", body = ErrorBody),
+        (status = 500, description = "MongoDB or Redis failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, if_none_match), fields(code = %barcode))]
+pub async fn get_product_by_barcode(
+    State(state): State<Arc<AppState>>,
+    Path(barcode): Path<String>,
+    Query(params): Query<ProductResponseParams>,
+    if_none_match: IfNoneMatch,
+) -> Result<(FetchedRemotely, Conditional<Value>)> {
+    check_barcode(&state, &barcode)?;
+    let shape = ProductShape::parse(&params, &PRODUCT_FIELDS)?;
+    let if_none_match = shape.if_none_match(&if_none_match);
+    let (product, fetched) =
+        find_product_by_barcode_or_fetch(&state, &barcode, &if_none_match).await?;
+    Ok((fetched, shape.apply(product, |product| product)?))
+}
+
+/// Refuses a code no scan can produce, which no product is stored under either, unless
+/// [`ALLOW_INTERNAL_CODES`] lets it through; see [`barcode::validate`].
+pub fn check_barcode(state: &AppState, code: &str) -> Result<()> {
+    barcode::validate(code, state.config.get(ALLOW_INTERNAL_CODES)).map_err(|e| {
+        ServiceError::InvalidBarcode(format!(
+            "Invalid barcode '{}': {}. Expected {}",
+            code,
+            e,
+            barcode::EXPECTED_FORMATS
+        ))
+    })
+}
 
-    let mut redis_conn = state
-        .redis_client
-        .get_multiplexed_async_connection()
+/// Cache-then-database barcode lookup shared by the HTTP handler and the internal gRPC server.
+pub async fn find_product_by_barcode(state: &AppState, barcode: &str) -> Result<Product> {
+    find_product_by_barcode_if_none_match(state, barcode, &IfNoneMatch::default())
         .await
-        .map_err(|e| {
-            error!("Failed to get async Redis connection: {}", e);
-            warn!("Proceeding without cache check due to Redis connection error.");
-            ServiceError::Redis(e)
-        })?;
+        .map(unconditional)
+}
+
+/// [`find_product_by_barcode`], or `NotModified` if `if_none_match` names the current ETag.
+pub async fn find_product_by_barcode_if_none_match(
+    state: &AppState,
+    barcode: &str,
+    if_none_match: &IfNoneMatch,
+) -> Result<Conditional<Product>> {
+    lookup_barcode(
+        state,
+        barcode,
+        if_none_match,
+        state.products.find_by_code(barcode),
+    )
+    .await
+}
+
+/// [`find_product_by_barcode_if_none_match`] for the barcode `GET`s, which look a
+/// barcode MongoDB doesn't have up on OpenFoodFacts; see [`off_fallback`]. A product
+/// fetched from there just now comes with `FetchedRemotely(true)`. Every product found
+/// counts as a scan for [`popularity`].
+pub async fn find_product_by_barcode_or_fetch(
+    state: &AppState,
+    barcode: &str,
+    if_none_match: &IfNoneMatch,
+) -> Result<(Conditional<Product>, FetchedRemotely)> {
+    let fetched = AtomicBool::new(false);
+    let fetch = find_by_code_or_fetch(state, barcode, &fetched);
+    let product = lookup_barcode(state, barcode, if_none_match, fetch).await?;
+    popularity::spawn_count_scan(state, barcode);
+    Ok((product, FetchedRemotely(fetched.into_inner())))
+}
+
+/// The product MongoDB has under `barcode`, or else the one OpenFoodFacts has, which
+/// sets `fetched`.
+async fn find_by_code_or_fetch(
+    state: &AppState,
+    barcode: &str,
+    fetched: &AtomicBool,
+) -> Result<Option<Product>> {
+    if let Some(product) = state.products.find_by_code(barcode).await? {
+        return Ok(Some(product));
+    }
+    let product = off_fallback::fetch_and_store(state, barcode).await?;
+    fetched.store(product.is_some(), Ordering::Relaxed);
+    Ok(product)
+}
+
+/// The product cached under `barcode`, or else the one `fetch` finds for it.
+async fn lookup_barcode(
+    state: &AppState,
+    barcode: &str,
+    if_none_match: &IfNoneMatch,
+    fetch: impl Future<Output = Result<Option<Product>>>,
+) -> Result<Conditional<Product>> {
+    info!("Attempting to get product by barcode: {}", barcode);
+
+    let lookup = ProductLookup {
+        cache_key: product_code_cache_key(barcode),
+        kind: "code",
+        ttl: BARCODE_CACHE_TTL_SECS,
+    };
+    match cached_product(state, &lookup, if_none_match, fetch).await? {
+        Some(found) => Ok(found),
+        None => {
+            info!(code = %barcode, "Product not found by barcode");
+            Err(ServiceError::NotFound(format!(
+                "Product with barcode {} not found",
+                barcode
+            )))
+        }
+    }
+}
+
+/// Where [`cached_product`] caches a product, the `kind` of lookup its metrics count and
+/// for how long.
+struct ProductLookup {
+    cache_key: String,
+    kind: &'static str,
+    ttl: Tunable<u64>,
+}
+
+/// The product cached under the lookup's key, or else the one `fetch` finds, which is
+/// then cached with its ETag. A cached ETag that `if_none_match` names answers
+/// `NotModified` before the cached JSON is parsed. `None` if there is no such product,
+/// which is cached as [`NOT_FOUND_SENTINEL`] too. Without a cache connection the lookup
+/// goes straight to the database and caches nothing.
+async fn cached_product(
+    state: &AppState,
+    lookup: &ProductLookup,
+    if_none_match: &IfNoneMatch,
+    fetch: impl Future<Output = Result<Option<Product>>>,
+) -> Result<Option<Conditional<Product>>> {
+    let mut cache_conn = connect_cache(state, lookup.kind).await;
+    let cached = match cache_conn.as_mut() {
+        Some(conn) => read_cached(conn.as_mut(), lookup, if_none_match).await,
+        None => None,
+    };
+    if let Some(answer) = cached {
+        return Ok(answer);
+    }
+
+    debug!(key = %lookup.cache_key, "Fetching product from storage ({})", lookup.kind);
+    let found = fetch.await?;
+    if let Some(conn) = cache_conn.as_mut() {
+        write_cached(state, conn.as_mut(), lookup, found.as_ref()).await;
+    }
+    Ok(found.map(|product| conditional(product, if_none_match)))
+}
+
+/// A connection to the cache, or `None` once the failure to connect is logged: a cache
+/// outage slows product reads down but doesn't fail them.
+pub(crate) async fn connect_cache(
+    state: &AppState,
+    kind: &'static str,
+) -> Option<Box<dyn CacheConnection>> {
+    match state.cache.connect().await {
+        Ok(conn) => Some(conn),
+        Err(e) => {
+            warn!(
+                "Failed to get async Redis connection: {}. Proceeding without the cache.",
+                e
+            );
+            record_cache_lookup(kind, CacheOutcome::Error);
+            None
+        }
+    }
+}
 
-    match redis_conn.get::<_, Option<String>>(&cache_key).await {
-        Ok(Some(cached_product_json_str)) if !cached_product_json_str.is_empty() => {
-            match serde_json::from_str::<Product>(&cached_product_json_str) {
+/// What the cache knows about the lookup's product, `None` if the database must answer.
+async fn read_cached(
+    conn: &mut dyn CacheConnection,
+    lookup: &ProductLookup,
+    if_none_match: &IfNoneMatch,
+) -> Option<Option<Conditional<Product>>> {
+    let (cache_key, kind) = (lookup.cache_key.as_str(), lookup.kind);
+    match conn.get(cache_key).await {
+        Ok(Some(cached)) if cached == NOT_FOUND_SENTINEL => {
+            debug!(key = %cache_key, "Cached miss for product ({})", kind);
+            record_cache_lookup(kind, CacheOutcome::NegativeHit);
+            Some(None)
+        }
+        Ok(Some(cached)) if !cached.is_empty() => {
+            let (etag, json) = decode_cached(&cached);
+            if let Some(etag) = etag.filter(|etag| if_none_match.matches(etag)) {
+                info!(key = %cache_key, "Cache hit for product ({}), client copy is current", kind);
+                record_cache_lookup(kind, CacheOutcome::Hit);
+                return Some(Some(Conditional::NotModified {
+                    etag: etag.to_string(),
+                }));
+            }
+            match serde_json::from_str::<Product>(json) {
                 Ok(product) => {
-                    info!(id = %object_id, "Cache hit for product ID");
-                    return Ok(Json(product));
+                    info!(key = %cache_key, "Cache hit for product ({})", kind);
+                    record_cache_lookup(kind, CacheOutcome::Hit);
+                    Some(Some(conditional(product, if_none_match)))
                 }
                 Err(e) => {
-                    error!(id = %object_id, "Failed to deserialize cached product (ID): {}. Fetching from DB.", e);
+                    error!(key = %cache_key, "Failed to deserialize cached product ({}): {}. Fetching from DB.", kind, e);
+                    record_cache_lookup(kind, CacheOutcome::Error);
+                    None
                 }
             }
         }
         Ok(_) => {
-            debug!(id = %object_id, "Cache miss for product ID (empty value).");
+            debug!(key = %cache_key, "Cache miss for product ({}) (empty value).", kind);
+            record_cache_lookup(kind, CacheOutcome::Miss);
+            None
         }
         Err(e) => {
-            warn!(id = %object_id, "Redis GET command failed (ID): {}. Fetching from DB.", e);
+            warn!(key = %cache_key, "Redis GET command failed ({}): {}. Fetching from DB.", kind, e);
+            record_cache_lookup(kind, CacheOutcome::Error);
+            None
         }
     }
+}
 
-    debug!(id = %object_id, "Fetching product from MongoDB by ID");
-    let collection = state.mongo_db.collection::<Product>("products");
-    let db_product = collection
-        .find_one(doc! { "_id": object_id })
-        .await
-        .map_err(|e| {
-            error!(id = %object_id, "MongoDB find_one by ID failed: {}", e);
-            ServiceError::MongoDb(e)
-        })?;
+/// Caches what the database found for the lookup: the product with its ETag, or the
+/// sentinel for a miss.
+async fn write_cached(
+    state: &AppState,
+    conn: &mut dyn CacheConnection,
+    lookup: &ProductLookup,
+    found: Option<&Product>,
+) {
+    let (cache_key, kind) = (lookup.cache_key.as_str(), lookup.kind);
+    let Some(product) = found else {
+        let ttl = cache_ttl(&state.config, NEGATIVE_CACHE_TTL_SECS);
+        if let Err(e) = conn.set_ex(cache_key, NOT_FOUND_SENTINEL, ttl).await {
+            warn!(key = %cache_key, "Failed to cache product miss ({}) in Redis: {}", kind, e);
+        }
+        return;
+    };
+    info!(id = product.id.as_ref().map(|id| id.to_string()).unwrap_or_default(), code = %product.code, "Product found in DB ({})", kind);
 
-    if let Some(product) = db_product {
-        info!(id = %object_id, code = product.code, "Product found in DB by ID");
-
-        match serde_json::to_string(&product) {
-            Ok(product_json) => {
-                match redis_conn
-                    .set_ex::<_, _, ()>(&cache_key, &product_json, CACHE_EXPIRATION_SECONDS)
-                    .await
-                {
-                    Ok(_) => {
-                        info!(id = %object_id, key = %cache_key, "Successfully cached product (ID) in Redis")
-                    }
-                    Err(e) => {
-                        warn!(id = %object_id, key = %cache_key, "Failed to cache product (ID) in Redis (SETEX): {}", e)
-                    }
+    match serde_json::to_string(product) {
+        Ok(product_json) => {
+            let value = encode_cached(&product_etag(product), &product_json);
+            match conn
+                .set_ex(cache_key, &value, cache_ttl(&state.config, lookup.ttl))
+                .await
+            {
+                Ok(_) => {
+                    info!(key = %cache_key, "Successfully cached product ({}) in Redis", kind)
+                }
+                Err(e) => {
+                    warn!(key = %cache_key, "Failed to cache product ({}) in Redis (SETEX): {}", kind, e)
                 }
             }
-            Err(e) => warn!(id = %object_id, "Failed to serialize product for caching (ID): {}", e),
         }
-        Ok(Json(product))
+        Err(e) => {
+            warn!(key = %cache_key, "Failed to serialize product for caching ({}): {}", kind, e)
+        }
+    }
+}
+
+fn conditional(product: Product, if_none_match: &IfNoneMatch) -> Conditional<Product> {
+    let etag = product_etag(&product);
+    if if_none_match.matches(&etag) {
+        Conditional::NotModified { etag }
     } else {
-        info!(id = %object_id, "Product not found by ID");
-        Err(ServiceError::NotFound(format!(
-            "Product with ID {} not found",
-            object_id
-        )))
+        Conditional::Modified {
+            etag,
+            value: product,
+        }
     }
 }
 
-#[instrument(skip(state), fields(code = %barcode))]
-pub async fn get_product_by_barcode(
+/// The product of a lookup made without `If-None-Match`, which is never `NotModified`.
+fn unconditional(found: Conditional<Product>) -> Product {
+    match found {
+        Conditional::Modified { value, .. } => value,
+        Conditional::NotModified { .. } => unreachable!("no If-None-Match to match"),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/products/batch",
+    tag = "v1",
+    request_body = BatchLookupPayload,
+    responses(
+        (status = 200, description = "The products found, by barcode, and the codes not found.", body = BatchLookupResponse),
+        (status = 400, description = "The body is not JSON.", body = ErrorBody),
+        (status = 422, description = "More than 100 codes, or a field of the wrong type.", body = ErrorBody),
+        (status = 500, description = "MongoDB or Redis failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, payload), fields(codes = payload.codes.len()))]
+pub async fn get_products_by_barcodes(
     State(state): State<Arc<AppState>>,
-    Path(barcode): Path<String>,
-) -> Result<Json<Product>> {
-    info!("Attempting to get product by barcode: {}", barcode);
+    ValidJson(payload): ValidJson<BatchLookupPayload>,
+) -> Result<Json<BatchLookupResponse>> {
+    find_products_by_barcodes(&state, payload.codes)
+        .await
+        .map(Json)
+}
+
+/// Barcode lookup for many codes at once: one MGET against the cache, one `$in` query for
+/// the misses, and one pipelined write to cache what the database found. Codes cached as
+/// missing are not found without a query.
+pub async fn find_products_by_barcodes(
+    state: &AppState,
+    codes: Vec<String>,
+) -> Result<BatchLookupResponse> {
+    let mut seen = HashSet::new();
+    let codes: Vec<String> = codes
+        .into_iter()
+        .filter(|code| seen.insert(code.clone()))
+        .collect();
+    if codes.is_empty() {
+        return Err(ServiceError::BadRequest(
+            "At least one product code is required".to_string(),
+        ));
+    }
+    info!("Attempting to get {} products by barcode", codes.len());
 
-    let cache_key = product_code_cache_key(&barcode);
+    let mut cache_conn = connect_cache(state, "code").await;
 
-    let mut redis_conn = state
-        .redis_client
-        .get_multiplexed_async_connection()
-        .await
-        .map_err(|e| {
-            error!("Failed to get async Redis connection: {}", e);
-            warn!("Proceeding without cache check due to Redis connection error.");
-            ServiceError::Redis(e)
-        })?;
+    let cache_keys: Vec<String> = codes
+        .iter()
+        .map(|code| product_code_cache_key(code))
+        .collect();
+    let key_refs: Vec<&str> = cache_keys.iter().map(String::as_str).collect();
+    let cached = match cache_conn.as_mut() {
+        Some(conn) => match conn.mget(&key_refs).await {
+            Ok(values) => values,
+            Err(e) => {
+                warn!(
+                    "Redis MGET command failed (code batch): {}. Fetching all from DB.",
+                    e
+                );
+                vec![None; codes.len()]
+            }
+        },
+        None => vec![None; codes.len()],
+    };
 
-    match redis_conn.get::<_, String>(&cache_key).await {
-        Ok(cached_product_json) if !cached_product_json.is_empty() => {
-            match serde_json::from_str::<Product>(&cached_product_json) {
+    let mut products = BTreeMap::new();
+    let mut misses = Vec::new();
+    let mut known_missing = 0;
+    for (code, cached) in codes.iter().zip(cached) {
+        match cached.filter(|value| !value.is_empty()) {
+            Some(value) if value == NOT_FOUND_SENTINEL => {
+                record_cache_lookup("code", CacheOutcome::NegativeHit);
+                known_missing += 1;
+            }
+            Some(value) => match serde_json::from_str::<Product>(decode_cached(&value).1) {
                 Ok(product) => {
-                    info!(code = %barcode, "Cache hit for product barcode");
-                    return Ok(Json(product));
+                    record_cache_lookup("code", CacheOutcome::Hit);
+                    products.insert(code.clone(), product);
                 }
                 Err(e) => {
-                    error!(code = %barcode, "Failed to deserialize cached product (code): {}. Fetching from DB.", e);
+                    error!(code = %code, "Failed to deserialize cached product (code): {}. Fetching from DB.", e);
+                    record_cache_lookup("code", CacheOutcome::Error);
+                    misses.push(code.clone());
                 }
+            },
+            None => {
+                record_cache_lookup("code", CacheOutcome::Miss);
+                misses.push(code.clone());
             }
         }
-        Ok(_) => {
-            debug!(code = %barcode, "Cache miss for product barcode (empty value).");
+    }
+    debug!(
+        hits = products.len(),
+        known_missing,
+        misses = misses.len(),
+        "Batch barcode cache lookup done"
+    );
+
+    if !misses.is_empty() {
+        let found = state
+            .products
+            .find_by_codes(misses.clone(), misses.len())
+            .await?;
+        let backfill: Vec<(String, String)> = found
+            .iter()
+            .filter_map(|product| match serde_json::to_string(product) {
+                Ok(json) => Some((
+                    product_code_cache_key(&product.code),
+                    encode_cached(&product_etag(product), &json),
+                )),
+                Err(e) => {
+                    warn!(code = %product.code, "Failed to serialize product for caching (code): {}", e);
+                    None
+                }
+            })
+            .collect();
+        let ttl = cache_ttl(&state.config, BARCODE_CACHE_TTL_SECS);
+        let backfilled = match cache_conn.as_mut() {
+            Some(conn) => conn.set_ex_many(&backfill, ttl).await,
+            None => Ok(()),
+        };
+        if let Err(e) = backfilled {
+            warn!(
+                "Failed to cache {} products (code) in Redis: {}",
+                backfill.len(),
+                e
+            );
         }
-        Err(e) => {
-            warn!(code = %barcode, "Redis GET command failed (code): {}. Fetching from DB.", e);
+        for product in found {
+            products.insert(product.code.clone(), product);
         }
     }
 
-    debug!(code = %barcode, "Fetching product from MongoDB by barcode");
-    let collection = state.mongo_db.collection::<Product>("products");
-    let db_product = collection
-        .find_one(doc! { "code": &barcode })
-        .await
-        .map_err(|e| {
-            error!(code = %barcode, "MongoDB find_one by code failed: {}", e);
-            ServiceError::MongoDb(e)
-        })?;
+    let not_found: Vec<String> = codes
+        .into_iter()
+        .filter(|code| !products.contains_key(code))
+        .collect();
+    info!(
+        found = products.len(),
+        not_found = not_found.len(),
+        "Batch barcode lookup done"
+    );
+    Ok(BatchLookupResponse {
+        products,
+        not_found,
+    })
+}
 
-    if let Some(product) = db_product {
-        info!(id = product.id.as_ref().map(|id| id.to_string()).unwrap_or_default(), code = %barcode, "Product found in DB by barcode");
-
-        match serde_json::to_string(&product) {
-            Ok(product_json) => {
-                match redis_conn
-                    .set_ex::<_, _, ()>(&cache_key, &product_json, CACHE_EXPIRATION_SECONDS)
-                    .await
-                {
-                    Ok(_) => {
-                        info!(code = %barcode, key = %cache_key, "Successfully cached product (code) in Redis")
-                    }
-                    Err(e) => {
-                        warn!(code = %barcode, key = %cache_key, "Failed to cache product (code) in Redis (SETEX): {}", e)
-                    }
-                }
-            }
-            Err(e) => {
-                warn!(code = %barcode, "Failed to serialize product for caching (code): {}", e)
-            }
+/// What a search for `params` keeps. Tags are compared as the catalog stores them, so
+/// `Ritter Sport` finds the brand `ritter-sport` and `Organic` the label `en:organic`.
+pub(crate) fn search_filter(params: &SearchParams) -> ProductFilter {
+    let trimmed = |value: &Option<String>| {
+        value
+            .as_deref()
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+    };
+    let text = trimmed(&params.q);
+    // An explicit `sort` wins; a `q` alone ranks by relevance.
+    let sort = match (params.sort, &text) {
+        (Some(sort), Some(_)) => sort,
+        (None, Some(_)) => SearchSort::Relevance,
+        (_, None) => SearchSort::Id,
+    };
+    let mut filter = ProductFilter {
+        text,
+        sort,
+        categories: normalize_tags(&params.category),
+        category_match: params.category_match,
+        brands: tags::normalize_tags(&params.brand),
+        labels: normalize_tags(&params.label),
+        countries: normalize_tags(&params.country),
+        nutriscore: trimmed(&params.nutriscore).map(|n| n.to_lowercase()),
+        ecoscore: params.ecoscore.clone(),
+        max_sugar: params.max_sugar,
+        max_salt: params.max_salt,
+        max_fat: params.max_fat,
+        min_protein: params.min_protein,
+        allergen_mode: params.allergen_mode,
+        included_ingredients: params.ingredients_include.clone(),
+        excluded_ingredients: params.ingredients_exclude.clone(),
+        ..Default::default()
+    };
+    if params.exclude_traces {
+        filter.excluded_traces = normalize_tags(&params.ingredients_exclude);
+    }
+
+    if let Some(user_allergens) = &params.user_allergens {
+        let allergen_tags = allergen_tags(user_allergens);
+        if !allergen_tags.is_empty() {
+            info!("Applying allergen filter (excluding): {:?}", allergen_tags);
+            filter.excluded_allergens = allergen_tags;
         }
+    }
 
-        Ok(Json(product))
-    } else {
-        info!(code = %barcode, "Product not found by barcode");
-        Err(ServiceError::NotFound(format!(
-            "Product with barcode {} not found",
-            barcode
-        )))
+    if let Some(user_diets) = &params.user_diets {
+        let conflicting_tags = diet_exclusion_tags(user_diets);
+        if !conflicting_tags.is_empty() {
+            info!(
+                "Applying diet filter (excluding tags): {:?}",
+                conflicting_tags
+            );
+            filter.excluded_labels = conflicting_tags;
+        }
     }
+    filter
 }
 
+/// Lists full products unless `view=summary` asks otherwise, whatever the page size:
+/// v1 responses keep their shape. v2 turns to summaries by itself on large pages.
+#[utoipa::path(
+    get,
+    path = "/api/v1/products/search",
+    tag = "v1",
+    params(
+        SearchParams,
+        PageParams<SearchPageLimit>,
+    ),
+    responses(
+        (status = 200, description = "One page of matching products, as summaries with `view=summary`, with `did_you_mean` when `q` found nothing.", body = SearchResponse, headers(("X-Total-Count" = u64, description = "Every match, with `include_count=true`."), ("X-Default-Country" = String, description = "The `DEFAULT_COUNTRY_TAG` the search was scoped to, as it named no `country`."))),
+        (status = 400, description = "An invalid parameter or cursor.", body = ErrorBody),
+        (status = 429, description = "Over the client's rate limit; retry after `Retry-After`.", body = ErrorBody),
+        (status = 500, description = "MongoDB failed, or the text index is missing.", body = ErrorBody),
+    )
+)]
 #[instrument(skip(state, params), fields(query = ?params))]
 pub async fn search_products(
     State(state): State<Arc<AppState>>,
-    Query(params): Query<SearchParams>,
-) -> Result<Json<Vec<Product>>> {
-    info!("Searching products with parameters: {:?}", params);
-
-    let mut filter = doc! {};
+    Query(mut params): Query<SearchParams>,
+    page: PageParams<SearchPageLimit>,
+) -> Result<(DefaultCountry, TotalCount, Json<SearchResponse>)> {
+    let default_country = scope_countries(&mut params.country, state.default_country.as_deref());
+    let view = params.view.unwrap_or_default();
+    let results = find_products_in_view(&state, &params, &page, view)
+        .await?
+        .highlighted_for(&params);
+    let total_count = search_total_count(&state, &params, results.total()).await?;
+    let did_you_mean = search_suggestions(&state, &params, &page, &results).await;
+    Ok((
+        default_country,
+        total_count,
+        Json(SearchResponse {
+            results,
+            did_you_mean,
+        }),
+    ))
+}
 
-    if let Some(q) = &params.q {
-        if !q.trim().is_empty() {
-            filter.insert("$text", doc! { "$search": q.trim() });
-        }
-    }
-    if let Some(category) = &params.category {
-        if !category.trim().is_empty() {
-            filter.insert("categories_tags", category.trim());
-        }
-    }
-    if let Some(brand) = &params.brand {
-        if !brand.trim().is_empty() {
-            filter.insert("brands_tags", brand.trim());
-        }
+/// The [`TotalCount`] of a search for `params`: the page's `total` if it has one, else
+/// counted now, and none without `include_count=true`.
+pub async fn search_total_count(
+    state: &AppState,
+    params: &SearchParams,
+    total: Option<u64>,
+) -> Result<TotalCount> {
+    if !params.include_count {
+        return Ok(TotalCount(None));
     }
-    if let Some(label) = &params.label {
-        if !label.trim().is_empty() {
-            filter.insert("labels_tags", label.trim());
-        }
+    match total {
+        Some(total) => Ok(TotalCount(Some(total))),
+        None => count_matches(state, &search_filter(params))
+            .await
+            .map(|count| TotalCount(Some(count))),
     }
-    if let Some(country) = &params.country {
-        if !country.trim().is_empty() {
-            filter.insert("countries_tags", country.trim());
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/products/count",
+    tag = "v1",
+    params(
+        SearchParams,
+    ),
+    responses(
+        (status = 200, description = "How many products match the search parameters.", body = ProductCount, headers(("X-Default-Country" = String, description = "The `DEFAULT_COUNTRY_TAG` the count was scoped to, as it named no `country`."))),
+        (status = 400, description = "An invalid parameter.", body = ErrorBody),
+        (status = 500, description = "MongoDB failed, or the text index is missing.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, params), fields(query = ?params))]
+pub async fn count_products(
+    State(state): State<Arc<AppState>>,
+    Query(mut params): Query<SearchParams>,
+) -> Result<(DefaultCountry, Json<ProductCount>)> {
+    params.validate()?;
+    let default_country = scope_countries(&mut params.country, state.default_country.as_deref());
+    let count = count_matches(&state, &search_filter(&params)).await?;
+    info!("Counted {} matching products", count);
+    Ok((default_country, Json(ProductCount { count })))
+}
+
+/// How many products match `filter`. The counts of the filters [`count_cache_key`] names
+/// are cached for [`COUNT_CACHE_TTL_SECS`], and may lag behind writes that long.
+pub async fn count_matches(state: &AppState, filter: &ProductFilter) -> Result<u64> {
+    let Some(cache_key) = count_cache_key(filter) else {
+        return state.products.count(filter).await;
+    };
+    let mut cache_conn = connect_cache(state, "count").await;
+    if let Some(conn) = cache_conn.as_mut() {
+        match conn.get(&cache_key).await {
+            Ok(Some(cached)) => match cached.parse::<u64>() {
+                Ok(count) => {
+                    debug!(key = %cache_key, "Cache hit for count");
+                    record_cache_lookup("count", CacheOutcome::Hit);
+                    return Ok(count);
+                }
+                Err(e) => {
+                    error!(key = %cache_key, "Failed to parse cached count {:?}: {}", cached, e);
+                    record_cache_lookup("count", CacheOutcome::Error);
+                }
+            },
+            Ok(None) => record_cache_lookup("count", CacheOutcome::Miss),
+            Err(e) => {
+                warn!(key = %cache_key, "Redis GET command failed (count): {}", e);
+                record_cache_lookup("count", CacheOutcome::Error);
+            }
         }
     }
-    if let Some(nutriscore) = &params.nutriscore {
-        if !nutriscore.trim().is_empty() {
-            filter.insert("nutrition_grade_fr", nutriscore.trim().to_lowercase());
+
+    let count = state.products.count(filter).await?;
+    if let Some(conn) = cache_conn.as_mut() {
+        let ttl = cache_ttl(&state.config, COUNT_CACHE_TTL_SECS);
+        if let Err(e) = conn.set_ex(&cache_key, &count.to_string(), ttl).await {
+            warn!(key = %cache_key, "Failed to cache count in Redis: {}", e);
         }
     }
+    Ok(count)
+}
 
-    if let Some(user_allergens) = &params.user_allergens {
-        if !user_allergens.is_empty() {
-            info!("Applying allergen filter (excluding): {:?}", user_allergens);
-            filter.insert("allergens_tags", doc! { "$nin": user_allergens });
-        }
+/// [`find_products`] or [`find_product_summaries`], as `view` says.
+pub async fn find_products_in_view(
+    state: &AppState,
+    params: &SearchParams,
+    page: &PageParams<SearchPageLimit>,
+    view: SearchView,
+) -> Result<SearchResults> {
+    match view {
+        SearchView::Full => find_products(state, params, page)
+            .await
+            .map(SearchResults::Full),
+        SearchView::Summary => find_product_summaries(state, params, page)
+            .await
+            .map(SearchResults::Summary),
     }
+}
 
-    if let Some(user_diets) = &params.user_diets {
-        if !user_diets.is_empty() {
-            let user_diets_set: HashSet<&str> = user_diets.iter().map(String::as_str).collect();
-            let mut conflicting_tags: Vec<&str> = Vec::new();
-            if user_diets_set.contains("vegan") {
-                conflicting_tags.extend(&[
-                    "en:non-vegan",
-                    "en:contains-milk",
-                    "en:dairy",
-                    "en:contains-eggs",
-                    "en:eggs",
-                    "en:contains-honey",
-                    "en:honey",
-                    "en:contains-meat",
-                    "en:meat",
-                    "en:contains-fish",
-                    "en:fish",
-                    "en:non-vegetarian",
-                    "en:vegetarian-status-unknown",
-                ]);
-            } else if user_diets_set.contains("vegetarian") {
-                conflicting_tags.extend(&[
-                    "en:non-vegetarian",
-                    "en:contains-meat",
-                    "en:meat",
-                    "en:contains-fish",
-                    "en:fish",
-                    "en:vegetarian-status-unknown",
-                ]);
-            }
-            if user_diets_set.contains("gluten_free") {
-                conflicting_tags.extend(&["en:contains-gluten", "en:gluten"]);
-            }
-            if user_diets_set.contains("lactose_free") {
-                conflicting_tags.extend(&["en:contains-milk", "en:dairy"]);
-            }
-            conflicting_tags.sort();
-            conflicting_tags.dedup();
+/// One page of products matching `params`, shared by every API version. Their scores
+/// are only kept with `debug=true`.
+///
+/// Pages come from the [search cache](crate::search_cache) when it has them. Creates,
+/// updates and deletes leave cached pages be rather than find every search they change,
+/// so a search may not show a write for up to
+/// [`SEARCH_CACHE_TTL_SECS`](crate::tunables::SEARCH_CACHE_TTL_SECS); product reads
+/// by ID and barcode are still current.
+pub async fn find_products(
+    state: &AppState,
+    params: &SearchParams,
+    page: &PageParams<SearchPageLimit>,
+) -> Result<Page<SearchHit>> {
+    let (filter, from) = search_start(state, params, page)?;
+    let fetch = page.limit + 1;
+    let key = search_cache_key(&filter, from, fetch, SearchView::Full);
+    let hits = cached_hits(state, &key, state.products.search(&filter, from, fetch)).await?;
+    search_page(state, params, &filter, from, page.limit, hits).await
+}
 
-            if !conflicting_tags.is_empty() {
-                info!(
-                    "Applying diet filter (excluding tags): {:?}",
-                    conflicting_tags
-                );
-                filter.insert("labels_tags", doc! { "$nin": conflicting_tags });
-            }
-        }
-    }
-    debug!("Final MongoDB filter: {:?}", filter);
-    let limit = params
-        .limit
-        .unwrap_or(DEFAULT_SEARCH_LIMIT)
-        .min(MAX_SEARCH_LIMIT);
-    let skip = params.offset.unwrap_or(0);
-    let find_options = FindOptions::builder()
-        .limit(limit as i64)
-        .skip(skip)
-        .build();
-    debug!("Applying pagination: limit={}, skip={}", limit, skip);
-
-    let collection = state.mongo_db.collection::<Product>("products");
-    let cursor = collection
-        .find(filter)
-        .with_options(find_options)
-        .await
-        .map_err(|e| {
-            error!("MongoDB find operation failed: {}", e);
-            ServiceError::MongoDb(e)
-        })?;
+/// [`find_products`] as summaries, read without the rest of each product.
+pub async fn find_product_summaries(
+    state: &AppState,
+    params: &SearchParams,
+    page: &PageParams<SearchPageLimit>,
+) -> Result<Page<SearchSummary>> {
+    let (filter, from) = search_start(state, params, page)?;
+    let fetch = page.limit + 1;
+    let key = search_cache_key(&filter, from, fetch, SearchView::Summary);
+    let hits = cached_hits(
+        state,
+        &key,
+        state.products.search_summaries(&filter, from, fetch),
+    )
+    .await?;
+    search_page(state, params, &filter, from, page.limit, hits).await
+}
 
-    let products: Vec<Product> = cursor.try_collect().await.map_err(|e| {
-        error!("Error collecting results from MongoDB cursor: {}", e);
-        ServiceError::MongoDb(e)
-    })?;
+/// The filter `params` make and where `page` starts in its matches.
+fn search_start(
+    state: &AppState,
+    params: &SearchParams,
+    page: &PageParams<SearchPageLimit>,
+) -> Result<(ProductFilter, SearchFrom)> {
+    info!(
+        "Searching products with parameters: {:?}, {:?}",
+        params, page
+    );
+
+    params.validate()?;
+    let filter = search_filter(params);
+    let from = match (page.position(&state.cursor_codec)?, filter.sort) {
+        (None, _) => SearchFrom::Offset(page.offset),
+        (Some(SearchCursor::After { last_id }), SearchSort::Id) => SearchFrom::After(last_id),
+        (Some(SearchCursor::Skip { skip }), SearchSort::Relevance) => SearchFrom::Offset(skip),
+        (Some(_), _) => {
+            return Err(ServiceError::BadRequest(
+                "The cursor belongs to a search in another order".to_string(),
+            ));
+        }
+    };
+    debug!("Applying pagination: limit={}, from={:?}", page.limit, from);
+    Ok((filter, from))
+}
 
+/// The first `limit` of `hits`, found from `from` and one more if there is a next page,
+/// as a page with the cursor to that page and the total if asked for.
+async fn search_page<H: Listed>(
+    state: &AppState,
+    params: &SearchParams,
+    filter: &ProductFilter,
+    from: SearchFrom,
+    limit: u64,
+    hits: Vec<H>,
+) -> Result<Page<H>> {
     info!(
         "Search completed. Found {} products matching criteria.",
-        products.len()
+        hits.len()
     );
 
-    Ok(Json(products))
+    let mut page = Page::overfetched(hits, limit, |last| {
+        let cursor = match from {
+            SearchFrom::Offset(skip) if filter.sort == SearchSort::Relevance => {
+                SearchCursor::Skip { skip: skip + limit }
+            }
+            _ => SearchCursor::After {
+                last_id: last.id()?,
+            },
+        };
+        Some(state.cursor_codec.encode(&cursor))
+    });
+    if !params.debug {
+        for hit in &mut page.items {
+            hit.clear_score();
+        }
+    }
+    if params.include_total.unwrap_or(true) {
+        Ok(page.with_total(count_matches(state, filter).await?))
+    } else {
+        Ok(page)
+    }
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/products",
+    tag = "v1",
+    request_body = CreateProductPayload,
+    responses(
+        (status = 201, description = "The product as stored.", body = Product),
+        (status = 400, description = "An invalid barcode, an image not on an allowed host, or a body that is not JSON.", body = ErrorBody),
+        (status = 401, description = "No valid bearer token.", body = ErrorBody),
+        (status = 409, description = "A product has the barcode already.", body = ErrorBody),
+        (status = 422, description = "A field of the wrong type or failing its rule.", body = ErrorBody),
+        (status = 500, description = "MongoDB or Redis failed.", body = ErrorBody),
+    ),
+    security(("bearer" = []))
+)]
 #[instrument(skip(state, payload), fields(code = %payload.code, name = ?payload.product_name))]
 pub async fn create_product(
     State(state): State<Arc<AppState>>,
-    Json(payload): Json<CreateProductPayload>,
+    actor: Actor,
+    ValidJson(payload): ValidJson<CreateProductPayload>,
 ) -> Result<(StatusCode, Json<Product>)> {
     info!("Attempting to create product");
+    check_barcode(&state, &payload.code)?;
+    let image_variants = match &payload.image_url {
+        Some(url) => images::check_image(&state, url).await?,
+        None => None,
+    };
 
+    let hints = payload
+        .ingredients
+        .as_deref()
+        .map(ingredient_hints)
+        .unwrap_or_default();
+    let allergens_tags = with_tags(
+        hints.allergens,
+        payload
+            .ingredients_text
+            .as_deref()
+            .map(extract_allergens)
+            .unwrap_or_default(),
+    );
     let now = Utc::now();
     let mut new_product = Product {
         id: None,
         code: payload.code,
         product_name: payload.product_name,
+        product_name_langs: None,
         generic_name: None,
-        brands: payload.brands,
+        brands: payload.brands.map(tags::normalize_tags),
         quantity: None,
-        categories: payload.categories,
+        categories: payload.categories.map(normalize_tags),
         main_category: None,
-        labels: None,
+        labels: Some(hints.labels).filter(|labels| !labels.is_empty()),
         ingredients_text: payload.ingredients_text,
-        allergens_tags: Vec::new(),
+        ingredients: payload.ingredients,
+        allergens_tags,
         traces_tags: None,
-        image_url: None,
-        image_small_url: None,
+        image_small_url: image_variants
+            .as_ref()
+            .map(|variants| variants.small.clone()),
+        image_thumb_url: image_variants.map(|variants| variants.thumb),
+        image_url: payload.image_url,
         countries: None,
         nutrition_grade_fr: None,
+        nutrition_grade_source: None,
+        ecoscore_grade: payload.ecoscore_grade.as_deref().and_then(ecoscore_grade),
+        ecoscore_score: payload.ecoscore_score,
+        nutriments: payload.nutriments,
         creator: Some("api_create".to_string()),
         source: Some("api_create_v1".to_string()),
+        scan_count: None,
         created_at: now,
         last_modified_at: now,
     };
+    nutriscore::fill_missing_grade(&mut new_product);
     debug!(product = ?new_product, "Constructed new product struct");
 
-    let collection = state.mongo_db.collection::<Product>("products");
-    debug!("Obtained handle to collection: products");
-
-    let insert_result = collection.insert_one(&new_product).await.map_err(|e| {
-        if let ErrorKind::Write(mongodb::error::WriteFailure::WriteError(write_error)) =
-            *e.kind.clone()
-        {
-            if write_error.code == 11000 {
-                error!("Duplicate key error on insert: {}", e);
-                return ServiceError::BadRequest(
-                    "Product with this code already exists.".to_string(),
-                );
-            }
-        }
-        error!("Failed to insert product into DB: {}", e);
-        ServiceError::MongoDb(e)
-    })?;
+    let new_product = state.products.insert(new_product).await?;
     info!(
         "Successfully inserted new product with ID: {}",
-        insert_result.inserted_id
+        new_product.id.map(|id| id.to_string()).unwrap_or_default()
     );
-
-    // Assign the generated ID back to the product struct
-    new_product.id = insert_result.inserted_id.as_object_id();
+    audit::record(&state, &actor, None, Some(&new_product)).await;
+    events::publish(&state, None, Some(&new_product)).await;
+    webhooks::notify(&state, EventType::ProductCreated, &new_product);
+
+    // A lookup of the code before it existed may have cached a miss.
+    if let Some(id) = &new_product.id {
+        let code_key = product_code_cache_key(&new_product.code);
+        invalidate_cache(&state, id, &[code_key.as_str()]).await;
+    }
+    vector_sync::spawn_index(&state, &new_product);
 
     info!(id = %new_product.id.unwrap(), "Returning created product");
     Ok((StatusCode::CREATED, Json(new_product)))
 }
 
+#[utoipa::path(
+    put,
+    path = "/api/v1/products/{id}",
+    tag = "v1",
+    params(
+        ("id" = String, Path, description = "The product's ObjectId."),
+    ),
+    request_body = UpdateProductPayload,
+    responses(
+        (status = 200, description = "The product as stored.", body = Product),
+        (status = 400, description = "An invalid id, an image not on an allowed host, or a body that is not JSON.", body = ErrorBody),
+        (status = 401, description = "No valid bearer token.", body = ErrorBody),
+        (status = 404, description = "No product has the id.", body = ErrorBody),
+        (status = 422, description = "A field of the wrong type or failing its rule.", body = ErrorBody),
+        (status = 500, description = "MongoDB or Redis failed.", body = ErrorBody),
+    ),
+    security(("bearer" = []))
+)]
 #[instrument(skip(state, payload), fields(id = %id_str))]
 pub async fn update_product(
     State(state): State<Arc<AppState>>,
     Path(id_str): Path<String>,
-    Json(payload): Json<UpdateProductPayload>,
+    actor: Actor,
+    ValidJson(payload): ValidJson<UpdateProductPayload>,
 ) -> Result<Json<Product>> {
     info!("Attempting to update product ID: {}", id_str);
 
-    let object_id = ObjectId::parse_str(&id_str).map_err(|e| {
-        error!("Invalid ObjectId format '{}': {}", id_str, e);
-        ServiceError::BadRequest(format!("Invalid product ID format: {}", id_str))
-    })?;
-    debug!("Parsed ObjectId: {}", object_id);
+    update_product_fields(&state, &id_str, &actor, payload)
+        .await
+        .map(Json)
+}
 
-    let mut set_doc = doc! {};
-    if let Some(val) = payload.product_name {
-        set_doc.insert("product_name", val);
-    }
-    if let Some(val) = payload.generic_name {
-        set_doc.insert("generic_name", val);
-    }
-    if let Some(val) = payload.image_url {
-        set_doc.insert("image_url", val);
-    }
-    if let Some(val) = payload.ingredients_text {
-        set_doc.insert("ingredients_text", val);
-    }
-    if let Some(val) = payload.brands {
-        set_doc.insert("brands_tags", val);
-    }
-    if let Some(val) = payload.categories {
-        set_doc.insert("categories_tags", val);
-    }
-    if let Some(val) = payload.labels {
-        set_doc.insert("labels_tags", val);
-    }
-    if let Some(val) = payload.traces {
-        set_doc.insert("traces_tags", val);
-    }
-    if let Some(val) = payload.quantity {
-        set_doc.insert("quantity", val);
-    }
-    if let Some(val) = payload.countries {
-        set_doc.insert("countries_tags", val);
-    }
-    if let Some(val) = payload.nutrition_grade_fr {
-        set_doc.insert("nutrition_grade_fr", val);
-    }
+/// Replaces the fields `payload` sets on the product `id_str` names, as `PUT` does, for
+/// changes made along with something else, like resolving a report.
+pub async fn update_product_fields(
+    state: &Arc<AppState>,
+    id_str: &str,
+    actor: &Actor,
+    payload: UpdateProductPayload,
+) -> Result<Product> {
+    apply_changes(state, id_str, actor, update_changes(payload)).await
+}
 
-    if set_doc.is_empty() {
-        warn!(id = %object_id, "Update request received with no fields to update.");
-        let collection = state.mongo_db.collection::<Product>("products");
-        return collection
-            .find_one(doc! {"_id": object_id})
-            .await
-            .map_err(ServiceError::MongoDb)?
-            .map(Json)
-            .ok_or_else(|| {
-                ServiceError::NotFound(format!("Product with ID {} not found", object_id))
-            });
+/// The replacement values as changes, their tags normalized; absent fields are kept.
+fn update_changes(payload: UpdateProductPayload) -> ProductChanges {
+    ProductChanges {
+        product_name: payload.product_name,
+        generic_name: payload.generic_name,
+        image_url: payload.image_url,
+        image_small_url: None,
+        image_thumb_url: None,
+        ingredients_text: payload.ingredients_text,
+        ingredients: payload.ingredients,
+        brands: payload.brands.map(tags::normalize_tags),
+        categories: payload.categories.map(normalize_tags),
+        labels: payload.labels.map(normalize_tags),
+        traces: payload.traces.map(normalize_tags),
+        quantity: payload.quantity,
+        allergens_tags: payload.allergens_tags.map(declared_allergen_tags),
+        countries: payload.countries.map(normalize_tags),
+        nutrition_grade_fr: payload.nutrition_grade_fr,
+        nutrition_grade_source: None,
+        ecoscore_grade: payload.ecoscore_grade.as_deref().and_then(ecoscore_grade),
+        ecoscore_score: payload.ecoscore_score,
+        nutriments: payload.nutriments,
+        unset: Vec::new(),
     }
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/products/{id}",
+    tag = "v1",
+    params(
+        ("id" = String, Path, description = "The product's ObjectId."),
+    ),
+    request_body = PatchProductPayload,
+    responses(
+        (status = 200, description = "The product as stored.", body = Product),
+        (status = 400, description = "An invalid id, an image not on an allowed host, or a body that is not JSON.", body = ErrorBody),
+        (status = 401, description = "No valid bearer token.", body = ErrorBody),
+        (status = 404, description = "No product has the id.", body = ErrorBody),
+        (status = 422, description = "A field of the wrong type or failing its rule.", body = ErrorBody),
+        (status = 500, description = "MongoDB or Redis failed.", body = ErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[instrument(skip(state, payload), fields(id = %id_str))]
+pub async fn patch_product(
+    State(state): State<Arc<AppState>>,
+    Path(id_str): Path<String>,
+    actor: Actor,
+    ValidJson(payload): ValidJson<PatchProductPayload>,
+) -> Result<Json<Product>> {
+    info!("Attempting to patch product ID: {}", id_str);
+
+    apply_changes(&state, &id_str, &actor, patch_changes(payload))
+        .await
+        .map(Json)
+}
 
-    set_doc.insert("last_modified_datetime", Utc::now());
+/// The merge patch as changes: values are set, `null`s become fields to unset.
+fn patch_changes(payload: PatchProductPayload) -> ProductChanges {
+    let mut unset = Vec::new();
+    ProductChanges {
+        product_name: patch(payload.product_name, ProductField::ProductName, &mut unset),
+        generic_name: patch(payload.generic_name, ProductField::GenericName, &mut unset),
+        image_url: patch(payload.image_url, ProductField::ImageUrl, &mut unset),
+        image_small_url: None,
+        image_thumb_url: None,
+        ingredients_text: patch(
+            payload.ingredients_text,
+            ProductField::IngredientsText,
+            &mut unset,
+        ),
+        ingredients: None,
+        brands: patch(payload.brands, ProductField::Brands, &mut unset).map(tags::normalize_tags),
+        categories: patch(payload.categories, ProductField::Categories, &mut unset)
+            .map(normalize_tags),
+        labels: patch(payload.labels, ProductField::Labels, &mut unset).map(normalize_tags),
+        traces: patch(payload.traces, ProductField::Traces, &mut unset).map(normalize_tags),
+        quantity: patch(payload.quantity, ProductField::Quantity, &mut unset),
+        allergens_tags: payload
+            .allergens_tags
+            .map(|tags| declared_allergen_tags(tags.unwrap_or_default())),
+        countries: patch(payload.countries, ProductField::Countries, &mut unset)
+            .map(normalize_tags),
+        nutrition_grade_fr: patch(
+            payload.nutrition_grade_fr,
+            ProductField::NutritionGrade,
+            &mut unset,
+        ),
+        nutrition_grade_source: None,
+        ecoscore_grade: patch(
+            payload.ecoscore_grade,
+            ProductField::EcoscoreGrade,
+            &mut unset,
+        )
+        .as_deref()
+        .and_then(ecoscore_grade),
+        ecoscore_score: patch(
+            payload.ecoscore_score,
+            ProductField::EcoscoreScore,
+            &mut unset,
+        ),
+        nutriments: None,
+        unset,
+    }
+}
 
-    let update_doc = doc! { "$set": set_doc };
-    debug!(id = %object_id, update = ?update_doc, "Constructed update document");
+fn patch<T>(
+    value: Option<Option<T>>,
+    field: ProductField,
+    unset: &mut Vec<ProductField>,
+) -> Option<T> {
+    if let Some(None) = value {
+        unset.push(field);
+    }
+    value.flatten()
+}
 
-    let collection = state.mongo_db.collection::<Product>("products");
-    let options = FindOneAndUpdateOptions::builder()
-        .return_document(ReturnDocument::After)
-        .build();
+/// Applies `changes` to the product `id_str` names, drops both of its cache entries and
+/// audits what changed. No changes at all just returns the product.
+async fn apply_changes(
+    state: &Arc<AppState>,
+    id_str: &str,
+    actor: &Actor,
+    changes: ProductChanges,
+) -> Result<Product> {
+    let object_id = ObjectId::parse_str(id_str).map_err(|e| {
+        error!("Invalid ObjectId format '{}': {}", id_str, e);
+        ServiceError::InvalidProductId(format!("Invalid product ID format: {}", id_str))
+    })?;
+    debug!("Parsed ObjectId: {}", object_id);
 
-    let update_result = collection
-        .find_one_and_update(doc! {"_id": object_id}, update_doc)
-        .with_options(options)
-        .await;
+    let Some(before) = state.products.find_by_id(object_id).await? else {
+        error!(id = %object_id, "Product not found for update");
+        return Err(ServiceError::NotFound(format!(
+            "Product with ID {} not found for update",
+            object_id
+        )));
+    };
+    if changes.is_empty() {
+        warn!(id = %object_id, "Update request received with no fields to update.");
+        return Ok(before);
+    }
+    let changes = images::with_checked_image(state, changes).await?;
+    let changes = with_ingredient_hints(with_text_allergens(changes, &before), &before);
+    let changes = with_computed_grade(changes, &before);
 
-    match update_result {
-        Ok(Some(updated_product)) => {
+    match state.products.update(object_id, &changes).await? {
+        Some(updated_product) => {
             info!(id = %object_id, "Successfully updated product in DB");
+            audit::record(state, actor, Some(&before), Some(&updated_product)).await;
+            events::publish(state, Some(&before), Some(&updated_product)).await;
+            webhooks::notify(state, EventType::ProductUpdated, &updated_product);
 
             let id_key = product_id_cache_key(&object_id);
             let code_key = product_code_cache_key(&updated_product.code);
 
             debug!(id = %object_id, code=%updated_product.code, keys=format!("{}, {}", id_key, code_key), "Attempting to invalidate cache");
-            match state.redis_client.get_multiplexed_async_connection().await {
-                Ok(mut redis_conn) => {
-                    match redis::cmd("DEL")
-                        .arg(&[&id_key, &code_key])
-                        .query_async::<i64>(&mut redis_conn)
-                        .await
-                    {
-                        Ok(deleted_count) => {
-                            info!(id = %object_id, count=deleted_count, "Cache invalidation DEL command successful ({} keys)", deleted_count)
-                        }
-                        Err(e) => {
-                            warn!(id = %object_id, "Failed to invalidate cache (DEL command failed): {}", e)
-                        }
-                    }
-                }
-                Err(e) => {
-                    warn!(id = %object_id, "Failed to get Redis connection for cache invalidation: {}", e)
-                }
-            }
+            invalidate_cache(state, &object_id, &[id_key.as_str(), code_key.as_str()]).await;
+            vector_sync::spawn_index(state, &updated_product);
 
-            Ok(Json(updated_product))
+            Ok(updated_product)
         }
-        Ok(None) => {
+        None => {
             error!(id = %object_id, "Product not found for update");
             Err(ServiceError::NotFound(format!(
                 "Product with ID {} not found for update",
                 object_id
             )))
         }
-        Err(e) => {
-            if let ErrorKind::Write(mongodb::error::WriteFailure::WriteError(write_error)) =
-                *e.kind.clone()
-            {
-                if write_error.code == 11000 {
-                    error!("Duplicate key error on update: {}", e);
-                    return Err(ServiceError::BadRequest(
-                        "Update failed due to duplicate key (e.g., code already exists)."
-                            .to_string(),
-                    ));
-                }
+    }
+}
+
+/// Adds the allergens and labels new structured ingredients imply to those the product
+/// will have, whether the update sets them or keeps the stored ones.
+fn with_ingredient_hints(mut changes: ProductChanges, before: &Product) -> ProductChanges {
+    let Some(ingredients) = &changes.ingredients else {
+        return changes;
+    };
+    let hints = ingredient_hints(ingredients);
+    if !hints.allergens.is_empty() {
+        let allergens = changes
+            .allergens_tags
+            .take()
+            .unwrap_or_else(|| before.allergens_tags.clone());
+        changes.allergens_tags = Some(with_tags(allergens, hints.allergens));
+    }
+    if !hints.labels.is_empty() {
+        let labels = changes
+            .labels
+            .take()
+            .or_else(|| before.labels.clone())
+            .unwrap_or_default();
+        changes.labels = Some(with_tags(labels, hints.labels));
+    }
+    changes
+}
+
+/// Refreshes the allergens read from the ingredients text when an update changes or
+/// clears the text without setting `allergens_tags` itself: those the old text named
+/// are dropped and those the new one names added, so allergens from anywhere else stay.
+fn with_text_allergens(mut changes: ProductChanges, before: &Product) -> ProductChanges {
+    if changes.allergens_tags.is_some() {
+        return changes;
+    }
+    let text = match &changes.ingredients_text {
+        Some(text) => text.as_str(),
+        None if changes.unset.contains(&ProductField::IngredientsText) => "",
+        None => return changes,
+    };
+    let named = extract_allergens(text);
+    let stale = before
+        .ingredients_text
+        .as_deref()
+        .map(extract_allergens)
+        .unwrap_or_default();
+    let kept = before
+        .allergens_tags
+        .iter()
+        .filter(|tag| named.contains(tag) || !stale.contains(tag))
+        .cloned()
+        .collect();
+    let allergens = with_tags(kept, named);
+    if allergens != before.allergens_tags {
+        changes.allergens_tags = Some(allergens);
+    }
+    changes
+}
+
+/// `tags` followed by those of `more` it doesn't have yet.
+fn with_tags(mut tags: Vec<String>, more: Vec<String>) -> Vec<String> {
+    for tag in more {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// Keeps a computed grade in step with the update. A declared grade drops the `computed`
+/// marker and stays as it is; otherwise a product left without a grade, or with a
+/// computed one whose nutriments or categories change, is graded from what the update
+/// leaves. A computed grade the nutriments no longer support is cleared.
+fn with_computed_grade(mut changes: ProductChanges, before: &Product) -> ProductChanges {
+    let computed = before.nutrition_grade_source.is_some();
+    if changes.nutrition_grade_fr.is_some() {
+        if computed {
+            changes.unset.push(ProductField::NutritionGradeSource);
+        }
+        return changes;
+    }
+    let cleared = changes.unset.contains(&ProductField::NutritionGrade);
+    let inputs_changed = changes.nutriments.is_some()
+        || changes.categories.is_some()
+        || changes.unset.contains(&ProductField::Categories);
+    if before.nutrition_grade_fr.is_some() && !cleared && !(computed && inputs_changed) {
+        return changes;
+    }
+
+    let mut after = before.clone();
+    if let Some(nutriments) = &changes.nutriments {
+        after.nutriments = Some(nutriments.clone());
+    }
+    if changes.unset.contains(&ProductField::Categories) {
+        after.categories = None;
+    } else if let Some(categories) = &changes.categories {
+        after.categories = Some(categories.clone());
+    }
+    match nutriscore::for_product(&after) {
+        Ok(score) => {
+            changes
+                .unset
+                .retain(|field| *field != ProductField::NutritionGrade);
+            changes.nutrition_grade_fr = Some(score.grade.as_str().to_string());
+            changes.nutrition_grade_source = Some(COMPUTED_GRADE_SOURCE.to_string());
+        }
+        Err(missing) if computed => {
+            debug!(code = %before.code, "Clearing the computed grade: {}", missing);
+            if !cleared {
+                changes.unset.push(ProductField::NutritionGrade);
+            }
+            changes.unset.push(ProductField::NutritionGradeSource);
+        }
+        Err(_) => {}
+    }
+    changes
+}
+
+async fn invalidate_cache(state: &AppState, object_id: &ObjectId, keys: &[&str]) {
+    match state.cache.connect().await {
+        Ok(mut cache_conn) => match cache_conn.del(keys).await {
+            Ok(deleted_count) => {
+                info!(id = %object_id, count=deleted_count, "Cache invalidation DEL command successful ({} keys)", deleted_count)
+            }
+            Err(e) => {
+                warn!(id = %object_id, "Failed to invalidate cache (DEL command failed): {}", e)
             }
-            error!(id = %object_id, "Failed to update product in DB: {}", e);
-            Err(ServiceError::MongoDb(e))
+        },
+        Err(e) => {
+            warn!(id = %object_id, "Failed to get Redis connection for cache invalidation: {}", e)
         }
     }
 }
 
+#[utoipa::path(
+    delete,
+    path = "/api/v1/products/{id}",
+    tag = "v1",
+    params(
+        ("id" = String, Path, description = "The product's ObjectId."),
+    ),
+    responses(
+        (status = 204, description = "Deleted."),
+        (status = 400, description = "The id is not an ObjectId.", body = ErrorBody),
+        (status = 401, description = "No valid bearer token.", body = ErrorBody),
+        (status = 404, description = "No product has the id.", body = ErrorBody),
+        (status = 500, description = "MongoDB or Redis failed.", body = ErrorBody),
+    ),
+    security(("bearer" = []))
+)]
 #[instrument(skip(state), fields(id = %id_str))]
 pub async fn delete_product(
     State(state): State<Arc<AppState>>,
     Path(id_str): Path<String>,
+    actor: Actor,
 ) -> Result<StatusCode> {
     info!("Attempting to delete product ID: {}", id_str);
 
     let object_id = ObjectId::parse_str(&id_str).map_err(|e| {
         error!("Invalid ObjectId format '{}': {}", id_str, e);
-        ServiceError::BadRequest(format!("Invalid product ID format: {}", id_str))
+        ServiceError::InvalidProductId(format!("Invalid product ID format: {}", id_str))
     })?;
     debug!("Parsed ObjectId: {}", object_id);
 
-    let collection = state.mongo_db.collection::<Product>("products");
-
-    let product_to_delete = collection
-        .find_one(doc! { "_id": object_id })
-        .projection(doc! { "code": 1 })
-        .await
-        .map_err(|e| {
-            error!(id = %object_id, "MongoDB find_one before delete failed: {}", e);
-            ServiceError::MongoDb(e)
-        })?;
-
-    let product_code = match product_to_delete {
-        Some(p) => p.code,
+    let product = match state.products.find_by_id(object_id).await? {
+        Some(product) => product,
         None => {
             info!(id = %object_id, "Product not found for deletion");
             return Err(ServiceError::NotFound(format!(
@@ -574,42 +1571,21 @@ pub async fn delete_product(
             )));
         }
     };
+    let product_code = product.code.clone();
     debug!(id = %object_id, code = %product_code, "Found product code for cache invalidation");
 
-    let delete_result = collection
-        .delete_one(doc! { "_id": object_id })
-        .await
-        .map_err(|e| {
-            error!(id = %object_id, "MongoDB delete_one failed: {}", e);
-            ServiceError::MongoDb(e)
-        })?;
-
-    if delete_result.deleted_count > 0 {
+    if state.products.delete(object_id).await? {
         info!(id = %object_id, code=%product_code, "Successfully deleted product from DB");
+        audit::record(&state, &actor, Some(&product), None).await;
+        events::publish(&state, Some(&product), None).await;
+        webhooks::notify(&state, EventType::ProductDeleted, &product);
 
         let id_key = product_id_cache_key(&object_id);
         let code_key = product_code_cache_key(&product_code);
 
         debug!(id = %object_id, code=%product_code, keys=format!("{}, {}", id_key, code_key), "Attempting to invalidate cache");
-        match state.redis_client.get_multiplexed_async_connection().await {
-            Ok(mut redis_conn) => {
-                match redis::cmd("DEL")
-                    .arg(&[&id_key, &code_key])
-                    .query_async::<i64>(&mut redis_conn)
-                    .await
-                {
-                    Ok(deleted_count) => {
-                        info!(id = %object_id, count=deleted_count, "Cache invalidation DEL command successful ({} keys)", deleted_count)
-                    }
-                    Err(e) => {
-                        warn!(id = %object_id, "Failed to invalidate cache (DEL command failed): {}", e)
-                    }
-                }
-            }
-            Err(e) => {
-                warn!(id = %object_id, "Failed to get Redis connection for cache invalidation: {}", e)
-            }
-        }
+        invalidate_cache(&state, &object_id, &[id_key.as_str(), code_key.as_str()]).await;
+        cascade::delete_copies(state.copies.as_ref(), &object_id, &product_code).await;
 
         Ok(StatusCode::NO_CONTENT)
     } else {
@@ -621,48 +1597,156 @@ pub async fn delete_product(
     }
 }
 
-#[instrument(skip(state), fields(product_id = %product_id_str))]
+/// Most recommendations a request can ask for.
+pub const MAX_RECOMMENDATION_LIMIT: u64 = 50;
+/// Candidates fetched from Qdrant per recommendation asked for, so that products missing
+/// from MongoDB still leave enough to fill the list.
+const RECOMMENDATION_OVERFETCH: usize = 2;
+
+/// A recommended product and its similarity to the source product.
+#[derive(Debug, Clone)]
+pub struct RecommendedProduct {
+    pub product: Product,
+    pub score: f32,
+}
+
+/// Products similar to a source product, most similar first, and whether a user profile
+/// narrowed them down.
+#[derive(Debug)]
+pub struct Recommendations {
+    pub products: Vec<RecommendedProduct>,
+    pub personalized: bool,
+}
+
+/// Names the user recommendations are for. The profile service serves their profile only
+/// to their own token, which the request carries along.
+pub const USER_ID_HEADER: &str = "x-user-id";
+
+/// The [`USER_ID_HEADER`] of a request, if it has a non-blank one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecommendedFor(pub Option<String>);
+
+impl<S> FromRequestParts<S> for RecommendedFor
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        Ok(RecommendedFor(
+            parts
+                .headers
+                .get(USER_ID_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string),
+        ))
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/products/{id}/recommendations",
+    tag = "v1",
+    params(
+        ("id" = String, Path, description = "The product's ObjectId."),
+        ("X-User-Id" = Option<String>, Header, description = "Leaves out products conflicting with this user's profile."),
+        RecommendationParams,
+    ),
+    responses(
+        (status = 200, description = "Similar products, most similar first.", body = Vec<Product>),
+        (status = 400, description = "An invalid id, `limit` or `min_score`.", body = ErrorBody),
+        (status = 404, description = "The product is not in the vector index.", body = ErrorBody),
+        (status = 500, description = "Qdrant, MongoDB or Neo4j failed.", body = ErrorBody),
+        (status = 502, description = "The user profile service refused the user's profile.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, headers), fields(product_id = %product_id_str, user_id = ?user_id))]
 pub async fn get_recommendations(
     State(state): State<Arc<AppState>>,
     Path(product_id_str): Path<String>, // This is the MongoDB ObjectId string of the source product
+    RecommendedFor(user_id): RecommendedFor,
+    headers: HeaderMap,
+    Query(params): Query<RecommendationParams>,
 ) -> Result<Json<Vec<Product>>> {
+    let recommendations = recommend(
+        &state,
+        &product_id_str,
+        user_id.as_deref(),
+        headers.get(AUTHORIZATION),
+        &params,
+    )
+    .await?;
+    Ok(Json(
+        recommendations
+            .products
+            .into_iter()
+            .map(|recommended| recommended.product)
+            .collect(),
+    ))
+}
+
+/// The `must_not` conditions of [`AllergenMode::Strict`]: points with no
+/// `ingredients_text`, or a null `allergens_tags`, are of products whose allergens are
+/// unknown. Qdrant has no test for a missing field alone, and `is_empty` would also take
+/// the empty tag lists strict mode keeps.
+fn unknown_allergen_conditions() -> Vec<Condition> {
+    vec![
+        Condition {
+            condition_one_of: Some(ConditionOneOf::IsEmpty(IsEmptyCondition {
+                key: "ingredients_text".to_string(),
+            })),
+        },
+        Condition {
+            condition_one_of: Some(ConditionOneOf::IsNull(IsNullCondition {
+                key: "allergens_tags".to_string(),
+            })),
+        },
+    ]
+}
+
+/// The vector search behind the recommendations of every API version: up to
+/// `params.limit` products, most similar first, leaving out those that conflict with the
+/// profile of `user_id` if there is one. `authorization` is the caller's header, which the
+/// profile route requires.
+pub async fn recommend(
+    state: &AppState,
+    product_id_str: &str,
+    user_id: Option<&str>,
+    authorization: Option<&HeaderValue>,
+    params: &RecommendationParams,
+) -> Result<Recommendations> {
     info!(
         "Received recommendation request for source product (Mongo OID): {}",
         product_id_str
     );
-
-    let source_qdrant_uuid = Uuid::new_v5(&Uuid::NAMESPACE_DNS, product_id_str.as_bytes());
-    let source_qdrant_uuid_str = source_qdrant_uuid.to_string();
-    let target_point_id_for_qdrant_vector_fetch: PointId = source_qdrant_uuid_str.clone().into();
-
-    debug!(
-        "Source product Mongo OID: {}, Qdrant UUID for vector fetch: {}",
-        product_id_str, source_qdrant_uuid_str
+    params.validate()?;
+    let recommendation_limit = params.limit.map_or_else(
+        || state.config.get(RECOMMENDATION_LIMIT),
+        |limit| limit.min(MAX_RECOMMENDATION_LIMIT) as usize,
     );
 
-    let get_request = GetPointsBuilder::new(
-        QDRANT_COLLECTION_NAME.to_string(),
-        vec![target_point_id_for_qdrant_vector_fetch.clone()],
-    )
-    .with_payload(false)
-    .with_vectors(true);
-
-    let retrieve_result = state.qdrant_client.get_points(get_request).await?;
+    // Vectors come from the sync worker's Qdrant index; in-memory storage has none, which
+    // reads the same as a product that hasn't been indexed yet.
+    let Some(clients) = &state.clients else {
+        warn!("No vector index on in-memory storage; recommendations are unavailable.");
+        return Err(ServiceError::NotFound(format!(
+            "Vector data not found for product OID {}",
+            product_id_str
+        )));
+    };
 
-    let target_vector = retrieve_result
-        .result
-        .into_iter()
-        .next()
-        .and_then(|point| point.vectors)
-        .and_then(|vectors| vectors.vectors_options)
-        .and_then(|options| match options {
-            vectors_output::VectorsOptions::Vector(v) => Some(v.data),
-            _ => None,
-        })
+    let target_point_id_for_qdrant_vector_fetch = product_point_id(product_id_str);
+    let target_vector = product_vector(clients, product_id_str)
+        .await?
         .ok_or_else(|| {
             error!(
-                "Target product vector not found in Qdrant for source Mongo OID: {} (Qdrant UUID: {})",
-                product_id_str, source_qdrant_uuid_str
+                "Target product vector not found in Qdrant for source Mongo OID: {}",
+                product_id_str
             );
             ServiceError::NotFound(format!(
                 "Vector data not found for product OID {}",
@@ -670,70 +1754,16 @@ pub async fn get_recommendations(
             ))
         })?;
 
-    if target_vector.is_empty() {
-        error!(
-            "Retrieved empty target vector for source Mongo OID: {}",
-            product_id_str
-        );
-        return Err(ServiceError::Internal(format!(
-            "Empty vector found for product OID {}",
-            product_id_str
-        )));
-    }
-    debug!(
-        "Target vector for source product (Mongo OID: {}) retrieved successfully (size: {})",
-        product_id_str,
-        target_vector.len()
-    );
-
-    const DUMMY_USER_ID: &str = "dummy-user-123";
-    warn!(
-        user_id = DUMMY_USER_ID,
-        "Using DUMMY user ID for profile fetch. Replace with actual authenticated user ID."
-    );
-
-    let profile_url = format!(
-        "{}/api/v1/users/{}/profile",
-        state.user_profile_service_url, DUMMY_USER_ID
-    );
-    debug!("Fetching user profile from: {}", profile_url);
-
-    let profile_resp = state
-        .http_client
-        .get(&profile_url)
-        .send()
-        .await
-        .map_err(ServiceError::Reqwest)?;
-    let (user_allergens, user_diets) = match profile_resp.status() {
-        HttpStatus::OK => {
-            let profile = profile_resp
-                .json::<UserProfileResponse>()
-                .await
-                .map_err(|e| {
-                    error!("Failed to deserialize user profile JSON: {}", e);
-                    ServiceError::Internal(format!("Failed to parse profile data: {}", e))
-                })?;
-            debug!(allergens = ?profile.allergens, diets = ?profile.dietary_prefs, "User profile fetched successfully");
-            (profile.allergens, profile.dietary_prefs)
-        }
-        HttpStatus::NOT_FOUND => {
-            warn!(
-                user_id = DUMMY_USER_ID,
-                "User profile not found. Proceeding without personalization filters."
-            );
-            (Vec::new(), Vec::new())
-        }
-        status => {
-            let error_body = profile_resp
-                .text()
-                .await
-                .unwrap_or_else(|_| "Failed to read error body".to_string());
-            error!(%status, body = %error_body, "User profile service request failed");
-            return Err(ServiceError::Internal(format!(
-                "User profile service failed with status {}",
-                status
-            )));
-        }
+    let (user_allergens, user_diets, personalized) = match recommendation_profile(
+        &state.upstream_client,
+        &state.user_profile_service_url,
+        user_id,
+        authorization,
+    )
+    .await?
+    {
+        Some(profile) => (profile.allergens, profile.dietary_prefs, true),
+        None => (Vec::new(), Vec::new(), false),
     };
 
     let mut must_not_conditions: Vec<Condition> = Vec::new();
@@ -743,18 +1773,19 @@ pub async fn get_recommendations(
         })),
     });
 
-    if !user_allergens.is_empty() {
+    let allergen_exclusion_tags = allergen_tags(&user_allergens);
+    if !allergen_exclusion_tags.is_empty() {
         debug!(
-            "Adding Qdrant filter for user_allergens on 'labels_tags': {:?}",
-            user_allergens
+            "Adding Qdrant filter for user allergens on 'allergens_tags': {:?}",
+            allergen_exclusion_tags
         );
         must_not_conditions.push(Condition {
             condition_one_of: Some(ConditionOneOf::Field(FieldCondition {
-                key: "labels_tags".to_string(), // Ensure this field is indexed for filtering in Qdrant
+                key: "allergens_tags".to_string(), // Ensure this field is indexed for filtering in Qdrant
                 r#match: Some(qdrant_client::qdrant::Match {
                     // Corrected: direct struct instantiation
                     match_value: Some(MatchValue::Keywords(RepeatedStrings {
-                        strings: user_allergens,
+                        strings: allergen_exclusion_tags,
                     })),
                 }),
                 ..Default::default() // Use default for other FieldCondition fields
@@ -762,14 +1793,20 @@ pub async fn get_recommendations(
         });
     }
 
-    if user_diets.contains(&"vegan".to_string()) {
-        debug!("Adding Qdrant filter for vegan diet (excluding 'non-vegan' from 'labels_tags')");
-        let diet_exclusion_tags = vec!["non-vegan".to_string()]; // Example, adjust as per your tags
+    if params.allergen_mode.unwrap_or_default() == AllergenMode::Strict {
+        must_not_conditions.extend(unknown_allergen_conditions());
+    }
+
+    let diet_exclusion_tags = diet_exclusion_tags(&user_diets);
+    if !diet_exclusion_tags.is_empty() {
+        debug!(
+            "Adding Qdrant filter for user diets on 'labels_tags': {:?}",
+            diet_exclusion_tags
+        );
         must_not_conditions.push(Condition {
             condition_one_of: Some(ConditionOneOf::Field(FieldCondition {
                 key: "labels_tags".to_string(), // Ensure this field is indexed
                 r#match: Some(qdrant_client::qdrant::Match {
-                    // Corrected: direct struct instantiation
                     match_value: Some(MatchValue::Keywords(RepeatedStrings {
                         strings: diet_exclusion_tags,
                     })),
@@ -791,7 +1828,7 @@ pub async fn get_recommendations(
         collection_name: QDRANT_COLLECTION_NAME.into(),
         vector: target_vector,
         filter: Some(qdrant_filter),
-        limit: 20,
+        limit: (recommendation_limit * RECOMMENDATION_OVERFETCH) as u64,
         offset: Some(0),
         with_payload: Some(WithPayloadSelector {
             selector_options: Some(
@@ -799,7 +1836,7 @@ pub async fn get_recommendations(
             ),
         }),
         with_vectors: None,
-        score_threshold: None,
+        score_threshold: params.min_score,
         params: None,
         vector_name: None,
         read_consistency: None,
@@ -809,23 +1846,278 @@ pub async fn get_recommendations(
     };
 
     info!("Performing Qdrant similarity search...");
-    let search_result = state.qdrant_client.search_points(search_request).await?;
+    let search_result = observe_qdrant(
+        "search_points",
+        clients.qdrant_client.search_points(search_request),
+    )
+    .await?;
     debug!(
         "Qdrant search returned {} results",
         search_result.result.len()
     );
 
-    let mut candidate_barcodes: Vec<String> = Vec::new();
-    for scored_point in search_result.result {
+    let candidates = scored_barcodes(search_result.result);
+    if candidates.is_empty() {
+        info!("No suitable candidates found after Qdrant search (no valid barcodes extracted).");
+        return Ok(Recommendations {
+            products: vec![],
+            personalized,
+        });
+    }
+    debug!("Candidate barcodes from Qdrant: {:?}", candidates);
+
+    info!(
+        "Fetching details for up to {} candidate products by barcode from MongoDB",
+        candidates.len()
+    );
+    let codes: Vec<String> = candidates.iter().map(|(code, _)| code.clone()).collect();
+    let found = state
+        .products
+        .find_by_codes(codes, candidates.len())
+        .await?;
+    let recommended_products = rank_by_score(&candidates, found, recommendation_limit);
+
+    info!(
+        "Returning {} recommended products.",
+        recommended_products.len()
+    );
+    Ok(Recommendations {
+        products: recommended_products,
+        personalized,
+    })
+}
+
+/// The point a product's vector is indexed under: a UUIDv5 of its ObjectId string, as
+/// the sync worker derives it.
+pub(crate) fn product_point_id(product_id_str: &str) -> PointId {
+    product_point_uuid(product_id_str).into()
+}
+
+fn product_point_uuid(product_id_str: &str) -> String {
+    Uuid::new_v5(&Uuid::NAMESPACE_DNS, product_id_str.as_bytes()).to_string()
+}
+
+/// The product's vector in the Qdrant index, `None` if it has not been indexed.
+pub(crate) async fn product_vector(
+    clients: &Clients,
+    product_id_str: &str,
+) -> Result<Option<Vec<f32>>> {
+    let point_id = product_point_id(product_id_str);
+    debug!(
+        "Source product Mongo OID: {}, Qdrant point for vector fetch: {:?}",
+        product_id_str, point_id
+    );
+
+    let get_request = GetPointsBuilder::new(QDRANT_COLLECTION_NAME.to_string(), vec![point_id])
+        .with_payload(false)
+        .with_vectors(true);
+
+    let retrieve_result =
+        observe_qdrant("get_points", clients.qdrant_client.get_points(get_request)).await?;
+
+    let Some(target_vector) = retrieve_result
+        .result
+        .into_iter()
+        .next()
+        .and_then(point_vector)
+    else {
+        debug!("No vector in Qdrant for Mongo OID: {}", product_id_str);
+        return Ok(None);
+    };
+
+    if target_vector.is_empty() {
+        error!(
+            "Retrieved empty target vector for source Mongo OID: {}",
+            product_id_str
+        );
+        return Err(ServiceError::Internal(format!(
+            "Empty vector found for product OID {}",
+            product_id_str
+        )));
+    }
+    debug!(
+        "Target vector for source product (Mongo OID: {}) retrieved successfully (size: {})",
+        product_id_str,
+        target_vector.len()
+    );
+    Ok(Some(target_vector))
+}
+
+/// The vectors of the products with these ObjectId strings, by id, fetched in one call.
+/// Products that have not been indexed, or whose vector is empty, are left out.
+pub(crate) async fn product_vectors(
+    clients: &Clients,
+    product_id_strs: &[String],
+) -> Result<HashMap<String, Vec<f32>>> {
+    if product_id_strs.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let mut ids_by_point: HashMap<String, &str> = product_id_strs
+        .iter()
+        .map(|id| (product_point_uuid(id), id.as_str()))
+        .collect();
+    let point_ids: Vec<PointId> = ids_by_point.keys().cloned().map(PointId::from).collect();
+    let get_request = GetPointsBuilder::new(QDRANT_COLLECTION_NAME.to_string(), point_ids)
+        .with_payload(false)
+        .with_vectors(true);
+    let retrieve_result =
+        observe_qdrant("get_points", clients.qdrant_client.get_points(get_request)).await?;
+
+    let mut vectors = HashMap::new();
+    for point in retrieve_result.result {
+        let point_uuid = match point
+            .id
+            .as_ref()
+            .and_then(|id| id.point_id_options.as_ref())
+        {
+            Some(PointIdOptions::Uuid(uuid)) => uuid.clone(),
+            _ => continue,
+        };
+        let Some(product_id) = ids_by_point.remove(&point_uuid) else {
+            continue;
+        };
+        match point_vector(point) {
+            Some(vector) if !vector.is_empty() => {
+                vectors.insert(product_id.to_string(), vector);
+            }
+            _ => debug!("No usable vector in Qdrant for Mongo OID: {}", product_id),
+        }
+    }
+    debug!(
+        "Fetched {} of {} product vectors from Qdrant",
+        vectors.len(),
+        product_id_strs.len()
+    );
+    Ok(vectors)
+}
+
+/// The unnamed dense vector of a point fetched `with_vectors`.
+fn point_vector(point: RetrievedPoint) -> Option<Vec<f32>> {
+    point
+        .vectors
+        .and_then(|vectors| vectors.vectors_options)
+        .and_then(|options| match options {
+            vectors_output::VectorsOptions::Vector(v) => Some(v.into_vector()),
+            _ => None,
+        })
+        .and_then(|vector| match vector {
+            vector_output::Vector::Dense(dense) => Some(dense.data),
+            _ => None,
+        })
+}
+
+/// The safety profile of `user_id` to narrow recommendations with. Anonymous requests
+/// skip the profile service; users it has no profile for get unpersonalized results too,
+/// as does everyone while the service is failing: `client` retries timeouts, connection
+/// errors and 5xx a few times first, and recommendations without the profile beat none.
+/// A 401 or 403 is an error: the profile exists but may not be read, and leaving out
+/// the user's filters would pass that off as an anonymous request. The profile route only
+/// serves a user's own profile, so `authorization` is forwarded as it came.
+pub async fn recommendation_profile(
+    client: &ResilientClient,
+    user_profile_service_url: &str,
+    user_id: Option<&str>,
+    authorization: Option<&HeaderValue>,
+) -> Result<Option<SafetyProfile>> {
+    let Some(user_id) = user_id else {
+        debug!("No user on the request; recommendations are not personalized.");
+        return Ok(None);
+    };
+    let profile_url = format!(
+        "{}/api/v1/users/{}/profile",
+        user_profile_service_url, user_id
+    );
+    debug!("Fetching user profile from: {}", profile_url);
+    let mut headers = HeaderMap::new();
+    if let Some(value) = authorization {
+        headers.insert(AUTHORIZATION, value.clone());
+    }
+
+    match client
+        .get_json_with_headers::<SafetyProfile>(&profile_url, headers)
+        .await
+    {
+        Ok(profile) => {
+            debug!(allergens = ?profile.allergens, diets = ?profile.dietary_prefs, "User profile fetched successfully");
+            Ok(Some(profile))
+        }
+        Err(UpstreamError {
+            kind: UpstreamErrorKind::Status(404),
+            ..
+        }) => {
+            warn!(
+                user_id,
+                "User profile not found. Proceeding without personalization filters."
+            );
+            Ok(None)
+        }
+        Err(
+            e @ UpstreamError {
+                kind: UpstreamErrorKind::Status(401 | 403),
+                ..
+            },
+        ) => {
+            error!(user_id, "User profile service refused the profile: {}", e);
+            Err(e.into())
+        }
+        Err(e) => {
+            warn!(
+                user_id,
+                "User profile service request failed: {}. Proceeding without personalization filters.",
+                e
+            );
+            Ok(None)
+        }
+    }
+}
+
+/// Up to `limit` of `products`, in the order of their codes in `candidates` and with
+/// their scores. MongoDB returns products in no particular order; candidates it did not
+/// return are left out.
+pub(crate) fn rank_by_score(
+    candidates: &[(String, f32)],
+    products: Vec<Product>,
+    limit: usize,
+) -> Vec<RecommendedProduct> {
+    let mut by_code: HashMap<String, Product> = products
+        .into_iter()
+        .map(|product| (product.code.clone(), product))
+        .collect();
+    candidates
+        .iter()
+        .filter_map(|(code, score)| {
+            by_code.remove(code).map(|product| RecommendedProduct {
+                product,
+                score: *score,
+            })
+        })
+        .take(limit)
+        .collect()
+}
+
+/// The barcodes in the points' `code` payload, best score first, each once.
+pub(crate) fn barcodes_in_score_order(points: Vec<ScoredPoint>) -> Vec<String> {
+    scored_barcodes(points)
+        .into_iter()
+        .map(|(code, _)| code)
+        .collect()
+}
+
+/// The barcodes in the points' `code` payload with their scores, best first, each once
+/// with its best score. Points without a usable code are logged and skipped.
+pub(crate) fn scored_barcodes(points: Vec<ScoredPoint>) -> Vec<(String, f32)> {
+    let mut seen = HashSet::new();
+    let mut barcodes: Vec<(String, f32)> = Vec::new();
+    for scored_point in points {
         if let Some(payload_value) = scored_point.payload.get(QDRANT_CODE_PAYLOAD_KEY) {
             if let Some(Kind::StringValue(barcode_str)) = &payload_value.kind {
-                if !barcode_str.is_empty() {
-                    candidate_barcodes.push(barcode_str.clone());
-                } else {
+                if barcode_str.is_empty() {
                     warn!(
                         "Qdrant point ID {:?} had empty '{}' in payload.",
                         scored_point.id, QDRANT_CODE_PAYLOAD_KEY
                     );
+                } else if seen.insert(barcode_str.clone()) {
+                    barcodes.push((barcode_str.clone(), scored_point.score));
                 }
             } else {
                 warn!(
@@ -840,50 +2132,634 @@ pub async fn get_recommendations(
             );
         }
     }
+    barcodes
+}
 
-    if candidate_barcodes.is_empty() {
-        info!("No suitable candidates found after Qdrant search (no valid barcodes extracted).");
-        return Ok(Json(vec![]));
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{Nutriments, TagMatch},
+        repository,
+    };
+    use qdrant_client::qdrant::Value;
+    use rust_database_clients::http_resilience::ResilienceConfig;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{header, method, path},
+    };
+    use yoloeats_domain::{
+        IngredientEntry,
+        fixtures::{ProductFixture, UserProfileFixture},
+    };
+    use yoloeats_tracing::{REQUEST_ID_HEADER, RequestId, with_request_id};
+
+    #[test]
+    fn structured_ingredients_add_to_the_allergens_and_labels() {
+        let before: Product = ProductFixture::new("4000417025005")
+            .with_allergens(["en:soybeans"])
+            .with_labels(["en:organic"])
+            .build();
+        let milk = IngredientEntry {
+            id: Some("en:milk".to_string()),
+            vegan: Some("no".to_string()),
+            ..Default::default()
+        };
+
+        let kept = with_ingredient_hints(
+            ProductChanges {
+                ingredients: Some(vec![milk.clone()]),
+                ..Default::default()
+            },
+            &before,
+        );
+        assert_eq!(
+            kept.allergens_tags,
+            Some(vec!["en:soybeans".to_string(), "en:milk".to_string()])
+        );
+        assert_eq!(
+            kept.labels,
+            Some(vec!["en:organic".to_string(), "en:non-vegan".to_string()])
+        );
+
+        let replaced = with_ingredient_hints(
+            ProductChanges {
+                ingredients: Some(vec![milk]),
+                allergens_tags: Some(vec!["en:milk".to_string()]),
+                ..Default::default()
+            },
+            &before,
+        );
+        assert_eq!(replaced.allergens_tags, Some(vec!["en:milk".to_string()]));
+
+        let text_only = ProductChanges {
+            ingredients_text: Some("milk".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(with_ingredient_hints(text_only.clone(), &before), text_only);
     }
 
-    let unique_candidate_barcodes: Vec<String> = candidate_barcodes
-        .into_iter()
-        .collect::<HashSet<_>>()
-        .into_iter()
-        .collect();
-    debug!(
-        "Unique candidate barcodes from Qdrant: {:?}",
-        unique_candidate_barcodes
-    );
+    #[test]
+    fn a_new_ingredients_text_refreshes_the_allergens_it_named() {
+        let before: Product = ProductFixture::new("4000417025005")
+            .with_ingredients("Zucker, Weizenmehl, Vollmilchpulver")
+            .with_allergens(["en:gluten", "en:milk", "en:sesame-seeds"])
+            .build();
+        let text = |text: &str| ProductChanges {
+            ingredients_text: Some(text.to_string()),
+            ..Default::default()
+        };
+
+        let refreshed = with_text_allergens(text("Zucker, Dinkelmehl, Haselnüsse"), &before);
+        assert_eq!(
+            refreshed.allergens_tags,
+            Some(vec![
+                "en:gluten".to_string(),
+                "en:sesame-seeds".to_string(),
+                "en:nuts".to_string(),
+            ]),
+            "milk goes with the old text, sesame came from elsewhere and stays"
+        );
 
-    const FINAL_RECOMMENDATION_LIMIT: usize = 10;
-    let final_barcodes_to_fetch: Vec<String> = unique_candidate_barcodes
-        .into_iter()
-        .take(FINAL_RECOMMENDATION_LIMIT)
-        .collect();
+        let cleared = with_text_allergens(
+            ProductChanges {
+                unset: vec![ProductField::IngredientsText],
+                ..Default::default()
+            },
+            &before,
+        );
+        assert_eq!(
+            cleared.allergens_tags,
+            Some(vec!["en:sesame-seeds".to_string()])
+        );
 
-    if final_barcodes_to_fetch.is_empty() {
-        info!("No barcodes to fetch from MongoDB after limiting.");
-        return Ok(Json(vec![]));
+        let unchanged = text("Weizenmehl, Milch, Zucker");
+        assert_eq!(with_text_allergens(unchanged.clone(), &before), unchanged);
+
+        let explicit = ProductChanges {
+            allergens_tags: Some(vec!["en:eggs".to_string()]),
+            ..text("Haselnüsse")
+        };
+        assert_eq!(with_text_allergens(explicit.clone(), &before), explicit);
+
+        let name_only = ProductChanges {
+            product_name: Some("Nussmischung".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(with_text_allergens(name_only.clone(), &before), name_only);
     }
 
-    info!(
-        "Fetching details for up to {} products by barcode from MongoDB",
-        final_barcodes_to_fetch.len()
-    );
+    fn nutella_nutriments() -> Nutriments {
+        Nutriments {
+            energy_kcal_100g: Some(539.0),
+            sugars_100g: Some(56.3),
+            saturated_fat_100g: Some(10.6),
+            salt_100g: Some(0.107),
+            proteins_100g: Some(6.3),
+            ..Default::default()
+        }
+    }
 
-    let mongo_filter = doc! { "code": { "$in": final_barcodes_to_fetch } };
-    let collection = state.mongo_db.collection::<Product>("products");
+    #[test]
+    fn updates_grade_products_without_a_declared_grade() {
+        let ungraded: Product = ProductFixture::new("3017620422003").build();
+        let changes = with_computed_grade(
+            ProductChanges {
+                nutriments: Some(nutella_nutriments()),
+                ..Default::default()
+            },
+            &ungraded,
+        );
+        assert_eq!(changes.nutrition_grade_fr.as_deref(), Some("e"));
+        assert_eq!(
+            changes.nutrition_grade_source.as_deref(),
+            Some(COMPUTED_GRADE_SOURCE)
+        );
 
-    let cursor = collection
-        .find(mongo_filter)
-        .limit(FINAL_RECOMMENDATION_LIMIT as i64)
-        .await?;
-    let recommended_products: Vec<Product> = cursor.try_collect().await?;
+        // A declared grade stays, whatever the nutriments say.
+        let declared: Product = ProductFixture::new("3017620422003")
+            .with_nutriscore("d")
+            .build();
+        let changes = with_computed_grade(
+            ProductChanges {
+                nutriments: Some(nutella_nutriments()),
+                ..Default::default()
+            },
+            &declared,
+        );
+        assert_eq!(changes.nutrition_grade_fr, None);
+        assert!(changes.unset.is_empty());
+
+        // A renamed product keeps its computed grade without scoring it again.
+        let mut computed = ungraded.clone();
+        computed.nutriments = Some(nutella_nutriments());
+        computed.nutrition_grade_fr = Some("e".to_string());
+        computed.nutrition_grade_source = Some(COMPUTED_GRADE_SOURCE.to_string());
+        let rename = ProductChanges {
+            product_name: Some("Nutella".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(with_computed_grade(rename.clone(), &computed), rename);
+    }
 
-    info!(
-        "Returning {} recommended products.",
-        recommended_products.len()
-    );
-    Ok(Json(recommended_products))
+    #[test]
+    fn declared_grades_replace_computed_ones() {
+        let mut computed: Product = ProductFixture::new("3017620422003").build();
+        computed.nutriments = Some(nutella_nutriments());
+        computed.nutrition_grade_fr = Some("e".to_string());
+        computed.nutrition_grade_source = Some(COMPUTED_GRADE_SOURCE.to_string());
+
+        let declared = with_computed_grade(
+            ProductChanges {
+                nutrition_grade_fr: Some("d".to_string()),
+                ..Default::default()
+            },
+            &computed,
+        );
+        assert_eq!(declared.nutrition_grade_fr.as_deref(), Some("d"));
+        assert_eq!(declared.unset, [ProductField::NutritionGradeSource]);
+
+        // Nutriments too sparse to score take the computed grade away.
+        let sparse = with_computed_grade(
+            ProductChanges {
+                nutriments: Some(Nutriments {
+                    sugars_100g: Some(56.3),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            &computed,
+        );
+        assert_eq!(sparse.nutrition_grade_fr, None);
+        assert_eq!(
+            sparse.unset,
+            [
+                ProductField::NutritionGrade,
+                ProductField::NutritionGradeSource
+            ]
+        );
+    }
+
+    #[test]
+    fn every_update_field_reaches_the_stored_document() {
+        // A literal, so a new payload field doesn't compile until it is listed here.
+        let payload = UpdateProductPayload {
+            product_name: Some("Alpine milk chocolate".to_string()),
+            generic_name: Some("Chocolate".to_string()),
+            image_url: Some("https://images.example/front.jpg".to_string()),
+            ingredients_text: Some("Sugar, cocoa butter, milk powder".to_string()),
+            ingredients: Some(vec![IngredientEntry {
+                id: Some("en:sugar".to_string()),
+                ..Default::default()
+            }]),
+            brands: Some(vec!["Milka".to_string()]),
+            categories: Some(vec!["en:Chocolates".to_string()]),
+            labels: Some(vec!["EN:Fair Trade".to_string()]),
+            traces: Some(vec!["en:Nuts".to_string()]),
+            allergens_tags: Some(
+                ["Milk", "en:Peanuts", "Soy", "sesame", "sulphites", "kiwi"]
+                    .map(String::from)
+                    .to_vec(),
+            ),
+            quantity: Some("100 g".to_string()),
+            countries: Some(vec!["en:Germany".to_string()]),
+            nutrition_grade_fr: Some("e".to_string()),
+            ecoscore_grade: Some("B".to_string()),
+            ecoscore_score: Some(62.0),
+            nutriments: Some(Nutriments {
+                sugars_100g: Some(56.3),
+                ..Default::default()
+            }),
+        };
+        let sent = serde_json::to_value(&payload).unwrap();
+        let set = repository::set_document(&update_changes(payload));
+
+        assert_eq!(set.len(), sent.as_object().unwrap().len());
+        assert_eq!(
+            set.get_array("allergens_tags").unwrap(),
+            &[
+                "en:milk",
+                "en:peanuts",
+                "en:soybeans",
+                "en:sesame-seeds",
+                "en:sulphur-dioxide-and-sulphites"
+            ]
+            .map(bson::Bson::from)
+            .to_vec()
+        );
+        assert_eq!(
+            set.get_array("labels_tags").unwrap(),
+            &vec![bson::Bson::from("en:fair-trade")]
+        );
+        assert_eq!(set.get_str("ecoscore_grade"), Ok("b"));
+        for key in [
+            "product_name",
+            "generic_name",
+            "image_url",
+            "ingredients_text",
+            "ingredients",
+            "brands_tags",
+            "categories_tags",
+            "traces_tags",
+            "quantity",
+            "countries_tags",
+            "nutrition_grade_fr",
+            "ecoscore_grade",
+            "ecoscore_score",
+            "nutriments",
+        ] {
+            assert!(set.contains_key(key), "{}", key);
+        }
+    }
+
+    #[test]
+    fn search_filter_takes_one_or_many_tags_in_any_case() {
+        let single = SearchParams {
+            brand: vec!["Ritter Sport".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(search_filter(&single).brands, ["ritter-sport"]);
+
+        let many = SearchParams {
+            category: vec!["EN:Snacks".to_string(), "chocolates".to_string()],
+            category_match: TagMatch::All,
+            country: vec!["en:germany".to_string(), "Germany".to_string()],
+            label: vec!["Organic".to_string(), "en:fair-trade".to_string()],
+            ..Default::default()
+        };
+        let filter = search_filter(&many);
+        assert_eq!(filter.categories, ["en:snacks", "en:chocolates"]);
+        assert_eq!(filter.category_match, TagMatch::All);
+        assert_eq!(filter.countries, ["en:germany"]);
+        assert_eq!(filter.labels, ["en:organic", "en:fair-trade"]);
+        assert!(filter.brands.is_empty());
+
+        assert_eq!(
+            search_filter(&SearchParams::default()),
+            ProductFilter::default()
+        );
+    }
+
+    #[test]
+    fn strict_recommendations_leave_out_points_of_unknown_allergens() {
+        let keys: Vec<(&str, String)> = unknown_allergen_conditions()
+            .into_iter()
+            .map(|condition| match condition.condition_one_of {
+                Some(ConditionOneOf::IsEmpty(empty)) => ("is_empty", empty.key),
+                Some(ConditionOneOf::IsNull(null)) => ("is_null", null.key),
+                other => panic!("unexpected condition {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                ("is_empty", "ingredients_text".to_string()),
+                ("is_null", "allergens_tags".to_string()),
+            ]
+        );
+        let strict = SearchParams {
+            allergen_mode: AllergenMode::Strict,
+            ..Default::default()
+        };
+        assert_eq!(search_filter(&strict).allergen_mode, AllergenMode::Strict);
+    }
+
+    #[test]
+    fn search_filter_excludes_traces_of_excluded_ingredients_on_request() {
+        let mut params = SearchParams {
+            ingredients_include: vec!["Oats".to_string()],
+            ingredients_exclude: vec!["Palm Oil".to_string()],
+            ..Default::default()
+        };
+        let filter = search_filter(&params);
+        assert_eq!(filter.included_ingredients, ["Oats"]);
+        assert_eq!(filter.excluded_ingredients, ["Palm Oil"]);
+        assert!(filter.excluded_traces.is_empty());
+
+        params.exclude_traces = true;
+        assert_eq!(search_filter(&params).excluded_traces, ["en:palm-oil"]);
+    }
+
+    #[test]
+    fn only_unfiltered_and_single_country_counts_are_cached() {
+        let key = |params: SearchParams| count_cache_key(&search_filter(&params));
+        assert_eq!(
+            key(SearchParams::default()).as_deref(),
+            Some("product:count:all")
+        );
+        assert_eq!(
+            key(SearchParams {
+                country: vec!["En:Germany".to_string()],
+                sort: Some(SearchSort::Id),
+                include_count: true,
+                ..Default::default()
+            })
+            .as_deref(),
+            Some("product:count:country:en:germany")
+        );
+        for params in [
+            SearchParams {
+                country: vec!["en:germany".to_string(), "en:france".to_string()],
+                ..Default::default()
+            },
+            SearchParams {
+                country: vec!["en:germany".to_string()],
+                brand: vec!["milka".to_string()],
+                ..Default::default()
+            },
+            SearchParams {
+                q: Some("nutella".to_string()),
+                ..Default::default()
+            },
+            SearchParams {
+                max_sugar: Some(5.0),
+                ..Default::default()
+            },
+        ] {
+            assert_eq!(key(params), None);
+        }
+    }
+
+    #[test]
+    fn a_query_ranks_by_relevance_unless_sorted_otherwise() {
+        let query = |q: &str, sort: Option<SearchSort>| SearchParams {
+            q: Some(q.to_string()),
+            sort,
+            ..Default::default()
+        };
+        assert_eq!(
+            search_filter(&query("nutella", None)).sort,
+            SearchSort::Relevance
+        );
+        assert_eq!(
+            search_filter(&query("nutella", Some(SearchSort::Id))).sort,
+            SearchSort::Id
+        );
+        assert_eq!(
+            search_filter(&query(" ", Some(SearchSort::Relevance))).sort,
+            SearchSort::Id
+        );
+        assert_eq!(search_filter(&SearchParams::default()).sort, SearchSort::Id);
+    }
+
+    fn point(code: &str, score: f32) -> ScoredPoint {
+        ScoredPoint {
+            payload: [(
+                QDRANT_CODE_PAYLOAD_KEY.to_string(),
+                Value {
+                    kind: Some(Kind::StringValue(code.to_string())),
+                },
+            )]
+            .into(),
+            score,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn recommendations_keep_the_qdrant_score_order() {
+        let points = vec![
+            point("333", 0.95),
+            point("111", 0.90),
+            point("333", 0.85),
+            point("222", 0.80),
+        ];
+        let candidates = scored_barcodes(points);
+        assert_eq!(
+            candidates,
+            [
+                ("333".to_string(), 0.95),
+                ("111".to_string(), 0.90),
+                ("222".to_string(), 0.80),
+            ]
+        );
+
+        // As MongoDB might return them.
+        let found: Vec<Product> = ["111", "222", "333"]
+            .into_iter()
+            .map(|code| ProductFixture::new(code).build())
+            .collect();
+        let ranked = rank_by_score(&candidates, found.clone(), 10);
+        let ranked: Vec<(&str, f32)> = ranked
+            .iter()
+            .map(|r| (r.product.code.as_str(), r.score))
+            .collect();
+        assert_eq!(ranked, [("333", 0.95), ("111", 0.90), ("222", 0.80)]);
+
+        // A candidate missing from MongoDB leaves its place to the next one.
+        let ranked = rank_by_score(&candidates, found[..2].to_vec(), 2);
+        let codes: Vec<&str> = ranked.iter().map(|r| r.product.code.as_str()).collect();
+        assert_eq!(codes, ["111", "222"]);
+    }
+
+    async fn profile_service(user_id: &str, response: ResponseTemplate) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(format!("/api/v1/users/{}/profile", user_id)))
+            .respond_with(response)
+            .expect(1)
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn client() -> ResilientClient {
+        ResilientClient::new(reqwest::Client::new(), ResilienceConfig::default())
+    }
+
+    #[tokio::test]
+    async fn recommendations_use_the_profile_of_the_requesting_user() {
+        let profile = UserProfileFixture::new("user-1")
+            .allergic_to(["milk"])
+            .following(["vegan"]);
+        let server = profile_service(
+            "user-1",
+            ResponseTemplate::new(200).set_body_json(profile.json()),
+        )
+        .await;
+
+        let found = recommendation_profile(&client(), &server.uri(), Some("user-1"), None)
+            .await
+            .unwrap()
+            .expect("the user's profile");
+        assert_eq!(found.allergens, ["milk"]);
+        assert_eq!(found.dietary_prefs, ["vegan"]);
+    }
+
+    #[tokio::test]
+    async fn recommendations_are_unpersonalized_for_users_without_a_profile() {
+        let server = profile_service("user-2", ResponseTemplate::new(404)).await;
+        let found = recommendation_profile(&client(), &server.uri(), Some("user-2"), None).await;
+        assert!(found.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn a_refused_profile_fetch_is_an_error() {
+        for status in [401, 403] {
+            let server = profile_service("user-6", ResponseTemplate::new(status)).await;
+            let found =
+                recommendation_profile(&client(), &server.uri(), Some("user-6"), None).await;
+            assert!(
+                matches!(found, Err(ServiceError::Upstream(_))),
+                "{}: {:?}",
+                status,
+                found
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn a_failed_profile_fetch_is_retried() {
+        let profile = UserProfileFixture::new("user-4").allergic_to(["peanuts"]);
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/users/user-4/profile"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/users/user-4/profile"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(profile.json()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let found = recommendation_profile(&client(), &server.uri(), Some("user-4"), None)
+            .await
+            .unwrap()
+            .expect("the profile from the second attempt");
+        assert_eq!(found.allergens, ["peanuts"]);
+    }
+
+    #[tokio::test]
+    async fn recommendations_are_unpersonalized_while_the_profile_service_fails() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(3)
+            .mount(&server)
+            .await;
+        let found = recommendation_profile(&client(), &server.uri(), Some("user-5"), None).await;
+        assert!(found.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn the_profile_fetch_forwards_the_request_id() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(header(REQUEST_ID_HEADER, "recommend-7"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&server)
+            .await;
+        // Traced like the client `main.rs` builds.
+        let client = ResilientClient::new(
+            yoloeats_tracing::http_client(reqwest::Client::new()),
+            ResilienceConfig::default(),
+        );
+
+        let id = RequestId::parse("recommend-7").unwrap();
+        let found = with_request_id(
+            id,
+            recommendation_profile(&client, &server.uri(), Some("user-3"), None),
+        )
+        .await;
+        assert!(found.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn recommendations_are_for_the_named_user() {
+        let user = |value: Option<&'static str>| async move {
+            let mut request = axum::http::Request::builder();
+            if let Some(value) = value {
+                request = request.header(USER_ID_HEADER, value);
+            }
+            let (mut parts, ()) = request.body(()).unwrap().into_parts();
+            RecommendedFor::from_request_parts(&mut parts, &())
+                .await
+                .unwrap()
+        };
+        assert_eq!(user(Some(" user-7 ")).await.0.as_deref(), Some("user-7"));
+        assert_eq!(user(Some("  ")).await, RecommendedFor(None));
+        assert_eq!(user(None).await, RecommendedFor(None));
+    }
+
+    #[tokio::test]
+    async fn the_profile_fetch_forwards_the_callers_authorization() {
+        let profile = UserProfileFixture::new("user-7").allergic_to(["milk"]);
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/users/user-7/profile"))
+            .and(header("authorization", "Bearer user-7-token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(profile.json()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let authorization = HeaderValue::from_static("Bearer user-7-token");
+        let found = recommendation_profile(
+            &client(),
+            &server.uri(),
+            Some("user-7"),
+            Some(&authorization),
+        )
+        .await
+        .unwrap()
+        .expect("the user's profile");
+        assert_eq!(found.allergens, ["milk"]);
+    }
+
+    #[tokio::test]
+    async fn anonymous_recommendations_skip_the_profile_service() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+        let found = recommendation_profile(&client(), &server.uri(), None, None).await;
+        assert!(found.unwrap().is_none());
+    }
 }
@@ -0,0 +1,117 @@
+//! Dependency checks behind `/health/ready` and `/health/details`.
+
+use crate::state::AppState;
+use rust_database_clients::http_resilience::{BreakerState, ResilientClient};
+use yoloeats_health::{
+    Criticality, HealthCheckable, HealthRegistry, async_trait,
+    checks::{HttpCheck, MongoCheck, Neo4jCheck, QdrantCheck, RedisCheck},
+};
+
+/// The breaker `client` keeps for the host of `url`: down while it isn't closed, that is
+/// while calls there fail fast (recommendations go unpersonalized) or wait on a probe.
+pub struct CircuitCheck {
+    pub client: ResilientClient,
+    pub url: String,
+}
+
+#[async_trait]
+impl HealthCheckable for CircuitCheck {
+    async fn check_health(&self) -> Result<(), String> {
+        match self.client.breaker_snapshot(&self.url) {
+            Some(snapshot) if snapshot.state != BreakerState::Closed => Err(format!(
+                "circuit {:?} after {} consecutive failures",
+                snapshot.state, snapshot.consecutive_failures
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Only Mongo is critical: without Redis, Qdrant, Neo4j or the profile service the
+/// catalog still serves products, just without caching or recommendations.
+pub fn registry(state: &AppState) -> HealthRegistry {
+    let mut registry = HealthRegistry::new("product-catalog-service");
+    // Nothing external to check on in-memory storage but the profile service.
+    if let Some(clients) = &state.clients {
+        registry = registry
+            .register(
+                "mongodb",
+                Criticality::Critical,
+                MongoCheck(clients.mongo_db.clone()),
+            )
+            .register(
+                "redis",
+                Criticality::Informational,
+                RedisCheck(clients.redis_client.clone()),
+            )
+            .register(
+                "qdrant",
+                Criticality::Informational,
+                QdrantCheck(clients.qdrant_client.clone()),
+            )
+            .register(
+                "neo4j",
+                Criticality::Informational,
+                Neo4jCheck(clients.neo4j_client.clone()),
+            );
+    }
+    registry
+        .register(
+            "user-profile-service",
+            Criticality::Informational,
+            HttpCheck::live(state.http_client.clone(), &state.user_profile_service_url),
+        )
+        .register(
+            "user-profile-service-circuit",
+            Criticality::Informational,
+            CircuitCheck {
+                client: state.upstream_client.clone(),
+                url: state.user_profile_service_url.clone(),
+            },
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_database_clients::http_resilience::ResilienceConfig;
+    use std::time::Duration;
+    use wiremock::{Mock, MockServer, ResponseTemplate, matchers::method};
+
+    #[tokio::test]
+    async fn an_open_circuit_is_reported_down() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+        let config = ResilienceConfig {
+            max_retries: 0,
+            failure_threshold: 2,
+            open_cooldown: Duration::from_secs(60),
+            ..ResilienceConfig::default()
+        };
+        let check = CircuitCheck {
+            client: ResilientClient::new(reqwest::Client::new(), config),
+            url: server.uri(),
+        };
+        let profile_url = format!("{}/api/v1/users/user-1/profile", server.uri());
+
+        assert_eq!(check.check_health().await, Ok(()), "not called yet");
+        check
+            .client
+            .get_json::<serde_json::Value>(&profile_url)
+            .await
+            .unwrap_err();
+        assert_eq!(check.check_health().await, Ok(()), "one failure short");
+        check
+            .client
+            .get_json::<serde_json::Value>(&profile_url)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            check.check_health().await,
+            Err("circuit Open after 2 consecutive failures".to_string())
+        );
+    }
+}
@@ -0,0 +1,296 @@
+//! `highlight=true` on a search with a `q`: each result gets `highlights`, the fields
+//! that name a query term with the terms marked, for the app to bold them, like
+//! `{"field": "product_name", "snippet": "Ritter Sport <em>Alpenmilch</em>"}`.
+//!
+//! The terms are the words of `q`, found anywhere in a word and ignoring case and
+//! accents, as MongoDB's text search compares them but without its stemming: `chocolate`
+//! marks the start of `Chocolates`, while `chocolates` marks nothing in `Chocolate`.
+//! Matches that overlap or touch are marked once. The rest of a snippet is HTML-escaped,
+//! so the app can show it as it is, and an `ingredients_text` snippet keeps the
+//! [`SNIPPET_WORDS`] words around its first match.
+
+use crate::ingredient_terms::{fold, folded_words};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use utoipa::ToSchema;
+
+/// How many words an `ingredients_text` snippet keeps.
+pub const SNIPPET_WORDS: usize = 30;
+
+/// The field whose snippets are cut to [`SNIPPET_WORDS`].
+const INGREDIENTS_FIELD: &str = "ingredients_text";
+
+/// A field of a search result that names a query term.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Highlight {
+    /// The field as the product is serialized, like `product_name`.
+    pub field: String,
+    /// The field's text, HTML-escaped, with `<em>` around each match.
+    #[schema(example = "Ritter Sport <em>Alpenmilch</em>")]
+    pub snippet: String,
+}
+
+/// The highlights of `q` in `fields`, pairs of a field name and its text, in their
+/// order. Fields without a text or a match are left out.
+pub fn highlight<'a>(
+    q: &str,
+    fields: impl IntoIterator<Item = (&'a str, Option<&'a str>)>,
+) -> Vec<Highlight> {
+    let terms = folded_words(q);
+    fields
+        .into_iter()
+        .filter_map(|(field, text)| {
+            let text = text?;
+            let matches = matched_ranges(text, &terms);
+            let first = matches.first()?.start;
+            let snippet = if field == INGREDIENTS_FIELD {
+                cut_snippet(text, first, &matches)
+            } else {
+                mark(text, &matches, 0)
+            };
+            Some(Highlight {
+                field: field.to_string(),
+                snippet,
+            })
+        })
+        .collect()
+}
+
+/// The byte ranges of `text` that `terms` match, in order and merged where they
+/// overlap or touch.
+fn matched_ranges(text: &str, terms: &[Vec<char>]) -> Vec<Range<usize>> {
+    // Each folded character with the bytes of the character it comes from.
+    let folded: Vec<(char, Range<usize>)> = text
+        .char_indices()
+        .flat_map(|(at, c)| fold(c).map(move |folded| (folded, at..at + c.len_utf8())))
+        .collect();
+    let chars: Vec<char> = folded.iter().map(|(c, _)| *c).collect();
+
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for start in 0..chars.len() {
+        for term in terms {
+            if term.is_empty() || !chars[start..].starts_with(term) {
+                continue;
+            }
+            let bytes = folded[start].1.start..folded[start + term.len() - 1].1.end;
+            match ranges.last_mut() {
+                Some(last) if bytes.start <= last.end => last.end = last.end.max(bytes.end),
+                _ => ranges.push(bytes),
+            }
+        }
+    }
+    ranges
+}
+
+/// The [`SNIPPET_WORDS`] words of `text` around the one at byte `first`, marked, with
+/// `…` where words were cut.
+fn cut_snippet(text: &str, first: usize, matches: &[Range<usize>]) -> String {
+    let words = word_ranges(text);
+    let at = words.iter().position(|word| word.end > first).unwrap_or(0);
+    let start = at
+        .saturating_sub(SNIPPET_WORDS / 2)
+        .min(words.len().saturating_sub(SNIPPET_WORDS));
+    let end = (start + SNIPPET_WORDS).min(words.len());
+    let kept = words[start].start..words[end - 1].end;
+
+    // Terms have no spaces, so a match is either inside the kept words or outside.
+    let inside: Vec<Range<usize>> = matches
+        .iter()
+        .filter(|range| range.start >= kept.start && range.end <= kept.end)
+        .cloned()
+        .collect();
+    let mut snippet = mark(&text[kept.clone()], &inside, kept.start);
+    if start > 0 {
+        snippet.insert_str(0, "… ");
+    }
+    if end < words.len() {
+        snippet.push_str(" …");
+    }
+    snippet
+}
+
+/// The byte ranges of the whitespace-separated words of `text`.
+fn word_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (at, c) in text.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(word_start)) => {
+                words.push(word_start..at);
+                start = None;
+            }
+            (false, None) => start = Some(at),
+            _ => {}
+        }
+    }
+    if let Some(word_start) = start {
+        words.push(word_start..text.len());
+    }
+    words
+}
+
+/// `text` HTML-escaped, with `<em>` around `ranges`, which are offset by `offset`.
+fn mark(text: &str, ranges: &[Range<usize>], offset: usize) -> String {
+    let mut snippet = String::with_capacity(text.len());
+    let mut at = 0;
+    for range in ranges {
+        let (start, end) = (range.start - offset, range.end - offset);
+        escape_into(&mut snippet, &text[at..start]);
+        snippet.push_str("<em>");
+        escape_into(&mut snippet, &text[start..end]);
+        snippet.push_str("</em>");
+        at = end;
+    }
+    escape_into(&mut snippet, &text[at..]);
+    snippet
+}
+
+fn escape_into(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snippet(q: &str, text: &str) -> Option<String> {
+        highlight(q, [("product_name", Some(text))])
+            .pop()
+            .map(|highlight| highlight.snippet)
+    }
+
+    #[test]
+    fn terms_are_marked_ignoring_case() {
+        assert_eq!(
+            snippet("alpenmilch ritter", "Ritter Sport Alpenmilch").as_deref(),
+            Some("<em>Ritter</em> Sport <em>Alpenmilch</em>")
+        );
+        assert_eq!(
+            snippet("MILK", "Milk chocolate with milk").as_deref(),
+            Some("<em>Milk</em> chocolate with <em>milk</em>")
+        );
+        assert_eq!(
+            snippet("chocolate", "Chocolates").as_deref(),
+            Some("<em>Chocolate</em>s")
+        );
+        assert_eq!(snippet("chocolates", "Chocolate"), None);
+        assert_eq!(snippet("", "Chocolate"), None);
+        assert_eq!(snippet("  ", "Chocolate"), None);
+    }
+
+    #[test]
+    fn overlapping_and_touching_terms_are_marked_once() {
+        assert_eq!(
+            snippet("hazel hazelnut nut", "Hazelnut spread").as_deref(),
+            Some("<em>Hazelnut</em> spread")
+        );
+        assert_eq!(
+            snippet("milk chocolate", "Milkchocolate").as_deref(),
+            Some("<em>Milkchocolate</em>")
+        );
+        assert_eq!(snippet("aa", "aaa").as_deref(), Some("<em>aaa</em>"));
+        assert_eq!(
+            snippet("nut spread", "Nut-spread").as_deref(),
+            Some("<em>Nut</em>-<em>spread</em>")
+        );
+    }
+
+    #[test]
+    fn unicode_is_matched_and_kept_as_written() {
+        assert_eq!(
+            snippet("creme MUSLI", "Crème fraîche & Müsli").as_deref(),
+            Some("<em>Crème</em> fraîche &amp; <em>Müsli</em>")
+        );
+        assert_eq!(snippet("müsli", "MUSLI").as_deref(), Some("<em>MUSLI</em>"));
+        assert_eq!(snippet("ÖL", "Rapsöl").as_deref(), Some("Raps<em>öl</em>"));
+        assert_eq!(
+            snippet("straße", "STRASSE Straße").as_deref(),
+            Some("STRASSE <em>Straße</em>")
+        );
+        assert_eq!(
+            snippet("抹茶", "抹茶ラテ").as_deref(),
+            Some("<em>抹茶</em>ラテ")
+        );
+    }
+
+    #[test]
+    fn snippets_are_escaped() {
+        assert_eq!(
+            snippet("<b>", "<b>bold</b> & \"quoted\"").as_deref(),
+            Some("<em>&lt;b&gt;</em>bold&lt;/b&gt; &amp; &quot;quoted&quot;")
+        );
+        assert_eq!(
+            snippet("bold", "<script>bold</script>").as_deref(),
+            Some("&lt;script&gt;<em>bold</em>&lt;/script&gt;")
+        );
+    }
+
+    #[test]
+    fn only_the_fields_with_a_match_are_highlighted() {
+        let highlights = highlight(
+            "ritter",
+            [
+                ("product_name", Some("Alpenmilch")),
+                ("brands_tags", Some("ritter-sport")),
+                ("ingredients_text", None),
+            ],
+        );
+        assert_eq!(
+            highlights,
+            [Highlight {
+                field: "brands_tags".to_string(),
+                snippet: "<em>ritter</em>-sport".to_string(),
+            }]
+        );
+        assert!(highlight("ritter", [("product_name", Some("Alpenmilch"))]).is_empty());
+    }
+
+    #[test]
+    fn ingredient_snippets_keep_the_words_around_the_first_match() {
+        let words: Vec<String> = (1..=100).map(|n| format!("w{}", n)).collect();
+        let text = words.join(" ");
+        let ingredients = |q: &str| {
+            highlight(q, [(INGREDIENTS_FIELD, Some(text.as_str()))])
+                .pop()
+                .unwrap()
+                .snippet
+        };
+
+        let middle = ingredients("w50 w90");
+        assert_eq!(
+            middle,
+            format!(
+                "… {} <em>w50</em> {} …",
+                words[34..49].join(" "),
+                words[50..64].join(" ")
+            )
+        );
+        assert!(!middle.contains("w90"));
+
+        let start = ingredients("w2");
+        assert!(start.starts_with("w1 <em>w2</em> w3"), "{}", start);
+        assert!(start.ends_with("w30 …"), "{}", start);
+
+        let end = ingredients("w100");
+        assert!(end.starts_with("… w71 "), "{}", end);
+        assert!(end.ends_with("<em>w100</em>"), "{}", end);
+
+        assert_eq!(
+            highlight(
+                "sugar",
+                [(INGREDIENTS_FIELD, Some("Sugar, cocoa butter, milk"))]
+            )[0]
+            .snippet,
+            "<em>Sugar</em>, cocoa butter, milk"
+        );
+    }
+}
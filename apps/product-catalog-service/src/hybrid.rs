@@ -0,0 +1,377 @@
+//! `/api/v1/products/search/hybrid`: the text search's best matches for `q`, re-ranked
+//! by how near their vectors are to the query's.
+//!
+//! A `$text` search alone misses how products relate beyond their words, and the vector
+//! index alone misses exact names. Hybrid search takes the [`CANDIDATES`] best text
+//! matches, so everything it lists names a word of `q`, fetches their vectors from the
+//! `product_vectors` index in one call and orders them by [`rerank`]: each one's text
+//! score, relative to the best one's, blended with its cosine similarity to the query by
+//! `semantic_weight` ([`DEFAULT_SEMANTIC_WEIGHT`] when absent). Candidates without a
+//! vector are ranked by their text score alone.
+//!
+//! `q` is embedded as semantic search embeds it, unless a POSTed body brings its
+//! `vector`. Without an embedding service, when it fails to embed `q`, and on in-memory
+//! storage, which has no vector index, the results are in text score order.
+
+use crate::{
+    embedding::EMBEDDING_SERVICE_URL_ENV,
+    errors::{Result, ServiceError},
+    handlers::product_vectors,
+    models::{HybridSearchParams, HybridSearchPayload, Product, SearchHit, SearchSort},
+    repository::{ProductFilter, SearchFrom},
+    semantic::{Exclusions, embed, non_blank},
+    state::AppState,
+};
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use serde::Serialize;
+use std::sync::Arc;
+use tracing::{debug, info, instrument, warn};
+use utoipa::ToSchema;
+use validator::Validate;
+use yoloeats_domain::ErrorBody;
+use yoloeats_metrics::ValidJson;
+
+/// How many of the best text matches are re-ranked.
+pub const CANDIDATES: u64 = 100;
+
+/// The share of the vector similarity in a score when the request names none.
+pub const DEFAULT_SEMANTIC_WEIGHT: f64 = 0.5;
+
+const DEFAULT_LIMIT: u64 = 10;
+const MAX_LIMIT: u64 = 50;
+
+/// A product hybrid search lists, with what it was ranked by.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct HybridHit {
+    #[serde(flatten)]
+    pub product: Product,
+    /// What the results are ordered by, from 0 to 1, higher being better.
+    pub score: f64,
+    /// MongoDB's text score for `q`.
+    pub text_score: f64,
+    /// The cosine similarity of the product's vector to the query's; none when the
+    /// product has no vector or the search had no query vector.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<f32>,
+}
+
+/// What a text match is re-ranked by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Candidate {
+    pub text_score: f64,
+    pub similarity: Option<f32>,
+}
+
+/// The indexes of `candidates`, best first, each with its score: `semantic_weight` of
+/// its similarity, taken as 0 when negative, plus the rest of its text score divided by
+/// the best text score. A candidate without a similarity scores its relative text score
+/// alone. Ties keep the candidates' order.
+pub fn rerank(candidates: &[Candidate], semantic_weight: f64) -> Vec<(usize, f64)> {
+    let semantic_weight = semantic_weight.clamp(0.0, 1.0);
+    let best_text_score = candidates
+        .iter()
+        .map(|candidate| candidate.text_score)
+        .fold(0.0, f64::max);
+    let mut ranked: Vec<(usize, f64)> = candidates
+        .iter()
+        .enumerate()
+        .map(|(index, candidate)| {
+            let text = if best_text_score > 0.0 {
+                candidate.text_score / best_text_score
+            } else {
+                0.0
+            };
+            let score = match candidate.similarity {
+                Some(similarity) => {
+                    let semantic = f64::from(similarity).clamp(0.0, 1.0);
+                    (1.0 - semantic_weight) * text + semantic_weight * semantic
+                }
+                None => text,
+            };
+            (index, score)
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranked
+}
+
+/// The cosine similarity of `a` and `b`; none when their dimensions differ or either
+/// has no length.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> Option<f32> {
+    if a.len() != b.len() {
+        return None;
+    }
+    let (dot, norm_a, norm_b) = a
+        .iter()
+        .zip(b)
+        .fold((0.0, 0.0, 0.0), |(dot, na, nb), (x, y)| {
+            let (x, y) = (f64::from(*x), f64::from(*y));
+            (dot + x * y, na + x * x, nb + y * y)
+        });
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return None;
+    }
+    Some((dot / (norm_a.sqrt() * norm_b.sqrt())) as f32)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/products/search/hybrid",
+    tag = "v1",
+    params(
+        HybridSearchParams,
+    ),
+    responses(
+        (status = 200, description = "The best text matches, re-ranked by their similarity to `q`, best first.", body = Vec<HybridHit>),
+        (status = 400, description = "No `q`, or a `limit` or `semantic_weight` out of range.", body = ErrorBody),
+        (status = 429, description = "Over the client's rate limit; retry after `Retry-After`.", body = ErrorBody),
+        (status = 500, description = "Qdrant or MongoDB failed, or the text index is missing.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, params), fields(q = ?params.q))]
+pub async fn hybrid_search_products(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<HybridSearchParams>,
+) -> Result<Json<Vec<HybridHit>>> {
+    params.validate()?;
+    let q = non_blank(params.q)
+        .ok_or_else(|| ServiceError::BadRequest("Query parameter 'q' is required".to_string()))?;
+    let exclusions = Exclusions::from_query(params.allergens.as_deref(), params.diets.as_deref());
+    hybrid_search(
+        &state,
+        &q,
+        None,
+        &exclusions,
+        params.limit,
+        params.semantic_weight,
+    )
+    .await
+    .map(Json)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/products/search/hybrid",
+    tag = "v1",
+    request_body = HybridSearchPayload,
+    responses(
+        (status = 200, description = "The best text matches, re-ranked by their similarity to `vector`, or to `q` without one, best first.", body = Vec<HybridHit>),
+        (status = 400, description = "A blank `q`, or a body that is not JSON.", body = ErrorBody),
+        (status = 422, description = "A field of the wrong type or failing its rule.", body = ErrorBody),
+        (status = 429, description = "Over the client's rate limit; retry after `Retry-After`.", body = ErrorBody),
+        (status = 500, description = "Qdrant or MongoDB failed, or the text index is missing.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, payload), fields(q = ?payload.q))]
+pub async fn hybrid_search_by_body(
+    State(state): State<Arc<AppState>>,
+    ValidJson(payload): ValidJson<HybridSearchPayload>,
+) -> Result<Json<Vec<HybridHit>>> {
+    let q = non_blank(Some(payload.q))
+        .ok_or_else(|| ServiceError::BadRequest("'q' must not be blank".to_string()))?;
+    let exclusions = Exclusions::new(payload.allergens, payload.diets);
+    hybrid_search(
+        &state,
+        &q,
+        payload.vector,
+        &exclusions,
+        payload.limit,
+        payload.semantic_weight,
+    )
+    .await
+    .map(Json)
+}
+
+/// Up to `limit` of the [`CANDIDATES`] best text matches for `q`, in [`rerank`] order.
+pub async fn hybrid_search(
+    state: &AppState,
+    q: &str,
+    vector: Option<Vec<f32>>,
+    exclusions: &Exclusions,
+    limit: Option<u64>,
+    semantic_weight: Option<f64>,
+) -> Result<Vec<HybridHit>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT) as usize;
+    let semantic_weight = semantic_weight.unwrap_or(DEFAULT_SEMANTIC_WEIGHT);
+    let filter = ProductFilter {
+        text: Some(q.to_string()),
+        excluded_allergens: exclusions.allergens.clone(),
+        excluded_labels: exclusions.labels.clone(),
+        sort: SearchSort::Relevance,
+        ..Default::default()
+    };
+    let hits = state
+        .products
+        .search(&filter, SearchFrom::Offset(0), CANDIDATES)
+        .await?;
+    if hits.is_empty() {
+        info!("Hybrid search found no text matches.");
+        return Ok(Vec::new());
+    }
+
+    let similarities = similarities(state, q, vector, &hits).await?;
+    let candidates: Vec<Candidate> = hits
+        .iter()
+        .zip(similarities)
+        .map(|(hit, similarity)| Candidate {
+            text_score: hit.score.unwrap_or_default(),
+            similarity,
+        })
+        .collect();
+    let mut hits: Vec<Option<SearchHit>> = hits.into_iter().map(Some).collect();
+    let ranked: Vec<HybridHit> = rerank(&candidates, semantic_weight)
+        .into_iter()
+        .take(limit)
+        .filter_map(|(index, score)| {
+            hits[index].take().map(|hit| HybridHit {
+                product: hit.product,
+                score,
+                text_score: candidates[index].text_score,
+                similarity: candidates[index].similarity,
+            })
+        })
+        .collect();
+    info!(
+        "Hybrid search returning {} of {} text matches, {} with a vector.",
+        ranked.len(),
+        candidates.len(),
+        candidates.iter().filter(|c| c.similarity.is_some()).count()
+    );
+    Ok(ranked)
+}
+
+/// The similarity of each hit's vector to the query's, in order; all none when there is
+/// no vector index or `q` can't be embedded.
+async fn similarities(
+    state: &AppState,
+    q: &str,
+    vector: Option<Vec<f32>>,
+    hits: &[SearchHit],
+) -> Result<Vec<Option<f32>>> {
+    let Some(clients) = &state.clients else {
+        debug!("No vector index on in-memory storage; hybrid search ranks by text score.");
+        return Ok(vec![None; hits.len()]);
+    };
+    let query_vector = match vector {
+        Some(vector) => vector,
+        None if state.embeddings.is_none() => {
+            warn!(
+                "{} is not set; hybrid search ranks by text score.",
+                EMBEDDING_SERVICE_URL_ENV
+            );
+            return Ok(vec![None; hits.len()]);
+        }
+        None => match embed(state, q).await {
+            Ok(vector) => vector,
+            Err(ServiceError::Embedding(e)) => {
+                warn!(
+                    "Could not embed the query; hybrid search ranks by text score: {}",
+                    e
+                );
+                return Ok(vec![None; hits.len()]);
+            }
+            Err(e) => return Err(e),
+        },
+    };
+
+    let ids: Vec<String> = hits
+        .iter()
+        .filter_map(|hit| hit.product.id.map(|id| id.to_hex()))
+        .collect();
+    let vectors = product_vectors(clients, &ids).await?;
+    Ok(hits
+        .iter()
+        .map(|hit| {
+            let id = hit.product.id?.to_hex();
+            cosine_similarity(&query_vector, vectors.get(&id)?)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(text_score: f64, similarity: Option<f32>) -> Candidate {
+        Candidate {
+            text_score,
+            similarity,
+        }
+    }
+
+    fn order(ranked: &[(usize, f64)]) -> Vec<usize> {
+        ranked.iter().map(|(index, _)| *index).collect()
+    }
+
+    #[test]
+    fn scores_blend_the_relative_text_score_and_the_similarity() {
+        let candidates = [
+            candidate(4.0, Some(0.2)),
+            candidate(2.0, Some(0.9)),
+            candidate(1.0, Some(0.5)),
+        ];
+        let ranked = rerank(&candidates, 0.5);
+        assert_eq!(order(&ranked), [1, 0, 2]);
+        let scores: Vec<f64> = ranked.iter().map(|(_, score)| *score).collect();
+        for (score, expected) in scores.iter().zip([0.7, 0.6, 0.375]) {
+            assert!((score - expected).abs() < 1e-6, "{:?}", scores);
+        }
+    }
+
+    #[test]
+    fn the_weight_moves_the_ranking_between_text_and_vectors() {
+        let candidates = [candidate(4.0, Some(0.2)), candidate(2.0, Some(0.9))];
+        assert_eq!(order(&rerank(&candidates, 0.0)), [0, 1]);
+        assert_eq!(order(&rerank(&candidates, 1.0)), [1, 0]);
+        assert_eq!(order(&rerank(&candidates, 0.2)), [0, 1]);
+        assert_eq!(order(&rerank(&candidates, 0.8)), [1, 0]);
+        // Out of range weights are held to 0..=1.
+        assert_eq!(rerank(&candidates, 7.0), rerank(&candidates, 1.0));
+    }
+
+    #[test]
+    fn candidates_without_a_vector_keep_their_text_rank() {
+        let candidates = [
+            candidate(5.0, None),
+            candidate(4.0, Some(0.0)),
+            candidate(2.0, None),
+            candidate(1.0, Some(1.0)),
+        ];
+        let ranked = rerank(&candidates, 0.5);
+        assert_eq!(order(&ranked), [0, 3, 1, 2]);
+        assert_eq!(ranked[0].1, 1.0);
+        assert!((ranked[3].1 - 0.4).abs() < 1e-9);
+
+        let text_only = [candidate(1.0, None), candidate(3.0, None)];
+        assert_eq!(order(&rerank(&text_only, 0.9)), [1, 0]);
+    }
+
+    #[test]
+    fn negative_similarities_count_as_none_and_ties_keep_the_text_order() {
+        let candidates = [
+            candidate(2.0, Some(-0.8)),
+            candidate(2.0, Some(0.0)),
+            candidate(0.0, Some(0.0)),
+            candidate(0.0, None),
+        ];
+        let ranked = rerank(&candidates, 0.5);
+        assert_eq!(order(&ranked), [0, 1, 2, 3]);
+        assert_eq!(ranked[0].1, ranked[1].1);
+        assert!(rerank(&[], 0.5).is_empty());
+    }
+
+    #[test]
+    fn cosine_similarity_ignores_length_and_needs_matching_dimensions() {
+        let similarity = |a: &[f32], b: &[f32]| cosine_similarity(a, b).unwrap();
+        assert!((similarity(&[1.0, 0.0], &[3.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(similarity(&[1.0, 0.0], &[0.0, 2.0]).abs() < 1e-6);
+        assert!((similarity(&[1.0, 1.0], &[-1.0, -1.0]) + 1.0).abs() < 1e-6);
+        assert!((similarity(&[1.0, 1.0], &[1.0, 0.0]) - 0.707_106_77).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[1.0, 0.0, 0.0]), None);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), None);
+        assert_eq!(cosine_similarity(&[], &[]), None);
+    }
+}
@@ -0,0 +1,307 @@
+//! Product image URLs. Creates and updates only take an `image_url` that is http or https
+//! on one of the [`ImageHosts`], OpenFoodFacts' unless `IMAGE_HOSTS` names others; any
+//! other answers 400. With [`IMAGE_CHECK_ENABLED`] the image is also asked for with one
+//! `HEAD` request, waiting at most [`IMAGE_CHECK_TIMEOUT_MS`]: a 404 or 410 answers 400,
+//! while a host that is slow or fails lets the URL through.
+//!
+//! An OpenFoodFacts image URL names its size, like `front_en.400.jpg`, so the
+//! `image_small_url` and `image_thumb_url` lists and scans show are derived by
+//! rewriting it (see [`image_variants`]). Setting or clearing the image sets or clears
+//! them along with it; an image without a size to rewrite leaves the product without.
+
+use crate::{
+    errors::{Result, ServiceError},
+    repository::{ProductChanges, ProductField},
+    state::AppState,
+    tunables::{IMAGE_CHECK_ENABLED, IMAGE_CHECK_TIMEOUT_MS},
+};
+use reqwest::{StatusCode, Url};
+use std::{env, time::Duration};
+use tracing::{debug, error, warn};
+
+/// Comma-separated hosts product images may be on; unset or blank, [`DEFAULT_IMAGE_HOSTS`].
+pub const IMAGE_HOSTS_ENV: &str = "IMAGE_HOSTS";
+
+/// Where OpenFoodFacts serves product images.
+pub const DEFAULT_IMAGE_HOSTS: [&str; 2] = ["images.openfoodfacts.org", "static.openfoodfacts.org"];
+
+/// The path OpenFoodFacts product images are under.
+const OFF_IMAGE_PATH: &str = "/images/products/";
+
+/// Widths in pixels of the derived images.
+const SMALL_WIDTH: u32 = 200;
+const THUMB_WIDTH: u32 = 100;
+
+/// The hosts product images may be on, lowercase. Only these exact hosts pass, not
+/// their subdomains.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageHosts(Vec<String>);
+
+impl Default for ImageHosts {
+    fn default() -> Self {
+        ImageHosts::new(DEFAULT_IMAGE_HOSTS)
+    }
+}
+
+impl ImageHosts {
+    pub fn new<S: AsRef<str>>(hosts: impl IntoIterator<Item = S>) -> Self {
+        ImageHosts(
+            hosts
+                .into_iter()
+                .map(|host| host.as_ref().trim().to_lowercase())
+                .filter(|host| !host.is_empty())
+                .collect(),
+        )
+    }
+
+    /// The [`IMAGE_HOSTS_ENV`] hosts. An entry that isn't a bare host, like one with a
+    /// scheme or a path, stops the service from starting.
+    pub fn from_env() -> Result<Self> {
+        let hosts = match env::var(IMAGE_HOSTS_ENV) {
+            Ok(value) if !value.trim().is_empty() => ImageHosts::new(value.split(',')),
+            _ => return Ok(ImageHosts::default()),
+        };
+        if let Some(host) = hosts.0.iter().find(|host| !is_bare_host(host)) {
+            error!(
+                "{} must list hosts like images.openfoodfacts.org, got '{}'",
+                IMAGE_HOSTS_ENV, host
+            );
+            return Err(ServiceError::InvalidVariable(IMAGE_HOSTS_ENV.to_string()));
+        }
+        Ok(hosts)
+    }
+
+    pub fn hosts(&self) -> &[String] {
+        &self.0
+    }
+
+    /// `url` parsed, if it is http or https on one of the hosts; a 400 otherwise.
+    pub fn check(&self, url: &str) -> Result<Url> {
+        let parsed = Url::parse(url)
+            .map_err(|_| ServiceError::BadRequest(format!("Invalid image URL: {}", url)))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(ServiceError::BadRequest(format!(
+                "Image URL must be http or https: {}",
+                url
+            )));
+        }
+        let host = parsed.host_str().unwrap_or_default();
+        if !self.0.iter().any(|allowed| allowed == host) {
+            return Err(ServiceError::BadRequest(format!(
+                "Images on '{}' are not allowed; use one of {}",
+                host,
+                self.0.join(", ")
+            )));
+        }
+        Ok(parsed)
+    }
+}
+
+fn is_bare_host(host: &str) -> bool {
+    host.chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+}
+
+/// The smaller versions of an OpenFoodFacts product image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageVariants {
+    /// [`SMALL_WIDTH`] pixels wide, for lists.
+    pub small: String,
+    /// [`THUMB_WIDTH`] pixels wide.
+    pub thumb: String,
+}
+
+/// The smaller versions of `url` when it is an OpenFoodFacts product image naming its
+/// size: `/images/products/.../front_en.400.jpg` has `front_en.200.jpg` and
+/// `front_en.100.jpg` beside it, and so does `front_en.full.jpg`. Anything else,
+/// including the uploads named only by number like `1.jpg`, has none.
+pub fn image_variants(url: &str) -> Option<ImageVariants> {
+    let url = Url::parse(url).ok()?;
+    if !url.path().starts_with(OFF_IMAGE_PATH) {
+        return None;
+    }
+    let (dir, file) = url.path().rsplit_once('/')?;
+    let mut parts = file.rsplitn(3, '.');
+    let (extension, size, name) = (parts.next()?, parts.next()?, parts.next()?);
+    let sized = size == "full" || (!size.is_empty() && size.bytes().all(|b| b.is_ascii_digit()));
+    if name.is_empty() || extension.is_empty() || !sized {
+        return None;
+    }
+    let resized = |width: u32| {
+        let mut variant = url.clone();
+        variant.set_path(&format!("{}/{}.{}.{}", dir, name, width, extension));
+        variant.to_string()
+    };
+    Some(ImageVariants {
+        small: resized(SMALL_WIDTH),
+        thumb: resized(THUMB_WIDTH),
+    })
+}
+
+/// Checks `url` as a product's image, as the module describes, and derives its smaller
+/// versions.
+pub async fn check_image(state: &AppState, url: &str) -> Result<Option<ImageVariants>> {
+    let parsed = state.image_hosts.check(url)?;
+    if state.config.get(IMAGE_CHECK_ENABLED) {
+        let timeout = Duration::from_millis(state.config.get(IMAGE_CHECK_TIMEOUT_MS));
+        image_exists(&state.http_client, parsed, timeout).await?;
+    }
+    Ok(image_variants(url))
+}
+
+/// One `HEAD` of `url`: a 400 when the host says there is no such image, and nothing
+/// when it answers otherwise, late or not at all.
+pub async fn image_exists(client: &reqwest::Client, url: Url, timeout: Duration) -> Result<()> {
+    match client.head(url.clone()).timeout(timeout).send().await {
+        Ok(response) if matches!(response.status(), StatusCode::NOT_FOUND | StatusCode::GONE) => {
+            Err(ServiceError::BadRequest(format!(
+                "No image at {}: the host answered {}",
+                url,
+                response.status()
+            )))
+        }
+        Ok(response) => {
+            debug!(%url, status = %response.status(), "Checked product image");
+            Ok(())
+        }
+        Err(e) => {
+            warn!(%url, "Could not check product image, taking it unchecked: {}", e);
+            Ok(())
+        }
+    }
+}
+
+/// Checks the image `changes` set, if any, and sets or clears its smaller versions with
+/// it. Clearing the image clears them too.
+pub(crate) async fn with_checked_image(
+    state: &AppState,
+    mut changes: ProductChanges,
+) -> Result<ProductChanges> {
+    let variants = match &changes.image_url {
+        Some(url) => check_image(state, url).await?,
+        None if changes.unset.contains(&ProductField::ImageUrl) => None,
+        None => return Ok(changes),
+    };
+    match variants {
+        Some(variants) => {
+            changes.image_small_url = Some(variants.small);
+            changes.image_thumb_url = Some(variants.thumb);
+        }
+        None => changes
+            .unset
+            .extend([ProductField::ImageSmallUrl, ProductField::ImageThumbUrl]),
+    }
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{method, path},
+    };
+
+    const FRONT: &str =
+        "https://images.openfoodfacts.org/images/products/301/762/042/2003/front_en.400.jpg";
+
+    #[test]
+    fn off_images_get_small_and_thumb_variants() {
+        assert_eq!(
+            image_variants(FRONT),
+            Some(ImageVariants {
+                small: FRONT.replace(".400.jpg", ".200.jpg"),
+                thumb: FRONT.replace(".400.jpg", ".100.jpg"),
+            })
+        );
+        let full = "https://static.openfoodfacts.org/images/products/400/041/702/5005/ingredients_de.12.full.png?v=3";
+        assert_eq!(
+            image_variants(full).unwrap().small,
+            "https://static.openfoodfacts.org/images/products/400/041/702/5005/ingredients_de.12.200.png?v=3"
+        );
+
+        for unsized_or_elsewhere in [
+            "https://images.openfoodfacts.org/images/products/301/762/042/2003/1.jpg",
+            "https://images.openfoodfacts.org/images/products/301/762/042/2003/front_en.jpg",
+            "https://images.openfoodfacts.org/images/products/301/762/042/2003/front_en.big.jpg",
+            "https://images.openfoodfacts.org/images/products/301/762/042/2003/.400.jpg",
+            "https://images.openfoodfacts.org/logo/front_en.400.jpg",
+            "not a url",
+        ] {
+            assert_eq!(
+                image_variants(unsized_or_elsewhere),
+                None,
+                "{}",
+                unsized_or_elsewhere
+            );
+        }
+    }
+
+    #[test]
+    fn only_http_images_on_allowed_hosts_pass() {
+        let hosts = ImageHosts::default();
+        assert!(hosts.check(FRONT).is_ok());
+        assert!(
+            hosts
+                .check("http://STATIC.openfoodfacts.org/images/products/1/front.400.jpg")
+                .is_ok()
+        );
+        for refused in [
+            "https://images.example/front.jpg",
+            "https://evil.images.openfoodfacts.org/front.jpg",
+            "ftp://images.openfoodfacts.org/front.jpg",
+            "data:image/png;base64,iVBORw0KGgo=",
+            "front.jpg",
+        ] {
+            assert!(
+                matches!(hosts.check(refused), Err(ServiceError::BadRequest(_))),
+                "{}",
+                refused
+            );
+        }
+
+        let custom = ImageHosts::new([" Images.Example ", ""]);
+        assert_eq!(custom.hosts(), ["images.example"]);
+        assert!(custom.check("https://images.example/front.jpg").is_ok());
+        assert!(!is_bare_host("https://images.example"));
+        assert!(is_bare_host("cdn-1.images.example"));
+    }
+
+    #[tokio::test]
+    async fn only_a_missing_image_fails_the_check() {
+        let server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .and(path("/gone.jpg"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("HEAD"))
+            .and(path("/slow.jpg"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(2)))
+            .mount(&server)
+            .await;
+        Mock::given(method("HEAD"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let client = reqwest::Client::new();
+        let url = |name: &str| Url::parse(&format!("{}/{}", server.uri(), name)).unwrap();
+        let timeout = Duration::from_millis(200);
+
+        assert!(matches!(
+            image_exists(&client, url("gone.jpg"), timeout).await,
+            Err(ServiceError::BadRequest(_))
+        ));
+        assert!(
+            image_exists(&client, url("front.jpg"), timeout)
+                .await
+                .is_ok()
+        );
+        assert!(
+            image_exists(&client, url("slow.jpg"), timeout)
+                .await
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,299 @@
+//! `POST /api/v{1,2}/products/import`: an OpenFoodFacts JSONL dump, one product per line,
+//! upserted by barcode.
+//!
+//! The body is read line by line as it arrives, so dumps larger than memory import
+//! fine; only the body size limit for the route bounds them. Lines are mapped with the
+//! [`off`](crate::off) mapping the `off-import` CLI uses, without its country filter, and
+//! written in batches of [`IMPORT_BATCH_SIZE`]. Batches written before a failure stay
+//! written; the import is idempotent, so the fix is to send the dump again.
+
+use crate::{
+    errors::{Result, ServiceError},
+    handlers::{product_code_cache_key, product_id_cache_key},
+    models::Product,
+    off::{Filter, Mapped, map_row, parse_jsonl},
+    state::AppState,
+    tunables::IMPORT_MAX_LINE_BYTES,
+};
+use axum::{Json, body::Body, extract::State};
+use chrono::Utc;
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, io, sync::Arc};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+use tokio_util::io::StreamReader;
+use tracing::{debug, info, instrument, warn};
+use utoipa::ToSchema;
+use yoloeats_domain::ErrorBody;
+
+/// Products upserted per `bulkWrite`.
+pub const IMPORT_BATCH_SIZE: usize = 500;
+/// Route groups for the import's larger body limit.
+pub const IMPORT_PATHS: [&str; 2] = ["/api/v1/products/import", "/api/v2/products/import"];
+/// Body limit of [`IMPORT_PATHS`]; default [`DEFAULT_MAX_IMPORT_BODY_BYTES`].
+pub const MAX_IMPORT_BODY_BYTES_ENV: &str = "MAX_IMPORT_BODY_BYTES";
+pub const DEFAULT_MAX_IMPORT_BODY_BYTES: usize = 1024 * 1024 * 1024;
+
+/// Line errors listed in the report; the rest are only counted.
+const MAX_REPORTED_ERRORS: usize = 10;
+
+/// What an import did. `skipped` counts the lines that could not be imported, of which
+/// the first few are in `errors`.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ImportReport {
+    pub inserted: u64,
+    pub updated: u64,
+    pub skipped: u64,
+    pub errors: Vec<LineError>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct LineError {
+    /// 1-based, blank lines included.
+    pub line: u64,
+    pub message: String,
+}
+
+impl ImportReport {
+    fn skip(&mut self, line: u64, message: String) {
+        debug!("Skipping import line {}: {}", line, message);
+        self.skipped += 1;
+        if self.errors.len() < MAX_REPORTED_ERRORS {
+            self.errors.push(LineError { line, message });
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/products/import",
+    tag = "v1",
+    request_body(content = String, content_type = "application/x-ndjson", description = "An OpenFoodFacts JSONL dump, one product per line."),
+    responses(
+        (status = 200, description = "What the import did.", body = ImportReport),
+        (status = 400, description = "The body could not be read.", body = ErrorBody),
+        (status = 401, description = "No valid bearer token.", body = ErrorBody),
+        (status = 500, description = "MongoDB or Redis failed.", body = ErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[instrument(skip(state, body))]
+pub async fn import_products(
+    State(state): State<Arc<AppState>>,
+    body: Body,
+) -> Result<Json<ImportReport>> {
+    let max_line_bytes = state.config.get(IMPORT_MAX_LINE_BYTES);
+    let mut reader = StreamReader::new(body.into_data_stream().map_err(io::Error::other));
+    let filter = Filter::everything();
+    let imported_at = Utc::now();
+
+    let mut report = ImportReport::default();
+    let mut batch: Vec<Product> = Vec::with_capacity(IMPORT_BATCH_SIZE);
+    let mut buf = Vec::new();
+    let mut line_no = 0u64;
+    loop {
+        let line = read_line(&mut reader, &mut buf, max_line_bytes)
+            .await
+            .map_err(|e| {
+                warn!("Import body ended after line {}: {}", line_no, e);
+                ServiceError::BadRequest(format!(
+                    "Could not read the request body after line {}: {}",
+                    line_no, e
+                ))
+            })?;
+        match line {
+            Line::End => break,
+            Line::TooLong => {
+                line_no += 1;
+                report.skip(
+                    line_no,
+                    format!("line is longer than {} bytes", max_line_bytes),
+                );
+            }
+            Line::Read => {
+                line_no += 1;
+                let mapped = match std::str::from_utf8(&buf) {
+                    Ok(line) if line.trim().is_empty() => continue,
+                    Ok(line) => parse_jsonl(line.trim_end_matches('\r'))
+                        .map(|row| map_row(&row, &filter, imported_at))
+                        .unwrap_or_else(Mapped::Rejected),
+                    Err(_) => Mapped::Rejected("line is not valid UTF-8".to_string()),
+                };
+                match mapped {
+                    Mapped::Product(product) => batch.push(*product),
+                    Mapped::Skipped(reason) => {
+                        report.skip(line_no, format!("filtered out: {:?}", reason))
+                    }
+                    Mapped::Rejected(reason) => report.skip(line_no, reason),
+                }
+            }
+        }
+        if batch.len() >= IMPORT_BATCH_SIZE {
+            flush(&state, &mut batch, &mut report).await?;
+        }
+    }
+    flush(&state, &mut batch, &mut report).await?;
+
+    info!(
+        "Imported {} lines: {} inserted, {} updated, {} skipped",
+        line_no, report.inserted, report.updated, report.skipped
+    );
+    Ok(Json(report))
+}
+
+/// Upserts `batch` and drops the cache entries of its codes, cached misses included, and
+/// of the ids of the products it replaced. A dump can list a code twice; the last line
+/// wins.
+async fn flush(
+    state: &AppState,
+    batch: &mut Vec<Product>,
+    report: &mut ImportReport,
+) -> Result<()> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+    let mut seen = HashSet::new();
+    let mut unique: Vec<Product> = batch
+        .drain(..)
+        .rev()
+        .filter(|p| seen.insert(p.code.clone()))
+        .collect();
+    unique.reverse();
+
+    let codes: Vec<String> = unique.iter().map(|p| p.code.clone()).collect();
+    let existing = state
+        .products
+        .find_by_codes(codes.clone(), unique.len())
+        .await?;
+    let counts = state.products.upsert_by_code(unique).await?;
+    report.inserted += counts.inserted;
+    report.updated += counts.updated;
+    debug!(
+        "Import batch: {} inserted, {} updated",
+        counts.inserted, counts.updated
+    );
+
+    let keys: Vec<String> = codes
+        .iter()
+        .map(|code| product_code_cache_key(code))
+        .chain(
+            existing
+                .iter()
+                .filter_map(|p| p.id.as_ref().map(product_id_cache_key)),
+        )
+        .collect();
+    let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+    match state.cache.connect().await {
+        Ok(mut conn) => {
+            if let Err(e) = conn.del(&keys).await {
+                warn!(
+                    "Failed to invalidate {} imported products: {}",
+                    codes.len(),
+                    e
+                );
+            }
+        }
+        Err(e) => warn!(
+            "Failed to connect to the cache to invalidate imports: {}",
+            e
+        ),
+    }
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+enum Line {
+    /// `buf` holds the line, without its `\n`.
+    Read,
+    /// The line was over the limit and has been read past; `buf` is empty.
+    TooLong,
+    End,
+}
+
+/// Reads up to the next `\n` into `buf`, never holding more than `max_bytes` of a line.
+async fn read_line<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    max_bytes: usize,
+) -> io::Result<Line> {
+    buf.clear();
+    let mut too_long = false;
+    let mut read_any = false;
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            return Ok(match (read_any, too_long) {
+                (false, _) => Line::End,
+                (true, true) => Line::TooLong,
+                (true, false) => Line::Read,
+            });
+        }
+        read_any = true;
+        let newline = available.iter().position(|&b| b == b'\n');
+        let content = newline.unwrap_or(available.len());
+        if !too_long {
+            if buf.len() + content > max_bytes {
+                too_long = true;
+                buf.clear();
+            } else {
+                buf.extend_from_slice(&available[..content]);
+            }
+        }
+        reader.consume(newline.map_or(content, |i| i + 1));
+        if newline.is_some() {
+            return Ok(if too_long { Line::TooLong } else { Line::Read });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn lines(input: &[u8], chunk: usize, max_bytes: usize) -> Vec<(Line, String)> {
+        let chunks: Vec<io::Result<io::Cursor<Vec<u8>>>> = input
+            .chunks(chunk)
+            .map(|c| Ok(io::Cursor::new(c.to_vec())))
+            .collect();
+        let mut reader = StreamReader::new(futures::stream::iter(chunks));
+        let mut buf = Vec::new();
+        let mut lines = Vec::new();
+        loop {
+            let line = read_line(&mut reader, &mut buf, max_bytes).await.unwrap();
+            if line == Line::End {
+                return lines;
+            }
+            lines.push((line, String::from_utf8(buf.clone()).unwrap()));
+        }
+    }
+
+    #[tokio::test]
+    async fn lines_are_split_across_chunks_and_capped() {
+        let input = b"{\"code\":\"1\"}\n\n0123456789abcdef\nlast";
+        for chunk in [1, 3, 64] {
+            assert_eq!(
+                lines(input, chunk, 12).await,
+                [
+                    (Line::Read, "{\"code\":\"1\"}".to_string()),
+                    (Line::Read, String::new()),
+                    (Line::TooLong, String::new()),
+                    (Line::Read, "last".to_string()),
+                ],
+                "chunks of {}",
+                chunk
+            );
+        }
+        assert!(lines(b"", 4, 12).await.is_empty());
+    }
+
+    #[test]
+    fn only_the_first_errors_are_listed() {
+        let mut report = ImportReport::default();
+        for line in 1..=15 {
+            report.skip(line, "invalid JSON".to_string());
+        }
+        assert_eq!(report.skipped, 15);
+        assert_eq!(report.errors.len(), MAX_REPORTED_ERRORS);
+        assert_eq!(report.errors[0].line, 1);
+    }
+}
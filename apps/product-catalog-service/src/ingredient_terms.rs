@@ -0,0 +1,205 @@
+//! The ingredient terms of a search: `ingredients_include=oats` keeps the products whose
+//! ingredients name oats, `ingredients_exclude=palm oil` leaves out those naming palm oil.
+//!
+//! A term is named by a product's `ingredients_text` or by the text or taxonomy id of an
+//! entry in its structured `ingredients`, as whole words, ignoring case and accents:
+//! `creme` finds `Crème fraîche` and `Müsli` finds `MUSLI`. The words of a term may be
+//! apart by any spaces and punctuation, so `palm oil` also finds `palm-oil` and
+//! `en:palm-oil`. Anything else in a term, like the `.` of `e.g.`, stands for itself.
+//!
+//! MongoDB matches a term with an unanchored regular expression, which no index serves,
+//! so a search takes at most [`MAX_INGREDIENT_TERMS`] and is best narrowed by a category
+//! or a `q` too. [`names_term`] matches the same in memory.
+
+use crate::models::Product;
+
+/// The most `ingredients_include` and `ingredients_exclude` terms of one search.
+pub const MAX_INGREDIENT_TERMS: usize = 5;
+
+/// Where MongoDB looks for a term.
+pub const INGREDIENT_FIELDS: [&str; 3] = ["ingredients_text", "ingredients.text", "ingredients.id"];
+
+/// The accented letters each letter stands for, as lowercase.
+const ACCENTED: [(char, &str); 8] = [
+    ('a', "àáâãäå"),
+    ('c', "ç"),
+    ('e', "èéêë"),
+    ('i', "ìíîï"),
+    ('n', "ñ"),
+    ('o', "òóôõöø"),
+    ('u', "ùúûü"),
+    ('y', "ýÿ"),
+];
+
+/// A word boundary in MongoDB's regular expressions: `\b` only knows ASCII letters.
+const NOT_A_WORD: &str = r"[^\p{L}\p{N}]";
+
+/// `c` lowercase and without its accent.
+pub(crate) fn fold(c: char) -> impl Iterator<Item = char> {
+    c.to_lowercase().map(|lower| {
+        ACCENTED
+            .iter()
+            .find(|(_, accented)| accented.contains(lower))
+            .map_or(lower, |(plain, _)| *plain)
+    })
+}
+
+/// The words of `term`, folded.
+pub(crate) fn folded_words(term: &str) -> Vec<Vec<char>> {
+    term.split_whitespace()
+        .map(|word| word.chars().flat_map(fold).collect())
+        .collect()
+}
+
+/// The regular expression MongoDB matches `term` with, case-insensitively.
+pub fn term_pattern(term: &str) -> String {
+    let words: Vec<String> = folded_words(term)
+        .iter()
+        .map(|word| {
+            word.iter()
+                .map(|&c| match ACCENTED.iter().find(|(plain, _)| *plain == c) {
+                    Some((plain, accented)) => format!("[{}{}]", plain, accented),
+                    None if c.is_ascii_punctuation() => format!(r"\{}", c),
+                    None => c.to_string(),
+                })
+                .collect()
+        })
+        .collect();
+    let separator = format!("{}+", NOT_A_WORD);
+    format!(
+        "(?:^|{0}){1}(?:{0}|$)",
+        NOT_A_WORD,
+        words.join(separator.as_str())
+    )
+}
+
+/// Whether `text` names `term`, as [`term_pattern`] matches it.
+pub fn names_term(text: &str, term: &str) -> bool {
+    let words = folded_words(term);
+    if words.is_empty() {
+        return false;
+    }
+    let text: Vec<char> = text.chars().flat_map(fold).collect();
+    (0..text.len()).any(|start| {
+        (start == 0 || !text[start - 1].is_alphanumeric()) && matches_at(&text, start, &words)
+    })
+}
+
+/// Whether `words` follow in `text` from `at` on, each apart and followed by a boundary.
+fn matches_at(text: &[char], at: usize, words: &[Vec<char>]) -> bool {
+    let Some((word, rest)) = words.split_first() else {
+        return text.get(at).is_none_or(|c| !c.is_alphanumeric());
+    };
+    if !text[at..].starts_with(word) {
+        return false;
+    }
+    let mut next = at + word.len();
+    if rest.is_empty() {
+        return matches_at(text, next, rest);
+    }
+    // Any run of separators, as the expression would backtrack through them.
+    while text.get(next).is_some_and(|c| !c.is_alphanumeric()) {
+        next += 1;
+        if matches_at(text, next, rest) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Whether `product`'s ingredients name `term`, in any of the [`INGREDIENT_FIELDS`].
+pub fn product_names(product: &Product, term: &str) -> bool {
+    let entries = product.ingredients.iter().flatten();
+    product
+        .ingredients_text
+        .iter()
+        .chain(entries.clone().filter_map(|entry| entry.text.as_ref()))
+        .chain(entries.filter_map(|entry| entry.id.as_ref()))
+        .any(|text| names_term(text, term))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yoloeats_domain::{IngredientEntry, fixtures::ProductFixture};
+
+    #[test]
+    fn terms_ignore_case_and_accents() {
+        let text = "Crème fraîche, Haferflocken, MÜSLI (12 %), Jalapeño";
+        for term in [
+            "creme",
+            "CRÈME FRAICHE",
+            "müsli",
+            "Musli",
+            "jalapeno",
+            "jalapeño",
+        ] {
+            assert!(names_term(text, term), "{}", term);
+        }
+        assert_eq!(
+            term_pattern("Crème"),
+            r"(?:^|[^\p{L}\p{N}])[cç]r[eèéêë]m[eèéêë](?:[^\p{L}\p{N}]|$)"
+        );
+    }
+
+    #[test]
+    fn terms_are_whole_words() {
+        let text = "Wheat flour, palm-oil, oats";
+        assert!(names_term(text, "oats"));
+        assert!(names_term(text, "palm oil"));
+        assert!(names_term(text, "palm  oil"));
+        assert!(names_term(text, "wheat flour"));
+        assert!(names_term("en:palm-oil", "palm oil"));
+        for term in ["oat", "flour wheat", "palm oil oats x", "heat", "", " "] {
+            assert!(!names_term(text, term), "{}", term);
+        }
+        assert!(!names_term("Haferflocken", "hafer"));
+    }
+
+    #[test]
+    fn metacharacters_stand_for_themselves() {
+        assert!(names_term("Milk (1.5% fat)", "1.5%"));
+        assert!(!names_term("Milk (135% fat)", "1.5%"));
+        assert!(names_term("Sugar, (oats)", "(oats"));
+        assert!(!names_term("Sugar, oats", "(oats"));
+        assert!(!names_term("anything", ".*"));
+        assert!(names_term("salt [e.g. sea salt]", "[e.g."));
+
+        assert_eq!(
+            term_pattern("1.5% (a|b)* [x]+ ^$\\?"),
+            [
+                r"(?:^|[^\p{L}\p{N}])",
+                r"1\.5\%",
+                r"[^\p{L}\p{N}]+",
+                r"\([aàáâãäå]\|b\)\*",
+                r"[^\p{L}\p{N}]+",
+                r"\[x\]\+",
+                r"[^\p{L}\p{N}]+",
+                r"\^\$\\\?",
+                r"(?:[^\p{L}\p{N}]|$)",
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn products_name_terms_in_the_text_or_the_structured_ingredients() {
+        let mut product: Product = ProductFixture::new("1")
+            .with_ingredients("Sugar, cocoa")
+            .build();
+        assert!(product_names(&product, "cocoa"));
+        assert!(!product_names(&product, "palm oil"));
+        product.ingredients = Some(vec![IngredientEntry {
+            id: Some("en:palm-oil".to_string()),
+            ..Default::default()
+        }]);
+        assert!(product_names(&product, "palm oil"));
+        product.ingredients_text = None;
+        product.ingredients = Some(vec![IngredientEntry {
+            text: Some("Hazelnuts".to_string()),
+            ..Default::default()
+        }]);
+        assert!(product_names(&product, "hazelnuts"));
+        assert!(!product_names(&product, "cocoa"));
+    }
+}
@@ -0,0 +1,214 @@
+//! Product names in the reader's language.
+//!
+//! OpenFoodFacts names a product once per language (`product_name_de`,
+//! `product_name_fr`, ...), and the import keeps those names in
+//! [`Product::product_name_langs`](crate::models::Product::product_name_langs). v2
+//! product and search responses add a `displayName`: the product's name in the first
+//! language the reader prefers that it has one in, or else its `product_name`. v1 keeps
+//! its frozen shape and names nothing. A `lang` query
+//! parameter wins over the `Accept-Language` header. The name is picked when answering;
+//! cached products and search pages stay the same for every language, and nothing is
+//! indexed per language. Responses that name products say so with [`VaryByLanguage`].
+
+use axum::{
+    extract::FromRequestParts,
+    http::{
+        HeaderValue,
+        header::{ACCEPT_LANGUAGE, VARY},
+        request::Parts,
+    },
+    response::{IntoResponseParts, ResponseParts},
+};
+use std::{collections::HashMap, convert::Infallible};
+
+/// The request's `Accept-Language`, if any.
+#[derive(Debug, Clone, Default)]
+pub struct AcceptLanguage(pub Option<String>);
+
+impl AcceptLanguage {
+    /// The languages to name products in, `lang` first; see [`preferred_languages`].
+    pub fn preferred(&self, lang: Option<&str>) -> Vec<String> {
+        preferred_languages(lang, self.0.as_deref())
+    }
+}
+
+impl<S> FromRequestParts<S> for AcceptLanguage
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        Ok(AcceptLanguage(
+            parts
+                .headers
+                .get(ACCEPT_LANGUAGE)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+        ))
+    }
+}
+
+/// `Vary: Accept-Language`, for a response with products named in the reader's
+/// language, so shared caches keep one copy per language.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct VaryByLanguage;
+
+impl IntoResponseParts for VaryByLanguage {
+    type Error = Infallible;
+
+    fn into_response_parts(
+        self,
+        mut res: ResponseParts,
+    ) -> std::result::Result<ResponseParts, Self::Error> {
+        res.headers_mut()
+            .append(VARY, HeaderValue::from_static("accept-language"));
+        Ok(res)
+    }
+}
+
+/// The primary subtag of a language tag, lowercased: `de` for `de-AT`. `None` for `*`
+/// and anything that isn't two or three letters.
+fn primary_language(tag: &str) -> Option<String> {
+    let primary = tag.trim().split(['-', '_']).next()?;
+    let letters =
+        (2..=3).contains(&primary.len()) && primary.chars().all(|c| c.is_ascii_alphabetic());
+    letters.then(|| primary.to_ascii_lowercase())
+}
+
+/// The languages a reader prefers, best first and without repeats: `lang`, then those
+/// of `accept_language` by descending `q` (ties keep their order). Regions are dropped,
+/// so `de-AT` asks for `de`. Entries with `q=0`, an unreadable `q` or no language are
+/// left out.
+pub fn preferred_languages(lang: Option<&str>, accept_language: Option<&str>) -> Vec<String> {
+    let mut weighted: Vec<(String, f32)> = accept_language
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let language = primary_language(parts.next()?)?;
+            let mut q = 1.0;
+            for param in parts {
+                if let Some(value) = param.trim().strip_prefix("q=") {
+                    q = value.trim().parse::<f32>().ok()?;
+                }
+            }
+            (q > 0.0).then_some((language, q))
+        })
+        .collect();
+    weighted.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+    let mut languages: Vec<String> = Vec::new();
+    let requested = lang.and_then(primary_language);
+    for language in requested
+        .into_iter()
+        .chain(weighted.into_iter().map(|(l, _)| l))
+    {
+        if !languages.contains(&language) {
+            languages.push(language);
+        }
+    }
+    languages
+}
+
+/// The name to show a reader who prefers `preferred`: the first of them `names` has a
+/// non-blank name in, or else `product_name`.
+pub fn display_name(
+    product_name: Option<&str>,
+    names: Option<&HashMap<String, String>>,
+    preferred: &[String],
+) -> Option<String> {
+    preferred
+        .iter()
+        .find_map(|language| names?.get(language).filter(|name| !name.trim().is_empty()))
+        .map(String::as_str)
+        .or(product_name)
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names() -> HashMap<String, String> {
+        HashMap::from([
+            ("de".to_string(), "Alpenmilch Schokolade".to_string()),
+            ("fr".to_string(), "Chocolat au lait des Alpes".to_string()),
+            ("it".to_string(), "  ".to_string()),
+        ])
+    }
+
+    #[test]
+    fn accept_language_is_ordered_by_quality() {
+        assert_eq!(
+            preferred_languages(None, Some("fr;q=0.5, de-AT, en-GB;q=0.8, de;q=0.9")),
+            ["de", "en", "fr"]
+        );
+        assert_eq!(
+            preferred_languages(None, Some("en, DE;q=0.7, *;q=0.1")),
+            ["en", "de"]
+        );
+        assert_eq!(
+            preferred_languages(None, Some("it;q=0.9, es;q=0.9")),
+            ["it", "es"]
+        );
+    }
+
+    #[test]
+    fn lang_wins_over_the_header() {
+        assert_eq!(
+            preferred_languages(Some("fr"), Some("de, fr;q=0.5")),
+            ["fr", "de"]
+        );
+        assert_eq!(preferred_languages(Some("pt-BR"), None), ["pt"]);
+        assert_eq!(preferred_languages(Some("german!"), Some("de")), ["de"]);
+    }
+
+    #[test]
+    fn unusable_entries_are_left_out() {
+        assert!(preferred_languages(None, None).is_empty());
+        assert!(preferred_languages(None, Some("")).is_empty());
+        assert_eq!(
+            preferred_languages(None, Some("de;q=0, en;q=abc, x, 12, fr;q=0.3")),
+            ["fr"]
+        );
+    }
+
+    #[test]
+    fn display_names_follow_the_preferences() {
+        let names = names();
+        let pick = |preferred: &[&str]| {
+            let preferred: Vec<String> = preferred.iter().map(|l| l.to_string()).collect();
+            display_name(Some("Alpine milk chocolate"), Some(&names), &preferred)
+        };
+        assert_eq!(pick(&["de"]).as_deref(), Some("Alpenmilch Schokolade"));
+        assert_eq!(
+            pick(&["es", "fr", "de"]).as_deref(),
+            Some("Chocolat au lait des Alpes")
+        );
+        // A blank name is as good as none.
+        assert_eq!(
+            pick(&["it", "de"]).as_deref(),
+            Some("Alpenmilch Schokolade")
+        );
+        assert_eq!(pick(&["es"]).as_deref(), Some("Alpine milk chocolate"));
+        assert_eq!(pick(&[]).as_deref(), Some("Alpine milk chocolate"));
+    }
+
+    #[test]
+    fn display_names_fall_back_to_the_product_name() {
+        let de = ["de".to_string()];
+        assert_eq!(
+            display_name(Some("Milk"), None, &de).as_deref(),
+            Some("Milk")
+        );
+        assert_eq!(display_name(None, None, &de), None);
+        assert_eq!(
+            display_name(None, Some(&names()), &de).as_deref(),
+            Some("Alpenmilch Schokolade")
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! Product catalog service: product CRUD, search, barcode lookup and recommendations.
+//!
+//! `main.rs` loads configuration and connects the clients; [`router`] is public so the
+//! integration harness can serve the same routes in-process with injected state. With
+//! `STORAGE_MODE=memory` products live in [`repository::MemoryProducts`] and the cache is
+//! a map in process; recommendations need the Qdrant index and answer 404 there.
+//!
+//! `/api/v1/products` is frozen and carries the `Deprecation`/`Sunset` headers once
+//! [`AppState::api_v1_deprecation`] dates it; [`v2`] serves the same operations in the
+//! v2 response shapes. Both are documented in [`openapi::ApiDoc`], served at
+//! `/api-docs/openapi.json` with Swagger UI at `/docs`.
+//!
+//! Writes need a bearer token and reads may too; see [`auth`].
+
+use auth::RouteAuth;
+use axum::{
+    Router,
+    routing::{delete, get, post, put},
+};
+use default_country::DEFAULT_COUNTRY_HEADER;
+use handlers::{
+    TOTAL_COUNT_HEADER, count_products, create_product, delete_product, get_product_by_barcode,
+    get_product_by_id, get_products_by_barcodes, get_recommendations, patch_product,
+    search_products, update_product,
+};
+use state::AppState;
+use std::sync::Arc;
+use tower_http::cors::{Any, CorsLayer};
+use utoipa::OpenApi;
+use yoloeats_auth::{Authenticator, InternalTokenLayer};
+use yoloeats_dynamic_config::config_router;
+use yoloeats_health::health_router;
+use yoloeats_metrics::{
+    BodyLimitLayer, HttpMetricsLayer, LoadShedLayer, RateLimitLayer, compression_layer,
+};
+use yoloeats_openapi::openapi_router;
+use yoloeats_tracing::RequestIdLayer;
+use yoloeats_versioning::DeprecationLayer;
+
+pub mod allergen_extraction;
+pub mod audit;
+pub mod auth;
+pub mod barcode;
+pub mod cascade;
+pub mod catalog_metrics;
+pub mod categories;
+pub mod changes;
+pub mod curation;
+pub mod db_setup;
+pub mod default_country;
+pub mod discovery;
+pub mod duplicates;
+pub mod embedding;
+pub mod errors;
+pub mod etag;
+pub mod events;
+pub mod grpc;
+pub mod handlers;
+pub mod health;
+pub mod highlight;
+pub mod hybrid;
+pub mod images;
+pub mod import;
+pub mod ingredient_terms;
+pub mod language;
+pub mod models;
+pub mod nutriscore;
+pub mod off;
+pub mod off_fallback;
+pub mod openapi;
+pub mod popularity;
+pub mod projection;
+pub mod qdrant_setup;
+pub mod recent;
+pub mod reindex;
+pub mod reports;
+pub mod repository;
+pub mod search_cache;
+pub mod semantic;
+pub mod state;
+pub mod suggestions;
+pub mod taxonomy;
+pub mod tunables;
+pub mod v2;
+pub mod vector_sync;
+pub mod webhooks;
+
+async fn health_check() -> &'static str {
+    "Product Catalog Service OK"
+}
+
+pub fn router(app_state: Arc<AppState>, authenticator: Authenticator) -> Router {
+    // Permissive for development. Paginated UIs read the search counts from the header.
+    let cors = CorsLayer::new()
+        .allow_origin(Any)
+        .allow_methods(Any)
+        .allow_headers(Any)
+        .expose_headers([TOTAL_COUNT_HEADER, DEFAULT_COUNTRY_HEADER]);
+    let auth = RouteAuth::new(authenticator, app_state.public_reads);
+
+    let v1_routes = Router::new()
+        .route("/", auth.write(post(create_product)))
+        .route("/search", auth.read(get(search_products)))
+        .route("/count", auth.read(get(count_products)))
+        .route("/random", auth.read(get(discovery::get_random_products)))
+        .route("/changes", auth.read(get(changes::list_changes)))
+        .route("/recent", auth.read(get(recent::list_recent_products)))
+        .route(
+            "/popular",
+            auth.read(get(popularity::list_popular_products)),
+        )
+        .route(
+            "/search/semantic",
+            auth.read(
+                get(semantic::semantic_search_products).post(semantic::semantic_search_by_body),
+            ),
+        )
+        .route(
+            "/search/hybrid",
+            auth.read(get(hybrid::hybrid_search_products).post(hybrid::hybrid_search_by_body)),
+        )
+        .route(
+            "/{id}",
+            auth.read(get(get_product_by_id)).merge(
+                auth.write(
+                    put(update_product)
+                        .patch(patch_product)
+                        .delete(delete_product),
+                ),
+            ),
+        )
+        .route("/barcode/{code}", auth.read(get(get_product_by_barcode)))
+        .route("/batch", auth.read(post(get_products_by_barcodes)))
+        .route("/import", auth.write(post(import::import_products)))
+        .route("/{id}/recommendations", auth.read(get(get_recommendations)))
+        .route(
+            "/{id}/duplicates",
+            auth.read(get(duplicates::get_duplicates)),
+        )
+        .route("/{id}/history", auth.read(get(audit::get_product_history)))
+        .route(
+            "/{id}/reports",
+            auth.read(get(reports::get_product_reports).post(reports::create_report)),
+        )
+        .route(
+            "/{id}/nutriscore",
+            auth.read(get(nutriscore::get_nutriscore)),
+        )
+        .route(
+            "/curation/incomplete",
+            auth.read(get(curation::list_incomplete_products)),
+        );
+
+    // Behind the internal token, which `InternalTokenLayer` asks for on `/api/v1/admin`.
+    // Closing a report also takes the curator's bearer token, which names them.
+    let admin_routes = Router::new()
+        .route(
+            "/webhooks",
+            post(webhooks::create_webhook).get(webhooks::list_webhooks),
+        )
+        .route("/webhooks/{id}", delete(webhooks::delete_webhook))
+        .route("/reindex", post(reindex::start_reindex))
+        .route("/reindex/{job_id}", get(reindex::get_reindex_job))
+        .route("/reports", get(reports::list_reports))
+        .route("/reports/stats", get(reports::get_report_stats))
+        .route(
+            "/reports/{id}/assign",
+            auth.write(post(reports::assign_report)),
+        )
+        .route(
+            "/reports/{id}/resolve",
+            auth.write(post(reports::resolve_report)),
+        );
+
+    Router::new()
+        .nest(
+            "/api/v1/products",
+            v1_routes.layer(DeprecationLayer::new(app_state.api_v1_deprecation)),
+        )
+        .nest("/api/v2/products", v2::routes(&auth))
+        .nest("/api/v1/admin", admin_routes)
+        .route(
+            "/api/v1/categories",
+            auth.read(get(categories::list_categories)),
+        )
+        .route("/", get(health_check))
+        .route("/health", get(health_check))
+        .merge(health_router(Arc::new(health::registry(&app_state))))
+        .merge(config_router(app_state.config.clone()))
+        .merge(openapi_router(openapi::ApiDoc::openapi()))
+        .layer(compression_layer())
+        .layer(InternalTokenLayer::new(app_state.internal_tokens.clone()))
+        .layer(BodyLimitLayer::new(app_state.body_limits.clone()))
+        .layer(
+            RateLimitLayer::new(
+                "product-catalog-service",
+                app_state.rate_limits.clone(),
+                app_state.rate_limit_store.clone(),
+            )
+            .trusting(app_state.trusted_proxies.clone()),
+        )
+        .layer(LoadShedLayer::new("product-catalog-service", app_state.load_shed))
+        .layer(HttpMetricsLayer::new("product-catalog-service"))
+        .layer(RequestIdLayer)
+        .layer(cors)
+        .with_state(app_state)
+}
@@ -1,130 +1,349 @@
-use crate::handlers::{
-    create_product, delete_product, get_product_by_barcode, get_product_by_id, get_recommendations,
-    search_products, update_product,
-};
-use axum::{
-    Router,
-    routing::{get, post},
-};
 use dotenvy::dotenv;
-use errors::{Result, ServiceError};
 use neo4rs::Graph as Neo4jClient;
+use product_catalog_service::{
+    audit::{AuditLog, MemoryAuditLog, MongoAuditLog},
+    auth::{PUBLIC_READS_ENV, public_reads_from_env},
+    cascade::{ExternalCopies, NoCopies, ProductCopies},
+    categories::{CategoryTaxonomy, GraphTaxonomy, NoTaxonomy},
+    db_setup,
+    default_country::{DEFAULT_COUNTRY_TAG_ENV, default_country_from_env},
+    embedding::{EMBEDDING_SERVICE_URL_ENV, EmbeddingClient, HttpEmbeddings},
+    errors::{Result, ServiceError},
+    grpc::ProductGrpc,
+    handlers::{
+        BARCODE_PATHS, DEFAULT_RATE_LIMIT_BARCODE_PER_MIN, DEFAULT_RATE_LIMIT_SEARCH_PER_MIN,
+        RATE_LIMIT_BARCODE_PER_MIN_ENV, RATE_LIMIT_SEARCH_PER_MIN_ENV, SEARCH_PATHS,
+    },
+    images::ImageHosts,
+    import::{DEFAULT_MAX_IMPORT_BODY_BYTES, IMPORT_PATHS, MAX_IMPORT_BODY_BYTES_ENV},
+    off_fallback::DEFAULT_OFF_API_URL,
+    popularity::{self, MemoryScanCounts, RedisScanCounts, ScanCounts},
+    qdrant_setup::{CollectionConfig, check_vector_size, ensure_qdrant_setup},
+    reports::{MemoryReportStore, MongoReportStore, ReportStore},
+    repository::{MemoryProducts, MongoProducts, ProductRepository},
+    router,
+    state::{AppState, Clients},
+    tunables,
+    webhooks::{MemoryWebhookStore, MongoWebhookStore, WebhookStore, Webhooks},
+};
 use qdrant_client::{Qdrant, config::QdrantConfig};
-use reqwest::Client as HttpClient;
-use rust_database_clients::{create_mongo_client, create_redis_client, load_config};
-use state::AppState;
+use rust_database_clients::{
+    Cache, MemoryCache, MongoSettings, RedisCache, StorageMode, create_mongo_client_with_settings,
+    create_redis_client,
+    http_client::HttpClientConfig,
+    http_resilience::{ResilienceConfig, ResilientClient},
+    load_config, validate_neo4j_uri, validate_qdrant_uri,
+};
 use std::{env, net::SocketAddr, sync::Arc};
-use tower_http::cors::{Any, CorsLayer};
 use tracing::{debug, error, info, warn};
-use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt};
-
-mod db_setup;
-mod errors;
-mod handlers;
-mod models;
-mod state;
-
-async fn health_check() -> &'static str {
-    "Product Catalog Service OK"
-}
+use yoloeats_auth::{AuthConfig, Authenticator, InternalTokens};
+use yoloeats_dynamic_config::{DEFAULT_REFRESH_INTERVAL, MemoryStore, RedisStore};
+use yoloeats_metrics::{
+    BodyLimits, LoadShedConfig, MemoryRateLimitStore, RateLimitStore, RateLimits,
+    RedisRateLimitStore, TrustedProxies, install_recorder, metrics_router,
+};
+use yoloeats_pagination::CursorCodec;
+use yoloeats_shutdown::{Shutdown, drain_timeout_from_env, shutdown_signal};
+use yoloeats_tracing::{RequestIdLayer, init_tracing};
+use yoloeats_versioning::{API_V1_DEPRECATED_AT_ENV, API_V1_SUNSET_AT_ENV, Deprecation};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenv().ok();
 
-    tracing_subscriber::registry()
-        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()))
-        .with(fmt::layer())
-        .init();
+    let _telemetry = init_tracing("product-catalog-service", env!("CARGO_PKG_VERSION"))
+        .map_err(|e| ServiceError::Internal(format!("Tracing initialization failed: {}", e)))?;
 
     info!("Starting Product Catalog Service...");
 
-    let (mongo_uri, redis_uri) = load_config()?;
-
-    let qdrant_uri = env::var("QDRANT_URI").map_err(|e| {
-        error!("Missing environment variable: QDRANT_URI");
-        ServiceError::VarError(e)
+    let auth_config = AuthConfig::from_env().map_err(|e| {
+        error!("Auth configuration failed: {}", e);
+        ServiceError::InvalidVariable(e.to_string())
     })?;
-    let neo4j_uri = env::var("NEO4J_URI").map_err(|e| {
-        error!("Missing environment variable: NEO4J_URI");
-        ServiceError::VarError(e)
-    })?;
-    let neo4j_user = env::var("NEO4J_USER")
-        .map_err(|_| ServiceError::MissingVariable("NEO4J_USER".to_string()))?;
-    let neo4j_password = env::var("NEO4J_PASSWORD")
-        .map_err(|_| ServiceError::MissingVariable("NEO4J_PASSWORD".to_string()))?;
+    let authenticator = Authenticator::new(auth_config);
+    let public_reads = public_reads_from_env()?;
+    info!("Authentication configured.");
+
+    let storage_mode = StorageMode::from_env()?;
     let user_profile_service_url = env::var("USER_PROFILE_SERVICE_URL").map_err(|e| {
         error!("Missing environment variable: USER_PROFILE_SERVICE_URL");
         ServiceError::VarError(e)
     })?;
-
-    info!("Configuration loaded.");
-    debug!("MONGO_URI: {}", mongo_uri);
-    debug!("REDIS_URI: {}", redis_uri);
-    debug!("QDRANT_URI: {}", qdrant_uri);
-    debug!("NEO4J_URI: {}", neo4j_uri);
     debug!("USER_PROFILE_SERVICE_URL: {}", user_profile_service_url);
+    let embedding_service_url = env::var(EMBEDDING_SERVICE_URL_ENV)
+        .ok()
+        .filter(|url| !url.trim().is_empty());
+    if embedding_service_url.is_none() {
+        warn!(
+            "{} not set; semantic search only accepts vectors.",
+            EMBEDDING_SERVICE_URL_ENV
+        );
+    }
+    let collection_config = CollectionConfig::from_env()?;
+
+    let off_api_url = env::var("OFF_API_URL")
+        .ok()
+        .filter(|url| !url.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_OFF_API_URL.to_string());
+    debug!("OFF_API_URL: {}", off_api_url);
+    let default_country = default_country_from_env()?;
+    match &default_country {
+        Some(country) => info!("Requests naming no country are scoped to {}.", country),
+        None => debug!(
+            "{} not set; requests naming no country see every country.",
+            DEFAULT_COUNTRY_TAG_ENV
+        ),
+    }
+
+    let (products, audit, webhooks, copies, cache, clients, config_store) = match storage_mode {
+        StorageMode::External => {
+            let (mongo_uri, redis_uri) = load_config()?;
+
+            let qdrant_uri = env::var("QDRANT_URI").map_err(|e| {
+                error!("Missing environment variable: QDRANT_URI");
+                ServiceError::VarError(e)
+            })?;
+            let neo4j_uri = env::var("NEO4J_URI").map_err(|e| {
+                error!("Missing environment variable: NEO4J_URI");
+                ServiceError::VarError(e)
+            })?;
+            let neo4j_user = env::var("NEO4J_USER")
+                .map_err(|_| ServiceError::MissingVariable("NEO4J_USER".to_string()))?;
+            let neo4j_password = env::var("NEO4J_PASSWORD")
+                .map_err(|_| ServiceError::MissingVariable("NEO4J_PASSWORD".to_string()))?;
+
+            validate_qdrant_uri(&qdrant_uri)?;
+            validate_neo4j_uri(&neo4j_uri)?;
+
+            info!("Configuration loaded.");
+            debug!("MONGO_URI: {}", mongo_uri);
+            debug!("REDIS_URI: {}", redis_uri);
+            debug!("QDRANT_URI: {}", qdrant_uri);
+            debug!("NEO4J_URI: {}", neo4j_uri);
+
+            let mongo_settings = MongoSettings::from_env("product-catalog-service")?;
+            let mongo_client =
+                create_mongo_client_with_settings(&mongo_uri, &mongo_settings).await?;
+            let db_handle = mongo_client.database("openfoods");
+            info!("MongoDB client connected. Database: {}", db_handle.name());
+
+            let redis_client_handle = create_redis_client(&redis_uri)?;
+            info!("Redis client connected.");
 
-    let mongo_client = create_mongo_client(&mongo_uri).await?;
-    let db_handle = mongo_client.database("openfoods");
-    info!("MongoDB client connected. Database: {}", db_handle.name());
+            info!("Initializing Qdrant client...");
+            let qdrant_config = QdrantConfig::from_url(&qdrant_uri);
+            let qdrant_client = Qdrant::new(qdrant_config)?;
+            info!("Qdrant client connected.");
+            ensure_qdrant_setup(&qdrant_client, &collection_config).await?;
+            info!("Qdrant collection checked/created successfully.");
 
-    let redis_client_handle = create_redis_client(&redis_uri)?;
-    info!("Redis client connected.");
+            info!("Initializing Neo4j client...");
+            let neo4j_client = Neo4jClient::new(&neo4j_uri, &neo4j_user, &neo4j_password).await?;
+            neo4j_client.run(neo4rs::query("RETURN 1")).await?;
+            info!("Neo4j client connected.");
 
-    info!("Initializing Qdrant client...");
-    let qdrant_config = QdrantConfig::from_url(&qdrant_uri);
-    let qdrant_client = Qdrant::new(qdrant_config)?;
-    info!("Qdrant client connected.");
+            db_setup::create_indexes(&db_handle).await?;
+            info!("MongoDB indexes checked/created successfully.");
 
-    info!("Initializing Neo4j client...");
-    let neo4j_client = Neo4jClient::new(&neo4j_uri, &neo4j_user, &neo4j_password).await?;
-    neo4j_client.run(neo4rs::query("RETURN 1")).await?;
-    info!("Neo4j client connected.");
+            let qdrant_client = Arc::new(qdrant_client);
+            (
+                Arc::new(MongoProducts::new(&db_handle)) as Arc<dyn ProductRepository>,
+                Arc::new(MongoAuditLog::new(&db_handle)) as Arc<dyn AuditLog>,
+                Arc::new(MongoWebhookStore::new(&db_handle)) as Arc<dyn WebhookStore>,
+                Arc::new(ExternalCopies::new(
+                    qdrant_client.clone(),
+                    neo4j_client.clone(),
+                )) as Arc<dyn ProductCopies>,
+                Arc::new(RedisCache::new(redis_client_handle.clone())) as Arc<dyn Cache>,
+                Some(Clients {
+                    mongo_db: db_handle,
+                    redis_client: redis_client_handle.clone(),
+                    qdrant_client,
+                    neo4j_client,
+                }),
+                Some(redis_client_handle),
+            )
+        }
+        StorageMode::Memory => {
+            warn!(
+                "STORAGE_MODE=memory: products, their history and reports, webhook subscriptions and the cache are held in process and lost on exit."
+            );
+            (
+                Arc::new(MemoryProducts::default()) as Arc<dyn ProductRepository>,
+                Arc::new(MemoryAuditLog::default()) as Arc<dyn AuditLog>,
+                Arc::new(MemoryWebhookStore::default()) as Arc<dyn WebhookStore>,
+                Arc::new(NoCopies) as Arc<dyn ProductCopies>,
+                Arc::new(MemoryCache::default()) as Arc<dyn Cache>,
+                None,
+                None,
+            )
+        }
+    };
+    let categories: Arc<dyn CategoryTaxonomy> = match &clients {
+        Some(clients) => Arc::new(GraphTaxonomy::new(clients.neo4j_client.clone())),
+        None => Arc::new(NoTaxonomy),
+    };
+    let reports: Arc<dyn ReportStore> = match &clients {
+        Some(clients) => Arc::new(MongoReportStore::new(&clients.mongo_db)),
+        None => Arc::new(MemoryReportStore::default()),
+    };
 
     info!("Initializing Reqwest HTTP client...");
-    let http_client = HttpClient::new();
+    let http_config = HttpClientConfig::from_env()?;
+    info!("Outbound HTTP: {:?}", http_config);
+    let http_client = http_config.build()?;
+    let resilience = ResilienceConfig::from_env()?;
+    info!(
+        "Upstream calls: circuit opens after {} consecutive failures, for {:?}.",
+        resilience.failure_threshold, resilience.open_cooldown
+    );
+    let upstream_client = ResilientClient::new(
+        yoloeats_tracing::http_client(http_client.clone()),
+        resilience,
+    );
     info!("Reqwest HTTP client created.");
+    let image_hosts = ImageHosts::from_env()?;
+    info!(
+        "Product images may be on: {}",
+        image_hosts.hosts().join(", ")
+    );
 
-    // db_setup::create_indexes(&db_handle).await?;
-    info!("MongoDB indexes checked/created successfully.");
+    let rate_limit_store = match &config_store {
+        Some(redis_client) => {
+            Arc::new(RedisRateLimitStore::new(redis_client.clone())) as Arc<dyn RateLimitStore>
+        }
+        None => Arc::new(MemoryRateLimitStore::default()) as Arc<dyn RateLimitStore>,
+    };
+    let scans = match &config_store {
+        Some(redis_client) => {
+            Arc::new(RedisScanCounts::new(redis_client.clone())) as Arc<dyn ScanCounts>
+        }
+        None => Arc::new(MemoryScanCounts::default()) as Arc<dyn ScanCounts>,
+    };
+    let config = match config_store {
+        Some(redis_client) => tunables::config(RedisStore::new(redis_client, tunables::SERVICE)),
+        None => tunables::config(MemoryStore::default()),
+    }
+    .map_err(|e| ServiceError::InvalidVariable(e.to_string()))?;
+    config.spawn_refresh(DEFAULT_REFRESH_INTERVAL);
+    info!(
+        "Runtime config loaded; overrides refresh every {:?}.",
+        DEFAULT_REFRESH_INTERVAL
+    );
+
+    let embeddings = embedding_service_url.map(|url| {
+        debug!("{}: {}", EMBEDDING_SERVICE_URL_ENV, url);
+        Arc::new(HttpEmbeddings::new(
+            &url,
+            upstream_client.clone(),
+            config.clone(),
+            collection_config.vector_size,
+        )) as Arc<dyn EmbeddingClient>
+    });
+    if let (Some(clients), Some(embeddings)) = (&clients, &embeddings) {
+        check_vector_size(&clients.qdrant_client, embeddings.dimensions()).await?;
+        info!(
+            "Embeddings of {} dimensions fit the Qdrant collection.",
+            embeddings.dimensions()
+        );
+    }
+
+    let load_shed =
+        LoadShedConfig::from_env().map_err(|e| ServiceError::InvalidVariable(e.to_string()))?;
+    info!("Load shedding: {:?}", load_shed);
+    let mut body_limits =
+        BodyLimits::from_env().map_err(|e| ServiceError::InvalidVariable(e.to_string()))?;
+    for path in IMPORT_PATHS {
+        body_limits = body_limits
+            .group_from_env(
+                path,
+                MAX_IMPORT_BODY_BYTES_ENV,
+                DEFAULT_MAX_IMPORT_BODY_BYTES,
+            )
+            .map_err(|e| ServiceError::InvalidVariable(e.to_string()))?;
+    }
+    info!("Request body limits: {:?}", body_limits);
+    let mut rate_limits = RateLimits::default();
+    for path in SEARCH_PATHS {
+        rate_limits = rate_limits
+            .route_from_env(
+                "search",
+                path,
+                RATE_LIMIT_SEARCH_PER_MIN_ENV,
+                DEFAULT_RATE_LIMIT_SEARCH_PER_MIN,
+            )
+            .map_err(|e| ServiceError::InvalidVariable(e.to_string()))?;
+    }
+    for path in BARCODE_PATHS {
+        rate_limits = rate_limits
+            .route_from_env(
+                "barcode",
+                path,
+                RATE_LIMIT_BARCODE_PER_MIN_ENV,
+                DEFAULT_RATE_LIMIT_BARCODE_PER_MIN,
+            )
+            .map_err(|e| ServiceError::InvalidVariable(e.to_string()))?;
+    }
+    info!("Rate limits per client and minute: {:?}", rate_limits);
+    let trusted_proxies =
+        TrustedProxies::from_env().map_err(|e| ServiceError::InvalidVariable(e.to_string()))?;
+    info!("Proxies trusted to name clients: {:?}", trusted_proxies);
+    let api_v1_deprecation = Deprecation::from_env(API_V1_DEPRECATED_AT_ENV, API_V1_SUNSET_AT_ENV)
+        .map_err(|e| ServiceError::InvalidVariable(e.to_string()))?;
+    info!("/api/v1 deprecation: {:?}", api_v1_deprecation);
+    yoloeats_taxonomy::load_from_env().map_err(|e| ServiceError::InvalidVariable(e.to_string()))?;
+    info!("Taxonomy loaded.");
+    let drain_timeout =
+        drain_timeout_from_env().map_err(|e| ServiceError::InvalidVariable(e.to_string()))?;
+    let shutdown = Shutdown::new(drain_timeout);
+    info!(
+        "Shutdown drains requests and background tasks for up to {:?}.",
+        drain_timeout
+    );
 
     let app_state = Arc::new(AppState {
-        mongo_db: db_handle,
-        redis_client: redis_client_handle,
-        qdrant_client: Arc::new(qdrant_client),
-        neo4j_client,
+        products,
+        audit,
+        reports,
+        copies,
+        categories,
+        scans,
+        webhooks: Webhooks::new(webhooks),
+        cache,
+        clients,
         http_client,
+        image_hosts,
+        upstream_client,
         user_profile_service_url,
+        embeddings,
+        off_api_url,
+        internal_tokens: InternalTokens::from_env(),
+        config,
+        load_shed,
+        body_limits,
+        rate_limits,
+        rate_limit_store,
+        trusted_proxies,
+        cursor_codec: CursorCodec::from_env(),
+        api_v1_deprecation,
+        default_country,
+        public_reads,
+        tasks: shutdown.tasks(),
     });
     info!("Application state created.");
+    popularity::spawn_folding(app_state.clone(), popularity::FOLD_INTERVAL);
+    info!(
+        "Scan counts are stored in MongoDB every {:?}.",
+        popularity::FOLD_INTERVAL
+    );
 
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
-    info!("CORS layer configured (permissive for development).");
-
-    let api_routes = Router::new()
-        .route("/", post(create_product))
-        .route("/search", get(search_products))
-        .route(
-            "/{id}",
-            get(get_product_by_id)
-                .put(update_product)
-                .delete(delete_product),
-        )
-        .route("/barcode/{code}", get(get_product_by_barcode))
-        .route("/{id}/recommendations", get(get_recommendations));
-
-    let app = Router::new()
-        .nest("/api/v1/products", api_routes)
-        .route("/", get(health_check))
-        .route("/health", get(health_check))
-        .layer(cors)
-        .with_state(app_state);
+    let metrics_handle = install_recorder().map_err(|e| {
+        error!("Failed to install metrics recorder: {}", e);
+        ServiceError::Internal(format!("Metrics recorder installation failed: {}", e))
+    })?;
 
-    info!("Axum router configured with routes and CORS.");
+    let app = router(app_state.clone(), authenticator).merge(metrics_router(metrics_handle));
+    info!("Axum router configured with routes, metrics and CORS.");
 
     let port_str = env::var("PRODUCT_CATALOG_SERVICE_PORT").unwrap_or_else(|_| {
         info!("PRODUCT_CATALOG_SERVICE_PORT not set, defaulting to 8002");
@@ -138,17 +357,58 @@ async fn main() -> Result<()> {
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
     info!("Server configured to listen on {}", addr);
 
+    let grpc_port_str =
+        env::var("PRODUCT_CATALOG_GRPC_PORT").unwrap_or_else(|_| "50052".to_string());
+    let grpc_port = grpc_port_str.parse::<u16>().unwrap_or_else(|e| {
+        error!(
+            "Invalid gRPC port '{}': {}. Defaulting to 50052",
+            grpc_port_str, e
+        );
+        50052
+    });
+    let grpc_addr = SocketAddr::from(([0, 0, 0, 0], grpc_port));
+    info!("Internal gRPC server configured to listen on {}", grpc_addr);
+
     let listener = tokio::net::TcpListener::bind(addr).await?;
 
-    warn!("Warning: No authentication/authorization implemented yet.");
+    if public_reads {
+        warn!(
+            "Warning: Reads are public; set {}=false to require a token.",
+            PUBLIC_READS_ENV
+        );
+    }
     info!(
         "Product Catalog Service successfully started, listening on {}",
         addr
     );
 
-    axum::serve(listener, app.into_make_service())
-        .await
-        .map_err(ServiceError::Io)?;
+    let grpc_server = tonic::transport::Server::builder()
+        .layer(RequestIdLayer)
+        .add_service(ProductGrpc::server(app_state))
+        .serve_with_shutdown(grpc_addr, shutdown.signalled());
+
+    shutdown.trigger_on(shutdown_signal());
+    let servers = async {
+        tokio::try_join!(
+            async {
+                // Rate limits are counted per client address.
+                axum::serve(
+                    listener,
+                    app.into_make_service_with_connect_info::<SocketAddr>(),
+                )
+                .with_graceful_shutdown(shutdown.signalled())
+                .await
+                .map_err(ServiceError::Io)
+            },
+            async {
+                grpc_server
+                    .await
+                    .map_err(|e| ServiceError::Internal(format!("gRPC server failed: {}", e)))
+            },
+        )
+        .map(|_| ())
+    };
+    shutdown.run(servers).await?;
 
     Ok(())
 }
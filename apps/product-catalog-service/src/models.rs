@@ -1,90 +1,1943 @@
-use bson::serde_helpers::chrono_datetime_as_bson_datetime;
+use crate::{
+    highlight::{Highlight, highlight},
+    ingredient_terms::MAX_INGREDIENT_TERMS,
+    language::display_name,
+};
 use chrono::{DateTime, Utc};
 use mongodb::bson::oid::ObjectId;
+use rust_database_clients::serde_helpers::{
+    chrono_datetime_as_rfc3339_or_bson, double_option, lenient_datetime, string_or_number,
+    string_or_vec,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use utoipa::{IntoParams, ToSchema};
+use validator::{Validate, ValidationError};
+use yoloeats_domain::{IngredientEntry, ProductSummary};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// A stored product. Documents imported from OpenFoodFacts dumps as they are may hold
+/// numbers for `quantity` and the names, single strings for tag lists and Unix
+/// timestamps; those fields read leniently, so one messy document degrades to missing
+/// values instead of failing every search it matches.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
 pub struct Product {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
     pub id: Option<ObjectId>,
 
     pub code: String, // Barcode is mandatory, and a string because it has leading zeros in mongodb
+    #[serde(default, deserialize_with = "string_or_number")]
     pub product_name: Option<String>,
+    /// The product's name by language code (`de`, `fr`, ...), from OpenFoodFacts'
+    /// `product_name_de`, `product_name_fr`, ...; see [`crate::language`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub product_name_langs: Option<HashMap<String, String>>,
+    #[serde(default, deserialize_with = "string_or_number")]
     pub generic_name: Option<String>,
-    #[serde(rename = "brands_tags")]
+    #[serde(rename = "brands_tags", default, deserialize_with = "string_or_vec")]
     pub brands: Option<Vec<String>>,
 
-    #[serde(rename = "categories_tags")]
+    #[serde(
+        rename = "categories_tags",
+        default,
+        deserialize_with = "string_or_vec"
+    )]
     pub categories: Option<Vec<String>>,
     #[serde(rename = "main_category")]
     pub main_category: Option<String>,
-    #[serde(rename = "labels_tags")]
+    #[serde(rename = "labels_tags", default, deserialize_with = "string_or_vec")]
     pub labels: Option<Vec<String>>,
 
     pub ingredients_text: Option<String>,
-    #[serde(rename = "traces_tags")]
+    /// OpenFoodFacts' structured ingredients, when known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ingredients: Option<Vec<IngredientEntry>>,
+    #[serde(rename = "traces_tags", default, deserialize_with = "string_or_vec")]
     pub traces_tags: Option<Vec<String>>,
-    #[serde(default)]
+    #[serde(default, deserialize_with = "string_or_vec")]
     pub allergens_tags: Vec<String>,
 
+    #[serde(default, deserialize_with = "string_or_number")]
     pub quantity: Option<String>, // Quantity contains number and unit ("500 g")
     pub image_url: Option<String>,
     pub image_small_url: Option<String>,
-    #[serde(rename = "countries_tags")]
+    /// 100 pixels wide; derived from an OpenFoodFacts `image_url`, see [`crate::images`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_thumb_url: Option<String>,
+    #[serde(rename = "countries_tags", default, deserialize_with = "string_or_vec")]
     pub countries: Option<Vec<String>>, // Need this to filter Germany (and maybe expand yoloeats to other countries)
 
     #[serde(rename = "nutrition_grade_fr")]
     pub nutrition_grade_fr: Option<String>,
+    /// `computed` when the catalog scored `nutrition_grade_fr` from the nutriments
+    /// itself (see [`crate::nutriscore`]); absent for grades the source declared.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nutrition_grade_source: Option<String>,
+    /// The Eco-Score's environmental impact grade, `a` (lowest) to `e`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ecoscore_grade: Option<String>,
+    /// The Eco-Score points the grade comes from, as OpenFoodFacts computes them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ecoscore_score: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nutriments: Option<Nutriments>,
 
     pub creator: Option<String>,
     pub source: Option<String>, // tracking origin of the data (e.g., OpenFoodFacts, user-contributed, etc.)
+    /// Barcode lookups of the product counted so far, see [`crate::popularity`]. Absent
+    /// until the first are stored; an import that replaces the product starts it over.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scan_count: Option<u64>,
 
-    #[serde(rename = "created_datetime", with = "chrono_datetime_as_bson_datetime")]
+    #[serde(rename = "created_datetime", default, with = "lenient_datetime")]
+    #[schema(required = true)]
     pub created_at: DateTime<Utc>,
+    #[serde(rename = "last_modified_datetime", default, with = "lenient_datetime")]
+    #[schema(required = true)]
+    pub last_modified_at: DateTime<Utc>,
+}
+
+/// Nutrition facts per 100 g, under OpenFoodFacts' names. Their `nutriments` carry
+/// many more; only these are kept. Values are grams unless named otherwise.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Validate, ToSchema)]
+pub struct Nutriments {
+    #[serde(rename = "energy-kcal_100g")]
+    #[validate(range(min = 0.0, message = "Energy must not be negative"))]
+    pub energy_kcal_100g: Option<f64>,
     #[serde(
-        rename = "last_modified_datetime",
-        with = "chrono_datetime_as_bson_datetime"
+        rename = "energy-kj_100g",
+        default,
+        skip_serializing_if = "Option::is_none"
     )]
-    pub last_modified_at: DateTime<Utc>,
+    #[validate(range(min = 0.0, message = "Energy must not be negative"))]
+    pub energy_kj_100g: Option<f64>,
+    #[validate(range(min = 0.0, max = 100.0, message = "Grams per 100 g must be 0-100"))]
+    pub sugars_100g: Option<f64>,
+    #[validate(range(min = 0.0, max = 100.0, message = "Grams per 100 g must be 0-100"))]
+    pub salt_100g: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 0.0, max = 100.0, message = "Grams per 100 g must be 0-100"))]
+    pub sodium_100g: Option<f64>,
+    #[validate(range(min = 0.0, max = 100.0, message = "Grams per 100 g must be 0-100"))]
+    pub fat_100g: Option<f64>,
+    #[serde(rename = "saturated-fat_100g")]
+    #[validate(range(min = 0.0, max = 100.0, message = "Grams per 100 g must be 0-100"))]
+    pub saturated_fat_100g: Option<f64>,
+    #[validate(range(min = 0.0, max = 100.0, message = "Grams per 100 g must be 0-100"))]
+    pub proteins_100g: Option<f64>,
+    #[validate(range(min = 0.0, max = 100.0, message = "Grams per 100 g must be 0-100"))]
+    pub fiber_100g: Option<f64>,
+    /// Fruit, vegetables, legumes and nuts, in percent.
+    #[serde(
+        rename = "fruits-vegetables-nuts_100g",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[validate(range(min = 0.0, max = 100.0, message = "Percent must be 0-100"))]
+    pub fruits_vegetables_nuts_100g: Option<f64>,
+}
+
+impl Nutriments {
+    /// `None` when no value is known.
+    pub fn non_empty(self) -> Option<Nutriments> {
+        if self == Nutriments::default() {
+            None
+        } else {
+            Some(self)
+        }
+    }
+}
+
+impl From<Product> for ProductSummary {
+    fn from(product: Product) -> Self {
+        ProductSummary {
+            code: product.code,
+            product_name: product.product_name,
+            ingredients_text: product.ingredients_text,
+            allergens_tags: product.allergens_tags,
+            traces_tags: product.traces_tags.unwrap_or_default(),
+            labels_tags: product.labels.unwrap_or_default(),
+            ingredients: product.ingredients,
+        }
+    }
+}
+
+/// One thing [`completeness`] looks for: any of `fields`, stored non-empty, earns its
+/// `points`.
+#[derive(Debug, Clone, Copy)]
+pub struct CompletenessCriterion {
+    /// The stored field names, as the curation query checks them in MongoDB.
+    pub fields: &'static [&'static str],
+    pub points: u8,
+    present: fn(&Product) -> bool,
+}
+
+fn has_text(value: &Option<String>) -> bool {
+    value.as_deref().is_some_and(|v| !v.is_empty())
+}
+
+fn has_tags(value: &Option<Vec<String>>) -> bool {
+    value.as_deref().is_some_and(|v| !v.is_empty())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// What a product's completeness is made of. The points add up to 100.
+pub const COMPLETENESS_CRITERIA: [CompletenessCriterion; 8] = [
+    CompletenessCriterion {
+        fields: &["product_name"],
+        points: 20,
+        present: |p| has_text(&p.product_name),
+    },
+    CompletenessCriterion {
+        fields: &["ingredients_text"],
+        points: 15,
+        present: |p| has_text(&p.ingredients_text),
+    },
+    CompletenessCriterion {
+        fields: &["allergens_tags"],
+        points: 10,
+        present: |p| !p.allergens_tags.is_empty(),
+    },
+    CompletenessCriterion {
+        fields: &["image_url", "image_small_url"],
+        points: 15,
+        present: |p| has_text(&p.image_url) || has_text(&p.image_small_url),
+    },
+    CompletenessCriterion {
+        fields: &["nutrition_grade_fr"],
+        points: 10,
+        present: |p| has_text(&p.nutrition_grade_fr),
+    },
+    CompletenessCriterion {
+        fields: &["quantity"],
+        points: 10,
+        present: |p| has_text(&p.quantity),
+    },
+    CompletenessCriterion {
+        fields: &["categories_tags"],
+        points: 10,
+        present: |p| has_tags(&p.categories),
+    },
+    CompletenessCriterion {
+        fields: &["countries_tags"],
+        points: 10,
+        present: |p| has_tags(&p.countries),
+    },
+];
+
+/// How complete a product's data is, 0 to 100, for curators to pick what to fix first.
+/// Derived on read and never stored.
+pub fn completeness(product: &Product) -> u8 {
+    COMPLETENESS_CRITERIA
+        .iter()
+        .filter(|criterion| (criterion.present)(product))
+        .map(|criterion| criterion.points)
+        .sum()
+}
+
+/// Whether a product has neither a name nor an image, which leaves nothing to show of it.
+pub fn is_blank(product: &Product) -> bool {
+    !has_text(&product.product_name)
+        && !has_text(&product.image_url)
+        && !has_text(&product.image_small_url)
+}
+
+/// A product the curation list found, with its [`completeness`].
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct IncompleteProduct {
+    #[serde(flatten)]
+    pub product: Product,
+    pub completeness: u8,
+}
+
+/// What is left of a deleted product in the `product_tombstones` collection, for sync
+/// clients to remove their copy by.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Tombstone {
+    #[serde(rename = "_id")]
+    #[schema(value_type = String)]
+    pub id: ObjectId,
+    pub code: String,
+    #[serde(
+        rename = "deleted_datetime",
+        with = "chrono_datetime_as_rfc3339_or_bson"
+    )]
+    pub deleted_at: DateTime<Utc>,
+}
+
+/// One entry of the changes feed: a product as it now is, or the tombstone of a deleted
+/// one. `change` says which; the other fields are the product's or the tombstone's.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum ChangeEntry {
+    Modified(Box<Product>),
+    Deleted(Tombstone),
+}
+
+impl ChangeEntry {
+    /// Where the entry sits in the feed, which is ordered by this time and then by id.
+    pub fn position(&self) -> (DateTime<Utc>, Option<ObjectId>) {
+        match self {
+            ChangeEntry::Modified(product) => (product.last_modified_at, product.id),
+            ChangeEntry::Deleted(tombstone) => (tombstone.deleted_at, Some(tombstone.id)),
+        }
+    }
+}
+
+/// The words of a product name, lowercased, with the spacing and punctuation between
+/// them dropped: what duplicate detection compares names by.
+pub fn name_tokens(name: &str) -> Vec<String> {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Longest brand, category or other tag a product takes, in characters.
+pub const MAX_TAG_CHARS: usize = 128;
+
+/// Refuses a text of nothing but whitespace.
+pub(crate) fn not_blank(value: &str) -> Result<(), ValidationError> {
+    if value.trim().is_empty() {
+        return Err(ValidationError::new("blank"));
+    }
+    Ok(())
+}
+
+/// The Eco-Score grades, from the lowest environmental impact to the highest.
+pub const ECOSCORE_GRADES: [&str; 5] = ["a", "b", "c", "d", "e"];
+
+/// `grade` as stored, lowercase, if it is an Eco-Score grade in any case.
+pub fn ecoscore_grade(grade: &str) -> Option<String> {
+    let grade = grade.trim().to_ascii_lowercase();
+    ECOSCORE_GRADES.contains(&grade.as_str()).then_some(grade)
+}
+
+/// Refuses anything but an Eco-Score grade.
+fn valid_ecoscore_grade(grade: &str) -> Result<(), ValidationError> {
+    if ecoscore_grade(grade).is_none() {
+        return Err(ValidationError::new("ecoscore_grade"));
+    }
+    Ok(())
+}
+
+/// Refuses a code with whitespace around it, which no scan produces.
+fn trimmed(value: &str) -> Result<(), ValidationError> {
+    if value.trim() != value {
+        return Err(ValidationError::new("untrimmed"));
+    }
+    Ok(())
+}
+
+/// Refuses a tag list with a tag over [`MAX_TAG_CHARS`].
+fn short_tags(tags: &[String]) -> Result<(), ValidationError> {
+    if tags.iter().any(|tag| tag.chars().count() > MAX_TAG_CHARS) {
+        return Err(ValidationError::new("tag_length"));
+    }
+    Ok(())
+}
+
+/// Body of `POST /api/v1/products`. Lengths count characters, not bytes.
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
 pub struct CreateProductPayload {
+    #[validate(
+        length(min = 1, max = 64, message = "Product code must be 1-64 characters"),
+        custom(
+            function = "trimmed",
+            message = "Product code must not start or end with whitespace"
+        )
+    )]
     pub code: String,
+    #[validate(
+        length(max = 512, message = "Product name must be at most 512 characters"),
+        custom(function = "not_blank", message = "Product name must not be blank")
+    )]
     pub product_name: Option<String>,
+    #[validate(length(max = 20000, message = "Ingredients must be at most 20000 characters"))]
     pub ingredients_text: Option<String>,
+    /// Structured ingredients; their flags add allergen and diet tags.
+    #[validate(length(max = 500, message = "At most 500 ingredients"))]
+    pub ingredients: Option<Vec<IngredientEntry>>,
+    #[validate(
+        length(max = 50, message = "At most 50 brands"),
+        custom(
+            function = "short_tags",
+            message = "Brands must be at most 128 characters"
+        )
+    )]
     pub brands: Option<Vec<String>>,
+    #[validate(
+        length(max = 100, message = "At most 100 categories"),
+        custom(
+            function = "short_tags",
+            message = "Categories must be at most 128 characters"
+        )
+    )]
     pub categories: Option<Vec<String>>,
+    /// Must be on an allowed host, see [`crate::images`]; its smaller versions are derived.
+    #[validate(url(message = "Image URL must be a valid URL"))]
+    pub image_url: Option<String>,
+    #[validate(custom(
+        function = "valid_ecoscore_grade",
+        message = "Eco-Score grade must be a, b, c, d or e"
+    ))]
+    pub ecoscore_grade: Option<String>,
+    pub ecoscore_score: Option<f64>,
+    /// Scored into a `nutrition_grade_fr` marked `computed` when enough are known.
+    #[validate(nested)]
+    pub nutriments: Option<Nutriments>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Body of `PUT /api/v1/products/{id}`: the fields to replace. Limited as
+/// [`CreateProductPayload`] is.
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
 pub struct UpdateProductPayload {
+    #[validate(
+        length(max = 512, message = "Product name must be at most 512 characters"),
+        custom(function = "not_blank", message = "Product name must not be blank")
+    )]
     pub product_name: Option<String>,
+    #[validate(
+        length(max = 512, message = "Generic name must be at most 512 characters"),
+        custom(function = "not_blank", message = "Generic name must not be blank")
+    )]
     pub generic_name: Option<String>,
+    #[validate(url(message = "Image URL must be a valid URL"))]
     pub image_url: Option<String>,
+    #[validate(length(max = 20000, message = "Ingredients must be at most 20000 characters"))]
     pub ingredients_text: Option<String>,
+    /// Replaces the structured ingredients; their flags add allergen and diet tags.
+    #[validate(length(max = 500, message = "At most 500 ingredients"))]
+    pub ingredients: Option<Vec<IngredientEntry>>,
+    #[validate(
+        length(max = 50, message = "At most 50 brands"),
+        custom(
+            function = "short_tags",
+            message = "Brands must be at most 128 characters"
+        )
+    )]
     pub brands: Option<Vec<String>>,
+    #[validate(
+        length(max = 100, message = "At most 100 categories"),
+        custom(
+            function = "short_tags",
+            message = "Categories must be at most 128 characters"
+        )
+    )]
     pub categories: Option<Vec<String>>,
+    #[validate(
+        length(max = 100, message = "At most 100 labels"),
+        custom(
+            function = "short_tags",
+            message = "Labels must be at most 128 characters"
+        )
+    )]
     pub labels: Option<Vec<String>>,
+    #[validate(
+        length(max = 50, message = "At most 50 traces"),
+        custom(
+            function = "short_tags",
+            message = "Traces must be at most 128 characters"
+        )
+    )]
     pub traces: Option<Vec<String>>,
-    pub allergens_tags: Option<Vec<String>>, // Allow updating allergens
+    /// Replaces the allergens; names and tags the catalog doesn't know are dropped.
+    #[validate(
+        length(max = 50, message = "At most 50 allergens"),
+        custom(
+            function = "short_tags",
+            message = "Allergens must be at most 128 characters"
+        )
+    )]
+    pub allergens_tags: Option<Vec<String>>,
+    #[validate(length(max = 100, message = "Quantity must be at most 100 characters"))]
     pub quantity: Option<String>,
+    #[validate(
+        length(max = 300, message = "At most 300 countries"),
+        custom(
+            function = "short_tags",
+            message = "Countries must be at most 128 characters"
+        )
+    )]
     pub countries: Option<Vec<String>>,
+    #[validate(length(max = 10, message = "Nutri-Score grade must be at most 10 characters"))]
     pub nutrition_grade_fr: Option<String>,
+    #[validate(custom(
+        function = "valid_ecoscore_grade",
+        message = "Eco-Score grade must be a, b, c, d or e"
+    ))]
+    pub ecoscore_grade: Option<String>,
+    pub ecoscore_score: Option<f64>,
+    /// Replaces the nutriments. Unless `nutrition_grade_fr` is given too, a product with
+    /// no grade, or a computed one, is graded from them.
+    #[validate(nested)]
+    pub nutriments: Option<Nutriments>,
+}
+
+/// Body of `PATCH /api/v1/products/{id}`, a JSON merge patch over [`Product`]: a field
+/// that is absent stays as it is, `null` clears it and a value replaces it. Clearing
+/// `allergens_tags` leaves an empty list.
+#[derive(Debug, Default, Deserialize, Validate, ToSchema)]
+pub struct PatchProductPayload {
+    #[serde(default, deserialize_with = "double_option")]
+    #[validate(
+        length(max = 512, message = "Product name must be at most 512 characters"),
+        custom(function = "not_blank", message = "Product name must not be blank")
+    )]
+    #[schema(value_type = Option<String>)]
+    pub product_name: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    #[validate(
+        length(max = 512, message = "Generic name must be at most 512 characters"),
+        custom(function = "not_blank", message = "Generic name must not be blank")
+    )]
+    #[schema(value_type = Option<String>)]
+    pub generic_name: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    #[validate(url(message = "Image URL must be a valid URL"))]
+    #[schema(value_type = Option<String>)]
+    pub image_url: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    #[validate(length(max = 20000, message = "Ingredients must be at most 20000 characters"))]
+    #[schema(value_type = Option<String>)]
+    pub ingredients_text: Option<Option<String>>,
+    #[serde(rename = "brands_tags", default, deserialize_with = "double_option")]
+    #[validate(
+        length(max = 50, message = "At most 50 brands"),
+        custom(
+            function = "short_tags",
+            message = "Brands must be at most 128 characters"
+        )
+    )]
+    #[schema(value_type = Option<Vec<String>>)]
+    pub brands: Option<Option<Vec<String>>>,
+    #[serde(
+        rename = "categories_tags",
+        default,
+        deserialize_with = "double_option"
+    )]
+    #[validate(
+        length(max = 100, message = "At most 100 categories"),
+        custom(
+            function = "short_tags",
+            message = "Categories must be at most 128 characters"
+        )
+    )]
+    #[schema(value_type = Option<Vec<String>>)]
+    pub categories: Option<Option<Vec<String>>>,
+    #[serde(rename = "labels_tags", default, deserialize_with = "double_option")]
+    #[validate(
+        length(max = 100, message = "At most 100 labels"),
+        custom(
+            function = "short_tags",
+            message = "Labels must be at most 128 characters"
+        )
+    )]
+    #[schema(value_type = Option<Vec<String>>)]
+    pub labels: Option<Option<Vec<String>>>,
+    #[serde(rename = "traces_tags", default, deserialize_with = "double_option")]
+    #[validate(
+        length(max = 50, message = "At most 50 traces"),
+        custom(
+            function = "short_tags",
+            message = "Traces must be at most 128 characters"
+        )
+    )]
+    #[schema(value_type = Option<Vec<String>>)]
+    pub traces: Option<Option<Vec<String>>>,
+    #[serde(default, deserialize_with = "double_option")]
+    #[validate(
+        length(max = 50, message = "At most 50 allergens"),
+        custom(
+            function = "short_tags",
+            message = "Allergens must be at most 128 characters"
+        )
+    )]
+    #[schema(value_type = Option<Vec<String>>)]
+    pub allergens_tags: Option<Option<Vec<String>>>,
+    #[serde(default, deserialize_with = "double_option")]
+    #[validate(length(max = 100, message = "Quantity must be at most 100 characters"))]
+    #[schema(value_type = Option<String>)]
+    pub quantity: Option<Option<String>>,
+    #[serde(rename = "countries_tags", default, deserialize_with = "double_option")]
+    #[validate(
+        length(max = 300, message = "At most 300 countries"),
+        custom(
+            function = "short_tags",
+            message = "Countries must be at most 128 characters"
+        )
+    )]
+    #[schema(value_type = Option<Vec<String>>)]
+    pub countries: Option<Option<Vec<String>>>,
+    #[serde(default, deserialize_with = "double_option")]
+    #[validate(length(max = 10, message = "Nutri-Score grade must be at most 10 characters"))]
+    #[schema(value_type = Option<String>)]
+    pub nutrition_grade_fr: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    #[validate(custom(
+        function = "valid_ecoscore_grade",
+        message = "Eco-Score grade must be a, b, c, d or e"
+    ))]
+    #[schema(value_type = Option<String>)]
+    pub ecoscore_grade: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    #[schema(value_type = Option<f64>)]
+    pub ecoscore_score: Option<Option<f64>>,
+}
+
+/// Body of `POST /api/v1/products/batch`.
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct BatchLookupPayload {
+    #[validate(length(max = 100, message = "At most 100 codes"))]
+    pub codes: Vec<String>,
+}
+
+/// The products found for a batch lookup, keyed by barcode, and the codes that were not.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BatchLookupResponse {
+    pub products: BTreeMap<String, Product>,
+    pub not_found: Vec<String>,
 }
 
-#[derive(Debug, Deserialize)]
+/// Query of the product search. The list parameters are repeated (`brand=a&brand=b`),
+/// comma-separated (`brand=a,b`) or both; empty entries are ignored, and a list left
+/// empty is as if the parameter were absent. Other parameters, such as the page's, are
+/// left to their own extractors.
+#[derive(Debug, Default, Deserialize, Validate)]
+#[serde(try_from = "Vec<(String, String)>")]
 pub struct SearchParams {
     pub q: Option<String>,
-    pub category: Option<String>,
-    pub brand: Option<String>,
-    pub label: Option<String>,
-    pub country: Option<String>,
+    /// Products in any of these categories, or all of them with `match=all`.
+    pub category: Vec<String>,
+    pub category_match: TagMatch,
+    /// Products of any of these brands.
+    pub brand: Vec<String>,
+    /// Products with any of these labels.
+    pub label: Vec<String>,
+    /// Products sold in any of these countries; `all` for every country, whatever the
+    /// `DEFAULT_COUNTRY_TAG`.
+    pub country: Vec<String>,
     pub nutriscore: Option<String>,
-    pub limit: Option<u64>,
-    pub offset: Option<u64>,
-    #[serde(rename = "allergens")]
+    /// An Eco-Score grade, lowercase.
+    pub ecoscore: Option<String>,
+    /// Grams per 100 g at most; products without the value are left out.
+    #[validate(range(min = 0.0, message = "max_sugar must not be negative"))]
+    pub max_sugar: Option<f64>,
+    #[validate(range(min = 0.0, message = "max_salt must not be negative"))]
+    pub max_salt: Option<f64>,
+    #[validate(range(min = 0.0, message = "max_fat must not be negative"))]
+    pub max_fat: Option<f64>,
+    /// Grams of protein per 100 g at least.
+    #[validate(range(min = 0.0, message = "min_protein must not be negative"))]
+    pub min_protein: Option<f64>,
+    /// `allergens`: products carrying any of them are left out.
     pub user_allergens: Option<Vec<String>>,
-    #[serde(rename = "diets")]
+    /// `diets`: products conflicting with any of them are left out.
     pub user_diets: Option<Vec<String>>,
+    /// Products whose ingredients name all of these; see [`crate::ingredient_terms`].
+    pub ingredients_include: Vec<String>,
+    /// Products whose ingredients name any of these are left out.
+    pub ingredients_exclude: Vec<String>,
+    /// `exclude_traces=true` also leaves out products with traces of an
+    /// `ingredients_exclude` term.
+    pub exclude_traces: bool,
+    /// `allergen_mode`; [`AllergenMode::Lenient`] when absent.
+    pub allergen_mode: AllergenMode,
+    /// Counts all matches into the page's `total`; `false` saves that query. Default `true`.
+    pub include_total: Option<bool>,
+    /// `include_count=true` sends the count of all matches as `X-Total-Count`, leaving
+    /// the body as it is.
+    pub include_count: bool,
+    /// `sort`, which wins over the relevance order a `q` otherwise implies.
+    pub sort: Option<SearchSort>,
+    /// `debug=true` shows each result's text score as `_score`.
+    pub debug: bool,
+    /// `highlight=true` marks where a `q` was found in each result; see
+    /// [`crate::highlight`].
+    pub highlight: bool,
+    /// `view`; left to the handler when absent.
+    pub view: Option<SearchView>,
+    /// `lang`: the language to show each product's v2 `displayName` in, ahead of
+    /// `Accept-Language`.
+    pub lang: Option<String>,
+}
+
+/// The order of search results: by `_id`, which is insertion order, or by how well
+/// they match `q`, best first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchSort {
+    #[default]
+    Id,
+    Relevance,
+}
+
+/// What a search lists of each product: all of it, or the [`SearchSummary`] fields.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SearchView {
+    #[default]
+    Full,
+    Summary,
+}
+
+/// A product a search found, with its text score when the search had a `q`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub product: Product,
+    #[serde(rename = "_score", default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+    /// The name in the reader's language, set when answering v2; see
+    /// [`crate::language`]. v1 never sets it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(ignore)]
+    pub display_name: Option<String>,
+    /// Where the search's `q` was found, with `highlight=true`; see [`crate::highlight`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub highlights: Option<Vec<Highlight>>,
+}
+
+impl SearchHit {
+    /// The hit with its `display_name` for a reader who prefers `preferred`.
+    pub fn named_for(self, preferred: &[String]) -> Self {
+        let display_name = display_name(
+            self.product.product_name.as_deref(),
+            self.product.product_name_langs.as_ref(),
+            preferred,
+        );
+        SearchHit {
+            display_name,
+            ..self
+        }
+    }
+
+    /// The hit with the highlights of `q` in its name, brands and ingredients.
+    pub fn highlighted(self, q: &str) -> Self {
+        let product = &self.product;
+        let brands = product.brands.as_ref().map(|brands| brands.join(", "));
+        let highlights = highlight(
+            q,
+            [
+                ("product_name", product.product_name.as_deref()),
+                ("brands_tags", brands.as_deref()),
+                ("ingredients_text", product.ingredients_text.as_deref()),
+            ],
+        );
+        SearchHit {
+            highlights: Some(highlights),
+            ..self
+        }
+    }
+}
+
+/// A product as `view=summary` lists it: enough for a result list, without the
+/// ingredients text, nutriments and most tag lists. Read with a projection, so the
+/// rest never leaves MongoDB.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SearchSummary {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub id: Option<ObjectId>,
+    pub code: String,
+    pub product_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub product_name_langs: Option<HashMap<String, String>>,
+    #[serde(rename = "brands_tags")]
+    pub brands: Option<Vec<String>>,
+    pub image_small_url: Option<String>,
+    pub nutrition_grade_fr: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ecoscore_grade: Option<String>,
+    #[serde(default)]
+    pub allergens_tags: Vec<String>,
+    #[serde(rename = "_score", default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+    /// As [`SearchHit::display_name`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(ignore)]
+    pub display_name: Option<String>,
+    /// As [`SearchHit::highlights`], without the ingredients a summary leaves out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub highlights: Option<Vec<Highlight>>,
+}
+
+impl SearchSummary {
+    /// The stored fields a summary is read from.
+    pub const FIELDS: [&'static str; 9] = [
+        "_id",
+        "code",
+        "product_name",
+        "product_name_langs",
+        "brands_tags",
+        "image_small_url",
+        "nutrition_grade_fr",
+        "ecoscore_grade",
+        "allergens_tags",
+    ];
+
+    /// The summary with its `display_name` for a reader who prefers `preferred`.
+    pub fn named_for(self, preferred: &[String]) -> Self {
+        let display_name = display_name(
+            self.product_name.as_deref(),
+            self.product_name_langs.as_ref(),
+            preferred,
+        );
+        SearchSummary {
+            display_name,
+            ..self
+        }
+    }
+
+    /// The summary with the highlights of `q` in its name and brands.
+    pub fn highlighted(self, q: &str) -> Self {
+        let brands = self.brands.as_ref().map(|brands| brands.join(", "));
+        let highlights = highlight(
+            q,
+            [
+                ("product_name", self.product_name.as_deref()),
+                ("brands_tags", brands.as_deref()),
+            ],
+        );
+        SearchSummary {
+            highlights: Some(highlights),
+            ..self
+        }
+    }
+}
+
+impl From<SearchHit> for SearchSummary {
+    fn from(hit: SearchHit) -> Self {
+        let product = hit.product;
+        SearchSummary {
+            id: product.id,
+            code: product.code,
+            product_name: product.product_name,
+            product_name_langs: product.product_name_langs,
+            brands: product.brands,
+            image_small_url: product.image_small_url,
+            nutrition_grade_fr: product.nutrition_grade_fr,
+            ecoscore_grade: product.ecoscore_grade,
+            allergens_tags: product.allergens_tags,
+            score: hit.score,
+            display_name: hit.display_name,
+            highlights: hit.highlights,
+        }
+    }
+}
+
+/// How a list of tags filters: `any` (the default) or `all` of them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TagMatch {
+    #[default]
+    Any,
+    All,
+}
+
+/// What a search or recommendation makes of products whose allergen data may be
+/// missing. `lenient`, the default, takes products without allergens as free of them;
+/// `strict` leaves out the products with no `ingredients_text` or no `allergens_tags`,
+/// keeping those whose tags are present but empty.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AllergenMode {
+    #[default]
+    Lenient,
+    Strict,
+}
+
+impl TryFrom<Vec<(String, String)>> for SearchParams {
+    type Error = String;
+
+    fn try_from(pairs: Vec<(String, String)>) -> Result<Self, Self::Error> {
+        let mut params = SearchParams::default();
+        let mut allergens = Vec::new();
+        let mut diets = Vec::new();
+        let list = |value: &str| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        let grams = |key: &str, value: &str| {
+            value
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|g| g.is_finite())
+                .ok_or_else(|| format!("{} must be a number, got '{}'", key, value))
+        };
+        for (key, value) in pairs {
+            match key.as_str() {
+                "q" => params.q = Some(value),
+                "category" => params.category.extend(list(&value)),
+                "brand" => params.brand.extend(list(&value)),
+                "label" => params.label.extend(list(&value)),
+                "country" => params.country.extend(list(&value)),
+                "nutriscore" => params.nutriscore = Some(value),
+                "ecoscore" => {
+                    params.ecoscore = Some(ecoscore_grade(&value).ok_or_else(|| {
+                        format!("ecoscore must be a grade from a to e, got '{}'", value)
+                    })?)
+                }
+                "lang" => params.lang = Some(value),
+                "max_sugar" => params.max_sugar = Some(grams(&key, &value)?),
+                "max_salt" => params.max_salt = Some(grams(&key, &value)?),
+                "max_fat" => params.max_fat = Some(grams(&key, &value)?),
+                "min_protein" => params.min_protein = Some(grams(&key, &value)?),
+                "allergens" => allergens.extend(list(&value)),
+                "diets" => diets.extend(list(&value)),
+                "ingredients_include" => params.ingredients_include.extend(list(&value)),
+                "ingredients_exclude" => params.ingredients_exclude.extend(list(&value)),
+                "allergen_mode" => {
+                    params.allergen_mode = match value.trim() {
+                        "lenient" => AllergenMode::Lenient,
+                        "strict" => AllergenMode::Strict,
+                        other => {
+                            return Err(format!(
+                                "allergen_mode must be 'lenient' or 'strict', got '{}'",
+                                other
+                            ));
+                        }
+                    }
+                }
+                "exclude_traces" => {
+                    params.exclude_traces = value.trim().parse().map_err(|_| {
+                        format!("exclude_traces must be true or false, got '{}'", value)
+                    })?
+                }
+                "match" => {
+                    params.category_match = match value.trim() {
+                        "any" => TagMatch::Any,
+                        "all" => TagMatch::All,
+                        other => {
+                            return Err(format!("match must be 'any' or 'all', got '{}'", other));
+                        }
+                    }
+                }
+                "include_total" => {
+                    params.include_total = Some(value.trim().parse().map_err(|_| {
+                        format!("include_total must be true or false, got '{}'", value)
+                    })?)
+                }
+                "include_count" => {
+                    params.include_count = value.trim().parse().map_err(|_| {
+                        format!("include_count must be true or false, got '{}'", value)
+                    })?
+                }
+                "sort" => {
+                    params.sort = Some(match value.trim() {
+                        "id" => SearchSort::Id,
+                        "relevance" => SearchSort::Relevance,
+                        other => {
+                            return Err(format!(
+                                "sort must be 'id' or 'relevance', got '{}'",
+                                other
+                            ));
+                        }
+                    })
+                }
+                "debug" => {
+                    params.debug = value
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("debug must be true or false, got '{}'", value))?
+                }
+                "highlight" => {
+                    params.highlight = value
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("highlight must be true or false, got '{}'", value))?
+                }
+                "view" => {
+                    params.view = Some(match value.trim() {
+                        "full" => SearchView::Full,
+                        "summary" => SearchView::Summary,
+                        other => {
+                            return Err(format!(
+                                "view must be 'summary' or 'full', got '{}'",
+                                other
+                            ));
+                        }
+                    })
+                }
+                _ => {}
+            }
+        }
+        if params.sort == Some(SearchSort::Relevance) && params.q.is_none() {
+            return Err("sort=relevance needs a q to rank by".to_string());
+        }
+        let terms = params.ingredients_include.len() + params.ingredients_exclude.len();
+        if terms > MAX_INGREDIENT_TERMS {
+            return Err(format!(
+                "At most {} ingredients_include and ingredients_exclude terms, got {}",
+                MAX_INGREDIENT_TERMS, terms
+            ));
+        }
+        params.user_allergens = Some(allergens).filter(|a| !a.is_empty());
+        params.user_diets = Some(diets).filter(|d| !d.is_empty());
+        Ok(params)
+    }
+}
+
+/// The parameters as [`SearchParams::try_from`] reads them, which a derive would not
+/// see through.
+impl IntoParams for SearchParams {
+    fn into_params(
+        _parameter_in_provider: impl Fn() -> Option<utoipa::openapi::path::ParameterIn>,
+    ) -> Vec<utoipa::openapi::path::Parameter> {
+        use utoipa::openapi::{
+            ArrayBuilder, ObjectBuilder, RefOr, Required, Schema,
+            path::{ParameterBuilder, ParameterIn},
+            schema::Type,
+        };
+
+        let string = || ObjectBuilder::new().schema_type(Type::String);
+        let list = || -> RefOr<Schema> { ArrayBuilder::new().items(string()).into() };
+        let grams = || -> RefOr<Schema> {
+            ObjectBuilder::new()
+                .schema_type(Type::Number)
+                .minimum(Some(0))
+                .into()
+        };
+        let flag = || -> RefOr<Schema> { ObjectBuilder::new().schema_type(Type::Boolean).into() };
+        [
+            (
+                "q",
+                "Text to search for; ranks by relevance unless `sort=id`.",
+                string().into(),
+            ),
+            (
+                "category",
+                "Categories, repeated or comma-separated.",
+                list(),
+            ),
+            (
+                "match",
+                "Whether products need `any` (the default) or `all` of the categories.",
+                string().enum_values(Some(["any", "all"])).into(),
+            ),
+            ("brand", "Brands, repeated or comma-separated.", list()),
+            ("label", "Labels, repeated or comma-separated.", list()),
+            ("country", "Countries, repeated or comma-separated; `all` searches every country even with a `DEFAULT_COUNTRY_TAG`.", list()),
+            ("nutriscore", "Nutri-Score grade.", string().into()),
+            (
+                "ecoscore",
+                "Eco-Score grade.",
+                string().enum_values(Some(ECOSCORE_GRADES)).into(),
+            ),
+            ("max_sugar", "Grams of sugar per 100 g at most.", grams()),
+            ("max_salt", "Grams of salt per 100 g at most.", grams()),
+            ("max_fat", "Grams of fat per 100 g at most.", grams()),
+            (
+                "min_protein",
+                "Grams of protein per 100 g at least.",
+                grams(),
+            ),
+            (
+                "allergens",
+                "Leaves out products with any of these allergens.",
+                list(),
+            ),
+            (
+                "diets",
+                "Leaves out products conflicting with any of these diets.",
+                list(),
+            ),
+            (
+                "allergen_mode",
+                "`strict` also leaves out products without `ingredients_text` or `allergens_tags`, whose allergens are unknown; `lenient` (the default) keeps them.",
+                string().enum_values(Some(["lenient", "strict"])).into(),
+            ),
+            (
+                "ingredients_include",
+                "Products whose ingredients name all of these words or phrases, ignoring case and accents; repeated or comma-separated. At most 5 terms with `ingredients_exclude`; unindexed, so best with a `category` or `q`.",
+                list(),
+            ),
+            (
+                "ingredients_exclude",
+                "Leaves out products whose ingredients name any of these words or phrases.",
+                list(),
+            ),
+            (
+                "exclude_traces",
+                "Also leaves out products with traces of an `ingredients_exclude` term.",
+                flag(),
+            ),
+            (
+                "include_total",
+                "Counts all matches into `total`; default `true`.",
+                flag(),
+            ),
+            (
+                "include_count",
+                "Sends the count of all matches as `X-Total-Count`.",
+                flag(),
+            ),
+            (
+                "sort",
+                "`id` or `relevance`, which needs a `q`.",
+                string().enum_values(Some(["id", "relevance"])).into(),
+            ),
+            (
+                "debug",
+                "Shows each result's text score as `_score`.",
+                flag(),
+            ),
+            (
+                "highlight",
+                "With a `q`, lists in each result's `highlights` the fields naming a query word, the words marked with `<em>`.",
+                flag(),
+            ),
+            (
+                "view",
+                "`full` products or their `summary`; what a search without it lists depends on the version and page size.",
+                string().enum_values(Some(["summary", "full"])).into(),
+            ),
+            (
+                "lang",
+                "The language to show each v2 `displayName` in, e.g. `de`; wins over `Accept-Language`. Ignored by v1.",
+                string().into(),
+            ),
+        ]
+        .into_iter()
+        .map(|(name, description, schema)| {
+            ParameterBuilder::new()
+                .name(name)
+                .parameter_in(ParameterIn::Query)
+                .required(Required::False)
+                .description(Some(description))
+                .schema(Some(schema))
+                .build()
+        })
+        .collect()
+    }
+}
+
+/// Query of `GET /api/v1/products/search/semantic`.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SemanticSearchParams {
+    pub q: Option<String>,
+    pub limit: Option<u64>,
+    /// Comma-separated, as in search.
+    pub allergens: Option<String>,
+    /// Comma-separated, as in search.
+    pub diets: Option<String>,
+}
+
+/// Query of `GET /api/v1/products/{id}/recommendations`. `limit` defaults to the
+/// `recommendation_limit` tunable and is capped at 50; `min_score` drops candidates less
+/// similar than it.
+#[derive(Debug, Default, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RecommendationParams {
+    #[validate(range(min = 1, message = "limit must be at least 1"))]
+    pub limit: Option<u64>,
+    #[validate(range(min = 0.0, max = 1.0, message = "min_score must be between 0 and 1"))]
+    pub min_score: Option<f32>,
+    /// As the search's `allergen_mode`; `lenient` when absent.
+    pub allergen_mode: Option<AllergenMode>,
+}
+
+/// Body of `POST /api/v1/products/search/semantic`: a text `q` to embed, or a `vector`
+/// already embedded with the index's model.
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct SemanticSearchPayload {
+    #[validate(length(max = 512, message = "Query must be at most 512 characters"))]
+    pub q: Option<String>,
+    #[validate(length(min = 1, max = 4096, message = "Vector must have 1-4096 dimensions"))]
+    pub vector: Option<Vec<f32>>,
+    pub limit: Option<u64>,
+    pub allergens: Option<Vec<String>>,
+    pub diets: Option<Vec<String>>,
+}
+
+/// Query of `GET /api/v1/products/search/hybrid`. `semantic_weight` is the share of the
+/// vector similarity in each product's score, the rest being its text score; see
+/// [`crate::hybrid`].
+#[derive(Debug, Default, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HybridSearchParams {
+    pub q: Option<String>,
+    #[validate(range(min = 1, message = "limit must be at least 1"))]
+    pub limit: Option<u64>,
+    #[validate(range(
+        min = 0.0,
+        max = 1.0,
+        message = "semantic_weight must be between 0 and 1"
+    ))]
+    #[param(example = 0.5)]
+    pub semantic_weight: Option<f64>,
+    /// Comma-separated, as in search.
+    pub allergens: Option<String>,
+    /// Comma-separated, as in search.
+    pub diets: Option<String>,
+}
+
+/// Body of `POST /api/v1/products/search/hybrid`: the text `q` to match, with its
+/// `vector` already embedded with the index's model, or without to have it embedded.
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct HybridSearchPayload {
+    #[validate(length(min = 1, max = 512, message = "Query must be 1-512 characters"))]
+    pub q: String,
+    #[validate(length(min = 1, max = 4096, message = "Vector must have 1-4096 dimensions"))]
+    pub vector: Option<Vec<f32>>,
+    #[validate(range(min = 1, message = "limit must be at least 1"))]
+    pub limit: Option<u64>,
+    #[validate(range(
+        min = 0.0,
+        max = 1.0,
+        message = "semantic_weight must be between 0 and 1"
+    ))]
+    pub semantic_weight: Option<f64>,
+    pub allergens: Option<Vec<String>>,
+    pub diets: Option<Vec<String>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yoloeats_domain::fixtures::{FIXTURE_TIME, ProductFixture};
+
+    fn search_params(query: &[(&str, &str)]) -> Result<SearchParams, String> {
+        SearchParams::try_from(
+            query
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn search_lists_are_repeated_or_comma_separated() {
+        let params = search_params(&[
+            ("brand", "ritter-sport"),
+            ("brand", "milka, ,lindt"),
+            ("category", "en:chocolates"),
+            ("country", ","),
+            ("allergens", "milk,peanuts"),
+            ("limit", "5"),
+        ])
+        .unwrap();
+        assert_eq!(params.brand, ["ritter-sport", "milka", "lindt"]);
+        assert_eq!(params.category, ["en:chocolates"]);
+        assert_eq!(params.category_match, TagMatch::Any);
+        assert!(params.country.is_empty());
+        assert!(params.label.is_empty());
+        assert_eq!(
+            params.user_allergens,
+            Some(vec!["milk".to_string(), "peanuts".to_string()])
+        );
+        assert_eq!(params.user_diets, None);
+    }
+
+    #[test]
+    fn nutrient_limits_are_numbers_and_not_negative() {
+        let params = search_params(&[("max_sugar", "5"), ("min_protein", " 10.5 ")]).unwrap();
+        assert_eq!(params.max_sugar, Some(5.0));
+        assert_eq!(params.min_protein, Some(10.5));
+        assert!(params.validate().is_ok());
+
+        let negative = search_params(&[("max_salt", "-0.1")]).unwrap();
+        assert!(
+            negative
+                .validate()
+                .unwrap_err()
+                .field_errors()
+                .contains_key("max_salt")
+        );
+        assert!(search_params(&[("max_fat", "lots")]).is_err());
+        assert!(search_params(&[("max_fat", "NaN")]).is_err());
+    }
+
+    #[test]
+    fn search_sort_and_debug_are_checked() {
+        let params = search_params(&[("q", "nutella"), ("sort", "id"), ("debug", "true")]).unwrap();
+        assert_eq!(params.sort, Some(SearchSort::Id));
+        assert!(params.debug);
+        let ranked = search_params(&[("sort", "relevance"), ("q", "nutella")]).unwrap();
+        assert_eq!(ranked.sort, Some(SearchSort::Relevance));
+        assert_eq!(search_params(&[]).unwrap().sort, None);
+
+        assert!(search_params(&[("sort", "relevance")]).is_err());
+        assert!(search_params(&[("q", "nutella"), ("sort", "name")]).is_err());
+        assert!(search_params(&[("debug", "yes")]).is_err());
+    }
+
+    #[test]
+    fn ingredient_terms_are_capped() {
+        let params = search_params(&[
+            ("ingredients_include", "oats"),
+            ("ingredients_exclude", "palm oil, sugar"),
+            ("exclude_traces", "true"),
+        ])
+        .unwrap();
+        assert_eq!(params.ingredients_include, ["oats"]);
+        assert_eq!(params.ingredients_exclude, ["palm oil", "sugar"]);
+        assert!(params.exclude_traces);
+        assert!(!search_params(&[]).unwrap().exclude_traces);
+
+        let five = search_params(&[
+            ("ingredients_include", "a,b,c"),
+            ("ingredients_exclude", "d,e"),
+        ]);
+        assert!(five.is_ok());
+        let six = search_params(&[
+            ("ingredients_include", "a,b,c"),
+            ("ingredients_exclude", "d,e,f"),
+        ]);
+        assert!(six.unwrap_err().contains("At most 5"));
+        assert!(search_params(&[("exclude_traces", "yes")]).is_err());
+    }
+
+    #[test]
+    fn allergen_mode_is_lenient_unless_strict() {
+        assert_eq!(
+            search_params(&[]).unwrap().allergen_mode,
+            AllergenMode::Lenient
+        );
+        assert_eq!(
+            search_params(&[("allergen_mode", " strict ")])
+                .unwrap()
+                .allergen_mode,
+            AllergenMode::Strict
+        );
+        assert_eq!(
+            search_params(&[("allergen_mode", "lenient")])
+                .unwrap()
+                .allergen_mode,
+            AllergenMode::Lenient
+        );
+        assert!(search_params(&[("allergen_mode", "paranoid")]).is_err());
+    }
+
+    #[test]
+    fn search_view_is_checked() {
+        assert_eq!(search_params(&[]).unwrap().view, None);
+        assert_eq!(
+            search_params(&[("view", "summary")]).unwrap().view,
+            Some(SearchView::Summary)
+        );
+        assert_eq!(
+            search_params(&[("view", " full ")]).unwrap().view,
+            Some(SearchView::Full)
+        );
+        assert!(search_params(&[("view", "compact")]).is_err());
+    }
+
+    #[test]
+    fn search_summaries_keep_only_their_fields() {
+        let mut product = sample_product();
+        product.ingredients_text = Some("sugar, palm oil, hazelnuts".to_string());
+        product.product_name_langs = Some(HashMap::from([(
+            "de".to_string(),
+            "Ritter Sport Alpenmilch".to_string(),
+        )]));
+        product.ecoscore_grade = Some("d".to_string());
+        let summary = SearchSummary::from(SearchHit {
+            product: product.clone(),
+            score: Some(2.0),
+            display_name: Some("Ritter Sport Alpenmilch".to_string()),
+            highlights: None,
+        });
+        let json = serde_json::to_value(&summary).unwrap();
+        let mut fields: Vec<&str> = json
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        fields.sort();
+        let mut expected: Vec<&str> = SearchSummary::FIELDS
+            .iter()
+            .copied()
+            .filter(|field| *field != "_id" || product.id.is_some())
+            .chain(["_score", "display_name"])
+            .collect();
+        expected.sort();
+        assert_eq!(fields, expected);
+        assert_eq!(json["code"], product.code);
+
+        // What the projection reads back from MongoDB.
+        let read: SearchSummary = serde_json::from_value(serde_json::json!({
+            "code": "1",
+            "product_name": "Hazelnut spread",
+        }))
+        .unwrap();
+        assert_eq!(read.allergens_tags, Vec::<String>::new());
+        assert_eq!(read.score, None);
+    }
+
+    #[test]
+    fn search_hits_show_their_score_only_when_set() {
+        let hit = SearchHit {
+            product: sample_product(),
+            score: None,
+            display_name: None,
+            highlights: None,
+        };
+        let json = serde_json::to_value(&hit).unwrap();
+        assert_eq!(json, serde_json::to_value(&hit.product).unwrap());
+
+        let scored = SearchHit {
+            score: Some(1.5),
+            ..hit
+        };
+        let json = serde_json::to_value(&scored).unwrap();
+        assert_eq!(json["_score"], 1.5);
+        assert_eq!(json["code"], scored.product.code);
+    }
+
+    #[test]
+    fn search_results_are_named_for_the_reader() {
+        let mut product = sample_product();
+        product.product_name_langs = Some(HashMap::from([(
+            "de".to_string(),
+            "Ritter Sport Alpenmilch".to_string(),
+        )]));
+        let hit = SearchHit {
+            product,
+            score: None,
+            display_name: None,
+            highlights: None,
+        };
+        let german = ["fr".to_string(), "de".to_string()];
+        let named = hit.clone().named_for(&german);
+        assert_eq!(
+            named.display_name.as_deref(),
+            Some("Ritter Sport Alpenmilch")
+        );
+        let json = serde_json::to_value(&named).unwrap();
+        assert_eq!(json["display_name"], "Ritter Sport Alpenmilch");
+        assert_eq!(json["product_name"], "Ritter Sport");
+        assert_eq!(json["product_name_langs"]["de"], "Ritter Sport Alpenmilch");
+
+        let summary = SearchSummary::from(hit.clone()).named_for(&german);
+        assert_eq!(summary.display_name, named.display_name);
+        let english = hit.named_for(&["en".to_string()]);
+        assert_eq!(english.display_name.as_deref(), Some("Ritter Sport"));
+    }
+
+    #[test]
+    fn search_results_are_highlighted_on_request() {
+        let mut product = sample_product();
+        product.ingredients_text = Some("Sugar, whole milk powder".to_string());
+        let hit = SearchHit {
+            product,
+            score: None,
+            display_name: None,
+            highlights: None,
+        };
+        assert!(
+            serde_json::to_value(&hit)
+                .unwrap()
+                .get("highlights")
+                .is_none()
+        );
+
+        let json = serde_json::to_value(hit.clone().highlighted("MILK sport")).unwrap();
+        assert_eq!(
+            json["highlights"],
+            serde_json::json!([
+                {"field": "product_name", "snippet": "Ritter <em>Sport</em>"},
+                {"field": "brands_tags", "snippet": "ritter-<em>sport</em>"},
+                {"field": "ingredients_text", "snippet": "Sugar, whole <em>milk</em> powder"},
+            ])
+        );
+        let json = serde_json::to_value(hit.clone().highlighted("hazelnut")).unwrap();
+        assert_eq!(json["highlights"], serde_json::json!([]));
+
+        let summary = SearchSummary::from(hit).highlighted("milk sport");
+        let fields: Vec<String> = summary
+            .highlights
+            .unwrap()
+            .into_iter()
+            .map(|highlight| highlight.field)
+            .collect();
+        assert_eq!(fields, ["product_name", "brands_tags"]);
+    }
+
+    #[test]
+    fn nutriments_use_openfoodfacts_names_and_are_optional() {
+        let product: Product = serde_json::from_value(serde_json::json!({
+            "code": "1",
+            "created_datetime": FIXTURE_TIME,
+            "last_modified_datetime": FIXTURE_TIME,
+            "nutriments": {
+                "energy-kcal_100g": 539,
+                "sugars_100g": 56.3,
+                "saturated-fat_100g": 10.6,
+                "sodium_100g": 0.043
+            }
+        }))
+        .unwrap();
+        let nutriments = product.nutriments.unwrap();
+        assert_eq!(nutriments.energy_kcal_100g, Some(539.0));
+        assert_eq!(nutriments.sugars_100g, Some(56.3));
+        assert_eq!(nutriments.saturated_fat_100g, Some(10.6));
+        assert_eq!(nutriments.salt_100g, None);
+
+        let without = sample_product();
+        assert!(without.nutriments.is_none());
+        let json = serde_json::to_value(&without).unwrap();
+        assert!(json.get("nutriments").is_none());
+    }
+
+    #[test]
+    fn search_match_and_include_total_are_checked() {
+        let params = search_params(&[("match", "all"), ("include_total", "false")]).unwrap();
+        assert_eq!(params.category_match, TagMatch::All);
+        assert_eq!(params.include_total, Some(false));
+        assert!(!params.include_count);
+        assert!(
+            search_params(&[("include_count", "true")])
+                .unwrap()
+                .include_count
+        );
+        assert!(search_params(&[("match", "some")]).is_err());
+        assert!(search_params(&[("include_total", "maybe")]).is_err());
+        assert!(search_params(&[("include_count", "1")]).is_err());
+    }
+
+    #[test]
+    fn ecoscore_grades_are_a_to_e_in_any_case() {
+        let params = search_params(&[("ecoscore", " B "), ("nutriscore", "a")]).unwrap();
+        assert_eq!(params.ecoscore.as_deref(), Some("b"));
+        assert_eq!(params.nutriscore.as_deref(), Some("a"));
+        for refused in ["f", "", "unknown", "not-applicable", "ab"] {
+            assert!(
+                search_params(&[("ecoscore", refused)]).is_err(),
+                "{}",
+                refused
+            );
+        }
+
+        let graded = |grade: &str| CreateProductPayload {
+            ecoscore_grade: Some(grade.to_string()),
+            ..create("123", None)
+        };
+        assert!(graded("C").validate().is_ok());
+        let errors = graded("unknown").validate().unwrap_err();
+        assert!(errors.field_errors().contains_key("ecoscore_grade"));
+        let patch: PatchProductPayload =
+            serde_json::from_str(r#"{"ecoscore_grade": "x", "ecoscore_score": null}"#).unwrap();
+        assert_eq!(patch.ecoscore_score, Some(None));
+        assert!(patch.validate().is_err());
+
+        // Products stored before the Eco-Score still read, and still write without it.
+        let json = serde_json::to_value(sample_product()).unwrap();
+        assert!(json.get("ecoscore_grade").is_none());
+        let read: Product = serde_json::from_value(json).unwrap();
+        assert_eq!((read.ecoscore_grade, read.ecoscore_score), (None, None));
+    }
+
+    fn sample_product() -> Product {
+        ProductFixture::new("4000417025005")
+            .named("Ritter Sport")
+            .with_brands(["ritter-sport"])
+            .with_allergens(["en:milk"])
+            .build()
+    }
+
+    #[test]
+    fn completeness_points_add_up_to_100() {
+        let total: u32 = COMPLETENESS_CRITERIA
+            .iter()
+            .map(|criterion| u32::from(criterion.points))
+            .sum();
+        assert_eq!(total, 100);
+    }
+
+    #[test]
+    fn completeness_scores_what_a_product_has() {
+        let mut product: Product = ProductFixture::new("4000417025005").build();
+        product.product_name = None;
+        product.allergens_tags = Vec::new();
+        assert_eq!(completeness(&product), 0);
+
+        product.product_name = Some("Ritter Sport".to_string());
+        product.image_small_url = Some("https://images.example/small.jpg".to_string());
+        product.categories = Some(vec!["en:chocolates".to_string()]);
+        assert_eq!(completeness(&product), 45);
+
+        // Empty values count as missing.
+        product.ingredients_text = Some(String::new());
+        product.countries = Some(Vec::new());
+        assert_eq!(completeness(&product), 45);
+
+        product.ingredients_text = Some("cocoa mass, sugar".to_string());
+        product.allergens_tags = vec!["en:milk".to_string()];
+        product.image_url = Some("https://images.example/full.jpg".to_string());
+        product.nutrition_grade_fr = Some("e".to_string());
+        product.quantity = Some("100 g".to_string());
+        product.countries = Some(vec!["en:germany".to_string()]);
+        assert_eq!(completeness(&product), 100);
+    }
+
+    #[test]
+    fn incomplete_products_carry_their_score() {
+        let json = serde_json::to_value(IncompleteProduct {
+            product: sample_product(),
+            completeness: 30,
+        })
+        .unwrap();
+        assert_eq!(json["completeness"], 30);
+        assert_eq!(json["code"], "4000417025005");
+    }
+
+    #[test]
+    fn product_json_cache_round_trip_uses_rfc3339() {
+        let product = sample_product();
+        let cached = serde_json::to_string(&product).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&cached).unwrap();
+        assert_eq!(value["created_datetime"], FIXTURE_TIME);
+        assert_eq!(value["last_modified_datetime"], FIXTURE_TIME);
+
+        let back: Product = serde_json::from_str(&cached).unwrap();
+        assert_eq!(back.created_at, product.created_at);
+        assert_eq!(back.last_modified_at, product.last_modified_at);
+    }
+
+    #[test]
+    fn product_bson_round_trip_keeps_native_datetimes() {
+        let product = sample_product();
+        let raw = bson::to_raw_document_buf(&product).unwrap();
+        assert!(matches!(
+            raw.get("created_datetime").unwrap(),
+            Some(bson::RawBsonRef::DateTime(_))
+        ));
+        let back: Product = bson::from_slice(raw.as_bytes()).unwrap();
+        assert_eq!(back.created_at, product.created_at);
+    }
+
+    #[test]
+    fn product_json_is_readable_as_shared_product_summary() {
+        let product = sample_product();
+        let json = serde_json::to_string(&product).unwrap();
+        let summary: ProductSummary = serde_json::from_str(&json).unwrap();
+        assert_eq!(summary.code, product.code);
+        assert_eq!(summary.product_name, product.product_name);
+        assert_eq!(summary.allergens_tags, product.allergens_tags);
+        assert!(summary.traces_tags.is_empty());
+        assert!(summary.labels_tags.is_empty());
+        // The gRPC server converts directly; both transports must agree.
+        assert_eq!(ProductSummary::from(product), summary);
+    }
+
+    #[test]
+    fn summary_conversion_matches_json_when_tag_lists_are_present() {
+        let product: Product = ProductFixture::new("4000417025005")
+            .with_traces(["en:nuts"])
+            .vegetarian()
+            .build();
+        let json = serde_json::to_string(&product).unwrap();
+        let summary: ProductSummary = serde_json::from_str(&json).unwrap();
+        assert_eq!(ProductSummary::from(product), summary);
+    }
+
+    /// Documents as OpenFoodFacts dumps imported with `mongoimport` leave them: numbers
+    /// for strings, single strings for tag lists, Unix and unreadable timestamps.
+    const MESSY_JSONL: &str = include_str!("../fixtures/messy_products.jsonl");
+
+    fn messy_products() -> Vec<Product> {
+        MESSY_JSONL
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    fn tags(values: &[&str]) -> Option<Vec<String>> {
+        Some(values.iter().map(|v| v.to_string()).collect())
+    }
+
+    #[test]
+    fn messy_documents_read_leniently() {
+        let products = messy_products();
+        assert_eq!(products.len(), 4);
+
+        let nutella = &products[0];
+        assert_eq!(nutella.brands, tags(&["nutella", "ferrero"]));
+        assert_eq!(
+            nutella.allergens_tags,
+            ["en:milk", "en:nuts", "en:soybeans"]
+        );
+        assert_eq!(nutella.quantity.as_deref(), Some("400"));
+        assert_eq!(nutella.created_at.timestamp(), 1457680652);
+        assert_eq!(nutella.last_modified_at.timestamp(), 1717430417);
+
+        let beer = &products[1];
+        assert_eq!(beer.product_name.as_deref(), Some("1664"));
+        assert_eq!(beer.generic_name, None);
+        assert_eq!(beer.brands, tags(&["kronenbourg"]));
+        assert_eq!(beer.labels, None);
+        assert_eq!(beer.countries, tags(&["en:france"]));
+        assert_eq!(beer.quantity.as_deref(), Some("0.25"));
+        assert_eq!(beer.created_at.to_rfc3339(), "2019-03-01T10:00:00+00:00");
+        assert_eq!(
+            beer.last_modified_at.timestamp_millis(),
+            DateTime::parse_from_rfc3339("2024-02-29T22:59:59.5Z")
+                .unwrap()
+                .timestamp_millis()
+        );
+
+        let kinder = &products[2];
+        assert_eq!(kinder.categories, Some(Vec::new()));
+        assert!(kinder.allergens_tags.is_empty());
+        assert_eq!(kinder.quantity, None);
+        assert_eq!(kinder.created_at, DateTime::UNIX_EPOCH);
+        assert_eq!(kinder.last_modified_at, DateTime::UNIX_EPOCH);
+
+        let ritter = &products[3];
+        assert_eq!(ritter.labels, None);
+        assert_eq!(ritter.allergens_tags, ["en:milk"]);
+        assert_eq!(ritter.created_at.timestamp_millis(), 1457680652500);
+        assert_eq!(ritter.last_modified_at, DateTime::UNIX_EPOCH);
+        assert_eq!(ritter.traces_tags, None);
+    }
+
+    #[test]
+    fn messy_documents_read_the_same_from_bson() {
+        for (line, from_json) in MESSY_JSONL.lines().zip(messy_products()) {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            let document = match bson::Bson::try_from(value).unwrap() {
+                bson::Bson::Document(document) => document,
+                other => panic!("expected a document, got {:?}", other),
+            };
+            let from_bson: Product = bson::from_document(document).unwrap();
+            assert_eq!(
+                serde_json::to_value(&from_bson).unwrap(),
+                serde_json::to_value(&from_json).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn clean_products_read_back_unchanged() {
+        let product: Product = ProductFixture::new("4000417025005")
+            .named("Alpine milk chocolate")
+            .with_allergens(["en:milk"])
+            .build();
+        let json = serde_json::to_value(&product).unwrap();
+        let back: Product = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&back).unwrap(), json);
+        let document = bson::to_document(&product).unwrap();
+        let back: Product = bson::from_document(document).unwrap();
+        assert_eq!(serde_json::to_value(&back).unwrap(), json);
+    }
+
+    #[test]
+    fn patch_payload_tells_absent_null_and_values_apart() {
+        let patch: PatchProductPayload = serde_json::from_str(
+            r#"{"image_url": null, "labels_tags": ["en:organic"], "generic_name": "Biscuits"}"#,
+        )
+        .unwrap();
+        assert_eq!(patch.image_url, Some(None));
+        assert_eq!(patch.labels, Some(Some(vec!["en:organic".to_string()])));
+        assert_eq!(patch.generic_name, Some(Some("Biscuits".to_string())));
+        assert_eq!(patch.quantity, None);
+        assert!(patch.validate().is_ok());
+
+        let patch: PatchProductPayload =
+            serde_json::from_str(r#"{"image_url": "not a url", "quantity": null}"#).unwrap();
+        assert_eq!(patch.quantity, Some(None));
+        assert!(patch.validate().is_err());
+    }
+
+    fn create(code: &str, name: Option<String>) -> CreateProductPayload {
+        CreateProductPayload {
+            code: code.to_string(),
+            product_name: name,
+            ingredients_text: None,
+            ingredients: None,
+            brands: None,
+            categories: None,
+            image_url: None,
+            ecoscore_grade: None,
+            ecoscore_score: None,
+            nutriments: None,
+        }
+    }
+
+    fn update() -> UpdateProductPayload {
+        serde_json::from_str("{}").unwrap()
+    }
+
+    #[test]
+    fn create_payloads_need_a_clean_code_and_a_name_with_text() {
+        assert!(create("4000417025005", None).validate().is_ok());
+        for code in ["", " 4000417025005", "4000417025005\n", &"4".repeat(65)] {
+            assert!(create(code, None).validate().is_err(), "{:?}", code);
+        }
+        for name in ["", "   ", "\t\n"] {
+            let errors = create("123", Some(name.to_string()))
+                .validate()
+                .unwrap_err();
+            assert!(errors.field_errors().contains_key("product_name"));
+        }
+    }
+
+    #[test]
+    fn lengths_count_characters_not_bytes() {
+        // "Ü" takes two bytes, "🍫" four: the limits still count one each.
+        let name = |n| Some("Ü".repeat(n));
+        assert!(create("123", name(512)).validate().is_ok());
+        assert!(create("123", name(513)).validate().is_err());
+        assert!(create(&"🍫".repeat(64), None).validate().is_ok());
+        assert!(create(&"🍫".repeat(65), None).validate().is_err());
+
+        let brands = |tag: String| CreateProductPayload {
+            brands: Some(vec!["Milka".to_string(), tag]),
+            ..create("123", None)
+        };
+        assert!(brands("ü".repeat(MAX_TAG_CHARS)).validate().is_ok());
+        let errors = brands("ü".repeat(MAX_TAG_CHARS + 1))
+            .validate()
+            .unwrap_err();
+        assert!(errors.field_errors().contains_key("brands"));
+    }
+
+    #[test]
+    fn tag_lists_are_capped() {
+        let too_many = |n: usize| (0..n).map(|i| format!("en:tag-{}", i)).collect::<Vec<_>>();
+        let payload = CreateProductPayload {
+            brands: Some(too_many(50)),
+            categories: Some(too_many(100)),
+            ..create("123", None)
+        };
+        assert!(payload.validate().is_ok());
+        let payload = CreateProductPayload {
+            brands: Some(too_many(51)),
+            ..create("123", None)
+        };
+        assert!(payload.validate().is_err());
+
+        let payload = UpdateProductPayload {
+            traces: Some(too_many(51)),
+            ..update()
+        };
+        assert!(
+            payload
+                .validate()
+                .unwrap_err()
+                .field_errors()
+                .contains_key("traces")
+        );
+        let payload = UpdateProductPayload {
+            labels: Some(vec!["x".repeat(MAX_TAG_CHARS + 1)]),
+            ..update()
+        };
+        assert!(
+            payload
+                .validate()
+                .unwrap_err()
+                .field_errors()
+                .contains_key("labels")
+        );
+    }
+
+    #[test]
+    fn nutriments_must_be_plausible_amounts() {
+        let with_sugars = |sugars: f64| UpdateProductPayload {
+            nutriments: Some(Nutriments {
+                sugars_100g: Some(sugars),
+                ..Default::default()
+            }),
+            ..update()
+        };
+        assert!(with_sugars(56.3).validate().is_ok());
+        for sugars in [-1.0, 100.5] {
+            let errors = with_sugars(sugars).validate().unwrap_err();
+            assert!(
+                errors.errors().contains_key("nutriments"),
+                "{}: {:?}",
+                sugars,
+                errors
+            );
+        }
+    }
+
+    #[test]
+    fn updates_and_patches_refuse_blank_names() {
+        assert!(update().validate().is_ok());
+        let payload = UpdateProductPayload {
+            product_name: Some(" ".to_string()),
+            generic_name: Some("Ü".repeat(513)),
+            ..update()
+        };
+        let errors = payload.validate().unwrap_err();
+        assert!(errors.field_errors().contains_key("product_name"));
+        assert!(errors.field_errors().contains_key("generic_name"));
+
+        let patch: PatchProductPayload =
+            serde_json::from_str(r#"{"product_name": " ", "generic_name": null}"#).unwrap();
+        assert!(patch.validate().is_err());
+    }
+
+    #[test]
+    fn recommendation_params_reject_nonsense() {
+        let params = |limit, min_score| RecommendationParams {
+            limit,
+            min_score,
+            allergen_mode: None,
+        };
+        assert!(params(None, None).validate().is_ok());
+        assert!(params(Some(500), Some(0.0)).validate().is_ok());
+        assert!(params(Some(1), Some(1.0)).validate().is_ok());
+        assert!(params(Some(0), None).validate().is_err());
+        assert!(params(None, Some(-0.1)).validate().is_err());
+        assert!(params(None, Some(1.5)).validate().is_err());
+    }
+
+    #[test]
+    fn products_without_a_name_or_an_image_are_blank() {
+        let mut product: Product = ProductFixture::new("123").unnamed().build();
+        assert!(is_blank(&product));
+        product.image_small_url = Some(String::new());
+        assert!(is_blank(&product));
+        product.image_small_url = Some("https://images.example/small.jpg".to_string());
+        assert!(!is_blank(&product));
+        assert!(!is_blank(&ProductFixture::new("123").build()));
+    }
+
+    #[test]
+    fn name_tokens_ignore_case_spacing_and_punctuation() {
+        assert_eq!(
+            name_tokens("  Ritter SPORT - Alpenmilch,100g "),
+            ["ritter", "sport", "alpenmilch", "100g"]
+        );
+        assert_eq!(name_tokens("Crème brûlée"), ["crème", "brûlée"]);
+        assert_eq!(name_tokens("Nutella"), name_tokens("nutella!"));
+        assert!(name_tokens(" -- ").is_empty());
+    }
+
+    #[test]
+    fn change_entries_are_tagged_by_their_change() {
+        let product: Product = ProductFixture::new("4000417025005").build();
+        let modified = serde_json::to_value(ChangeEntry::Modified(Box::new(product))).unwrap();
+        assert_eq!(modified["change"], "modified");
+        assert_eq!(modified["code"], "4000417025005");
+        assert_eq!(modified["last_modified_datetime"], FIXTURE_TIME);
+
+        let id = ObjectId::parse_str("65f0c0ffee0000000000beef").unwrap();
+        let deleted_at = DateTime::parse_from_rfc3339(FIXTURE_TIME)
+            .unwrap()
+            .with_timezone(&Utc);
+        let tombstone = ChangeEntry::Deleted(Tombstone {
+            id,
+            code: "4000417025005".to_string(),
+            deleted_at,
+        });
+        assert_eq!(tombstone.position(), (deleted_at, Some(id)));
+        assert_eq!(
+            serde_json::to_value(tombstone).unwrap(),
+            serde_json::json!({
+                "change": "deleted",
+                "_id": { "$oid": "65f0c0ffee0000000000beef" },
+                "code": "4000417025005",
+                "deleted_datetime": FIXTURE_TIME,
+            })
+        );
+    }
 }
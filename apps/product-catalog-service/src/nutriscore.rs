@@ -0,0 +1,603 @@
+//! The Nutri-Score, computed from a product's [`Nutriments`] for the many imported
+//! products that come without OpenFoodFacts' `nutrition_grade_fr`, and
+//! `/api/v1/products/{id}/nutriscore`, which shows how a product's score comes about.
+//!
+//! This is the 2017 algorithm OpenFoodFacts' grades were made with: negative points for
+//! energy, sugars, saturated fat and sodium, less positive points for fruit, vegetables
+//! and nuts, fiber and protein, with the tables and grade bands of the beverage variant
+//! for drinks. Protein only counts when the negative points are below 11, when the fruit
+//! and vegetables earn full points, or for cheese. Plain water is always an A.
+
+use crate::{
+    errors::{Result, ServiceError},
+    handlers::find_product_by_id,
+    models::{Nutriments, Product},
+    state::AppState,
+};
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use serde::Serialize;
+use std::{fmt, sync::Arc};
+use thiserror::Error;
+use tracing::{debug, instrument};
+use utoipa::ToSchema;
+use yoloeats_domain::ErrorBody;
+
+/// The `nutrition_grade_source` of a grade computed here rather than declared by the
+/// product's source.
+pub const COMPUTED_GRADE_SOURCE: &str = "computed";
+
+/// Energy from kilocalories, when only those are known.
+const KJ_PER_KCAL: f64 = 4.184;
+
+// Each value earns a point for every threshold it is above.
+const ENERGY_KJ: [f64; 10] = [
+    335.0, 670.0, 1005.0, 1340.0, 1675.0, 2010.0, 2345.0, 2680.0, 3015.0, 3350.0,
+];
+const SUGARS_G: [f64; 10] = [4.5, 9.0, 13.5, 18.0, 22.5, 27.0, 31.0, 36.0, 40.0, 45.0];
+const SATURATED_FAT_G: [f64; 10] = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0];
+const SODIUM_MG: [f64; 10] = [
+    90.0, 180.0, 270.0, 360.0, 450.0, 540.0, 630.0, 720.0, 810.0, 900.0,
+];
+const FIBER_G: [f64; 5] = [0.9, 1.9, 2.8, 3.7, 4.7];
+const PROTEINS_G: [f64; 5] = [1.6, 3.2, 4.8, 6.4, 8.0];
+const BEVERAGE_ENERGY_KJ: [f64; 10] = [
+    0.0, 30.0, 60.0, 90.0, 120.0, 150.0, 180.0, 210.0, 240.0, 270.0,
+];
+const BEVERAGE_SUGARS_G: [f64; 10] = [0.0, 1.5, 3.0, 4.5, 6.0, 7.5, 9.0, 10.5, 12.0, 13.5];
+/// Fruit, vegetables and nuts in percent; the points for being above each are in
+/// [`FoodKind::fruit_points`].
+const FRUITS_VEGETABLES_NUTS_PERCENT: [f64; 3] = [40.0, 60.0, 80.0];
+
+/// Negative points from which protein no longer counts, unless the fruit and vegetables
+/// earn full points or the product is a cheese.
+const PROTEIN_CUTOFF: u8 = 11;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum NutriScoreGrade {
+    A,
+    B,
+    C,
+    D,
+    E,
+}
+
+impl NutriScoreGrade {
+    /// The grade as `nutrition_grade_fr` stores it: a lowercase letter.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NutriScoreGrade::A => "a",
+            NutriScoreGrade::B => "b",
+            NutriScoreGrade::C => "c",
+            NutriScoreGrade::D => "d",
+            NutriScoreGrade::E => "e",
+        }
+    }
+}
+
+/// Which of the algorithm's variants applies, from the product's categories.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FoodKind {
+    General,
+    /// Protein always counts.
+    Cheese,
+    /// Drinks other than milk, with their own tables and grade bands.
+    Beverage,
+    /// Plain water, always graded A.
+    Water,
+}
+
+impl FoodKind {
+    /// The kind `categories_tags` name: water and other beverages, dairy drinks aside,
+    /// cheese, or anything else.
+    pub fn from_categories(categories: &[String]) -> FoodKind {
+        let has = |tag: &str| categories.iter().any(|category| category == tag);
+        if has("en:cheeses") {
+            FoodKind::Cheese
+        } else if has("en:waters") && !has("en:flavoured-waters") && !has("en:flavored-waters") {
+            FoodKind::Water
+        } else if has("en:beverages") && !has("en:dairies") {
+            FoodKind::Beverage
+        } else {
+            FoodKind::General
+        }
+    }
+
+    fn is_beverage(self) -> bool {
+        matches!(self, FoodKind::Beverage | FoodKind::Water)
+    }
+
+    /// Points for fruit, vegetables and nuts above none, one, two or all of
+    /// [`FRUITS_VEGETABLES_NUTS_PERCENT`].
+    fn fruit_points(self) -> [u8; 4] {
+        if self.is_beverage() {
+            [0, 2, 4, 10]
+        } else {
+            [0, 1, 2, 5]
+        }
+    }
+
+    fn grade(self, score: i32) -> NutriScoreGrade {
+        match self {
+            FoodKind::Water => NutriScoreGrade::A,
+            FoodKind::Beverage => match score {
+                ..=1 => NutriScoreGrade::B,
+                2..=5 => NutriScoreGrade::C,
+                6..=9 => NutriScoreGrade::D,
+                _ => NutriScoreGrade::E,
+            },
+            FoodKind::General | FoodKind::Cheese => match score {
+                ..=-1 => NutriScoreGrade::A,
+                0..=2 => NutriScoreGrade::B,
+                3..=10 => NutriScoreGrade::C,
+                11..=18 => NutriScoreGrade::D,
+                _ => NutriScoreGrade::E,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Nutrient {
+    Energy,
+    Sugars,
+    SaturatedFat,
+    Sodium,
+    FruitsVegetablesNuts,
+    Fiber,
+    Proteins,
+}
+
+impl Nutrient {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Nutrient::Energy => "energy",
+            Nutrient::Sugars => "sugars",
+            Nutrient::SaturatedFat => "saturated_fat",
+            Nutrient::Sodium => "sodium",
+            Nutrient::FruitsVegetablesNuts => "fruits_vegetables_nuts",
+            Nutrient::Fiber => "fiber",
+            Nutrient::Proteins => "proteins",
+        }
+    }
+
+    /// The unit [`ScoreComponent::value`] is in.
+    pub fn unit(self) -> &'static str {
+        match self {
+            Nutrient::Energy => "kJ",
+            Nutrient::Sodium => "mg",
+            Nutrient::FruitsVegetablesNuts => "%",
+            Nutrient::Sugars | Nutrient::SaturatedFat | Nutrient::Fiber | Nutrient::Proteins => "g",
+        }
+    }
+}
+
+impl fmt::Display for Nutrient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The nutriments a score can't do without, none of which the product has.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Missing {}", .0.iter().map(|n| n.as_str()).collect::<Vec<_>>().join(", "))]
+pub struct MissingNutriments(pub Vec<Nutrient>);
+
+/// What one nutrient adds to the score.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct ScoreComponent {
+    pub nutrient: Nutrient,
+    /// Per 100 g, or 100 ml for beverages; 0 when unknown, for the optional ones.
+    pub value: f64,
+    /// `kJ`, `g`, `mg` or `%`.
+    pub unit: String,
+    pub points: u8,
+    pub max_points: u8,
+    /// `false` for protein when the rules leave it out of the score.
+    pub counted: bool,
+}
+
+/// A computed Nutri-Score and the points it is made of.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct NutriScore {
+    pub grade: NutriScoreGrade,
+    /// The negative points less the counted positive ones; the lower, the better.
+    pub score: i32,
+    pub kind: FoodKind,
+    pub negative_points: u8,
+    pub positive_points: u8,
+    /// Energy, sugars, saturated fat and sodium.
+    pub negative: Vec<ScoreComponent>,
+    /// Fruit, vegetables and nuts, fiber and protein.
+    pub positive: Vec<ScoreComponent>,
+}
+
+fn above(value: f64, thresholds: &[f64]) -> u8 {
+    thresholds
+        .iter()
+        .filter(|threshold| value > **threshold)
+        .count() as u8
+}
+
+fn component(nutrient: Nutrient, value: f64, thresholds: &[f64]) -> ScoreComponent {
+    ScoreComponent {
+        nutrient,
+        value,
+        unit: nutrient.unit().to_string(),
+        points: above(value, thresholds),
+        max_points: thresholds.len() as u8,
+        counted: true,
+    }
+}
+
+/// The score of `nutriments` as a product of `kind`. Energy (in kJ or kcal), sugars,
+/// saturated fat and sodium (or salt) are needed; unknown fruit, vegetables and nuts,
+/// fiber or protein earn no points.
+pub fn compute(
+    nutriments: &Nutriments,
+    kind: FoodKind,
+) -> std::result::Result<NutriScore, MissingNutriments> {
+    let energy_kj = nutriments
+        .energy_kj_100g
+        .or(nutriments.energy_kcal_100g.map(|kcal| kcal * KJ_PER_KCAL));
+    let sodium_mg = nutriments
+        .sodium_100g
+        .or(nutriments.salt_100g.map(|salt| salt / 2.5))
+        .map(|grams| grams * 1000.0);
+    let required = [
+        (Nutrient::Energy, energy_kj),
+        (Nutrient::Sugars, nutriments.sugars_100g),
+        (Nutrient::SaturatedFat, nutriments.saturated_fat_100g),
+        (Nutrient::Sodium, sodium_mg),
+    ];
+    let missing: Vec<Nutrient> = required
+        .iter()
+        .filter(|(_, value)| value.is_none())
+        .map(|(nutrient, _)| *nutrient)
+        .collect();
+    if !missing.is_empty() {
+        return Err(MissingNutriments(missing));
+    }
+    let [energy, sugars, saturated_fat, sodium] = required.map(|(_, value)| value.unwrap_or(0.0));
+
+    let (energy_table, sugars_table) = if kind.is_beverage() {
+        (&BEVERAGE_ENERGY_KJ, &BEVERAGE_SUGARS_G)
+    } else {
+        (&ENERGY_KJ, &SUGARS_G)
+    };
+    let negative = vec![
+        component(Nutrient::Energy, energy, energy_table),
+        component(Nutrient::Sugars, sugars, sugars_table),
+        component(Nutrient::SaturatedFat, saturated_fat, &SATURATED_FAT_G),
+        component(Nutrient::Sodium, sodium, &SODIUM_MG),
+    ];
+    let negative_points: u8 = negative.iter().map(|c| c.points).sum();
+
+    let fruit_points = kind.fruit_points();
+    let mut fruit = component(
+        Nutrient::FruitsVegetablesNuts,
+        nutriments.fruits_vegetables_nuts_100g.unwrap_or(0.0),
+        &FRUITS_VEGETABLES_NUTS_PERCENT,
+    );
+    fruit.points = fruit_points[fruit.points as usize];
+    fruit.max_points = fruit_points[FRUITS_VEGETABLES_NUTS_PERCENT.len()];
+    let mut proteins = component(
+        Nutrient::Proteins,
+        nutriments.proteins_100g.unwrap_or(0.0),
+        &PROTEINS_G,
+    );
+    proteins.counted = negative_points < PROTEIN_CUTOFF
+        || kind == FoodKind::Cheese
+        || fruit.points == fruit.max_points;
+    let positive = vec![
+        fruit,
+        component(
+            Nutrient::Fiber,
+            nutriments.fiber_100g.unwrap_or(0.0),
+            &FIBER_G,
+        ),
+        proteins,
+    ];
+    let positive_points: u8 = positive
+        .iter()
+        .filter(|c| c.counted)
+        .map(|c| c.points)
+        .sum();
+
+    let score = i32::from(negative_points) - i32::from(positive_points);
+    Ok(NutriScore {
+        grade: kind.grade(score),
+        score,
+        kind,
+        negative_points,
+        positive_points,
+        negative,
+        positive,
+    })
+}
+
+/// The score of `product`'s nutriments, as the kind its categories name.
+pub fn for_product(product: &Product) -> std::result::Result<NutriScore, MissingNutriments> {
+    let kind = FoodKind::from_categories(product.categories.as_deref().unwrap_or_default());
+    compute(
+        product
+            .nutriments
+            .as_ref()
+            .unwrap_or(&Nutriments::default()),
+        kind,
+    )
+}
+
+/// Gives a product without a `nutrition_grade_fr` the grade its nutriments score,
+/// marked as [`COMPUTED_GRADE_SOURCE`]. Products with a grade, and those whose
+/// nutriments fall short, are left as they are.
+pub fn fill_missing_grade(product: &mut Product) {
+    if product.nutrition_grade_fr.is_some() || product.nutriments.is_none() {
+        return;
+    }
+    match for_product(product) {
+        Ok(score) => {
+            product.nutrition_grade_fr = Some(score.grade.as_str().to_string());
+            product.nutrition_grade_source = Some(COMPUTED_GRADE_SOURCE.to_string());
+        }
+        Err(missing) => debug!(code = %product.code, "No Nutri-Score computed: {}", missing),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/products/{id}/nutriscore",
+    tag = "v1",
+    params(("id" = String, Path, description = "The product's ObjectId.")),
+    responses(
+        (status = 200, description = "The Nutri-Score the product's nutriments score, point by point. It may differ from a declared `nutrition_grade_fr`.", body = NutriScore),
+        (status = 400, description = "An invalid id.", body = ErrorBody),
+        (status = 404, description = "No product with this id, or it lacks the nutriments a score needs.", body = ErrorBody),
+        (status = 500, description = "MongoDB or Redis failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state), fields(product_id = %id_str))]
+pub async fn get_nutriscore(
+    State(state): State<Arc<AppState>>,
+    Path(id_str): Path<String>,
+) -> Result<Json<NutriScore>> {
+    let product = find_product_by_id(&state, &id_str).await?;
+    for_product(&product).map(Json).map_err(|missing| {
+        ServiceError::NotFound(format!(
+            "No Nutri-Score for product {}: {}",
+            id_str, missing
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yoloeats_domain::fixtures::ProductFixture;
+
+    fn nutriments(
+        energy_kcal: f64,
+        sugars: f64,
+        saturated_fat: f64,
+        salt: f64,
+        fiber: Option<f64>,
+        proteins: f64,
+    ) -> Nutriments {
+        Nutriments {
+            energy_kcal_100g: Some(energy_kcal),
+            sugars_100g: Some(sugars),
+            saturated_fat_100g: Some(saturated_fat),
+            salt_100g: Some(salt),
+            fiber_100g: fiber,
+            proteins_100g: Some(proteins),
+            ..Nutriments::default()
+        }
+    }
+
+    fn points(components: &[ScoreComponent]) -> Vec<(Nutrient, u8)> {
+        components.iter().map(|c| (c.nutrient, c.points)).collect()
+    }
+
+    fn categories(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    #[test]
+    fn hazelnut_spread_is_an_e() {
+        // Nutella: 539 kcal, 56.3 g sugars, 10.6 g saturated fat, 0.107 g salt.
+        let score = compute(
+            &nutriments(539.0, 56.3, 10.6, 0.107, None, 6.3),
+            FoodKind::General,
+        )
+        .unwrap();
+        assert_eq!(
+            points(&score.negative),
+            [
+                (Nutrient::Energy, 6),
+                (Nutrient::Sugars, 10),
+                (Nutrient::SaturatedFat, 10),
+                (Nutrient::Sodium, 0),
+            ]
+        );
+        assert_eq!(score.negative_points, 26);
+        let proteins = &score.positive[2];
+        assert_eq!((proteins.points, proteins.counted), (3, false));
+        assert_eq!(score.positive_points, 0);
+        assert_eq!((score.score, score.grade), (26, NutriScoreGrade::E));
+    }
+
+    #[test]
+    fn rolled_oats_are_an_a() {
+        let score = compute(
+            &nutriments(372.0, 1.1, 1.3, 0.01, Some(10.0), 13.5),
+            FoodKind::General,
+        )
+        .unwrap();
+        assert_eq!(score.negative_points, 5);
+        assert_eq!(
+            points(&score.positive),
+            [
+                (Nutrient::FruitsVegetablesNuts, 0),
+                (Nutrient::Fiber, 5),
+                (Nutrient::Proteins, 5),
+            ]
+        );
+        assert_eq!((score.score, score.grade), (-5, NutriScoreGrade::A));
+    }
+
+    #[test]
+    fn cheese_keeps_its_protein_points() {
+        // Camembert: 289 kcal, 0.5 g sugars, 14 g saturated fat, 1.6 g salt, 20 g protein.
+        let camembert = nutriments(289.0, 0.5, 14.0, 1.6, None, 20.0);
+        let as_cheese = compute(&camembert, FoodKind::Cheese).unwrap();
+        assert_eq!(as_cheese.negative_points, 20);
+        assert_eq!((as_cheese.score, as_cheese.grade), (15, NutriScoreGrade::D));
+
+        let as_general = compute(&camembert, FoodKind::General).unwrap();
+        assert_eq!(
+            (as_general.score, as_general.grade),
+            (20, NutriScoreGrade::E)
+        );
+    }
+
+    #[test]
+    fn plenty_of_fruit_keeps_the_protein_points() {
+        // A fruit compote past the negative points cutoff.
+        let mut compote = nutriments(300.0, 40.0, 0.5, 0.0, Some(1.0), 2.0);
+        compote.fruits_vegetables_nuts_100g = Some(85.0);
+        let score = compute(&compote, FoodKind::General).unwrap();
+        assert_eq!(score.negative_points, 11);
+        assert_eq!(
+            points(&score.positive),
+            [
+                (Nutrient::FruitsVegetablesNuts, 5),
+                (Nutrient::Fiber, 1),
+                (Nutrient::Proteins, 1),
+            ]
+        );
+        assert!(score.positive.iter().all(|c| c.counted));
+        assert_eq!((score.score, score.grade), (4, NutriScoreGrade::C));
+    }
+
+    #[test]
+    fn cola_is_an_e_among_beverages() {
+        // 42 kcal (176 kJ) and 10.6 g sugars per 100 ml.
+        let score = compute(
+            &nutriments(42.0, 10.6, 0.0, 0.0, None, 0.0),
+            FoodKind::Beverage,
+        )
+        .unwrap();
+        assert_eq!(
+            points(&score.negative),
+            [
+                (Nutrient::Energy, 6),
+                (Nutrient::Sugars, 8),
+                (Nutrient::SaturatedFat, 0),
+                (Nutrient::Sodium, 0),
+            ]
+        );
+        assert_eq!((score.score, score.grade), (14, NutriScoreGrade::E));
+    }
+
+    #[test]
+    fn pure_orange_juice_is_a_c() {
+        let mut juice = nutriments(45.0, 8.9, 0.0, 0.0, Some(0.2), 0.7);
+        juice.energy_kj_100g = Some(188.0);
+        juice.fruits_vegetables_nuts_100g = Some(100.0);
+        let score = compute(&juice, FoodKind::Beverage).unwrap();
+        assert_eq!(score.negative_points, 13);
+        assert_eq!(score.positive[0].points, 10);
+        assert_eq!((score.score, score.grade), (3, NutriScoreGrade::C));
+    }
+
+    #[test]
+    fn water_is_always_an_a() {
+        let score = compute(&nutriments(0.0, 0.0, 0.0, 0.01, None, 0.0), FoodKind::Water).unwrap();
+        assert_eq!(score.grade, NutriScoreGrade::A);
+    }
+
+    #[test]
+    fn values_on_a_threshold_earn_nothing_for_it() {
+        let mut on_thresholds = nutriments(0.0, 4.5, 1.0, 0.0, Some(0.9), 1.6);
+        on_thresholds.energy_kj_100g = Some(335.0);
+        on_thresholds.sodium_100g = Some(0.09);
+        let score = compute(&on_thresholds, FoodKind::General).unwrap();
+        assert_eq!((score.negative_points, score.positive_points), (0, 0));
+        assert_eq!(score.grade, NutriScoreGrade::B);
+    }
+
+    #[test]
+    fn sodium_and_kilojoules_win_over_salt_and_kilocalories() {
+        let mut both = nutriments(1000.0, 0.0, 0.0, 10.0, None, 0.0);
+        both.energy_kj_100g = Some(100.0);
+        both.sodium_100g = Some(0.1);
+        let score = compute(&both, FoodKind::General).unwrap();
+        assert_eq!(score.negative[0].value, 100.0);
+        assert_eq!(score.negative[3].value, 100.0);
+        assert_eq!(score.negative[3].unit, "mg");
+        assert_eq!(score.negative_points, 1);
+    }
+
+    #[test]
+    fn required_nutriments_must_be_known() {
+        let partial = Nutriments {
+            energy_kcal_100g: Some(100.0),
+            sugars_100g: Some(5.0),
+            ..Nutriments::default()
+        };
+        let missing = compute(&partial, FoodKind::General).unwrap_err();
+        assert_eq!(missing.0, [Nutrient::SaturatedFat, Nutrient::Sodium]);
+        assert_eq!(missing.to_string(), "Missing saturated_fat, sodium");
+    }
+
+    #[test]
+    fn categories_choose_the_variant() {
+        let kind = |tags: &[&str]| FoodKind::from_categories(&categories(tags));
+        assert_eq!(kind(&["en:beverages", "en:sodas"]), FoodKind::Beverage);
+        assert_eq!(kind(&["en:beverages", "en:waters"]), FoodKind::Water);
+        assert_eq!(
+            kind(&["en:beverages", "en:waters", "en:flavoured-waters"]),
+            FoodKind::Beverage
+        );
+        assert_eq!(
+            kind(&["en:dairies", "en:beverages", "en:milks"]),
+            FoodKind::General
+        );
+        assert_eq!(kind(&["en:dairies", "en:cheeses"]), FoodKind::Cheese);
+        assert_eq!(kind(&["en:spreads"]), FoodKind::General);
+        assert_eq!(kind(&[]), FoodKind::General);
+    }
+
+    #[test]
+    fn only_products_without_a_grade_get_one_computed() {
+        let mut product = ProductFixture::new("3017620422003").build::<Product>();
+        product.categories = Some(categories(&["en:spreads"]));
+        product.nutriments = Some(nutriments(539.0, 56.3, 10.6, 0.107, None, 6.3));
+        fill_missing_grade(&mut product);
+        assert_eq!(product.nutrition_grade_fr.as_deref(), Some("e"));
+        assert_eq!(
+            product.nutrition_grade_source.as_deref(),
+            Some(COMPUTED_GRADE_SOURCE)
+        );
+
+        let mut declared = ProductFixture::new("3017620422003").build::<Product>();
+        declared.nutrition_grade_fr = Some("d".to_string());
+        declared.nutriments = product.nutriments.clone();
+        fill_missing_grade(&mut declared);
+        assert_eq!(declared.nutrition_grade_fr.as_deref(), Some("d"));
+        assert_eq!(declared.nutrition_grade_source, None);
+
+        let mut sparse = ProductFixture::new("3017620422003").build::<Product>();
+        sparse.nutriments = Some(Nutriments {
+            sugars_100g: Some(56.3),
+            ..Nutriments::default()
+        });
+        fill_missing_grade(&mut sparse);
+        assert_eq!(sparse.nutrition_grade_fr, None);
+    }
+}
@@ -0,0 +1,521 @@
+//! OpenFoodFacts row -> catalog [`Product`], shared by the `off-import` CLI and
+//! `POST /api/v1/products/import`.
+//!
+//! JSONL rows carry tag lists as arrays, CSV rows as comma-separated strings, and both
+//! spell some fields differently; every accessor below accepts either shape and a list
+//! of fallback names, in order of preference.
+
+use crate::{
+    models::{Nutriments, Product, ecoscore_grade},
+    nutriscore,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use yoloeats_domain::{
+    IngredientEntry,
+    tags::{extract_allergen_tags, normalize_tag, normalize_tags},
+};
+
+pub const SOURCE: &str = "openfoodfacts";
+
+/// One dump row as field name -> value, whatever the dump format. CSV cells are all
+/// strings; the mapping accepts both shapes.
+pub type RawRow = Map<String, Value>;
+
+/// Parses one JSONL dump line; the error is the reject reason.
+pub fn parse_jsonl(line: &str) -> Result<RawRow, String> {
+    match serde_json::from_str::<Value>(line) {
+        Ok(Value::Object(row)) => Ok(row),
+        Ok(_) => Err("line is not a JSON object".to_string()),
+        Err(e) => Err(format!("invalid JSON: {}", e)),
+    }
+}
+
+/// Which rows are worth importing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    /// A row must carry at least one of these `countries_tags`; empty accepts all.
+    pub countries: Vec<String>,
+    /// Minimum OFF `completeness` score (0.0-1.0). Rows without a score count as 0.
+    pub min_completeness: f64,
+}
+
+impl Filter {
+    /// Keeps every row with a usable code.
+    pub fn everything() -> Self {
+        Filter {
+            countries: Vec::new(),
+            min_completeness: 0.0,
+        }
+    }
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter {
+            countries: vec!["en:germany".to_string()],
+            min_completeness: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    Country,
+    Incomplete,
+}
+
+/// Filtered-out rows are skipped silently; only rows we wanted but couldn't map are
+/// rejected, so the rejects file isn't drowned in other countries' data.
+#[derive(Debug, Clone)]
+pub enum Mapped {
+    Product(Box<Product>),
+    Skipped(SkipReason),
+    Rejected(String),
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// The first of `names` holding a non-blank string or a number.
+fn text(row: &RawRow, names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .find_map(|name| row.get(*name).and_then(scalar))
+}
+
+/// The localized names, `product_name_de` and the like, by language code. `None` if
+/// there are none.
+fn product_names(row: &RawRow) -> Option<HashMap<String, String>> {
+    let names: HashMap<String, String> = row
+        .iter()
+        .filter_map(|(key, value)| {
+            let language = key.strip_prefix("product_name_")?;
+            let code = language.len() == 2 && language.chars().all(|c| c.is_ascii_lowercase());
+            Some((language.to_string(), scalar(value)?)).filter(|_| code)
+        })
+        .collect();
+    if names.is_empty() { None } else { Some(names) }
+}
+
+/// The first of `names` that is present, as normalized tags.
+fn tags(row: &RawRow, names: &[&str]) -> Vec<String> {
+    let Some(value) = names.iter().find_map(|name| row.get(*name)) else {
+        return Vec::new();
+    };
+    match value {
+        Value::Array(items) => normalize_tags(items.iter().filter_map(scalar)),
+        Value::String(list) => normalize_tags(list.split(',')),
+        _ => Vec::new(),
+    }
+}
+
+fn non_empty(tags: Vec<String>) -> Option<Vec<String>> {
+    if tags.is_empty() { None } else { Some(tags) }
+}
+
+fn number(row: &RawRow, name: &str) -> Option<f64> {
+    match row.get(name)? {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn timestamp(row: &RawRow, name: &str) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(number(row, name)? as i64, 0)
+}
+
+/// Nutri-Score letters only; OFF also writes `unknown` and `not-applicable`.
+fn nutrition_grade(row: &RawRow) -> Option<String> {
+    text(
+        row,
+        &["nutrition_grade_fr", "nutriscore_grade", "nutrition_grades"],
+    )
+    .map(|grade| grade.to_ascii_lowercase())
+    .filter(|grade| matches!(grade.as_str(), "a" | "b" | "c" | "d" | "e"))
+}
+
+/// Eco-Score letters only; OFF also writes `unknown` and `not-applicable` here.
+fn ecoscore(row: &RawRow) -> Option<String> {
+    text(row, &["ecoscore_grade"]).and_then(|grade| ecoscore_grade(&grade))
+}
+
+/// The structured list JSONL rows carry; CSV rows only have the text. Entries that
+/// aren't objects are skipped.
+fn ingredients(row: &RawRow) -> Option<Vec<IngredientEntry>> {
+    let Some(Value::Array(entries)) = row.get("ingredients") else {
+        return None;
+    };
+    let entries: Vec<IngredientEntry> = entries
+        .iter()
+        .filter(|entry| entry.is_object())
+        .filter_map(|entry| IngredientEntry::deserialize(entry).ok())
+        .collect();
+    if entries.is_empty() {
+        None
+    } else {
+        Some(entries)
+    }
+}
+
+/// JSONL rows nest the per-100 g values in `nutriments`; CSV rows have them as columns
+/// of the same names.
+fn nutriments(row: &RawRow) -> Option<Nutriments> {
+    let values = match row.get("nutriments") {
+        Some(Value::Object(nutriments)) => nutriments,
+        _ => row,
+    };
+    Nutriments {
+        energy_kcal_100g: number(values, "energy-kcal_100g"),
+        energy_kj_100g: number(values, "energy-kj_100g"),
+        sugars_100g: number(values, "sugars_100g"),
+        salt_100g: number(values, "salt_100g"),
+        sodium_100g: number(values, "sodium_100g"),
+        fat_100g: number(values, "fat_100g"),
+        saturated_fat_100g: number(values, "saturated-fat_100g"),
+        proteins_100g: number(values, "proteins_100g"),
+        fiber_100g: number(values, "fiber_100g"),
+        // OFF estimates the share from the ingredients when the label doesn't give it.
+        fruits_vegetables_nuts_100g: number(values, "fruits-vegetables-nuts_100g").or_else(|| {
+            number(
+                values,
+                "fruits-vegetables-nuts-estimate-from-ingredients_100g",
+            )
+        }),
+    }
+    .non_empty()
+}
+
+/// `imported_at` stands in for missing OFF timestamps. Rows without a Nutri-Score get
+/// the one their nutriments score, if they have enough.
+pub fn map_row(row: &RawRow, filter: &Filter, imported_at: DateTime<Utc>) -> Mapped {
+    let countries = tags(row, &["countries_tags"]);
+    if !filter.countries.is_empty() && !countries.iter().any(|c| filter.countries.contains(c)) {
+        return Mapped::Skipped(SkipReason::Country);
+    }
+    if number(row, "completeness").unwrap_or(0.0) < filter.min_completeness {
+        return Mapped::Skipped(SkipReason::Incomplete);
+    }
+
+    let Some(code) = text(row, &["code"]) else {
+        return Mapped::Rejected("missing code".to_string());
+    };
+    if !code.chars().all(|c| c.is_ascii_digit()) {
+        return Mapped::Rejected(format!("code '{}' is not numeric", code));
+    }
+
+    let categories = tags(row, &["categories_tags"]);
+    // OFF's main category is the most specific one, i.e. the last in the hierarchy.
+    let main_category = text(row, &["main_category"])
+        .and_then(|c| normalize_tag(&c))
+        .or_else(|| categories.last().cloned());
+    let created_at = timestamp(row, "created_t").unwrap_or(imported_at);
+
+    let mut product = Product {
+        id: None,
+        code,
+        product_name: text(row, &["product_name", "product_name_de", "product_name_en"]),
+        product_name_langs: product_names(row),
+        generic_name: text(row, &["generic_name", "generic_name_de", "generic_name_en"]),
+        brands: non_empty(tags(row, &["brands_tags", "brands"])),
+        categories: non_empty(categories),
+        main_category,
+        labels: non_empty(tags(row, &["labels_tags"])),
+        ingredients_text: text(
+            row,
+            &[
+                "ingredients_text",
+                "ingredients_text_de",
+                "ingredients_text_en",
+            ],
+        ),
+        ingredients: ingredients(row),
+        traces_tags: non_empty(tags(row, &["traces_tags", "traces"])),
+        allergens_tags: extract_allergen_tags(tags(row, &["allergens_tags", "allergens"])),
+        quantity: text(row, &["quantity"]),
+        image_url: text(row, &["image_url", "image_front_url"]),
+        image_small_url: text(row, &["image_small_url", "image_front_small_url"]),
+        image_thumb_url: text(row, &["image_thumb_url", "image_front_thumb_url"]),
+        countries: non_empty(countries),
+        nutrition_grade_fr: nutrition_grade(row),
+        nutrition_grade_source: None,
+        ecoscore_grade: ecoscore(row),
+        ecoscore_score: number(row, "ecoscore_score"),
+        nutriments: nutriments(row),
+        creator: text(row, &["creator"]),
+        source: Some(SOURCE.to_string()),
+        scan_count: None,
+        created_at,
+        last_modified_at: timestamp(row, "last_modified_t").unwrap_or(created_at),
+    };
+    nutriscore::fill_missing_grade(&mut product);
+    Mapped::Product(Box::new(product))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// Rows captured from the public OFF dumps, trimmed to the fields we read plus a
+    /// few we don't.
+    const SAMPLE_JSONL: &str = include_str!("../fixtures/off_sample.jsonl");
+
+    fn imported_at() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()
+    }
+
+    fn jsonl_rows() -> Vec<RawRow> {
+        SAMPLE_JSONL
+            .lines()
+            .map(|line| parse_jsonl(line).unwrap())
+            .collect()
+    }
+
+    fn product(mapped: Mapped) -> Product {
+        match mapped {
+            Mapped::Product(product) => *product,
+            other => panic!("expected a product, got {:?}", other),
+        }
+    }
+
+    fn strings(values: &[&str]) -> Option<Vec<String>> {
+        Some(values.iter().map(|v| v.to_string()).collect())
+    }
+
+    #[test]
+    fn maps_a_full_jsonl_row() {
+        let rows = jsonl_rows();
+        let nutella = product(map_row(&rows[0], &Filter::default(), imported_at()));
+
+        assert_eq!(nutella.code, "3017620422003");
+        assert_eq!(nutella.product_name.as_deref(), Some("Nutella"));
+        assert_eq!(
+            nutella.product_name_langs,
+            Some(HashMap::from([("de".to_string(), "Nutella".to_string())]))
+        );
+        assert_eq!(nutella.brands, strings(&["nutella", "ferrero"]));
+        assert_eq!(
+            nutella.allergens_tags,
+            vec!["en:milk", "en:nuts", "en:soybeans"]
+        );
+        assert_eq!(nutella.labels, strings(&["en:no-gluten", "en:green-dot"]));
+        assert_eq!(
+            nutella.main_category.as_deref(),
+            Some("en:cocoa-and-hazelnuts-spreads")
+        );
+        assert_eq!(nutella.nutrition_grade_fr.as_deref(), Some("e"));
+        assert_eq!(nutella.ecoscore_grade.as_deref(), Some("d"));
+        assert_eq!(nutella.ecoscore_score, Some(31.0));
+        let nutriments = nutella.nutriments.unwrap();
+        assert_eq!(nutriments.sugars_100g, Some(56.3));
+        assert_eq!(nutriments.energy_kcal_100g, Some(539.0));
+        assert_eq!(nutriments.salt_100g, None);
+        assert_eq!(nutella.quantity.as_deref(), Some("400 g"));
+        assert_eq!(nutella.traces_tags, None);
+        assert_eq!(nutella.source.as_deref(), Some(SOURCE));
+        assert_eq!(nutella.created_at.timestamp(), 1457680652);
+        assert_eq!(nutella.last_modified_at.timestamp(), 1717430417);
+        assert!(
+            nutella
+                .ingredients_text
+                .as_deref()
+                .unwrap()
+                .starts_with("Sucre, huile de palme")
+        );
+    }
+
+    #[test]
+    fn keeps_the_name_of_every_language() {
+        let row = parse_jsonl(
+            r#"{"code": "4000417025005", "product_name": "Alpine milk chocolate",
+                "product_name_de": " Alpenmilch Schokolade ", "product_name_fr": "",
+                "product_name_it": "Cioccolato al latte", "product_name_en_imported": "x",
+                "product_name_xyz": "y"}"#,
+        )
+        .unwrap();
+        let named = product(map_row(&row, &Filter::everything(), imported_at()));
+        assert_eq!(
+            named.product_name_langs,
+            Some(HashMap::from([
+                ("de".to_string(), "Alpenmilch Schokolade".to_string()),
+                ("it".to_string(), "Cioccolato al latte".to_string()),
+            ]))
+        );
+
+        let row = parse_jsonl(r#"{"code": "4000417025005", "product_name": "Milk"}"#).unwrap();
+        let unnamed = product(map_row(&row, &Filter::everything(), imported_at()));
+        assert_eq!(unnamed.product_name_langs, None);
+    }
+
+    #[test]
+    fn falls_back_to_localized_fields_and_numeric_codes() {
+        let rows = jsonl_rows();
+        let ritter = product(map_row(&rows[1], &Filter::default(), imported_at()));
+
+        assert_eq!(ritter.code, "4000417025005");
+        assert_eq!(
+            ritter.product_name.as_deref(),
+            Some("Alpenmilch Schokolade")
+        );
+        assert_eq!(
+            ritter.product_name_langs.unwrap()["de"],
+            "Alpenmilch Schokolade"
+        );
+        assert!(ritter.ingredients_text.unwrap().starts_with("Zucker"));
+        assert_eq!(ritter.traces_tags, strings(&["en:nuts", "en:peanuts"]));
+        assert_eq!(ritter.allergens_tags, vec!["en:milk"]);
+        assert_eq!(ritter.nutrition_grade_fr, None, "'unknown' is not a grade");
+        assert_eq!(ritter.ecoscore_grade, None, "nor is 'not-applicable'");
+        assert_eq!(ritter.created_at, imported_at(), "no created_t in the row");
+        assert_eq!(ritter.last_modified_at, imported_at());
+    }
+
+    #[test]
+    fn reads_nutriments_from_csv_columns() {
+        let row = |json: Value| json.as_object().unwrap().clone();
+        let csv = row(serde_json::json!({
+            "code": "4000417025005",
+            "countries_tags": "en:germany",
+            "sugars_100g": "51",
+            "salt_100g": "0.23",
+            "proteins_100g": "",
+        }));
+        let nutriments = product(map_row(&csv, &Filter::default(), imported_at()))
+            .nutriments
+            .unwrap();
+        assert_eq!(nutriments.sugars_100g, Some(51.0));
+        assert_eq!(nutriments.salt_100g, Some(0.23));
+        assert_eq!(nutriments.proteins_100g, None);
+
+        let bare = row(serde_json::json!({ "code": "1", "countries_tags": "en:germany" }));
+        let mapped = product(map_row(&bare, &Filter::default(), imported_at()));
+        assert_eq!(mapped.nutriments, None);
+    }
+
+    #[test]
+    fn rows_without_a_grade_are_scored_from_their_nutriments() {
+        let row = |json: Value| json.as_object().unwrap().clone();
+        let nutriments = serde_json::json!({
+            "energy-kj_100g": 2255,
+            "sugars_100g": 56.3,
+            "saturated-fat_100g": 10.6,
+            "sodium_100g": 0.0428,
+            "proteins_100g": 6.3,
+            "fruits-vegetables-nuts-estimate-from-ingredients_100g": 13,
+        });
+        let ungraded = product(map_row(
+            &row(serde_json::json!({ "code": "3017620422003", "nutriments": nutriments })),
+            &Filter::everything(),
+            imported_at(),
+        ));
+        let read = ungraded.nutriments.as_ref().unwrap();
+        assert_eq!(read.energy_kj_100g, Some(2255.0));
+        assert_eq!(read.sodium_100g, Some(0.0428));
+        assert_eq!(read.fruits_vegetables_nuts_100g, Some(13.0));
+        assert_eq!(ungraded.nutrition_grade_fr.as_deref(), Some("e"));
+        assert_eq!(
+            ungraded.nutrition_grade_source.as_deref(),
+            Some(nutriscore::COMPUTED_GRADE_SOURCE)
+        );
+
+        let graded = product(map_row(
+            &row(serde_json::json!({
+                "code": "3017620422003",
+                "nutriscore_grade": "d",
+                "nutriments": nutriments,
+            })),
+            &Filter::everything(),
+            imported_at(),
+        ));
+        assert_eq!(graded.nutrition_grade_fr.as_deref(), Some("d"));
+        assert_eq!(graded.nutrition_grade_source, None);
+    }
+
+    #[test]
+    fn keeps_structured_ingredients() {
+        let row = serde_json::json!({
+            "code": "4000417025005",
+            "countries_tags": ["en:germany"],
+            "ingredients": [
+                { "id": "en:sugar", "text": "Zucker", "percent_estimate": "51.2", "rank": 1 },
+                "not an entry",
+                { "id": "en:whole-milk-powder", "vegan": "no", "vegetarian": "yes" }
+            ]
+        });
+        let mapped = product(map_row(
+            row.as_object().unwrap(),
+            &Filter::default(),
+            imported_at(),
+        ));
+        let ingredients = mapped.ingredients.unwrap();
+        assert_eq!(ingredients.len(), 2);
+        assert_eq!(ingredients[0].percent_estimate, Some(51.2));
+        assert_eq!(ingredients[1].vegan.as_deref(), Some("no"));
+
+        let rows = jsonl_rows();
+        let without = product(map_row(&rows[0], &Filter::default(), imported_at()));
+        assert_eq!(without.ingredients, None);
+    }
+
+    #[test]
+    fn filters_by_country_and_completeness() {
+        let rows = jsonl_rows();
+        let strict = Filter {
+            min_completeness: 0.5,
+            ..Filter::default()
+        };
+        assert!(matches!(
+            map_row(&rows[2], &Filter::default(), imported_at()),
+            Mapped::Skipped(SkipReason::Country)
+        ));
+        assert!(matches!(
+            map_row(&rows[3], &strict, imported_at()),
+            Mapped::Skipped(SkipReason::Incomplete)
+        ));
+        assert!(matches!(
+            map_row(&rows[3], &Filter::default(), imported_at()),
+            Mapped::Product(_)
+        ));
+
+        assert!(matches!(
+            map_row(&rows[2], &Filter::everything(), imported_at()),
+            Mapped::Product(_)
+        ));
+    }
+
+    #[test]
+    fn rejects_rows_without_a_usable_code() {
+        let rows = jsonl_rows();
+        match map_row(&rows[4], &Filter::default(), imported_at()) {
+            Mapped::Rejected(reason) => assert_eq!(reason, "missing code"),
+            other => panic!("expected a reject, got {:?}", other),
+        }
+        match map_row(&rows[5], &Filter::default(), imported_at()) {
+            Mapped::Rejected(reason) => assert_eq!(reason, "code 'abc-123' is not numeric"),
+            other => panic!("expected a reject, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn jsonl_lines_must_be_objects() {
+        assert_eq!(parse_jsonl(r#"{"code": "123"}"#).unwrap()["code"], "123");
+        assert_eq!(
+            parse_jsonl("[1, 2]").unwrap_err(),
+            "line is not a JSON object"
+        );
+        assert!(
+            parse_jsonl(r#"{"code": "#)
+                .unwrap_err()
+                .starts_with("invalid JSON")
+        );
+    }
+}
@@ -0,0 +1,413 @@
+//! OpenFoodFacts' public API as a fallback for barcodes the catalog doesn't have.
+//!
+//! With [`OFF_FALLBACK_ENABLED`], a barcode `GET` that MongoDB has no product for asks
+//! `{OFF_API_URL}/api/v2/product/{code}`, maps the answer like an import row (see
+//! [`crate::off`]), stores it with `source: "openfoodfacts_live"` and answers with it,
+//! marked by [`FETCHED_FROM_HEADER`]. From then on the product is the catalog's own and
+//! is cached like any other. A timeout, an error, an unknown product or an answer that
+//! doesn't map ends in the usual 404, and that miss is cached as usual too.
+//!
+//! OpenFoodFacts asks API users to keep product reads to about 100 a minute, so calls
+//! take a token from a bucket in Redis, shared by every replica, that refills at
+//! [`OFF_FALLBACK_PER_MINUTE`]. An empty bucket or an unreachable Redis skips the call.
+//! With `STORAGE_MODE=memory` there is no bucket and no fallback.
+
+use crate::{
+    barcode,
+    errors::Result,
+    models::Product,
+    off::{self, Filter, Mapped},
+    state::AppState,
+    tunables::{OFF_FALLBACK_ENABLED, OFF_FALLBACK_PER_MINUTE, OFF_FALLBACK_TIMEOUT_MS},
+};
+use axum::{
+    http::{HeaderName, HeaderValue},
+    response::{IntoResponseParts, ResponseParts},
+};
+use chrono::{DateTime, Utc};
+use redis::Client as RedisClient;
+use reqwest::{Client as HttpClient, StatusCode, header::USER_AGENT};
+use serde_json::Value;
+use std::convert::Infallible;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// The `source` of products fetched through the fallback; imports use [`off::SOURCE`].
+pub const LIVE_SOURCE: &str = "openfoodfacts_live";
+
+/// Where the public API is asked unless `OFF_API_URL` says otherwise.
+pub const DEFAULT_OFF_API_URL: &str = "https://world.openfoodfacts.org";
+
+/// Marks a barcode `GET` answered with a product just fetched from OpenFoodFacts.
+pub const FETCHED_FROM_HEADER: HeaderName = HeaderName::from_static("x-fetched-from");
+
+/// OpenFoodFacts asks API clients to name themselves.
+const USER_AGENT_VALUE: &str = concat!(
+    "YoloEats product-catalog-service/",
+    env!("CARGO_PKG_VERSION")
+);
+
+const BUCKET_KEY: &str = "off:fallback:tokens";
+
+/// Takes a token from the bucket in `KEYS[1]`, which holds up to `ARGV[1]` and refills
+/// at `ARGV[1]` a minute. 1 if there was one.
+const TAKE_TOKEN_SCRIPT: &str = r"
+local capacity = tonumber(ARGV[1])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'at')
+local tokens = tonumber(bucket[1]) or capacity
+local at = tonumber(bucket[2]) or now
+tokens = math.min(capacity, tokens + math.max(0, now - at) * capacity / 60000)
+local taken = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+    taken = 1
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'at', now)
+redis.call('PEXPIRE', KEYS[1], 60000)
+return taken
+";
+
+/// The [`FETCHED_FROM_HEADER`] of a barcode `GET`, none for products the catalog had.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FetchedRemotely(pub bool);
+
+impl IntoResponseParts for FetchedRemotely {
+    type Error = Infallible;
+
+    fn into_response_parts(
+        self,
+        mut res: ResponseParts,
+    ) -> std::result::Result<ResponseParts, Self::Error> {
+        if self.0 {
+            res.headers_mut()
+                .insert(FETCHED_FROM_HEADER, HeaderValue::from_static(off::SOURCE));
+        }
+        Ok(res)
+    }
+}
+
+/// The product OpenFoodFacts has under `code`, stored in the catalog and read back from
+/// it. `None` if the fallback is off, `code` is no GTIN, the bucket is empty or
+/// OpenFoodFacts has nothing usable; only a failure to store it is an error.
+pub async fn fetch_and_store(state: &AppState, code: &str) -> Result<Option<Product>> {
+    if !state.config.get(OFF_FALLBACK_ENABLED) || barcode::parse(code).is_err() {
+        return Ok(None);
+    }
+    let Some(clients) = &state.clients else {
+        return Ok(None);
+    };
+    let per_minute = state.config.get(OFF_FALLBACK_PER_MINUTE);
+    if !take_token(&clients.redis_client, per_minute).await {
+        return Ok(None);
+    }
+
+    let timeout = Duration::from_millis(state.config.get(OFF_FALLBACK_TIMEOUT_MS));
+    let Some(product) = fetch(
+        &state.http_client,
+        &state.off_api_url,
+        code,
+        timeout,
+        Utc::now(),
+    )
+    .await
+    else {
+        return Ok(None);
+    };
+    state.products.upsert_by_code(vec![product]).await?;
+    info!(code, "Stored product fetched from OpenFoodFacts");
+    state.products.find_by_code(code).await
+}
+
+/// Whether the bucket had a token for one more call. A Redis failure is logged and
+/// counts as an empty bucket.
+async fn take_token(redis: &RedisClient, per_minute: u64) -> bool {
+    let taken: redis::RedisResult<u8> = async {
+        let mut conn = redis.get_multiplexed_async_connection().await?;
+        redis::Script::new(TAKE_TOKEN_SCRIPT)
+            .key(BUCKET_KEY)
+            .arg(per_minute)
+            .invoke_async(&mut conn)
+            .await
+    }
+    .await;
+    match taken {
+        Ok(1) => true,
+        Ok(_) => {
+            warn!(
+                "OpenFoodFacts fallback over {} calls a minute; skipping",
+                per_minute
+            );
+            false
+        }
+        Err(e) => {
+            warn!(
+                "Failed to count an OpenFoodFacts call in Redis: {}; skipping",
+                e
+            );
+            false
+        }
+    }
+}
+
+/// The product the API at `base_url` has under `code`, mapped for the catalog; `None`,
+/// logged, if it has none or doesn't answer within `timeout`.
+pub async fn fetch(
+    client: &HttpClient,
+    base_url: &str,
+    code: &str,
+    timeout: Duration,
+    fetched_at: DateTime<Utc>,
+) -> Option<Product> {
+    let url = format!("{}/api/v2/product/{}", base_url.trim_end_matches('/'), code);
+    debug!("Fetching product from OpenFoodFacts: {}", url);
+    let response = client
+        .get(&url)
+        .header(USER_AGENT, USER_AGENT_VALUE)
+        .timeout(timeout)
+        .send()
+        .await
+        .inspect_err(|e| warn!(code, "OpenFoodFacts request failed: {}", e))
+        .ok()?;
+    match response.status() {
+        StatusCode::NOT_FOUND => {
+            debug!(code, "OpenFoodFacts has no such product");
+            return None;
+        }
+        status if !status.is_success() => {
+            warn!(code, %status, "OpenFoodFacts answered with an error");
+            return None;
+        }
+        _ => {}
+    }
+    let body: Value = response
+        .json()
+        .await
+        .inspect_err(|e| warn!(code, "Unreadable OpenFoodFacts answer: {}", e))
+        .ok()?;
+    map_response(&body, code, fetched_at)
+}
+
+/// Maps an API answer, `{"status": 1, "product": {...}}`, for the product asked for by
+/// `code`. The product is stored under `code` whatever code OpenFoodFacts spells it
+/// with, so the next lookup finds it. `None` for any other status, a missing product or
+/// one [`off::map_row`] rejects.
+pub fn map_response(body: &Value, code: &str, fetched_at: DateTime<Utc>) -> Option<Product> {
+    let found = match body.get("status") {
+        Some(Value::Number(status)) => status.as_i64() == Some(1),
+        Some(Value::String(status)) => status.trim() == "1",
+        _ => false,
+    };
+    if !found {
+        debug!(code, status = ?body.get("status_verbose"), "OpenFoodFacts found no product");
+        return None;
+    }
+    let Some(Value::Object(row)) = body.get("product") else {
+        warn!(code, "OpenFoodFacts answer has no product object");
+        return None;
+    };
+    let mut row = row.clone();
+    row.insert("code".to_string(), Value::String(code.to_string()));
+    match off::map_row(&row, &Filter::everything(), fetched_at) {
+        Mapped::Product(product) => Some(Product {
+            source: Some(LIVE_SOURCE.to_string()),
+            ..*product
+        }),
+        Mapped::Rejected(reason) => {
+            warn!(code, "OpenFoodFacts product does not map: {}", reason);
+            None
+        }
+        Mapped::Skipped(reason) => {
+            warn!(code, ?reason, "OpenFoodFacts product was filtered out");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde_json::json;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{header, method, path},
+    };
+
+    /// An answer of the public API, trimmed to the fields we read plus a few we don't.
+    const API_PRODUCT: &str = include_str!("../fixtures/off_api_product.json");
+
+    const CODE: &str = "4008400402222";
+
+    fn fetched_at() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap()
+    }
+
+    fn api_product() -> Value {
+        serde_json::from_str(API_PRODUCT).unwrap()
+    }
+
+    fn strings(values: &[&str]) -> Option<Vec<String>> {
+        Some(values.iter().map(|v| v.to_string()).collect())
+    }
+
+    #[test]
+    fn maps_a_found_product() {
+        let product = map_response(&api_product(), CODE, fetched_at()).unwrap();
+
+        assert_eq!(product.id, None);
+        assert_eq!(product.code, CODE);
+        assert_eq!(product.source.as_deref(), Some(LIVE_SOURCE));
+        assert_eq!(product.product_name.as_deref(), Some("Kinder Schokolade"));
+        assert_eq!(
+            product
+                .product_name_langs
+                .unwrap()
+                .get("fr")
+                .map(String::as_str),
+            Some("Kinder Chocolat")
+        );
+        assert_eq!(product.brands, strings(&["kinder", "ferrero"]));
+        assert_eq!(product.main_category.as_deref(), Some("en:milk-chocolates"));
+        assert_eq!(product.allergens_tags, ["en:milk", "en:soybeans"]);
+        assert_eq!(product.traces_tags, strings(&["en:nuts"]));
+        assert!(
+            product
+                .ingredients_text
+                .unwrap()
+                .starts_with("Milchschokolade")
+        );
+        assert_eq!(product.nutrition_grade_fr.as_deref(), Some("e"));
+        assert!(product.image_url.unwrap().ends_with("front_de.187.400.jpg"));
+
+        let nutriments = product.nutriments.unwrap();
+        assert_eq!(nutriments.energy_kcal_100g, Some(566.0));
+        // Numbers OpenFoodFacts sends as strings still read.
+        assert_eq!(nutriments.sugars_100g, Some(53.5));
+        assert_eq!(
+            product.created_at,
+            Utc.with_ymd_and_hms(2013, 4, 10, 13, 39, 47).unwrap()
+        );
+        assert_eq!(product.last_modified_at.timestamp(), 1_718_093_122);
+    }
+
+    #[test]
+    fn answers_without_a_product_map_to_nothing() {
+        let answers = [
+            json!({"code": CODE, "status": 0, "status_verbose": "product not found"}),
+            json!({"code": CODE, "status": "0"}),
+            json!({"code": CODE, "product": {"product_name": "No status"}}),
+            json!({"code": CODE, "status": 1}),
+            json!({"code": CODE, "status": 1, "product": "Kinder Schokolade"}),
+            json!({"code": CODE, "status": 1, "product": null}),
+            json!([]),
+        ];
+        for answer in answers {
+            assert!(
+                map_response(&answer, CODE, fetched_at()).is_none(),
+                "{}",
+                answer
+            );
+        }
+    }
+
+    #[test]
+    fn loose_shapes_still_map() {
+        let answer = json!({
+            "status": "1",
+            "product": {
+                "product_name": 7_622_210_449_283u64,
+                "brands": "Milka, Mondelez",
+                "allergens": "en:milk",
+                "nutriscore_grade": "unknown",
+                "nutriments": {"salt_100g": "0.1", "fat_100g": null},
+                "created_t": "not a time",
+            },
+        });
+        let product = map_response(&answer, CODE, fetched_at()).unwrap();
+
+        assert_eq!(product.code, CODE);
+        assert_eq!(product.product_name.as_deref(), Some("7622210449283"));
+        assert_eq!(product.brands, strings(&["milka", "mondelez"]));
+        assert_eq!(product.allergens_tags, ["en:milk"]);
+        assert_eq!(product.nutrition_grade_fr, None);
+        assert_eq!(product.nutriments.unwrap().salt_100g, Some(0.1));
+        assert_eq!(product.created_at, fetched_at());
+        assert_eq!(product.last_modified_at, fetched_at());
+    }
+
+    #[test]
+    fn products_are_stored_under_the_code_asked_for() {
+        let mut answer = api_product();
+        answer["product"]["code"] = json!("04008400402222");
+        let product = map_response(&answer, CODE, fetched_at()).unwrap();
+        assert_eq!(product.code, CODE);
+
+        let empty = json!({"status": 1, "product": {}});
+        let product = map_response(&empty, CODE, fetched_at()).unwrap();
+        assert_eq!(product.code, CODE);
+        assert_eq!(product.product_name, None);
+        assert_eq!(product.source.as_deref(), Some(LIVE_SOURCE));
+    }
+
+    #[test]
+    fn the_header_marks_only_fetched_products() {
+        use axum::response::IntoResponse;
+
+        let fetched = (FetchedRemotely(true), "").into_response();
+        assert_eq!(fetched.headers()[FETCHED_FROM_HEADER], off::SOURCE);
+        let local = (FetchedRemotely(false), "").into_response();
+        assert!(!local.headers().contains_key(FETCHED_FROM_HEADER));
+    }
+
+    async fn off_api(response: ResponseTemplate) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(format!("/api/v2/product/{}", CODE)))
+            .and(header(USER_AGENT, USER_AGENT_VALUE))
+            .respond_with(response)
+            .expect(1)
+            .mount(&server)
+            .await;
+        server
+    }
+
+    async fn fetch_from(server: &MockServer) -> Option<Product> {
+        let timeout = Duration::from_millis(200);
+        fetch(
+            &HttpClient::new(),
+            &server.uri(),
+            CODE,
+            timeout,
+            fetched_at(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn fetches_and_maps_the_product() {
+        let server = off_api(ResponseTemplate::new(200).set_body_json(api_product())).await;
+        let product = fetch_from(&server).await.unwrap();
+        assert_eq!(product.product_name.as_deref(), Some("Kinder Schokolade"));
+        assert_eq!(product.source.as_deref(), Some(LIVE_SOURCE));
+    }
+
+    #[tokio::test]
+    async fn unknown_products_errors_and_timeouts_fetch_nothing() {
+        let not_found = json!({"code": CODE, "status": 0, "status_verbose": "product not found"});
+        let answers = [
+            ResponseTemplate::new(404).set_body_json(not_found.clone()),
+            ResponseTemplate::new(200).set_body_json(not_found),
+            ResponseTemplate::new(503),
+            ResponseTemplate::new(200).set_body_string("<html>busy</html>"),
+            ResponseTemplate::new(200)
+                .set_body_json(api_product())
+                .set_delay(Duration::from_secs(1)),
+        ];
+        for answer in answers {
+            let server = off_api(answer).await;
+            assert!(fetch_from(&server).await.is_none());
+        }
+    }
+}
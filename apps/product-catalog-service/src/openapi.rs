@@ -0,0 +1,369 @@
+//! The service's OpenAPI document, served by [`yoloeats_openapi::openapi_router`].
+//!
+//! Both API versions are in it, tagged `v1` and `v2`, with the stored field names of
+//! [`Product`](crate::models::Product) in v1 and the camelCase of
+//! [`ProductV2`](crate::v2::ProductV2) in v2. The writes take the `bearer` scheme; the
+//! `admin` routes take the `X-Internal-Token` header instead.
+
+use crate::{
+    audit, categories, changes, curation, discovery, duplicates, handlers, hybrid, import,
+    nutriscore, popularity, recent, reindex, reports, semantic, v2, webhooks,
+};
+use utoipa::OpenApi;
+use yoloeats_openapi::{BearerAuth, MiddlewareResponses};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Product Catalog Service",
+        description = "Product CRUD, search, barcode lookup and recommendations."
+    ),
+    paths(
+        handlers::create_product,
+        handlers::search_products,
+        handlers::count_products,
+        discovery::get_random_products,
+        changes::list_changes,
+        recent::list_recent_products,
+        popularity::list_popular_products,
+        semantic::semantic_search_products,
+        semantic::semantic_search_by_body,
+        hybrid::hybrid_search_products,
+        hybrid::hybrid_search_by_body,
+        handlers::get_product_by_id,
+        handlers::update_product,
+        handlers::patch_product,
+        handlers::delete_product,
+        handlers::get_product_by_barcode,
+        handlers::get_products_by_barcodes,
+        import::import_products,
+        handlers::get_recommendations,
+        duplicates::get_duplicates,
+        nutriscore::get_nutriscore,
+        audit::get_product_history,
+        reports::create_report,
+        reports::get_product_reports,
+        curation::list_incomplete_products,
+        categories::list_categories,
+        v2::create_product,
+        v2::search_products,
+        v2::semantic_search_products,
+        v2::semantic_search_by_body,
+        v2::get_product_by_id,
+        v2::update_product,
+        v2::patch_product,
+        v2::delete_product,
+        v2::get_product_by_barcode,
+        v2::get_products_by_barcodes,
+        v2::import_products,
+        v2::get_recommendations,
+        v2::get_product_history,
+        webhooks::create_webhook,
+        webhooks::list_webhooks,
+        webhooks::delete_webhook,
+        reindex::start_reindex,
+        reindex::get_reindex_job,
+        reports::list_reports,
+        reports::assign_report,
+        reports::resolve_report,
+        reports::get_report_stats,
+    ),
+    // The body of the webhook requests, which no route serves.
+    components(schemas(webhooks::WebhookEvent)),
+    modifiers(&BearerAuth, &MiddlewareResponses)
+)]
+pub struct ApiDoc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+    use yoloeats_domain::ErrorCode;
+
+    fn spec() -> Value {
+        serde_json::from_str(&ApiDoc::openapi().to_json().unwrap()).unwrap()
+    }
+
+    fn param_names(operation: &Value) -> Vec<&str> {
+        operation["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|param| param["name"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn both_versions_have_every_route() {
+        let spec = spec();
+        for version in ["v1", "v2"] {
+            let base = format!("/api/{}/products", version);
+            for (method, path) in [
+                ("post", ""),
+                ("get", "/search"),
+                ("get", "/search/semantic"),
+                ("post", "/search/semantic"),
+                ("get", "/{id}"),
+                ("put", "/{id}"),
+                ("patch", "/{id}"),
+                ("delete", "/{id}"),
+                ("get", "/barcode/{code}"),
+                ("post", "/batch"),
+                ("post", "/import"),
+                ("get", "/{id}/recommendations"),
+                ("get", "/{id}/history"),
+            ] {
+                let path = format!("{}{}", base, path);
+                assert!(
+                    spec["paths"][&path][method].is_object(),
+                    "{} {}",
+                    method,
+                    path
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn operations_list_their_parameters_and_errors() {
+        let spec = spec();
+        let search = &spec["paths"]["/api/v1/products/search"]["get"];
+        let names = param_names(search);
+        for name in [
+            "q",
+            "category",
+            "match",
+            "allergens",
+            "sort",
+            "view",
+            "lang",
+            "include_count",
+            "highlight",
+            "limit",
+            "cursor",
+        ] {
+            assert!(names.contains(&name), "{}", name);
+        }
+        assert_eq!(
+            search["responses"]["429"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/ErrorBody"
+        );
+
+        let by_id = &spec["paths"]["/api/v1/products/{id}"]["get"];
+        assert!(param_names(by_id).contains(&"If-None-Match"));
+        assert!(param_names(by_id).contains(&"lang"));
+        assert!(!param_names(by_id).contains(&"Accept-Language"));
+        assert!(!names.contains(&"Accept-Language"));
+        let v2_by_id = &spec["paths"]["/api/v2/products/{id}"]["get"];
+        assert!(param_names(v2_by_id).contains(&"Accept-Language"));
+        for status in ["200", "304", "400", "404", "500", "503"] {
+            assert!(by_id["responses"][status].is_object(), "{}", status);
+        }
+        assert!(by_id["responses"]["200"]["headers"]["ETag"].is_object());
+        assert!(spec["paths"]["/api/v1/products"]["post"]["responses"]["409"].is_object());
+
+        let incomplete = &spec["paths"]["/api/v1/products/curation/incomplete"]["get"];
+        let names = param_names(incomplete);
+        for name in ["max_score", "country", "limit", "cursor"] {
+            assert!(names.contains(&name), "{}", name);
+        }
+        assert!(param_names(by_id).contains(&"include_completeness"));
+        assert!(search["responses"]["200"]["headers"]["X-Total-Count"].is_object());
+        for path in [
+            "/api/v1/products/search",
+            "/api/v1/products/count",
+            "/api/v1/products/recent",
+            "/api/v1/products/popular",
+            "/api/v1/categories",
+            "/api/v2/products/search",
+        ] {
+            let headers = &spec["paths"][path]["get"]["responses"]["200"]["headers"];
+            assert!(headers["X-Default-Country"].is_object(), "{}", path);
+        }
+        let count = &spec["paths"]["/api/v1/products/count"]["get"];
+        for name in [
+            "q",
+            "country",
+            "max_sugar",
+            "ingredients_exclude",
+            "exclude_traces",
+        ] {
+            assert!(param_names(count).contains(&name), "{}", name);
+        }
+        let random = &spec["paths"]["/api/v1/products/random"]["get"];
+        for name in ["country", "category", "allergens", "diets", "count"] {
+            assert!(param_names(random).contains(&name), "{}", name);
+        }
+        let changes = &spec["paths"]["/api/v1/products/changes"]["get"];
+        for name in ["since", "limit", "cursor"] {
+            assert!(param_names(changes).contains(&name), "{}", name);
+        }
+        assert!(changes["responses"]["410"].is_object());
+        let recent = &spec["paths"]["/api/v1/products/recent"]["get"];
+        for name in ["kind", "country", "limit", "cursor"] {
+            assert!(param_names(recent).contains(&name), "{}", name);
+        }
+        let hybrid = &spec["paths"]["/api/v1/products/search/hybrid"];
+        for name in ["q", "limit", "semantic_weight", "allergens", "diets"] {
+            assert!(param_names(&hybrid["get"]).contains(&name), "{}", name);
+        }
+        assert!(hybrid["post"]["requestBody"].is_object());
+        let popular = &spec["paths"]["/api/v1/products/popular"]["get"];
+        for name in ["window", "limit", "country"] {
+            assert!(param_names(popular).contains(&name), "{}", name);
+        }
+        let categories = &spec["paths"]["/api/v1/categories"]["get"];
+        for name in ["parent", "prefix", "country", "min_count", "limit"] {
+            assert!(param_names(categories).contains(&name), "{}", name);
+        }
+        assert!(categories["responses"]["503"].is_object());
+        let duplicates = &spec["paths"]["/api/v1/products/{id}/duplicates"]["get"];
+        let names = param_names(duplicates);
+        for name in ["id", "min_score", "limit"] {
+            assert!(names.contains(&name), "{}", name);
+        }
+        assert!(spec["paths"]["/api/v1/products"]["post"]["responses"]["413"].is_object());
+        assert!(by_id["security"].is_null());
+        for (method, path) in [
+            ("post", "/api/v1/products"),
+            ("delete", "/api/v2/products/{id}"),
+            ("post", "/api/v2/products/import"),
+        ] {
+            let operation = &spec["paths"][path][method];
+            assert_eq!(
+                operation["security"],
+                json!([{ "bearer": [] }]),
+                "{} {}",
+                method,
+                path
+            );
+            assert!(
+                operation["responses"]["401"].is_object(),
+                "{} {}",
+                method,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn error_bodies_list_every_code() {
+        let spec = spec();
+        let code = &spec["components"]["schemas"]["ErrorBody"]["properties"]["code"];
+        let listed: Vec<&str> = code["enum"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|code| code.as_str().unwrap())
+            .collect();
+        assert_eq!(listed.len(), ErrorCode::ALL.len());
+        for code in [
+            "product_not_found",
+            "product_conflict",
+            "upstream_unavailable",
+        ] {
+            assert!(listed.contains(&code), "{}", code);
+        }
+    }
+
+    #[test]
+    fn admin_routes_take_the_internal_token() {
+        let spec = spec();
+        // Closing a report also names the curator by their bearer token.
+        let named = [
+            "/api/v1/admin/reports/{id}/assign",
+            "/api/v1/admin/reports/{id}/resolve",
+        ];
+        for (method, path) in [
+            ("post", "/api/v1/admin/webhooks"),
+            ("get", "/api/v1/admin/webhooks"),
+            ("delete", "/api/v1/admin/webhooks/{id}"),
+            ("post", "/api/v1/admin/reindex"),
+            ("get", "/api/v1/admin/reindex/{job_id}"),
+            ("get", "/api/v1/admin/reports"),
+            ("post", named[0]),
+            ("post", named[1]),
+            ("get", "/api/v1/admin/reports/stats"),
+        ] {
+            let operation = &spec["paths"][path][method];
+            assert_eq!(operation["tags"], json!(["admin"]), "{} {}", method, path);
+            assert!(
+                param_names(operation).contains(&"X-Internal-Token"),
+                "{} {}",
+                method,
+                path
+            );
+            assert_eq!(
+                operation["security"].is_null(),
+                !named.contains(&path),
+                "{} {}",
+                method,
+                path
+            );
+            assert!(
+                operation["responses"]["401"].is_object(),
+                "{} {}",
+                method,
+                path
+            );
+        }
+        let schemas = &spec["components"]["schemas"];
+        assert!(schemas["WebhookSubscription"]["properties"]["secret"].is_null());
+        assert!(schemas["WebhookEvent"]["properties"]["type"].is_object());
+        assert_eq!(
+            schemas["EventType"]["enum"],
+            json!(["product.created", "product.updated", "product.deleted"])
+        );
+    }
+
+    #[test]
+    fn schemas_use_the_wire_names() {
+        let spec = spec();
+        let schemas = &spec["components"]["schemas"];
+
+        let product = &schemas["Product"]["properties"];
+        for field in [
+            "_id",
+            "brands_tags",
+            "categories_tags",
+            "labels_tags",
+            "countries_tags",
+            "nutrition_grade_fr",
+            "created_datetime",
+            "last_modified_datetime",
+        ] {
+            assert!(product[field].is_object(), "{}", field);
+        }
+        assert!(product["brands"].is_null());
+        let nutriments = &schemas["Nutriments"]["properties"];
+        assert!(nutriments["energy-kcal_100g"].is_object());
+        assert!(nutriments["saturated-fat_100g"].is_object());
+        assert!(nutriments["fruits-vegetables-nuts_100g"].is_object());
+        assert_eq!(
+            schemas["NutriScoreGrade"]["enum"],
+            json!(["a", "b", "c", "d", "e"])
+        );
+        assert!(schemas["PatchProductPayload"]["properties"]["labels_tags"].is_object());
+        assert!(schemas["BatchLookupResponse"]["properties"]["not_found"].is_object());
+
+        let v2 = &schemas["ProductV2"]["properties"];
+        for field in ["id", "name", "genericName", "imageSmallUrl", "createdAt"] {
+            assert!(v2[field].is_object(), "{}", field);
+        }
+        assert!(schemas["BatchLookupV2"]["properties"]["notFound"].is_object());
+        assert!(schemas["RecommendationsV2"]["properties"]["sourceId"].is_object());
+        assert_eq!(
+            schemas["AuditAction"]["enum"],
+            json!(["created", "updated", "deleted"])
+        );
+        assert_eq!(
+            schemas["ReportReason"]["enum"],
+            json!([
+                "wrong_allergens",
+                "wrong_ingredients",
+                "wrong_image",
+                "other"
+            ])
+        );
+    }
+}
@@ -0,0 +1,650 @@
+//! Scan counting and `/api/v1/products/popular`: which products people actually look up.
+//!
+//! Every barcode `GET` that resolves counts a scan in Redis, in a task that never holds
+//! up or fails the lookup. `scan_count:{code}` adds up the code's scans until
+//! [`spawn_folding`] moves them into the product's `scan_count` in MongoDB, and the
+//! sorted set `scans:{day}` counts each code's scans of that UTC day. A day's set
+//! expires once no window reaches back to it.
+//!
+//! `popular` adds up the day sets of its window with `ZUNIONSTORE` into a key that is
+//! kept for a minute, so answers may lag by that long, and reads the products of the
+//! top codes from MongoDB. In memory mode the counts are kept in process.
+
+use crate::{
+    default_country::{DefaultCountry, scope_country},
+    errors::{Result, ServiceError},
+    models::{Product, SearchHit, SearchSummary},
+    repository::ProductRepository,
+    state::AppState,
+    taxonomy::normalize_tags,
+};
+use async_trait::async_trait;
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use chrono::{DateTime, Days, NaiveDate, NaiveTime, Utc};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    task::JoinHandle,
+    time::{MissedTickBehavior, interval},
+};
+use tracing::{Instrument, debug, error, info, instrument, warn};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+use yoloeats_domain::ErrorBody;
+
+/// The longest `window` of `popular`, and so how long a day's scans are kept.
+pub const MAX_WINDOW_DAYS: u32 = 30;
+const DEFAULT_WINDOW_DAYS: u32 = 7;
+
+const DEFAULT_LIMIT: usize = 20;
+
+/// With a `country`, how many of the top codes are read per product asked for, as the
+/// most scanned ones may be sold elsewhere.
+const COUNTRY_CANDIDATES: usize = 10;
+
+/// How long the union of a window's day sets is reused.
+const UNION_TTL_SECS: i64 = 60;
+
+/// How often [`spawn_folding`] moves the counted scans into MongoDB.
+pub const FOLD_INTERVAL: Duration = Duration::from_secs(60);
+
+const TOTAL_KEY_PREFIX: &str = "scan_count:";
+
+/// Where a code's scans wait to be folded into its `scan_count`.
+pub fn total_key(code: &str) -> String {
+    format!("{}{}", TOTAL_KEY_PREFIX, code)
+}
+
+/// The sorted set of the codes scanned on `day`, by their scans that day.
+pub fn day_key(day: NaiveDate) -> String {
+    format!("scans:{}", day)
+}
+
+/// The union of the day sets of the `days` up to `today`.
+pub fn union_key(today: NaiveDate, days: usize) -> String {
+    format!("scans:{}:{}d", today, days)
+}
+
+/// When the set of `day`'s scans goes: a day after the last window reaching back to it
+/// has ended, for replicas whose clocks are behind.
+pub fn day_key_expiry(day: NaiveDate) -> DateTime<Utc> {
+    (day + Days::new(u64::from(MAX_WINDOW_DAYS) + 1))
+        .and_time(NaiveTime::MIN)
+        .and_utc()
+}
+
+/// The days of a window of `days` ending on `today`, newest first.
+pub fn window_days(today: NaiveDate, days: u32) -> Vec<NaiveDate> {
+    (0..u64::from(days))
+        .filter_map(|back| today.checked_sub_days(Days::new(back)))
+        .collect()
+}
+
+/// The days of a `window` such as `7d`: 1 to [`MAX_WINDOW_DAYS`], 7 when absent. Taken
+/// as a string so a bad value gets our message, not serde's.
+pub fn parse_window(window: Option<&str>) -> Result<u32> {
+    let window = window.map(str::trim).filter(|window| !window.is_empty());
+    let Some(window) = window else {
+        return Ok(DEFAULT_WINDOW_DAYS);
+    };
+    window
+        .strip_suffix('d')
+        .and_then(|days| days.parse::<u32>().ok())
+        .filter(|days| (1..=MAX_WINDOW_DAYS).contains(days))
+        .ok_or_else(|| {
+            ServiceError::BadRequest(format!(
+                "Invalid window '{}': expected 1d to {}d",
+                window, MAX_WINDOW_DAYS
+            ))
+        })
+}
+
+/// Where scans are counted.
+#[async_trait]
+pub trait ScanCounts: Send + Sync {
+    /// Counts a scan of `code` on `day`.
+    async fn record(&self, code: &str, day: NaiveDate) -> Result<()>;
+
+    /// The `limit` codes scanned most on `days`, most first, with their scans.
+    async fn top(&self, days: &[NaiveDate], limit: usize) -> Result<Vec<(String, u64)>>;
+
+    /// Takes the scans counted per code since they were last taken.
+    async fn take_totals(&self) -> Result<Vec<(String, u64)>>;
+
+    /// Puts back totals that were taken but could not be stored.
+    async fn restore_totals(&self, totals: &[(String, u64)]) -> Result<()>;
+}
+
+/// Counts in Redis, shared by every replica.
+pub struct RedisScanCounts {
+    client: redis::Client,
+}
+
+impl RedisScanCounts {
+    pub fn new(client: redis::Client) -> Self {
+        RedisScanCounts { client }
+    }
+}
+
+#[async_trait]
+impl ScanCounts for RedisScanCounts {
+    async fn record(&self, code: &str, day: NaiveDate) -> Result<()> {
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let day_set = day_key(day);
+        redis::pipe()
+            .atomic()
+            .incr(total_key(code), 1)
+            .ignore()
+            .zincr(&day_set, code, 1)
+            .ignore()
+            .expire_at(&day_set, day_key_expiry(day).timestamp())
+            .ignore()
+            .query_async::<()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Reuses the window's union while it lasts.
+    async fn top(&self, days: &[NaiveDate], limit: usize) -> Result<Vec<(String, u64)>> {
+        let Some(today) = days.first() else {
+            return Ok(Vec::new());
+        };
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let union = union_key(*today, days.len());
+        if !conn.exists::<_, bool>(&union).await? {
+            let day_sets: Vec<String> = days.iter().copied().map(day_key).collect();
+            redis::pipe()
+                .atomic()
+                .cmd("ZUNIONSTORE")
+                .arg(&union)
+                .arg(day_sets.len())
+                .arg(&day_sets)
+                .ignore()
+                .expire(&union, UNION_TTL_SECS)
+                .ignore()
+                .query_async::<()>(&mut conn)
+                .await?;
+        }
+        let top: Vec<(String, f64)> = conn
+            .zrevrange_withscores(&union, 0, limit as isize - 1)
+            .await?;
+        Ok(top
+            .into_iter()
+            .map(|(code, scans)| (code, scans as u64))
+            .collect())
+    }
+
+    /// `GETDEL`s each total, so that two replicas never take the same scans.
+    async fn take_totals(&self) -> Result<Vec<(String, u64)>> {
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let pattern = format!("{}*", TOTAL_KEY_PREFIX);
+        let mut keys: Vec<String> = Vec::new();
+        let mut cursor: u64 = 0;
+        loop {
+            let (next, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(&pattern)
+                .arg("COUNT")
+                .arg(500)
+                .query_async(&mut conn)
+                .await?;
+            keys.extend(batch);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut pipe = redis::pipe();
+        for key in &keys {
+            pipe.cmd("GETDEL").arg(key);
+        }
+        let totals: Vec<Option<u64>> = pipe.query_async(&mut conn).await?;
+        // SCAN may list a key twice; the second GETDEL finds nothing.
+        Ok(keys
+            .into_iter()
+            .zip(totals)
+            .filter_map(|(key, total)| {
+                let code = key.strip_prefix(TOTAL_KEY_PREFIX)?.to_string();
+                Some((code, total?))
+            })
+            .collect())
+    }
+
+    async fn restore_totals(&self, totals: &[(String, u64)]) -> Result<()> {
+        if totals.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let mut pipe = redis::pipe();
+        for (code, total) in totals {
+            pipe.incr(total_key(code), *total).ignore();
+        }
+        pipe.query_async::<()>(&mut conn).await?;
+        Ok(())
+    }
+}
+
+/// Process-local counts, for `STORAGE_MODE=memory` and tests. Days are dropped when
+/// Redis would expire them.
+#[derive(Default)]
+pub struct MemoryScanCounts {
+    days: Mutex<BTreeMap<NaiveDate, HashMap<String, u64>>>,
+    totals: Mutex<HashMap<String, u64>>,
+}
+
+#[async_trait]
+impl ScanCounts for MemoryScanCounts {
+    async fn record(&self, code: &str, day: NaiveDate) -> Result<()> {
+        let mut days = self.days.lock().unwrap();
+        *days
+            .entry(day)
+            .or_default()
+            .entry(code.to_string())
+            .or_default() += 1;
+        let now = day.and_time(NaiveTime::MIN).and_utc();
+        days.retain(|kept, _| day_key_expiry(*kept) > now);
+        *self
+            .totals
+            .lock()
+            .unwrap()
+            .entry(code.to_string())
+            .or_default() += 1;
+        Ok(())
+    }
+
+    /// Ties are in descending code order, as `ZREVRANGE` lists them.
+    async fn top(&self, days: &[NaiveDate], limit: usize) -> Result<Vec<(String, u64)>> {
+        let counted = self.days.lock().unwrap();
+        let mut scans: HashMap<&str, u64> = HashMap::new();
+        for day in days {
+            for (code, count) in counted.get(day).into_iter().flatten() {
+                *scans.entry(code.as_str()).or_default() += count;
+            }
+        }
+        let mut top: Vec<(String, u64)> = scans
+            .into_iter()
+            .map(|(code, count)| (code.to_string(), count))
+            .collect();
+        top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| b.0.cmp(&a.0)));
+        top.truncate(limit);
+        Ok(top)
+    }
+
+    async fn take_totals(&self) -> Result<Vec<(String, u64)>> {
+        Ok(self.totals.lock().unwrap().drain().collect())
+    }
+
+    async fn restore_totals(&self, totals: &[(String, u64)]) -> Result<()> {
+        let mut kept = self.totals.lock().unwrap();
+        for (code, total) in totals {
+            *kept.entry(code.clone()).or_default() += total;
+        }
+        Ok(())
+    }
+}
+
+/// Counts a scan of `code` today in a task tracked by [`AppState::tasks`]. A failure is
+/// only logged: the lookup has been answered by then.
+pub fn spawn_count_scan(state: &AppState, code: &str) {
+    let scans = state.scans.clone();
+    let code = code.to_string();
+    let today = Utc::now().date_naive();
+    let task = async move {
+        if let Err(e) = scans.record(&code, today).await {
+            warn!("Could not count a scan of {}: {}", code, e);
+        }
+    };
+    state.tasks.spawn(task.in_current_span());
+}
+
+/// Moves the scans counted since the last fold into the products' `scan_count` and
+/// returns how many products got some. Scans MongoDB doesn't take are put back for the
+/// next fold.
+pub async fn fold_scans(scans: &dyn ScanCounts, products: &dyn ProductRepository) -> Result<usize> {
+    let totals = scans.take_totals().await?;
+    if totals.is_empty() {
+        return Ok(0);
+    }
+    if let Err(e) = products.add_scans(&totals).await {
+        if let Err(restore) = scans.restore_totals(&totals).await {
+            error!(
+                "Lost the scans of {} products, which could not be put back: {}",
+                totals.len(),
+                restore
+            );
+        }
+        return Err(e);
+    }
+    Ok(totals.len())
+}
+
+/// Folds the counted scans into MongoDB every `every`, for as long as the service runs.
+pub fn spawn_folding(state: Arc<AppState>, every: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticks = interval(every);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticks.tick().await;
+            match fold_scans(state.scans.as_ref(), state.products.as_ref()).await {
+                Ok(0) => {}
+                Ok(folded) => debug!("Stored the new scans of {} products", folded),
+                Err(e) => warn!(
+                    "Could not store scan counts, retrying in {:?}: {}",
+                    every, e
+                ),
+            }
+        }
+    })
+}
+
+/// Query of `GET /api/v1/products/popular`.
+#[derive(Debug, Default, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PopularParams {
+    /// The days whose scans count, today (UTC) included: `1d` to `30d`; `7d` when absent.
+    #[param(example = "7d")]
+    pub window: Option<String>,
+    /// 1 to 100 products; 20 when absent.
+    #[validate(range(min = 1, max = 100, message = "limit must be between 1 and 100"))]
+    pub limit: Option<usize>,
+    /// Only products sold in this country; `all` lists every country even with a
+    /// `DEFAULT_COUNTRY_TAG`.
+    pub country: Option<String>,
+}
+
+/// A product as `popular` lists it: its summary and its scans in the window.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct PopularProduct {
+    #[serde(flatten)]
+    pub product: SearchSummary,
+    pub scans: u64,
+}
+
+impl PopularProduct {
+    fn new(product: Product, scans: u64) -> Self {
+        let hit = SearchHit {
+            product,
+            score: None,
+            display_name: None,
+            highlights: None,
+        };
+        PopularProduct {
+            product: SearchSummary::from(hit),
+            scans,
+        }
+    }
+}
+
+/// The `limit` products scanned most on `days`, most first, of those sold in `country`
+/// if given. Codes no product has any more are passed over.
+pub async fn find_popular(
+    scans: &dyn ScanCounts,
+    products: &dyn ProductRepository,
+    days: &[NaiveDate],
+    country: Option<&str>,
+    limit: usize,
+) -> Result<Vec<PopularProduct>> {
+    let candidates = match country {
+        Some(_) => limit * COUNTRY_CANDIDATES,
+        None => limit,
+    };
+    let top = scans.top(days, candidates).await?;
+    let codes = top.iter().map(|(code, _)| code.clone()).collect();
+    let mut found: HashMap<String, Product> = products
+        .find_by_codes(codes, top.len())
+        .await?
+        .into_iter()
+        .map(|product| (product.code.clone(), product))
+        .collect();
+    let sold_in_country = |product: &Product| {
+        country.is_none_or(|country| product.countries.iter().flatten().any(|c| c == country))
+    };
+    Ok(top
+        .into_iter()
+        .filter_map(|(code, scans)| Some((found.remove(&code)?, scans)))
+        .filter(|(product, _)| sold_in_country(product))
+        .take(limit)
+        .map(|(product, scans)| PopularProduct::new(product, scans))
+        .collect())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/products/popular",
+    tag = "v1",
+    params(PopularParams),
+    responses(
+        (status = 200, description = "The products scanned most in the window, most first, as summaries with their `scans`.", body = Vec<PopularProduct>, headers(("X-Default-Country" = String, description = "The `DEFAULT_COUNTRY_TAG` the list was scoped to, as no `country` was named."))),
+        (status = 400, description = "An invalid `window` or `limit`.", body = ErrorBody),
+        (status = 500, description = "MongoDB or Redis failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, params), fields(query = ?params))]
+pub async fn list_popular_products(
+    State(state): State<Arc<AppState>>,
+    Query(mut params): Query<PopularParams>,
+) -> Result<(DefaultCountry, Json<Vec<PopularProduct>>)> {
+    params.validate()?;
+    let default_country = scope_country(&mut params.country, state.default_country.as_deref());
+    let window = parse_window(params.window.as_deref())?;
+    let days = window_days(Utc::now().date_naive(), window);
+    let country = normalize_tags(params.country.as_deref()).pop();
+    let popular = find_popular(
+        state.scans.as_ref(),
+        state.products.as_ref(),
+        &days,
+        country.as_deref(),
+        params.limit.unwrap_or(DEFAULT_LIMIT),
+    )
+    .await?;
+    info!(
+        "Returning {} popular products over {} days",
+        popular.len(),
+        window
+    );
+    Ok((default_country, Json(popular)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::MemoryProducts;
+    use yoloeats_domain::fixtures::ProductFixture;
+
+    fn day(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    #[test]
+    fn windows_are_whole_days_up_to_today() {
+        assert_eq!(parse_window(None).unwrap(), 7);
+        assert_eq!(parse_window(Some(" ")).unwrap(), 7);
+        assert_eq!(parse_window(Some("1d")).unwrap(), 1);
+        assert_eq!(parse_window(Some("30d")).unwrap(), 30);
+        for window in ["0d", "31d", "7", "d", "-1d", "1w", "week"] {
+            assert!(
+                matches!(parse_window(Some(window)), Err(ServiceError::BadRequest(_))),
+                "{}",
+                window
+            );
+        }
+
+        assert_eq!(window_days(day("2024-06-10"), 1), [day("2024-06-10")]);
+        assert_eq!(
+            window_days(day("2025-01-02"), 4),
+            [
+                day("2025-01-02"),
+                day("2025-01-01"),
+                day("2024-12-31"),
+                day("2024-12-30"),
+            ]
+        );
+        assert_eq!(window_days(day("2024-03-01"), 30).len(), 30);
+        assert_eq!(
+            window_days(day("2024-03-01"), 2)[1],
+            day("2024-02-29"),
+            "a leap day"
+        );
+    }
+
+    #[test]
+    fn day_sets_outlive_every_window_that_reads_them() {
+        let scanned = day("2024-06-10");
+        let last_reader = scanned + Days::new(u64::from(MAX_WINDOW_DAYS) - 1);
+        assert!(window_days(last_reader, MAX_WINDOW_DAYS).contains(&scanned));
+        assert!(!window_days(last_reader + Days::new(1), MAX_WINDOW_DAYS).contains(&scanned));
+
+        let last_read_ends = (last_reader + Days::new(1))
+            .and_time(NaiveTime::MIN)
+            .and_utc();
+        let expiry = day_key_expiry(scanned);
+        assert!(expiry >= last_read_ends);
+        assert!(expiry - last_read_ends <= chrono::Duration::days(1));
+    }
+
+    #[test]
+    fn keys_name_the_code_or_the_days() {
+        assert_eq!(total_key("4000417025005"), "scan_count:4000417025005");
+        assert_eq!(day_key(day("2024-06-10")), "scans:2024-06-10");
+        assert_eq!(union_key(day("2024-06-10"), 7), "scans:2024-06-10:7d");
+        assert!(!day_key(day("2024-06-10")).starts_with(TOTAL_KEY_PREFIX));
+    }
+
+    #[tokio::test]
+    async fn memory_counts_add_up_over_the_window_and_are_taken_once() {
+        let scans = MemoryScanCounts::default();
+        for (code, date) in [
+            ("a", "2024-06-08"),
+            ("a", "2024-06-10"),
+            ("b", "2024-06-10"),
+            ("b", "2024-06-10"),
+            ("b", "2024-06-01"),
+            ("c", "2024-06-09"),
+        ] {
+            scans.record(code, day(date)).await.unwrap();
+        }
+        let week = window_days(day("2024-06-10"), 7);
+        assert_eq!(
+            scans.top(&week, 10).await.unwrap(),
+            [
+                ("b".to_string(), 2),
+                ("a".to_string(), 2),
+                ("c".to_string(), 1)
+            ]
+        );
+        assert_eq!(
+            scans.top(&week[..1], 1).await.unwrap(),
+            [("b".to_string(), 2)]
+        );
+
+        let mut totals = scans.take_totals().await.unwrap();
+        totals.sort();
+        assert_eq!(
+            totals,
+            [
+                ("a".to_string(), 2),
+                ("b".to_string(), 3),
+                ("c".to_string(), 1)
+            ]
+        );
+        assert!(scans.take_totals().await.unwrap().is_empty());
+        scans.restore_totals(&totals[..1]).await.unwrap();
+        assert_eq!(scans.take_totals().await.unwrap(), [("a".to_string(), 2)]);
+    }
+
+    #[tokio::test]
+    async fn memory_days_are_dropped_once_out_of_every_window() {
+        let scans = MemoryScanCounts::default();
+        let first = day("2024-06-01");
+        scans.record("a", first).await.unwrap();
+        scans
+            .record("b", first + Days::new(u64::from(MAX_WINDOW_DAYS)))
+            .await
+            .unwrap();
+        assert_eq!(scans.top(&[first], 10).await.unwrap().len(), 1);
+        scans
+            .record("b", first + Days::new(u64::from(MAX_WINDOW_DAYS) + 1))
+            .await
+            .unwrap();
+        assert!(scans.top(&[first], 10).await.unwrap().is_empty());
+    }
+
+    async fn catalog() -> MemoryProducts {
+        let products = MemoryProducts::default();
+        for (code, country) in [("a", "en:germany"), ("b", "en:france"), ("c", "en:germany")] {
+            products
+                .insert(ProductFixture::new(code).with_countries([country]).build())
+                .await
+                .unwrap();
+        }
+        products
+    }
+
+    #[tokio::test]
+    async fn popular_products_are_read_for_the_top_codes() {
+        let (products, scans) = (catalog().await, MemoryScanCounts::default());
+        let today = day("2024-06-10");
+        for code in ["a", "b", "b", "b", "c", "c", "gone", "gone", "gone", "gone"] {
+            scans.record(code, today).await.unwrap();
+        }
+        let listed = |popular: Vec<PopularProduct>| -> Vec<(String, u64)> {
+            popular
+                .into_iter()
+                .map(|popular| (popular.product.code, popular.scans))
+                .collect()
+        };
+
+        let popular = find_popular(&scans, &products, &[today], None, 2)
+            .await
+            .unwrap();
+        assert_eq!(
+            listed(popular),
+            [("b".to_string(), 3)],
+            "gone is passed over"
+        );
+        let popular = find_popular(&scans, &products, &[today], Some("en:germany"), 5)
+            .await
+            .unwrap();
+        assert_eq!(
+            listed(popular),
+            [("c".to_string(), 2), ("a".to_string(), 1)]
+        );
+        let popular = find_popular(&scans, &products, &[day("2024-06-09")], None, 5)
+            .await
+            .unwrap();
+        assert!(popular.is_empty());
+    }
+
+    #[tokio::test]
+    async fn folding_moves_the_totals_into_the_products() {
+        let (products, scans) = (catalog().await, MemoryScanCounts::default());
+        for code in ["a", "a", "c"] {
+            scans.record(code, day("2024-06-10")).await.unwrap();
+        }
+        assert_eq!(fold_scans(&scans, &products).await.unwrap(), 2);
+        assert_eq!(fold_scans(&scans, &products).await.unwrap(), 0);
+        scans.record("a", day("2024-06-11")).await.unwrap();
+        fold_scans(&scans, &products).await.unwrap();
+
+        for (code, scan_count) in [("a", Some(3)), ("b", None), ("c", Some(1))] {
+            let product = products.find_by_code(code).await.unwrap().unwrap();
+            assert_eq!(product.scan_count, scan_count, "{}", code);
+        }
+    }
+}
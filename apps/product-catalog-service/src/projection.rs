@@ -0,0 +1,423 @@
+//! Shaping single-product responses: `?fields=code,product_name,image_small_url` returns
+//! only the named top-level fields, for clients like the scanner that don't need the
+//! ingredients text and the tag lists, and `include_completeness=true` adds the
+//! product's [`completeness`]. A v2 product also gets a `displayName` in the reader's
+//! language, which `fields` can name too; see [`crate::language`]. v1 responses are
+//! frozen and carry no display name.
+//!
+//! The names are checked against the response model's schema, so each API version takes
+//! its own field names. The lookup and its cache entry stay the full product; the
+//! projection is applied to its JSON on the way out. `code` is always kept.
+
+use crate::{
+    errors::{Result, ServiceError},
+    etag::{Conditional, IfNoneMatch, localized_etag},
+    language::{AcceptLanguage, display_name},
+    models::{Product, completeness},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use utoipa::{
+    IntoParams, PartialSchema,
+    openapi::{RefOr, Schema},
+};
+
+/// Kept whatever the projection names.
+pub const ALWAYS_INCLUDED: &str = "code";
+
+/// Where a response carries its product's [`completeness`].
+pub const COMPLETENESS_FIELD: &str = "completeness";
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ProductResponseParams {
+    /// Comma-separated top-level fields to return; `code` always is. All of them when
+    /// absent.
+    #[param(example = "code,product_name,image_small_url,nutrition_grade_fr")]
+    pub fields: Option<String>,
+    /// Adds the product's `completeness`, 0 to 100.
+    #[serde(default)]
+    pub include_completeness: bool,
+    /// The language to show a v2 product's `displayName` in, e.g. `de`; wins over
+    /// `Accept-Language`. Ignored by v1.
+    #[param(example = "de")]
+    pub lang: Option<String>,
+}
+
+/// The top-level fields of `T`'s JSON, as its schema names them.
+pub fn schema_fields<T: PartialSchema>() -> BTreeSet<String> {
+    match T::schema() {
+        RefOr::T(Schema::Object(object)) => object.properties.into_keys().collect(),
+        _ => BTreeSet::new(),
+    }
+}
+
+/// The fields a response is limited to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Projection {
+    fields: BTreeSet<String>,
+}
+
+impl Projection {
+    /// The projection a `fields` parameter asks for, `None` without one. A name not in
+    /// `allowed` is a bad request.
+    pub fn parse(fields: Option<&str>, allowed: &BTreeSet<String>) -> Result<Option<Self>> {
+        let Some(fields) = fields else {
+            return Ok(None);
+        };
+        let mut names: BTreeSet<String> = fields
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect();
+        let unknown: Vec<&str> = names
+            .iter()
+            .filter(|name| !allowed.contains(*name))
+            .map(String::as_str)
+            .collect();
+        if !unknown.is_empty() {
+            return Err(ServiceError::BadRequest(format!(
+                "Unknown fields: {}. Expected any of: {}",
+                unknown.join(", "),
+                allowed.iter().cloned().collect::<Vec<_>>().join(", ")
+            )));
+        }
+        names.insert(ALWAYS_INCLUDED.to_string());
+        Ok(Some(Projection { fields: names }))
+    }
+
+    /// `value` without the top-level fields not projected. Anything but an object is
+    /// returned as is.
+    pub fn apply(&self, value: Value) -> Value {
+        match value {
+            Value::Object(object) => Value::Object(
+                object
+                    .into_iter()
+                    .filter(|(name, _)| self.fields.contains(name))
+                    .collect(),
+            ),
+            other => other,
+        }
+    }
+}
+
+/// How a product response is shaped, checked before the lookup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProductShape {
+    projection: Option<Projection>,
+    include_completeness: bool,
+    languages: Vec<String>,
+    display_field: Option<&'static str>,
+}
+
+impl ProductShape {
+    /// The shape `params` ask for, with `allowed` the fields they may name. It carries
+    /// no display name and keeps the stored ETag.
+    pub fn parse(params: &ProductResponseParams, allowed: &BTreeSet<String>) -> Result<Self> {
+        Ok(ProductShape {
+            projection: Projection::parse(params.fields.as_deref(), allowed)?,
+            include_completeness: params.include_completeness,
+            languages: Vec::new(),
+            display_field: None,
+        })
+    }
+
+    /// The shape with the product's name for a reader with `accept_language` in `field`,
+    /// `params.lang` winning over the header. The ETag then has the reader's languages
+    /// folded in.
+    pub fn named(
+        self,
+        field: &'static str,
+        params: &ProductResponseParams,
+        accept_language: &AcceptLanguage,
+    ) -> Self {
+        ProductShape {
+            languages: accept_language.preferred(params.lang.as_deref()),
+            display_field: Some(field),
+            ..self
+        }
+    }
+
+    /// The `If-None-Match` tags `sent` for this shape's languages, as the stored ETags
+    /// the lookup compares; see [`IfNoneMatch::for_languages`].
+    pub fn if_none_match(&self, sent: &IfNoneMatch) -> IfNoneMatch {
+        sent.for_languages(&self.languages)
+    }
+
+    /// The found product as JSON in this shape, `to_json` making its API version's
+    /// model of it. The ETag has the shape's languages folded in.
+    pub fn apply<T: Serialize>(
+        &self,
+        found: Conditional<Product>,
+        to_json: impl FnOnce(Product) -> T,
+    ) -> Result<Conditional<Value>> {
+        let (etag, product) = match found {
+            Conditional::NotModified { etag } => {
+                return Ok(Conditional::NotModified {
+                    etag: localized_etag(&etag, &self.languages),
+                });
+            }
+            Conditional::Modified { etag, value } => {
+                (localized_etag(&etag, &self.languages), value)
+            }
+        };
+        let score = self.include_completeness.then(|| completeness(&product));
+        let name = self.display_field.map(|field| {
+            let name = display_name(
+                product.product_name.as_deref(),
+                product.product_name_langs.as_ref(),
+                &self.languages,
+            );
+            (field, name)
+        });
+        let mut value = serde_json::to_value(to_json(product)).map_err(|e| {
+            ServiceError::Internal(format!("Failed to serialize the product: {}", e))
+        })?;
+        if let (Some((field, name)), Value::Object(object)) = (name, &mut value) {
+            object.insert(field.to_string(), name.into());
+        }
+        let mut value = match &self.projection {
+            Some(projection) => projection.apply(value),
+            None => value,
+        };
+        if let (Some(score), Value::Object(object)) = (score, &mut value) {
+            object.insert(COMPLETENESS_FIELD.to_string(), score.into());
+        }
+        Ok(Conditional::Modified { etag, value })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::v2::ProductV2;
+    use serde_json::json;
+    use yoloeats_domain::fixtures::ProductFixture;
+
+    fn allowed() -> BTreeSet<String> {
+        schema_fields::<Product>()
+    }
+
+    fn product() -> Value {
+        json!({
+            "_id": "65f0c0ffee0000000000beef",
+            "code": "4000417025005",
+            "product_name": "Alpine milk chocolate",
+            "ingredients_text": "sugar, cocoa butter, milk powder",
+            "image_small_url": "https://images.example/small.jpg",
+            "nutrition_grade_fr": "e",
+            "brands_tags": ["alpine"],
+        })
+    }
+
+    #[test]
+    fn allowlist_has_the_serialized_names() {
+        let allowed = allowed();
+        for name in [
+            "_id",
+            "code",
+            "product_name",
+            "brands_tags",
+            "image_small_url",
+            "nutrition_grade_fr",
+            "last_modified_datetime",
+        ] {
+            assert!(allowed.contains(name), "{}", name);
+        }
+        assert!(!allowed.contains("brands"));
+        assert!(!allowed.contains("last_modified_at"));
+
+        let v2 = schema_fields::<ProductV2>();
+        for name in ["code", "name", "imageSmallUrl", "nutriscore"] {
+            assert!(v2.contains(name), "{}", name);
+        }
+        assert!(!v2.contains("image_small_url"));
+    }
+
+    #[test]
+    fn keeps_only_the_requested_fields_and_the_code() {
+        let projection = Projection::parse(
+            Some("product_name, image_small_url,,nutrition_grade_fr"),
+            &allowed(),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            projection.apply(product()),
+            json!({
+                "code": "4000417025005",
+                "product_name": "Alpine milk chocolate",
+                "image_small_url": "https://images.example/small.jpg",
+                "nutrition_grade_fr": "e",
+            })
+        );
+
+        let only_code = Projection::parse(Some(""), &allowed()).unwrap().unwrap();
+        assert_eq!(
+            only_code.apply(product()),
+            json!({ "code": "4000417025005" })
+        );
+    }
+
+    #[test]
+    fn fields_missing_from_the_document_are_left_out() {
+        let projection = Projection::parse(Some("code,generic_name"), &allowed())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            projection.apply(product()),
+            json!({ "code": "4000417025005" })
+        );
+    }
+
+    #[test]
+    fn no_fields_parameter_is_no_projection() {
+        assert_eq!(Projection::parse(None, &allowed()).unwrap(), None);
+    }
+
+    #[test]
+    fn unknown_fields_are_a_bad_request() {
+        for fields in [
+            "code,secret_sauce",
+            "brands",
+            "product_name,NUTRITION_GRADE_FR",
+        ] {
+            let err = Projection::parse(Some(fields), &allowed()).unwrap_err();
+            assert!(matches!(err, ServiceError::BadRequest(_)), "{}", fields);
+        }
+        let err = Projection::parse(Some("secret_sauce,code"), &allowed()).unwrap_err();
+        assert!(err.to_string().contains("Unknown fields: secret_sauce."));
+    }
+
+    #[test]
+    fn shapes_leave_not_modified_alone() {
+        let shape = ProductShape::parse(
+            &ProductResponseParams {
+                fields: Some("product_name".to_string()),
+                include_completeness: true,
+                lang: None,
+            },
+            &allowed(),
+        )
+        .unwrap();
+        let etag = "W/\"x-1\"".to_string();
+        assert_eq!(
+            shape
+                .apply(Conditional::NotModified { etag: etag.clone() }, |p| p)
+                .unwrap(),
+            Conditional::NotModified { etag }
+        );
+    }
+
+    #[test]
+    fn shapes_add_the_completeness_after_the_projection() {
+        let product: Product = ProductFixture::new("4000417025005")
+            .named("Ritter Sport")
+            .build();
+        let found = || Conditional::Modified {
+            etag: "W/\"x-1\"".to_string(),
+            value: product.clone(),
+        };
+        let shape = |fields: Option<&str>, include_completeness| {
+            ProductShape::parse(
+                &ProductResponseParams {
+                    fields: fields.map(str::to_string),
+                    include_completeness,
+                    lang: None,
+                },
+                &allowed(),
+            )
+            .unwrap()
+        };
+        let value = |found: Conditional<Value>| match found {
+            Conditional::Modified { value, .. } => value,
+            Conditional::NotModified { .. } => panic!("not modified"),
+        };
+
+        let whole = value(shape(None, false).apply(found(), |p| p).unwrap());
+        assert!(whole.get(COMPLETENESS_FIELD).is_none());
+        assert_eq!(whole, serde_json::to_value(&product).unwrap());
+
+        let scored = value(
+            shape(Some("product_name"), true)
+                .apply(found(), |p| p)
+                .unwrap(),
+        );
+        assert_eq!(
+            scored,
+            json!({
+                "code": "4000417025005",
+                "product_name": "Ritter Sport",
+                "completeness": completeness(&product),
+            })
+        );
+
+        let v2 = value(shape(None, true).apply(found(), ProductV2::from).unwrap());
+        assert_eq!(v2["name"], "Ritter Sport");
+        assert_eq!(v2[COMPLETENESS_FIELD], completeness(&product));
+    }
+
+    #[test]
+    fn shapes_name_the_product_in_the_readers_language() {
+        let mut product: Product = ProductFixture::new("4000417025005")
+            .named("Alpine milk chocolate")
+            .build();
+        product.product_name_langs = Some(
+            [("de", "Alpenmilch Schokolade"), ("fr", "Chocolat au lait")]
+                .map(|(lang, name)| (lang.to_string(), name.to_string()))
+                .into(),
+        );
+        let found = || Conditional::Modified {
+            etag: "W/\"x-1\"".to_string(),
+            value: product.clone(),
+        };
+        let header = AcceptLanguage(Some("de-DE,de;q=0.9,en;q=0.8".to_string()));
+        let mut v2_fields = schema_fields::<ProductV2>();
+        v2_fields.insert("displayName".to_string());
+        let shape = |fields: Option<&str>, lang: Option<&str>| {
+            let params = ProductResponseParams {
+                fields: fields.map(str::to_string),
+                include_completeness: false,
+                lang: lang.map(str::to_string),
+            };
+            ProductShape::parse(&params, &v2_fields)
+                .unwrap()
+                .named("displayName", &params, &header)
+        };
+        let value = |found: Conditional<Value>| match found {
+            Conditional::Modified { value, .. } => value,
+            Conditional::NotModified { .. } => panic!("not modified"),
+        };
+
+        let german = shape(None, None).apply(found(), ProductV2::from).unwrap();
+        assert!(matches!(&german, Conditional::Modified { etag, .. } if etag != "W/\"x-1\""));
+        let german = value(german);
+        assert_eq!(german["displayName"], "Alpenmilch Schokolade");
+        assert_eq!(german["nameLangs"]["fr"], "Chocolat au lait");
+
+        let french = shape(Some("displayName"), Some("fr"))
+            .apply(found(), ProductV2::from)
+            .unwrap();
+        assert_eq!(
+            value(french),
+            json!({ "code": "4000417025005", "displayName": "Chocolat au lait" })
+        );
+        let projected_out = shape(Some("name"), None)
+            .apply(found(), ProductV2::from)
+            .unwrap();
+        assert!(value(projected_out).get("displayName").is_none());
+
+        let params = ProductResponseParams {
+            lang: Some("fr".to_string()),
+            ..Default::default()
+        };
+        let v1 = ProductShape::parse(&params, &schema_fields::<Product>())
+            .unwrap()
+            .apply(found(), |p| p)
+            .unwrap();
+        assert!(matches!(&v1, Conditional::Modified { etag, .. } if etag == "W/\"x-1\""));
+        assert_eq!(value(v1), serde_json::to_value(&product).unwrap());
+    }
+}
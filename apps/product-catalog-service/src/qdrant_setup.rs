@@ -0,0 +1,205 @@
+//! The Qdrant collection behind recommendations and semantic search, set up at startup.
+//!
+//! A fresh deployment has no collection, and every vector request would fail until the
+//! sync worker or the vectorization script created one. [`ensure_qdrant_setup`] creates
+//! it as the worker does, with keyword indexes on the payload fields the catalog filters
+//! on, and adds any of those indexes an existing collection lacks. It changes nothing
+//! that is already there, so it runs on every start.
+//!
+//! Nor does it change the dimensions of an existing collection, so a collection made for
+//! another model stays as it is. [`check_vector_size`] stops startup when the embedding
+//! model's vectors don't fit it, instead of every upsert and text query failing later.
+
+use crate::{
+    errors::{Result, ServiceError},
+    handlers::QDRANT_COLLECTION_NAME,
+};
+use qdrant_client::{
+    Qdrant,
+    qdrant::{
+        CreateCollectionBuilder, CreateFieldIndexCollectionBuilder, Distance, FieldType,
+        VectorParamsBuilder, vectors_config,
+    },
+};
+use std::env;
+use tracing::{debug, info};
+
+/// Dimensions of a new collection's vectors; must match the embedding model.
+pub const QDRANT_VECTOR_SIZE_ENV: &str = "QDRANT_VECTOR_SIZE";
+/// Distance of a new collection: `cosine` (the default), `dot`, `euclid` or `manhattan`.
+pub const QDRANT_DISTANCE_ENV: &str = "QDRANT_DISTANCE";
+/// The sync worker's default dimensions.
+pub const DEFAULT_VECTOR_SIZE: u64 = 384;
+
+/// Payload fields the catalog filters on; the same the sync worker indexes.
+pub const INDEXED_PAYLOAD_FIELDS: [&str; 4] =
+    ["code", "labels_tags", "allergens_tags", "countries_tags"];
+
+/// How a missing collection is created.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CollectionConfig {
+    pub vector_size: u64,
+    pub distance: Distance,
+}
+
+impl Default for CollectionConfig {
+    fn default() -> Self {
+        CollectionConfig {
+            vector_size: DEFAULT_VECTOR_SIZE,
+            distance: Distance::Cosine,
+        }
+    }
+}
+
+impl CollectionConfig {
+    /// Reads [`QDRANT_VECTOR_SIZE_ENV`] and [`QDRANT_DISTANCE_ENV`]; unset or empty keeps
+    /// the default.
+    pub fn from_env() -> Result<Self> {
+        let var = |name: &str| env::var(name).ok().filter(|v| !v.trim().is_empty());
+        let defaults = CollectionConfig::default();
+        Ok(CollectionConfig {
+            vector_size: match var(QDRANT_VECTOR_SIZE_ENV) {
+                Some(raw) => parse_vector_size(&raw)?,
+                None => defaults.vector_size,
+            },
+            distance: match var(QDRANT_DISTANCE_ENV) {
+                Some(raw) => parse_distance(&raw)?,
+                None => defaults.distance,
+            },
+        })
+    }
+}
+
+fn parse_vector_size(raw: &str) -> Result<u64> {
+    match raw.trim().parse::<u64>() {
+        Ok(size) if size > 0 => Ok(size),
+        _ => Err(ServiceError::InvalidVariable(format!(
+            "{} must be a positive integer, got '{}'",
+            QDRANT_VECTOR_SIZE_ENV, raw
+        ))),
+    }
+}
+
+fn parse_distance(raw: &str) -> Result<Distance> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "cosine" => Ok(Distance::Cosine),
+        "dot" => Ok(Distance::Dot),
+        "euclid" => Ok(Distance::Euclid),
+        "manhattan" => Ok(Distance::Manhattan),
+        _ => Err(ServiceError::InvalidVariable(format!(
+            "{} must be 'cosine', 'dot', 'euclid' or 'manhattan', got '{}'",
+            QDRANT_DISTANCE_ENV, raw
+        ))),
+    }
+}
+
+/// Creates the collection if it is missing and the payload indexes it lacks.
+pub async fn ensure_qdrant_setup(qdrant: &Qdrant, config: &CollectionConfig) -> Result<()> {
+    let name = QDRANT_COLLECTION_NAME;
+    if qdrant.collection_exists(name).await? {
+        debug!("Qdrant collection '{}' exists", name);
+    } else {
+        info!(
+            "Creating Qdrant collection '{}' ({} dimensions, {:?} distance)",
+            name, config.vector_size, config.distance
+        );
+        qdrant
+            .create_collection(CreateCollectionBuilder::new(name).vectors_config(
+                VectorParamsBuilder::new(config.vector_size, config.distance),
+            ))
+            .await?;
+    }
+
+    let indexed = qdrant
+        .collection_info(name)
+        .await?
+        .result
+        .map(|info| info.payload_schema)
+        .unwrap_or_default();
+    for field in INDEXED_PAYLOAD_FIELDS {
+        if indexed.contains_key(field) {
+            continue;
+        }
+        info!("Indexing payload field '{}' of '{}'", field, name);
+        qdrant
+            .create_field_index(
+                CreateFieldIndexCollectionBuilder::new(name, field, FieldType::Keyword).wait(true),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+/// Fails unless the collection's vectors have the `dimensions` of the embedding model.
+pub async fn check_vector_size(qdrant: &Qdrant, dimensions: u64) -> Result<()> {
+    let vectors = qdrant
+        .collection_info(QDRANT_COLLECTION_NAME)
+        .await?
+        .result
+        .and_then(|info| info.config)
+        .and_then(|config| config.params)
+        .and_then(|params| params.vectors_config)
+        .and_then(|vectors| vectors.config);
+    let size = match vectors {
+        Some(vectors_config::Config::Params(params)) => Some(params.size),
+        _ => None,
+    };
+    vector_size_fits(size, dimensions)?;
+    debug!(
+        "Qdrant collection '{}' takes the {} dimensions of the embeddings",
+        QDRANT_COLLECTION_NAME, dimensions
+    );
+    Ok(())
+}
+
+/// Whether a collection of `size` dimensions, `None` for one of named vectors the catalog
+/// doesn't use, takes vectors of `dimensions`.
+fn vector_size_fits(size: Option<u64>, dimensions: u64) -> Result<()> {
+    match size {
+        Some(size) if size == dimensions => Ok(()),
+        Some(size) => Err(ServiceError::InvalidVariable(format!(
+            "{} is {} but Qdrant collection '{}' has {} dimensions",
+            QDRANT_VECTOR_SIZE_ENV, dimensions, QDRANT_COLLECTION_NAME, size
+        ))),
+        None => Err(ServiceError::InvalidVariable(format!(
+            "{} is {} but Qdrant collection '{}' has no unnamed vectors",
+            QDRANT_VECTOR_SIZE_ENV, dimensions, QDRANT_COLLECTION_NAME
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vector_sizes_are_positive_integers() {
+        assert_eq!(parse_vector_size(" 768 ").unwrap(), 768);
+        for raw in ["0", "-1", "large"] {
+            assert!(matches!(
+                parse_vector_size(raw),
+                Err(ServiceError::InvalidVariable(msg)) if msg.contains(QDRANT_VECTOR_SIZE_ENV)
+            ));
+        }
+    }
+
+    #[test]
+    fn distances_parse_loosely() {
+        assert_eq!(parse_distance("Cosine").unwrap(), Distance::Cosine);
+        assert_eq!(parse_distance(" dot").unwrap(), Distance::Dot);
+        assert_eq!(parse_distance("EUCLID").unwrap(), Distance::Euclid);
+        assert_eq!(parse_distance("manhattan").unwrap(), Distance::Manhattan);
+        assert!(parse_distance("hamming").is_err());
+    }
+
+    #[test]
+    fn embeddings_must_fit_the_collection() {
+        assert!(vector_size_fits(Some(384), 384).is_ok());
+        for size in [Some(768), None] {
+            assert!(matches!(
+                vector_size_fits(size, 384),
+                Err(ServiceError::InvalidVariable(msg)) if msg.contains(QDRANT_VECTOR_SIZE_ENV)
+            ));
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! `/api/v1/products/recent`: the newest products, by when they were created or last
+//! updated, for curators and power users to see what's new.
+//!
+//! Products are listed as [`SearchSummary`]s, newest first and then by descending `_id`,
+//! and the cursor resumes before the last one listed. Deletes are hard deletes, so
+//! deleted products are simply gone from both feeds. The first page of each feed is
+//! cached in Redis per country for [`RECENT_CACHE_TTL_SECS`] and never invalidated:
+//! what is new shows up after at most that long.
+
+use crate::{
+    catalog_metrics::{CacheOutcome, record_cache_lookup},
+    default_country::{DefaultCountry, scope_country},
+    errors::{Result, ServiceError},
+    handlers::connect_cache,
+    models::SearchSummary,
+    repository::{ProductFilter, RecentFrom, RecentKind},
+    state::AppState,
+    taxonomy::normalize_tags,
+    tunables::{RECENT_CACHE_TTL_SECS, cache_ttl},
+};
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use bson::oid::ObjectId;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{debug, error, info, instrument, warn};
+use utoipa::IntoParams;
+use yoloeats_domain::ErrorBody;
+use yoloeats_pagination::{Page, PageLimit, PageParams, PaginationError};
+
+/// Page sizes for [`list_recent_products`].
+pub struct RecentPageLimit;
+
+impl PageLimit for RecentPageLimit {
+    const DEFAULT: u64 = 20;
+    const MAX: u64 = 100;
+}
+
+/// Where the next recent page starts: before the last product of the previous one. The
+/// time is RFC 3339 to the nanosecond, as in-memory products are stamped.
+#[derive(Debug, Serialize, Deserialize)]
+struct RecentCursor {
+    at: String,
+    before_id: ObjectId,
+}
+
+/// Query of `GET /api/v1/products/recent` besides the page.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RecentParams {
+    /// `created` lists products by when they were created, `updated` by when they were
+    /// last modified; `created` when absent.
+    #[param(example = "updated")]
+    pub kind: Option<String>,
+    /// Only products sold in this country; `all` lists every country even with a
+    /// `DEFAULT_COUNTRY_TAG`.
+    pub country: Option<String>,
+}
+
+/// The `kind` of a recent request. Taken as a string so a bad value gets our message,
+/// not serde's.
+pub fn parse_kind(kind: Option<&str>) -> Result<RecentKind> {
+    match kind.map(str::trim).unwrap_or_default() {
+        "" | "created" => Ok(RecentKind::Created),
+        "updated" => Ok(RecentKind::Updated),
+        other => Err(ServiceError::BadRequest(format!(
+            "Invalid kind '{}': expected created or updated",
+            other
+        ))),
+    }
+}
+
+/// Where the first page of a feed is cached; one per kind, country and page size.
+pub fn recent_cache_key(kind: RecentKind, country: Option<&str>, limit: u64) -> String {
+    format!(
+        "recent:{}:{}:{}",
+        kind.field(),
+        country.unwrap_or("all"),
+        limit
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/products/recent",
+    tag = "v1",
+    params(
+        RecentParams,
+        PageParams<RecentPageLimit>,
+    ),
+    responses(
+        (status = 200, description = "One page of product summaries, newest first.", body = Page<SearchSummary>, headers(("X-Default-Country" = String, description = "The `DEFAULT_COUNTRY_TAG` the feed was scoped to, as no `country` was named."))),
+        (status = 400, description = "An invalid `kind`, `limit` or cursor.", body = ErrorBody),
+        (status = 500, description = "MongoDB failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, params), fields(query = ?params))]
+pub async fn list_recent_products(
+    State(state): State<Arc<AppState>>,
+    Query(mut params): Query<RecentParams>,
+    page: PageParams<RecentPageLimit>,
+) -> Result<(DefaultCountry, Json<Page<SearchSummary>>)> {
+    let kind = parse_kind(params.kind.as_deref())?;
+    let default_country = scope_country(&mut params.country, state.default_country.as_deref());
+    let country = normalize_tags(params.country.as_deref()).pop();
+    let from = match page.position::<RecentCursor>(&state.cursor_codec)? {
+        Some(cursor) => Some(RecentFrom {
+            at: DateTime::parse_from_rfc3339(&cursor.at)
+                .map_err(|_| PaginationError::InvalidCursor)?
+                .with_timezone(&Utc),
+            before_id: cursor.before_id,
+        }),
+        None => None,
+    };
+    debug!(
+        "Recent page: kind={:?}, limit={}, from={:?}",
+        kind, page.limit, from
+    );
+
+    // Only first pages are cached: they are what almost every request asks for.
+    let cache_key = match from {
+        None => Some(recent_cache_key(kind, country.as_deref(), page.limit)),
+        Some(_) => None,
+    };
+    let mut cache_conn = match &cache_key {
+        Some(_) => connect_cache(&state, "recent").await,
+        None => None,
+    };
+    if let (Some(conn), Some(key)) = (cache_conn.as_mut(), &cache_key) {
+        match conn.get(key).await {
+            Ok(Some(cached)) => match serde_json::from_str(&cached) {
+                Ok(products) => {
+                    debug!(key = %key, "Cache hit for recent products");
+                    record_cache_lookup("recent", CacheOutcome::Hit);
+                    return Ok((default_country, Json(products)));
+                }
+                Err(e) => {
+                    error!(key = %key, "Failed to deserialize cached recent products: {}", e);
+                    record_cache_lookup("recent", CacheOutcome::Error);
+                }
+            },
+            Ok(None) => record_cache_lookup("recent", CacheOutcome::Miss),
+            Err(e) => {
+                warn!(key = %key, "Redis GET command failed (recent): {}", e);
+                record_cache_lookup("recent", CacheOutcome::Error);
+            }
+        }
+    }
+
+    let filter = ProductFilter {
+        countries: country.into_iter().collect(),
+        ..Default::default()
+    };
+    let recent = state
+        .products
+        .recent(kind, &filter, from, page.limit + 1)
+        .await?;
+    let products = Page::overfetched(recent, page.limit, |last| {
+        Some(state.cursor_codec.encode(&RecentCursor {
+            at: last.at.to_rfc3339_opts(SecondsFormat::Nanos, true),
+            before_id: last.summary.id?,
+        }))
+    })
+    .map(|product| product.summary);
+    info!("Returning {} recent products", products.items.len());
+
+    if let (Some(conn), Some(key)) = (cache_conn.as_mut(), &cache_key) {
+        match serde_json::to_string(&products) {
+            Ok(json) => {
+                let ttl = cache_ttl(&state.config, RECENT_CACHE_TTL_SECS);
+                if let Err(e) = conn.set_ex(key, &json, ttl).await {
+                    warn!(key = %key, "Failed to cache recent products in Redis: {}", e);
+                }
+            }
+            Err(e) => error!(key = %key, "Failed to serialize recent products: {}", e),
+        }
+    }
+    Ok((default_country, Json(products)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kind_is_created_or_updated() {
+        assert_eq!(parse_kind(None).unwrap(), RecentKind::Created);
+        assert_eq!(parse_kind(Some("")).unwrap(), RecentKind::Created);
+        assert_eq!(parse_kind(Some("created")).unwrap(), RecentKind::Created);
+        assert_eq!(parse_kind(Some(" updated ")).unwrap(), RecentKind::Updated);
+        for kind in ["modified", "Updated", "newest"] {
+            assert!(
+                matches!(parse_kind(Some(kind)), Err(ServiceError::BadRequest(_))),
+                "{}",
+                kind
+            );
+        }
+    }
+
+    #[test]
+    fn first_pages_are_cached_per_kind_country_and_size() {
+        let keys = [
+            recent_cache_key(RecentKind::Created, None, 20),
+            recent_cache_key(RecentKind::Updated, None, 20),
+            recent_cache_key(RecentKind::Created, Some("en:germany"), 20),
+            recent_cache_key(RecentKind::Created, Some("en:france"), 20),
+            recent_cache_key(RecentKind::Created, None, 50),
+        ];
+        for (i, key) in keys.iter().enumerate() {
+            assert!(key.starts_with("recent:"), "{}", key);
+            assert!(!keys[i + 1..].contains(key), "{}", key);
+        }
+    }
+}
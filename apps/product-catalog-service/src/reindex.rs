@@ -0,0 +1,500 @@
+//! `/api/v1/admin/reindex`: rebuilds the Qdrant `product_vectors` collection from
+//! MongoDB, after the embedding model changes or Qdrant is recovered empty.
+//!
+//! A job reads every product in `_id` order, [`BATCH_SIZE`] at a time, embeds each batch
+//! in one request to the embedding service and upserts its points, [`CONCURRENCY`]
+//! batches at once. The points are the sync worker's, built by the shared [`ProductDoc`]
+//! as [`crate::vector_sync`] builds them, so the worker can go on syncing on top. A
+//! missing collection is created first as at startup; one of the wrong dimensions for a
+//! new model has to be dropped before. Points of products gone from MongoDB are left as
+//! they are.
+//!
+//! The job runs in the background of the replica that took the POST. Its progress is
+//! kept in Redis under `reindex:{job_id}` for [`JOB_TTL`], and `reindex:lock` holds the
+//! running job's id, so a second POST answers 409 until it ends. The lock lapses
+//! [`LOCK_TTL`] after the last batch, so a job whose replica died doesn't hold it
+//! forever. Without Redis, Qdrant or an embedding service there is nothing to reindex.
+
+use crate::{
+    catalog_metrics::observe_qdrant,
+    embedding::EMBEDDING_SERVICE_URL_ENV,
+    errors::{Result, ServiceError},
+    handlers::QDRANT_COLLECTION_NAME,
+    qdrant_setup::{CollectionConfig, ensure_qdrant_setup},
+    repository::PRODUCTS_COLLECTION,
+    state::{AppState, Clients},
+};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use bson::{Document, doc, oid::ObjectId};
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use qdrant_client::{
+    Qdrant,
+    qdrant::{PointStruct, UpsertPointsBuilder},
+};
+use redis::{AsyncCommands, Client as RedisClient, Script};
+use rust_database_clients::serde_helpers::chrono_datetime_as_rfc3339_or_bson;
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+use tracing::{Instrument, debug, error, info, instrument, warn};
+use utoipa::ToSchema;
+use yoloeats_domain::{
+    ErrorBody,
+    point::{ProductDoc, point_id},
+};
+use yoloeats_tracing::{current_request_id, with_request_id};
+
+/// Products read, embedded and upserted together.
+pub const BATCH_SIZE: usize = 100;
+/// Batches in flight at once.
+pub const CONCURRENCY: usize = 4;
+/// How long a job's progress can be read after its last batch.
+pub const JOB_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+/// How long the lock outlives the last batch of a job that stopped without releasing it.
+pub const LOCK_TTL: Duration = Duration::from_secs(10 * 60);
+
+const LOCK_KEY: &str = "reindex:lock";
+
+/// Deletes or extends the lock only for the job holding it.
+const RELEASE_SCRIPT: &str = r"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+";
+const REFRESH_SCRIPT: &str = r"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('EXPIRE', KEYS[1], ARGV[2])
+end
+return 0
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReindexStatus {
+    Running,
+    Completed,
+    /// Stopped before the last product: MongoDB, Redis or the collection setup failed.
+    Failed,
+}
+
+/// A reindex job and how far it got.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ReindexJob {
+    pub job_id: String,
+    pub status: ReindexStatus,
+    /// Products in MongoDB when the job started, as its metadata estimates them.
+    pub total: u64,
+    /// Products read so far.
+    pub processed: u64,
+    /// Products whose points were upserted.
+    pub indexed: u64,
+    /// Products left unindexed: unreadable, or in a batch the embedding service or
+    /// Qdrant refused.
+    pub failed: u64,
+    #[serde(with = "chrono_datetime_as_rfc3339_or_bson")]
+    pub started_at: DateTime<Utc>,
+    /// The last batch's time; when it finished, once the job is over.
+    #[serde(with = "chrono_datetime_as_rfc3339_or_bson")]
+    pub updated_at: DateTime<Utc>,
+    /// Why the job failed; `null` unless it did.
+    pub error: Option<String>,
+}
+
+impl ReindexJob {
+    fn started(job_id: String, total: u64, at: DateTime<Utc>) -> Self {
+        ReindexJob {
+            job_id,
+            status: ReindexStatus::Running,
+            total,
+            processed: 0,
+            indexed: 0,
+            failed: 0,
+            started_at: at,
+            updated_at: at,
+            error: None,
+        }
+    }
+
+    fn count(&mut self, batch: BatchOutcome, at: DateTime<Utc>) {
+        self.processed += batch.read;
+        self.indexed += batch.indexed;
+        self.failed += batch.read - batch.indexed;
+        self.updated_at = at;
+    }
+
+    fn finish(&mut self, outcome: Result<()>, at: DateTime<Utc>) {
+        match outcome {
+            Ok(()) => self.status = ReindexStatus::Completed,
+            Err(e) => {
+                self.status = ReindexStatus::Failed;
+                self.error = Some(e.to_string());
+            }
+        }
+        self.updated_at = at;
+    }
+}
+
+/// What became of one batch's products.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BatchOutcome {
+    read: u64,
+    indexed: u64,
+}
+
+fn job_key(job_id: &str) -> String {
+    format!("reindex:{}", job_id)
+}
+
+async fn save(redis: &RedisClient, job: &ReindexJob) -> Result<()> {
+    let json = serde_json::to_string(job).expect("reindex jobs serialize");
+    let mut conn = redis.get_multiplexed_async_connection().await?;
+    let _: () = conn
+        .set_ex(job_key(&job.job_id), json, JOB_TTL.as_secs())
+        .await?;
+    Ok(())
+}
+
+/// Takes the lock for `job_id`; `false` while another job holds it.
+async fn acquire_lock(redis: &RedisClient, job_id: &str) -> Result<bool> {
+    let mut conn = redis.get_multiplexed_async_connection().await?;
+    let set: Option<String> = redis::cmd("SET")
+        .arg(LOCK_KEY)
+        .arg(job_id)
+        .arg("NX")
+        .arg("EX")
+        .arg(LOCK_TTL.as_secs())
+        .query_async(&mut conn)
+        .await?;
+    Ok(set.is_some())
+}
+
+/// Runs `script` on the lock for `job_id`; `false` if the job no longer holds it.
+async fn on_lock(redis: &RedisClient, script: &str, job_id: &str) -> Result<bool> {
+    let mut conn = redis.get_multiplexed_async_connection().await?;
+    let changed: i64 = Script::new(script)
+        .key(LOCK_KEY)
+        .arg(job_id)
+        .arg(LOCK_TTL.as_secs())
+        .invoke_async(&mut conn)
+        .await?;
+    Ok(changed == 1)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/reindex",
+    tag = "admin",
+    params(
+        ("X-Internal-Token" = String, Header, description = "The shared secret of internal callers."),
+    ),
+    responses(
+        (status = 202, description = "The job, started; poll its `job_id` for progress.", body = ReindexJob),
+        (status = 401, description = "No valid internal token.", body = ErrorBody),
+        (status = 409, description = "Another reindex job is running.", body = ErrorBody),
+        (status = 500, description = "MongoDB or Redis failed.", body = ErrorBody),
+        (status = 503, description = "No Qdrant, Redis or embedding service to reindex with.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state))]
+pub async fn start_reindex(
+    State(state): State<Arc<AppState>>,
+) -> Result<(StatusCode, Json<ReindexJob>)> {
+    let (Some(clients), Some(_)) = (&state.clients, &state.embeddings) else {
+        return Err(ServiceError::SemanticSearchUnavailable(format!(
+            "Reindexing needs Qdrant, Redis and {}",
+            EMBEDDING_SERVICE_URL_ENV
+        )));
+    };
+    let redis = &clients.redis_client;
+    let total = clients
+        .mongo_db
+        .collection::<Document>(PRODUCTS_COLLECTION)
+        .estimated_document_count()
+        .await?;
+    let job_id = ObjectId::new().to_hex();
+    if !acquire_lock(redis, &job_id).await? {
+        let running: Option<String> = redis
+            .get_multiplexed_async_connection()
+            .await?
+            .get(LOCK_KEY)
+            .await?;
+        return Err(ServiceError::Conflict(format!(
+            "Reindex job {} is still running",
+            running.unwrap_or_default()
+        )));
+    }
+    let job = ReindexJob::started(job_id, total, Utc::now());
+    if let Err(e) = save(redis, &job).await {
+        on_lock(redis, RELEASE_SCRIPT, &job.job_id).await.ok();
+        return Err(e);
+    }
+    info!(job_id = %job.job_id, "Started reindexing {} products", total);
+
+    let task = run(state.clone(), clients.clone(), job.clone()).in_current_span();
+    match current_request_id() {
+        Some(request_id) => state.tasks.spawn(with_request_id(request_id, task)),
+        None => state.tasks.spawn(task),
+    };
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/reindex/{job_id}",
+    tag = "admin",
+    params(
+        ("job_id" = String, Path, description = "The id the job was started with."),
+        ("X-Internal-Token" = String, Header, description = "The shared secret of internal callers."),
+    ),
+    responses(
+        (status = 200, description = "The job and its counts so far.", body = ReindexJob),
+        (status = 401, description = "No valid internal token.", body = ErrorBody),
+        (status = 404, description = "No such job, or it ended over a week ago.", body = ErrorBody),
+        (status = 500, description = "Redis failed.", body = ErrorBody),
+        (status = 503, description = "No Redis to keep jobs in.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state))]
+pub async fn get_reindex_job(
+    State(state): State<Arc<AppState>>,
+    Path(job_id): Path<String>,
+) -> Result<Json<ReindexJob>> {
+    let Some(clients) = &state.clients else {
+        return Err(ServiceError::SemanticSearchUnavailable(
+            "Reindex jobs are kept in Redis, which in-memory storage has none of".to_string(),
+        ));
+    };
+    let mut conn = clients
+        .redis_client
+        .get_multiplexed_async_connection()
+        .await?;
+    let json: Option<String> = conn.get(job_key(&job_id)).await?;
+    let Some(json) = json else {
+        return Err(ServiceError::NotFound(format!(
+            "Reindex job {} not found",
+            job_id
+        )));
+    };
+    let job = serde_json::from_str(&json)
+        .map_err(|e| ServiceError::Internal(format!("Unreadable reindex job {}: {}", job_id, e)))?;
+    Ok(Json(job))
+}
+
+/// Reindexes everything under `job`, then records how it ended and lets the lock go.
+async fn run(state: Arc<AppState>, clients: Clients, mut job: ReindexJob) {
+    let outcome = reindex(&state, &clients, &mut job).await;
+    match &outcome {
+        Ok(()) => info!(
+            job_id = %job.job_id,
+            "Reindexed {} of {} products read, {} failed",
+            job.indexed, job.processed, job.failed
+        ),
+        Err(e) => error!(
+            job_id = %job.job_id,
+            "Reindexing failed after {} products: {}", job.processed, e
+        ),
+    }
+    job.finish(outcome, Utc::now());
+    let redis = &clients.redis_client;
+    if let Err(e) = save(redis, &job).await {
+        warn!(job_id = %job.job_id, "Failed to record the end of the reindex job: {}", e);
+    }
+    if let Err(e) = on_lock(redis, RELEASE_SCRIPT, &job.job_id).await {
+        warn!(job_id = %job.job_id, "Failed to release the reindex lock, which lapses on its own: {}", e);
+    }
+}
+
+async fn reindex(state: &AppState, clients: &Clients, job: &mut ReindexJob) -> Result<()> {
+    let qdrant = clients.qdrant_client.as_ref();
+    ensure_qdrant_setup(qdrant, &CollectionConfig::from_env()?).await?;
+
+    let cursor = clients
+        .mongo_db
+        .collection::<Document>(PRODUCTS_COLLECTION)
+        .find(doc! {})
+        .sort(doc! { "_id": 1 })
+        .batch_size(BATCH_SIZE as u32)
+        .await?;
+    let mut batches = cursor
+        .try_chunks(BATCH_SIZE)
+        .map_err(|e| ServiceError::from(e.1))
+        .map_ok(|documents| index_batch(state, qdrant, documents))
+        .try_buffer_unordered(CONCURRENCY);
+
+    while let Some(batch) = batches.try_next().await? {
+        job.count(batch, Utc::now());
+        debug!(job_id = %job.job_id, "Reindexed {} of {} products", job.processed, job.total);
+        let redis = &clients.redis_client;
+        if !on_lock(redis, REFRESH_SCRIPT, &job.job_id).await? {
+            return Err(ServiceError::Internal(
+                "The reindex lock lapsed and was taken by another job".to_string(),
+            ));
+        }
+        if let Err(e) = save(redis, job).await {
+            warn!(job_id = %job.job_id, "Failed to record reindex progress: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Indexes the products of `documents`. A batch the embedding service or Qdrant refuses
+/// counts as failed, and the job goes on.
+async fn index_batch(
+    state: &AppState,
+    qdrant: &Qdrant,
+    documents: Vec<Document>,
+) -> Result<BatchOutcome> {
+    let read = documents.len() as u64;
+    let products: Vec<ProductDoc> = documents.into_iter().filter_map(product_doc).collect();
+    if products.is_empty() {
+        return Ok(BatchOutcome { read, indexed: 0 });
+    }
+    match upsert(state, qdrant, &products).await {
+        Ok(()) => Ok(BatchOutcome {
+            read,
+            indexed: products.len() as u64,
+        }),
+        Err(e) => {
+            warn!(
+                "Failed to reindex the {} products from {}: {}",
+                products.len(),
+                products[0].id,
+                e
+            );
+            Ok(BatchOutcome { read, indexed: 0 })
+        }
+    }
+}
+
+/// What the worker reads of a stored product; `None`, logged, for one it can't read.
+fn product_doc(document: Document) -> Option<ProductDoc> {
+    let Ok(id) = document.get_object_id("_id") else {
+        warn!(
+            "Skipping product without an ObjectId _id: {:?}",
+            document.get("_id")
+        );
+        return None;
+    };
+    ProductDoc::from_document(document)
+        .inspect_err(|e| warn!("Skipping unreadable product {}: {}", id, e))
+        .ok()
+}
+
+async fn upsert(state: &AppState, qdrant: &Qdrant, products: &[ProductDoc]) -> Result<()> {
+    let Some(embeddings) = &state.embeddings else {
+        return Err(ServiceError::SemanticSearchUnavailable(format!(
+            "{} is not set",
+            EMBEDDING_SERVICE_URL_ENV
+        )));
+    };
+    let texts: Vec<String> = products.iter().map(ProductDoc::embedding_text).collect();
+    let vectors = embeddings.embed(&texts).await?;
+    let points: Vec<PointStruct> = products
+        .iter()
+        .zip(vectors)
+        .map(|(product, vector)| PointStruct::new(point_id(&product.id), vector, product.payload()))
+        .collect();
+    observe_qdrant(
+        "upsert_points",
+        qdrant.upsert_points(UpsertPointsBuilder::new(QDRANT_COLLECTION_NAME, points).wait(true)),
+    )
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn time(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn jobs_count_their_batches_and_how_they_ended() {
+        let started = time("2024-06-01T12:00:00Z");
+        let mut job = ReindexJob::started("job".to_string(), 250, started);
+        job.count(
+            BatchOutcome {
+                read: 100,
+                indexed: 100,
+            },
+            started,
+        );
+        job.count(
+            BatchOutcome {
+                read: 100,
+                indexed: 0,
+            },
+            started,
+        );
+        job.count(
+            BatchOutcome {
+                read: 50,
+                indexed: 49,
+            },
+            time("2024-06-01T12:01:00Z"),
+        );
+        assert_eq!((job.processed, job.indexed, job.failed), (250, 149, 101));
+        assert_eq!(job.status, ReindexStatus::Running);
+
+        let mut completed = job.clone();
+        completed.finish(Ok(()), time("2024-06-01T12:02:00Z"));
+        assert_eq!(completed.status, ReindexStatus::Completed);
+        assert_eq!(completed.error, None);
+
+        job.finish(
+            Err(ServiceError::Internal("lost the lock".to_string())),
+            time("2024-06-01T12:02:00Z"),
+        );
+        assert_eq!(job.status, ReindexStatus::Failed);
+        assert!(job.error.unwrap().contains("lost the lock"));
+    }
+
+    #[test]
+    fn jobs_are_stored_as_json() {
+        let job = ReindexJob::started("665f1c".to_string(), 3, time("2024-06-01T12:00:00Z"));
+        let stored = serde_json::to_value(&job).unwrap();
+        assert_eq!(
+            stored,
+            json!({
+                "job_id": "665f1c",
+                "status": "running",
+                "total": 3,
+                "processed": 0,
+                "indexed": 0,
+                "failed": 0,
+                "started_at": "2024-06-01T12:00:00.000Z",
+                "updated_at": "2024-06-01T12:00:00.000Z",
+                "error": null,
+            })
+        );
+        assert_eq!(serde_json::from_value::<ReindexJob>(stored).unwrap(), job);
+        assert_eq!(job_key(&job.job_id), "reindex:665f1c");
+    }
+
+    #[test]
+    fn products_are_read_as_the_worker_reads_them() {
+        let id = ObjectId::new();
+        let doc = product_doc(doc! {
+            "_id": id,
+            "code": "4000417025005",
+            "product_name": "Alpine milk chocolate",
+            "labels_tags": ["en:fair-trade"],
+        })
+        .unwrap();
+        assert_eq!(doc.id, id);
+        assert_eq!(doc.labels_tags, Some(vec!["en:fair-trade".to_string()]));
+
+        assert!(product_doc(doc! { "_id": "4000417025005", "code": "4000417025005" }).is_none());
+        assert!(product_doc(doc! { "_id": ObjectId::new() }).is_none());
+    }
+}
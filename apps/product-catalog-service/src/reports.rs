@@ -0,0 +1,1261 @@
+//! Users' reports of wrong product data: `POST /api/v1/products/{id}/reports` files one
+//! in the `product_reports` collection as `open`, `GET` on the same path lists a
+//! product's reports newest first, and `/api/v1/admin/reports` lists them across
+//! products for moderators, by status.
+//!
+//! Moderators work through them under `/api/v1/admin/reports` too: `assign` hands an
+//! open report to someone, `resolve` closes it, and `stats` counts the open ones per
+//! reason. Only open reports are assigned or resolved; a closed one answers 409. A
+//! `fixed` resolution may carry the correction: the report is claimed as `resolving`, the
+//! correction applied to the product as `PUT` would, and the report closed, or handed
+//! back `open` when the correction fails. Two resolutions never both correct a product.
+//!
+//! A report keeps the product's code and name as they were when it was filed, so it
+//! still makes sense after the product is changed or deleted. Filing is limited to
+//! [`REPORTS_PER_MINUTE`] per client, counted in the [`AppState::rate_limit_store`] like
+//! the request limits: a client is its address, plus its subject when authenticated. The
+//! `reporter_id` a report names is only recorded, never trusted to split the count.
+
+use crate::{
+    audit::{Actor, HistoryFrom},
+    errors::{Result, ServiceError},
+    handlers::update_product_fields,
+    models::{UpdateProductPayload, not_blank},
+    state::AppState,
+    tunables::REPORTS_PER_MINUTE,
+};
+use async_trait::async_trait;
+use axum::{
+    Json,
+    extract::{FromRequestParts, Path, Query, State},
+    http::{StatusCode, request::Parts},
+};
+use bson::{Document, doc, oid::ObjectId};
+use chrono::{DateTime, Utc};
+use futures::stream::TryStreamExt;
+use mongodb::{
+    Collection, Database,
+    options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument},
+};
+use rust_database_clients::serde_helpers::chrono_datetime_as_rfc3339_or_bson;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    convert::Infallible,
+    sync::{Arc, Mutex},
+};
+use tracing::{debug, error, info, instrument, warn};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+use yoloeats_auth::AuthContext;
+use yoloeats_domain::ErrorBody;
+use yoloeats_metrics::{ClientAddress, RateLimiter, RouteLimit, ValidJson, rate_limit_client};
+use yoloeats_pagination::{Page, PageLimit, PageParams};
+
+pub const REPORTS_COLLECTION: &str = "product_reports";
+
+/// Page sizes for the report listings.
+pub struct ReportPageLimit;
+
+impl PageLimit for ReportPageLimit {
+    const DEFAULT: u64 = 20;
+    const MAX: u64 = 100;
+}
+
+/// Where the next report page starts: after the oldest report of the previous one.
+#[derive(Debug, Serialize, Deserialize)]
+struct ReportCursor {
+    before_id: ObjectId,
+}
+
+/// What is wrong with the product.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportReason {
+    WrongAllergens,
+    WrongIngredients,
+    WrongImage,
+    Other,
+}
+
+impl ReportReason {
+    pub const ALL: [ReportReason; 4] = [
+        ReportReason::WrongAllergens,
+        ReportReason::WrongIngredients,
+        ReportReason::WrongImage,
+        ReportReason::Other,
+    ];
+}
+
+/// Where a report is in moderation. Reports are filed `open` and stay so when assigned;
+/// resolving one makes it `resolved`, or `dismissed`. A resolution correcting the product
+/// holds the report `resolving` while the correction is applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportStatus {
+    Open,
+    Resolving,
+    Resolved,
+    Dismissed,
+}
+
+impl ReportStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReportStatus::Open => "open",
+            ReportStatus::Resolving => "resolving",
+            ReportStatus::Resolved => "resolved",
+            ReportStatus::Dismissed => "dismissed",
+        }
+    }
+}
+
+/// How a moderator closed a report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportAction {
+    /// Nothing was wrong.
+    Dismissed,
+    /// The product was corrected.
+    Fixed,
+    /// The product was taken out of the catalog.
+    ProductDeleted,
+}
+
+impl ReportAction {
+    /// The status of a report closed this way.
+    pub fn status(self) -> ReportStatus {
+        match self {
+            ReportAction::Dismissed => ReportStatus::Dismissed,
+            ReportAction::Fixed | ReportAction::ProductDeleted => ReportStatus::Resolved,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ReportAssignment {
+    pub assignee: String,
+    /// The [`Actor`] who assigned the report: the subject of their bearer token.
+    pub assigned_by: Option<String>,
+    #[serde(with = "chrono_datetime_as_rfc3339_or_bson")]
+    pub assigned_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ReportResolution {
+    pub action: ReportAction,
+    /// The [`Actor`] who resolved the report: the subject of their bearer token.
+    pub resolved_by: Option<String>,
+    #[serde(with = "chrono_datetime_as_rfc3339_or_bson")]
+    pub resolved_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ProductReport {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub id: Option<ObjectId>,
+    #[schema(value_type = String)]
+    pub product_id: ObjectId,
+    /// The product's code when the report was filed.
+    pub code: String,
+    /// The product's name when the report was filed, if it had one.
+    pub product_name: Option<String>,
+    pub reason: ReportReason,
+    pub comment: String,
+    /// `None` when the reporter did not name themselves.
+    pub reporter_id: Option<String>,
+    pub status: ReportStatus,
+    #[serde(with = "chrono_datetime_as_rfc3339_or_bson")]
+    pub created_at: DateTime<Utc>,
+    /// The latest assignment, once there is one.
+    #[serde(default)]
+    pub assignment: Option<ReportAssignment>,
+    /// How the report was closed, once it is.
+    #[serde(default)]
+    pub resolution: Option<ReportResolution>,
+}
+
+/// Body of `POST /api/v1/products/{id}/reports`. A `reason` of none of the
+/// [`ReportReason`]s fails to deserialize.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateReportPayload {
+    pub reason: ReportReason,
+    /// What is wrong, in the reporter's words.
+    #[validate(
+        length(min = 1, max = 1000, message = "Comment must be 1-1000 characters"),
+        custom(function = "not_blank", message = "Comment must not be blank")
+    )]
+    pub comment: String,
+    #[validate(length(min = 1, max = 128, message = "Reporter ID must be 1-128 characters"))]
+    pub reporter_id: Option<String>,
+}
+
+/// Body of `POST /api/v1/admin/reports/{id}/assign`.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct AssignReportPayload {
+    #[validate(
+        length(min = 1, max = 128, message = "Assignee must be 1-128 characters"),
+        custom(function = "not_blank", message = "Assignee must not be blank")
+    )]
+    pub assignee: String,
+}
+
+/// Body of `POST /api/v1/admin/reports/{id}/resolve`.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ResolveReportPayload {
+    pub action: ReportAction,
+    /// The correction of a `fixed` report, applied to its product as `PUT` would.
+    #[validate(nested)]
+    pub product: Option<UpdateProductPayload>,
+}
+
+/// The open reports, in all and per reason.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ReportStats {
+    pub open: u64,
+    /// Every [`ReportReason`], those of no open report with 0.
+    pub open_by_reason: BTreeMap<ReportReason, u64>,
+}
+
+impl ReportStats {
+    fn from_counts(counts: Vec<(ReportReason, u64)>) -> Self {
+        let mut open_by_reason: BTreeMap<ReportReason, u64> = ReportReason::ALL
+            .into_iter()
+            .map(|reason| (reason, 0))
+            .collect();
+        for (reason, count) in counts {
+            *open_by_reason.entry(reason).or_default() += count;
+        }
+        ReportStats {
+            open: open_by_reason.values().sum(),
+            open_by_reason,
+        }
+    }
+}
+
+/// Query of the report listings.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ReportListParams {
+    /// Only reports in this status.
+    pub status: Option<ReportStatus>,
+}
+
+/// Which reports a listing takes; `None` takes any.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ReportFilter {
+    pub product_id: Option<ObjectId>,
+    pub status: Option<ReportStatus>,
+}
+
+impl ReportFilter {
+    fn matches(&self, report: &ProductReport) -> bool {
+        self.product_id.is_none_or(|id| report.product_id == id)
+            && self.status.is_none_or(|status| report.status == status)
+    }
+
+    fn document(&self) -> Document {
+        let mut filter = Document::new();
+        if let Some(product_id) = self.product_id {
+            filter.insert("product_id", product_id);
+        }
+        if let Some(status) = self.status {
+            filter.insert("status", status.as_str());
+        }
+        filter
+    }
+}
+
+/// What a moderator does to a report.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReportChange {
+    Assign(ReportAssignment),
+    /// Holds the report `resolving` while its product is corrected.
+    Claim,
+    /// Hands a claimed report back to `open` when its correction failed.
+    Release,
+    Resolve(ReportResolution),
+}
+
+impl ReportChange {
+    fn apply(self, report: &mut ProductReport) {
+        match self {
+            ReportChange::Assign(assignment) => report.assignment = Some(assignment),
+            ReportChange::Claim => report.status = ReportStatus::Resolving,
+            ReportChange::Release => report.status = ReportStatus::Open,
+            ReportChange::Resolve(resolution) => {
+                report.status = resolution.action.status();
+                report.resolution = Some(resolution);
+            }
+        }
+    }
+
+    /// The fields the change sets. Serialized through BSON bytes, as the driver serializes
+    /// a new report, so the times are stored as dates.
+    fn set_document(&self) -> Result<Document> {
+        fn stored<T: Serialize>(value: &T) -> Result<Document> {
+            Ok(bson::from_slice(&bson::to_vec(value)?)?)
+        }
+        Ok(match self {
+            ReportChange::Assign(assignment) => doc! { "assignment": stored(assignment)? },
+            ReportChange::Claim => doc! { "status": ReportStatus::Resolving.as_str() },
+            ReportChange::Release => doc! { "status": ReportStatus::Open.as_str() },
+            ReportChange::Resolve(resolution) => doc! {
+                "status": resolution.action.status().as_str(),
+                "resolution": stored(resolution)?,
+            },
+        })
+    }
+}
+
+/// A `$group` result of [`ReportStore::open_by_reason`].
+#[derive(Deserialize)]
+struct ReasonCount {
+    #[serde(rename = "_id")]
+    reason: ReportReason,
+    count: u64,
+}
+
+#[async_trait]
+pub trait ReportStore: Send + Sync {
+    /// Stores `report`, returning it with its new id.
+    async fn insert(&self, report: ProductReport) -> Result<ProductReport>;
+
+    /// Up to `limit` of the reports `filter` takes, starting at `from`, newest first.
+    async fn list(
+        &self,
+        filter: ReportFilter,
+        from: HistoryFrom,
+        limit: u64,
+    ) -> Result<Vec<ProductReport>>;
+
+    /// How many reports `filter` takes in all.
+    async fn count(&self, filter: ReportFilter) -> Result<u64>;
+
+    async fn find(&self, id: ObjectId) -> Result<Option<ProductReport>>;
+
+    /// Applies `change` to the report if it is in status `from`, returning the changed
+    /// report; `None` when there is no report with this id in that status.
+    async fn change(
+        &self,
+        id: ObjectId,
+        from: ReportStatus,
+        change: ReportChange,
+    ) -> Result<Option<ProductReport>>;
+
+    /// How many reports are open per reason, leaving out reasons without any.
+    async fn open_by_reason(&self) -> Result<Vec<(ReportReason, u64)>>;
+}
+
+pub struct MongoReportStore {
+    collection: Collection<ProductReport>,
+}
+
+impl MongoReportStore {
+    pub fn new(db: &Database) -> Self {
+        MongoReportStore {
+            collection: db.collection(REPORTS_COLLECTION),
+        }
+    }
+}
+
+#[async_trait]
+impl ReportStore for MongoReportStore {
+    async fn insert(&self, mut report: ProductReport) -> Result<ProductReport> {
+        let inserted = self.collection.insert_one(&report).await?;
+        report.id = inserted.inserted_id.as_object_id();
+        Ok(report)
+    }
+
+    async fn list(
+        &self,
+        filter: ReportFilter,
+        from: HistoryFrom,
+        limit: u64,
+    ) -> Result<Vec<ProductReport>> {
+        let mut query = filter.document();
+        let mut find_options = FindOptions::builder()
+            .limit(limit as i64)
+            .sort(doc! { "_id": -1 })
+            .build();
+        match from {
+            HistoryFrom::Offset(skip) => find_options.skip = Some(skip),
+            HistoryFrom::Before(before_id) => {
+                query.insert("_id", doc! { "$lt": before_id });
+            }
+        }
+        let cursor = self
+            .collection
+            .find(query)
+            .with_options(find_options)
+            .await
+            .map_err(|e| {
+                error!(
+                    ?filter,
+                    "MongoDB find on {} failed: {}", REPORTS_COLLECTION, e
+                );
+                ServiceError::MongoDb(e)
+            })?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn count(&self, filter: ReportFilter) -> Result<u64> {
+        Ok(self.collection.count_documents(filter.document()).await?)
+    }
+
+    async fn find(&self, id: ObjectId) -> Result<Option<ProductReport>> {
+        Ok(self.collection.find_one(doc! { "_id": id }).await?)
+    }
+
+    async fn change(
+        &self,
+        id: ObjectId,
+        from: ReportStatus,
+        change: ReportChange,
+    ) -> Result<Option<ProductReport>> {
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+        let current = doc! { "_id": id, "status": from.as_str() };
+        self.collection
+            .find_one_and_update(current, doc! { "$set": change.set_document()? })
+            .with_options(options)
+            .await
+            .map_err(|e| {
+                error!(id = %id, "Failed to change product report: {}", e);
+                ServiceError::MongoDb(e)
+            })
+    }
+
+    async fn open_by_reason(&self) -> Result<Vec<(ReportReason, u64)>> {
+        let pipeline = vec![
+            doc! { "$match": { "status": ReportStatus::Open.as_str() } },
+            doc! { "$group": { "_id": "$reason", "count": { "$sum": 1 } } },
+        ];
+        let cursor = self.collection.aggregate(pipeline).await.map_err(|e| {
+            error!("MongoDB report stats aggregation failed: {}", e);
+            ServiceError::MongoDb(e)
+        })?;
+        let documents: Vec<Document> = cursor.try_collect().await?;
+        documents
+            .into_iter()
+            .map(|document| {
+                let counted: ReasonCount = bson::from_document(document)?;
+                Ok((counted.reason, counted.count))
+            })
+            .collect()
+    }
+}
+
+/// Reports in filing order. Clones share the same list.
+#[derive(Clone, Default)]
+pub struct MemoryReportStore {
+    reports: Arc<Mutex<Vec<ProductReport>>>,
+}
+
+#[async_trait]
+impl ReportStore for MemoryReportStore {
+    async fn insert(&self, mut report: ProductReport) -> Result<ProductReport> {
+        report.id = Some(ObjectId::new());
+        self.reports.lock().unwrap().push(report.clone());
+        Ok(report)
+    }
+
+    async fn list(
+        &self,
+        filter: ReportFilter,
+        from: HistoryFrom,
+        limit: u64,
+    ) -> Result<Vec<ProductReport>> {
+        let reports = self.reports.lock().unwrap();
+        let newest_first = reports.iter().rev().filter(|report| filter.matches(report));
+        let page: Vec<ProductReport> = match from {
+            HistoryFrom::Offset(skip) => newest_first
+                .skip(skip as usize)
+                .take(limit as usize)
+                .cloned()
+                .collect(),
+            HistoryFrom::Before(before_id) => newest_first
+                .filter(|report| report.id.is_some_and(|id| id < before_id))
+                .take(limit as usize)
+                .cloned()
+                .collect(),
+        };
+        Ok(page)
+    }
+
+    async fn count(&self, filter: ReportFilter) -> Result<u64> {
+        let reports = self.reports.lock().unwrap();
+        Ok(reports
+            .iter()
+            .filter(|report| filter.matches(report))
+            .count() as u64)
+    }
+
+    async fn find(&self, id: ObjectId) -> Result<Option<ProductReport>> {
+        let reports = self.reports.lock().unwrap();
+        Ok(reports.iter().find(|report| report.id == Some(id)).cloned())
+    }
+
+    async fn change(
+        &self,
+        id: ObjectId,
+        from: ReportStatus,
+        change: ReportChange,
+    ) -> Result<Option<ProductReport>> {
+        let mut reports = self.reports.lock().unwrap();
+        let Some(report) = reports
+            .iter_mut()
+            .find(|report| report.id == Some(id) && report.status == from)
+        else {
+            return Ok(None);
+        };
+        change.apply(report);
+        Ok(Some(report.clone()))
+    }
+
+    async fn open_by_reason(&self) -> Result<Vec<(ReportReason, u64)>> {
+        let reports = self.reports.lock().unwrap();
+        let mut counts: BTreeMap<ReportReason, u64> = BTreeMap::new();
+        for report in reports
+            .iter()
+            .filter(|report| report.status == ReportStatus::Open)
+        {
+            *counts.entry(report.reason).or_default() += 1;
+        }
+        Ok(counts.into_iter().collect())
+    }
+}
+
+/// Whom [`REPORTS_PER_MINUTE`] counts a report against, named as the request limits
+/// name clients: their [`ClientAddress`], the one a trusted proxy like the gateway
+/// forwarded for, plus the subject of the caller's token when the route checked one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReportingClient(pub String);
+
+impl<S> FromRequestParts<S> for ReportingClient
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let Ok(ClientAddress(address)) = ClientAddress::from_request_parts(parts, state).await;
+        Ok(ReportingClient(rate_limit_client(
+            address,
+            parts
+                .extensions
+                .get::<AuthContext>()
+                .map(|context| context.subject.as_str()),
+        )))
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/products/{id}/reports",
+    tag = "v1",
+    params(
+        ("id" = String, Path, description = "The product's ObjectId."),
+    ),
+    request_body = CreateReportPayload,
+    responses(
+        (status = 201, description = "The report, filed as `open`.", body = ProductReport),
+        (status = 400, description = "An invalid id, or a body that is not JSON.", body = ErrorBody),
+        (status = 404, description = "No product with this id.", body = ErrorBody),
+        (status = 422, description = "An unknown reason, or a blank or too long comment.", body = ErrorBody),
+        (status = 429, description = "Over the client's report limit; retry after `Retry-After`.", body = ErrorBody),
+        (status = 500, description = "MongoDB failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, client, payload), fields(id = %id_str, reason = ?payload.reason))]
+pub async fn create_report(
+    State(state): State<Arc<AppState>>,
+    Path(id_str): Path<String>,
+    ReportingClient(client): ReportingClient,
+    ValidJson(payload): ValidJson<CreateReportPayload>,
+) -> Result<(StatusCode, Json<ProductReport>)> {
+    let product_id = parse_product_id(&id_str)?;
+    let reporter_id = payload
+        .reporter_id
+        .map(|reporter_id| reporter_id.trim().to_string())
+        .filter(|reporter_id| !reporter_id.is_empty());
+
+    let route = RouteLimit {
+        group: "reports",
+        prefix: "/api/v1/products",
+        per_minute: state.config.get(REPORTS_PER_MINUTE),
+    };
+    let limiter = RateLimiter::new("product-catalog-service", state.rate_limit_store.clone());
+    if let Some(wait) = limiter.check(route, &client).await {
+        return Err(ServiceError::RateLimited { route, wait });
+    }
+
+    let product = state
+        .products
+        .find_by_id(product_id)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound(format!("Product with ID {} not found", id_str)))?;
+    let report = state
+        .reports
+        .insert(ProductReport {
+            id: None,
+            product_id,
+            code: product.code,
+            product_name: product.product_name,
+            reason: payload.reason,
+            comment: payload.comment.trim().to_string(),
+            reporter_id,
+            status: ReportStatus::Open,
+            created_at: Utc::now(),
+            assignment: None,
+            resolution: None,
+        })
+        .await?;
+    info!(id = %product_id, report = ?report.id, "Filed product report");
+    Ok((StatusCode::CREATED, Json(report)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/products/{id}/reports",
+    tag = "v1",
+    params(
+        ("id" = String, Path, description = "The product's ObjectId."),
+        ReportListParams,
+        PageParams<ReportPageLimit>,
+    ),
+    responses(
+        (status = 200, description = "One page of the product's reports, newest first.", body = Page<ProductReport>),
+        (status = 400, description = "An invalid id, status or cursor.", body = ErrorBody),
+        (status = 500, description = "MongoDB failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, page), fields(id = %id_str))]
+pub async fn get_product_reports(
+    State(state): State<Arc<AppState>>,
+    Path(id_str): Path<String>,
+    Query(params): Query<ReportListParams>,
+    page: PageParams<ReportPageLimit>,
+) -> Result<Json<Page<ProductReport>>> {
+    let filter = ReportFilter {
+        product_id: Some(parse_product_id(&id_str)?),
+        status: params.status,
+    };
+    find_reports(&state, filter, &page).await.map(Json)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/reports",
+    tag = "admin",
+    params(
+        ("X-Internal-Token" = String, Header, description = "The shared secret of internal callers."),
+        ReportListParams,
+        PageParams<ReportPageLimit>,
+    ),
+    responses(
+        (status = 200, description = "One page of the reports on every product, newest first.", body = Page<ProductReport>),
+        (status = 400, description = "An invalid status or cursor.", body = ErrorBody),
+        (status = 401, description = "No valid internal token.", body = ErrorBody),
+        (status = 500, description = "MongoDB failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, page))]
+pub async fn list_reports(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ReportListParams>,
+    page: PageParams<ReportPageLimit>,
+) -> Result<Json<Page<ProductReport>>> {
+    let filter = ReportFilter {
+        product_id: None,
+        status: params.status,
+    };
+    find_reports(&state, filter, &page).await.map(Json)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/reports/{id}/assign",
+    tag = "admin",
+    params(
+        ("id" = String, Path, description = "The report's ObjectId."),
+        ("X-Internal-Token" = String, Header, description = "The shared secret of internal callers."),
+    ),
+    request_body = AssignReportPayload,
+    responses(
+        (status = 200, description = "The report, assigned; an assigned report is assigned anew.", body = ProductReport),
+        (status = 400, description = "An invalid id, or a body that is not JSON.", body = ErrorBody),
+        (status = 401, description = "No valid internal token, or no valid bearer token naming who assigns the report.", body = ErrorBody),
+        (status = 404, description = "No report with this id.", body = ErrorBody),
+        (status = 409, description = "The report is already resolved or dismissed, or a resolution is correcting its product.", body = ErrorBody),
+        (status = 422, description = "A blank or too long assignee.", body = ErrorBody),
+        (status = 500, description = "MongoDB failed.", body = ErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[instrument(skip(state, actor, payload), fields(id = %id_str, assignee = %payload.assignee))]
+pub async fn assign_report(
+    State(state): State<Arc<AppState>>,
+    Path(id_str): Path<String>,
+    actor: Actor,
+    ValidJson(payload): ValidJson<AssignReportPayload>,
+) -> Result<Json<ProductReport>> {
+    let id = parse_report_id(&id_str)?;
+    let assignment = ReportAssignment {
+        assignee: payload.assignee.trim().to_string(),
+        assigned_by: actor.0,
+        assigned_at: Utc::now(),
+    };
+    let report = change_open_report(&state, id, ReportChange::Assign(assignment)).await?;
+    info!(id = %id, "Assigned product report");
+    Ok(Json(report))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/reports/{id}/resolve",
+    tag = "admin",
+    params(
+        ("id" = String, Path, description = "The report's ObjectId."),
+        ("X-Internal-Token" = String, Header, description = "The shared secret of internal callers."),
+    ),
+    request_body = ResolveReportPayload,
+    responses(
+        (status = 200, description = "The report, closed, after its product was corrected if the body had a correction.", body = ProductReport),
+        (status = 400, description = "An invalid id, a correction with an action other than `fixed`, or a body that is not JSON.", body = ErrorBody),
+        (status = 401, description = "No valid internal token, or no valid bearer token naming who resolves the report.", body = ErrorBody),
+        (status = 404, description = "No report with this id, or a correction for a product that is gone.", body = ErrorBody),
+        (status = 409, description = "The report is already resolved or dismissed, or a resolution is correcting its product.", body = ErrorBody),
+        (status = 422, description = "An unknown action, or a correction failing a product rule.", body = ErrorBody),
+        (status = 500, description = "MongoDB or Redis failed.", body = ErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[instrument(skip(state, actor, payload), fields(id = %id_str, action = ?payload.action))]
+pub async fn resolve_report(
+    State(state): State<Arc<AppState>>,
+    Path(id_str): Path<String>,
+    actor: Actor,
+    ValidJson(payload): ValidJson<ResolveReportPayload>,
+) -> Result<Json<ProductReport>> {
+    let id = parse_report_id(&id_str)?;
+    if payload.product.is_some() && payload.action != ReportAction::Fixed {
+        return Err(ServiceError::BadRequest(
+            "Only a 'fixed' resolution takes a product correction".to_string(),
+        ));
+    }
+
+    // A correction is applied only under a claim on the open report, so a report closed
+    // or claimed meanwhile is neither corrected nor resolved twice.
+    let from = match payload.product {
+        Some(correction) => {
+            let report = change_open_report(&state, id, ReportChange::Claim).await?;
+            let product_id = report.product_id.to_hex();
+            if let Err(e) = update_product_fields(&state, &product_id, &actor, correction).await {
+                release_claim(&state, id).await;
+                return Err(e);
+            }
+            info!(id = %id, product_id = %product_id, "Corrected reported product");
+            ReportStatus::Resolving
+        }
+        None => ReportStatus::Open,
+    };
+
+    let resolution = ReportResolution {
+        action: payload.action,
+        resolved_by: actor.0,
+        resolved_at: Utc::now(),
+    };
+    let report = change_report(&state, id, from, ReportChange::Resolve(resolution)).await?;
+    info!(id = %id, status = report.status.as_str(), "Resolved product report");
+    Ok(Json(report))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/reports/stats",
+    tag = "admin",
+    params(
+        ("X-Internal-Token" = String, Header, description = "The shared secret of internal callers."),
+    ),
+    responses(
+        (status = 200, description = "How many reports are open, in all and per reason.", body = ReportStats),
+        (status = 401, description = "No valid internal token.", body = ErrorBody),
+        (status = 500, description = "MongoDB failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state))]
+pub async fn get_report_stats(State(state): State<Arc<AppState>>) -> Result<Json<ReportStats>> {
+    let stats = ReportStats::from_counts(state.reports.open_by_reason().await?);
+    info!("{} product reports are open", stats.open);
+    Ok(Json(stats))
+}
+
+/// Applies `change` to the open report `id`, telling a closed report from a missing one
+/// when it can't.
+async fn change_open_report(
+    state: &AppState,
+    id: ObjectId,
+    change: ReportChange,
+) -> Result<ProductReport> {
+    change_report(state, id, ReportStatus::Open, change).await
+}
+
+/// Applies `change` to the report `id` if it is in status `from`.
+async fn change_report(
+    state: &AppState,
+    id: ObjectId,
+    from: ReportStatus,
+    change: ReportChange,
+) -> Result<ProductReport> {
+    if let Some(report) = state.reports.change(id, from, change).await? {
+        return Ok(report);
+    }
+    match state.reports.find(id).await? {
+        Some(report) => Err(already_closed(id, report.status)),
+        None => Err(report_not_found(id)),
+    }
+}
+
+/// Hands a report whose correction failed back to `open`. A failure here is logged, not
+/// returned, so the caller still sees why the correction failed.
+async fn release_claim(state: &AppState, id: ObjectId) {
+    match state
+        .reports
+        .change(id, ReportStatus::Resolving, ReportChange::Release)
+        .await
+    {
+        Ok(Some(_)) => info!(id = %id, "Released product report after a failed correction"),
+        Ok(None) => warn!(id = %id, "Product report was no longer claimed to release"),
+        Err(e) => error!(id = %id, "Failed to release product report: {}", e),
+    }
+}
+
+fn already_closed(id: ObjectId, status: ReportStatus) -> ServiceError {
+    ServiceError::Conflict(format!("Report {} is already {}", id, status.as_str()))
+}
+
+fn report_not_found(id: ObjectId) -> ServiceError {
+    ServiceError::NotFound(format!("Report with ID {} not found", id))
+}
+
+fn parse_report_id(id_str: &str) -> Result<ObjectId> {
+    ObjectId::parse_str(id_str)
+        .map_err(|_| ServiceError::BadRequest(format!("Invalid report ID format: {}", id_str)))
+}
+
+fn parse_product_id(id_str: &str) -> Result<ObjectId> {
+    ObjectId::parse_str(id_str).map_err(|e| {
+        error!("Invalid ObjectId format '{}': {}", id_str, e);
+        ServiceError::InvalidProductId(format!("Invalid product ID format: {}", id_str))
+    })
+}
+
+/// One page of the reports `filter` takes. Reports of deleted products are kept.
+async fn find_reports(
+    state: &AppState,
+    filter: ReportFilter,
+    page: &PageParams<ReportPageLimit>,
+) -> Result<Page<ProductReport>> {
+    let from = page
+        .position::<ReportCursor>(&state.cursor_codec)?
+        .map_or(HistoryFrom::Offset(page.offset), |cursor| {
+            HistoryFrom::Before(cursor.before_id)
+        });
+    debug!(
+        "Report page: filter={:?}, limit={}, from={:?}",
+        filter, page.limit, from
+    );
+
+    let reports = state.reports.list(filter, from, page.limit + 1).await?;
+    let reports = Page::overfetched(reports, page.limit, |oldest| {
+        let before_id = oldest.id?;
+        Some(state.cursor_codec.encode(&ReportCursor { before_id }))
+    });
+    info!("Returning {} product reports", reports.items.len());
+
+    let total = state.reports.count(filter).await?;
+    Ok(reports.with_total(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Router,
+        body::{Body, to_bytes},
+        extract::ConnectInfo,
+        routing::post,
+    };
+    use serde_json::json;
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use tower::ServiceExt;
+    use yoloeats_metrics::{MemoryRateLimitStore, RateLimitLayer, RateLimits, TrustedProxies};
+
+    fn report(product_id: ObjectId, reason: ReportReason, status: ReportStatus) -> ProductReport {
+        ProductReport {
+            id: None,
+            product_id,
+            code: "4000417025005".to_string(),
+            product_name: None,
+            reason,
+            comment: "Contains milk".to_string(),
+            reporter_id: None,
+            status,
+            created_at: Utc::now(),
+            assignment: None,
+            resolution: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn memory_reports_filter_newest_first_and_page() {
+        let store = MemoryReportStore::default();
+        let (product, other) = (ObjectId::new(), ObjectId::new());
+        for (id, reason, status) in [
+            (product, ReportReason::WrongAllergens, ReportStatus::Open),
+            (product, ReportReason::WrongImage, ReportStatus::Resolved),
+            (other, ReportReason::Other, ReportStatus::Open),
+            (product, ReportReason::WrongIngredients, ReportStatus::Open),
+        ] {
+            store.insert(report(id, reason, status)).await.unwrap();
+        }
+
+        let reasons = |reports: Vec<ProductReport>| -> Vec<ReportReason> {
+            reports.into_iter().map(|r| r.reason).collect()
+        };
+        let of_product = ReportFilter {
+            product_id: Some(product),
+            status: None,
+        };
+        let first = store
+            .list(of_product, HistoryFrom::Offset(0), 2)
+            .await
+            .unwrap();
+        let before_id = first[1].id.unwrap();
+        assert_eq!(
+            reasons(first),
+            [ReportReason::WrongIngredients, ReportReason::WrongImage]
+        );
+        assert_eq!(
+            reasons(
+                store
+                    .list(of_product, HistoryFrom::Before(before_id), 2)
+                    .await
+                    .unwrap()
+            ),
+            [ReportReason::WrongAllergens]
+        );
+        assert_eq!(store.count(of_product).await.unwrap(), 3);
+
+        let open = ReportFilter {
+            product_id: None,
+            status: Some(ReportStatus::Open),
+        };
+        assert_eq!(
+            reasons(store.list(open, HistoryFrom::Offset(1), 5).await.unwrap()),
+            [ReportReason::Other, ReportReason::WrongAllergens]
+        );
+        assert_eq!(store.count(open).await.unwrap(), 3);
+        assert_eq!(store.count(ReportFilter::default()).await.unwrap(), 4);
+    }
+
+    #[tokio::test]
+    async fn only_open_reports_change() {
+        let store = MemoryReportStore::default();
+        let product = ObjectId::new();
+        let open = store
+            .insert(report(
+                product,
+                ReportReason::WrongImage,
+                ReportStatus::Open,
+            ))
+            .await
+            .unwrap()
+            .id
+            .unwrap();
+        let assignment = ReportAssignment {
+            assignee: "curator-1".to_string(),
+            assigned_by: Some("lead".to_string()),
+            assigned_at: Utc::now(),
+        };
+        let resolution = ReportResolution {
+            action: ReportAction::Dismissed,
+            resolved_by: None,
+            resolved_at: Utc::now(),
+        };
+
+        let assigned = store
+            .change(
+                open,
+                ReportStatus::Open,
+                ReportChange::Assign(assignment.clone()),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(assigned.status, ReportStatus::Open);
+        assert_eq!(assigned.assignment, Some(assignment.clone()));
+        let dismissed = store
+            .change(
+                open,
+                ReportStatus::Open,
+                ReportChange::Resolve(resolution.clone()),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(dismissed.status, ReportStatus::Dismissed);
+        assert_eq!(dismissed.resolution, Some(resolution.clone()));
+        assert_eq!(store.find(open).await.unwrap(), Some(dismissed));
+
+        for change in [
+            ReportChange::Assign(assignment),
+            ReportChange::Resolve(resolution),
+        ] {
+            assert_eq!(
+                store
+                    .change(open, ReportStatus::Open, change.clone())
+                    .await
+                    .unwrap(),
+                None
+            );
+            assert_eq!(
+                store
+                    .change(ObjectId::new(), ReportStatus::Open, change)
+                    .await
+                    .unwrap(),
+                None
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn a_claimed_report_is_resolved_or_released_once() {
+        let store = MemoryReportStore::default();
+        let id = store
+            .insert(report(
+                ObjectId::new(),
+                ReportReason::WrongAllergens,
+                ReportStatus::Open,
+            ))
+            .await
+            .unwrap()
+            .id
+            .unwrap();
+        let resolution = ReportResolution {
+            action: ReportAction::Fixed,
+            resolved_by: Some("lead".to_string()),
+            resolved_at: Utc::now(),
+        };
+
+        let claimed = store
+            .change(id, ReportStatus::Open, ReportChange::Claim)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(claimed.status, ReportStatus::Resolving);
+        // A second resolution can neither claim nor resolve it from open.
+        assert_eq!(
+            store
+                .change(id, ReportStatus::Open, ReportChange::Claim)
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            store
+                .change(
+                    id,
+                    ReportStatus::Open,
+                    ReportChange::Resolve(resolution.clone())
+                )
+                .await
+                .unwrap(),
+            None
+        );
+
+        let released = store
+            .change(id, ReportStatus::Resolving, ReportChange::Release)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(released.status, ReportStatus::Open);
+        store
+            .change(id, ReportStatus::Open, ReportChange::Claim)
+            .await
+            .unwrap()
+            .unwrap();
+        let resolved = store
+            .change(
+                id,
+                ReportStatus::Resolving,
+                ReportChange::Resolve(resolution),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(resolved.status, ReportStatus::Resolved);
+        assert_eq!(
+            store
+                .change(id, ReportStatus::Resolving, ReportChange::Release)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn stats_count_open_reports_by_every_reason() {
+        let store = MemoryReportStore::default();
+        let product = ObjectId::new();
+        for (reason, status) in [
+            (ReportReason::WrongAllergens, ReportStatus::Open),
+            (ReportReason::WrongAllergens, ReportStatus::Open),
+            (ReportReason::Other, ReportStatus::Open),
+            (ReportReason::WrongImage, ReportStatus::Resolved),
+        ] {
+            store.insert(report(product, reason, status)).await.unwrap();
+        }
+
+        let stats = ReportStats::from_counts(store.open_by_reason().await.unwrap());
+        assert_eq!(stats.open, 3);
+        assert_eq!(
+            serde_json::to_value(&stats).unwrap(),
+            json!({
+                "open": 3,
+                "open_by_reason": {
+                    "wrong_allergens": 2,
+                    "wrong_ingredients": 0,
+                    "wrong_image": 0,
+                    "other": 1
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn resolutions_set_the_status_and_store_dates() {
+        let change = ReportChange::Resolve(ReportResolution {
+            action: ReportAction::ProductDeleted,
+            resolved_by: Some("curator-1".to_string()),
+            resolved_at: Utc::now(),
+        });
+        let set = change.set_document().unwrap();
+        assert_eq!(set.get_str("status").unwrap(), "resolved");
+        let resolution = set.get_document("resolution").unwrap();
+        assert_eq!(resolution.get_str("action").unwrap(), "product_deleted");
+        assert!(resolution.get_datetime("resolved_at").is_ok());
+        assert_eq!(ReportAction::Fixed.status(), ReportStatus::Resolved);
+        assert_eq!(ReportAction::Dismissed.status(), ReportStatus::Dismissed);
+    }
+
+    #[test]
+    fn filters_become_mongo_queries() {
+        let product_id = ObjectId::new();
+        let filter = ReportFilter {
+            product_id: Some(product_id),
+            status: Some(ReportStatus::Open),
+        };
+        assert_eq!(
+            filter.document(),
+            doc! { "product_id": product_id, "status": "open" }
+        );
+        assert_eq!(ReportFilter::default().document(), Document::new());
+    }
+
+    #[test]
+    fn payloads_need_a_known_reason_and_a_comment_that_fits() {
+        let payload = |body: serde_json::Value| {
+            serde_json::from_value::<CreateReportPayload>(body).map(|p| p.validate())
+        };
+        assert!(matches!(
+            payload(json!({ "reason": "wrong_allergens", "comment": "Lists no milk" })),
+            Ok(Ok(()))
+        ));
+        assert!(payload(json!({ "reason": "tastes_bad", "comment": "Meh" })).is_err());
+        for comment in [String::new(), "   ".to_string(), "x".repeat(1001)] {
+            assert!(matches!(
+                payload(json!({ "reason": "other", "comment": comment })),
+                Ok(Err(_))
+            ));
+        }
+        assert!(matches!(
+            payload(json!({ "reason": "other", "comment": "é".repeat(1000) })),
+            Ok(Ok(()))
+        ));
+        assert!(matches!(
+            payload(json!({ "reason": "other", "comment": "Old", "reporter_id": "" })),
+            Ok(Err(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn reporters_are_counted_per_address_and_authenticated_subject() {
+        let client = |subject: Option<&str>| {
+            let mut request = axum::http::Request::builder()
+                .header("x-user-id", "someone-else")
+                .extension(ConnectInfo(SocketAddr::new(
+                    IpAddr::V4(Ipv4Addr::new(10, 0, 0, 7)),
+                    53_211,
+                )));
+            if let Some(subject) = subject {
+                request = request.extension(AuthContext {
+                    subject: subject.to_string(),
+                    roles: Vec::new(),
+                });
+            }
+            request.body(()).unwrap().into_parts().0
+        };
+        let read = |mut parts: Parts| async move {
+            ReportingClient::from_request_parts(&mut parts, &())
+                .await
+                .unwrap()
+                .0
+        };
+        assert_eq!(read(client(None)).await, "10.0.0.7");
+        assert_eq!(read(client(Some("u-1"))).await, "10.0.0.7:u-1");
+    }
+
+    #[tokio::test]
+    async fn reporters_behind_the_gateway_are_told_apart() {
+        let gateway = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let app = Router::new()
+            .route(
+                "/reports",
+                post(|ReportingClient(client): ReportingClient| async move { client }),
+            )
+            .layer(
+                RateLimitLayer::new(
+                    "svc",
+                    RateLimits::default(),
+                    Arc::new(MemoryRateLimitStore::default()),
+                )
+                .trusting(TrustedProxies::new([gateway])),
+            );
+        let report_from = |peer: IpAddr, forwarded: &'static str| {
+            let request = axum::http::Request::post("/reports")
+                .header("x-forwarded-for", forwarded)
+                .extension(ConnectInfo(SocketAddr::new(peer, 40000)))
+                .body(Body::empty())
+                .unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                String::from_utf8(bytes.to_vec()).unwrap()
+            }
+        };
+
+        assert_eq!(report_from(gateway, "198.51.100.1").await, "198.51.100.1");
+        assert_eq!(report_from(gateway, "198.51.100.2").await, "198.51.100.2");
+        // Anyone else's forwarding is their own claim.
+        let client = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7));
+        assert_eq!(report_from(client, "198.51.100.1").await, "203.0.113.7");
+    }
+}
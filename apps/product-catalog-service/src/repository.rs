@@ -0,0 +1,2611 @@
+//! Where products are stored: the `products` collection, or a list in process for
+//! `STORAGE_MODE=memory`.
+
+use crate::{
+    errors::{Result, ServiceError},
+    ingredient_terms::{INGREDIENT_FIELDS, product_names, term_pattern},
+    models::{
+        AllergenMode, COMPLETENESS_CRITERIA, ChangeEntry, IncompleteProduct, Nutriments, Product,
+        SearchHit, SearchSort, SearchSummary, TagMatch, Tombstone, completeness, is_blank,
+        name_tokens,
+    },
+    suggestions::MIN_WORD_CHARS,
+};
+use async_trait::async_trait;
+use bson::{Bson, Document, doc, oid::ObjectId};
+use chrono::{DateTime, Utc};
+use futures::stream::TryStreamExt;
+use mongodb::{
+    Collection, Database,
+    error::ErrorKind,
+    options::{
+        FindOneAndUpdateOptions, FindOptions, ReplaceOneModel, ReturnDocument, UpdateOneModel,
+    },
+};
+use rand::seq::{IteratorRandom, SliceRandom};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tracing::{debug, error};
+use yoloeats_domain::IngredientEntry;
+
+/// The MongoDB collection products are stored in.
+pub const PRODUCTS_COLLECTION: &str = "products";
+
+/// The MongoDB collection the [`Tombstone`]s of deleted products are stored in.
+pub const TOMBSTONES_COLLECTION: &str = "product_tombstones";
+
+/// Where a text search's `textScore` is projected; no stored product has this field.
+const TEXT_SCORE_FIELD: &str = "_text_score";
+
+/// Where the curation query puts a product's completeness; no stored product has this
+/// field.
+const COMPLETENESS_FIELD: &str = "_completeness";
+
+/// MongoDB's `IndexNotFound`, which a `$text` query without a text index fails with.
+const INDEX_NOT_FOUND_CODE: i32 = 27;
+
+/// What a search keeps. Values are already trimmed; `None` and empty lists don't filter.
+/// Serialized, it is the search cache's key, so every field has to be serialized.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ProductFilter {
+    pub text: Option<String>,
+    /// Products in any, or with [`TagMatch::All`] all, of these categories.
+    pub categories: Vec<String>,
+    pub category_match: TagMatch,
+    /// Products of any of these brands.
+    pub brands: Vec<String>,
+    /// Products with any of these labels.
+    pub labels: Vec<String>,
+    /// Products sold in any of these countries.
+    pub countries: Vec<String>,
+    pub nutriscore: Option<String>,
+    /// An Eco-Score grade, lowercase.
+    pub ecoscore: Option<String>,
+    /// Bounds on the nutriments, in grams per 100 g. A product without the value is
+    /// left out.
+    pub max_sugar: Option<f64>,
+    pub max_salt: Option<f64>,
+    pub max_fat: Option<f64>,
+    pub min_protein: Option<f64>,
+    /// Products carrying any of these allergen tags are left out.
+    pub excluded_allergens: Vec<String>,
+    /// [`AllergenMode::Strict`] also leaves out the products whose allergens are unknown.
+    pub allergen_mode: AllergenMode,
+    /// Products carrying any of these label tags are left out.
+    pub excluded_labels: Vec<String>,
+    /// Products whose ingredients name all of these terms, see [`crate::ingredient_terms`].
+    pub included_ingredients: Vec<String>,
+    /// Products whose ingredients name any of these terms are left out.
+    pub excluded_ingredients: Vec<String>,
+    /// Products carrying any of these trace tags are left out.
+    pub excluded_traces: Vec<String>,
+    /// The order of the matches. [`SearchSort::Relevance`] needs `text`; ties are in
+    /// `_id` order.
+    pub sort: SearchSort,
+}
+
+/// Which categories [`ProductRepository::category_counts`] lists. Tags are normalized,
+/// so `prefix` holds no regex metacharacters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CategoryQuery {
+    /// Categories whose tag starts with this.
+    pub prefix: Option<String>,
+    /// Only these categories; `None` lists any.
+    pub within: Option<Vec<String>>,
+    /// Categories with fewer matching products are left out.
+    pub min_count: u64,
+    pub limit: u64,
+}
+
+/// A tag and how many products carry it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: u64,
+}
+
+/// Where a search page starts. Either way of paging walks the matches in the same
+/// order, but only `_id` order can be resumed `After` a product.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchFrom {
+    /// After skipping this many matches.
+    Offset(u64),
+    /// At the first match whose `_id` is greater than this one, however many came before.
+    After(ObjectId),
+}
+
+/// Where a changes page starts: at the first product modified, or tombstone written,
+/// after `at`, or at `at` itself with an `_id` greater than `after_id`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChangesFrom {
+    pub at: DateTime<Utc>,
+    pub after_id: Option<ObjectId>,
+}
+
+/// The time the recent feeds list products by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RecentKind {
+    #[default]
+    Created,
+    Updated,
+}
+
+impl RecentKind {
+    /// The stored field of the time.
+    pub fn field(self) -> &'static str {
+        match self {
+            RecentKind::Created => "created_datetime",
+            RecentKind::Updated => "last_modified_datetime",
+        }
+    }
+
+    pub fn time(self, product: &Product) -> DateTime<Utc> {
+        match self {
+            RecentKind::Created => product.created_at,
+            RecentKind::Updated => product.last_modified_at,
+        }
+    }
+}
+
+/// Where a recent page starts: at the newest product from before `at`, or from `at`
+/// itself with an `_id` less than `before_id`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecentFrom {
+    pub at: DateTime<Utc>,
+    pub before_id: ObjectId,
+}
+
+/// A product a recent feed lists, with the time it is listed by.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecentProduct {
+    pub at: DateTime<Utc>,
+    pub summary: SearchSummary,
+}
+
+/// The fields an update sets, already normalized, and those it clears. `None` leaves a
+/// field as it is.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProductChanges {
+    pub product_name: Option<String>,
+    pub generic_name: Option<String>,
+    pub image_url: Option<String>,
+    pub image_small_url: Option<String>,
+    pub image_thumb_url: Option<String>,
+    pub ingredients_text: Option<String>,
+    pub ingredients: Option<Vec<IngredientEntry>>,
+    pub brands: Option<Vec<String>>,
+    pub categories: Option<Vec<String>>,
+    pub labels: Option<Vec<String>>,
+    pub traces: Option<Vec<String>>,
+    pub quantity: Option<String>,
+    pub allergens_tags: Option<Vec<String>>,
+    pub countries: Option<Vec<String>>,
+    pub nutrition_grade_fr: Option<String>,
+    pub nutrition_grade_source: Option<String>,
+    pub ecoscore_grade: Option<String>,
+    pub ecoscore_score: Option<f64>,
+    pub nutriments: Option<Nutriments>,
+    /// Cleared back to null; stored products lose the field.
+    pub unset: Vec<ProductField>,
+}
+
+/// The optional product fields, as a patch names them to clear them, and the source of
+/// the grade, cleared when a grade is declared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProductField {
+    ProductName,
+    GenericName,
+    ImageUrl,
+    ImageSmallUrl,
+    ImageThumbUrl,
+    IngredientsText,
+    Brands,
+    Categories,
+    Labels,
+    Traces,
+    Quantity,
+    Countries,
+    NutritionGrade,
+    NutritionGradeSource,
+    EcoscoreGrade,
+    EcoscoreScore,
+}
+
+impl ProductField {
+    /// The field's name in the stored document.
+    pub fn key(self) -> &'static str {
+        match self {
+            ProductField::ProductName => "product_name",
+            ProductField::GenericName => "generic_name",
+            ProductField::ImageUrl => "image_url",
+            ProductField::ImageSmallUrl => "image_small_url",
+            ProductField::ImageThumbUrl => "image_thumb_url",
+            ProductField::IngredientsText => "ingredients_text",
+            ProductField::Brands => "brands_tags",
+            ProductField::Categories => "categories_tags",
+            ProductField::Labels => "labels_tags",
+            ProductField::Traces => "traces_tags",
+            ProductField::Quantity => "quantity",
+            ProductField::Countries => "countries_tags",
+            ProductField::NutritionGrade => "nutrition_grade_fr",
+            ProductField::NutritionGradeSource => "nutrition_grade_source",
+            ProductField::EcoscoreGrade => "ecoscore_grade",
+            ProductField::EcoscoreScore => "ecoscore_score",
+        }
+    }
+}
+
+impl ProductChanges {
+    pub fn is_empty(&self) -> bool {
+        *self == ProductChanges::default()
+    }
+}
+
+/// What [`ProductRepository::upsert_by_code`] did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UpsertCounts {
+    pub inserted: u64,
+    pub updated: u64,
+}
+
+#[async_trait]
+pub trait ProductRepository: Send + Sync {
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Product>>;
+
+    async fn find_by_code(&self, code: &str) -> Result<Option<Product>>;
+
+    /// At most `limit` of the products with these codes, in no particular order.
+    async fn find_by_codes(&self, codes: Vec<String>, limit: usize) -> Result<Vec<Product>>;
+
+    /// Up to `limit` products other than `except` whose names have exactly these
+    /// [`name_tokens`], in `_id` order. No tokens match nothing.
+    async fn find_by_name_tokens(
+        &self,
+        tokens: &[String],
+        except: ObjectId,
+        limit: u64,
+    ) -> Result<Vec<Product>>;
+
+    /// Up to `limit` matches starting at `from`, in the filter's order, scored when the
+    /// filter has a text.
+    async fn search(
+        &self,
+        filter: &ProductFilter,
+        from: SearchFrom,
+        limit: u64,
+    ) -> Result<Vec<SearchHit>>;
+
+    /// [`ProductRepository::search`] reading only the [`SearchSummary`] fields.
+    async fn search_summaries(
+        &self,
+        filter: &ProductFilter,
+        from: SearchFrom,
+        limit: u64,
+    ) -> Result<Vec<SearchSummary>>;
+
+    /// Up to `limit` products matching `filter` whose [`completeness`] is at most
+    /// `max_score`, least complete first and then in `_id` order, after skipping `skip`.
+    async fn least_complete(
+        &self,
+        filter: &ProductFilter,
+        max_score: u8,
+        skip: u64,
+        limit: u64,
+    ) -> Result<Vec<IncompleteProduct>>;
+
+    /// Up to `size` products matching `filter`, picked at random and in random order.
+    /// [`is_blank`] products are never picked.
+    async fn sample(&self, filter: &ProductFilter, size: u64) -> Result<Vec<Product>>;
+
+    /// How many products match `filter` in all.
+    async fn count(&self, filter: &ProductFilter) -> Result<u64>;
+
+    /// How many of the products matching `filter` are in each category `query` lists,
+    /// most first and then by tag, up to `query.limit` categories.
+    async fn category_counts(
+        &self,
+        filter: &ProductFilter,
+        query: &CategoryQuery,
+    ) -> Result<Vec<TagCount>>;
+
+    /// The `limit` words most frequent in product names and brand tags, lowercased and
+    /// at least [`MIN_WORD_CHARS`] long, most first and then alphabetically.
+    async fn vocabulary(&self, limit: u64) -> Result<Vec<String>>;
+
+    /// Stores a new product and returns it with its id. A taken code is a `Conflict`.
+    async fn insert(&self, product: Product) -> Result<Product>;
+
+    /// Inserts each product, or replaces the one with its code, keeping that one's id.
+    /// The codes in `products` must be distinct.
+    async fn upsert_by_code(&self, products: Vec<Product>) -> Result<UpsertCounts>;
+
+    /// Applies non-empty `changes` and returns the updated product, `None` if there is
+    /// no product with this id.
+    async fn update(&self, id: ObjectId, changes: &ProductChanges) -> Result<Option<Product>>;
+
+    /// Adds each `(code, scans)` to that product's `scan_count`, without touching its
+    /// modification time. Codes no product has are skipped.
+    async fn add_scans(&self, scans: &[(String, u64)]) -> Result<()>;
+
+    /// Up to `limit` products modified and tombstones written from `from` on, ordered by
+    /// [`ChangeEntry::position`].
+    async fn changes(&self, from: ChangesFrom, limit: u64) -> Result<Vec<ChangeEntry>>;
+
+    /// Up to `limit` summaries of the products matching `filter`, newest `kind` time
+    /// first and then in descending `_id` order, starting at `from`.
+    async fn recent(
+        &self,
+        kind: RecentKind,
+        filter: &ProductFilter,
+        from: Option<RecentFrom>,
+        limit: u64,
+    ) -> Result<Vec<RecentProduct>>;
+
+    /// Whether a product was deleted. A deleted product leaves a [`Tombstone`].
+    async fn delete(&self, id: ObjectId) -> Result<bool>;
+}
+
+pub struct MongoProducts {
+    collection: Collection<Product>,
+    tombstones: Collection<Tombstone>,
+}
+
+impl MongoProducts {
+    pub fn new(db: &Database) -> Self {
+        MongoProducts {
+            collection: db.collection(PRODUCTS_COLLECTION),
+            tombstones: db.collection(TOMBSTONES_COLLECTION),
+        }
+    }
+
+    /// The raw documents of a search page, with their text score under
+    /// [`TEXT_SCORE_FIELD`] when the filter has a text. With `fields`, the documents
+    /// carry only those.
+    async fn find_matches(
+        &self,
+        filter: &ProductFilter,
+        from: SearchFrom,
+        limit: u64,
+        fields: Option<&[&str]>,
+    ) -> Result<Vec<Document>> {
+        let text_score = doc! { "$meta": "textScore" };
+        let mut query = search_document(filter);
+        let mut find_options = FindOptions::builder()
+            .limit(limit as i64)
+            .sort(doc! { "_id": 1 })
+            .build();
+        let mut projection: Document = fields
+            .unwrap_or_default()
+            .iter()
+            .map(|field| (field.to_string(), Bson::Int32(1)))
+            .collect();
+        if filter.text.is_some() {
+            projection.insert(TEXT_SCORE_FIELD, text_score.clone());
+            if filter.sort == SearchSort::Relevance {
+                find_options.sort = Some(doc! { TEXT_SCORE_FIELD: text_score, "_id": 1 });
+            }
+        }
+        if !projection.is_empty() {
+            find_options.projection = Some(projection);
+        }
+        match from {
+            SearchFrom::Offset(skip) => find_options.skip = Some(skip),
+            SearchFrom::After(last_id) => {
+                query.insert("_id", doc! { "$gt": last_id });
+            }
+        }
+        debug!("Final MongoDB filter: {:?}", query);
+
+        let cursor = self
+            .collection
+            .clone_with_type::<Document>()
+            .find(query)
+            .with_options(find_options)
+            .await
+            .map_err(|e| {
+                error!("MongoDB find operation failed: {}", e);
+                text_search_error(e)
+            })?;
+
+        cursor.try_collect().await.map_err(|e| {
+            error!("Error collecting results from MongoDB cursor: {}", e);
+            ServiceError::MongoDb(e)
+        })
+    }
+}
+
+fn is_duplicate_key(e: &mongodb::error::Error) -> bool {
+    matches!(
+        *e.kind.clone(),
+        ErrorKind::Write(mongodb::error::WriteFailure::WriteError(write_error))
+            if write_error.code == 11000
+    )
+}
+
+/// A `$text` query failed for want of the text index `db_setup` creates; anything else
+/// is a plain database error.
+fn text_search_error(e: mongodb::error::Error) -> ServiceError {
+    match e.kind.as_ref() {
+        ErrorKind::Command(command_error) if command_error.code == INDEX_NOT_FOUND_CODE => {
+            ServiceError::TextIndexMissing
+        }
+        _ => ServiceError::MongoDb(e),
+    }
+}
+
+/// [`completeness`] as an aggregation expression over a stored product. A field counts
+/// when it is there and neither null, `""` nor `[]`.
+fn completeness_expression() -> Document {
+    let present = |field: &str| {
+        let value = doc! { "$ifNull": [format!("${}", field), Bson::Null] };
+        doc! { "$not": [{ "$in": [value, [Bson::Null, "", []]] }] }
+    };
+    let points: Vec<Document> = COMPLETENESS_CRITERIA
+        .iter()
+        .map(|criterion| {
+            let any: Vec<Document> = criterion
+                .fields
+                .iter()
+                .map(|field| present(field))
+                .collect();
+            doc! { "$cond": [{ "$or": any }, i32::from(criterion.points), 0] }
+        })
+        .collect();
+    doc! { "$add": points }
+}
+
+/// Matches the products [`is_blank`] does not: those with a name or an image.
+fn not_blank_document() -> Document {
+    let present = |field: &str| doc! { field: { "$nin": [Bson::Null, ""] } };
+    doc! {
+        "$or": [present("product_name"), present("image_url"), present("image_small_url")]
+    }
+}
+
+/// Matches the documents whose `field` time comes from `from` on.
+fn changed_from_document(field: &str, from: ChangesFrom) -> Document {
+    match from.after_id {
+        Some(after_id) => doc! {
+            "$or": [
+                { field: { "$gt": from.at } },
+                { field: from.at, "_id": { "$gt": after_id } },
+            ]
+        },
+        None => doc! { field: { "$gt": from.at } },
+    }
+}
+
+/// Matches the documents whose `field` time and `_id` come before `from`.
+fn before_document(field: &str, from: RecentFrom) -> Document {
+    doc! {
+        "$or": [
+            { field: { "$lt": from.at } },
+            { field: from.at, "_id": { "$lt": from.before_id } },
+        ]
+    }
+}
+
+/// The first `limit` of `entries`, ordered by [`ChangeEntry::position`].
+fn first_changes(mut entries: Vec<ChangeEntry>, limit: u64) -> Vec<ChangeEntry> {
+    entries.sort_by_key(ChangeEntry::position);
+    entries.truncate(limit as usize);
+    entries
+}
+
+/// Matches the names made of exactly `tokens`, with anything but letters and digits
+/// around and between them. The tokens are letters and digits only, so need no escaping.
+/// No index serves it, so it scans the collection.
+fn name_tokens_pattern(tokens: &[String]) -> String {
+    format!(r"^[\W_]*{}[\W_]*$", tokens.join(r"[\W_]+"))
+}
+
+fn search_document(filter: &ProductFilter) -> Document {
+    let mut document = doc! {};
+    if let Some(text) = &filter.text {
+        document.insert("$text", doc! { "$search": text });
+    }
+    if !filter.categories.is_empty() {
+        let operator = match filter.category_match {
+            TagMatch::Any => "$in",
+            TagMatch::All => "$all",
+        };
+        document.insert("categories_tags", doc! { operator: &filter.categories });
+    }
+    for (field, tags) in [
+        ("brands_tags", &filter.brands),
+        ("countries_tags", &filter.countries),
+    ] {
+        if !tags.is_empty() {
+            document.insert(field, doc! { "$in": tags });
+        }
+    }
+    if let Some(nutriscore) = &filter.nutriscore {
+        document.insert("nutrition_grade_fr", nutriscore);
+    }
+    if let Some(ecoscore) = &filter.ecoscore {
+        document.insert("ecoscore_grade", ecoscore);
+    }
+    for (field, operator, bound) in [
+        ("nutriments.sugars_100g", "$lte", filter.max_sugar),
+        ("nutriments.salt_100g", "$lte", filter.max_salt),
+        ("nutriments.fat_100g", "$lte", filter.max_fat),
+        ("nutriments.proteins_100g", "$gte", filter.min_protein),
+    ] {
+        if let Some(bound) = bound {
+            document.insert(field, doc! { operator: bound });
+        }
+    }
+    let mut allergens = doc! {};
+    if !filter.excluded_allergens.is_empty() {
+        allergens.insert("$nin", &filter.excluded_allergens);
+    }
+    if filter.allergen_mode == AllergenMode::Strict {
+        allergens.insert("$exists", true);
+        allergens.insert("$ne", Bson::Null);
+        document.insert(
+            "ingredients_text",
+            doc! { "$exists": true, "$nin": [Bson::Null, ""] },
+        );
+    }
+    if !allergens.is_empty() {
+        document.insert("allergens_tags", allergens);
+    }
+    let mut labels = doc! {};
+    if !filter.labels.is_empty() {
+        labels.insert("$in", &filter.labels);
+    }
+    if !filter.excluded_labels.is_empty() {
+        labels.insert("$nin", &filter.excluded_labels);
+    }
+    if !labels.is_empty() {
+        document.insert("labels_tags", labels);
+    }
+    if !filter.included_ingredients.is_empty() {
+        let named: Vec<Document> = filter
+            .included_ingredients
+            .iter()
+            .map(|term| names_ingredient_document(term))
+            .collect();
+        document.insert("$and", named);
+    }
+    if !filter.excluded_ingredients.is_empty() {
+        let named: Vec<Document> = filter
+            .excluded_ingredients
+            .iter()
+            .map(|term| names_ingredient_document(term))
+            .collect();
+        document.insert("$nor", named);
+    }
+    if !filter.excluded_traces.is_empty() {
+        document.insert("traces_tags", doc! { "$nin": &filter.excluded_traces });
+    }
+    document
+}
+
+/// Matches the products whose ingredients name `term`, in any of the
+/// [`INGREDIENT_FIELDS`].
+fn names_ingredient_document(term: &str) -> Document {
+    let pattern = term_pattern(term);
+    let fields: Vec<Document> = INGREDIENT_FIELDS
+        .iter()
+        .map(|field| doc! { *field: { "$regex": &pattern, "$options": "i" } })
+        .collect();
+    doc! { "$or": fields }
+}
+
+/// The `$set` of an update making `changes`; the fields to unset are left to the caller.
+pub(crate) fn set_document(changes: &ProductChanges) -> Document {
+    let mut set_doc = doc! {};
+    if let Some(val) = &changes.product_name {
+        set_doc.insert("product_name", val);
+    }
+    if let Some(val) = &changes.generic_name {
+        set_doc.insert("generic_name", val);
+    }
+    if let Some(val) = &changes.image_url {
+        set_doc.insert("image_url", val);
+    }
+    if let Some(val) = &changes.image_small_url {
+        set_doc.insert("image_small_url", val);
+    }
+    if let Some(val) = &changes.image_thumb_url {
+        set_doc.insert("image_thumb_url", val);
+    }
+    if let Some(val) = &changes.ingredients_text {
+        set_doc.insert("ingredients_text", val);
+    }
+    if let Some(val) = &changes.ingredients {
+        let entries = bson::to_bson(val).expect("ingredient entries are plain BSON values");
+        set_doc.insert("ingredients", entries);
+    }
+    if let Some(val) = &changes.brands {
+        set_doc.insert("brands_tags", val);
+    }
+    if let Some(val) = &changes.categories {
+        set_doc.insert("categories_tags", val);
+    }
+    if let Some(val) = &changes.labels {
+        set_doc.insert("labels_tags", val);
+    }
+    if let Some(val) = &changes.traces {
+        set_doc.insert("traces_tags", val);
+    }
+    if let Some(val) = &changes.quantity {
+        set_doc.insert("quantity", val);
+    }
+    if let Some(val) = &changes.allergens_tags {
+        set_doc.insert("allergens_tags", val);
+    }
+    if let Some(val) = &changes.countries {
+        set_doc.insert("countries_tags", val);
+    }
+    if let Some(val) = &changes.nutrition_grade_fr {
+        set_doc.insert("nutrition_grade_fr", val);
+    }
+    if let Some(val) = &changes.nutrition_grade_source {
+        set_doc.insert("nutrition_grade_source", val);
+    }
+    if let Some(val) = &changes.ecoscore_grade {
+        set_doc.insert("ecoscore_grade", val);
+    }
+    if let Some(val) = changes.ecoscore_score {
+        set_doc.insert("ecoscore_score", val);
+    }
+    if let Some(val) = &changes.nutriments {
+        let nutriments = bson::to_bson(val).expect("nutriments are plain BSON values");
+        set_doc.insert("nutriments", nutriments);
+    }
+    set_doc
+}
+
+#[async_trait]
+impl ProductRepository for MongoProducts {
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Product>> {
+        self.collection
+            .find_one(doc! { "_id": id })
+            .await
+            .map_err(|e| {
+                error!(id = %id, "MongoDB find_one by ID failed: {}", e);
+                ServiceError::MongoDb(e)
+            })
+    }
+
+    async fn find_by_code(&self, code: &str) -> Result<Option<Product>> {
+        self.collection
+            .find_one(doc! { "code": code })
+            .await
+            .map_err(|e| {
+                error!(code = %code, "MongoDB find_one by code failed: {}", e);
+                ServiceError::MongoDb(e)
+            })
+    }
+
+    async fn find_by_codes(&self, codes: Vec<String>, limit: usize) -> Result<Vec<Product>> {
+        let cursor = self
+            .collection
+            .find(doc! { "code": { "$in": codes } })
+            .limit(limit as i64)
+            .await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn find_by_name_tokens(
+        &self,
+        tokens: &[String],
+        except: ObjectId,
+        limit: u64,
+    ) -> Result<Vec<Product>> {
+        if tokens.is_empty() {
+            return Ok(Vec::new());
+        }
+        let cursor = self
+            .collection
+            .find(doc! {
+                "_id": { "$ne": except },
+                "product_name": { "$regex": name_tokens_pattern(tokens), "$options": "i" },
+            })
+            .sort(doc! { "_id": 1 })
+            .limit(limit as i64)
+            .await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn search(
+        &self,
+        filter: &ProductFilter,
+        from: SearchFrom,
+        limit: u64,
+    ) -> Result<Vec<SearchHit>> {
+        self.find_matches(filter, from, limit, None)
+            .await?
+            .into_iter()
+            .map(|mut document| {
+                let score = document.remove(TEXT_SCORE_FIELD).and_then(|s| s.as_f64());
+                let product = bson::from_document(document)?;
+                Ok(SearchHit {
+                    product,
+                    score,
+                    display_name: None,
+                    highlights: None,
+                })
+            })
+            .collect()
+    }
+
+    async fn search_summaries(
+        &self,
+        filter: &ProductFilter,
+        from: SearchFrom,
+        limit: u64,
+    ) -> Result<Vec<SearchSummary>> {
+        self.find_matches(filter, from, limit, Some(&SearchSummary::FIELDS))
+            .await?
+            .into_iter()
+            .map(|mut document| {
+                let score = document.remove(TEXT_SCORE_FIELD).and_then(|s| s.as_f64());
+                let summary: SearchSummary = bson::from_document(document)?;
+                Ok(SearchSummary { score, ..summary })
+            })
+            .collect()
+    }
+
+    /// Scores every matching product in the pipeline; only the page is returned.
+    async fn least_complete(
+        &self,
+        filter: &ProductFilter,
+        max_score: u8,
+        skip: u64,
+        limit: u64,
+    ) -> Result<Vec<IncompleteProduct>> {
+        let pipeline = vec![
+            doc! { "$match": search_document(filter) },
+            doc! { "$addFields": { COMPLETENESS_FIELD: completeness_expression() } },
+            doc! { "$match": { COMPLETENESS_FIELD: { "$lte": i32::from(max_score) } } },
+            doc! { "$sort": { COMPLETENESS_FIELD: 1, "_id": 1 } },
+            doc! { "$skip": skip as i64 },
+            doc! { "$limit": limit as i64 },
+        ];
+        let cursor = self.collection.aggregate(pipeline).await.map_err(|e| {
+            error!("MongoDB completeness aggregation failed: {}", e);
+            ServiceError::MongoDb(e)
+        })?;
+        let documents: Vec<Document> = cursor.try_collect().await?;
+        documents
+            .into_iter()
+            .map(|mut document| {
+                let completeness = document
+                    .remove(COMPLETENESS_FIELD)
+                    .and_then(|score| score.as_i32())
+                    .unwrap_or_default() as u8;
+                let product = bson::from_document(document)?;
+                Ok(IncompleteProduct {
+                    product,
+                    completeness,
+                })
+            })
+            .collect()
+    }
+
+    async fn sample(&self, filter: &ProductFilter, size: u64) -> Result<Vec<Product>> {
+        let pipeline = vec![
+            doc! { "$match": search_document(filter) },
+            doc! { "$match": not_blank_document() },
+            doc! { "$sample": { "size": size as i64 } },
+        ];
+        let cursor = self.collection.aggregate(pipeline).await.map_err(|e| {
+            error!("MongoDB sample aggregation failed: {}", e);
+            text_search_error(e)
+        })?;
+        let documents: Vec<Document> = cursor.try_collect().await?;
+        documents
+            .into_iter()
+            .map(|document| Ok(bson::from_document(document)?))
+            .collect()
+    }
+
+    /// Reads the collection's metadata instead when nothing is filtered, which is far
+    /// cheaper than counting and only off right after an unclean shutdown.
+    async fn count(&self, filter: &ProductFilter) -> Result<u64> {
+        let query = search_document(filter);
+        if query.is_empty() {
+            return self
+                .collection
+                .estimated_document_count()
+                .await
+                .map_err(|e| {
+                    error!("MongoDB estimated_document_count failed: {}", e);
+                    ServiceError::MongoDb(e)
+                });
+        }
+        self.collection.count_documents(query).await.map_err(|e| {
+            error!("MongoDB count_documents failed: {}", e);
+            text_search_error(e)
+        })
+    }
+
+    async fn category_counts(
+        &self,
+        filter: &ProductFilter,
+        query: &CategoryQuery,
+    ) -> Result<Vec<TagCount>> {
+        let mut categories = doc! {};
+        if let Some(prefix) = &query.prefix {
+            categories.insert("$regex", format!("^{}", prefix));
+        }
+        if let Some(within) = &query.within {
+            categories.insert("$in", within.clone());
+        }
+        let mut pipeline = vec![
+            doc! { "$match": search_document(filter) },
+            doc! { "$unwind": "$categories_tags" },
+        ];
+        if !categories.is_empty() {
+            pipeline.push(doc! { "$match": { "categories_tags": categories } });
+        }
+        pipeline.extend([
+            doc! { "$group": { "_id": "$categories_tags", "count": { "$sum": 1 } } },
+            doc! { "$match": { "count": { "$gte": query.min_count as i64 } } },
+            doc! { "$sort": { "count": -1, "_id": 1 } },
+            doc! { "$limit": query.limit as i64 },
+            doc! { "$project": { "_id": 0, "tag": "$_id", "count": 1 } },
+        ]);
+        let cursor = self.collection.aggregate(pipeline).await.map_err(|e| {
+            error!("MongoDB category aggregation failed: {}", e);
+            text_search_error(e)
+        })?;
+        let documents: Vec<Document> = cursor.try_collect().await?;
+        documents
+            .into_iter()
+            .map(|document| Ok(bson::from_document(document)?))
+            .collect()
+    }
+
+    async fn vocabulary(&self, limit: u64) -> Result<Vec<String>> {
+        let pipeline = vec![
+            doc! { "$project": {
+                "_id": 0,
+                "texts": { "$concatArrays": [
+                    [{ "$ifNull": ["$product_name", ""] }],
+                    { "$cond": [{ "$isArray": "$brands_tags" }, "$brands_tags", []] },
+                ] },
+            } },
+            doc! { "$unwind": "$texts" },
+            doc! { "$project": { "words": { "$map": {
+                "input": { "$regexFindAll": {
+                    "input": { "$toLower": "$texts" },
+                    "regex": r"[\p{L}\p{N}]+",
+                } },
+                "in": "$$this.match",
+            } } } },
+            doc! { "$unwind": "$words" },
+            doc! { "$match": { "$expr": {
+                "$gte": [{ "$strLenCP": "$words" }, MIN_WORD_CHARS as i64],
+            } } },
+            doc! { "$group": { "_id": "$words", "count": { "$sum": 1 } } },
+            doc! { "$sort": { "count": -1, "_id": 1 } },
+            doc! { "$limit": limit as i64 },
+        ];
+        let cursor = self
+            .collection
+            .aggregate(pipeline)
+            .allow_disk_use(true)
+            .await
+            .map_err(|e| {
+                error!("MongoDB vocabulary aggregation failed: {}", e);
+                ServiceError::MongoDb(e)
+            })?;
+        let documents: Vec<Document> = cursor.try_collect().await?;
+        // $toLower only lowers ASCII, so words like `Über` are lowered here and kept
+        // where their most frequent spelling ranks.
+        let mut words: Vec<String> = Vec::with_capacity(documents.len());
+        for document in documents {
+            if let Ok(word) = document.get_str("_id") {
+                let word = word.to_lowercase();
+                if !words.contains(&word) {
+                    words.push(word);
+                }
+            }
+        }
+        Ok(words)
+    }
+
+    async fn insert(&self, mut product: Product) -> Result<Product> {
+        let insert_result = self.collection.insert_one(&product).await.map_err(|e| {
+            if is_duplicate_key(&e) {
+                error!("Duplicate key error on insert: {}", e);
+                return ServiceError::Conflict(
+                    "Product with this code already exists.".to_string(),
+                );
+            }
+            error!("Failed to insert product into DB: {}", e);
+            ServiceError::MongoDb(e)
+        })?;
+        product.id = insert_result.inserted_id.as_object_id();
+        Ok(product)
+    }
+
+    /// One unordered client-level `bulkWrite`, which needs MongoDB 8.0.
+    async fn upsert_by_code(&self, products: Vec<Product>) -> Result<UpsertCounts> {
+        if products.is_empty() {
+            return Ok(UpsertCounts::default());
+        }
+        let namespace = self.collection.namespace();
+        let models = products
+            .iter()
+            .map(|product| {
+                Ok(ReplaceOneModel::builder()
+                    .namespace(namespace.clone())
+                    .filter(doc! { "code": &product.code })
+                    .replacement(bson::to_document(product)?)
+                    .upsert(true)
+                    .build())
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let result = self
+            .collection
+            .client()
+            .bulk_write(models)
+            .ordered(false)
+            .await
+            .map_err(|e| {
+                error!("Bulk upsert of {} products failed: {}", products.len(), e);
+                ServiceError::MongoDb(e)
+            })?;
+        Ok(UpsertCounts {
+            inserted: result.upserted_count as u64,
+            updated: result.matched_count as u64,
+        })
+    }
+
+    async fn update(&self, id: ObjectId, changes: &ProductChanges) -> Result<Option<Product>> {
+        let mut set_doc = set_document(changes);
+        set_doc.insert("last_modified_datetime", Utc::now());
+
+        let mut update_doc = doc! { "$set": set_doc };
+        if !changes.unset.is_empty() {
+            let mut unset_doc = doc! {};
+            for field in &changes.unset {
+                unset_doc.insert(field.key(), "");
+            }
+            update_doc.insert("$unset", unset_doc);
+        }
+        debug!(id = %id, update = ?update_doc, "Constructed update document");
+
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        self.collection
+            .find_one_and_update(doc! {"_id": id}, update_doc)
+            .with_options(options)
+            .await
+            .map_err(|e| {
+                if is_duplicate_key(&e) {
+                    error!("Duplicate key error on update: {}", e);
+                    return ServiceError::Conflict(
+                        "Update failed due to duplicate key (e.g., code already exists)."
+                            .to_string(),
+                    );
+                }
+                error!(id = %id, "Failed to update product in DB: {}", e);
+                ServiceError::MongoDb(e)
+            })
+    }
+
+    /// Each query reads at most `limit` of its kind; the first `limit` of both are the
+    /// page.
+    async fn changes(&self, from: ChangesFrom, limit: u64) -> Result<Vec<ChangeEntry>> {
+        let products_options = FindOptions::builder()
+            .sort(doc! { "last_modified_datetime": 1, "_id": 1 })
+            .limit(limit as i64)
+            .build();
+        let products: Vec<Product> = self
+            .collection
+            .find(changed_from_document("last_modified_datetime", from))
+            .with_options(products_options)
+            .await?
+            .try_collect()
+            .await?;
+
+        let tombstones_options = FindOptions::builder()
+            .sort(doc! { "deleted_datetime": 1, "_id": 1 })
+            .limit(limit as i64)
+            .build();
+        let tombstones: Vec<Tombstone> = self
+            .tombstones
+            .find(changed_from_document("deleted_datetime", from))
+            .with_options(tombstones_options)
+            .await?
+            .try_collect()
+            .await?;
+
+        let entries = products
+            .into_iter()
+            .map(|product| ChangeEntry::Modified(Box::new(product)))
+            .chain(tombstones.into_iter().map(ChangeEntry::Deleted))
+            .collect();
+        Ok(first_changes(entries, limit))
+    }
+
+    async fn add_scans(&self, scans: &[(String, u64)]) -> Result<()> {
+        if scans.is_empty() {
+            return Ok(());
+        }
+        let namespace = self.collection.namespace();
+        let models: Vec<UpdateOneModel> = scans
+            .iter()
+            .map(|(code, scans)| {
+                UpdateOneModel::builder()
+                    .namespace(namespace.clone())
+                    .filter(doc! { "code": code })
+                    .update(doc! { "$inc": { "scan_count": *scans as i64 } })
+                    .build()
+            })
+            .collect();
+        self.collection
+            .client()
+            .bulk_write(models)
+            .ordered(false)
+            .await
+            .map_err(|e| {
+                error!("Adding the scans of {} products failed: {}", scans.len(), e);
+                ServiceError::MongoDb(e)
+            })?;
+        Ok(())
+    }
+
+    /// Reads only the [`SearchSummary`] fields and the time.
+    async fn recent(
+        &self,
+        kind: RecentKind,
+        filter: &ProductFilter,
+        from: Option<RecentFrom>,
+        limit: u64,
+    ) -> Result<Vec<RecentProduct>> {
+        let field = kind.field();
+        let query = match from {
+            Some(from) => doc! { "$and": [search_document(filter), before_document(field, from)] },
+            None => search_document(filter),
+        };
+        let projection: Document = SearchSummary::FIELDS
+            .iter()
+            .copied()
+            .chain([field])
+            .map(|field| (field.to_string(), Bson::Int32(1)))
+            .collect();
+        let options = FindOptions::builder()
+            .sort(doc! { field: -1, "_id": -1 })
+            .projection(projection)
+            .limit(limit as i64)
+            .build();
+        let documents: Vec<Document> = self
+            .collection
+            .clone_with_type::<Document>()
+            .find(query)
+            .with_options(options)
+            .await?
+            .try_collect()
+            .await?;
+        documents
+            .into_iter()
+            .map(|document| {
+                let at = match document.get(field) {
+                    Some(Bson::DateTime(at)) => at.to_chrono(),
+                    _ => DateTime::UNIX_EPOCH,
+                };
+                let summary: SearchSummary = bson::from_document(document)?;
+                Ok(RecentProduct { at, summary })
+            })
+            .collect()
+    }
+
+    /// Writes the tombstone first, so a product is never gone without one; it is taken
+    /// back if the delete then fails.
+    async fn delete(&self, id: ObjectId) -> Result<bool> {
+        let Some(product) = self.find_by_id(id).await? else {
+            return Ok(false);
+        };
+        let tombstone = Tombstone {
+            id,
+            code: product.code,
+            deleted_at: Utc::now(),
+        };
+        self.tombstones
+            .replace_one(doc! { "_id": id }, &tombstone)
+            .upsert(true)
+            .await
+            .map_err(|e| {
+                error!(id = %id, "Failed to write the product's tombstone: {}", e);
+                ServiceError::MongoDb(e)
+            })?;
+
+        match self.collection.delete_one(doc! { "_id": id }).await {
+            Ok(delete_result) => Ok(delete_result.deleted_count > 0),
+            Err(e) => {
+                error!(id = %id, "MongoDB delete_one failed: {}", e);
+                if let Err(e) = self.tombstones.delete_one(doc! { "_id": id }).await {
+                    error!(id = %id, "Failed to remove the tombstone of an undeleted product: {}", e);
+                }
+                Err(ServiceError::MongoDb(e))
+            }
+        }
+    }
+}
+
+/// Products in insertion order, and the tombstones of deleted ones. Clones share the
+/// same lists.
+#[derive(Clone, Default)]
+pub struct MemoryProducts {
+    products: Arc<Mutex<Vec<Product>>>,
+    tombstones: Arc<Mutex<Vec<Tombstone>>>,
+}
+
+impl MemoryProducts {
+    /// Stores `product` as is, id and timestamps included; unlike
+    /// [`ProductRepository::insert`] it neither assigns an id nor checks the code.
+    pub fn seed(&self, product: Product) {
+        self.products.lock().unwrap().push(product);
+    }
+}
+
+/// Like `$in`, or `$all` for [`TagMatch::All`]; no wanted tags match everything.
+fn matches_tags(values: &Option<Vec<String>>, wanted: &[String], how: TagMatch) -> bool {
+    let has = |tag: &String| values.iter().flatten().any(|v| v == tag);
+    match how {
+        _ if wanted.is_empty() => true,
+        TagMatch::Any => wanted.iter().any(has),
+        TagMatch::All => wanted.iter().all(has),
+    }
+}
+
+fn has_any(values: &[String], excluded: &[String]) -> bool {
+    values.iter().any(|v| excluded.contains(v))
+}
+
+/// Like MongoDB's `$text`: any of the query's words, ignoring case.
+fn matches_text(product: &Product, text: &str) -> bool {
+    text_score(product, text) > 0
+}
+
+/// How many of the query's words the product has, a stand-in for MongoDB's `textScore`.
+fn text_score(product: &Product, text: &str) -> usize {
+    let haystack = [
+        product.product_name.as_deref(),
+        product.generic_name.as_deref(),
+        product.ingredients_text.as_deref(),
+    ]
+    .into_iter()
+    .flatten()
+    .chain(product.brands.iter().flatten().map(String::as_str))
+    .collect::<Vec<_>>()
+    .join(" ")
+    .to_lowercase();
+    text.to_lowercase()
+        .split_whitespace()
+        .filter(|word| haystack.contains(word))
+        .count()
+}
+
+fn matches_filter(product: &Product, filter: &ProductFilter) -> bool {
+    let nutriment =
+        |value: fn(&Nutriments) -> Option<f64>| product.nutriments.as_ref().and_then(value);
+    let at_most = |value: fn(&Nutriments) -> Option<f64>, max: Option<f64>| {
+        max.is_none_or(|max| nutriment(value).is_some_and(|v| v <= max))
+    };
+    filter
+        .text
+        .as_deref()
+        .is_none_or(|t| matches_text(product, t))
+        && matches_tags(
+            &product.categories,
+            &filter.categories,
+            filter.category_match,
+        )
+        && matches_tags(&product.brands, &filter.brands, TagMatch::Any)
+        && matches_tags(&product.labels, &filter.labels, TagMatch::Any)
+        && matches_tags(&product.countries, &filter.countries, TagMatch::Any)
+        && filter
+            .nutriscore
+            .as_deref()
+            .is_none_or(|n| product.nutrition_grade_fr.as_deref() == Some(n))
+        && filter
+            .ecoscore
+            .as_deref()
+            .is_none_or(|e| product.ecoscore_grade.as_deref() == Some(e))
+        && at_most(|n| n.sugars_100g, filter.max_sugar)
+        && at_most(|n| n.salt_100g, filter.max_salt)
+        && at_most(|n| n.fat_100g, filter.max_fat)
+        && filter
+            .min_protein
+            .is_none_or(|min| nutriment(|n| n.proteins_100g).is_some_and(|v| v >= min))
+        && !has_any(&product.allergens_tags, &filter.excluded_allergens)
+        && (filter.allergen_mode == AllergenMode::Lenient
+            || product
+                .ingredients_text
+                .as_deref()
+                .is_some_and(|t| !t.is_empty()))
+        && !has_any(
+            product.labels.as_deref().unwrap_or_default(),
+            &filter.excluded_labels,
+        )
+        && filter
+            .included_ingredients
+            .iter()
+            .all(|term| product_names(product, term))
+        && !filter
+            .excluded_ingredients
+            .iter()
+            .any(|term| product_names(product, term))
+        && !has_any(
+            product.traces_tags.as_deref().unwrap_or_default(),
+            &filter.excluded_traces,
+        )
+}
+
+/// Like [`changed_from_document`].
+fn is_changed_from(entry: &ChangeEntry, from: ChangesFrom) -> bool {
+    let (at, id) = entry.position();
+    at > from.at || (at == from.at && from.after_id.is_some_and(|after_id| id > Some(after_id)))
+}
+
+fn apply(product: &mut Product, changes: &ProductChanges) {
+    let changes = changes.clone();
+    if let Some(val) = changes.product_name {
+        product.product_name = Some(val);
+    }
+    if let Some(val) = changes.generic_name {
+        product.generic_name = Some(val);
+    }
+    if let Some(val) = changes.image_url {
+        product.image_url = Some(val);
+    }
+    if let Some(val) = changes.image_small_url {
+        product.image_small_url = Some(val);
+    }
+    if let Some(val) = changes.image_thumb_url {
+        product.image_thumb_url = Some(val);
+    }
+    if let Some(val) = changes.ingredients_text {
+        product.ingredients_text = Some(val);
+    }
+    if let Some(val) = changes.ingredients {
+        product.ingredients = Some(val);
+    }
+    if let Some(val) = changes.brands {
+        product.brands = Some(val);
+    }
+    if let Some(val) = changes.categories {
+        product.categories = Some(val);
+    }
+    if let Some(val) = changes.labels {
+        product.labels = Some(val);
+    }
+    if let Some(val) = changes.traces {
+        product.traces_tags = Some(val);
+    }
+    if let Some(val) = changes.quantity {
+        product.quantity = Some(val);
+    }
+    if let Some(val) = changes.allergens_tags {
+        product.allergens_tags = val;
+    }
+    if let Some(val) = changes.countries {
+        product.countries = Some(val);
+    }
+    if let Some(val) = changes.nutrition_grade_fr {
+        product.nutrition_grade_fr = Some(val);
+    }
+    if let Some(val) = changes.nutrition_grade_source {
+        product.nutrition_grade_source = Some(val);
+    }
+    if let Some(val) = changes.ecoscore_grade {
+        product.ecoscore_grade = Some(val);
+    }
+    if let Some(val) = changes.ecoscore_score {
+        product.ecoscore_score = Some(val);
+    }
+    if let Some(val) = changes.nutriments {
+        product.nutriments = Some(val);
+    }
+    for field in changes.unset {
+        match field {
+            ProductField::ProductName => product.product_name = None,
+            ProductField::GenericName => product.generic_name = None,
+            ProductField::ImageUrl => product.image_url = None,
+            ProductField::ImageSmallUrl => product.image_small_url = None,
+            ProductField::ImageThumbUrl => product.image_thumb_url = None,
+            ProductField::IngredientsText => product.ingredients_text = None,
+            ProductField::Brands => product.brands = None,
+            ProductField::Categories => product.categories = None,
+            ProductField::Labels => product.labels = None,
+            ProductField::Traces => product.traces_tags = None,
+            ProductField::Quantity => product.quantity = None,
+            ProductField::Countries => product.countries = None,
+            ProductField::NutritionGrade => product.nutrition_grade_fr = None,
+            ProductField::NutritionGradeSource => product.nutrition_grade_source = None,
+            ProductField::EcoscoreGrade => product.ecoscore_grade = None,
+            ProductField::EcoscoreScore => product.ecoscore_score = None,
+        }
+    }
+    product.last_modified_at = Utc::now();
+}
+
+#[async_trait]
+impl ProductRepository for MemoryProducts {
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Product>> {
+        let products = self.products.lock().unwrap();
+        Ok(products.iter().find(|p| p.id == Some(id)).cloned())
+    }
+
+    async fn find_by_code(&self, code: &str) -> Result<Option<Product>> {
+        let products = self.products.lock().unwrap();
+        Ok(products.iter().find(|p| p.code == code).cloned())
+    }
+
+    async fn find_by_codes(&self, codes: Vec<String>, limit: usize) -> Result<Vec<Product>> {
+        let products = self.products.lock().unwrap();
+        Ok(products
+            .iter()
+            .filter(|p| codes.contains(&p.code))
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn find_by_name_tokens(
+        &self,
+        tokens: &[String],
+        except: ObjectId,
+        limit: u64,
+    ) -> Result<Vec<Product>> {
+        if tokens.is_empty() {
+            return Ok(Vec::new());
+        }
+        let products = self.products.lock().unwrap();
+        Ok(products
+            .iter()
+            .filter(|p| p.id != Some(except))
+            .filter(|p| p.product_name.as_deref().map(name_tokens).as_deref() == Some(tokens))
+            .take(limit as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn search(
+        &self,
+        filter: &ProductFilter,
+        from: SearchFrom,
+        limit: u64,
+    ) -> Result<Vec<SearchHit>> {
+        let products = self.products.lock().unwrap();
+        let mut matches: Vec<(&Product, Option<usize>)> = products
+            .iter()
+            .filter(|p| matches_filter(p, filter))
+            .map(|p| (p, filter.text.as_deref().map(|t| text_score(p, t))))
+            .collect();
+        matches.sort_by_key(|(p, _)| p.id);
+        if filter.sort == SearchSort::Relevance {
+            matches.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+        }
+        let skip = match from {
+            SearchFrom::Offset(skip) => skip as usize,
+            SearchFrom::After(last_id) => matches.partition_point(|(p, _)| p.id <= Some(last_id)),
+        };
+        Ok(matches
+            .into_iter()
+            .skip(skip)
+            .take(limit as usize)
+            .map(|(product, score)| SearchHit {
+                product: product.clone(),
+                score: score.map(|s| s as f64),
+                display_name: None,
+                highlights: None,
+            })
+            .collect())
+    }
+
+    async fn search_summaries(
+        &self,
+        filter: &ProductFilter,
+        from: SearchFrom,
+        limit: u64,
+    ) -> Result<Vec<SearchSummary>> {
+        let hits = self.search(filter, from, limit).await?;
+        Ok(hits.into_iter().map(SearchSummary::from).collect())
+    }
+
+    async fn least_complete(
+        &self,
+        filter: &ProductFilter,
+        max_score: u8,
+        skip: u64,
+        limit: u64,
+    ) -> Result<Vec<IncompleteProduct>> {
+        let products = self.products.lock().unwrap();
+        let mut matches: Vec<IncompleteProduct> = products
+            .iter()
+            .filter(|p| matches_filter(p, filter))
+            .map(|p| IncompleteProduct {
+                product: p.clone(),
+                completeness: completeness(p),
+            })
+            .filter(|found| found.completeness <= max_score)
+            .collect();
+        matches.sort_by_key(|found| (found.completeness, found.product.id));
+        Ok(matches
+            .into_iter()
+            .skip(skip as usize)
+            .take(limit as usize)
+            .collect())
+    }
+
+    async fn sample(&self, filter: &ProductFilter, size: u64) -> Result<Vec<Product>> {
+        let products = self.products.lock().unwrap();
+        let mut rng = rand::rng();
+        let mut picked: Vec<&Product> = products
+            .iter()
+            .filter(|p| matches_filter(p, filter) && !is_blank(p))
+            .choose_multiple(&mut rng, size as usize);
+        picked.shuffle(&mut rng);
+        Ok(picked.into_iter().cloned().collect())
+    }
+
+    async fn count(&self, filter: &ProductFilter) -> Result<u64> {
+        let products = self.products.lock().unwrap();
+        Ok(products
+            .iter()
+            .filter(|p| matches_filter(p, filter))
+            .count() as u64)
+    }
+
+    async fn category_counts(
+        &self,
+        filter: &ProductFilter,
+        query: &CategoryQuery,
+    ) -> Result<Vec<TagCount>> {
+        let products = self.products.lock().unwrap();
+        let mut counts: HashMap<&str, u64> = HashMap::new();
+        for product in products.iter().filter(|p| matches_filter(p, filter)) {
+            for tag in product.categories.iter().flatten() {
+                let listed = query
+                    .prefix
+                    .as_ref()
+                    .is_none_or(|prefix| tag.starts_with(prefix.as_str()))
+                    && query
+                        .within
+                        .as_ref()
+                        .is_none_or(|within| within.contains(tag));
+                if listed {
+                    *counts.entry(tag.as_str()).or_default() += 1;
+                }
+            }
+        }
+        let mut counts: Vec<TagCount> = counts
+            .into_iter()
+            .filter(|(_, count)| *count >= query.min_count)
+            .map(|(tag, count)| TagCount {
+                tag: tag.to_string(),
+                count,
+            })
+            .collect();
+        counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.cmp(&b.tag)));
+        counts.truncate(query.limit as usize);
+        Ok(counts)
+    }
+
+    async fn vocabulary(&self, limit: u64) -> Result<Vec<String>> {
+        let products = self.products.lock().unwrap();
+        let mut counts: HashMap<String, u64> = HashMap::new();
+        for product in products.iter() {
+            let texts = product
+                .product_name
+                .iter()
+                .chain(product.brands.iter().flatten());
+            for word in texts.flat_map(|text| name_tokens(text)) {
+                if word.chars().count() >= MIN_WORD_CHARS {
+                    *counts.entry(word).or_default() += 1;
+                }
+            }
+        }
+        let mut counts: Vec<(String, u64)> = counts.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts.truncate(limit as usize);
+        Ok(counts.into_iter().map(|(word, _)| word).collect())
+    }
+
+    async fn insert(&self, mut product: Product) -> Result<Product> {
+        let mut products = self.products.lock().unwrap();
+        if products.iter().any(|p| p.code == product.code) {
+            return Err(ServiceError::Conflict(
+                "Product with this code already exists.".to_string(),
+            ));
+        }
+        product.id = Some(ObjectId::new());
+        products.push(product.clone());
+        Ok(product)
+    }
+
+    async fn upsert_by_code(&self, products: Vec<Product>) -> Result<UpsertCounts> {
+        let mut stored = self.products.lock().unwrap();
+        let mut counts = UpsertCounts::default();
+        for mut product in products {
+            match stored.iter_mut().find(|p| p.code == product.code) {
+                Some(existing) => {
+                    product.id = existing.id;
+                    *existing = product;
+                    counts.updated += 1;
+                }
+                None => {
+                    product.id = Some(ObjectId::new());
+                    stored.push(product);
+                    counts.inserted += 1;
+                }
+            }
+        }
+        Ok(counts)
+    }
+
+    async fn update(&self, id: ObjectId, changes: &ProductChanges) -> Result<Option<Product>> {
+        let mut products = self.products.lock().unwrap();
+        Ok(products.iter_mut().find(|p| p.id == Some(id)).map(|p| {
+            apply(p, changes);
+            p.clone()
+        }))
+    }
+
+    async fn add_scans(&self, scans: &[(String, u64)]) -> Result<()> {
+        let mut products = self.products.lock().unwrap();
+        for (code, scans) in scans {
+            if let Some(product) = products.iter_mut().find(|p| &p.code == code) {
+                product.scan_count = Some(product.scan_count.unwrap_or_default() + scans);
+            }
+        }
+        Ok(())
+    }
+
+    async fn changes(&self, from: ChangesFrom, limit: u64) -> Result<Vec<ChangeEntry>> {
+        let products = self.products.lock().unwrap();
+        let tombstones = self.tombstones.lock().unwrap();
+        let entries = products
+            .iter()
+            .cloned()
+            .map(|product| ChangeEntry::Modified(Box::new(product)))
+            .chain(tombstones.iter().cloned().map(ChangeEntry::Deleted))
+            .filter(|entry| is_changed_from(entry, from))
+            .collect();
+        Ok(first_changes(entries, limit))
+    }
+
+    async fn recent(
+        &self,
+        kind: RecentKind,
+        filter: &ProductFilter,
+        from: Option<RecentFrom>,
+        limit: u64,
+    ) -> Result<Vec<RecentProduct>> {
+        let position = |product: &Product| (kind.time(product), product.id);
+        let products = self.products.lock().unwrap();
+        let mut matches: Vec<&Product> = products
+            .iter()
+            .filter(|p| matches_filter(p, filter))
+            .filter(|p| from.is_none_or(|from| position(p) < (from.at, Some(from.before_id))))
+            .collect();
+        matches.sort_by_key(|p| std::cmp::Reverse(position(p)));
+        Ok(matches
+            .into_iter()
+            .take(limit as usize)
+            .map(|product| RecentProduct {
+                at: kind.time(product),
+                summary: SearchSummary::from(SearchHit {
+                    product: product.clone(),
+                    score: None,
+                    display_name: None,
+                    highlights: None,
+                }),
+            })
+            .collect())
+    }
+
+    async fn delete(&self, id: ObjectId) -> Result<bool> {
+        let mut products = self.products.lock().unwrap();
+        let Some(index) = products.iter().position(|p| p.id == Some(id)) else {
+            return Ok(false);
+        };
+        let product = products.remove(index);
+        let mut tombstones = self.tombstones.lock().unwrap();
+        tombstones.retain(|t| t.id != id);
+        tombstones.push(Tombstone {
+            id,
+            code: product.code,
+            deleted_at: Utc::now(),
+        });
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yoloeats_domain::fixtures::ProductFixture;
+
+    fn product(code: &str, name: &str) -> ProductFixture {
+        ProductFixture::new(code)
+            .named(name)
+            .with_categories(["en:snacks"])
+    }
+
+    #[tokio::test]
+    async fn memory_insert_assigns_ids_and_refuses_taken_codes() {
+        let products = MemoryProducts::default();
+        let inserted = products
+            .insert(product("123", "Crisps").build())
+            .await
+            .unwrap();
+        let id = inserted.id.expect("id assigned");
+        assert_eq!(products.find_by_id(id).await.unwrap().unwrap().code, "123");
+        assert!(matches!(
+            products.insert(product("123", "Other").build()).await,
+            Err(ServiceError::Conflict(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn memory_find_by_name_tokens_matches_whole_names_only() {
+        let products = MemoryProducts::default();
+        let mut ids = Vec::new();
+        for (code, name) in [
+            ("1", "Ritter Sport Alpenmilch"),
+            ("2", "RITTER SPORT - alpenmilch"),
+            ("3", "Ritter Sport Alpenmilch Nuss"),
+            ("4", "ritter sport  alpenmilch!"),
+        ] {
+            let inserted = products.insert(product(code, name).build()).await.unwrap();
+            ids.push(inserted.id.unwrap());
+        }
+        let tokens = name_tokens("Ritter Sport Alpenmilch");
+
+        let found = products
+            .find_by_name_tokens(&tokens, ids[0], 10)
+            .await
+            .unwrap();
+        let codes: Vec<&str> = found.iter().map(|p| p.code.as_str()).collect();
+        assert_eq!(codes, ["2", "4"]);
+
+        let first = products
+            .find_by_name_tokens(&tokens, ids[0], 1)
+            .await
+            .unwrap();
+        assert_eq!(first[0].code, "2");
+        assert!(
+            products
+                .find_by_name_tokens(&[], ids[0], 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn name_tokens_pattern_allows_any_separators() {
+        assert_eq!(
+            name_tokens_pattern(&name_tokens("Ritter Sport, 100g")),
+            r"^[\W_]*ritter[\W_]+sport[\W_]+100g[\W_]*$"
+        );
+    }
+
+    #[tokio::test]
+    async fn memory_sample_picks_matching_products_that_are_not_blank() {
+        let products = MemoryProducts::default();
+        for i in 0..20 {
+            products
+                .insert(product(&format!("{}", 100 + i), "Crisps").build())
+                .await
+                .unwrap();
+        }
+        products
+            .insert(ProductFixture::new("999").unnamed().build())
+            .await
+            .unwrap();
+        products
+            .insert(
+                product("998", "Peanut crisps")
+                    .with_allergens(["en:peanuts"])
+                    .build(),
+            )
+            .await
+            .unwrap();
+        let filter = ProductFilter {
+            excluded_allergens: vec!["en:peanuts".to_string()],
+            ..Default::default()
+        };
+
+        let mut seen = std::collections::HashSet::new();
+        for _ in 0..20 {
+            let picked = products.sample(&filter, 5).await.unwrap();
+            assert_eq!(picked.len(), 5);
+            for p in picked {
+                assert!(p.code != "999" && p.code != "998", "{}", p.code);
+                seen.insert(p.code);
+            }
+        }
+        assert!(seen.len() > 5, "{:?}", seen);
+
+        let everything = products
+            .sample(&ProductFilter::default(), 50)
+            .await
+            .unwrap();
+        assert_eq!(everything.len(), 21);
+    }
+
+    #[tokio::test]
+    async fn memory_category_counts_are_filtered_and_ordered_by_count() {
+        let products = MemoryProducts::default();
+        for (code, categories, country) in [
+            ("101", &["en:snacks", "en:chocolates"][..], "en:germany"),
+            ("102", &["en:snacks", "en:chocolates"], "en:germany"),
+            ("103", &["en:snacks", "en:crisps"], "en:germany"),
+            ("104", &["en:spreads", "en:chocolate-spreads"], "en:france"),
+        ] {
+            products
+                .insert(
+                    product(code, "Snack")
+                        .with_categories(categories)
+                        .with_countries([country])
+                        .build(),
+                )
+                .await
+                .unwrap();
+        }
+        let query = CategoryQuery {
+            prefix: None,
+            within: None,
+            min_count: 1,
+            limit: 10,
+        };
+        let counts = |filter: ProductFilter, query: CategoryQuery| {
+            let products = &products;
+            async move {
+                products
+                    .category_counts(&filter, &query)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|count| (count.tag, count.count))
+                    .collect::<Vec<_>>()
+            }
+        };
+        let pairs = |expected: &[(&str, u64)]| -> Vec<(String, u64)> {
+            expected
+                .iter()
+                .map(|(tag, count)| (tag.to_string(), *count))
+                .collect()
+        };
+
+        assert_eq!(
+            counts(ProductFilter::default(), query.clone()).await,
+            pairs(&[
+                ("en:snacks", 3),
+                ("en:chocolates", 2),
+                ("en:chocolate-spreads", 1),
+                ("en:crisps", 1),
+                ("en:spreads", 1),
+            ])
+        );
+        let in_germany = ProductFilter {
+            countries: vec!["en:germany".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            counts(
+                in_germany,
+                CategoryQuery {
+                    prefix: Some("en:c".to_string()),
+                    ..query.clone()
+                }
+            )
+            .await,
+            pairs(&[("en:chocolates", 2), ("en:crisps", 1)])
+        );
+        assert_eq!(
+            counts(
+                ProductFilter::default(),
+                CategoryQuery {
+                    min_count: 2,
+                    ..query.clone()
+                }
+            )
+            .await,
+            pairs(&[("en:snacks", 3), ("en:chocolates", 2)])
+        );
+        assert_eq!(
+            counts(
+                ProductFilter::default(),
+                CategoryQuery {
+                    within: Some(vec!["en:crisps".to_string(), "en:spreads".to_string()]),
+                    limit: 1,
+                    ..query
+                }
+            )
+            .await,
+            pairs(&[("en:crisps", 1)])
+        );
+    }
+
+    #[tokio::test]
+    async fn memory_vocabulary_counts_name_and_brand_words() {
+        let products = MemoryProducts::default();
+        for (code, name, brand) in [
+            ("101", "Nutella Hazelnut Spread", "ferrero"),
+            ("102", "Nutella B-ready", "ferrero"),
+            ("103", "Ritter Sport Alpenmilch", "ritter-sport"),
+        ] {
+            products
+                .insert(product(code, name).with_brands([brand]).build())
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            products.vocabulary(100).await.unwrap(),
+            [
+                "ferrero",
+                "nutella",
+                "ritter",
+                "sport",
+                "alpenmilch",
+                "hazelnut",
+                "ready",
+                "spread",
+            ]
+        );
+        assert_eq!(
+            products.vocabulary(2).await.unwrap(),
+            ["ferrero", "nutella"]
+        );
+    }
+
+    #[tokio::test]
+    async fn memory_update_and_delete_by_id() {
+        let products = MemoryProducts::default();
+        let id = products
+            .insert(product("123", "Crisps").build())
+            .await
+            .unwrap()
+            .id
+            .unwrap();
+        let changes = ProductChanges {
+            ingredients_text: Some("potatoes, salt".to_string()),
+            ..Default::default()
+        };
+        let updated = products.update(id, &changes).await.unwrap().unwrap();
+        assert_eq!(updated.ingredients_text.as_deref(), Some("potatoes, salt"));
+        assert_eq!(updated.product_name.as_deref(), Some("Crisps"));
+        assert!(
+            products
+                .update(ObjectId::new(), &changes)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        assert!(products.delete(id).await.unwrap());
+        assert!(!products.delete(id).await.unwrap());
+        assert!(products.find_by_id(id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn memory_changes_list_products_then_tombstones_in_time_and_id_order() {
+        let products = MemoryProducts::default();
+        let since = DateTime::parse_from_rfc3339("2024-06-01T08:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let at = |minutes| since + chrono::Duration::minutes(minutes);
+        for (code, minutes, id) in [
+            ("a", 0, 1),
+            ("b", 1, 2),
+            ("c", 2, 4),
+            ("d", 2, 3),
+            ("e", 3, 5),
+        ] {
+            let mut stored: Product = product(code, "Crisps").build();
+            let mut bytes = [0; 12];
+            bytes[11] = id;
+            stored.id = Some(ObjectId::from_bytes(bytes));
+            stored.last_modified_at = at(minutes);
+            products.seed(stored);
+        }
+        let deleted = products.find_by_code("e").await.unwrap().unwrap();
+        assert!(products.delete(deleted.id.unwrap()).await.unwrap());
+
+        let codes = |entries: &[ChangeEntry]| -> Vec<String> {
+            entries
+                .iter()
+                .map(|entry| match entry {
+                    ChangeEntry::Modified(product) => product.code.clone(),
+                    ChangeEntry::Deleted(tombstone) => format!("-{}", tombstone.code),
+                })
+                .collect()
+        };
+        let from = ChangesFrom {
+            at: since,
+            after_id: None,
+        };
+        let all = products.changes(from, 10).await.unwrap();
+        assert_eq!(codes(&all), ["b", "d", "c", "-e"]);
+
+        let first = products.changes(from, 2).await.unwrap();
+        assert_eq!(codes(&first), ["b", "d"]);
+        let (at, after_id) = first[1].position();
+        let rest = products
+            .changes(ChangesFrom { at, after_id }, 2)
+            .await
+            .unwrap();
+        assert_eq!(codes(&rest), ["c", "-e"]);
+    }
+
+    #[tokio::test]
+    async fn memory_recent_products_are_newest_first_by_either_time() {
+        let products = MemoryProducts::default();
+        let start = DateTime::parse_from_rfc3339("2024-06-01T08:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let at = |minutes| start + chrono::Duration::minutes(minutes);
+        for (code, created, modified, id, country) in [
+            ("a", 0, 5, 1, "en:germany"),
+            ("b", 1, 1, 2, "en:germany"),
+            ("c", 2, 3, 4, "en:france"),
+            ("d", 2, 2, 3, "en:germany"),
+        ] {
+            let mut stored: Product = product(code, "Crisps").with_countries([country]).build();
+            let mut bytes = [0; 12];
+            bytes[11] = id;
+            stored.id = Some(ObjectId::from_bytes(bytes));
+            stored.created_at = at(created);
+            stored.last_modified_at = at(modified);
+            products.seed(stored);
+        }
+        let codes = |recent: &[RecentProduct]| -> Vec<String> {
+            recent
+                .iter()
+                .map(|product| product.summary.code.clone())
+                .collect()
+        };
+        let all = ProductFilter::default();
+
+        let created = products
+            .recent(RecentKind::Created, &all, None, 10)
+            .await
+            .unwrap();
+        assert_eq!(codes(&created), ["c", "d", "b", "a"]);
+        assert_eq!(created[0].at, at(2));
+        let updated = products
+            .recent(RecentKind::Updated, &all, None, 10)
+            .await
+            .unwrap();
+        assert_eq!(codes(&updated), ["a", "c", "d", "b"]);
+
+        let first = products
+            .recent(RecentKind::Created, &all, None, 1)
+            .await
+            .unwrap();
+        let from = RecentFrom {
+            at: first[0].at,
+            before_id: first[0].summary.id.unwrap(),
+        };
+        let rest = products
+            .recent(RecentKind::Created, &all, Some(from), 10)
+            .await
+            .unwrap();
+        assert_eq!(codes(&rest), ["d", "b", "a"]);
+
+        let germany = ProductFilter {
+            countries: vec!["en:germany".to_string()],
+            ..Default::default()
+        };
+        let created = products
+            .recent(RecentKind::Created, &germany, None, 2)
+            .await
+            .unwrap();
+        assert_eq!(codes(&created), ["d", "b"]);
+    }
+
+    #[test]
+    fn mongo_recent_resumes_before_the_last_time_and_id() {
+        let at = DateTime::parse_from_rfc3339("2024-06-01T08:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let before_id = ObjectId::new();
+        assert_eq!(
+            before_document("created_datetime", RecentFrom { at, before_id }),
+            doc! {
+                "$or": [
+                    { "created_datetime": { "$lt": at } },
+                    { "created_datetime": at, "_id": { "$lt": before_id } },
+                ]
+            }
+        );
+    }
+
+    #[test]
+    fn mongo_changes_resume_after_the_last_time_and_id() {
+        let at = DateTime::parse_from_rfc3339("2024-06-01T08:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            changed_from_document("deleted_datetime", ChangesFrom { at, after_id: None }),
+            doc! { "deleted_datetime": { "$gt": at } }
+        );
+        let after_id = ObjectId::new();
+        assert_eq!(
+            changed_from_document(
+                "last_modified_datetime",
+                ChangesFrom {
+                    at,
+                    after_id: Some(after_id)
+                }
+            ),
+            doc! {
+                "$or": [
+                    { "last_modified_datetime": { "$gt": at } },
+                    { "last_modified_datetime": at, "_id": { "$gt": after_id } },
+                ]
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn memory_update_unsets_fields() {
+        let products = MemoryProducts::default();
+        let mut stored: Product = product("123", "Crisps")
+            .with_labels(["en:vegan"])
+            .with_allergens(["en:milk"])
+            .build();
+        stored.image_url = Some("https://images.example/crisps.jpg".to_string());
+        stored.quantity = Some("150 g".to_string());
+        let id = products.insert(stored).await.unwrap().id.unwrap();
+
+        let changes = ProductChanges {
+            labels: Some(vec!["en:organic".to_string()]),
+            unset: vec![ProductField::ImageUrl, ProductField::Quantity],
+            ..Default::default()
+        };
+        assert!(!changes.is_empty());
+        let updated = products.update(id, &changes).await.unwrap().unwrap();
+        assert_eq!(updated.image_url, None);
+        assert_eq!(updated.quantity, None);
+        assert_eq!(updated.labels, Some(vec!["en:organic".to_string()]));
+        assert_eq!(updated.allergens_tags, ["en:milk"]);
+        assert_eq!(updated.product_name.as_deref(), Some("Crisps"));
+    }
+
+    #[tokio::test]
+    async fn memory_upsert_by_code_keeps_ids_of_replaced_products() {
+        let products = MemoryProducts::default();
+        let id = products
+            .insert(product("123", "Crisps").build())
+            .await
+            .unwrap()
+            .id
+            .unwrap();
+
+        let counts = products
+            .upsert_by_code(vec![
+                product("123", "Salted crisps").build(),
+                product("456", "Pretzels").build(),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(
+            counts,
+            UpsertCounts {
+                inserted: 1,
+                updated: 1
+            }
+        );
+        let replaced = products.find_by_id(id).await.unwrap().unwrap();
+        assert_eq!(replaced.product_name.as_deref(), Some("Salted crisps"));
+        let inserted = products.find_by_code("456").await.unwrap().unwrap();
+        assert!(inserted.id.is_some_and(|new_id| new_id != id));
+    }
+
+    #[tokio::test]
+    async fn memory_scans_add_up_without_touching_the_product_otherwise() {
+        let products = MemoryProducts::default();
+        let stored = products
+            .insert(product("123", "Crisps").build())
+            .await
+            .unwrap();
+        assert_eq!(stored.scan_count, None);
+
+        let scans = |n: u64| vec![("123".to_string(), n), ("999".to_string(), 1)];
+        products.add_scans(&scans(3)).await.unwrap();
+        products.add_scans(&scans(2)).await.unwrap();
+        let counted = products.find_by_code("123").await.unwrap().unwrap();
+        assert_eq!(counted.scan_count, Some(5));
+        assert_eq!(counted.last_modified_at, stored.last_modified_at);
+        assert!(products.find_by_code("999").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn memory_least_complete_lists_the_lowest_scores_first() {
+        let products = MemoryProducts::default();
+        // Name, category and country: 40.
+        products
+            .insert(
+                product("1", "Crisps")
+                    .with_countries(["en:germany"])
+                    .build(),
+            )
+            .await
+            .unwrap();
+        // And allergens: 50.
+        products
+            .insert(
+                product("2", "Milk chocolate")
+                    .with_allergens(["en:milk"])
+                    .with_countries(["en:germany"])
+                    .build(),
+            )
+            .await
+            .unwrap();
+        // Country only: 10.
+        products
+            .insert(
+                ProductFixture::new("3")
+                    .unnamed()
+                    .with_countries(["en:france"])
+                    .build(),
+            )
+            .await
+            .unwrap();
+
+        let found = |filter: ProductFilter, max_score: u8, skip: u64| {
+            let products = &products;
+            async move {
+                products
+                    .least_complete(&filter, max_score, skip, 10)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|found| (found.product.code, found.completeness))
+                    .collect::<Vec<_>>()
+            }
+        };
+        let everywhere = ProductFilter::default();
+        assert_eq!(
+            found(everywhere.clone(), 100, 0).await,
+            [
+                ("3".to_string(), 10),
+                ("1".to_string(), 40),
+                ("2".to_string(), 50)
+            ]
+        );
+        assert_eq!(found(everywhere, 40, 1).await, [("1".to_string(), 40)]);
+        let germany = ProductFilter {
+            countries: vec!["en:germany".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            found(germany, 100, 0).await,
+            [("1".to_string(), 40), ("2".to_string(), 50)]
+        );
+    }
+
+    #[tokio::test]
+    async fn memory_search_filters_and_pages() {
+        let products = MemoryProducts::default();
+        let chocolate = product("1", "Milk Chocolate").with_allergens(["en:milk"]);
+        products.insert(chocolate.build()).await.unwrap();
+        let vegan = product("2", "Dark Chocolate").with_labels(["en:vegan"]);
+        products.insert(vegan.build()).await.unwrap();
+        products
+            .insert(product("3", "Crisps").build())
+            .await
+            .unwrap();
+
+        let codes = |found: Vec<SearchHit>| {
+            found
+                .into_iter()
+                .map(|h| h.product.code)
+                .collect::<Vec<_>>()
+        };
+
+        let text = ProductFilter {
+            text: Some("chocolate".to_string()),
+            ..Default::default()
+        };
+        let all = products
+            .search(&text, SearchFrom::Offset(0), 10)
+            .await
+            .unwrap();
+        let first_id = all[0].product.id.unwrap();
+        assert_eq!(codes(all), ["1", "2"]);
+        assert_eq!(
+            codes(
+                products
+                    .search(&text, SearchFrom::Offset(1), 10)
+                    .await
+                    .unwrap()
+            ),
+            ["2"]
+        );
+        assert_eq!(
+            codes(
+                products
+                    .search(&text, SearchFrom::After(first_id), 10)
+                    .await
+                    .unwrap()
+            ),
+            ["2"]
+        );
+        assert_eq!(products.count(&text).await.unwrap(), 2);
+
+        let no_milk = ProductFilter {
+            excluded_allergens: vec!["en:milk".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            codes(
+                products
+                    .search(&no_milk, SearchFrom::Offset(0), 10)
+                    .await
+                    .unwrap()
+            ),
+            ["2", "3"]
+        );
+
+        let vegan_label = ProductFilter {
+            labels: vec!["en:vegan".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            codes(
+                products
+                    .search(&vegan_label, SearchFrom::Offset(0), 10)
+                    .await
+                    .unwrap()
+            ),
+            ["2"]
+        );
+        assert_eq!(products.count(&vegan_label).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn memory_search_matches_any_or_all_tags() {
+        let products = MemoryProducts::default();
+        for (code, brand, categories) in [
+            ("1", "milka", vec!["en:snacks", "en:chocolates"]),
+            ("2", "lindt", vec!["en:chocolates"]),
+            ("3", "haribo", vec!["en:snacks"]),
+        ] {
+            let fixture = ProductFixture::new(code)
+                .with_brands([brand])
+                .with_categories(categories);
+            products.insert(fixture.build()).await.unwrap();
+        }
+        let products = &products;
+        let codes = |filter: ProductFilter| async move {
+            let found = products
+                .search(&filter, SearchFrom::Offset(0), 10)
+                .await
+                .unwrap();
+            found
+                .into_iter()
+                .map(|h| h.product.code)
+                .collect::<Vec<_>>()
+        };
+
+        let two_brands = ProductFilter {
+            brands: vec!["milka".to_string(), "haribo".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(codes(two_brands).await, ["1", "3"]);
+        let categories = vec!["en:snacks".to_string(), "en:chocolates".to_string()];
+        let any_category = ProductFilter {
+            categories: categories.clone(),
+            ..Default::default()
+        };
+        assert_eq!(codes(any_category).await, ["1", "2", "3"]);
+        let all_categories = ProductFilter {
+            categories,
+            category_match: TagMatch::All,
+            ..Default::default()
+        };
+        assert_eq!(codes(all_categories).await, ["1"]);
+    }
+
+    #[tokio::test]
+    async fn memory_search_ranks_by_relevance_when_asked() {
+        let products = MemoryProducts::default();
+        for (code, name) in [
+            ("1", "Hazelnut spread"),
+            ("2", "Nutella hazelnut spread"),
+            ("3", "Nutella biscuits"),
+        ] {
+            products.insert(product(code, name).build()).await.unwrap();
+        }
+        let search = |sort: SearchSort, from: SearchFrom| {
+            let products = &products;
+            async move {
+                let filter = ProductFilter {
+                    text: Some("nutella hazelnut".to_string()),
+                    sort,
+                    ..Default::default()
+                };
+                products.search(&filter, from, 10).await.unwrap()
+            }
+        };
+
+        let ranked = search(SearchSort::Relevance, SearchFrom::Offset(0)).await;
+        let ranked: Vec<_> = ranked
+            .iter()
+            .map(|h| (h.product.code.as_str(), h.score))
+            .collect();
+        assert_eq!(
+            ranked,
+            [("2", Some(2.0)), ("1", Some(1.0)), ("3", Some(1.0))]
+        );
+        let second_page = search(SearchSort::Relevance, SearchFrom::Offset(1)).await;
+        assert_eq!(second_page[0].product.code, "1");
+
+        let by_id = search(SearchSort::Id, SearchFrom::Offset(0)).await;
+        let codes: Vec<_> = by_id.iter().map(|h| h.product.code.as_str()).collect();
+        assert_eq!(codes, ["1", "2", "3"]);
+        assert_eq!(by_id[1].score, Some(2.0));
+    }
+
+    #[test]
+    fn mongo_filter_uses_in_and_all_for_tag_lists() {
+        let filter = ProductFilter {
+            categories: vec!["en:snacks".to_string()],
+            brands: vec!["milka".to_string(), "lindt".to_string()],
+            countries: vec!["en:germany".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            search_document(&filter),
+            doc! {
+                "categories_tags": { "$in": ["en:snacks"] },
+                "brands_tags": { "$in": ["milka", "lindt"] },
+                "countries_tags": { "$in": ["en:germany"] },
+            }
+        );
+
+        let all = ProductFilter {
+            categories: vec!["en:snacks".to_string(), "en:chocolates".to_string()],
+            category_match: TagMatch::All,
+            ..Default::default()
+        };
+        assert_eq!(
+            search_document(&all),
+            doc! { "categories_tags": { "$all": ["en:snacks", "en:chocolates"] } }
+        );
+    }
+
+    #[tokio::test]
+    async fn memory_search_bounds_nutriments() {
+        let products = MemoryProducts::default();
+        for (code, sugars, proteins) in [
+            ("1", Some(56.3), Some(6.3)),
+            ("2", Some(4.0), Some(12.0)),
+            ("3", None, None),
+        ] {
+            let mut product: Product = ProductFixture::new(code).build();
+            product.nutriments = Some(Nutriments {
+                sugars_100g: sugars,
+                proteins_100g: proteins,
+                ..Default::default()
+            });
+            products.insert(product).await.unwrap();
+        }
+        let codes = |filter: ProductFilter| {
+            let products = &products;
+            async move {
+                let found = products
+                    .search(&filter, SearchFrom::Offset(0), 10)
+                    .await
+                    .unwrap();
+                found
+                    .into_iter()
+                    .map(|h| h.product.code)
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        let low_sugar = ProductFilter {
+            max_sugar: Some(5.0),
+            ..Default::default()
+        };
+        assert_eq!(codes(low_sugar).await, ["2"]);
+        let high_protein = ProductFilter {
+            min_protein: Some(6.3),
+            ..Default::default()
+        };
+        assert_eq!(codes(high_protein).await, ["1", "2"]);
+    }
+
+    #[test]
+    fn mongo_filter_bounds_nutriments() {
+        let filter = ProductFilter {
+            max_sugar: Some(5.0),
+            max_salt: Some(0.3),
+            min_protein: Some(10.0),
+            ..Default::default()
+        };
+        assert_eq!(
+            search_document(&filter),
+            doc! {
+                "nutriments.sugars_100g": { "$lte": 5.0 },
+                "nutriments.salt_100g": { "$lte": 0.3 },
+                "nutriments.proteins_100g": { "$gte": 10.0 },
+            }
+        );
+    }
+
+    #[test]
+    fn ecoscore_filter_adds_to_the_nutriscore_filter() {
+        let filter = ProductFilter {
+            nutriscore: Some("a".to_string()),
+            ecoscore: Some("b".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            search_document(&filter),
+            doc! { "nutrition_grade_fr": "a", "ecoscore_grade": "b" }
+        );
+
+        let mut product = ProductFixture::new("4000417025005")
+            .with_nutriscore("a")
+            .build();
+        assert!(!matches_filter(&product, &filter), "no Eco-Score");
+        product.ecoscore_grade = Some("b".to_string());
+        assert!(matches_filter(&product, &filter));
+        product.nutrition_grade_fr = Some("c".to_string());
+        assert!(!matches_filter(&product, &filter));
+    }
+
+    #[test]
+    fn mongo_filter_keeps_the_label_and_the_diet_exclusion() {
+        let filter = ProductFilter {
+            labels: vec!["en:organic".to_string()],
+            excluded_labels: vec!["en:non-vegan".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            search_document(&filter),
+            doc! { "labels_tags": { "$in": ["en:organic"], "$nin": ["en:non-vegan"] } }
+        );
+    }
+
+    #[test]
+    fn mongo_strict_filter_needs_ingredients_and_allergen_tags() {
+        let strict = ProductFilter {
+            excluded_allergens: vec!["en:milk".to_string()],
+            allergen_mode: AllergenMode::Strict,
+            ..Default::default()
+        };
+        assert_eq!(
+            search_document(&strict),
+            doc! {
+                "allergens_tags": { "$nin": ["en:milk"], "$exists": true, "$ne": null },
+                "ingredients_text": { "$exists": true, "$nin": [null, ""] },
+            }
+        );
+        let lenient = ProductFilter {
+            allergen_mode: AllergenMode::Lenient,
+            ..strict
+        };
+        assert_eq!(
+            search_document(&lenient),
+            doc! { "allergens_tags": { "$nin": ["en:milk"] } }
+        );
+    }
+
+    #[tokio::test]
+    async fn memory_strict_search_leaves_out_products_without_ingredients() {
+        let products = MemoryProducts::default();
+        for (code, ingredients) in [("1", Some("Oats")), ("2", Some("")), ("3", None)] {
+            let mut product: Product = ProductFixture::new(code).build();
+            product.ingredients_text = ingredients.map(str::to_string);
+            products.insert(product).await.unwrap();
+        }
+        for (allergen_mode, expected) in [
+            (AllergenMode::Lenient, vec!["1", "2", "3"]),
+            (AllergenMode::Strict, vec!["1"]),
+        ] {
+            let filter = ProductFilter {
+                allergen_mode,
+                ..Default::default()
+            };
+            let found = products
+                .search(&filter, SearchFrom::Offset(0), 10)
+                .await
+                .unwrap();
+            let codes: Vec<String> = found.into_iter().map(|h| h.product.code).collect();
+            assert_eq!(codes, expected, "{:?}", allergen_mode);
+        }
+    }
+
+    #[test]
+    fn mongo_filter_names_ingredients_in_every_ingredient_field() {
+        let filter = ProductFilter {
+            included_ingredients: vec!["oats".to_string()],
+            excluded_ingredients: vec!["palm oil".to_string(), "a.b".to_string()],
+            excluded_traces: vec!["en:palm-oil".to_string()],
+            ..Default::default()
+        };
+        let names = |pattern: &str| {
+            doc! {
+                "$or": [
+                    { "ingredients_text": { "$regex": pattern, "$options": "i" } },
+                    { "ingredients.text": { "$regex": pattern, "$options": "i" } },
+                    { "ingredients.id": { "$regex": pattern, "$options": "i" } },
+                ]
+            }
+        };
+        assert_eq!(
+            search_document(&filter),
+            doc! {
+                "$and": [names(r"(?:^|[^\p{L}\p{N}])[oòóôõöø][aàáâãäå]ts(?:[^\p{L}\p{N}]|$)")],
+                "$nor": [
+                    names(&term_pattern("palm oil")),
+                    names(r"(?:^|[^\p{L}\p{N}])[aàáâãäå]\.b(?:[^\p{L}\p{N}]|$)"),
+                ],
+                "traces_tags": { "$nin": ["en:palm-oil"] },
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn memory_search_names_ingredients_and_traces() {
+        let products = MemoryProducts::default();
+        for (code, ingredients, traces) in [
+            ("1", "Haferflocken, Rohrzucker", None),
+            ("2", "Oats, sugar, palm oil", None),
+            ("3", "OATS (100 %)", Some("en:palm-oil")),
+            ("4", "Crème, Sucre", None),
+        ] {
+            let mut product: Product = ProductFixture::new(code)
+                .with_ingredients(ingredients)
+                .build();
+            product.traces_tags = traces.map(|trace| vec![trace.to_string()]);
+            products.insert(product).await.unwrap();
+        }
+        let codes = |filter: ProductFilter| {
+            let products = &products;
+            async move {
+                let found = products
+                    .search(&filter, SearchFrom::Offset(0), 10)
+                    .await
+                    .unwrap();
+                found
+                    .into_iter()
+                    .map(|h| h.product.code)
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        let oats = |excluded: &[&str], excluded_traces: &[&str]| ProductFilter {
+            included_ingredients: vec!["oats".to_string()],
+            excluded_ingredients: excluded.iter().map(|e| e.to_string()).collect(),
+            excluded_traces: excluded_traces.iter().map(|e| e.to_string()).collect(),
+            ..Default::default()
+        };
+        assert_eq!(codes(oats(&[], &[])).await, ["2", "3"]);
+        assert_eq!(codes(oats(&["Palm Oil"], &[])).await, ["3"]);
+        assert!(
+            codes(oats(&["palm oil"], &["en:palm-oil"]))
+                .await
+                .is_empty()
+        );
+        let creme = ProductFilter {
+            included_ingredients: vec!["creme".to_string(), "SUCRE".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(codes(creme).await, ["4"]);
+    }
+}
@@ -0,0 +1,261 @@
+//! Search result pages cached in Redis, so popular searches ("milk", "nutella", category
+//! pages) don't reach MongoDB on every request.
+//!
+//! A page of hits is cached under `search:{hash}`, the SHA-256 of a canonical form of
+//! everything that picks them: the [`ProductFilter`] as serialized, with its lists
+//! sorted, so a field added to it is part of the key and users never see each other's
+//! filtered results; where the page starts, its size and the view. Writes never
+//! invalidate these keys: a cached page may miss a product created, show one deleted
+//! or show one as it was, for up to [`SEARCH_CACHE_TTL_SECS`]. Totals are counted
+//! apart and are not cached here. [`SEARCH_CACHE_ENABLED`] turns caching off, for every
+//! search from the next one.
+
+use crate::{
+    catalog_metrics::{CacheOutcome, record_cache_lookup},
+    errors::Result,
+    handlers::connect_cache,
+    models::SearchView,
+    repository::{ProductFilter, SearchFrom},
+    state::AppState,
+    tunables::{SEARCH_CACHE_ENABLED, SEARCH_CACHE_TTL_SECS, cache_ttl},
+};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::future::Future;
+use tracing::{debug, error, warn};
+
+/// Where the page of `filter`'s matches starting at `from`, `limit` long, is cached in
+/// `view`. Filters that keep the same products in the same order share a key.
+pub fn search_cache_key(
+    filter: &ProductFilter,
+    from: SearchFrom,
+    limit: u64,
+    view: SearchView,
+) -> String {
+    let mut fields = serde_json::to_value(filter).expect("a filter serializes");
+    // Every list is a set of tags or terms: its order and repeats pick nothing.
+    if let Some(fields) = fields.as_object_mut() {
+        for value in fields.values_mut() {
+            if let Value::Array(values) = value {
+                values.sort_by(|a, b| a.as_str().cmp(&b.as_str()));
+                values.dedup();
+            }
+        }
+    }
+    let canonical = json!({
+        "filter": fields,
+        "from": match from {
+            SearchFrom::Offset(skip) => format!("offset:{}", skip),
+            SearchFrom::After(last_id) => format!("after:{}", last_id),
+        },
+        "limit": limit,
+        "view": format!("{:?}", view),
+    });
+    // serde_json objects keep their keys sorted, so equal filters print the same.
+    let digest = Sha256::digest(canonical.to_string().as_bytes());
+    let hash: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("search:{}", hash)
+}
+
+/// The hits `search` finds for `key`, from the cache when it has them. A cache that is
+/// off, down or holds something unreadable leaves the search to `search`.
+pub async fn cached_hits<H, F>(state: &AppState, key: &str, search: F) -> Result<Vec<H>>
+where
+    H: Serialize + DeserializeOwned,
+    F: Future<Output = Result<Vec<H>>>,
+{
+    if !state.config.get(SEARCH_CACHE_ENABLED) {
+        return search.await;
+    }
+    let mut cache_conn = connect_cache(state, "search").await;
+    if let Some(conn) = cache_conn.as_mut() {
+        match conn.get(key).await {
+            Ok(Some(cached)) => match serde_json::from_str(&cached) {
+                Ok(hits) => {
+                    debug!(key = %key, "Cache hit for search");
+                    record_cache_lookup("search", CacheOutcome::Hit);
+                    return Ok(hits);
+                }
+                Err(e) => {
+                    error!(key = %key, "Failed to deserialize cached search: {}", e);
+                    record_cache_lookup("search", CacheOutcome::Error);
+                }
+            },
+            Ok(None) => record_cache_lookup("search", CacheOutcome::Miss),
+            Err(e) => {
+                warn!(key = %key, "Redis GET command failed (search): {}", e);
+                record_cache_lookup("search", CacheOutcome::Error);
+            }
+        }
+    }
+
+    let hits = search.await?;
+    if let Some(conn) = cache_conn.as_mut() {
+        match serde_json::to_string(&hits) {
+            Ok(json) => {
+                let ttl = cache_ttl(&state.config, SEARCH_CACHE_TTL_SECS);
+                if let Err(e) = conn.set_ex(key, &json, ttl).await {
+                    warn!(key = %key, "Failed to cache search in Redis: {}", e);
+                }
+            }
+            Err(e) => error!(key = %key, "Failed to serialize search for caching: {}", e),
+        }
+    }
+    Ok(hits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AllergenMode, SearchHit, SearchSort, TagMatch};
+    use bson::oid::ObjectId;
+    use yoloeats_domain::fixtures::ProductFixture;
+
+    fn key(filter: &ProductFilter) -> String {
+        search_cache_key(filter, SearchFrom::Offset(0), 20, SearchView::Full)
+    }
+
+    #[test]
+    fn equal_searches_share_a_key() {
+        let filter = ProductFilter {
+            categories: vec!["en:spreads".to_string(), "en:breakfasts".to_string()],
+            excluded_allergens: vec!["en:milk".to_string(), "en:nuts".to_string()],
+            ..Default::default()
+        };
+        let reordered = ProductFilter {
+            categories: vec![
+                "en:breakfasts".to_string(),
+                "en:spreads".to_string(),
+                "en:spreads".to_string(),
+            ],
+            excluded_allergens: vec!["en:nuts".to_string(), "en:milk".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(key(&filter), key(&reordered));
+        assert!(key(&filter).starts_with("search:"));
+        assert_eq!(key(&filter).len(), "search:".len() + 64);
+    }
+
+    #[test]
+    fn anything_that_changes_the_page_changes_the_key() {
+        let base = ProductFilter {
+            text: Some("nutella".to_string()),
+            sort: SearchSort::Relevance,
+            ..Default::default()
+        };
+        let keys = [
+            key(&base),
+            key(&ProductFilter {
+                excluded_allergens: vec!["en:peanuts".to_string()],
+                ..base.clone()
+            }),
+            key(&ProductFilter {
+                excluded_labels: vec!["en:non-vegan".to_string()],
+                ..base.clone()
+            }),
+            key(&ProductFilter {
+                categories: vec!["en:spreads".to_string()],
+                category_match: TagMatch::All,
+                ..base.clone()
+            }),
+            key(&ProductFilter {
+                sort: SearchSort::Id,
+                ..base.clone()
+            }),
+            key(&ProductFilter {
+                max_sugar: Some(5.0),
+                ..base.clone()
+            }),
+            key(&ProductFilter {
+                ecoscore: Some("a".to_string()),
+                ..base.clone()
+            }),
+            key(&ProductFilter {
+                text: Some("nutella biscuits".to_string()),
+                ..base.clone()
+            }),
+            key(&ProductFilter {
+                allergen_mode: AllergenMode::Strict,
+                ..base.clone()
+            }),
+            key(&ProductFilter {
+                countries: vec!["en:france".to_string()],
+                ..base.clone()
+            }),
+            key(&ProductFilter {
+                excluded_traces: vec!["en:peanuts".to_string()],
+                ..base.clone()
+            }),
+            key(&ProductFilter {
+                included_ingredients: vec!["hazelnut".to_string()],
+                ..base.clone()
+            }),
+            key(&ProductFilter {
+                excluded_ingredients: vec!["hazelnut".to_string()],
+                ..base.clone()
+            }),
+            search_cache_key(&base, SearchFrom::Offset(20), 20, SearchView::Full),
+            search_cache_key(
+                &base,
+                SearchFrom::After(ObjectId::new()),
+                20,
+                SearchView::Full,
+            ),
+            search_cache_key(&base, SearchFrom::Offset(0), 50, SearchView::Full),
+            search_cache_key(&base, SearchFrom::Offset(0), 20, SearchView::Summary),
+        ];
+        for (i, a) in keys.iter().enumerate() {
+            for b in &keys[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn every_filter_field_is_part_of_the_key() {
+        let fields = serde_json::to_value(ProductFilter::default()).unwrap();
+        let fields = fields.as_object().unwrap();
+        // Fails once a field is skipped when serialized, and so left out of the key.
+        let ProductFilter {
+            text: _,
+            categories: _,
+            category_match: _,
+            brands: _,
+            labels: _,
+            countries: _,
+            nutriscore: _,
+            ecoscore: _,
+            max_sugar: _,
+            max_salt: _,
+            max_fat: _,
+            min_protein: _,
+            excluded_allergens: _,
+            allergen_mode: _,
+            excluded_labels: _,
+            included_ingredients: _,
+            excluded_ingredients: _,
+            excluded_traces: _,
+            sort: _,
+        } = ProductFilter::default();
+        assert_eq!(fields.len(), 19, "{:?}", fields.keys());
+    }
+
+    #[test]
+    fn cached_hits_read_back_as_they_were() {
+        let hits = vec![SearchHit {
+            product: ProductFixture::new("4000417025005")
+                .named("Alpine milk chocolate")
+                .with_allergens(["en:milk"])
+                .build(),
+            score: Some(1.5),
+            display_name: None,
+            highlights: None,
+        }];
+        let json = serde_json::to_string(&hits).unwrap();
+        let cached: Vec<SearchHit> = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&cached).unwrap(), json);
+        assert_eq!(cached[0].product.id, hits[0].product.id);
+        assert_eq!(cached[0].product.created_at, hits[0].product.created_at);
+    }
+}
@@ -0,0 +1,331 @@
+//! `/api/v1/products/search/semantic`: products whose vectors in the `product_vectors`
+//! index are nearest to a query, the index the recommendations search.
+//!
+//! Text queries are embedded by the [`EmbeddingClient`](crate::embedding::EmbeddingClient)
+//! of the state; without one only a POSTed `vector` works. The
+//! `allergens` and `diets` exclusions are those of product search, applied as `must_not`
+//! conditions on the points' `allergens_tags` and `labels_tags`. Points indexed before
+//! `allergens_tags` was in the payload lack it, so the hydrated products are checked again.
+
+use crate::{
+    catalog_metrics::observe_qdrant,
+    embedding::EMBEDDING_SERVICE_URL_ENV,
+    errors::{Result, ServiceError},
+    handlers::{QDRANT_COLLECTION_NAME, barcodes_in_score_order},
+    models::{Product, SemanticSearchParams, SemanticSearchPayload},
+    state::AppState,
+    taxonomy::{allergen_tags, diet_exclusion_tags},
+};
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use qdrant_client::qdrant::{
+    Condition, FieldCondition, Filter, Match, RepeatedStrings, SearchPointsBuilder,
+    condition::ConditionOneOf, r#match::MatchValue,
+};
+use std::{collections::HashMap, sync::Arc};
+use tracing::{debug, info, instrument, warn};
+use yoloeats_domain::ErrorBody;
+use yoloeats_metrics::ValidJson;
+
+const DEFAULT_LIMIT: u64 = 10;
+const MAX_LIMIT: u64 = 50;
+
+/// What to search with.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SemanticQuery {
+    Text(String),
+    Vector(Vec<f32>),
+}
+
+/// Tags a result must not carry.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Exclusions {
+    pub allergens: Vec<String>,
+    pub labels: Vec<String>,
+}
+
+impl Exclusions {
+    /// The allergens as their tags, the diets as the labels that conflict with them.
+    pub fn new(allergens: Option<Vec<String>>, diets: Option<Vec<String>>) -> Self {
+        Exclusions {
+            allergens: allergen_tags(allergens.unwrap_or_default()),
+            labels: diet_exclusion_tags(diets.unwrap_or_default()),
+        }
+    }
+
+    /// As [`Exclusions::new`], from the comma-separated lists of a query string.
+    pub fn from_query(allergens: Option<&str>, diets: Option<&str>) -> Self {
+        let list = |value: Option<&str>| {
+            value.map(|value| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|v| !v.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+        };
+        Exclusions::new(list(allergens), list(diets))
+    }
+
+    fn filter(&self) -> Filter {
+        let mut must_not = Vec::new();
+        for (key, tags) in [
+            ("allergens_tags", &self.allergens),
+            ("labels_tags", &self.labels),
+        ] {
+            if !tags.is_empty() {
+                must_not.push(Condition {
+                    condition_one_of: Some(ConditionOneOf::Field(FieldCondition {
+                        key: key.to_string(),
+                        r#match: Some(Match {
+                            match_value: Some(MatchValue::Keywords(RepeatedStrings {
+                                strings: tags.clone(),
+                            })),
+                        }),
+                        ..Default::default()
+                    })),
+                });
+            }
+        }
+        Filter {
+            must_not,
+            ..Default::default()
+        }
+    }
+
+    fn allows(&self, product: &Product) -> bool {
+        let carries =
+            |tags: &[String], excluded: &[String]| tags.iter().any(|t| excluded.contains(t));
+        !carries(&product.allergens_tags, &self.allergens)
+            && !carries(product.labels.as_deref().unwrap_or_default(), &self.labels)
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/products/search/semantic",
+    tag = "v1",
+    params(
+        SemanticSearchParams,
+    ),
+    responses(
+        (status = 200, description = "The nearest products, best match first.", body = Vec<Product>),
+        (status = 400, description = "No `q`.", body = ErrorBody),
+        (status = 429, description = "Over the client's rate limit; retry after `Retry-After`.", body = ErrorBody),
+        (status = 500, description = "Qdrant or MongoDB failed.", body = ErrorBody),
+        (status = 503, description = "No embedding service to embed `q` with, or it failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, params), fields(q = ?params.q))]
+pub async fn semantic_search_products(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SemanticSearchParams>,
+) -> Result<Json<Vec<Product>>> {
+    let (query, exclusions, limit) = query_from_params(params)?;
+    semantic_search(&state, query, &exclusions, limit)
+        .await
+        .map(Json)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/products/search/semantic",
+    tag = "v1",
+    request_body = SemanticSearchPayload,
+    responses(
+        (status = 200, description = "The nearest products, best match first.", body = Vec<Product>),
+        (status = 400, description = "Not exactly one of `q` and `vector`, or a body that is not JSON.", body = ErrorBody),
+        (status = 422, description = "A field of the wrong type or failing its rule.", body = ErrorBody),
+        (status = 429, description = "Over the client's rate limit; retry after `Retry-After`.", body = ErrorBody),
+        (status = 500, description = "Qdrant or MongoDB failed.", body = ErrorBody),
+        (status = 503, description = "No embedding service to embed `q` with, or it failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, payload), fields(q = ?payload.q))]
+pub async fn semantic_search_by_body(
+    State(state): State<Arc<AppState>>,
+    ValidJson(payload): ValidJson<SemanticSearchPayload>,
+) -> Result<Json<Vec<Product>>> {
+    let (query, exclusions, limit) = query_from_payload(payload)?;
+    semantic_search(&state, query, &exclusions, limit)
+        .await
+        .map(Json)
+}
+
+pub(crate) fn query_from_params(
+    params: SemanticSearchParams,
+) -> Result<(SemanticQuery, Exclusions, Option<u64>)> {
+    let text = non_blank(params.q)
+        .ok_or_else(|| ServiceError::BadRequest("Query parameter 'q' is required".to_string()))?;
+    let exclusions = Exclusions::from_query(params.allergens.as_deref(), params.diets.as_deref());
+    Ok((SemanticQuery::Text(text), exclusions, params.limit))
+}
+
+pub(crate) fn query_from_payload(
+    payload: SemanticSearchPayload,
+) -> Result<(SemanticQuery, Exclusions, Option<u64>)> {
+    let query = match (non_blank(payload.q), payload.vector) {
+        (Some(text), None) => SemanticQuery::Text(text),
+        (None, Some(vector)) => SemanticQuery::Vector(vector),
+        _ => {
+            return Err(ServiceError::BadRequest(
+                "Send exactly one of 'q' and 'vector'".to_string(),
+            ));
+        }
+    };
+    let exclusions = Exclusions::new(payload.allergens, payload.diets);
+    Ok((query, exclusions, payload.limit))
+}
+
+pub(crate) fn non_blank(q: Option<String>) -> Option<String> {
+    q.map(|q| q.trim().to_string()).filter(|q| !q.is_empty())
+}
+
+/// Up to `limit` products nearest to `query`, best match first. No matches, or no vector
+/// index at all on in-memory storage, is an empty list.
+pub async fn semantic_search(
+    state: &AppState,
+    query: SemanticQuery,
+    exclusions: &Exclusions,
+    limit: Option<u64>,
+) -> Result<Vec<Product>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let Some(clients) = &state.clients else {
+        warn!("No vector index on in-memory storage; semantic search finds nothing.");
+        return Ok(Vec::new());
+    };
+
+    let vector = match query {
+        SemanticQuery::Vector(vector) => vector,
+        SemanticQuery::Text(text) => embed(state, &text).await?,
+    };
+    let filter = exclusions.filter();
+    debug!("Constructed Qdrant filter: {:?}", filter);
+
+    let search = SearchPointsBuilder::new(QDRANT_COLLECTION_NAME, vector, limit)
+        .filter(filter)
+        .with_payload(true);
+    let search_result =
+        observe_qdrant("search_points", clients.qdrant_client.search_points(search)).await?;
+    let barcodes = barcodes_in_score_order(search_result.result);
+    if barcodes.is_empty() {
+        info!("Semantic search found no matching products.");
+        return Ok(Vec::new());
+    }
+
+    let mut found: HashMap<String, Product> = state
+        .products
+        .find_by_codes(barcodes.clone(), barcodes.len())
+        .await?
+        .into_iter()
+        .map(|product| (product.code.clone(), product))
+        .collect();
+    let products: Vec<Product> = barcodes
+        .iter()
+        .filter_map(|code| found.remove(code))
+        .filter(|product| exclusions.allows(product))
+        .collect();
+    info!(
+        "Semantic search returning {} of {} indexed matches.",
+        products.len(),
+        barcodes.len()
+    );
+    Ok(products)
+}
+
+pub(crate) async fn embed(state: &AppState, text: &str) -> Result<Vec<f32>> {
+    let embeddings = state.embeddings.as_ref().ok_or_else(|| {
+        ServiceError::SemanticSearchUnavailable(format!(
+            "{} is not set; send a 'vector' instead",
+            EMBEDDING_SERVICE_URL_ENV
+        ))
+    })?;
+    let mut vectors = embeddings.embed(&[text.to_string()]).await?;
+    Ok(vectors.remove(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yoloeats_domain::fixtures::ProductFixture;
+
+    #[test]
+    fn exclusions_become_must_not_keyword_conditions() {
+        let exclusions = Exclusions::new(
+            Some(vec!["en:milk".to_string()]),
+            Some(vec!["vegan".to_string()]),
+        );
+        let filter = exclusions.filter();
+        assert!(filter.must.is_empty());
+        let keys: Vec<&str> = filter
+            .must_not
+            .iter()
+            .map(|condition| match &condition.condition_one_of {
+                Some(ConditionOneOf::Field(field)) => field.key.as_str(),
+                other => panic!("unexpected condition {:?}", other),
+            })
+            .collect();
+        assert_eq!(keys, ["allergens_tags", "labels_tags"]);
+
+        assert!(Exclusions::default().filter().must_not.is_empty());
+    }
+
+    #[test]
+    fn query_exclusions_are_comma_separated() {
+        assert_eq!(
+            Exclusions::from_query(Some("en:milk, gluten,"), Some("vegan")),
+            Exclusions::new(
+                Some(vec!["en:milk".to_string(), "gluten".to_string()]),
+                Some(vec!["vegan".to_string()]),
+            )
+        );
+        assert_eq!(Exclusions::from_query(None, None), Exclusions::default());
+    }
+
+    #[test]
+    fn payload_takes_exactly_one_of_text_and_vector() {
+        let payload = |q: Option<&str>, vector: Option<Vec<f32>>| SemanticSearchPayload {
+            q: q.map(str::to_string),
+            vector,
+            limit: None,
+            allergens: None,
+            diets: None,
+        };
+        assert!(matches!(
+            query_from_payload(payload(Some(" oat milk "), None)),
+            Ok((SemanticQuery::Text(text), _, None)) if text == "oat milk"
+        ));
+        assert!(matches!(
+            query_from_payload(payload(None, Some(vec![0.5, 0.5]))),
+            Ok((SemanticQuery::Vector(_), _, None))
+        ));
+        for both_or_neither in [
+            payload(Some("oat milk"), Some(vec![0.5])),
+            payload(Some("  "), None),
+            payload(None, None),
+        ] {
+            assert!(matches!(
+                query_from_payload(both_or_neither),
+                Err(ServiceError::BadRequest(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn hydrated_products_are_checked_against_the_exclusions() {
+        let exclusions = Exclusions::new(Some(vec!["en:milk".to_string()]), None);
+        let milk: Product = ProductFixture::new("1").with_allergens(["en:milk"]).build();
+        let plain: Product = ProductFixture::new("2").build();
+        assert!(!exclusions.allows(&milk));
+        assert!(exclusions.allows(&plain));
+
+        let vegan = Exclusions::new(None, Some(vec!["vegan".to_string()]));
+        let cheese: Product = ProductFixture::new("3")
+            .with_labels(["en:non-vegan"])
+            .build();
+        assert!(!vegan.allows(&cheese));
+    }
+}
@@ -1,17 +1,77 @@
+use crate::{
+    audit::AuditLog, cascade::ProductCopies, categories::CategoryTaxonomy,
+    embedding::EmbeddingClient, images::ImageHosts, popularity::ScanCounts, reports::ReportStore,
+    repository::ProductRepository, webhooks::Webhooks,
+};
 use mongodb::Database;
 use neo4rs::Graph as Neo4jClient;
 use qdrant_client::Qdrant as QdrantClient;
 use redis::Client as RedisClient;
 use reqwest::Client as HttpClient;
+use rust_database_clients::{Cache, http_resilience::ResilientClient};
 use std::sync::Arc;
+use yoloeats_auth::InternalTokens;
+use yoloeats_dynamic_config::DynamicConfig;
+use yoloeats_metrics::{BodyLimits, LoadShedConfig, RateLimitStore, RateLimits, TrustedProxies};
+use yoloeats_pagination::CursorCodec;
+use yoloeats_shutdown::TaskTracker;
+use yoloeats_versioning::Deprecation;
 
 #[derive(Clone)]
 pub struct AppState {
+    pub products: Arc<dyn ProductRepository>,
+    /// Product changes made through the API, see [`crate::audit`].
+    pub audit: Arc<dyn AuditLog>,
+    /// Users' reports of wrong product data, see [`crate::reports`].
+    pub reports: Arc<dyn ReportStore>,
+    /// Where deleted products are removed from besides MongoDB, see [`crate::cascade`].
+    pub copies: Arc<dyn ProductCopies>,
+    /// Category names and hierarchy, see [`crate::categories`].
+    pub categories: Arc<dyn CategoryTaxonomy>,
+    /// Barcode scans, see [`crate::popularity`].
+    pub scans: Arc<dyn ScanCounts>,
+    /// Who is told about product changes, see [`crate::webhooks`].
+    pub webhooks: Webhooks,
+    pub cache: Arc<dyn Cache>,
+    /// `None` with `STORAGE_MODE=memory`.
+    pub clients: Option<Clients>,
+    pub http_client: HttpClient,
+    /// Hosts product images may be on, see [`crate::images`].
+    pub image_hosts: ImageHosts,
+    pub upstream_client: ResilientClient,
+    pub user_profile_service_url: String,
+    /// Embeds search queries and indexed products, see [`crate::embedding`]; `None`
+    /// leaves only vector queries.
+    pub embeddings: Option<Arc<dyn EmbeddingClient>>,
+    /// Where [`crate::off_fallback`] looks up barcodes the catalog doesn't have.
+    pub off_api_url: String,
+    pub internal_tokens: InternalTokens,
+    pub config: DynamicConfig,
+    pub load_shed: LoadShedConfig,
+    pub body_limits: BodyLimits,
+    pub rate_limits: RateLimits,
+    /// Where [`AppState::rate_limits`] are counted: Redis, or a map in process with
+    /// `STORAGE_MODE=memory`.
+    pub rate_limit_store: Arc<dyn RateLimitStore>,
+    /// Whose `X-Forwarded-For` names the client the limits count, like the gateway's.
+    pub trusted_proxies: TrustedProxies,
+    pub cursor_codec: CursorCodec,
+    /// Announced on every `/api/v1` response.
+    pub api_v1_deprecation: Deprecation,
+    /// Where requests naming no country are scoped to; see [`crate::default_country`].
+    pub default_country: Option<String>,
+    /// Serves the reads without a bearer token; see [`crate::auth`].
+    pub public_reads: bool,
+    /// Background work a shutdown waits for, like the indexing in [`crate::vector_sync`].
+    pub tasks: TaskTracker,
+}
+
+/// The external clients behind [`AppState::products`] and [`AppState::cache`], plus the
+/// vector index the recommendations search, which has no in-memory counterpart.
+#[derive(Clone)]
+pub struct Clients {
     pub mongo_db: Database,
     pub redis_client: RedisClient,
-
     pub qdrant_client: Arc<QdrantClient>,
     pub neo4j_client: Neo4jClient,
-    pub http_client: HttpClient,
-    pub user_profile_service_url: String,
 }
@@ -0,0 +1,284 @@
+//! "Did you mean": a search whose `q` finds nothing gets up to [`MAX_SUGGESTIONS`]
+//! queries with its unknown words swapped for known ones spelled alike, so `nutela`
+//! suggests `nutella` and `joghurt` suggests `yoghurt`.
+//!
+//! The known words are the vocabulary: the [`VOCABULARY_SIZE`] most frequent words of
+//! product names and brand tags, which MongoDB counts in an aggregation over the whole
+//! catalog. The first search to need it builds it and Redis keeps it for
+//! [`VOCABULARY_TTL_SECS`], so it is counted at most once an hour, and by one search at
+//! a time in each replica; searches that find nothing meanwhile get no suggestions.
+//! Without Redis there are no suggestions at all, rather than an aggregation for every
+//! search that finds nothing. Searches that find something never read the vocabulary.
+//!
+//! A known word is spelled alike when its Levenshtein distance to the query word is at
+//! most [`max_distance`] of the query word's length.
+
+use crate::{
+    catalog_metrics::{CacheOutcome, record_cache_lookup},
+    handlers::connect_cache,
+    models::name_tokens,
+    state::AppState,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{debug, error, info, warn};
+
+/// The most suggestions a search gets.
+pub const MAX_SUGGESTIONS: usize = 3;
+
+/// How many of the most frequent words the vocabulary keeps.
+pub const VOCABULARY_SIZE: u64 = 5_000;
+
+/// How long Redis keeps the vocabulary before a search builds it again.
+pub const VOCABULARY_TTL_SECS: u64 = 3_600;
+
+/// Shorter words are neither in the vocabulary nor corrected.
+pub const MIN_WORD_CHARS: usize = 3;
+
+/// Where Redis keeps the vocabulary, as a JSON array of words.
+const VOCABULARY_KEY: &str = "search:vocabulary";
+
+/// Whether a search of this replica is building the vocabulary.
+static BUILDING: AtomicBool = AtomicBool::new(false);
+
+/// Marks the vocabulary as being built until dropped, even by a cancelled search.
+struct Building;
+
+impl Building {
+    fn start() -> Option<Self> {
+        (!BUILDING.swap(true, Ordering::AcqRel)).then_some(Building)
+    }
+}
+
+impl Drop for Building {
+    fn drop(&mut self) {
+        BUILDING.store(false, Ordering::Release);
+    }
+}
+
+/// How many edits a query word of `chars` characters may be from a known word: one for
+/// up to four characters, two up to eight and three beyond.
+pub fn max_distance(chars: usize) -> usize {
+    match chars {
+        0..=4 => 1,
+        5..=8 => 2,
+        _ => 3,
+    }
+}
+
+/// The Levenshtein distance between `a` and `b`: the fewest characters to insert,
+/// delete or replace to turn one into the other.
+pub fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = Vec::with_capacity(b.len() + 1);
+        current.push(i + 1);
+        for (j, cb) in b.iter().enumerate() {
+            let replaced = previous[j] + usize::from(ca != cb);
+            current.push(replaced.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The words of `vocabulary` spelled like `word`, closest first and then in the
+/// vocabulary's order, the most frequent first.
+pub fn corrections<'a>(word: &str, vocabulary: &'a [String]) -> Vec<&'a str> {
+    let word: Vec<char> = word.chars().collect();
+    let limit = max_distance(word.len());
+    let mut close: Vec<(usize, &str)> = vocabulary
+        .iter()
+        .filter_map(|known| {
+            let known_chars: Vec<char> = known.chars().collect();
+            // Words further apart in length are further apart in edits too.
+            if known_chars.len().abs_diff(word.len()) > limit {
+                return None;
+            }
+            let distance = levenshtein(&word, &known_chars);
+            (distance <= limit).then_some((distance, known.as_str()))
+        })
+        .collect();
+    close.sort_by_key(|(distance, _)| *distance);
+    close.into_iter().map(|(_, known)| known).collect()
+}
+
+/// Up to [`MAX_SUGGESTIONS`] queries like `q`, lowercased, with each word the
+/// vocabulary doesn't know replaced by its closest corrections in turn. None when no
+/// word has a correction.
+pub fn suggest(q: &str, vocabulary: &[String]) -> Vec<String> {
+    let words = name_tokens(q);
+    let options: Vec<Vec<&str>> = words
+        .iter()
+        .map(|word| {
+            if word.chars().count() < MIN_WORD_CHARS || vocabulary.contains(word) {
+                return vec![word.as_str()];
+            }
+            let found = corrections(word, vocabulary);
+            if found.is_empty() {
+                vec![word.as_str()]
+            } else {
+                found
+            }
+        })
+        .collect();
+
+    let typed = words.join(" ");
+    let rounds = options.iter().map(Vec::len).max().unwrap_or(0);
+    let mut suggestions: Vec<String> = Vec::new();
+    for round in 0..rounds {
+        if suggestions.len() == MAX_SUGGESTIONS {
+            break;
+        }
+        let suggestion = options
+            .iter()
+            .map(|choices| *choices.get(round).unwrap_or(&choices[0]))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if suggestion != typed && !suggestions.contains(&suggestion) {
+            suggestions.push(suggestion);
+        }
+    }
+    suggestions
+}
+
+/// The suggestions for a search for `q` that found nothing; none when the vocabulary
+/// can't be had.
+pub async fn did_you_mean(state: &AppState, q: &str) -> Vec<String> {
+    match vocabulary(state).await {
+        Some(vocabulary) => suggest(q, &vocabulary),
+        None => Vec::new(),
+    }
+}
+
+/// The vocabulary Redis keeps, built if it has none. `None` without Redis, while another
+/// search of this replica builds it, or when building fails.
+async fn vocabulary(state: &AppState) -> Option<Vec<String>> {
+    let mut conn = connect_cache(state, "vocabulary").await?;
+    match conn.get(VOCABULARY_KEY).await {
+        Ok(Some(cached)) => match serde_json::from_str(&cached) {
+            Ok(vocabulary) => {
+                record_cache_lookup("vocabulary", CacheOutcome::Hit);
+                return Some(vocabulary);
+            }
+            Err(e) => {
+                error!("Failed to deserialize the cached vocabulary: {}", e);
+                record_cache_lookup("vocabulary", CacheOutcome::Error);
+            }
+        },
+        Ok(None) => record_cache_lookup("vocabulary", CacheOutcome::Miss),
+        Err(e) => {
+            warn!("Redis GET command failed (vocabulary): {}", e);
+            record_cache_lookup("vocabulary", CacheOutcome::Error);
+            return None;
+        }
+    }
+
+    let Some(_building) = Building::start() else {
+        debug!("The vocabulary is being built; no suggestions meanwhile");
+        return None;
+    };
+    let vocabulary = match state.products.vocabulary(VOCABULARY_SIZE).await {
+        Ok(vocabulary) => vocabulary,
+        Err(e) => {
+            error!("Failed to build the search vocabulary: {}", e);
+            return None;
+        }
+    };
+    info!("Built the search vocabulary of {} words", vocabulary.len());
+    match serde_json::to_string(&vocabulary) {
+        Ok(json) => {
+            if let Err(e) = conn
+                .set_ex(VOCABULARY_KEY, &json, VOCABULARY_TTL_SECS)
+                .await
+            {
+                warn!("Failed to cache the vocabulary in Redis: {}", e);
+            }
+        }
+        Err(e) => error!("Failed to serialize the vocabulary: {}", e),
+    }
+    Some(vocabulary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distance(a: &str, b: &str) -> usize {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+        levenshtein(&a, &b)
+    }
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn levenshtein_counts_edits_in_characters() {
+        assert_eq!(distance("nutella", "nutella"), 0);
+        assert_eq!(distance("nutela", "nutella"), 1);
+        assert_eq!(distance("joghurt", "yoghurt"), 1);
+        assert_eq!(distance("joghurt", "yogurt"), 2);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("", "milk"), 4);
+        assert_eq!(distance("müsli", "musli"), 1);
+        assert_eq!(distance("käse", "kase"), 1);
+    }
+
+    #[test]
+    fn longer_words_may_be_further_off() {
+        assert_eq!(max_distance(3), 1);
+        assert_eq!(max_distance(4), 1);
+        assert_eq!(max_distance(5), 2);
+        assert_eq!(max_distance(8), 2);
+        assert_eq!(max_distance(9), 3);
+
+        let vocabulary = words(&["milk", "mild", "silk", "chocolate", "yogurt", "oat"]);
+        assert_eq!(corrections("milc", &vocabulary), ["milk", "mild"]);
+        assert_eq!(corrections("mlk", &vocabulary), ["milk"]);
+        assert!(corrections("mkl", &vocabulary).is_empty());
+        assert_eq!(corrections("joghurt", &vocabulary), ["yogurt"]);
+        assert!(corrections("jgurtt", &vocabulary).is_empty());
+        assert_eq!(corrections("chcolat", &vocabulary), ["chocolate"]);
+        assert!(corrections("chclat", &vocabulary).is_empty());
+    }
+
+    #[test]
+    fn closer_and_then_more_frequent_words_come_first() {
+        let vocabulary = words(&["bread", "break", "breed", "bead"]);
+        assert_eq!(
+            corrections("breaf", &vocabulary),
+            ["bread", "break", "breed", "bead"]
+        );
+        assert_eq!(corrections("bred", &vocabulary), ["bread", "breed"]);
+    }
+
+    #[test]
+    fn suggestions_replace_the_unknown_words() {
+        let vocabulary = words(&["nutella", "ferrero", "spread", "yoghurt", "yogurt", "greek"]);
+        assert_eq!(suggest("Nutela", &vocabulary), ["nutella"]);
+        assert_eq!(
+            suggest("greek joghurt", &vocabulary),
+            ["greek yoghurt", "greek yogurt"]
+        );
+        assert_eq!(
+            suggest("Ferero nutela spred!", &vocabulary),
+            ["ferrero nutella spread"]
+        );
+        assert!(suggest("nutella", &vocabulary).is_empty());
+        assert!(suggest("xyzzy", &vocabulary).is_empty());
+        assert!(suggest("", &vocabulary).is_empty());
+        assert!(suggest("nutela", &[]).is_empty());
+    }
+
+    #[test]
+    fn there_are_three_suggestions_at_most() {
+        let vocabulary = words(&["bread", "break", "breed", "bead", "brand"]);
+        assert_eq!(suggest("breaf", &vocabulary), ["bread", "break", "breed"]);
+        // A word that needs no correction is kept in every suggestion.
+        assert_eq!(
+            suggest("brand breaf", &vocabulary),
+            ["brand bread", "brand break", "brand breed"]
+        );
+    }
+}
@@ -0,0 +1,261 @@
+//! Reads the allergens and diets that clients and user profiles name, which come in
+//! whatever form their source uses, into the tags products are stored with.
+//!
+//! Profiles hold plain ids (`peanuts`, `gluten_free`), OpenFoodFacts data holds tags
+//! (`en:peanuts`, `en:non-vegan`), and exclusion filters match those tags exactly. A
+//! filter on the raw values would silently exclude nothing. The same goes for the
+//! categories, labels, traces and countries clients send, which [`normalize_tags`]
+//! reads into OpenFoodFacts' form.
+
+use yoloeats_domain::{
+    IngredientEntry,
+    tags::{KNOWN_ALLERGENS, normalize_tag},
+};
+use yoloeats_taxonomy::{Diet, allergen_to_tags, conflicting_tags_for_diets};
+
+/// Taxonomy tags as OpenFoodFacts stores them: [`normalize_tag`]'s form, with `en:` for
+/// values that have no language prefix, so `Vegan`, ` VEGAN ` and `en:vegan` are all
+/// `en:vegan`. Empties and duplicates are dropped and the order kept.
+///
+/// Brands have no language and keep the domain's plain `normalize_tags`.
+pub fn normalize_tags<I, S>(raw: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut tags: Vec<String> = Vec::new();
+    for tag in raw.into_iter().filter_map(|t| normalize_tag(t.as_ref())) {
+        let tag = if tag.contains(':') {
+            tag
+        } else {
+            format!("en:{}", tag)
+        };
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// Other names for the taxonomy's allergen ids, as [`normalize_tag`] leaves them.
+const ALLERGEN_SYNONYMS: &[(&str, &str)] = &[
+    ("dairy", "milk"),
+    ("egg", "eggs"),
+    ("lactose", "milk"),
+    ("peanut", "peanuts"),
+    ("sesame-seeds", "sesame"),
+    ("soy", "soybeans"),
+    ("soya", "soybeans"),
+    ("sulfites", "sulphites"),
+    ("tree-nuts", "nuts"),
+];
+
+/// The `allergens_tags` that `raw` allergens stand for, in order and deduplicated.
+/// Tagged values (`EN:Milk`) are only normalized; plain ones are looked up in the
+/// taxonomy, through [`ALLERGEN_SYNONYMS`], and otherwise get the `en:` prefix.
+pub fn allergen_tags<I, S>(raw: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut tags: Vec<String> = Vec::new();
+    for allergen in raw.into_iter().filter_map(|a| normalize_tag(a.as_ref())) {
+        for tag in tags_for_allergen(allergen) {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+    }
+    tags
+}
+
+/// The [`allergen_tags`] of `raw` that are in [`KNOWN_ALLERGENS`], the only ones a
+/// product's `allergens_tags` hold: `Soy` is kept as `en:soybeans`, `kiwi` is dropped.
+pub fn declared_allergen_tags<I, S>(raw: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    allergen_tags(raw)
+        .into_iter()
+        .filter(|tag| KNOWN_ALLERGENS.contains(&tag.as_str()))
+        .collect()
+}
+
+fn tags_for_allergen(allergen: String) -> Vec<String> {
+    if allergen.contains(':') {
+        return vec![allergen];
+    }
+    let id = ALLERGEN_SYNONYMS
+        .iter()
+        .find(|(synonym, _)| *synonym == allergen)
+        .map_or(allergen.as_str(), |(_, id)| id);
+    match allergen_to_tags(id) {
+        [] => vec![format!("en:{}", allergen)],
+        tags => tags.to_vec(),
+    }
+}
+
+/// The diets among `raw`, however spelled: `Vegan`, `en:vegan` and `gluten-free` are
+/// all read. Unknown names are dropped, as [`Diet::parse_all`] drops them.
+pub fn diets<I, S>(raw: I) -> Vec<Diet>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut diets: Vec<Diet> = Vec::new();
+    for name in raw.into_iter().filter_map(|d| normalize_tag(d.as_ref())) {
+        let name = name.split_once(':').map_or(name.as_str(), |(_, n)| n);
+        match Diet::parse(&name.replace('-', "_")) {
+            Some(diet) if !diets.contains(&diet) => diets.push(diet),
+            _ => {}
+        }
+    }
+    diets
+}
+
+/// The label tags products following any of the `raw` diets must not carry.
+pub fn diet_exclusion_tags<I, S>(raw: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    conflicting_tags_for_diets(&diets(raw))
+}
+
+/// The tags a product's structured ingredients imply.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IngredientHints {
+    /// The declarable allergens the ingredients are, such as `en:milk` for `en:milk` or
+    /// `en:eggs` for `en:egg`.
+    pub allergens: Vec<String>,
+    /// `en:non-vegan` and `en:non-vegetarian`, for an ingredient flagged as not so.
+    pub labels: Vec<String>,
+}
+
+pub fn ingredient_hints(ingredients: &[IngredientEntry]) -> IngredientHints {
+    let names = ingredients.iter().filter_map(IngredientEntry::name);
+    let allergens = declared_allergen_tags(names);
+    let mut labels = Vec::new();
+    if ingredients.iter().any(IngredientEntry::not_vegan) {
+        labels.push("en:non-vegan".to_string());
+    }
+    if ingredients.iter().any(IngredientEntry::not_vegetarian) {
+        labels.push("en:non-vegetarian".to_string());
+    }
+    IngredientHints { allergens, labels }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_take_one_canonical_form() {
+        let cases: &[(&[&str], &[&str])] = &[
+            (&["Vegan", "en:vegan", " VEGAN "], &["en:vegan"]),
+            (&["Fair Trade", "en:fair  trade"], &["en:fair-trade"]),
+            (
+                &["de:Bio-Produkte", "FR:Sans Gluten"],
+                &["de:bio-produkte", "fr:sans-gluten"],
+            ),
+            (
+                &["Germany", "en:france", "Germany"],
+                &["en:germany", "en:france"],
+            ),
+            (&["e:number", "abc:def"], &["en:e-number", "en:abc-def"]),
+            (&["", "  ", "en:", "!"], &[]),
+            (&[], &[]),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(normalize_tags(*raw), *expected, "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn normalized_tags_stay_as_they_are() {
+        for tags in [
+            &["en:vegan", "en:organic"][..],
+            &["en:sesame-seeds", "de:bio-produkte", "fr:sans-gluten"],
+            &["en:müsli", "en:non-vegan"],
+        ] {
+            assert_eq!(normalize_tags(tags), tags);
+            assert_eq!(normalize_tags(normalize_tags(tags)), tags);
+        }
+    }
+
+    #[test]
+    fn profile_ids_become_tags() {
+        assert_eq!(
+            allergen_tags(["peanuts", "milk", "sesame"]),
+            ["en:peanuts", "en:milk", "en:sesame-seeds"]
+        );
+    }
+
+    #[test]
+    fn tags_are_normalized_and_kept() {
+        assert_eq!(
+            allergen_tags([" EN:Milk ", "de:Erdnüsse", "en:milk"]),
+            ["en:milk", "de:erdnüsse"]
+        );
+    }
+
+    #[test]
+    fn synonyms_and_unknown_names() {
+        assert_eq!(
+            allergen_tags(["Peanut", "Dairy", "milk", "Soya", "kiwi", " "]),
+            ["en:peanuts", "en:milk", "en:soybeans", "en:kiwi"]
+        );
+    }
+
+    #[test]
+    fn declared_allergens_keep_synonyms_and_drop_the_unknown() {
+        assert_eq!(
+            declared_allergen_tags(["Soy", "peanut", "sesame", "sulphites", "kiwi"]),
+            [
+                "en:soybeans",
+                "en:peanuts",
+                "en:sesame-seeds",
+                "en:sulphur-dioxide-and-sulphites"
+            ]
+        );
+    }
+
+    #[test]
+    fn diets_are_read_however_spelled() {
+        assert_eq!(
+            diets(["Vegan", "en:gluten-free", "Lactose Free", "vegan", "keto"]),
+            [Diet::Vegan, Diet::GlutenFree, Diet::LactoseFree]
+        );
+        assert_eq!(
+            diet_exclusion_tags(["en:vegetarian"]),
+            conflicting_tags_for_diets(&[Diet::Vegetarian])
+        );
+        assert!(diet_exclusion_tags(["keto"]).is_empty());
+    }
+
+    #[test]
+    fn ingredients_hint_at_allergens_and_diets() {
+        let entry = |id: &str, vegan: &str, vegetarian: &str| IngredientEntry {
+            id: Some(id.to_string()),
+            vegan: Some(vegan.to_string()),
+            vegetarian: Some(vegetarian.to_string()),
+            ..Default::default()
+        };
+        let hints = ingredient_hints(&[
+            entry("en:sugar", "yes", "yes"),
+            entry("en:milk", "no", "yes"),
+            entry("en:egg", "no", "yes"),
+            entry("en:whole-milk-powder", "no", "yes"),
+        ]);
+        assert_eq!(hints.allergens, ["en:milk", "en:eggs"]);
+        assert_eq!(hints.labels, ["en:non-vegan"]);
+
+        let gelatin = entry("en:gelatin", "no", "no");
+        assert_eq!(
+            ingredient_hints(&[gelatin]).labels,
+            ["en:non-vegan", "en:non-vegetarian"]
+        );
+        assert_eq!(ingredient_hints(&[]), IngredientHints::default());
+    }
+}
@@ -0,0 +1,232 @@
+//! Settings that can be changed at runtime through `/internal/v1/config`; see
+//! `yoloeats_dynamic_config`. Defaults come from the environment variable in each comment.
+
+use rand::Rng;
+use yoloeats_dynamic_config::{
+    ConfigError, DynamicConfig, OverrideStore, Tunable, env_default, in_range,
+};
+
+pub const SERVICE: &str = "product-catalog-service";
+
+/// `PRODUCT_CACHE_TTL_SECS`, default 300.
+pub const PRODUCT_CACHE_TTL_SECS: Tunable<u64> = Tunable::new("product_cache_ttl_secs");
+/// `BARCODE_CACHE_TTL_SECS`, default 300.
+pub const BARCODE_CACHE_TTL_SECS: Tunable<u64> = Tunable::new("barcode_cache_ttl_secs");
+/// `CACHE_TTL_JITTER_PERCENT`, default 10.
+pub const CACHE_TTL_JITTER_PERCENT: Tunable<u64> = Tunable::new("cache_ttl_jitter_percent");
+/// `NEGATIVE_CACHE_TTL_SECS`, default 30.
+pub const NEGATIVE_CACHE_TTL_SECS: Tunable<u64> = Tunable::new("negative_cache_ttl_secs");
+/// `COUNT_CACHE_TTL_SECS`, default 60.
+pub const COUNT_CACHE_TTL_SECS: Tunable<u64> = Tunable::new("count_cache_ttl_secs");
+/// `SEARCH_CACHE_TTL_SECS`, default 90.
+pub const SEARCH_CACHE_TTL_SECS: Tunable<u64> = Tunable::new("search_cache_ttl_secs");
+/// `CATEGORY_CACHE_TTL_SECS`, default 300.
+pub const CATEGORY_CACHE_TTL_SECS: Tunable<u64> = Tunable::new("category_cache_ttl_secs");
+/// `RECENT_CACHE_TTL_SECS`, default 60.
+pub const RECENT_CACHE_TTL_SECS: Tunable<u64> = Tunable::new("recent_cache_ttl_secs");
+/// `SEARCH_CACHE_ENABLED`, default true.
+pub const SEARCH_CACHE_ENABLED: Tunable<bool> = Tunable::new("search_cache_enabled");
+/// `CHANGES_RETENTION_DAYS`, default 30.
+pub const CHANGES_RETENTION_DAYS: Tunable<u64> = Tunable::new("changes_retention_days");
+/// `RECOMMENDATION_LIMIT`, default 10.
+pub const RECOMMENDATION_LIMIT: Tunable<usize> = Tunable::new("recommendation_limit");
+/// `IMPORT_MAX_LINE_BYTES`, default 1 MiB.
+pub const IMPORT_MAX_LINE_BYTES: Tunable<usize> = Tunable::new("import_max_line_bytes");
+/// `ALLOW_INTERNAL_CODES`, default false.
+pub const ALLOW_INTERNAL_CODES: Tunable<bool> = Tunable::new("allow_internal_codes");
+/// `OFF_FALLBACK_ENABLED`, default false.
+pub const OFF_FALLBACK_ENABLED: Tunable<bool> = Tunable::new("off_fallback_enabled");
+/// `OFF_FALLBACK_TIMEOUT_MS`, default 1500.
+pub const OFF_FALLBACK_TIMEOUT_MS: Tunable<u64> = Tunable::new("off_fallback_timeout_ms");
+/// `OFF_FALLBACK_PER_MINUTE`, default 60.
+pub const OFF_FALLBACK_PER_MINUTE: Tunable<u64> = Tunable::new("off_fallback_per_minute");
+/// `EMBEDDING_TIMEOUT_MS`, default 10000.
+pub const EMBEDDING_TIMEOUT_MS: Tunable<u64> = Tunable::new("embedding_timeout_ms");
+/// `REPORTS_PER_MINUTE`, default 5.
+pub const REPORTS_PER_MINUTE: Tunable<u64> = Tunable::new("reports_per_minute");
+/// `IMAGE_CHECK_ENABLED`, default false.
+pub const IMAGE_CHECK_ENABLED: Tunable<bool> = Tunable::new("image_check_enabled");
+/// `IMAGE_CHECK_TIMEOUT_MS`, default 1000.
+pub const IMAGE_CHECK_TIMEOUT_MS: Tunable<u64> = Tunable::new("image_check_timeout_ms");
+
+pub fn config(store: impl OverrideStore + 'static) -> Result<DynamicConfig, ConfigError> {
+    DynamicConfig::builder(SERVICE)
+        .register_validated(
+            PRODUCT_CACHE_TTL_SECS,
+            env_default("PRODUCT_CACHE_TTL_SECS", 300),
+            "Redis TTL of products cached by ID",
+            in_range(1, 86_400),
+        )
+        .register_validated(
+            BARCODE_CACHE_TTL_SECS,
+            env_default("BARCODE_CACHE_TTL_SECS", 300),
+            "Redis TTL of products cached by barcode",
+            in_range(1, 86_400),
+        )
+        .register_validated(
+            CACHE_TTL_JITTER_PERCENT,
+            env_default("CACHE_TTL_JITTER_PERCENT", 10),
+            "Cache TTLs vary by up to this percentage either way, so keys set together expire apart",
+            in_range(0, 50),
+        )
+        .register_validated(
+            NEGATIVE_CACHE_TTL_SECS,
+            env_default("NEGATIVE_CACHE_TTL_SECS", 30),
+            "Redis TTL of the marker left by an ID or barcode lookup that found nothing",
+            in_range(1, 3_600),
+        )
+        .register_validated(
+            COUNT_CACHE_TTL_SECS,
+            env_default("COUNT_CACHE_TTL_SECS", 60),
+            "Redis TTL of the match counts of unfiltered and single-country searches",
+            in_range(1, 3_600),
+        )
+        .register_validated(
+            SEARCH_CACHE_TTL_SECS,
+            env_default("SEARCH_CACHE_TTL_SECS", 90),
+            "Redis TTL of search result pages; writes show in searches after at most this long",
+            in_range(1, 600),
+        )
+        .register_validated(
+            CATEGORY_CACHE_TTL_SECS,
+            env_default("CATEGORY_CACHE_TTL_SECS", 300),
+            "Redis TTL of category listings; new products show in their counts after at most this long",
+            in_range(1, 86_400),
+        )
+        .register_validated(
+            RECENT_CACHE_TTL_SECS,
+            env_default("RECENT_CACHE_TTL_SECS", 60),
+            "Redis TTL of the first page of the recent products feeds",
+            in_range(1, 3_600),
+        )
+        .register(
+            SEARCH_CACHE_ENABLED,
+            env_default("SEARCH_CACHE_ENABLED", true),
+            "Cache search result pages in Redis; off, every search reads MongoDB",
+        )
+        .register_validated(
+            CHANGES_RETENTION_DAYS,
+            env_default("CHANGES_RETENTION_DAYS", 30),
+            "How far back the changes feed serves; older `since` times must resync in full",
+            in_range(1, 365),
+        )
+        .register_validated(
+            RECOMMENDATION_LIMIT,
+            env_default("RECOMMENDATION_LIMIT", 10),
+            "Maximum products returned by the recommendations endpoint",
+            in_range(1, 50),
+        )
+        .register_validated(
+            IMPORT_MAX_LINE_BYTES,
+            env_default("IMPORT_MAX_LINE_BYTES", 1024 * 1024),
+            "Longest product import line; longer lines are skipped",
+            in_range(1024, 16 * 1024 * 1024),
+        )
+        .register(
+            ALLOW_INTERNAL_CODES,
+            env_default("ALLOW_INTERNAL_CODES", false),
+            "Accept store-internal (prefix 2) and non-GTIN product codes besides EAN-8, UPC-A and EAN-13",
+        )
+        .register(
+            OFF_FALLBACK_ENABLED,
+            env_default("OFF_FALLBACK_ENABLED", false),
+            "Look barcodes MongoDB doesn't have up on the OpenFoodFacts API and store what it finds",
+        )
+        .register_validated(
+            OFF_FALLBACK_TIMEOUT_MS,
+            env_default("OFF_FALLBACK_TIMEOUT_MS", 1500),
+            "How long a barcode lookup waits for the OpenFoodFacts API before answering 404",
+            in_range(100, 10_000),
+        )
+        .register_validated(
+            OFF_FALLBACK_PER_MINUTE,
+            env_default("OFF_FALLBACK_PER_MINUTE", 60),
+            "OpenFoodFacts API calls a minute across all replicas; OpenFoodFacts asks for at most 100",
+            in_range(1, 100),
+        )
+        .register_validated(
+            EMBEDDING_TIMEOUT_MS,
+            env_default("EMBEDDING_TIMEOUT_MS", 10_000),
+            "How long a semantic search or vector upsert waits for the embedding service",
+            in_range(100, 60_000),
+        )
+        .register_validated(
+            REPORTS_PER_MINUTE,
+            env_default("REPORTS_PER_MINUTE", 5),
+            "Product reports one client may file a minute",
+            in_range(1, 600),
+        )
+        .register(
+            IMAGE_CHECK_ENABLED,
+            env_default("IMAGE_CHECK_ENABLED", false),
+            "Ask for a product's new image with a HEAD request and refuse it if the host has none",
+        )
+        .register_validated(
+            IMAGE_CHECK_TIMEOUT_MS,
+            env_default("IMAGE_CHECK_TIMEOUT_MS", 1000),
+            "How long the image check waits before taking the image unchecked",
+            in_range(100, 5_000),
+        )
+        .build(store)
+}
+
+/// The current value of a cache TTL, moved by a random amount within
+/// [`CACHE_TTL_JITTER_PERCENT`] of it.
+pub fn cache_ttl(config: &DynamicConfig, ttl: Tunable<u64>) -> u64 {
+    jitter(
+        config.get(ttl),
+        config.get(CACHE_TTL_JITTER_PERCENT),
+        &mut rand::rng(),
+    )
+}
+
+/// `ttl_secs` plus or minus up to `percent` of it, never below one second.
+fn jitter(ttl_secs: u64, percent: u64, rng: &mut impl Rng) -> u64 {
+    let spread = ttl_secs * percent / 100;
+    rng.random_range(ttl_secs - spread..=ttl_secs + spread)
+        .max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yoloeats_dynamic_config::MemoryStore;
+
+    #[test]
+    fn every_tunable_is_registered_with_its_type() {
+        let config = config(MemoryStore::default()).unwrap();
+        assert_eq!(config.get(PRODUCT_CACHE_TTL_SECS), 300);
+        assert_eq!(config.get(BARCODE_CACHE_TTL_SECS), 300);
+        assert_eq!(config.get(NEGATIVE_CACHE_TTL_SECS), 30);
+        assert_eq!(config.get(CACHE_TTL_JITTER_PERCENT), 10);
+        assert_eq!(config.get(COUNT_CACHE_TTL_SECS), 60);
+        assert_eq!(config.get(SEARCH_CACHE_TTL_SECS), 90);
+        assert_eq!(config.get(CATEGORY_CACHE_TTL_SECS), 300);
+        assert_eq!(config.get(RECENT_CACHE_TTL_SECS), 60);
+        assert!(config.get(SEARCH_CACHE_ENABLED));
+        assert_eq!(config.get(CHANGES_RETENTION_DAYS), 30);
+        assert_eq!(config.get(RECOMMENDATION_LIMIT), 10);
+        assert_eq!(config.get(IMPORT_MAX_LINE_BYTES), 1024 * 1024);
+        assert!(!config.get(ALLOW_INTERNAL_CODES));
+        assert!(!config.get(OFF_FALLBACK_ENABLED));
+        assert_eq!(config.get(OFF_FALLBACK_TIMEOUT_MS), 1500);
+        assert_eq!(config.get(OFF_FALLBACK_PER_MINUTE), 60);
+        assert_eq!(config.get(EMBEDDING_TIMEOUT_MS), 10_000);
+        assert_eq!(config.get(REPORTS_PER_MINUTE), 5);
+        assert!(!config.get(IMAGE_CHECK_ENABLED));
+        assert_eq!(config.get(IMAGE_CHECK_TIMEOUT_MS), 1000);
+    }
+
+    #[test]
+    fn jitter_stays_within_the_percentage() {
+        let mut rng = rand::rng();
+        let ttls: Vec<u64> = (0..1000).map(|_| jitter(300, 10, &mut rng)).collect();
+        assert!(ttls.iter().all(|ttl| (270..=330).contains(ttl)));
+        assert!(ttls.iter().any(|&ttl| ttl != 300));
+
+        assert!((0..100).all(|_| jitter(300, 0, &mut rng) == 300));
+        // Short TTLs round the spread down, and never reach zero.
+        assert!((0..100).all(|_| jitter(5, 10, &mut rng) == 5));
+        assert!((0..100).all(|_| (1..=3).contains(&jitter(2, 50, &mut rng))));
+    }
+}
@@ -0,0 +1,839 @@
+//! `/api/v2/products`: the v1 handlers' logic behind the v2 response shapes.
+//!
+//! v2 products are camelCase, with a plain string id, lists that are never null and
+//! timestamps from [`yoloeats_versioning::timestamp`]. Recommendations come wrapped with
+//! their source and whether a profile narrowed them, each with its similarity score. v1 keeps [`Product`] as stored.
+//! History entries get the same id and timestamp treatment; their changes still name the
+//! stored fields.
+
+use crate::{
+    audit::{self, Actor, AuditAction, AuditEntry, FieldChange, HistoryPageLimit},
+    auth::RouteAuth,
+    default_country::{DefaultCountry, scope_countries},
+    errors::Result,
+    etag::{Conditional, IfNoneMatch},
+    handlers::{
+        self, RecommendedFor, RecommendedProduct, SearchPageLimit, SearchResults, TotalCount,
+        find_product_by_barcode_or_fetch, find_product_by_id_if_none_match,
+        find_products_by_barcodes, find_products_in_view, recommend, search_suggestions,
+        search_total_count,
+    },
+    highlight::Highlight,
+    import::{self, ImportReport},
+    language::{AcceptLanguage, VaryByLanguage},
+    models::{
+        BatchLookupPayload, CreateProductPayload, PatchProductPayload, Product,
+        RecommendationParams, SearchParams, SearchSummary, SearchView, SemanticSearchParams,
+        SemanticSearchPayload, UpdateProductPayload,
+    },
+    off_fallback::FetchedRemotely,
+    projection::{ProductResponseParams, ProductShape, schema_fields},
+    semantic,
+    state::AppState,
+};
+use axum::{
+    Json, Router,
+    body::Body,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode, header::AUTHORIZATION},
+    routing::{get, post, put},
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::{Arc, LazyLock},
+};
+use tracing::instrument;
+use utoipa::ToSchema;
+use yoloeats_domain::ErrorBody;
+use yoloeats_metrics::ValidJson;
+use yoloeats_pagination::{Page, PageParams};
+use yoloeats_versioning::timestamp;
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ProductV2 {
+    pub id: String,
+    pub code: String,
+    pub name: Option<String>,
+    /// The name by language code.
+    pub name_langs: HashMap<String, String>,
+    /// The name in the reader's language, or else `name`; see [`crate::language`].
+    pub display_name: Option<String>,
+    pub generic_name: Option<String>,
+    pub brands: Vec<String>,
+    pub categories: Vec<String>,
+    pub main_category: Option<String>,
+    pub labels: Vec<String>,
+    pub ingredients_text: Option<String>,
+    pub allergens: Vec<String>,
+    pub traces: Vec<String>,
+    pub quantity: Option<String>,
+    pub image_url: Option<String>,
+    pub image_small_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_thumb_url: Option<String>,
+    pub countries: Vec<String>,
+    pub nutriscore: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ecoscore: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ecoscore_score: Option<f64>,
+    pub created_at: String,
+    pub updated_at: String,
+    /// Where the search's `q` was found, with `highlight=true`, by the fields' v2 names.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub highlights: Option<Vec<Highlight>>,
+}
+
+impl From<Product> for ProductV2 {
+    fn from(product: Product) -> Self {
+        ProductV2 {
+            id: product.id.map(|id| id.to_hex()).unwrap_or_default(),
+            code: product.code,
+            display_name: product.product_name.clone(),
+            name: product.product_name,
+            name_langs: product.product_name_langs.unwrap_or_default(),
+            generic_name: product.generic_name,
+            brands: product.brands.unwrap_or_default(),
+            categories: product.categories.unwrap_or_default(),
+            main_category: product.main_category,
+            labels: product.labels.unwrap_or_default(),
+            ingredients_text: product.ingredients_text,
+            allergens: product.allergens_tags,
+            traces: product.traces_tags.unwrap_or_default(),
+            quantity: product.quantity,
+            image_url: product.image_url,
+            image_small_url: product.image_small_url,
+            image_thumb_url: product.image_thumb_url,
+            countries: product.countries.unwrap_or_default(),
+            nutriscore: product.nutrition_grade_fr,
+            ecoscore: product.ecoscore_grade,
+            ecoscore_score: product.ecoscore_score,
+            created_at: timestamp(&product.created_at),
+            updated_at: timestamp(&product.last_modified_at),
+            highlights: None,
+        }
+    }
+}
+
+/// A product as `view=summary` lists it.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchSummaryV2 {
+    pub id: String,
+    pub code: String,
+    pub name: Option<String>,
+    pub display_name: Option<String>,
+    pub brands: Vec<String>,
+    pub image_small_url: Option<String>,
+    pub nutriscore: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ecoscore: Option<String>,
+    pub allergens: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub highlights: Option<Vec<Highlight>>,
+}
+
+impl From<SearchSummary> for SearchSummaryV2 {
+    fn from(summary: SearchSummary) -> Self {
+        SearchSummaryV2 {
+            id: summary.id.map(|id| id.to_hex()).unwrap_or_default(),
+            code: summary.code,
+            name: summary.product_name,
+            display_name: summary.display_name,
+            brands: summary.brands.unwrap_or_default(),
+            image_small_url: summary.image_small_url,
+            nutriscore: summary.nutrition_grade_fr,
+            ecoscore: summary.ecoscore_grade,
+            allergens: summary.allergens_tags,
+            highlights: highlights_v2(summary.highlights),
+        }
+    }
+}
+
+/// `highlights` with their fields under their v2 names.
+fn highlights_v2(highlights: Option<Vec<Highlight>>) -> Option<Vec<Highlight>> {
+    let renamed = |highlight: Highlight| {
+        let field = match highlight.field.as_str() {
+            "product_name" => "name",
+            "brands_tags" => "brands",
+            "ingredients_text" => "ingredientsText",
+            other => other,
+        }
+        .to_string();
+        Highlight { field, ..highlight }
+    };
+    highlights.map(|highlights| highlights.into_iter().map(renamed).collect())
+}
+
+/// A v2 search page in the view it was asked for.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum SearchResultsV2 {
+    Full(Page<ProductV2>),
+    Summary(Page<SearchSummaryV2>),
+}
+
+impl From<SearchResults> for SearchResultsV2 {
+    fn from(results: SearchResults) -> Self {
+        match results {
+            SearchResults::Full(hits) => SearchResultsV2::Full(hits.map(|hit| ProductV2 {
+                display_name: hit.display_name,
+                highlights: highlights_v2(hit.highlights),
+                ..ProductV2::from(hit.product)
+            })),
+            SearchResults::Summary(summaries) => {
+                SearchResultsV2::Summary(summaries.map(SearchSummaryV2::from))
+            }
+        }
+    }
+}
+
+/// A v2 search page, with `didYouMean` when its `q` found nothing.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResponseV2 {
+    #[serde(flatten)]
+    pub results: SearchResultsV2,
+    /// Up to three queries like `q` with its words spelled as in the catalog, only when
+    /// the search found nothing.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub did_you_mean: Vec<String>,
+}
+
+/// Above this page size, a v2 search without `view` lists summaries.
+pub const SUMMARY_VIEW_ABOVE_LIMIT: u64 = 50;
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RecommendationsV2 {
+    pub source_id: String,
+    /// Whether the user's allergens and diets were applied: not for anonymous requests,
+    /// users without a profile, or while the profile service can't be reached.
+    pub personalized: bool,
+    pub items: Vec<RecommendedProductV2>,
+}
+
+/// A recommended product with its similarity to the source, higher being more similar.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct RecommendedProductV2 {
+    #[serde(flatten)]
+    pub product: ProductV2,
+    pub score: f32,
+}
+
+impl From<RecommendedProduct> for RecommendedProductV2 {
+    fn from(recommended: RecommendedProduct) -> Self {
+        RecommendedProductV2 {
+            product: recommended.product.into(),
+            score: recommended.score,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntryV2 {
+    pub id: String,
+    pub product_id: String,
+    pub code: String,
+    pub action: AuditAction,
+    pub changes: Vec<FieldChange>,
+    pub actor: Option<String>,
+    pub at: String,
+}
+
+impl From<AuditEntry> for AuditEntryV2 {
+    fn from(entry: AuditEntry) -> Self {
+        AuditEntryV2 {
+            id: entry.id.map(|id| id.to_hex()).unwrap_or_default(),
+            product_id: entry.product_id.to_hex(),
+            code: entry.code,
+            action: entry.action,
+            changes: entry.changes,
+            actor: entry.actor,
+            at: timestamp(&entry.at),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchLookupV2 {
+    pub products: BTreeMap<String, ProductV2>,
+    pub not_found: Vec<String>,
+}
+
+pub fn routes(auth: &RouteAuth) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", auth.write(post(create_product)))
+        .route("/search", auth.read(get(search_products)))
+        .route(
+            "/search/semantic",
+            auth.read(get(semantic_search_products).post(semantic_search_by_body)),
+        )
+        .route(
+            "/{id}",
+            auth.read(get(get_product_by_id)).merge(
+                auth.write(
+                    put(update_product)
+                        .patch(patch_product)
+                        .delete(delete_product),
+                ),
+            ),
+        )
+        .route("/barcode/{code}", auth.read(get(get_product_by_barcode)))
+        .route("/batch", auth.read(post(get_products_by_barcodes)))
+        .route("/import", auth.write(post(import_products)))
+        .route("/{id}/recommendations", auth.read(get(get_recommendations)))
+        .route("/{id}/history", auth.read(get(get_product_history)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v2/products/{id}",
+    operation_id = "get_product_by_id_v2",
+    tag = "v2",
+    params(
+        ("id" = String, Path, description = "The product's ObjectId."),
+        ProductResponseParams,
+        ("If-None-Match" = Option<String>, Header, description = "An ETag from an earlier answer."),
+        ("Accept-Language" = Option<String>, Header, description = "Languages to show the `displayName` in, unless `lang` says."),
+    ),
+    responses(
+        (status = 200, description = "The product.", body = ProductV2, headers(("ETag" = String, description = "Weak; changes with every update."))),
+        (status = 304, description = "The `If-None-Match` ETag is still current."),
+        (status = 400, description = "The id is not an ObjectId, or `fields` names an unknown field.", body = ErrorBody),
+        (status = 404, description = "No product has the id.", body = ErrorBody),
+        (status = 500, description = "MongoDB or Redis failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, if_none_match, accept_language), fields(id = %id_str))]
+pub async fn get_product_by_id(
+    State(state): State<Arc<AppState>>,
+    Path(id_str): Path<String>,
+    Query(params): Query<ProductResponseParams>,
+    if_none_match: IfNoneMatch,
+    accept_language: AcceptLanguage,
+) -> Result<(VaryByLanguage, Conditional<Value>)> {
+    let shape = ProductShape::parse(&params, &PRODUCT_V2_FIELDS)?.named(
+        DISPLAY_NAME_FIELD_V2,
+        &params,
+        &accept_language,
+    );
+    let if_none_match = shape.if_none_match(&if_none_match);
+    let product = find_product_by_id_if_none_match(&state, &id_str, &if_none_match).await?;
+    Ok((VaryByLanguage, shape.apply(product, ProductV2::from)?))
+}
+
+/// What `fields` may name on the v2 product GETs: the camelCase names.
+static PRODUCT_V2_FIELDS: LazyLock<BTreeSet<String>> = LazyLock::new(schema_fields::<ProductV2>);
+
+/// [`ProductV2::display_name`] as serialized.
+const DISPLAY_NAME_FIELD_V2: &str = "displayName";
+
+#[utoipa::path(
+    get,
+    path = "/api/v2/products/barcode/{code}",
+    operation_id = "get_product_by_barcode_v2",
+    tag = "v2",
+    params(
+        ("code" = String, Path, description = "An EAN-8, UPC-A or EAN-13 barcode."),
+        ProductResponseParams,
+        ("If-None-Match" = Option<String>, Header, description = "An ETag from an earlier answer."),
+        ("Accept-Language" = Option<String>, Header, description = "Languages to show the `displayName` in, unless `lang` says."),
+    ),
+    responses(
+        (status = 200, description = "The product.", body = ProductV2, headers(("ETag" = String, description = "Weak; changes with every update."), ("X-Fetched-From" = String, description = "`openfoodfacts` when the product was just fetched from there."))),
+        (status = 304, description = "The `If-None-Match` ETag is still current."),
+        (status = 400, description = "The code is not a valid barcode, or `fields` names an unknown field.", body = ErrorBody),
+        (status = 404, description = "No product has the barcode.", body = ErrorBody),
+        (status = 429, description = "Over the client's rate limit; retry after `Retry-After`.", body = ErrorBody),
+        (status = 500, description = "MongoDB or Redis failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, if_none_match, accept_language), fields(code = %barcode))]
+pub async fn get_product_by_barcode(
+    State(state): State<Arc<AppState>>,
+    Path(barcode): Path<String>,
+    Query(params): Query<ProductResponseParams>,
+    if_none_match: IfNoneMatch,
+    accept_language: AcceptLanguage,
+) -> Result<(FetchedRemotely, VaryByLanguage, Conditional<Value>)> {
+    handlers::check_barcode(&state, &barcode)?;
+    let shape = ProductShape::parse(&params, &PRODUCT_V2_FIELDS)?.named(
+        DISPLAY_NAME_FIELD_V2,
+        &params,
+        &accept_language,
+    );
+    let if_none_match = shape.if_none_match(&if_none_match);
+    let (product, fetched) =
+        find_product_by_barcode_or_fetch(&state, &barcode, &if_none_match).await?;
+    Ok((
+        fetched,
+        VaryByLanguage,
+        shape.apply(product, ProductV2::from)?,
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v2/products/batch",
+    operation_id = "get_products_by_barcodes_v2",
+    tag = "v2",
+    request_body = BatchLookupPayload,
+    responses(
+        (status = 200, description = "The products found, by barcode, and the codes not found.", body = BatchLookupV2),
+        (status = 400, description = "The body is not JSON.", body = ErrorBody),
+        (status = 422, description = "More than 100 codes, or a field of the wrong type.", body = ErrorBody),
+        (status = 500, description = "MongoDB or Redis failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, payload), fields(codes = payload.codes.len()))]
+pub async fn get_products_by_barcodes(
+    State(state): State<Arc<AppState>>,
+    ValidJson(payload): ValidJson<BatchLookupPayload>,
+) -> Result<Json<BatchLookupV2>> {
+    let found = find_products_by_barcodes(&state, payload.codes).await?;
+    Ok(Json(BatchLookupV2 {
+        products: found
+            .products
+            .into_iter()
+            .map(|(code, product)| (code, product.into()))
+            .collect(),
+        not_found: found.not_found,
+    }))
+}
+
+/// Lists summaries with `view=summary` and full products with `view=full`. Without
+/// `view`, pages of up to [`SUMMARY_VIEW_ABOVE_LIMIT`] list full products and larger
+/// ones summaries, which keeps a 100-product page small.
+#[utoipa::path(
+    get,
+    path = "/api/v2/products/search",
+    operation_id = "search_products_v2",
+    tag = "v2",
+    params(
+        SearchParams,
+        PageParams<SearchPageLimit>,
+        ("Accept-Language" = Option<String>, Header, description = "Languages to show each `displayName` in, unless `lang` says."),
+    ),
+    responses(
+        (status = 200, description = "One page of matching products, as summaries with `view=summary` or, without `view`, over 50 to a page, with `didYouMean` when `q` found nothing.", body = SearchResponseV2, headers(("X-Total-Count" = u64, description = "Every match, with `include_count=true`."), ("X-Default-Country" = String, description = "The `DEFAULT_COUNTRY_TAG` the search was scoped to, as it named no `country`."))),
+        (status = 400, description = "An invalid parameter or cursor.", body = ErrorBody),
+        (status = 429, description = "Over the client's rate limit; retry after `Retry-After`.", body = ErrorBody),
+        (status = 500, description = "MongoDB failed, or the text index is missing.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, params, accept_language), fields(query = ?params))]
+pub async fn search_products(
+    State(state): State<Arc<AppState>>,
+    Query(mut params): Query<SearchParams>,
+    page: PageParams<SearchPageLimit>,
+    accept_language: AcceptLanguage,
+) -> Result<(
+    DefaultCountry,
+    TotalCount,
+    VaryByLanguage,
+    Json<SearchResponseV2>,
+)> {
+    let default_country = scope_countries(&mut params.country, state.default_country.as_deref());
+    let view = params
+        .view
+        .unwrap_or(if page.limit > SUMMARY_VIEW_ABOVE_LIMIT {
+            SearchView::Summary
+        } else {
+            SearchView::Full
+        });
+    let results = find_products_in_view(&state, &params, &page, view)
+        .await?
+        .named_for(&accept_language.preferred(params.lang.as_deref()))
+        .highlighted_for(&params);
+    let total_count = search_total_count(&state, &params, results.total()).await?;
+    let did_you_mean = search_suggestions(&state, &params, &page, &results).await;
+    Ok((
+        default_country,
+        total_count,
+        VaryByLanguage,
+        Json(SearchResponseV2 {
+            results: results.into(),
+            did_you_mean,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v2/products/search/semantic",
+    operation_id = "semantic_search_products_v2",
+    tag = "v2",
+    params(
+        SemanticSearchParams,
+    ),
+    responses(
+        (status = 200, description = "The nearest products, best match first.", body = Vec<ProductV2>),
+        (status = 400, description = "No `q`.", body = ErrorBody),
+        (status = 429, description = "Over the client's rate limit; retry after `Retry-After`.", body = ErrorBody),
+        (status = 500, description = "Qdrant or MongoDB failed.", body = ErrorBody),
+        (status = 503, description = "No embedding service to embed `q` with.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, params), fields(q = ?params.q))]
+pub async fn semantic_search_products(
+    state: State<Arc<AppState>>,
+    Query(params): Query<SemanticSearchParams>,
+) -> Result<Json<Vec<ProductV2>>> {
+    let Json(products) = semantic::semantic_search_products(state, Query(params)).await?;
+    Ok(Json(products.into_iter().map(ProductV2::from).collect()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v2/products/search/semantic",
+    operation_id = "semantic_search_by_body_v2",
+    tag = "v2",
+    request_body = SemanticSearchPayload,
+    responses(
+        (status = 200, description = "The nearest products, best match first.", body = Vec<ProductV2>),
+        (status = 400, description = "Not exactly one of `q` and `vector`, or a body that is not JSON.", body = ErrorBody),
+        (status = 422, description = "A field of the wrong type or failing its rule.", body = ErrorBody),
+        (status = 429, description = "Over the client's rate limit; retry after `Retry-After`.", body = ErrorBody),
+        (status = 500, description = "Qdrant or MongoDB failed.", body = ErrorBody),
+        (status = 503, description = "No embedding service to embed `q` with.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, payload), fields(q = ?payload.q))]
+pub async fn semantic_search_by_body(
+    state: State<Arc<AppState>>,
+    payload: ValidJson<SemanticSearchPayload>,
+) -> Result<Json<Vec<ProductV2>>> {
+    let Json(products) = semantic::semantic_search_by_body(state, payload).await?;
+    Ok(Json(products.into_iter().map(ProductV2::from).collect()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v2/products",
+    operation_id = "create_product_v2",
+    tag = "v2",
+    request_body = CreateProductPayload,
+    responses(
+        (status = 201, description = "The product as stored.", body = ProductV2),
+        (status = 400, description = "An invalid barcode, or a body that is not JSON.", body = ErrorBody),
+        (status = 401, description = "No valid bearer token.", body = ErrorBody),
+        (status = 409, description = "A product has the barcode already.", body = ErrorBody),
+        (status = 422, description = "A field of the wrong type or failing its rule.", body = ErrorBody),
+        (status = 500, description = "MongoDB or Redis failed.", body = ErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[instrument(skip(state, payload), fields(code = %payload.code))]
+pub async fn create_product(
+    state: State<Arc<AppState>>,
+    actor: Actor,
+    payload: ValidJson<CreateProductPayload>,
+) -> Result<(StatusCode, Json<ProductV2>)> {
+    let (status, Json(product)) = handlers::create_product(state, actor, payload).await?;
+    Ok((status, Json(product.into())))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v2/products/{id}",
+    operation_id = "update_product_v2",
+    tag = "v2",
+    params(
+        ("id" = String, Path, description = "The product's ObjectId."),
+    ),
+    request_body = UpdateProductPayload,
+    responses(
+        (status = 200, description = "The product as stored.", body = ProductV2),
+        (status = 400, description = "An invalid id, or a body that is not JSON.", body = ErrorBody),
+        (status = 401, description = "No valid bearer token.", body = ErrorBody),
+        (status = 404, description = "No product has the id.", body = ErrorBody),
+        (status = 422, description = "A field of the wrong type or failing its rule.", body = ErrorBody),
+        (status = 500, description = "MongoDB or Redis failed.", body = ErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[instrument(skip(state, payload), fields(id = %id_str))]
+pub async fn update_product(
+    state: State<Arc<AppState>>,
+    Path(id_str): Path<String>,
+    actor: Actor,
+    payload: ValidJson<UpdateProductPayload>,
+) -> Result<Json<ProductV2>> {
+    let Json(product) = handlers::update_product(state, Path(id_str), actor, payload).await?;
+    Ok(Json(product.into()))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v2/products/{id}",
+    operation_id = "patch_product_v2",
+    tag = "v2",
+    params(
+        ("id" = String, Path, description = "The product's ObjectId."),
+    ),
+    request_body = PatchProductPayload,
+    responses(
+        (status = 200, description = "The product as stored.", body = ProductV2),
+        (status = 400, description = "An invalid id, or a body that is not JSON.", body = ErrorBody),
+        (status = 401, description = "No valid bearer token.", body = ErrorBody),
+        (status = 404, description = "No product has the id.", body = ErrorBody),
+        (status = 422, description = "A field of the wrong type or failing its rule.", body = ErrorBody),
+        (status = 500, description = "MongoDB or Redis failed.", body = ErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[instrument(skip(state, payload), fields(id = %id_str))]
+pub async fn patch_product(
+    state: State<Arc<AppState>>,
+    Path(id_str): Path<String>,
+    actor: Actor,
+    payload: ValidJson<PatchProductPayload>,
+) -> Result<Json<ProductV2>> {
+    let Json(product) = handlers::patch_product(state, Path(id_str), actor, payload).await?;
+    Ok(Json(product.into()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v2/products/{id}",
+    operation_id = "delete_product_v2",
+    tag = "v2",
+    params(
+        ("id" = String, Path, description = "The product's ObjectId."),
+    ),
+    responses(
+        (status = 204, description = "Deleted."),
+        (status = 400, description = "The id is not an ObjectId.", body = ErrorBody),
+        (status = 401, description = "No valid bearer token.", body = ErrorBody),
+        (status = 404, description = "No product has the id.", body = ErrorBody),
+        (status = 500, description = "MongoDB or Redis failed.", body = ErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[instrument(skip(state), fields(id = %id_str))]
+pub async fn delete_product(
+    state: State<Arc<AppState>>,
+    Path(id_str): Path<String>,
+    actor: Actor,
+) -> Result<StatusCode> {
+    handlers::delete_product(state, Path(id_str), actor).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v2/products/import",
+    operation_id = "import_products_v2",
+    tag = "v2",
+    request_body(content = String, content_type = "application/x-ndjson", description = "An OpenFoodFacts JSONL dump, one product per line."),
+    responses(
+        (status = 200, description = "What the import did.", body = ImportReport),
+        (status = 400, description = "The body could not be read.", body = ErrorBody),
+        (status = 401, description = "No valid bearer token.", body = ErrorBody),
+        (status = 500, description = "MongoDB or Redis failed.", body = ErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[instrument(skip(state, body))]
+pub async fn import_products(
+    state: State<Arc<AppState>>,
+    body: Body,
+) -> Result<Json<ImportReport>> {
+    import::import_products(state, body).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v2/products/{id}/recommendations",
+    operation_id = "get_recommendations_v2",
+    tag = "v2",
+    params(
+        ("id" = String, Path, description = "The product's ObjectId."),
+        ("X-User-Id" = Option<String>, Header, description = "Leaves out products conflicting with this user's profile."),
+        RecommendationParams,
+    ),
+    responses(
+        (status = 200, description = "Similar products, most similar first.", body = RecommendationsV2),
+        (status = 400, description = "An invalid id, `limit` or `min_score`.", body = ErrorBody),
+        (status = 404, description = "The product is not in the vector index.", body = ErrorBody),
+        (status = 500, description = "Qdrant, MongoDB or Neo4j failed.", body = ErrorBody),
+        (status = 502, description = "The user profile service refused the user's profile.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, headers), fields(product_id = %product_id_str, user_id = ?user_id))]
+pub async fn get_recommendations(
+    State(state): State<Arc<AppState>>,
+    Path(product_id_str): Path<String>,
+    RecommendedFor(user_id): RecommendedFor,
+    headers: HeaderMap,
+    Query(params): Query<RecommendationParams>,
+) -> Result<Json<RecommendationsV2>> {
+    let recommendations = recommend(
+        &state,
+        &product_id_str,
+        user_id.as_deref(),
+        headers.get(AUTHORIZATION),
+        &params,
+    )
+    .await?;
+    Ok(Json(RecommendationsV2 {
+        source_id: product_id_str,
+        personalized: recommendations.personalized,
+        items: recommendations
+            .products
+            .into_iter()
+            .map(RecommendedProductV2::from)
+            .collect(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v2/products/{id}/history",
+    operation_id = "get_product_history_v2",
+    tag = "v2",
+    params(
+        ("id" = String, Path, description = "The product's ObjectId."),
+        PageParams<HistoryPageLimit>,
+    ),
+    responses(
+        (status = 200, description = "One page of the product's changes, newest first.", body = Page<AuditEntryV2>),
+        (status = 400, description = "An invalid id or cursor.", body = ErrorBody),
+        (status = 500, description = "MongoDB failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state), fields(id = %id_str))]
+pub async fn get_product_history(
+    State(state): State<Arc<AppState>>,
+    Path(id_str): Path<String>,
+    page: PageParams<HistoryPageLimit>,
+) -> Result<Json<Page<AuditEntryV2>>> {
+    let history = audit::find_history(&state, &id_str, &page).await?;
+    Ok(Json(history.map(AuditEntryV2::from)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::SearchHit;
+    use bson::oid::ObjectId;
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+    use yoloeats_domain::fixtures::ProductFixture;
+
+    #[test]
+    fn product_maps_to_the_v2_shape() {
+        let created = Utc.with_ymd_and_hms(2024, 6, 1, 8, 0, 0).unwrap();
+        let product = Product {
+            id: Some(ObjectId::parse_str("663a1f2e9b1e8a3f4c5d6e7f").unwrap()),
+            code: "4000417025005".to_string(),
+            product_name: Some("Ritter Sport".to_string()),
+            product_name_langs: None,
+            generic_name: None,
+            brands: Some(vec!["ritter-sport".to_string()]),
+            categories: None,
+            main_category: None,
+            labels: None,
+            ingredients_text: None,
+            ingredients: None,
+            traces_tags: None,
+            allergens_tags: vec!["en:milk".to_string()],
+            quantity: None,
+            image_url: None,
+            image_small_url: None,
+            image_thumb_url: None,
+            countries: None,
+            nutrition_grade_fr: Some("c".to_string()),
+            nutrition_grade_source: None,
+            ecoscore_grade: Some("b".to_string()),
+            ecoscore_score: Some(68.0),
+            nutriments: None,
+            creator: Some("api_create".to_string()),
+            source: None,
+            scan_count: None,
+            created_at: created,
+            last_modified_at: created + chrono::Duration::milliseconds(1500),
+        };
+        assert_eq!(
+            serde_json::to_value(ProductV2::from(product)).unwrap(),
+            json!({
+                "id": "663a1f2e9b1e8a3f4c5d6e7f",
+                "code": "4000417025005",
+                "name": "Ritter Sport",
+                "nameLangs": {},
+                "displayName": "Ritter Sport",
+                "genericName": null,
+                "brands": ["ritter-sport"],
+                "categories": [],
+                "mainCategory": null,
+                "labels": [],
+                "ingredientsText": null,
+                "allergens": ["en:milk"],
+                "traces": [],
+                "quantity": null,
+                "imageUrl": null,
+                "imageSmallUrl": null,
+                "countries": [],
+                "nutriscore": "c",
+                "ecoscore": "b",
+                "ecoscoreScore": 68.0,
+                "createdAt": "2024-06-01T08:00:00Z",
+                "updatedAt": "2024-06-01T08:00:01Z",
+            })
+        );
+    }
+
+    #[test]
+    fn highlights_name_the_v2_fields() {
+        let product: Product = ProductFixture::new("4000417025005")
+            .named("Ritter Sport")
+            .with_brands(["ritter-sport"])
+            .with_ingredients("Sugar, whole milk powder")
+            .build();
+        let hit = SearchHit {
+            product,
+            score: None,
+            display_name: None,
+            highlights: None,
+        };
+        let page = |hit: SearchHit| Page::new(vec![hit]);
+
+        let results = SearchResultsV2::from(SearchResults::Full(page(hit.clone())));
+        let json = serde_json::to_value(&results).unwrap();
+        assert!(json["items"][0].get("highlights").is_none());
+
+        let results = SearchResultsV2::from(SearchResults::Full(page(
+            hit.clone().highlighted("sport milk"),
+        )));
+        let json = serde_json::to_value(&results).unwrap();
+        let fields: Vec<&str> = json["items"][0]["highlights"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|highlight| highlight["field"].as_str().unwrap())
+            .collect();
+        assert_eq!(fields, ["name", "brands", "ingredientsText"]);
+
+        let summary = SearchSummary::from(hit).highlighted("sport");
+        let json = serde_json::to_value(SearchSummaryV2::from(summary)).unwrap();
+        assert_eq!(json["highlights"][0]["field"], "name");
+        assert_eq!(json["highlights"][0]["snippet"], "Ritter <em>Sport</em>");
+    }
+
+    #[test]
+    fn recommended_product_carries_its_score_beside_the_product() {
+        let product: Product = ProductFixture::new("123").build();
+        let value = serde_json::to_value(RecommendedProductV2::from(RecommendedProduct {
+            product,
+            score: 0.5,
+        }))
+        .unwrap();
+        assert_eq!(value["code"], "123");
+        assert_eq!(value["score"], 0.5);
+    }
+}
@@ -0,0 +1,113 @@
+//! Indexes products in Qdrant as the API creates and updates them, so they can be
+//! recommended right away rather than once the catalog-sync-worker's change stream gets
+//! to them. The points are the worker's: the same id, embedding text and payload, built
+//! by the shared [`ProductDoc`], so whichever writes last writes the same point.
+//!
+//! Indexing runs in a task tracked by [`AppState::tasks`] and never holds up the response,
+//! though a shutdown waits for it. A failure is retried once, then logged, leaving the
+//! product to the worker. Nothing is indexed without
+//! Qdrant or an embedding service.
+
+use crate::{
+    catalog_metrics::observe_qdrant, errors::Result, handlers::QDRANT_COLLECTION_NAME,
+    models::Product, semantic::embed, state::AppState,
+};
+use qdrant_client::{
+    Qdrant,
+    qdrant::{PointStruct, UpsertPointsBuilder},
+};
+use std::{sync::Arc, time::Duration};
+use tracing::{Instrument, debug, error, warn};
+use yoloeats_domain::point::{ProductDoc, point_id};
+use yoloeats_tracing::{current_request_id, with_request_id};
+
+/// Wait before the one retry of a failed indexing.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Indexes `product` in the background, as it is now.
+pub fn spawn_index(state: &Arc<AppState>, product: &Product) {
+    let (Some(clients), Some(_)) = (&state.clients, &state.embeddings) else {
+        debug!("No Qdrant or embedding service; leaving product indexing to the sync worker.");
+        return;
+    };
+    let Some(doc) = product_doc(product) else {
+        warn!("Not indexing product {} without an id", product.code);
+        return;
+    };
+    let tasks = state.tasks.clone();
+    let state = state.clone();
+    let qdrant = clients.qdrant_client.clone();
+    let task = async move {
+        if let Err(e) = index(&state, &qdrant, &doc).await {
+            warn!("Indexing product {} failed, retrying once: {}", doc.id, e);
+            tokio::time::sleep(RETRY_DELAY).await;
+            if let Err(e) = index(&state, &qdrant, &doc).await {
+                error!(
+                    "Could not index product {}; leaving it to the sync worker: {}",
+                    doc.id, e
+                );
+            }
+        }
+    }
+    .in_current_span();
+    match current_request_id() {
+        Some(request_id) => tasks.spawn(with_request_id(request_id, task)),
+        None => tasks.spawn(task),
+    };
+}
+
+async fn index(state: &AppState, qdrant: &Qdrant, product: &ProductDoc) -> Result<()> {
+    let vector = embed(state, &product.embedding_text()).await?;
+    let point = PointStruct::new(point_id(&product.id), vector, product.payload());
+    observe_qdrant(
+        "upsert_points",
+        qdrant.upsert_points(
+            UpsertPointsBuilder::new(QDRANT_COLLECTION_NAME, vec![point]).wait(true),
+        ),
+    )
+    .await?;
+    debug!("Indexed product {} in Qdrant", product.id);
+    Ok(())
+}
+
+/// What the worker reads of a stored product; `None` for one not stored yet.
+fn product_doc(product: &Product) -> Option<ProductDoc> {
+    Some(ProductDoc {
+        id: product.id?,
+        code: product.code.clone(),
+        product_name: product.product_name.clone(),
+        generic_name: product.generic_name.clone(),
+        ingredients_text: product.ingredients_text.clone(),
+        categories_tags: product.categories.clone(),
+        brands_tags: product.brands.clone(),
+        labels_tags: product.labels.clone(),
+        traces_tags: product.traces_tags.clone(),
+        allergens_tags: Some(product.allergens_tags.clone()),
+        countries_tags: product.countries.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yoloeats_domain::fixtures::ProductFixture;
+
+    #[test]
+    fn stored_products_read_as_the_worker_reads_them() {
+        let product: Product = ProductFixture::new("4000417025005")
+            .named("Alpine milk chocolate")
+            .with_allergens(["en:milk"])
+            .build();
+        let doc = product_doc(&product).unwrap();
+        // The worker's own reading of the stored document.
+        let stored = bson::to_document(&product).unwrap();
+        assert_eq!(ProductDoc::from_document(stored).unwrap(), doc);
+        assert!(doc.embedding_text().contains("Alpine milk chocolate"));
+
+        let unsaved = Product {
+            id: None,
+            ..product
+        };
+        assert!(product_doc(&unsaved).is_none());
+    }
+}
@@ -0,0 +1,850 @@
+//! Outbound webhooks: partners register a URL under `/api/v1/admin/webhooks` and are
+//! sent an event for every product the API creates, updates or deletes.
+//!
+//! Subscriptions live in the `webhook_subscriptions` collection. Each event is a JSON
+//! [`WebhookEvent`] POSTed with its HMAC-SHA256 under the subscription's secret in
+//! [`SIGNATURE_HEADER`], so receivers can tell it came from us. Delivery runs in a task
+//! tracked by [`AppState::tasks`] and never holds up the response. Network errors, 5xx
+//! and 429 answers are retried with exponential backoff, up to
+//! [`DeliveryPolicy::max_attempts`]; other answers are final. The outcome is recorded on
+//! the subscription, and a delivery that gave up counts in its `failed_deliveries`.
+//! Bulk imports send no events.
+
+use crate::{
+    errors::{Result, ServiceError},
+    models::Product,
+    state::AppState,
+};
+use async_trait::async_trait;
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::{StatusCode, header::CONTENT_TYPE},
+};
+use bson::{Document, doc, oid::ObjectId};
+use chrono::{DateTime, Utc};
+use futures::{future::join_all, stream::TryStreamExt};
+use hmac::{Hmac, Mac};
+use mongodb::{Collection, Database};
+use reqwest::Client as HttpClient;
+use rust_database_clients::serde_helpers::chrono_datetime_as_rfc3339_or_bson;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{Instrument, debug, error, info, instrument, warn};
+use utoipa::ToSchema;
+use validator::Validate;
+use yoloeats_domain::ErrorBody;
+use yoloeats_metrics::ValidJson;
+use yoloeats_tracing::{current_request_id, with_request_id};
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const WEBHOOKS_COLLECTION: &str = "webhook_subscriptions";
+
+/// Carries `sha256=<hex>`, the HMAC-SHA256 of the request body under the subscription's
+/// secret.
+pub const SIGNATURE_HEADER: &str = "x-signature";
+
+/// Carries the event's [`EventType`], so receivers can route without parsing the body.
+pub const EVENT_HEADER: &str = "x-webhook-event";
+
+/// What happened to a product.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+pub enum EventType {
+    #[serde(rename = "product.created")]
+    ProductCreated,
+    #[serde(rename = "product.updated")]
+    ProductUpdated,
+    #[serde(rename = "product.deleted")]
+    ProductDeleted,
+}
+
+impl EventType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventType::ProductCreated => "product.created",
+            EventType::ProductUpdated => "product.updated",
+            EventType::ProductDeleted => "product.deleted",
+        }
+    }
+}
+
+/// The body of a webhook request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct WebhookEvent {
+    /// Unique per event, for receivers to drop the duplicates retries may bring.
+    pub id: String,
+    #[serde(rename = "type")]
+    pub event_type: EventType,
+    #[serde(with = "chrono_datetime_as_rfc3339_or_bson")]
+    pub occurred_at: DateTime<Utc>,
+    /// The product's ObjectId.
+    pub product_id: String,
+    pub code: String,
+    /// The product as it now is; `null` for deletes.
+    pub product: Option<Product>,
+}
+
+/// How one event went to one subscription, after its retries.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DeliveryAttempt {
+    #[serde(with = "chrono_datetime_as_rfc3339_or_bson")]
+    pub at: DateTime<Utc>,
+    pub event_id: String,
+    pub event_type: EventType,
+    /// Requests sent, retries included.
+    pub attempts: u32,
+    /// The HTTP status of the last request; `null` when it got no answer.
+    pub status: Option<u16>,
+    /// Why the delivery failed; `null` when it succeeded.
+    pub error: Option<String>,
+}
+
+/// A stored subscription. Its secret is never served back.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Subscription {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<EventType>,
+    #[serde(with = "chrono_datetime_as_rfc3339_or_bson")]
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub last_delivery: Option<DeliveryAttempt>,
+    #[serde(default)]
+    pub last_failure: Option<DeliveryAttempt>,
+    #[serde(default)]
+    pub failed_deliveries: u64,
+}
+
+/// A subscription as the admin API serves it, without its secret.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct WebhookSubscription {
+    /// The subscription's ObjectId.
+    pub id: String,
+    pub url: String,
+    pub events: Vec<EventType>,
+    #[serde(with = "chrono_datetime_as_rfc3339_or_bson")]
+    pub created_at: DateTime<Utc>,
+    pub last_delivery: Option<DeliveryAttempt>,
+    pub last_failure: Option<DeliveryAttempt>,
+    /// Deliveries that gave up, in all.
+    pub failed_deliveries: u64,
+}
+
+impl From<Subscription> for WebhookSubscription {
+    fn from(subscription: Subscription) -> Self {
+        WebhookSubscription {
+            id: subscription.id.to_hex(),
+            url: subscription.url,
+            events: subscription.events,
+            created_at: subscription.created_at,
+            last_delivery: subscription.last_delivery,
+            last_failure: subscription.last_failure,
+            failed_deliveries: subscription.failed_deliveries,
+        }
+    }
+}
+
+/// Body of `POST /api/v1/admin/webhooks`.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateWebhookPayload {
+    /// Where events are POSTed; http or https.
+    #[validate(url(message = "Webhook URL must be a valid URL"))]
+    pub url: String,
+    /// Signs the events; 16 to 256 characters.
+    #[validate(length(min = 16, max = 256, message = "Secret must be 16-256 characters"))]
+    pub secret: String,
+    /// The events to send.
+    #[validate(length(min = 1, message = "At least one event type"))]
+    pub events: Vec<EventType>,
+}
+
+/// How hard a delivery tries.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeliveryPolicy {
+    /// Requests sent before giving up, the first included.
+    pub max_attempts: u32,
+    /// Wait before the first retry; each later one waits twice as long as the last.
+    pub initial_backoff: Duration,
+    /// How long one request may take.
+    pub timeout: Duration,
+}
+
+impl Default for DeliveryPolicy {
+    /// Five requests over about fifteen seconds.
+    fn default() -> Self {
+        DeliveryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(1),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+#[async_trait]
+pub trait WebhookStore: Send + Sync {
+    async fn insert(&self, subscription: Subscription) -> Result<()>;
+
+    /// Every subscription, oldest first.
+    async fn list(&self) -> Result<Vec<Subscription>>;
+
+    /// Whether there was a subscription with this id.
+    async fn delete(&self, id: ObjectId) -> Result<bool>;
+
+    /// The subscriptions that take `event_type`.
+    async fn subscribed_to(&self, event_type: EventType) -> Result<Vec<Subscription>>;
+
+    /// Keeps `attempt` as the subscription's last delivery, and as its last failure if
+    /// it failed. A deleted subscription is left deleted.
+    async fn record_delivery(&self, id: ObjectId, attempt: DeliveryAttempt) -> Result<()>;
+}
+
+pub struct MongoWebhookStore {
+    collection: Collection<Subscription>,
+}
+
+impl MongoWebhookStore {
+    pub fn new(db: &Database) -> Self {
+        MongoWebhookStore {
+            collection: db.collection(WEBHOOKS_COLLECTION),
+        }
+    }
+}
+
+#[async_trait]
+impl WebhookStore for MongoWebhookStore {
+    async fn insert(&self, subscription: Subscription) -> Result<()> {
+        self.collection.insert_one(&subscription).await?;
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<Subscription>> {
+        let cursor = self
+            .collection
+            .find(doc! {})
+            .sort(doc! { "_id": 1 })
+            .await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn delete(&self, id: ObjectId) -> Result<bool> {
+        let result = self.collection.delete_one(doc! { "_id": id }).await?;
+        Ok(result.deleted_count > 0)
+    }
+
+    async fn subscribed_to(&self, event_type: EventType) -> Result<Vec<Subscription>> {
+        let cursor = self
+            .collection
+            .find(doc! { "events": event_type.as_str() })
+            .await
+            .map_err(|e| {
+                error!("MongoDB find on {} failed: {}", WEBHOOKS_COLLECTION, e);
+                ServiceError::MongoDb(e)
+            })?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn record_delivery(&self, id: ObjectId, attempt: DeliveryAttempt) -> Result<()> {
+        // Through BSON bytes, as the driver serializes, so `at` is stored as a date.
+        let recorded: Document = bson::from_slice(&bson::to_vec(&attempt)?)?;
+        let update = if attempt.error.is_some() {
+            doc! {
+                "$set": { "last_delivery": recorded.clone(), "last_failure": recorded },
+                "$inc": { "failed_deliveries": 1_i64 },
+            }
+        } else {
+            doc! { "$set": { "last_delivery": recorded } }
+        };
+        self.collection
+            .update_one(doc! { "_id": id }, update)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Subscriptions in registration order. Clones share the same list.
+#[derive(Clone, Default)]
+pub struct MemoryWebhookStore {
+    subscriptions: Arc<Mutex<Vec<Subscription>>>,
+}
+
+#[async_trait]
+impl WebhookStore for MemoryWebhookStore {
+    async fn insert(&self, subscription: Subscription) -> Result<()> {
+        self.subscriptions.lock().unwrap().push(subscription);
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<Subscription>> {
+        Ok(self.subscriptions.lock().unwrap().clone())
+    }
+
+    async fn delete(&self, id: ObjectId) -> Result<bool> {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let before = subscriptions.len();
+        subscriptions.retain(|subscription| subscription.id != id);
+        Ok(subscriptions.len() < before)
+    }
+
+    async fn subscribed_to(&self, event_type: EventType) -> Result<Vec<Subscription>> {
+        let subscriptions = self.subscriptions.lock().unwrap();
+        Ok(subscriptions
+            .iter()
+            .filter(|subscription| subscription.events.contains(&event_type))
+            .cloned()
+            .collect())
+    }
+
+    async fn record_delivery(&self, id: ObjectId, attempt: DeliveryAttempt) -> Result<()> {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        if let Some(subscription) = subscriptions.iter_mut().find(|s| s.id == id) {
+            if attempt.error.is_some() {
+                subscription.last_failure = Some(attempt.clone());
+                subscription.failed_deliveries += 1;
+            }
+            subscription.last_delivery = Some(attempt);
+        }
+        Ok(())
+    }
+}
+
+/// The subscriptions and how they are delivered to.
+#[derive(Clone)]
+pub struct Webhooks {
+    pub store: Arc<dyn WebhookStore>,
+    pub policy: DeliveryPolicy,
+}
+
+impl Webhooks {
+    pub fn new(store: Arc<dyn WebhookStore>) -> Self {
+        Webhooks {
+            store,
+            policy: DeliveryPolicy::default(),
+        }
+    }
+
+    pub fn with_policy(self, policy: DeliveryPolicy) -> Self {
+        Webhooks { policy, ..self }
+    }
+}
+
+/// The [`SIGNATURE_HEADER`] of `body` under `secret`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    let digest: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", digest)
+}
+
+/// Sends `event`, serialized as `body`, to the subscription, retrying as `policy` says.
+pub async fn deliver(
+    client: &HttpClient,
+    subscription: &Subscription,
+    event: &WebhookEvent,
+    body: &[u8],
+    policy: &DeliveryPolicy,
+) -> DeliveryAttempt {
+    let signature = sign(&subscription.secret, body);
+    let mut backoff = policy.initial_backoff;
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let sent = client
+            .post(&subscription.url)
+            .timeout(policy.timeout)
+            .header(CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, &signature)
+            .header(EVENT_HEADER, event.event_type.as_str())
+            .body(body.to_vec())
+            .send()
+            .await;
+        let (status, error, retryable) = match sent {
+            Ok(response) if response.status().is_success() => {
+                (Some(response.status().as_u16()), None, false)
+            }
+            Ok(response) => {
+                let status = response.status();
+                (
+                    Some(status.as_u16()),
+                    Some(format!("Answered {}", status)),
+                    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS,
+                )
+            }
+            Err(e) => (None, Some(e.to_string()), true),
+        };
+        match error {
+            Some(error) if retryable && attempts < policy.max_attempts => {
+                warn!(
+                    subscription = %subscription.id,
+                    event = %event.id,
+                    "Webhook delivery failed ({}); retrying in {:?}",
+                    error,
+                    backoff
+                );
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            error => {
+                return DeliveryAttempt {
+                    at: Utc::now(),
+                    event_id: event.id.clone(),
+                    event_type: event.event_type,
+                    attempts,
+                    status,
+                    error,
+                };
+            }
+        }
+    }
+}
+
+/// Sends `event_type` for `product`, as it is now or as it was before its delete, to
+/// every subscription that takes it, in the background.
+pub(crate) fn notify(state: &Arc<AppState>, event_type: EventType, product: &Product) {
+    let Some(product_id) = product.id else {
+        warn!(code = %product.code, "Not sending webhooks for a product without an id");
+        return;
+    };
+    let event = WebhookEvent {
+        id: ObjectId::new().to_hex(),
+        event_type,
+        occurred_at: Utc::now(),
+        product_id: product_id.to_hex(),
+        code: product.code.clone(),
+        product: (event_type != EventType::ProductDeleted).then(|| product.clone()),
+    };
+    let tasks = state.tasks.clone();
+    let state = state.clone();
+    let task = async move { dispatch(&state, &event).await }.in_current_span();
+    match current_request_id() {
+        Some(request_id) => tasks.spawn(with_request_id(request_id, task)),
+        None => tasks.spawn(task),
+    };
+}
+
+async fn dispatch(state: &AppState, event: &WebhookEvent) {
+    let webhooks = &state.webhooks;
+    let subscriptions = match webhooks.store.subscribed_to(event.event_type).await {
+        Ok(subscriptions) => subscriptions,
+        Err(e) => {
+            error!(event = %event.id, "Could not look up webhook subscriptions: {}", e);
+            return;
+        }
+    };
+    if subscriptions.is_empty() {
+        debug!("No webhook subscriptions for {}", event.event_type.as_str());
+        return;
+    }
+    let body = match serde_json::to_vec(event) {
+        Ok(body) => body,
+        Err(e) => {
+            error!(event = %event.id, "Failed to serialize webhook event: {}", e);
+            return;
+        }
+    };
+    let deliveries = subscriptions.iter().map(|subscription| async {
+        let attempt = deliver(
+            &state.http_client,
+            subscription,
+            event,
+            &body,
+            &webhooks.policy,
+        )
+        .await;
+        match &attempt.error {
+            None => debug!(subscription = %subscription.id, event = %event.id, "Delivered webhook"),
+            Some(e) => error!(
+                subscription = %subscription.id,
+                event = %event.id,
+                "Gave up on webhook delivery after {} attempts: {}",
+                attempt.attempts,
+                e
+            ),
+        }
+        if let Err(e) = webhooks
+            .store
+            .record_delivery(subscription.id, attempt)
+            .await
+        {
+            warn!(subscription = %subscription.id, "Failed to record webhook delivery: {}", e);
+        }
+    });
+    join_all(deliveries).await;
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/webhooks",
+    tag = "admin",
+    params(
+        ("X-Internal-Token" = String, Header, description = "The shared secret of internal callers."),
+    ),
+    request_body = CreateWebhookPayload,
+    responses(
+        (status = 201, description = "The subscription, without its secret.", body = WebhookSubscription),
+        (status = 400, description = "A URL that is not http or https, or a body that is not JSON.", body = ErrorBody),
+        (status = 401, description = "No valid internal token.", body = ErrorBody),
+        (status = 422, description = "An invalid URL, secret or event type.", body = ErrorBody),
+        (status = 500, description = "MongoDB failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, payload), fields(url = %payload.url))]
+pub async fn create_webhook(
+    State(state): State<Arc<AppState>>,
+    ValidJson(payload): ValidJson<CreateWebhookPayload>,
+) -> Result<(StatusCode, Json<WebhookSubscription>)> {
+    if !(payload.url.starts_with("http://") || payload.url.starts_with("https://")) {
+        return Err(ServiceError::BadRequest(format!(
+            "Webhook URL must be http or https: {}",
+            payload.url
+        )));
+    }
+    let events: BTreeSet<EventType> = payload.events.into_iter().collect();
+    let subscription = Subscription {
+        id: ObjectId::new(),
+        url: payload.url,
+        secret: payload.secret,
+        events: events.into_iter().collect(),
+        created_at: Utc::now(),
+        last_delivery: None,
+        last_failure: None,
+        failed_deliveries: 0,
+    };
+    state.webhooks.store.insert(subscription.clone()).await?;
+    info!(id = %subscription.id, "Registered webhook subscription");
+    Ok((StatusCode::CREATED, Json(subscription.into())))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/webhooks",
+    tag = "admin",
+    params(
+        ("X-Internal-Token" = String, Header, description = "The shared secret of internal callers."),
+    ),
+    responses(
+        (status = 200, description = "Every subscription, oldest first, with how its last deliveries went.", body = Vec<WebhookSubscription>),
+        (status = 401, description = "No valid internal token.", body = ErrorBody),
+        (status = 500, description = "MongoDB failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state))]
+pub async fn list_webhooks(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<WebhookSubscription>>> {
+    let subscriptions = state.webhooks.store.list().await?;
+    info!("Returning {} webhook subscriptions", subscriptions.len());
+    Ok(Json(subscriptions.into_iter().map(Into::into).collect()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/webhooks/{id}",
+    tag = "admin",
+    params(
+        ("id" = String, Path, description = "The subscription's ObjectId."),
+        ("X-Internal-Token" = String, Header, description = "The shared secret of internal callers."),
+    ),
+    responses(
+        (status = 204, description = "The subscription is gone; events already on their way are still delivered."),
+        (status = 400, description = "An invalid id.", body = ErrorBody),
+        (status = 401, description = "No valid internal token.", body = ErrorBody),
+        (status = 404, description = "No subscription with this id.", body = ErrorBody),
+        (status = 500, description = "MongoDB failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state), fields(id = %id_str))]
+pub async fn delete_webhook(
+    State(state): State<Arc<AppState>>,
+    Path(id_str): Path<String>,
+) -> Result<StatusCode> {
+    let id = ObjectId::parse_str(&id_str).map_err(|_| {
+        ServiceError::BadRequest(format!("Invalid webhook subscription ID: {}", id_str))
+    })?;
+    if !state.webhooks.store.delete(id).await? {
+        return Err(ServiceError::NotFound(format!(
+            "Webhook subscription {} not found",
+            id
+        )));
+    }
+    info!(id = %id, "Deleted webhook subscription");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Bytes, http::HeaderMap, routing::post};
+    use std::collections::VecDeque;
+    use tokio::net::TcpListener;
+    use yoloeats_domain::fixtures::ProductFixture;
+
+    const SECRET: &str = "receiver-shared-secret";
+
+    /// A receiver answering with `statuses` in turn, then 200, and keeping what it got.
+    #[derive(Clone, Default)]
+    struct Receiver {
+        statuses: Arc<Mutex<VecDeque<StatusCode>>>,
+        received: Arc<Mutex<Vec<(HeaderMap, Bytes)>>>,
+    }
+
+    impl Receiver {
+        async fn start(statuses: impl IntoIterator<Item = StatusCode>) -> (Self, String) {
+            let receiver = Receiver {
+                statuses: Arc::new(Mutex::new(statuses.into_iter().collect())),
+                ..Default::default()
+            };
+            let state = receiver.clone();
+            let app = Router::new().route(
+                "/hook",
+                post(move |headers: HeaderMap, body: Bytes| {
+                    let state = state.clone();
+                    async move {
+                        state.received.lock().unwrap().push((headers, body));
+                        let next = state.statuses.lock().unwrap().pop_front();
+                        next.unwrap_or(StatusCode::OK)
+                    }
+                }),
+            );
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/hook", listener.local_addr().unwrap());
+            tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+            (receiver, url)
+        }
+
+        fn received(&self) -> Vec<(HeaderMap, Bytes)> {
+            self.received.lock().unwrap().clone()
+        }
+    }
+
+    fn subscription(url: &str, events: &[EventType]) -> Subscription {
+        Subscription {
+            id: ObjectId::new(),
+            url: url.to_string(),
+            secret: SECRET.to_string(),
+            events: events.to_vec(),
+            created_at: Utc::now(),
+            last_delivery: None,
+            last_failure: None,
+            failed_deliveries: 0,
+        }
+    }
+
+    fn event(event_type: EventType) -> WebhookEvent {
+        let product: Product = ProductFixture::new("4000417025005")
+            .named("Alpine milk chocolate")
+            .build();
+        WebhookEvent {
+            id: ObjectId::new().to_hex(),
+            event_type,
+            occurred_at: Utc::now(),
+            product_id: ObjectId::new().to_hex(),
+            code: product.code.clone(),
+            product: Some(product),
+        }
+    }
+
+    fn fast_policy(max_attempts: u32) -> DeliveryPolicy {
+        DeliveryPolicy {
+            max_attempts,
+            initial_backoff: Duration::from_millis(5),
+            timeout: Duration::from_secs(2),
+        }
+    }
+
+    #[test]
+    fn signatures_are_hex_hmac_sha256() {
+        // RFC 4231, test case 2.
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_ne!(sign("other", b"body"), sign(SECRET, b"body"));
+    }
+
+    #[test]
+    fn events_are_named_by_their_dotted_type() {
+        let json = serde_json::to_value(event(EventType::ProductUpdated)).unwrap();
+        assert_eq!(json["type"], "product.updated");
+        assert_eq!(json["code"], "4000417025005");
+        assert_eq!(json["product"]["product_name"], "Alpine milk chocolate");
+        assert!(json["occurred_at"].is_string());
+        for event_type in [
+            EventType::ProductCreated,
+            EventType::ProductUpdated,
+            EventType::ProductDeleted,
+        ] {
+            assert_eq!(
+                serde_json::to_value(event_type).unwrap(),
+                event_type.as_str()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn deliveries_are_signed_json_events() {
+        let (receiver, url) = Receiver::start([]).await;
+        let subscription = subscription(&url, &[EventType::ProductCreated]);
+        let event = event(EventType::ProductCreated);
+        let body = serde_json::to_vec(&event).unwrap();
+
+        let attempt = deliver(
+            &HttpClient::new(),
+            &subscription,
+            &event,
+            &body,
+            &fast_policy(3),
+        )
+        .await;
+        assert_eq!(attempt.attempts, 1);
+        assert_eq!(attempt.status, Some(200));
+        assert_eq!(attempt.error, None);
+        assert_eq!(attempt.event_id, event.id);
+
+        let received = receiver.received();
+        assert_eq!(received.len(), 1);
+        let (headers, body) = &received[0];
+        assert_eq!(headers[CONTENT_TYPE], "application/json");
+        assert_eq!(headers[EVENT_HEADER], "product.created");
+        assert_eq!(headers[SIGNATURE_HEADER], sign(SECRET, body).as_str());
+        let sent: WebhookEvent = serde_json::from_slice(body).unwrap();
+        assert_eq!(sent.id, event.id);
+        assert_eq!(sent.event_type, EventType::ProductCreated);
+        assert_eq!(sent.product.unwrap().code, "4000417025005");
+    }
+
+    #[tokio::test]
+    async fn server_errors_are_retried_until_delivered() {
+        let (receiver, url) = Receiver::start([
+            StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::TOO_MANY_REQUESTS,
+        ])
+        .await;
+        let subscription = subscription(&url, &[EventType::ProductUpdated]);
+        let event = event(EventType::ProductUpdated);
+        let body = serde_json::to_vec(&event).unwrap();
+
+        let attempt = deliver(
+            &HttpClient::new(),
+            &subscription,
+            &event,
+            &body,
+            &fast_policy(5),
+        )
+        .await;
+        assert_eq!(attempt.attempts, 3);
+        assert_eq!(attempt.status, Some(200));
+        assert_eq!(attempt.error, None);
+        // Every retry is the same signed event.
+        let received = receiver.received();
+        assert_eq!(received.len(), 3);
+        assert!(received.iter().all(|(headers, sent)| {
+            sent[..] == body[..] && headers[SIGNATURE_HEADER] == sign(SECRET, &body).as_str()
+        }));
+    }
+
+    #[tokio::test]
+    async fn deliveries_give_up_after_max_attempts_or_a_client_error() {
+        let (receiver, url) = Receiver::start([StatusCode::SERVICE_UNAVAILABLE; 5]).await;
+        let event = event(EventType::ProductDeleted);
+        let body = serde_json::to_vec(&event).unwrap();
+        let attempt = deliver(
+            &HttpClient::new(),
+            &subscription(&url, &[EventType::ProductDeleted]),
+            &event,
+            &body,
+            &fast_policy(3),
+        )
+        .await;
+        assert_eq!(attempt.attempts, 3);
+        assert_eq!(attempt.status, Some(503));
+        assert!(attempt.error.unwrap().contains("503"));
+        assert_eq!(receiver.received().len(), 3);
+
+        let (receiver, url) = Receiver::start([StatusCode::GONE]).await;
+        let attempt = deliver(
+            &HttpClient::new(),
+            &subscription(&url, &[EventType::ProductDeleted]),
+            &event,
+            &body,
+            &fast_policy(3),
+        )
+        .await;
+        assert_eq!(attempt.attempts, 1);
+        assert_eq!(attempt.status, Some(410));
+        assert_eq!(receiver.received().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn memory_store_keeps_failures_on_the_subscription() {
+        let store = MemoryWebhookStore::default();
+        let created = subscription("http://a.example/hook", &[EventType::ProductCreated]);
+        let deleted = subscription("http://b.example/hook", &[EventType::ProductDeleted]);
+        store.insert(created.clone()).await.unwrap();
+        store.insert(deleted.clone()).await.unwrap();
+
+        let subscribed = store
+            .subscribed_to(EventType::ProductDeleted)
+            .await
+            .unwrap();
+        assert_eq!(subscribed, std::slice::from_ref(&deleted));
+        assert!(
+            store
+                .subscribed_to(EventType::ProductUpdated)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let attempt = |error: Option<&str>| DeliveryAttempt {
+            at: Utc::now(),
+            event_id: ObjectId::new().to_hex(),
+            event_type: EventType::ProductDeleted,
+            attempts: 5,
+            status: None,
+            error: error.map(str::to_string),
+        };
+        let failed = attempt(Some("connection refused"));
+        store
+            .record_delivery(deleted.id, failed.clone())
+            .await
+            .unwrap();
+        let delivered = attempt(None);
+        store
+            .record_delivery(deleted.id, delivered.clone())
+            .await
+            .unwrap();
+
+        let listed = store.list().await.unwrap();
+        assert_eq!(listed[0], created);
+        assert_eq!(listed[1].last_delivery, Some(delivered));
+        assert_eq!(listed[1].last_failure, Some(failed));
+        assert_eq!(listed[1].failed_deliveries, 1);
+
+        assert!(store.delete(created.id).await.unwrap());
+        assert!(!store.delete(created.id).await.unwrap());
+        assert_eq!(store.list().await.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn served_subscriptions_leave_out_the_secret() {
+        let served: WebhookSubscription =
+            subscription("https://partner.example/hook", &[EventType::ProductCreated]).into();
+        let json = serde_json::to_value(served).unwrap();
+        assert!(json.get("secret").is_none());
+        assert_eq!(json["events"], serde_json::json!(["product.created"]));
+    }
+}
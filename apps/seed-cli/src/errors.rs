@@ -0,0 +1,15 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SeedError {
+    #[error("MongoDB error: {0}")]
+    Mongo(#[from] mongodb::error::Error),
+
+    #[error("Neo4j error: {0}")]
+    Neo4j(#[from] neo4rs::Error),
+
+    #[error("Unexpected Neo4j result: {0}")]
+    Neo4jRow(#[from] neo4rs::DeError),
+}
+
+pub type Result<T, E = SeedError> = std::result::Result<T, E>;
@@ -0,0 +1,321 @@
+//! Deterministic fixture data. The same arguments always produce the same documents, so
+//! reseeding is a no-op and scenarios can rely on known barcodes.
+
+use chrono::{DateTime, TimeZone, Utc};
+use product_catalog_service::models::Product;
+use user_profile_service::models::{RiskLevel, UserProfile};
+
+pub use yoloeats_domain::ingredient_graph::{
+    INGREDIENT_ALLERGENS, INGREDIENT_DIET_CONFLICTS, TRACE_ALLERGENS,
+};
+
+/// GS1 prefix 400 (Germany); the next nine digits are the fixture index.
+const BARCODE_PREFIX: &str = "400";
+
+pub const DEFAULT_PRODUCT_COUNT: usize = 20;
+pub const DEFAULT_USERS: &[&str] = &["alice", "bob", "carol"];
+
+struct ProductTemplate {
+    name: &'static str,
+    brand: &'static str,
+    category: &'static str,
+    /// Comma-separated and lowercase, the way the checker splits ingredient text.
+    ingredients: &'static str,
+    allergens: &'static [&'static str],
+    traces: &'static [&'static str],
+    labels: &'static [&'static str],
+    quantity: &'static str,
+    nutrition_grade: &'static str,
+}
+
+const TEMPLATES: &[ProductTemplate] = &[
+    ProductTemplate {
+        name: "Alpine milk chocolate",
+        brand: "alpenglueck",
+        category: "en:chocolates",
+        ingredients: "sugar, cocoa butter, whole milk powder, cocoa mass, hazelnuts",
+        allergens: &["en:milk", "en:nuts"],
+        traces: &["en:peanuts"],
+        labels: &["en:vegetarian"],
+        quantity: "100 g",
+        nutrition_grade: "e",
+    },
+    ProductTemplate {
+        name: "Crunchy peanut butter",
+        brand: "nussmuehle",
+        category: "en:peanut-butters",
+        ingredients: "roasted peanuts, salt",
+        allergens: &["en:peanuts"],
+        traces: &[],
+        labels: &["en:vegan"],
+        quantity: "350 g",
+        nutrition_grade: "c",
+    },
+    ProductTemplate {
+        name: "Whole wheat bread",
+        brand: "backstube",
+        category: "en:breads",
+        ingredients: "whole wheat flour, water, yeast, salt, sunflower seeds",
+        allergens: &["en:gluten"],
+        traces: &["en:sesame-seeds"],
+        labels: &["en:vegan"],
+        quantity: "500 g",
+        nutrition_grade: "a",
+    },
+    ProductTemplate {
+        name: "Oat drink",
+        brand: "haferhof",
+        category: "en:plant-based-milk-alternatives",
+        ingredients: "water, oats, rapeseed oil, salt",
+        allergens: &["en:gluten"],
+        traces: &[],
+        labels: &["en:vegan"],
+        quantity: "1 l",
+        nutrition_grade: "b",
+    },
+    ProductTemplate {
+        name: "Natural yoghurt",
+        brand: "weidegruen",
+        category: "en:yogurts",
+        ingredients: "milk, live cultures",
+        allergens: &["en:milk"],
+        traces: &[],
+        labels: &["en:vegetarian"],
+        quantity: "500 g",
+        nutrition_grade: "a",
+    },
+    ProductTemplate {
+        name: "Classic hummus",
+        brand: "levante",
+        category: "en:spreads",
+        ingredients: "chickpeas, tahini, lemon juice, garlic, salt",
+        allergens: &["en:sesame-seeds"],
+        traces: &[],
+        labels: &["en:vegan"],
+        quantity: "200 g",
+        nutrition_grade: "b",
+    },
+    ProductTemplate {
+        name: "Fish fingers",
+        brand: "nordkutter",
+        category: "en:frozen-fish",
+        ingredients: "cod, wheat flour, sunflower oil, water, salt",
+        allergens: &["en:fish", "en:gluten"],
+        traces: &[],
+        labels: &[],
+        quantity: "450 g",
+        nutrition_grade: "b",
+    },
+    ProductTemplate {
+        name: "Egg noodles",
+        brand: "schwabenteig",
+        category: "en:pastas",
+        ingredients: "durum wheat semolina, eggs, water",
+        allergens: &["en:eggs", "en:gluten"],
+        traces: &["en:soybeans"],
+        labels: &["en:vegetarian"],
+        quantity: "500 g",
+        nutrition_grade: "a",
+    },
+    ProductTemplate {
+        name: "Soy sauce",
+        brand: "kikuya",
+        category: "en:sauces",
+        ingredients: "water, soybeans, wheat, salt",
+        allergens: &["en:soybeans", "en:gluten"],
+        traces: &[],
+        labels: &["en:vegan"],
+        quantity: "150 ml",
+        nutrition_grade: "e",
+    },
+    ProductTemplate {
+        name: "Cloudy apple juice",
+        brand: "obstgarten",
+        category: "en:apple-juices",
+        ingredients: "apple juice",
+        allergens: &[],
+        traces: &[],
+        labels: &["en:vegan", "en:organic"],
+        quantity: "1 l",
+        nutrition_grade: "c",
+    },
+];
+
+/// Profiles are handed out in this order, cycling: the first user is allergic to milk,
+/// so product 0 (milk chocolate) is always unsafe for them.
+const PROFILE_PRESETS: &[(&[&str], &[&str], RiskLevel)] = &[
+    (&["milk"], &[], RiskLevel::Low),
+    (&["peanuts", "nuts"], &[], RiskLevel::Medium),
+    (&["gluten"], &["vegan"], RiskLevel::Low),
+    (&[], &["vegetarian"], RiskLevel::High),
+];
+
+/// Fixed so reseeding leaves documents byte-for-byte identical.
+pub fn seed_timestamp() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()
+}
+
+/// EAN-13 barcode of fixture product `index`.
+pub fn barcode(index: usize) -> String {
+    let body = format!("{}{:09}", BARCODE_PREFIX, index);
+    let sum: u32 = body
+        .chars()
+        .map(|c| c.to_digit(10).expect("barcode body is numeric"))
+        .enumerate()
+        .map(|(i, digit)| if i % 2 == 0 { digit } else { digit * 3 })
+        .sum();
+    format!("{}{}", body, (10 - sum % 10) % 10)
+}
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
+pub fn products(count: usize) -> Vec<Product> {
+    (0..count).map(product).collect()
+}
+
+fn product(index: usize) -> Product {
+    let template = &TEMPLATES[index % TEMPLATES.len()];
+    let round = index / TEMPLATES.len();
+    let name = match round {
+        0 => template.name.to_string(),
+        n => format!("{} (variant {})", template.name, n + 1),
+    };
+    Product {
+        id: None,
+        code: barcode(index),
+        product_name: Some(name),
+        product_name_langs: None,
+        generic_name: None,
+        brands: Some(vec![template.brand.to_string()]),
+        categories: Some(vec![template.category.to_string()]),
+        main_category: Some(template.category.to_string()),
+        labels: Some(strings(template.labels)),
+        ingredients_text: Some(template.ingredients.to_string()),
+        ingredients: None,
+        traces_tags: Some(strings(template.traces)),
+        allergens_tags: strings(template.allergens),
+        quantity: Some(template.quantity.to_string()),
+        image_url: None,
+        image_small_url: None,
+        image_thumb_url: None,
+        countries: Some(vec!["en:germany".to_string()]),
+        nutrition_grade_fr: Some(template.nutrition_grade.to_string()),
+        nutrition_grade_source: None,
+        ecoscore_grade: None,
+        ecoscore_score: None,
+        nutriments: None,
+        creator: Some("seed-cli".to_string()),
+        source: Some("seed-cli".to_string()),
+        scan_count: None,
+        created_at: seed_timestamp(),
+        last_modified_at: seed_timestamp(),
+    }
+}
+
+pub fn profiles(users: &[String]) -> Vec<UserProfile> {
+    users
+        .iter()
+        .enumerate()
+        .map(|(index, user_id)| {
+            let (allergens, diets, risk) = &PROFILE_PRESETS[index % PROFILE_PRESETS.len()];
+            UserProfile {
+                id: None,
+                user_id: user_id.clone(),
+                username: Some(user_id.clone()),
+                email: Some(format!("{}@example.test", user_id)),
+                allergens: strings(allergens),
+                dietary_prefs: strings(diets),
+                risk_tolerance: risk.clone(),
+                created_at: seed_timestamp(),
+                updated_at: seed_timestamp(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn is_valid_ean13(code: &str) -> bool {
+        let digits: Vec<u32> = code.chars().filter_map(|c| c.to_digit(10)).collect();
+        digits.len() == 13
+            && digits
+                .iter()
+                .enumerate()
+                .map(|(i, d)| if i % 2 == 0 { *d } else { d * 3 })
+                .sum::<u32>()
+                % 10
+                == 0
+    }
+
+    #[test]
+    fn barcodes_are_valid_unique_ean13() {
+        assert_eq!(barcode(0), "4000000000006");
+        assert_eq!(barcode(7), "4000000000075");
+        let codes: HashSet<String> = products(250).into_iter().map(|p| p.code).collect();
+        assert_eq!(codes.len(), 250);
+        assert!(codes.iter().all(|code| is_valid_ean13(code)), "{:?}", codes);
+    }
+
+    #[test]
+    fn fixtures_are_deterministic() {
+        let first = products(12);
+        let second = products(12);
+        for (a, b) in first.iter().zip(&second) {
+            assert_eq!(a.code, b.code);
+            assert_eq!(a.product_name, b.product_name);
+            assert_eq!(a.created_at, b.created_at);
+        }
+        assert_eq!(
+            first[10].product_name.as_deref(),
+            Some("Alpine milk chocolate (variant 2)")
+        );
+    }
+
+    #[test]
+    fn every_graph_ingredient_appears_in_some_product() {
+        let ingredients: HashSet<String> = TEMPLATES
+            .iter()
+            .flat_map(|t| t.ingredients.split(',').map(|i| i.trim().to_string()))
+            .collect();
+        let traces: HashSet<&str> = TEMPLATES
+            .iter()
+            .flat_map(|t| t.traces.iter().copied())
+            .collect();
+        for (ingredient, _) in INGREDIENT_ALLERGENS.iter().chain(INGREDIENT_DIET_CONFLICTS) {
+            assert!(
+                ingredients.contains(*ingredient),
+                "unused ingredient {}",
+                ingredient
+            );
+        }
+        for (trace, _) in TRACE_ALLERGENS {
+            assert!(traces.contains(trace), "unused trace {}", trace);
+        }
+    }
+
+    #[test]
+    fn first_user_is_allergic_to_the_first_product() {
+        let profile = &profiles(&["alice".to_string()])[0];
+        let product = &products(1)[0];
+        let ingredients = product.ingredients_text.as_deref().unwrap();
+        assert!(profile.allergens.iter().any(|allergen| {
+            INGREDIENT_ALLERGENS
+                .iter()
+                .any(|(ingredient, a)| a == allergen && ingredients.contains(ingredient))
+        }));
+    }
+
+    #[test]
+    fn profiles_cycle_through_presets() {
+        let users: Vec<String> = (0..5).map(|i| format!("user-{}", i)).collect();
+        let profiles = profiles(&users);
+        assert_eq!(profiles[0].allergens, profiles[4].allergens);
+        assert_eq!(profiles[2].dietary_prefs, vec!["vegan".to_string()]);
+        assert_eq!(profiles[1].email.as_deref(), Some("user-1@example.test"));
+    }
+}
@@ -0,0 +1,17 @@
+//! Fills a local stack with fixture data: catalog products, user profiles and the
+//! ingredient graph the allergy checker walks.
+//!
+//! Fixtures are built from the services' own model structs, so they always match what
+//! the services read, and every write is an upsert keyed on a natural id (barcode,
+//! user id, node name), so seeding twice changes nothing. `main.rs` is the CLI; the
+//! library is public so the integration harness can seed containers directly.
+
+pub mod errors;
+pub mod fixtures;
+pub mod seed;
+
+pub use errors::SeedError;
+pub use fixtures::{DEFAULT_PRODUCT_COUNT, DEFAULT_USERS, barcode};
+pub use seed::{
+    CATALOG_DB, Counts, PROFILE_DB, Summary, seed_all, seed_graph, seed_products, seed_profiles,
+};
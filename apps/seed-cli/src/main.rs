@@ -0,0 +1,122 @@
+use clap::{Args, Parser, Subcommand};
+use dotenvy::dotenv;
+use mongodb::Client as MongoClient;
+use neo4rs::Graph;
+use rust_database_clients::{
+    MongoSettings, create_mongo_client_with_settings, validate_mongo_uri, validate_neo4j_uri,
+};
+use seed_cli::{
+    CATALOG_DB, DEFAULT_PRODUCT_COUNT, DEFAULT_USERS, PROFILE_DB, Summary, seed_all, seed_graph,
+    seed_products, seed_profiles,
+};
+use std::env;
+use tracing::info;
+use yoloeats_tracing::init_tracing;
+
+/// Seeds a local YoloEats stack with fixture data. Safe to rerun: every write is an upsert.
+#[derive(Parser, Debug)]
+#[command(name = "seed-cli", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Upsert fixture data into the databases named by MONGO_URI and NEO4J_URI.
+    Seed {
+        #[command(subcommand)]
+        target: SeedTarget,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SeedTarget {
+    /// Catalog products with barcodes, ingredients, allergens and categories.
+    Products(ProductArgs),
+    /// User profiles with allergens and dietary preferences.
+    Profiles(ProfileArgs),
+    /// The ingredient, allergen and diet nodes the allergy checker queries.
+    Graph,
+    /// Products, profiles and graph.
+    All {
+        #[command(flatten)]
+        products: ProductArgs,
+        #[command(flatten)]
+        profiles: ProfileArgs,
+    },
+}
+
+#[derive(Args, Debug)]
+struct ProductArgs {
+    /// Number of products; fixtures cycle through the templates with new barcodes.
+    #[arg(long, default_value_t = DEFAULT_PRODUCT_COUNT)]
+    count: usize,
+}
+
+#[derive(Args, Debug)]
+struct ProfileArgs {
+    /// Comma-separated user ids. The first one is allergic to milk.
+    #[arg(long, value_delimiter = ',', default_values_t = DEFAULT_USERS.iter().map(|u| u.to_string()))]
+    users: Vec<String>,
+}
+
+async fn mongo() -> Result<MongoClient, Box<dyn std::error::Error>> {
+    let mongo_uri = env::var("MONGO_URI").map_err(|_| "MONGO_URI must be set")?;
+    validate_mongo_uri(&mongo_uri)?;
+    let settings = MongoSettings::from_env("seed-cli")?;
+    Ok(create_mongo_client_with_settings(&mongo_uri, &settings).await?)
+}
+
+async fn neo4j() -> Result<Graph, Box<dyn std::error::Error>> {
+    let neo4j_uri = env::var("NEO4J_URI").map_err(|_| "NEO4J_URI must be set")?;
+    let neo4j_user = env::var("NEO4J_USER").unwrap_or_else(|_| "neo4j".to_string());
+    let neo4j_password = env::var("NEO4J_PASSWORD").map_err(|_| "NEO4J_PASSWORD must be set")?;
+    validate_neo4j_uri(&neo4j_uri)?;
+    Ok(Graph::new(&neo4j_uri, &neo4j_user, &neo4j_password).await?)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    dotenv().ok();
+
+    let _telemetry = init_tracing("seed-cli", env!("CARGO_PKG_VERSION"))?;
+    let Command::Seed { target } = Cli::parse().command;
+    info!("Seeding {:?}", target);
+
+    // Only connect to what the target writes, so `seed graph` works without Mongo.
+    let mut summary = Summary::default();
+    match target {
+        SeedTarget::Products(args) => {
+            let mongo = mongo().await?;
+            summary.push(
+                "products",
+                seed_products(&mongo.database(CATALOG_DB), args.count).await?,
+            );
+        }
+        SeedTarget::Profiles(args) => {
+            let mongo = mongo().await?;
+            summary.push(
+                "profiles",
+                seed_profiles(&mongo.database(PROFILE_DB), &args.users).await?,
+            );
+        }
+        SeedTarget::Graph => {
+            summary.push("graph", seed_graph(&neo4j().await?).await?);
+        }
+        SeedTarget::All { products, profiles } => {
+            let mongo = mongo().await?;
+            summary = seed_all(
+                &mongo.database(CATALOG_DB),
+                &mongo.database(PROFILE_DB),
+                &neo4j().await?,
+                products.count,
+                &profiles.users,
+            )
+            .await?;
+        }
+    }
+
+    print!("{}", summary);
+    Ok(())
+}
@@ -0,0 +1,254 @@
+use crate::errors::Result;
+use crate::fixtures::{self, INGREDIENT_ALLERGENS, INGREDIENT_DIET_CONFLICTS, TRACE_ALLERGENS};
+use bson::doc;
+use mongodb::{Database, results::UpdateResult};
+use neo4rs::{Graph, query};
+use product_catalog_service::models::Product;
+use std::fmt;
+use tracing::{debug, info};
+use user_profile_service::models::UserProfile;
+
+/// Databases and collections the services read; the names are fixed in their handlers.
+pub const CATALOG_DB: &str = "openfoods";
+pub const PRODUCTS_COLLECTION: &str = "products";
+pub const PROFILE_DB: &str = "yoloeats_user_profile";
+pub const PROFILES_COLLECTION: &str = "user_profiles";
+
+/// What an upsert run did to one kind of record.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Counts {
+    pub inserted: u64,
+    pub updated: u64,
+    pub unchanged: u64,
+}
+
+impl Counts {
+    fn record(&mut self, result: &UpdateResult) {
+        self.count(result.upserted_id.is_some(), result.modified_count > 0);
+    }
+
+    fn count(&mut self, inserted: bool, modified: bool) {
+        if inserted {
+            self.inserted += 1;
+        } else if modified {
+            self.updated += 1;
+        } else {
+            self.unchanged += 1;
+        }
+    }
+}
+
+/// One row per seeded target, printed as a table.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Summary {
+    pub rows: Vec<(&'static str, Counts)>,
+}
+
+impl Summary {
+    pub fn push(&mut self, target: &'static str, counts: Counts) {
+        self.rows.push((target, counts));
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<10} {:>9} {:>9} {:>10}",
+            "target", "inserted", "updated", "unchanged"
+        )?;
+        for (target, counts) in &self.rows {
+            writeln!(
+                f,
+                "{:<10} {:>9} {:>9} {:>10}",
+                target, counts.inserted, counts.updated, counts.unchanged
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Upserts `count` fixture products keyed by barcode.
+pub async fn seed_products(db: &Database, count: usize) -> Result<Counts> {
+    let collection = db.collection::<Product>(PRODUCTS_COLLECTION);
+    let mut counts = Counts::default();
+    for product in fixtures::products(count) {
+        let result = collection
+            .replace_one(doc! { "code": &product.code }, &product)
+            .upsert(true)
+            .await?;
+        debug!("Product {}: {:?}", product.code, result);
+        counts.record(&result);
+    }
+    info!("Seeded products: {:?}", counts);
+    Ok(counts)
+}
+
+/// Upserts one fixture profile per user id.
+pub async fn seed_profiles(db: &Database, users: &[String]) -> Result<Counts> {
+    let collection = db.collection::<UserProfile>(PROFILES_COLLECTION);
+    let mut counts = Counts::default();
+    for profile in fixtures::profiles(users) {
+        let result = collection
+            .replace_one(doc! { "user_id": &profile.user_id }, &profile)
+            .upsert(true)
+            .await?;
+        debug!("Profile {}: {:?}", profile.user_id, result);
+        counts.record(&result);
+    }
+    info!("Seeded profiles: {:?}", counts);
+    Ok(counts)
+}
+
+/// One relationship the checker's query walks, from an ingredient to a target node.
+struct Link {
+    ingredient: &'static str,
+    relationship: &'static str,
+    label: &'static str,
+    target: &'static str,
+}
+
+fn links() -> impl Iterator<Item = Link> {
+    let link = |relationship, label| {
+        move |&(ingredient, target): &(&'static str, &'static str)| Link {
+            ingredient,
+            relationship,
+            label,
+            target,
+        }
+    };
+    INGREDIENT_ALLERGENS
+        .iter()
+        .map(link("IS_ALLERGEN", "Allergen"))
+        .chain(
+            TRACE_ALLERGENS
+                .iter()
+                .map(link("MAY_CONTAIN_TRACE", "Allergen")),
+        )
+        .chain(
+            INGREDIENT_DIET_CONFLICTS
+                .iter()
+                .map(link("CONFLICTS_WITH_DIET", "DietaryPreference")),
+        )
+}
+
+/// MERGEs the ingredient, allergen and diet nodes and their relationships. A link that
+/// already existed counts as unchanged; relationships carry no properties to update.
+pub async fn seed_graph(graph: &Graph) -> Result<Counts> {
+    let mut counts = Counts::default();
+    for link in links() {
+        // Relationship types and labels can't be parameters; they come from the
+        // constant tables above, never from input.
+        let cypher = format!(
+            "OPTIONAL MATCH (:Ingredient {{name: $ingredient}})-[existing:{rel}]->(:{label} {{name: $target}}) \
+             WITH existing IS NOT NULL AS existed \
+             MERGE (i:Ingredient {{name: $ingredient}}) \
+             MERGE (t:{label} {{name: $target}}) \
+             MERGE (i)-[:{rel}]->(t) \
+             RETURN existed",
+            rel = link.relationship,
+            label = link.label,
+        );
+        let mut rows = graph
+            .execute(
+                query(&cypher)
+                    .param("ingredient", link.ingredient)
+                    .param("target", link.target),
+            )
+            .await?;
+        let existed = match rows.next().await? {
+            Some(row) => row.get::<bool>("existed")?,
+            None => false,
+        };
+        debug!(
+            "({})-[:{}]->({}) existed: {}",
+            link.ingredient, link.relationship, link.target, existed
+        );
+        if existed {
+            counts.unchanged += 1;
+        } else {
+            counts.inserted += 1;
+        }
+    }
+    info!("Seeded graph links: {:?}", counts);
+    Ok(counts)
+}
+
+/// Everything a local stack needs for a meaningful `/api/v1/check`.
+pub async fn seed_all(
+    catalog_db: &Database,
+    profile_db: &Database,
+    graph: &Graph,
+    count: usize,
+    users: &[String],
+) -> Result<Summary> {
+    let mut summary = Summary::default();
+    summary.push("products", seed_products(catalog_db, count).await?);
+    summary.push("profiles", seed_profiles(profile_db, users).await?);
+    summary.push("graph", seed_graph(graph).await?);
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_classify_upserts() {
+        let mut counts = Counts::default();
+        counts.count(true, false);
+        counts.count(false, true);
+        counts.count(false, false);
+        counts.count(false, false);
+        assert_eq!(
+            counts,
+            Counts {
+                inserted: 1,
+                updated: 1,
+                unchanged: 2
+            }
+        );
+    }
+
+    #[test]
+    fn links_cover_every_relationship_kind() {
+        let links: Vec<Link> = links().collect();
+        assert_eq!(
+            links.len(),
+            INGREDIENT_ALLERGENS.len() + TRACE_ALLERGENS.len() + INGREDIENT_DIET_CONFLICTS.len()
+        );
+        assert!(links.iter().any(|l| l.relationship == "MAY_CONTAIN_TRACE"
+            && l.ingredient == "en:peanuts"
+            && l.label == "Allergen"));
+        assert!(links.iter().any(|l| l.relationship == "CONFLICTS_WITH_DIET"
+            && l.label == "DietaryPreference"
+            && l.target == "vegan"));
+    }
+
+    #[test]
+    fn summary_renders_a_table() {
+        let mut summary = Summary::default();
+        summary.push(
+            "products",
+            Counts {
+                inserted: 20,
+                updated: 0,
+                unchanged: 0,
+            },
+        );
+        summary.push(
+            "graph",
+            Counts {
+                inserted: 0,
+                updated: 0,
+                unchanged: 21,
+            },
+        );
+        assert_eq!(
+            summary.to_string(),
+            "target      inserted   updated  unchanged\n\
+             products          20         0          0\n\
+             graph              0         0         21\n"
+        );
+    }
+}
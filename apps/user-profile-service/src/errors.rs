@@ -3,9 +3,13 @@ use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
 };
-use serde_json::json;
 use thiserror::Error;
 use tracing::error;
+use yoloeats_domain::{
+    ErrorBody, ErrorCode,
+    validation::{validation_details, validation_summary},
+};
+use yoloeats_tracing::current_request_id;
 
 #[derive(Error, Debug)]
 pub enum AppError {
@@ -30,20 +34,27 @@ pub enum AppError {
     #[error("Invalid input: {0}")]
     BadRequest(String),
 
+    #[error("Input validation failed: {}", validation_summary(.0))]
+    Validation(#[from] validator::ValidationErrors),
+
     #[error("Resource not found: {0}")]
     NotFound(String),
 
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
     #[error("Internal server error: {0}")]
     Internal(String),
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, error_message) = match &self {
+        let (status, code, error_message) = match &self {
             AppError::Io(e) => {
                 error!("IO error: {}", e);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::InternalError,
                     "An internal input/output error occurred".to_string(),
                 )
             }
@@ -51,6 +62,7 @@ impl IntoResponse for AppError {
                 error!("MongoDB error: {}", e);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::DatabaseError,
                     "Database operation failed".to_string(),
                 )
             }
@@ -58,6 +70,7 @@ impl IntoResponse for AppError {
                 error!("Redis error: {}", e);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::CacheError,
                     "Cache or session operation failed".to_string(),
                 )
             }
@@ -65,6 +78,7 @@ impl IntoResponse for AppError {
                 error!("BSON serialization error: {}", e);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::InternalError,
                     "Failed to serialize data".to_string(),
                 )
             }
@@ -72,6 +86,7 @@ impl IntoResponse for AppError {
                 error!("BSON deserialization error: {}", e);
                 (
                     StatusCode::BAD_REQUEST, // Assuming deserialization errors are client errors
+                    ErrorCode::InvalidData,
                     "Failed to deserialize data".to_string(),
                 )
             }
@@ -79,26 +94,186 @@ impl IntoResponse for AppError {
                 error!("Configuration error encountered during request: {}", e);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::ConfigurationError,
                     "Internal configuration problem".to_string(),
                 )
             }
-            AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
-            AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg.clone()),
+            AppError::BadRequest(msg) => (
+                StatusCode::BAD_REQUEST,
+                ErrorCode::InvalidRequest,
+                msg.clone(),
+            ),
+            // Profiles only validate request bodies; see `yoloeats_metrics::ValidJson`.
+            AppError::Validation(errors) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorCode::ValidationFailed,
+                format!("Input validation failed: {}", validation_summary(errors)),
+            ),
+            AppError::NotFound(msg) => (
+                StatusCode::NOT_FOUND,
+                ErrorCode::ProfileNotFound,
+                msg.clone(),
+            ),
+            AppError::Conflict(msg) => (
+                StatusCode::CONFLICT,
+                ErrorCode::ProfileConflict,
+                msg.clone(),
+            ),
             AppError::Internal(msg) => {
                 error!("Internal server error: {}", msg);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::InternalError,
                     "An unexpected internal error occurred".to_string(), // Generic message to client
                 )
             }
         };
 
-        let body = Json(json!({
-            "error": error_message,
-        }));
+        let mut body = ErrorBody::new(code, error_message)
+            .with_request_id(current_request_id().map(|id| id.to_string()));
+        if let AppError::Validation(errors) = &self {
+            body = body.with_details(validation_details(errors));
+        }
+        (status, Json(body)).into_response()
+    }
+}
 
-        (status, body).into_response()
+impl From<AppError> for tonic::Status {
+    fn from(err: AppError) -> Self {
+        match err {
+            AppError::NotFound(msg) => tonic::Status::not_found(msg),
+            AppError::BadRequest(msg) => tonic::Status::invalid_argument(msg),
+            AppError::Validation(errors) => {
+                tonic::Status::invalid_argument(validation_summary(&errors))
+            }
+            AppError::Conflict(msg) => tonic::Status::already_exists(msg),
+            other => {
+                error!("Internal gRPC request failed: {}", other);
+                tonic::Status::internal("An unexpected internal error occurred")
+            }
+        }
     }
 }
 
 pub type Result<T> = std::result::Result<T, AppError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+    use yoloeats_tracing::{RequestId, with_request_id};
+
+    async fn render(err: AppError) -> (StatusCode, Value) {
+        let id = RequestId::parse("req-1").unwrap();
+        let response = with_request_id(id, async move { err.into_response() }).await;
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    fn envelope(code: &str, message: &str) -> Value {
+        json!({
+            "code": code,
+            "message": message,
+            "error": message,
+            "requestId": "req-1"
+        })
+    }
+
+    #[tokio::test]
+    async fn error_envelope_json_snapshots() {
+        let cases = [
+            (
+                AppError::Io(std::io::Error::other("disk")),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                envelope("internal_error", "An internal input/output error occurred"),
+            ),
+            (
+                AppError::MongoDb(std::io::Error::other("socket").into()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                envelope("database_error", "Database operation failed"),
+            ),
+            (
+                AppError::Redis((redis::ErrorKind::IoError, "refused").into()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                envelope("cache_error", "Cache or session operation failed"),
+            ),
+            (
+                AppError::BsonSerialize(serde::ser::Error::custom("bad")),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                envelope("internal_error", "Failed to serialize data"),
+            ),
+            (
+                AppError::BsonDeserialize(serde::de::Error::custom("bad")),
+                StatusCode::BAD_REQUEST,
+                envelope("invalid_data", "Failed to deserialize data"),
+            ),
+            (
+                AppError::Config(rust_database_clients::ConfigError::MissingVariable(
+                    "MONGO_URI".to_string(),
+                )),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                envelope("configuration_error", "Internal configuration problem"),
+            ),
+            (
+                AppError::BadRequest("No fields provided for update.".to_string()),
+                StatusCode::BAD_REQUEST,
+                envelope("invalid_request", "No fields provided for update."),
+            ),
+            (
+                AppError::NotFound("Profile for user u1 not found".to_string()),
+                StatusCode::NOT_FOUND,
+                envelope("profile_not_found", "Profile for user u1 not found"),
+            ),
+            (
+                AppError::Conflict("conflicting unique identifier".to_string()),
+                StatusCode::CONFLICT,
+                envelope("profile_conflict", "conflicting unique identifier"),
+            ),
+            (
+                AppError::Internal("upsert returned None".to_string()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                envelope("internal_error", "An unexpected internal error occurred"),
+            ),
+        ];
+
+        for (err, expected_status, expected_body) in cases {
+            let label = format!("{:?}", err);
+            let (status, body) = render(err).await;
+            assert_eq!(status, expected_status, "{}", label);
+            assert_eq!(body, expected_body, "{}", label);
+        }
+    }
+
+    #[tokio::test]
+    async fn validation_errors_carry_field_details() {
+        use validator::Validate;
+
+        let payload = crate::models::UpdateProfilePayload {
+            username: Some("al".to_string()),
+            email: None,
+            allergens: None,
+            dietary_prefs: None,
+            risk_tolerance: None,
+        };
+        let err = AppError::from(payload.validate().unwrap_err());
+        let (status, body) = render(err).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "validation_failed");
+        assert_eq!(
+            body["message"],
+            "Input validation failed: username: Username must be at least 3 characters long"
+        );
+        assert_eq!(
+            body["details"],
+            json!([{
+                "field": "username",
+                "code": "length",
+                "message": "Username must be at least 3 characters long",
+                "params": { "min": 3 }
+            }])
+        );
+    }
+}
@@ -0,0 +1,36 @@
+use crate::{handlers::load_profile, models::SafetyProfile, state::AppState};
+use std::sync::Arc;
+use tonic::{Request, Response, Status};
+use tracing::instrument;
+use yoloeats_proto::v1::{
+    self, GetSafetyProfileRequest,
+    profile_service_server::{ProfileService, ProfileServiceServer},
+};
+
+/// Internal gRPC view of the profile store. Served on its own port for other services
+/// only, so unlike `/api/v1/users` it does not go through the bearer-token layer.
+pub struct ProfileGrpc {
+    state: Arc<AppState>,
+}
+
+impl ProfileGrpc {
+    pub fn server(state: Arc<AppState>) -> ProfileServiceServer<Self> {
+        ProfileServiceServer::new(ProfileGrpc { state })
+    }
+}
+
+#[tonic::async_trait]
+impl ProfileService for ProfileGrpc {
+    #[instrument(skip(self, request), fields(user_id = %request.get_ref().user_id))]
+    async fn get_safety_profile(
+        &self,
+        request: Request<GetSafetyProfileRequest>,
+    ) -> Result<Response<v1::SafetyProfile>, Status> {
+        let user_id = request.into_inner().user_id;
+        if user_id.trim().is_empty() {
+            return Err(Status::invalid_argument("user_id must not be empty"));
+        }
+        let profile = load_profile(&self.state, &user_id).await?;
+        Ok(Response::new(SafetyProfile::from(profile).into()))
+    }
+}
@@ -0,0 +1,27 @@
+//! Dependency checks behind `/health/ready` and `/health/details`.
+
+use crate::state::AppState;
+use yoloeats_health::{
+    Criticality, HealthRegistry,
+    checks::{MongoCheck, RedisCheck},
+};
+
+/// Redis only caches profiles, so losing it degrades latency, not correctness.
+pub fn registry(state: &AppState) -> HealthRegistry {
+    let registry = HealthRegistry::new("user-profile-service");
+    // Nothing external to check on in-memory storage.
+    let Some(clients) = &state.clients else {
+        return registry;
+    };
+    registry
+        .register(
+            "mongodb",
+            Criticality::Critical,
+            MongoCheck(clients.mongo_db.clone()),
+        )
+        .register(
+            "redis",
+            Criticality::Informational,
+            RedisCheck(clients.redis_client.clone()),
+        )
+}
@@ -0,0 +1,88 @@
+//! User profile service: per-user profiles (allergens, diets, risk tolerance) and the
+//! allergen catalogue.
+//!
+//! `main.rs` loads configuration and connects the clients; [`router`] is public so the
+//! integration harness can serve the same routes in-process with injected state. With
+//! `STORAGE_MODE=memory` profiles live in [`repository::MemoryProfiles`] and the cache is
+//! a map in process, so the service runs without MongoDB or Redis.
+//!
+//! The `/api/v1` routes are frozen and carry the `Deprecation`/`Sunset` headers once
+//! [`AppState::api_v1_deprecation`] dates them; [`v2`] serves the same operations in the
+//! v2 response shapes. Both are documented in [`openapi::ApiDoc`], served at
+//! `/api-docs/openapi.json` with Swagger UI at `/docs`.
+
+use axum::{Router, routing::get};
+use handlers::{get_allergens, get_profile, update_profile};
+use state::AppState;
+use std::sync::Arc;
+use tower_http::cors::{Any, CorsLayer};
+use utoipa::OpenApi;
+use yoloeats_auth::{AuthLayer, Authenticator, InternalTokenLayer, require_subject_matches_path};
+use yoloeats_dynamic_config::config_router;
+use yoloeats_health::health_router;
+use yoloeats_metrics::{BodyLimitLayer, HttpMetricsLayer, LoadShedLayer, compression_layer};
+use yoloeats_openapi::openapi_router;
+use yoloeats_tracing::RequestIdLayer;
+use yoloeats_versioning::DeprecationLayer;
+
+pub mod errors;
+pub mod grpc;
+pub mod handlers;
+pub mod health;
+pub mod models;
+pub mod openapi;
+pub mod repository;
+pub mod state;
+pub mod tunables;
+pub mod v2;
+
+async fn root_handler() -> &'static str {
+    "User Profile Service OK V2"
+}
+
+pub fn router(app_state: Arc<AppState>, authenticator: Authenticator) -> Router {
+    let cors = CorsLayer::new()
+        .allow_origin(Any)
+        .allow_methods(Any)
+        .allow_headers(Any);
+
+    let v1_routes = Router::new()
+        .nest(
+            "/users",
+            Router::new()
+                .route("/{user_id}/profile", get(get_profile).put(update_profile))
+                .route_layer(require_subject_matches_path("user_id"))
+                .layer(AuthLayer::new(authenticator.clone())),
+        )
+        .route("/allergens", get(get_allergens))
+        .layer(DeprecationLayer::new(app_state.api_v1_deprecation));
+
+    let v2_routes = Router::new()
+        .nest(
+            "/users",
+            Router::new()
+                .route(
+                    "/{user_id}/profile",
+                    get(v2::get_profile).put(v2::update_profile),
+                )
+                .route_layer(require_subject_matches_path("user_id"))
+                .layer(AuthLayer::new(authenticator)),
+        )
+        .route("/allergens", get(v2::get_allergens));
+
+    Router::new()
+        .route("/", get(root_handler))
+        .nest("/api/v1", v1_routes)
+        .nest("/api/v2", v2_routes)
+        .merge(health_router(Arc::new(health::registry(&app_state))))
+        .merge(config_router(app_state.config.clone()))
+        .merge(openapi_router(openapi::ApiDoc::openapi()))
+        .layer(compression_layer())
+        .layer(InternalTokenLayer::new(app_state.internal_tokens.clone()))
+        .layer(BodyLimitLayer::new(app_state.body_limits.clone()))
+        .layer(LoadShedLayer::new("user-profile-service", app_state.load_shed))
+        .layer(HttpMetricsLayer::new("user-profile-service"))
+        .layer(RequestIdLayer)
+        .layer(cors)
+        .with_state(app_state)
+}
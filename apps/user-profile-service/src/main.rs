@@ -1,88 +1,189 @@
-use axum::{Router, routing::get};
-use handlers::{get_allergens, get_profile, update_profile};
-use rust_database_clients::{create_mongo_client, create_redis_client, load_config};
-use state::AppState;
+use rust_database_clients::{
+    Cache, MemoryCache, MongoSettings, RedisCache, StorageMode, create_mongo_client_with_settings,
+    create_redis_client, load_config,
+};
 use std::{env, net::SocketAddr, sync::Arc};
-use tower_http::cors::{Any, CorsLayer};
 use tracing::{error, info, warn};
-use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt};
-
-mod errors;
-mod handlers;
-mod models;
-mod state;
-
-async fn root_handler() -> &'static str {
-    "User Profile Service OK V2"
-}
+use user_profile_service::{
+    grpc::ProfileGrpc,
+    repository::{MemoryProfiles, MongoProfiles, ProfileRepository},
+    router,
+    state::{AppState, Clients},
+    tunables,
+};
+use yoloeats_auth::{AuthConfig, Authenticator, InternalTokens};
+use yoloeats_dynamic_config::{DEFAULT_REFRESH_INTERVAL, MemoryStore, RedisStore};
+use yoloeats_metrics::{BodyLimits, LoadShedConfig, install_recorder, metrics_router};
+use yoloeats_shutdown::{Shutdown, drain_timeout_from_env, shutdown_signal};
+use yoloeats_tracing::{RequestIdLayer, init_tracing};
+use yoloeats_versioning::{API_V1_DEPRECATED_AT_ENV, API_V1_SUNSET_AT_ENV, Deprecation};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenvy::dotenv().ok();
 
-    tracing_subscriber::registry()
-        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()))
-        .with(fmt::layer())
-        .init();
+    let _telemetry = init_tracing("user-profile-service", env!("CARGO_PKG_VERSION"))?;
 
     info!("Starting User Profile Service (V2)...");
 
-    let (mongo_uri, redis_uri) = load_config().map_err(|e| {
-        error!("Config loading failed: {}", e);
+    let auth_config = AuthConfig::from_env().map_err(|e| {
+        error!("Auth configuration failed: {}", e);
         Box::new(e) as Box<dyn std::error::Error>
     })?;
+    let authenticator = Authenticator::new(auth_config);
+    info!("Authentication configured.");
 
-    let mongo_client = create_mongo_client(&mongo_uri).await.map_err(|e| {
-        error!("Mongo connection failed: {}", e);
+    let storage_mode = StorageMode::from_env().map_err(|e| {
+        error!("Config loading failed: {}", e);
         Box::new(e) as Box<dyn std::error::Error>
     })?;
-    info!("MongoDB client created successfully.");
-    let mongo_db = mongo_client.database("yoloeats_user_profile");
-    info!("Using MongoDB database: {}", mongo_db.name());
 
-    let redis_client = create_redis_client(&redis_uri).map_err(|e| {
-        error!("Redis connection failed: {}", e);
+    let (profiles, cache, clients, config) = match storage_mode {
+        StorageMode::External => {
+            let (mongo_uri, redis_uri) = load_config().map_err(|e| {
+                error!("Config loading failed: {}", e);
+                Box::new(e) as Box<dyn std::error::Error>
+            })?;
+
+            let mongo_settings = MongoSettings::from_env("user-profile-service").map_err(|e| {
+                error!("Invalid MongoDB settings: {}", e);
+                Box::new(e) as Box<dyn std::error::Error>
+            })?;
+            let mongo_client = create_mongo_client_with_settings(&mongo_uri, &mongo_settings)
+                .await
+                .map_err(|e| {
+                    error!("Mongo connection failed: {}", e);
+                    Box::new(e) as Box<dyn std::error::Error>
+                })?;
+            info!("MongoDB client created successfully.");
+            let mongo_db = mongo_client.database("yoloeats_user_profile");
+            info!("Using MongoDB database: {}", mongo_db.name());
+
+            let redis_client = create_redis_client(&redis_uri).map_err(|e| {
+                error!("Redis connection failed: {}", e);
+                Box::new(e) as Box<dyn std::error::Error>
+            })?;
+            info!("Redis client created successfully.");
+
+            let config = tunables::config(RedisStore::new(redis_client.clone(), tunables::SERVICE))
+                .map_err(|e| {
+                    error!("Invalid runtime config default: {}", e);
+                    Box::new(e) as Box<dyn std::error::Error>
+                })?;
+            (
+                Arc::new(MongoProfiles::new(&mongo_db)) as Arc<dyn ProfileRepository>,
+                Arc::new(RedisCache::new(redis_client.clone())) as Arc<dyn Cache>,
+                Some(Clients {
+                    mongo_db,
+                    redis_client,
+                }),
+                config,
+            )
+        }
+        StorageMode::Memory => {
+            warn!(
+                "STORAGE_MODE=memory: profiles and the cache are held in process and lost on exit."
+            );
+            let config = tunables::config(MemoryStore::default()).map_err(|e| {
+                error!("Invalid runtime config default: {}", e);
+                Box::new(e) as Box<dyn std::error::Error>
+            })?;
+            (
+                Arc::new(MemoryProfiles::default()) as Arc<dyn ProfileRepository>,
+                Arc::new(MemoryCache::default()) as Arc<dyn Cache>,
+                None,
+                config,
+            )
+        }
+    };
+    config.spawn_refresh(DEFAULT_REFRESH_INTERVAL);
+    info!(
+        "Runtime config loaded; overrides refresh every {:?}.",
+        DEFAULT_REFRESH_INTERVAL
+    );
+
+    let load_shed = LoadShedConfig::from_env().map_err(|e| {
+        error!("Invalid load shedding config: {}", e);
+        Box::new(e) as Box<dyn std::error::Error>
+    })?;
+    info!("Load shedding: {:?}", load_shed);
+    let body_limits = BodyLimits::from_env().map_err(|e| {
+        error!("Invalid request body limit: {}", e);
+        Box::new(e) as Box<dyn std::error::Error>
+    })?;
+    info!("Request body limits: {:?}", body_limits);
+    let api_v1_deprecation = Deprecation::from_env(API_V1_DEPRECATED_AT_ENV, API_V1_SUNSET_AT_ENV)
+        .map_err(|e| {
+            error!("Invalid /api/v1 deprecation dates: {}", e);
+            Box::new(e) as Box<dyn std::error::Error>
+        })?;
+    info!("/api/v1 deprecation: {:?}", api_v1_deprecation);
+    let drain_timeout = drain_timeout_from_env().map_err(|e| {
+        error!("Invalid shutdown drain timeout: {}", e);
         Box::new(e) as Box<dyn std::error::Error>
     })?;
-    info!("Redis client created successfully.");
+    let shutdown = Shutdown::new(drain_timeout);
+    info!("Shutdown drains requests for up to {:?}.", drain_timeout);
 
     let app_state = Arc::new(AppState {
-        mongo_db,
-        redis_client,
+        profiles,
+        cache,
+        clients,
+        internal_tokens: InternalTokens::from_env(),
+        config,
+        load_shed,
+        body_limits,
+        api_v1_deprecation,
     });
 
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
-
-    let user_profile_routes =
-        Router::new().route("/{user_id}/profile", get(get_profile).put(update_profile));
-
-    let allergen_routes = Router::new().route("/", get(get_allergens));
+    let metrics_handle = install_recorder().map_err(|e| {
+        error!("Metrics recorder installation failed: {}", e);
+        Box::new(e) as Box<dyn std::error::Error>
+    })?;
 
-    let app = Router::new()
-        .route("/", get(root_handler))
-        .nest("/api/v1/users", user_profile_routes)
-        .nest("/api/v1/allergens", allergen_routes)
-        .layer(cors)
-        .with_state(app_state);
+    let app = router(app_state.clone(), authenticator).merge(metrics_router(metrics_handle));
 
     let port_str = env::var("USER_PROFILE_SERVICE_PORT").unwrap_or_else(|_| "8001".to_string());
     let port = port_str.parse::<u16>().unwrap_or(8001);
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
     info!("Server configured to listen on {}", addr);
 
+    let grpc_port_str = env::var("USER_PROFILE_GRPC_PORT").unwrap_or_else(|_| "50051".to_string());
+    let grpc_port = grpc_port_str.parse::<u16>().unwrap_or(50051);
+    let grpc_addr = SocketAddr::from(([0, 0, 0, 0], grpc_port));
+    info!("Internal gRPC server configured to listen on {}", grpc_addr);
+
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    warn!(
-        "Warning: Authentication not implemented. User ID in path is currently not validated against an authenticated principal."
-    );
+    warn!("Warning: The allergens listing and /metrics are served without authentication.");
+    warn!("Warning: The internal gRPC port is unauthenticated; do not expose it publicly.");
     info!(
         "User Profile Service (V2) successfully started, listening on {}",
         addr
     );
 
-    axum::serve(listener, app.into_make_service()).await?;
+    let grpc_server = tonic::transport::Server::builder()
+        .layer(RequestIdLayer)
+        .add_service(ProfileGrpc::server(app_state))
+        .serve_with_shutdown(grpc_addr, shutdown.signalled());
+
+    shutdown.trigger_on(shutdown_signal());
+    let servers = async {
+        tokio::try_join!(
+            async {
+                axum::serve(listener, app.into_make_service())
+                    .with_graceful_shutdown(shutdown.signalled())
+                    .await
+                    .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
+            },
+            async {
+                grpc_server
+                    .await
+                    .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
+            },
+        )
+        .map(|_| ())
+    };
+    shutdown.run(servers).await?;
 
     Ok(())
 }
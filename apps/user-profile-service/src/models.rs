@@ -1,20 +1,15 @@
 use bson::oid::ObjectId;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use validator::Validate;
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
-#[serde(rename_all = "lowercase")]
-pub enum RiskLevel {
-    Low,
-    #[default]
-    Medium,
-    High,
-}
+pub use yoloeats_domain::{AllergenInfo, RiskLevel, SafetyProfile};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct UserProfile {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
     pub id: Option<ObjectId>,
 
     pub user_id: String,
@@ -34,14 +29,25 @@ pub struct UserProfile {
     #[serde(default)]
     pub risk_tolerance: RiskLevel,
 
-    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    #[serde(with = "rust_database_clients::serde_helpers::chrono_datetime_as_rfc3339_or_bson")]
     pub created_at: DateTime<Utc>,
 
-    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    #[serde(with = "rust_database_clients::serde_helpers::chrono_datetime_as_rfc3339_or_bson")]
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Validate)]
+impl From<UserProfile> for SafetyProfile {
+    fn from(profile: UserProfile) -> Self {
+        SafetyProfile {
+            user_id: profile.user_id,
+            allergens: profile.allergens,
+            dietary_prefs: profile.dietary_prefs,
+            risk_tolerance: profile.risk_tolerance,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
 pub struct UpdateProfilePayload {
     #[validate(length(min = 3, message = "Username must be at least 3 characters long"))]
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -61,9 +67,59 @@ pub struct UpdateProfilePayload {
     pub risk_tolerance: Option<RiskLevel>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AllergenInfo {
-    pub id: String,
-    pub name: String,
-    pub description: Option<String>,
+impl UpdateProfilePayload {
+    /// True when the request sets no field at all.
+    pub fn is_empty(&self) -> bool {
+        self.username.is_none()
+            && self.email.is_none()
+            && self.allergens.is_none()
+            && self.dietary_prefs.is_none()
+            && self.risk_tolerance.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yoloeats_domain::fixtures::{FIXTURE_TIME, UserProfileFixture};
+
+    #[test]
+    fn profile_timestamps_are_rfc3339_in_json_and_native_in_bson() {
+        let profile: UserProfile = UserProfileFixture::new("user-1")
+            .allergic_to(["peanuts"])
+            .risk(RiskLevel::Low)
+            .build();
+
+        let json = serde_json::to_value(&profile).unwrap();
+        assert_eq!(json["created_at"], FIXTURE_TIME);
+        assert_eq!(json["updated_at"], FIXTURE_TIME);
+        let from_json: UserProfile = serde_json::from_value(json).unwrap();
+        assert_eq!(from_json.updated_at, profile.updated_at);
+
+        let raw = bson::to_raw_document_buf(&profile).unwrap();
+        assert!(matches!(
+            raw.get("updated_at").unwrap(),
+            Some(bson::RawBsonRef::DateTime(_))
+        ));
+        let from_bson: UserProfile = bson::from_slice(raw.as_bytes()).unwrap();
+        assert_eq!(from_bson.created_at, profile.created_at);
+    }
+
+    #[test]
+    fn profile_json_is_readable_as_shared_safety_profile() {
+        let profile: UserProfile = UserProfileFixture::new("user-1")
+            .named("alice")
+            .allergic_to(["peanuts"])
+            .following(["vegan"])
+            .risk(RiskLevel::High)
+            .build();
+        let json = serde_json::to_string(&profile).unwrap();
+        let safety: SafetyProfile = serde_json::from_str(&json).unwrap();
+        assert_eq!(safety.user_id, "user-1");
+        assert_eq!(safety.allergens, profile.allergens);
+        assert_eq!(safety.dietary_prefs, profile.dietary_prefs);
+        assert_eq!(safety.risk_tolerance, RiskLevel::High);
+        // The gRPC server converts directly; both transports must agree.
+        assert_eq!(SafetyProfile::from(profile), safety);
+    }
 }
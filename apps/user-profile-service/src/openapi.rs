@@ -0,0 +1,98 @@
+//! The service's OpenAPI document, served by [`yoloeats_openapi::openapi_router`].
+//!
+//! Both API versions are in it, tagged `v1` and `v2`. The profile routes take the
+//! `bearer` scheme: a JWT whose subject is the path's user.
+
+use crate::{handlers, v2};
+use utoipa::OpenApi;
+use yoloeats_openapi::{BearerAuth, MiddlewareResponses};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "User Profile Service",
+        description = "Per-user profiles (allergens, diets, risk tolerance) and the allergen catalogue."
+    ),
+    paths(
+        handlers::get_profile,
+        handlers::update_profile,
+        handlers::get_allergens,
+        v2::get_profile,
+        v2::update_profile,
+        v2::get_allergens,
+    ),
+    modifiers(&BearerAuth, &MiddlewareResponses)
+)]
+pub struct ApiDoc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+
+    fn spec() -> Value {
+        serde_json::from_str(&ApiDoc::openapi().to_json().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn every_route_is_documented() {
+        let spec = spec();
+        for (method, path) in [
+            ("get", "/api/v1/users/{user_id}/profile"),
+            ("put", "/api/v1/users/{user_id}/profile"),
+            ("get", "/api/v1/allergens"),
+            ("get", "/api/v2/users/{user_id}/profile"),
+            ("put", "/api/v2/users/{user_id}/profile"),
+            ("get", "/api/v2/allergens"),
+        ] {
+            assert!(
+                spec["paths"][path][method].is_object(),
+                "{} {}",
+                method,
+                path
+            );
+        }
+
+        let get = &spec["paths"]["/api/v1/users/{user_id}/profile"]["get"];
+        assert_eq!(get["security"], json!([{ "bearer": [] }]));
+        assert_eq!(
+            get["responses"]["404"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/ErrorBody"
+        );
+        assert!(get["responses"]["503"].is_object());
+        assert_eq!(
+            spec["components"]["securitySchemes"]["bearer"]["scheme"],
+            "bearer"
+        );
+    }
+
+    #[test]
+    fn schemas_use_the_wire_names() {
+        let spec = spec();
+        let schemas = &spec["components"]["schemas"];
+
+        let v1 = &schemas["UserProfile"]["properties"];
+        for field in [
+            "_id",
+            "user_id",
+            "dietary_prefs",
+            "risk_tolerance",
+            "created_at",
+        ] {
+            assert!(v1[field].is_object(), "{}", field);
+        }
+        assert_eq!(v1["created_at"]["format"], "date-time");
+        assert!(schemas["UpdateProfilePayload"]["properties"]["dietary_prefs"].is_object());
+
+        let v2 = &schemas["ProfileV2"]["properties"];
+        for field in ["userId", "dietaryPreferences", "riskTolerance", "updatedAt"] {
+            assert!(v2[field].is_object(), "{}", field);
+        }
+        assert!(v2["dietary_prefs"].is_null());
+
+        assert_eq!(
+            schemas["RiskLevel"]["enum"],
+            json!(["low", "medium", "high"])
+        );
+    }
+}
@@ -0,0 +1,226 @@
+//! Where profiles are stored: the `user_profiles` collection, or a map in process for
+//! `STORAGE_MODE=memory`.
+
+use crate::{
+    errors::{AppError, Result},
+    models::{UpdateProfilePayload, UserProfile},
+};
+use async_trait::async_trait;
+use bson::{doc, oid::ObjectId};
+use chrono::Utc;
+use mongodb::{
+    Collection, Database,
+    error::ErrorKind as MongoErrorKind,
+    options::{FindOneAndUpdateOptions, ReturnDocument},
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tracing::{debug, error, info};
+
+#[async_trait]
+pub trait ProfileRepository: Send + Sync {
+    async fn find(&self, user_id: &str) -> Result<Option<UserProfile>>;
+
+    /// Applies the fields set in `changes`, creating the profile if the user has none.
+    async fn upsert(&self, user_id: &str, changes: &UpdateProfilePayload) -> Result<UserProfile>;
+}
+
+pub struct MongoProfiles {
+    collection: Collection<UserProfile>,
+}
+
+impl MongoProfiles {
+    pub fn new(db: &Database) -> Self {
+        MongoProfiles {
+            collection: db.collection("user_profiles"),
+        }
+    }
+}
+
+#[async_trait]
+impl ProfileRepository for MongoProfiles {
+    async fn find(&self, user_id: &str) -> Result<Option<UserProfile>> {
+        let filter = doc! { "user_id": user_id };
+        self.collection.find_one(filter).await.map_err(|e| {
+            error!(user_id = %user_id, "MongoDB find_one failed: {}", e);
+            AppError::MongoDb(e)
+        })
+    }
+
+    async fn upsert(&self, user_id: &str, changes: &UpdateProfilePayload) -> Result<UserProfile> {
+        let mut set_updates_doc = bson::to_document(changes).map_err(AppError::BsonSerialize)?;
+
+        let now = Utc::now();
+        set_updates_doc.insert("updated_at", bson::DateTime::from_chrono(now));
+
+        let set_on_insert_doc = doc! {
+            "user_id": user_id,
+            "created_at": bson::DateTime::from_chrono(now)
+        };
+
+        let update_doc = doc! {
+            "$set": set_updates_doc,
+            "$setOnInsert": set_on_insert_doc
+        };
+        debug!(user_id = %user_id, update = ?update_doc, "Constructed upsert document");
+
+        let filter = doc! { "user_id": user_id };
+        let options = FindOneAndUpdateOptions::builder()
+            .upsert(true)
+            .return_document(ReturnDocument::After)
+            .build();
+
+        let update_result = self
+            .collection
+            .find_one_and_update(filter, update_doc)
+            .with_options(options)
+            .await;
+
+        match update_result {
+            Ok(Some(updated_profile)) => {
+                info!(user_id = %user_id, id = updated_profile.id.map(|id| id.to_string()).unwrap_or_default(), "Successfully upserted user profile in DB");
+                Ok(updated_profile)
+            }
+            Ok(None) => {
+                error!(user_id = %user_id, "Upsert operation returned None unexpectedly. This might indicate an issue with MongoDB's return behavior or query.");
+                Err(AppError::Internal(
+                    "Profile update failed unexpectedly after upsert operation.".to_string(),
+                ))
+            }
+            Err(e) => {
+                if matches!(
+                    &*e.kind,
+                    MongoErrorKind::Write(mongodb::error::WriteFailure::WriteError(write_error))
+                        if write_error.code == 11000
+                ) {
+                    error!(user_id = %user_id, "Duplicate key error on upsert: {}. This could indicate a race condition or an issue with the upsert logic if user_id is not the shard key or has a unique constraint being violated unexpectedly.", e);
+                    return Err(AppError::Conflict(
+                        "Update failed due to a conflicting unique identifier. Please check data integrity.".to_string(),
+                    ));
+                }
+                error!(user_id = %user_id, "Failed to upsert profile in DB: {}", e);
+                Err(AppError::MongoDb(e))
+            }
+        }
+    }
+}
+
+/// Profiles keyed by user id. Clones share the same map.
+#[derive(Clone, Default)]
+pub struct MemoryProfiles {
+    profiles: Arc<Mutex<HashMap<String, UserProfile>>>,
+}
+
+impl MemoryProfiles {
+    /// Stores `profile` as is, replacing any profile of the same user.
+    pub fn seed(&self, profile: UserProfile) {
+        self.profiles
+            .lock()
+            .unwrap()
+            .insert(profile.user_id.clone(), profile);
+    }
+}
+
+#[async_trait]
+impl ProfileRepository for MemoryProfiles {
+    async fn find(&self, user_id: &str) -> Result<Option<UserProfile>> {
+        Ok(self.profiles.lock().unwrap().get(user_id).cloned())
+    }
+
+    async fn upsert(&self, user_id: &str, changes: &UpdateProfilePayload) -> Result<UserProfile> {
+        let now = Utc::now();
+        let mut profiles = self.profiles.lock().unwrap();
+        let profile = profiles
+            .entry(user_id.to_string())
+            .or_insert_with(|| UserProfile {
+                id: Some(ObjectId::new()),
+                user_id: user_id.to_string(),
+                username: None,
+                email: None,
+                allergens: Vec::new(),
+                dietary_prefs: Vec::new(),
+                risk_tolerance: Default::default(),
+                created_at: now,
+                updated_at: now,
+            });
+        if let Some(username) = &changes.username {
+            profile.username = Some(username.clone());
+        }
+        if let Some(email) = &changes.email {
+            profile.email = Some(email.clone());
+        }
+        if let Some(allergens) = &changes.allergens {
+            profile.allergens = allergens.clone();
+        }
+        if let Some(dietary_prefs) = &changes.dietary_prefs {
+            profile.dietary_prefs = dietary_prefs.clone();
+        }
+        if let Some(risk_tolerance) = &changes.risk_tolerance {
+            profile.risk_tolerance = risk_tolerance.clone();
+        }
+        profile.updated_at = now;
+        Ok(profile.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::RiskLevel;
+
+    fn changes() -> UpdateProfilePayload {
+        UpdateProfilePayload {
+            username: None,
+            email: None,
+            allergens: None,
+            dietary_prefs: None,
+            risk_tolerance: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn memory_upsert_creates_then_updates_only_the_given_fields() {
+        let profiles = MemoryProfiles::default();
+        assert!(profiles.find("alice").await.unwrap().is_none());
+
+        let created = profiles
+            .upsert(
+                "alice",
+                &UpdateProfilePayload {
+                    allergens: Some(vec!["milk".to_string()]),
+                    ..changes()
+                },
+            )
+            .await
+            .unwrap();
+        assert!(created.id.is_some());
+        assert_eq!(created.allergens, vec!["milk".to_string()]);
+        assert_eq!(created.risk_tolerance, RiskLevel::default());
+
+        let updated = profiles
+            .upsert(
+                "alice",
+                &UpdateProfilePayload {
+                    risk_tolerance: Some(RiskLevel::High),
+                    ..changes()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(updated.id, created.id);
+        assert_eq!(updated.created_at, created.created_at);
+        assert_eq!(updated.allergens, vec!["milk".to_string()]);
+        assert_eq!(updated.risk_tolerance, RiskLevel::High);
+        assert_eq!(
+            profiles
+                .find("alice")
+                .await
+                .unwrap()
+                .unwrap()
+                .risk_tolerance,
+            RiskLevel::High
+        );
+    }
+}
@@ -1,8 +1,31 @@
+use crate::repository::ProfileRepository;
 use mongodb::Database;
 use redis::Client as RedisClient;
+use rust_database_clients::Cache;
+use std::sync::Arc;
+use yoloeats_auth::InternalTokens;
+use yoloeats_dynamic_config::DynamicConfig;
+use yoloeats_metrics::{BodyLimits, LoadShedConfig};
+use yoloeats_versioning::Deprecation;
 
 #[derive(Clone)]
 pub struct AppState {
+    pub profiles: Arc<dyn ProfileRepository>,
+    pub cache: Arc<dyn Cache>,
+    /// `None` with `STORAGE_MODE=memory`.
+    pub clients: Option<Clients>,
+    pub internal_tokens: InternalTokens,
+    pub config: DynamicConfig,
+    pub load_shed: LoadShedConfig,
+    pub body_limits: BodyLimits,
+    /// Announced on every `/api/v1` response.
+    pub api_v1_deprecation: Deprecation,
+}
+
+/// The external clients behind [`AppState::profiles`] and [`AppState::cache`], kept for
+/// the health checks.
+#[derive(Clone)]
+pub struct Clients {
     pub mongo_db: Database,
     pub redis_client: RedisClient,
 }
@@ -0,0 +1,43 @@
+//! Settings that can be changed at runtime through `/internal/v1/config`; see
+//! `yoloeats_dynamic_config`. Defaults come from the environment variable in each comment.
+
+use yoloeats_dynamic_config::{
+    ConfigError, DynamicConfig, OverrideStore, Tunable, env_default, in_range,
+};
+
+pub const SERVICE: &str = "user-profile-service";
+
+/// `PROFILE_CACHE_TTL_SECS`, default 3600.
+pub const PROFILE_CACHE_TTL_SECS: Tunable<u64> = Tunable::new("profile_cache_ttl_secs");
+/// `ALLERGEN_CACHE_TTL_SECS`, default 86400.
+pub const ALLERGEN_CACHE_TTL_SECS: Tunable<u64> = Tunable::new("allergen_cache_ttl_secs");
+
+pub fn config(store: impl OverrideStore + 'static) -> Result<DynamicConfig, ConfigError> {
+    DynamicConfig::builder(SERVICE)
+        .register_validated(
+            PROFILE_CACHE_TTL_SECS,
+            env_default("PROFILE_CACHE_TTL_SECS", 3600),
+            "Redis TTL of cached user profiles",
+            in_range(1, 86_400),
+        )
+        .register_validated(
+            ALLERGEN_CACHE_TTL_SECS,
+            env_default("ALLERGEN_CACHE_TTL_SECS", 86_400),
+            "Redis TTL of the cached allergen list",
+            in_range(1, 7 * 86_400),
+        )
+        .build(store)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yoloeats_dynamic_config::MemoryStore;
+
+    #[test]
+    fn every_tunable_is_registered_with_its_type() {
+        let config = config(MemoryStore::default()).unwrap();
+        assert_eq!(config.get(PROFILE_CACHE_TTL_SECS), 3600);
+        assert_eq!(config.get(ALLERGEN_CACHE_TTL_SECS), 86_400);
+    }
+}
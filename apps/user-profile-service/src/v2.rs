@@ -0,0 +1,158 @@
+//! `/api/v2/users` and `/api/v2/allergens`: the v1 handlers' logic behind the v2 response
+//! shapes.
+//!
+//! v2 profiles are camelCase without the storage id, with timestamps from
+//! [`yoloeats_versioning::timestamp`]; the allergen list comes in the shared [`Page`]
+//! envelope. Requests take the v1 payloads.
+
+use crate::{
+    errors::Result,
+    handlers::{self, load_profile},
+    models::{AllergenInfo, RiskLevel, UpdateProfilePayload, UserProfile},
+    state::AppState,
+};
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use serde::Serialize;
+use std::sync::Arc;
+use tracing::instrument;
+use utoipa::ToSchema;
+use yoloeats_domain::ErrorBody;
+use yoloeats_metrics::ValidJson;
+use yoloeats_pagination::Page;
+use yoloeats_versioning::timestamp;
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileV2 {
+    pub user_id: String,
+    pub username: Option<String>,
+    pub email: Option<String>,
+    pub allergens: Vec<String>,
+    pub dietary_preferences: Vec<String>,
+    pub risk_tolerance: RiskLevel,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl From<UserProfile> for ProfileV2 {
+    fn from(profile: UserProfile) -> Self {
+        ProfileV2 {
+            user_id: profile.user_id,
+            username: profile.username,
+            email: profile.email,
+            allergens: profile.allergens,
+            dietary_preferences: profile.dietary_prefs,
+            risk_tolerance: profile.risk_tolerance,
+            created_at: timestamp(&profile.created_at),
+            updated_at: timestamp(&profile.updated_at),
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v2/users/{user_id}/profile",
+    operation_id = "get_profile_v2",
+    tag = "v2",
+    params(("user_id" = String, Path, description = "The token's subject.")),
+    responses(
+        (status = 200, description = "The profile.", body = ProfileV2),
+        (status = 401, description = "No valid bearer token.", body = ErrorBody),
+        (status = 403, description = "The token is another user's.", body = ErrorBody),
+        (status = 404, description = "The user has no profile.", body = ErrorBody),
+        (status = 500, description = "MongoDB or Redis failed.", body = ErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[instrument(skip(state), fields(user_id = %user_id_param))]
+pub async fn get_profile(
+    State(state): State<Arc<AppState>>,
+    Path(user_id_param): Path<String>,
+) -> Result<Json<ProfileV2>> {
+    let profile = load_profile(&state, &user_id_param).await?;
+    Ok(Json(profile.into()))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v2/users/{user_id}/profile",
+    operation_id = "update_profile_v2",
+    tag = "v2",
+    params(("user_id" = String, Path, description = "The token's subject.")),
+    request_body = UpdateProfilePayload,
+    responses(
+        (status = 200, description = "The profile, created if the user had none.", body = ProfileV2),
+        (status = 400, description = "No field at all, or a body that is not JSON.", body = ErrorBody),
+        (status = 401, description = "No valid bearer token.", body = ErrorBody),
+        (status = 403, description = "The token is another user's.", body = ErrorBody),
+        (status = 409, description = "A unique key conflicted on save.", body = ErrorBody),
+        (status = 422, description = "An invalid field.", body = ErrorBody),
+        (status = 500, description = "MongoDB failed.", body = ErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[instrument(skip(state, payload), fields(user_id = %user_id_param))]
+pub async fn update_profile(
+    state: State<Arc<AppState>>,
+    Path(user_id_param): Path<String>,
+    payload: ValidJson<UpdateProfilePayload>,
+) -> Result<Json<ProfileV2>> {
+    let Json(profile) = handlers::update_profile(state, Path(user_id_param), payload).await?;
+    Ok(Json(profile.into()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v2/allergens",
+    operation_id = "get_allergens_v2",
+    tag = "v2",
+    responses(
+        (status = 200, description = "The common allergens, all on one page.", body = Page<AllergenInfo>),
+        (status = 500, description = "MongoDB or Redis failed.", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state))]
+pub async fn get_allergens(state: State<Arc<AppState>>) -> Result<Json<Page<AllergenInfo>>> {
+    let Json(allergens) = handlers::get_allergens(state).await?;
+    let total = allergens.len() as u64;
+    Ok(Json(Page::new(allergens).with_total(total)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+
+    #[test]
+    fn profile_maps_to_the_v2_shape() {
+        let created = Utc.with_ymd_and_hms(2024, 6, 1, 8, 0, 0).unwrap();
+        let profile = UserProfile {
+            id: None,
+            user_id: "alice".to_string(),
+            username: None,
+            email: Some("alice@example.com".to_string()),
+            allergens: vec!["milk".to_string()],
+            dietary_prefs: vec!["vegan".to_string()],
+            risk_tolerance: RiskLevel::Low,
+            created_at: created,
+            updated_at: created + chrono::Duration::milliseconds(250),
+        };
+        assert_eq!(
+            serde_json::to_value(ProfileV2::from(profile)).unwrap(),
+            json!({
+                "userId": "alice",
+                "username": null,
+                "email": "alice@example.com",
+                "allergens": ["milk"],
+                "dietaryPreferences": ["vegan"],
+                "riskTolerance": "low",
+                "createdAt": "2024-06-01T08:00:00Z",
+                "updatedAt": "2024-06-01T08:00:00Z",
+            })
+        );
+    }
+}
@@ -92,13 +92,15 @@ class ProductApiService {
       final response = await _dio.get('/products/search', queryParameters: queryParams);
 
       if (response.statusCode == 200) {
-        final List<dynamic>? jsonData = response.data as List<dynamic>?;
-        if (jsonData != null) {
-          return jsonData
+        // Paged envelope: {"items": [...], "total": ..., "nextCursor": ...}
+        final Map<String, dynamic>? page = response.data as Map<String, dynamic>?;
+        final List<dynamic>? items = page?['items'] as List<dynamic>?;
+        if (items != null) {
+          return items
               .map((item) => Product.fromJson(item as Map<String, dynamic>))
               .toList();
         } else {
-          print('API Service: Product search returned 200 but data is null or not a list.');
+          print('API Service: Product search returned 200 but no items list.');
           return [];
         }
       } else {
@@ -0,0 +1,199 @@
+//! The key-value cache in front of the services' databases: Redis, or a process-local
+//! map when running on in-memory storage.
+
+use async_trait::async_trait;
+use redis::{AsyncCommands, RedisResult, aio::MultiplexedConnection};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Hands out a connection per request. Failing to connect is an error the handlers
+/// report; a failed command on an open connection is logged and treated as a miss.
+#[async_trait]
+pub trait Cache: Send + Sync {
+    async fn connect(&self) -> RedisResult<Box<dyn CacheConnection>>;
+}
+
+#[async_trait]
+pub trait CacheConnection: Send {
+    /// `None` when the key is missing or expired.
+    async fn get(&mut self, key: &str) -> RedisResult<Option<String>>;
+
+    /// [`get`](Self::get) for several keys in one round trip; one value per key, in order.
+    async fn mget(&mut self, keys: &[&str]) -> RedisResult<Vec<Option<String>>>;
+
+    async fn set_ex(&mut self, key: &str, value: &str, ttl_secs: u64) -> RedisResult<()>;
+
+    /// [`set_ex`](Self::set_ex) for every `(key, value)`, pipelined into one round trip.
+    async fn set_ex_many(&mut self, entries: &[(String, String)], ttl_secs: u64)
+    -> RedisResult<()>;
+
+    /// Returns how many of `keys` existed.
+    async fn del(&mut self, keys: &[&str]) -> RedisResult<i64>;
+}
+
+pub struct RedisCache {
+    client: redis::Client,
+}
+
+impl RedisCache {
+    pub fn new(client: redis::Client) -> Self {
+        RedisCache { client }
+    }
+}
+
+#[async_trait]
+impl Cache for RedisCache {
+    async fn connect(&self) -> RedisResult<Box<dyn CacheConnection>> {
+        let conn = self.client.get_multiplexed_async_connection().await?;
+        Ok(Box::new(RedisCacheConnection(conn)))
+    }
+}
+
+struct RedisCacheConnection(MultiplexedConnection);
+
+#[async_trait]
+impl CacheConnection for RedisCacheConnection {
+    async fn get(&mut self, key: &str) -> RedisResult<Option<String>> {
+        self.0.get(key).await
+    }
+
+    async fn mget(&mut self, keys: &[&str]) -> RedisResult<Vec<Option<String>>> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        redis::cmd("MGET").arg(keys).query_async(&mut self.0).await
+    }
+
+    async fn set_ex(&mut self, key: &str, value: &str, ttl_secs: u64) -> RedisResult<()> {
+        self.0.set_ex(key, value, ttl_secs).await
+    }
+
+    async fn set_ex_many(
+        &mut self,
+        entries: &[(String, String)],
+        ttl_secs: u64,
+    ) -> RedisResult<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let mut pipe = redis::pipe();
+        for (key, value) in entries {
+            pipe.set_ex(key, value, ttl_secs).ignore();
+        }
+        pipe.query_async(&mut self.0).await
+    }
+
+    async fn del(&mut self, keys: &[&str]) -> RedisResult<i64> {
+        self.0.del(keys).await
+    }
+}
+
+/// Process-local cache honouring TTLs. Clones share the same map.
+#[derive(Clone, Default)]
+pub struct MemoryCache {
+    entries: Arc<Mutex<HashMap<String, (String, Instant)>>>,
+}
+
+#[async_trait]
+impl Cache for MemoryCache {
+    async fn connect(&self) -> RedisResult<Box<dyn CacheConnection>> {
+        Ok(Box::new(self.clone()))
+    }
+}
+
+#[async_trait]
+impl CacheConnection for MemoryCache {
+    async fn get(&mut self, key: &str) -> RedisResult<Option<String>> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some((value, expires_at)) if *expires_at > Instant::now() => Ok(Some(value.clone())),
+            Some(_) => {
+                entries.remove(key);
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn mget(&mut self, keys: &[&str]) -> RedisResult<Vec<Option<String>>> {
+        let mut values = Vec::with_capacity(keys.len());
+        for key in keys {
+            values.push(self.get(key).await?);
+        }
+        Ok(values)
+    }
+
+    async fn set_ex(&mut self, key: &str, value: &str, ttl_secs: u64) -> RedisResult<()> {
+        let expires_at = Instant::now() + Duration::from_secs(ttl_secs);
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), (value.to_string(), expires_at));
+        Ok(())
+    }
+
+    async fn set_ex_many(
+        &mut self,
+        entries: &[(String, String)],
+        ttl_secs: u64,
+    ) -> RedisResult<()> {
+        for (key, value) in entries {
+            self.set_ex(key, value, ttl_secs).await?;
+        }
+        Ok(())
+    }
+
+    async fn del(&mut self, keys: &[&str]) -> RedisResult<i64> {
+        let mut entries = self.entries.lock().unwrap();
+        Ok(keys
+            .iter()
+            .filter(|key| entries.remove(**key).is_some())
+            .count() as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn memory_cache_round_trips_and_deletes() {
+        let cache = MemoryCache::default();
+        let mut conn = cache.connect().await.unwrap();
+        assert_eq!(conn.get("a").await.unwrap(), None);
+
+        conn.set_ex("a", "1", 60).await.unwrap();
+        conn.set_ex("b", "2", 60).await.unwrap();
+        // A second connection sees the same entries.
+        let mut other = cache.connect().await.unwrap();
+        assert_eq!(other.get("a").await.unwrap(), Some("1".to_string()));
+
+        assert_eq!(conn.del(&["a", "b", "missing"]).await.unwrap(), 2);
+        assert_eq!(other.get("b").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn memory_cache_batches_keep_key_order() {
+        let mut conn = MemoryCache::default().connect().await.unwrap();
+        let entries = [
+            ("a".to_string(), "1".to_string()),
+            ("c".to_string(), "3".to_string()),
+        ];
+        conn.set_ex_many(&entries, 60).await.unwrap();
+        assert_eq!(
+            conn.mget(&["c", "b", "a"]).await.unwrap(),
+            [Some("3".to_string()), None, Some("1".to_string())]
+        );
+        assert!(conn.mget(&[]).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn memory_cache_expires_entries() {
+        let mut conn = MemoryCache::default().connect().await.unwrap();
+        conn.set_ex("gone", "x", 0).await.unwrap();
+        assert_eq!(conn.get("gone").await.unwrap(), None);
+    }
+}
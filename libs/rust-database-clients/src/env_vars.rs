@@ -0,0 +1,26 @@
+use crate::ConfigError;
+
+/// `value`, the content of `var`, as a whole number no smaller than `min`; `None` when
+/// unset or blank.
+pub(crate) fn number_at_least(
+    var: &str,
+    value: Option<String>,
+    min: u64,
+) -> Result<Option<u64>, ConfigError> {
+    match value {
+        Some(value) if !value.trim().is_empty() => match value.trim().parse::<u64>() {
+            Ok(parsed) if parsed >= min => Ok(Some(parsed)),
+            _ => Err(ConfigError::InvalidVariable(format!(
+                "{} '{}': expected an integer of at least {}",
+                var, value, min
+            ))),
+        },
+        _ => Ok(None),
+    }
+}
+
+/// The environment variable `var` as a whole number above zero; `None` when unset.
+#[cfg(feature = "http")]
+pub(crate) fn positive_var(var: &str) -> Result<Option<u64>, ConfigError> {
+    number_at_least(var, std::env::var(var).ok(), 1)
+}
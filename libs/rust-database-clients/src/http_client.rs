@@ -0,0 +1,127 @@
+use reqwest::Client;
+use std::time::Duration;
+
+use crate::{ConfigError, env_vars::positive_var};
+
+pub const HTTP_CONNECT_TIMEOUT_MS_ENV: &str = "HTTP_CONNECT_TIMEOUT_MS";
+pub const HTTP_REQUEST_TIMEOUT_MS_ENV: &str = "HTTP_REQUEST_TIMEOUT_MS";
+pub const HTTP_POOL_IDLE_TIMEOUT_SECS_ENV: &str = "HTTP_POOL_IDLE_TIMEOUT_SECS";
+pub const HTTP_POOL_MAX_IDLE_PER_HOST_ENV: &str = "HTTP_POOL_MAX_IDLE_PER_HOST";
+
+/// How the services' outbound `reqwest::Client` connects and how long it waits. A
+/// request's own `timeout` (the OpenFoodFacts fallback, the embedding service, webhook
+/// deliveries) replaces `request_timeout` for that request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HttpClientConfig {
+    /// Establishing the TCP (and TLS) connection.
+    pub connect_timeout: Duration,
+    /// A whole request, from connecting until the body is read.
+    pub request_timeout: Duration,
+    /// How long an unused pooled connection is kept.
+    pub pool_idle_timeout: Duration,
+    /// Unused connections kept per host.
+    pub pool_max_idle_per_host: usize,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        HttpClientConfig {
+            connect_timeout: Duration::from_secs(2),
+            request_timeout: Duration::from_secs(10),
+            pool_idle_timeout: Duration::from_secs(90),
+            pool_max_idle_per_host: 32,
+        }
+    }
+}
+
+impl HttpClientConfig {
+    /// The defaults, overridden by [`HTTP_CONNECT_TIMEOUT_MS_ENV`],
+    /// [`HTTP_REQUEST_TIMEOUT_MS_ENV`], [`HTTP_POOL_IDLE_TIMEOUT_SECS_ENV`] and
+    /// [`HTTP_POOL_MAX_IDLE_PER_HOST_ENV`] where set. Zero or anything but a whole number
+    /// is rejected rather than read as "no limit".
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let defaults = HttpClientConfig::default();
+        Ok(HttpClientConfig {
+            connect_timeout: positive_var(HTTP_CONNECT_TIMEOUT_MS_ENV)?
+                .map_or(defaults.connect_timeout, Duration::from_millis),
+            request_timeout: positive_var(HTTP_REQUEST_TIMEOUT_MS_ENV)?
+                .map_or(defaults.request_timeout, Duration::from_millis),
+            pool_idle_timeout: positive_var(HTTP_POOL_IDLE_TIMEOUT_SECS_ENV)?
+                .map_or(defaults.pool_idle_timeout, Duration::from_secs),
+            pool_max_idle_per_host: positive_var(HTTP_POOL_MAX_IDLE_PER_HOST_ENV)?
+                .map_or(defaults.pool_max_idle_per_host, |max| max as usize),
+        })
+    }
+
+    pub fn build(&self) -> reqwest::Result<Client> {
+        Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.request_timeout)
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+    use tokio::net::{TcpSocket, TcpStream};
+    use wiremock::{Mock, MockServer, ResponseTemplate, matchers::method};
+
+    #[tokio::test]
+    async fn a_slow_upstream_times_out_instead_of_hanging() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(30)))
+            .mount(&server)
+            .await;
+        let client = HttpClientConfig {
+            request_timeout: Duration::from_millis(100),
+            ..HttpClientConfig::default()
+        }
+        .build()
+        .unwrap();
+
+        let outcome = tokio::time::timeout(Duration::from_secs(5), client.get(server.uri()).send())
+            .await
+            .expect("the client gives up on its own");
+        assert!(outcome.unwrap_err().is_timeout());
+    }
+
+    #[tokio::test]
+    async fn unreachable_hosts_fail_within_the_connect_timeout() {
+        // A local listener that never accepts, its backlog filled: a new connection is
+        // neither accepted nor refused, its handshake just goes unanswered.
+        let socket = TcpSocket::new_v4().unwrap();
+        socket.bind(([127, 0, 0, 1], 0).into()).unwrap();
+        let listener = socket.listen(1).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut queued = Vec::new();
+        while let Ok(Ok(stream)) =
+            tokio::time::timeout(Duration::from_millis(200), TcpStream::connect(addr)).await
+        {
+            queued.push(stream);
+            assert!(queued.len() < 64, "the listener's backlog never filled");
+        }
+        let connect_timeout = Duration::from_millis(100);
+        let client = HttpClientConfig {
+            connect_timeout,
+            ..HttpClientConfig::default()
+        }
+        .build()
+        .unwrap();
+
+        let started = Instant::now();
+        let outcome = tokio::time::timeout(
+            Duration::from_secs(5),
+            client.get(format!("http://{}/", addr)).send(),
+        )
+        .await
+        .expect("the client gives up on its own");
+        let err = outcome.unwrap_err();
+        assert!(err.is_connect() || err.is_timeout(), "{:?}", err);
+        assert!(started.elapsed() >= connect_timeout, "{:?}", err);
+    }
+}
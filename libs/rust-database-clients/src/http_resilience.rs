@@ -0,0 +1,743 @@
+use reqwest::{
+    Method, Request, Response, Url,
+    header::{CONTENT_TYPE, HeaderMap, HeaderValue},
+};
+use reqwest_middleware::ClientWithMiddleware;
+use serde::{Serialize, de::DeserializeOwned};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+use crate::{
+    ConfigError,
+    env_vars::positive_var,
+    retry::{RetryPolicy, retry},
+};
+
+pub const UPSTREAM_FAILURE_THRESHOLD_ENV: &str = "UPSTREAM_FAILURE_THRESHOLD";
+pub const UPSTREAM_OPEN_COOLDOWN_SECS_ENV: &str = "UPSTREAM_OPEN_COOLDOWN_SECS";
+
+/// Gauge of each host's breaker as of its latest call: 0 closed, 1 half-open, 2 open.
+pub const UPSTREAM_CIRCUIT_STATE: &str = "upstream_circuit_state";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpstreamErrorKind {
+    Timeout,
+    Open,
+    Status(u16),
+    Transport,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Upstream call to '{host}' failed: {kind:?}")]
+pub struct UpstreamError {
+    pub kind: UpstreamErrorKind,
+    pub host: String,
+}
+
+impl UpstreamError {
+    fn new(kind: UpstreamErrorKind, host: &str) -> Self {
+        UpstreamError {
+            kind,
+            host: host.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ResilienceConfig {
+    /// Applied to each attempt unless the request already carries its own timeout.
+    pub request_timeout: Duration,
+    /// Extra attempts after the first one; only used for idempotent methods.
+    pub max_retries: u32,
+    /// Longest delay before the first retry, doubled for every following one; each
+    /// delay is jittered as [`RetryPolicy::delay`] describes.
+    pub retry_base_delay: Duration,
+    /// Consecutive failures that trip a host's breaker.
+    pub failure_threshold: u32,
+    /// How long a tripped breaker rejects calls before letting a probe through.
+    pub open_cooldown: Duration,
+}
+
+impl Default for ResilienceConfig {
+    fn default() -> Self {
+        ResilienceConfig {
+            request_timeout: Duration::from_secs(5),
+            max_retries: 2,
+            retry_base_delay: Duration::from_millis(100),
+            failure_threshold: 5,
+            open_cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+impl ResilienceConfig {
+    /// The defaults, with the breaker tuned by [`UPSTREAM_FAILURE_THRESHOLD_ENV`] and
+    /// [`UPSTREAM_OPEN_COOLDOWN_SECS_ENV`] where set.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let defaults = ResilienceConfig::default();
+        let failure_threshold = positive_var(UPSTREAM_FAILURE_THRESHOLD_ENV)?
+            .map_or(defaults.failure_threshold, |threshold| {
+                threshold.min(u32::MAX as u64) as u32
+            });
+        let open_cooldown = positive_var(UPSTREAM_OPEN_COOLDOWN_SECS_ENV)?
+            .map_or(defaults.open_cooldown, Duration::from_secs);
+        Ok(ResilienceConfig {
+            failure_threshold,
+            open_cooldown,
+            ..defaults
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+impl BreakerState {
+    /// The value [`UPSTREAM_CIRCUIT_STATE`] reports.
+    pub fn gauge_value(self) -> f64 {
+        match self {
+            BreakerState::Closed => 0.0,
+            BreakerState::HalfOpen => 1.0,
+            BreakerState::Open => 2.0,
+        }
+    }
+}
+
+/// Per-host breaker. All transitions take `now` explicitly so they can be driven
+/// deterministically in tests.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    open_cooldown: Duration,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    probe_in_flight: bool,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, open_cooldown: Duration) -> Self {
+        CircuitBreaker {
+            failure_threshold: failure_threshold.max(1),
+            open_cooldown,
+            consecutive_failures: 0,
+            opened_at: None,
+            probe_in_flight: false,
+        }
+    }
+
+    pub fn state(&self, now: Instant) -> BreakerState {
+        match self.opened_at {
+            None => BreakerState::Closed,
+            Some(opened_at) if now.duration_since(opened_at) >= self.open_cooldown => {
+                BreakerState::HalfOpen
+            }
+            Some(_) => BreakerState::Open,
+        }
+    }
+
+    /// Returns whether a call may proceed. In half-open state only a single probe
+    /// is let through until its outcome is recorded.
+    pub fn try_acquire(&mut self, now: Instant) -> bool {
+        match self.state(now) {
+            BreakerState::Closed => true,
+            BreakerState::Open => false,
+            BreakerState::HalfOpen if self.probe_in_flight => false,
+            BreakerState::HalfOpen => {
+                self.probe_in_flight = true;
+                true
+            }
+        }
+    }
+
+    pub fn record_success(&mut self) {
+        self.consecutive_failures = 0;
+        self.opened_at = None;
+        self.probe_in_flight = false;
+    }
+
+    pub fn record_failure(&mut self, now: Instant) {
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        let was_probe = self.probe_in_flight;
+        self.probe_in_flight = false;
+        if was_probe || self.consecutive_failures >= self.failure_threshold {
+            self.opened_at = Some(now);
+        }
+    }
+
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BreakerSnapshot {
+    pub host: String,
+    pub state: BreakerState,
+    pub consecutive_failures: u32,
+}
+
+/// HTTP client wrapper adding per-host circuit breakers, timeouts and retries
+/// of idempotent requests. Cheap to clone; clones share breaker state.
+#[derive(Clone)]
+pub struct ResilientClient {
+    client: ClientWithMiddleware,
+    config: ResilienceConfig,
+    breakers: Arc<Mutex<HashMap<String, CircuitBreaker>>>,
+}
+
+impl ResilientClient {
+    /// Accepts a plain `reqwest::Client` or one already wrapped in middleware.
+    pub fn new(client: impl Into<ClientWithMiddleware>, config: ResilienceConfig) -> Self {
+        ResilientClient {
+            client: client.into(),
+            config,
+            breakers: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn inner(&self) -> &ClientWithMiddleware {
+        &self.client
+    }
+
+    /// Breaker state for every host contacted so far, for health/readiness output.
+    pub fn breaker_snapshots(&self) -> Vec<BreakerSnapshot> {
+        let now = Instant::now();
+        let breakers = self.breakers.lock().unwrap_or_else(|e| e.into_inner());
+        let mut snapshots: Vec<BreakerSnapshot> = breakers
+            .iter()
+            .map(|(host, breaker)| BreakerSnapshot {
+                host: host.clone(),
+                state: breaker.state(now),
+                consecutive_failures: breaker.consecutive_failures(),
+            })
+            .collect();
+        snapshots.sort_by(|a, b| a.host.cmp(&b.host));
+        snapshots
+    }
+
+    /// Breaker state for the host of `url`; `None` until that host has been called.
+    pub fn breaker_snapshot(&self, url: &str) -> Option<BreakerSnapshot> {
+        let host = host_key(&Url::parse(url).ok()?);
+        let breakers = self.breakers.lock().unwrap_or_else(|e| e.into_inner());
+        breakers.get(&host).map(|breaker| BreakerSnapshot {
+            state: breaker.state(Instant::now()),
+            consecutive_failures: breaker.consecutive_failures(),
+            host,
+        })
+    }
+
+    /// GETs `url` and decodes a 2xx JSON body. Non-2xx statuses become `Status(code)`.
+    pub async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T, UpstreamError> {
+        let parsed = parse_url(url)?;
+        self.fetch_json(Request::new(Method::GET, parsed)).await
+    }
+
+    /// [`get_json`](Self::get_json) with `headers` on the request, e.g. a caller's
+    /// `Authorization` to forward.
+    pub async fn get_json_with_headers<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        headers: HeaderMap,
+    ) -> Result<T, UpstreamError> {
+        let mut request = Request::new(Method::GET, parse_url(url)?);
+        *request.headers_mut() = headers;
+        self.fetch_json(request).await
+    }
+
+    /// POSTs `body` as JSON to `url` and decodes a 2xx JSON body, like
+    /// [`get_json`](Self::get_json). Not retried: POST is not idempotent.
+    pub async fn post_json<B: Serialize, T: DeserializeOwned>(
+        &self,
+        url: &str,
+        body: &B,
+    ) -> Result<T, UpstreamError> {
+        self.fetch_json(json_post(url, body)?).await
+    }
+
+    /// [`post_json`](Self::post_json) for endpoints known to be slow, waiting `timeout`
+    /// instead of the configured `request_timeout`.
+    pub async fn post_json_with_timeout<B: Serialize, T: DeserializeOwned>(
+        &self,
+        url: &str,
+        body: &B,
+        timeout: Duration,
+    ) -> Result<T, UpstreamError> {
+        let mut request = json_post(url, body)?;
+        *request.timeout_mut() = Some(timeout);
+        self.fetch_json(request).await
+    }
+
+    async fn fetch_json<T: DeserializeOwned>(&self, request: Request) -> Result<T, UpstreamError> {
+        let host = host_key(request.url());
+        let response = self.send(request).await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(UpstreamError::new(
+                UpstreamErrorKind::Status(status.as_u16()),
+                &host,
+            ));
+        }
+        response.json::<T>().await.map_err(|e| {
+            tracing::warn!(host = %host, "Failed to decode upstream JSON: {}", e);
+            UpstreamError::new(UpstreamErrorKind::Transport, &host)
+        })
+    }
+
+    /// Sends `request` through the host's breaker. Responses with a status below 500
+    /// are returned as-is; timeouts, transport errors and 5xx count as failures and are
+    /// retried with jittered exponential backoff when the method is idempotent.
+    pub async fn send(&self, request: Request) -> Result<Response, UpstreamError> {
+        let host = host_key(request.url());
+        let host = host.as_str();
+        // Bodies that are streams cannot be cloned; those requests are sent once.
+        let retried = is_idempotent(request.method()) && request.try_clone().is_some();
+        let policy = RetryPolicy {
+            attempts: if retried {
+                self.config.max_retries + 1
+            } else {
+                1
+            },
+            base_delay: self.config.retry_base_delay,
+        };
+
+        let mut request = Some(request);
+        retry(
+            policy,
+            |error: &UpstreamError| error.kind != UpstreamErrorKind::Open,
+            |attempt| {
+                let current = if attempt < policy.attempts {
+                    request.as_ref().and_then(Request::try_clone)
+                } else {
+                    request.take()
+                };
+                let current = match current {
+                    Some(req) => req,
+                    None => unreachable!("only requests that clone are retried"),
+                };
+                self.attempt(host, current, attempt)
+            },
+        )
+        .await
+    }
+
+    async fn attempt(
+        &self,
+        host: &str,
+        mut request: Request,
+        attempt: u32,
+    ) -> Result<Response, UpstreamError> {
+        if !self.try_acquire(host) {
+            tracing::warn!(host = %host, "Circuit open, failing fast");
+            return Err(UpstreamError::new(UpstreamErrorKind::Open, host));
+        }
+        if request.timeout().is_none() {
+            *request.timeout_mut() = Some(self.config.request_timeout);
+        }
+
+        let failure = match self.client.execute(request).await {
+            Ok(response) if !response.status().is_server_error() => {
+                self.record(host, true);
+                return Ok(response);
+            }
+            Ok(response) => UpstreamErrorKind::Status(response.status().as_u16()),
+            Err(reqwest_middleware::Error::Reqwest(e)) if e.is_timeout() => {
+                UpstreamErrorKind::Timeout
+            }
+            Err(e) => {
+                tracing::debug!(host = %host, "Upstream transport error: {}", e);
+                UpstreamErrorKind::Transport
+            }
+        };
+        self.record(host, false);
+        tracing::warn!(host = %host, attempt, "Upstream call failed: {:?}", failure);
+        Err(UpstreamError::new(failure, host))
+    }
+
+    fn try_acquire(&self, host: &str) -> bool {
+        let now = Instant::now();
+        let mut breakers = self.breakers.lock().unwrap_or_else(|e| e.into_inner());
+        let breaker = breakers.entry(host.to_string()).or_insert_with(|| {
+            CircuitBreaker::new(self.config.failure_threshold, self.config.open_cooldown)
+        });
+        let acquired = breaker.try_acquire(now);
+        publish_state(host, breaker.state(now));
+        acquired
+    }
+
+    fn record(&self, host: &str, success: bool) {
+        let now = Instant::now();
+        let mut breakers = self.breakers.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(breaker) = breakers.get_mut(host) {
+            if success {
+                breaker.record_success();
+            } else {
+                breaker.record_failure(now);
+            }
+            publish_state(host, breaker.state(now));
+        }
+    }
+}
+
+fn publish_state(host: &str, state: BreakerState) {
+    metrics::gauge!(UPSTREAM_CIRCUIT_STATE, "host" => host.to_string()).set(state.gauge_value());
+}
+
+fn json_post<B: Serialize>(url: &str, body: &B) -> Result<Request, UpstreamError> {
+    let parsed = parse_url(url)?;
+    let payload = serde_json::to_vec(body).map_err(|e| {
+        tracing::warn!("Failed to encode upstream JSON for '{}': {}", url, e);
+        UpstreamError::new(UpstreamErrorKind::Transport, url)
+    })?;
+    let mut request = Request::new(Method::POST, parsed);
+    request
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    *request.body_mut() = Some(payload.into());
+    Ok(request)
+}
+
+fn parse_url(url: &str) -> Result<Url, UpstreamError> {
+    Url::parse(url).map_err(|e| {
+        tracing::warn!("Invalid upstream URL '{}': {}", url, e);
+        UpstreamError::new(UpstreamErrorKind::Transport, url)
+    })
+}
+
+fn host_key(url: &Url) -> String {
+    match (url.host_str(), url.port_or_known_default()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_string(),
+        (None, _) => url.as_str().to_string(),
+    }
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS | Method::TRACE
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metrics_exporter_prometheus::PrometheusBuilder;
+    use reqwest::Client as HttpClient;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{body_json, header, method, path},
+    };
+
+    #[test]
+    fn breaker_opens_after_threshold_and_recovers_via_probe() {
+        let start = Instant::now();
+        let mut breaker = CircuitBreaker::new(3, Duration::from_secs(10));
+
+        for _ in 0..2 {
+            assert!(breaker.try_acquire(start));
+            breaker.record_failure(start);
+        }
+        assert_eq!(breaker.state(start), BreakerState::Closed);
+
+        assert!(breaker.try_acquire(start));
+        breaker.record_failure(start);
+        assert_eq!(breaker.state(start), BreakerState::Open);
+        assert!(!breaker.try_acquire(start + Duration::from_secs(9)));
+
+        let later = start + Duration::from_secs(10);
+        assert_eq!(breaker.state(later), BreakerState::HalfOpen);
+        assert!(breaker.try_acquire(later));
+        assert!(!breaker.try_acquire(later), "only one probe in half-open");
+
+        breaker.record_success();
+        assert_eq!(breaker.state(later), BreakerState::Closed);
+        assert_eq!(breaker.consecutive_failures(), 0);
+    }
+
+    #[test]
+    fn failed_probe_reopens_breaker() {
+        let start = Instant::now();
+        let mut breaker = CircuitBreaker::new(1, Duration::from_secs(5));
+        breaker.record_failure(start);
+        assert_eq!(breaker.state(start), BreakerState::Open);
+
+        let probe_time = start + Duration::from_secs(5);
+        assert!(breaker.try_acquire(probe_time));
+        breaker.record_failure(probe_time);
+        assert_eq!(breaker.state(probe_time), BreakerState::Open);
+        assert_eq!(
+            breaker.state(probe_time + Duration::from_secs(5)),
+            BreakerState::HalfOpen
+        );
+    }
+
+    #[test]
+    fn success_resets_failure_count() {
+        let now = Instant::now();
+        let mut breaker = CircuitBreaker::new(2, Duration::from_secs(5));
+        breaker.record_failure(now);
+        breaker.record_success();
+        breaker.record_failure(now);
+        assert_eq!(breaker.state(now), BreakerState::Closed);
+    }
+
+    fn test_config() -> ResilienceConfig {
+        ResilienceConfig {
+            request_timeout: Duration::from_millis(100),
+            max_retries: 2,
+            retry_base_delay: Duration::ZERO,
+            failure_threshold: 10,
+            open_cooldown: Duration::from_secs(60),
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_idempotent_get_after_server_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/profile"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/profile"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"ok": true})))
+            .mount(&server)
+            .await;
+
+        let client = ResilientClient::new(HttpClient::new(), test_config());
+        let body: serde_json::Value = client
+            .get_json(&format!("{}/profile", server.uri()))
+            .await
+            .unwrap();
+        assert_eq!(body["ok"], true);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_post() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = ResilientClient::new(HttpClient::new(), test_config());
+        let request = client
+            .inner()
+            .post(format!("{}/check", server.uri()))
+            .build()
+            .unwrap();
+        let err = client.send(request).await.unwrap_err();
+        assert_eq!(err.kind, UpstreamErrorKind::Status(500));
+    }
+
+    #[tokio::test]
+    async fn get_json_with_headers_sends_them() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(header("authorization", "Bearer abc"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"ok": true})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = ResilientClient::new(HttpClient::new(), test_config());
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", HeaderValue::from_static("Bearer abc"));
+        let answer: serde_json::Value = client
+            .get_json_with_headers(&server.uri(), headers)
+            .await
+            .unwrap();
+        assert_eq!(answer["ok"], true);
+    }
+
+    #[tokio::test]
+    async fn post_json_sends_and_decodes_json_once() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/embed"))
+            .and(body_json(serde_json::json!({"texts": ["milk"]})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"ok": true})))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/down"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = ResilientClient::new(HttpClient::new(), test_config());
+        let body = serde_json::json!({"texts": ["milk"]});
+        let answer: serde_json::Value = client
+            .post_json(&format!("{}/embed", server.uri()), &body)
+            .await
+            .unwrap();
+        assert_eq!(answer["ok"], true);
+        let err = client
+            .post_json::<_, serde_json::Value>(&format!("{}/down", server.uri()), &body)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind, UpstreamErrorKind::Status(503));
+    }
+
+    #[tokio::test]
+    async fn slow_upstream_yields_timeout() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(500)))
+            .mount(&server)
+            .await;
+
+        let client = ResilientClient::new(HttpClient::new(), test_config());
+        let err = client
+            .get_json::<serde_json::Value>(&format!("{}/slow", server.uri()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind, UpstreamErrorKind::Timeout);
+    }
+
+    #[tokio::test]
+    async fn slow_endpoints_get_their_own_timeout() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"ok": true}))
+                    .set_delay(Duration::from_millis(300)),
+            )
+            .mount(&server)
+            .await;
+
+        let client = ResilientClient::new(HttpClient::new(), test_config());
+        let url = format!("{}/embed", server.uri());
+        let body = serde_json::json!({"texts": ["milk"]});
+        let err = client
+            .post_json::<_, serde_json::Value>(&url, &body)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind, UpstreamErrorKind::Timeout);
+
+        let answer: serde_json::Value = client
+            .post_json_with_timeout(&url, &body, Duration::from_secs(2))
+            .await
+            .unwrap();
+        assert_eq!(answer["ok"], true);
+        let err = client
+            .post_json_with_timeout::<_, serde_json::Value>(&url, &body, Duration::from_millis(50))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind, UpstreamErrorKind::Timeout);
+    }
+
+    #[tokio::test]
+    async fn open_breaker_fails_fast_and_is_visible_in_snapshots() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let config = ResilienceConfig {
+            max_retries: 0,
+            failure_threshold: 2,
+            ..test_config()
+        };
+        let client = ResilientClient::new(HttpClient::new(), config);
+        let url = format!("{}/down", server.uri());
+        for _ in 0..2 {
+            let err = client.get_json::<serde_json::Value>(&url).await.unwrap_err();
+            assert_eq!(err.kind, UpstreamErrorKind::Status(500));
+        }
+
+        let err = client.get_json::<serde_json::Value>(&url).await.unwrap_err();
+        assert_eq!(err.kind, UpstreamErrorKind::Open);
+
+        let snapshots = client.breaker_snapshots();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].state, BreakerState::Open);
+    }
+
+    #[test]
+    fn breaker_state_is_published_per_host() {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let host = metrics::with_local_recorder(&recorder, || {
+            runtime.block_on(async {
+                let server = MockServer::start().await;
+                Mock::given(method("GET"))
+                    .respond_with(ResponseTemplate::new(500))
+                    .mount(&server)
+                    .await;
+                let config = ResilienceConfig {
+                    max_retries: 0,
+                    failure_threshold: 2,
+                    ..test_config()
+                };
+                let client = ResilientClient::new(HttpClient::new(), config);
+                let url = format!("{}/down", server.uri());
+                assert!(client.breaker_snapshot(&url).is_none());
+
+                client
+                    .get_json::<serde_json::Value>(&url)
+                    .await
+                    .unwrap_err();
+                let snapshot = client.breaker_snapshot(&url).unwrap();
+                assert_eq!(snapshot.state, BreakerState::Closed);
+                assert_eq!(snapshot.consecutive_failures, 1);
+
+                client
+                    .get_json::<serde_json::Value>(&url)
+                    .await
+                    .unwrap_err();
+                let snapshot = client.breaker_snapshot(&url).unwrap();
+                assert_eq!(snapshot.state, BreakerState::Open);
+                snapshot.host
+            })
+        });
+
+        let rendered = handle.render();
+        let gauge = format!("{}{{host=\"{}\"}} 2", UPSTREAM_CIRCUIT_STATE, host);
+        assert!(rendered.contains(&gauge), "{}", rendered);
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_returned_without_tripping_breaker() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+
+        let config = ResilienceConfig {
+            failure_threshold: 1,
+            ..test_config()
+        };
+        let client = ResilientClient::new(HttpClient::new(), config);
+        let err = client
+            .get_json::<serde_json::Value>(&format!("{}/missing", server.uri()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind, UpstreamErrorKind::Status(404));
+        assert_eq!(client.breaker_snapshots()[0].state, BreakerState::Closed);
+    }
+}
@@ -4,10 +4,36 @@ use redis::Commands;
 use std::env;
 use thiserror::Error;
 
+pub mod cache;
+mod env_vars;
+#[cfg(feature = "http")]
+pub mod http_client;
+#[cfg(feature = "http")]
+pub mod http_resilience;
+mod mongo;
+pub mod retry;
+pub mod serde_helpers;
+mod storage;
+mod uri_validation;
+
+pub use cache::{Cache, CacheConnection, MemoryCache, RedisCache};
+pub use mongo::{
+    DEFAULT_SERVER_SELECTION_TIMEOUT, MONGO_APP_NAME_ENV, MONGO_CONNECT_TIMEOUT_MS_ENV,
+    MONGO_MAX_POOL_SIZE_ENV, MONGO_MIN_POOL_SIZE_ENV, MONGO_SERVER_SELECTION_TIMEOUT_MS_ENV,
+    MongoSettings,
+};
+pub use retry::{RetryPolicy, retry};
+pub use storage::{STORAGE_MODE_ENV, StorageMode};
+pub use uri_validation::{
+    validate_mongo_uri, validate_neo4j_uri, validate_qdrant_uri, validate_redis_uri,
+};
+
 #[derive(Error, Debug)]
 pub enum ConfigError {
     #[error("Missing environment variable: {0}")]
     MissingVariable(String),
+    #[error("Invalid environment variable: {0}")]
+    InvalidVariable(String),
     #[error("Dotenv error: {0}")]
     Dotenv(#[from] dotenvy::Error),
 }
@@ -30,12 +56,32 @@ pub fn load_config() -> Result<(String, String), ConfigError> {
     let redis_uri =
         env::var("REDIS_URI").map_err(|_| ConfigError::MissingVariable("REDIS_URI".to_string()))?;
 
+    validate_mongo_uri(&mongo_uri)?;
+    validate_redis_uri(&redis_uri)?;
+
     Ok((mongo_uri, redis_uri))
 }
 
 pub async fn create_mongo_client(db_uri: &str) -> Result<MongoClient, mongodb::error::Error> {
+    create_mongo_client_with_settings(db_uri, &MongoSettings::default()).await
+}
+
+/// Connects with the options in `db_uri`, `settings` applied on top, and pings the
+/// server. The ping waits no longer than the server selection timeout, so a Mongo that
+/// is down fails startup within seconds.
+pub async fn create_mongo_client_with_settings(
+    db_uri: &str,
+    settings: &MongoSettings,
+) -> Result<MongoClient, mongodb::error::Error> {
     tracing::info!("Attempting to connect to MongoDB at {}", db_uri);
-    let client_options = ClientOptions::parse(db_uri).await?;
+    let mut client_options = ClientOptions::parse(db_uri).await?;
+    settings.apply(&mut client_options);
+    tracing::info!(
+        app_name = ?client_options.app_name,
+        max_pool_size = ?client_options.max_pool_size,
+        server_selection_timeout = ?client_options.server_selection_timeout,
+        "MongoDB client options"
+    );
     let client = MongoClient::with_options(client_options)?;
     client
         .database("admin")
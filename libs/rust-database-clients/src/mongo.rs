@@ -0,0 +1,199 @@
+use crate::{ConfigError, env_vars::number_at_least};
+use mongodb::options::ClientOptions;
+use std::{env, time::Duration};
+
+pub const MONGO_MAX_POOL_SIZE_ENV: &str = "MONGO_MAX_POOL_SIZE";
+pub const MONGO_MIN_POOL_SIZE_ENV: &str = "MONGO_MIN_POOL_SIZE";
+pub const MONGO_CONNECT_TIMEOUT_MS_ENV: &str = "MONGO_CONNECT_TIMEOUT_MS";
+pub const MONGO_SERVER_SELECTION_TIMEOUT_MS_ENV: &str = "MONGO_SERVER_SELECTION_TIMEOUT_MS";
+pub const MONGO_APP_NAME_ENV: &str = "MONGO_APP_NAME";
+
+/// Used when neither the settings nor the URI choose one. The driver's own 30 seconds
+/// would keep a service whose Mongo is down hanging at startup for that long.
+pub const DEFAULT_SERVER_SELECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Client options applied on top of those in the URI. `None` keeps the URI's value, or
+/// the driver's default if the URI has none.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MongoSettings {
+    /// Shown in the server's logs and `currentOp` for this client's connections.
+    pub app_name: Option<String>,
+    pub max_pool_size: Option<u32>,
+    pub min_pool_size: Option<u32>,
+    pub connect_timeout: Option<Duration>,
+    /// How long an operation waits for a suitable server, the startup `ping` included;
+    /// [`DEFAULT_SERVER_SELECTION_TIMEOUT`] if neither this nor the URI sets it.
+    pub server_selection_timeout: Option<Duration>,
+}
+
+impl MongoSettings {
+    /// Settings from the `MONGO_*` variables, the app name defaulting to `app_name` (the
+    /// service's own) unless [`MONGO_APP_NAME_ENV`] is set. Timeouts and the maximum
+    /// pool size must be above zero, and the minimum pool size no larger than the
+    /// maximum.
+    pub fn from_env(app_name: &str) -> Result<Self, ConfigError> {
+        Self::from_lookup(app_name, |var| env::var(var).ok())
+    }
+
+    fn from_lookup(
+        app_name: &str,
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        let number = |var: &str, min: u64| number_at_least(var, lookup(var), min);
+        let pool_size = |var: &str, min: u64| -> Result<Option<u32>, ConfigError> {
+            number(var, min)?
+                .map(|size| {
+                    u32::try_from(size).map_err(|_| {
+                        ConfigError::InvalidVariable(format!("{} '{}': too large", var, size))
+                    })
+                })
+                .transpose()
+        };
+
+        let settings = MongoSettings {
+            app_name: Some(
+                lookup(MONGO_APP_NAME_ENV)
+                    .map(|name| name.trim().to_string())
+                    .filter(|name| !name.is_empty())
+                    .unwrap_or_else(|| app_name.to_string()),
+            ),
+            max_pool_size: pool_size(MONGO_MAX_POOL_SIZE_ENV, 1)?,
+            min_pool_size: pool_size(MONGO_MIN_POOL_SIZE_ENV, 0)?,
+            connect_timeout: number(MONGO_CONNECT_TIMEOUT_MS_ENV, 1)?.map(Duration::from_millis),
+            server_selection_timeout: number(MONGO_SERVER_SELECTION_TIMEOUT_MS_ENV, 1)?
+                .map(Duration::from_millis),
+        };
+        match (settings.min_pool_size, settings.max_pool_size) {
+            (Some(min), Some(max)) if min > max => Err(ConfigError::InvalidVariable(format!(
+                "{} ({}) is above {} ({})",
+                MONGO_MIN_POOL_SIZE_ENV, min, MONGO_MAX_POOL_SIZE_ENV, max
+            ))),
+            _ => Ok(settings),
+        }
+    }
+
+    /// Writes the settings that are set into `options`.
+    pub fn apply(&self, options: &mut ClientOptions) {
+        if let Some(app_name) = &self.app_name {
+            options.app_name = Some(app_name.clone());
+        }
+        if let Some(max_pool_size) = self.max_pool_size {
+            options.max_pool_size = Some(max_pool_size);
+        }
+        if let Some(min_pool_size) = self.min_pool_size {
+            options.min_pool_size = Some(min_pool_size);
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            options.connect_timeout = Some(connect_timeout);
+        }
+        options.server_selection_timeout = self
+            .server_selection_timeout
+            .or(options.server_selection_timeout)
+            .or(Some(DEFAULT_SERVER_SELECTION_TIMEOUT));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn settings(vars: &[(&str, &str)]) -> Result<MongoSettings, ConfigError> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(var, value)| (var.to_string(), value.to_string()))
+            .collect();
+        MongoSettings::from_lookup("product-catalog-service", |var| vars.get(var).cloned())
+    }
+
+    #[test]
+    fn unset_variables_leave_everything_but_the_app_name_alone() {
+        assert_eq!(
+            settings(&[]).unwrap(),
+            MongoSettings {
+                app_name: Some("product-catalog-service".to_string()),
+                ..MongoSettings::default()
+            }
+        );
+        assert_eq!(
+            settings(&[(MONGO_APP_NAME_ENV, "  "), (MONGO_MAX_POOL_SIZE_ENV, "")])
+                .unwrap()
+                .app_name
+                .as_deref(),
+            Some("product-catalog-service")
+        );
+    }
+
+    #[test]
+    fn variables_are_read_in_their_units() {
+        let read = settings(&[
+            (MONGO_APP_NAME_ENV, "catalog-eu-1"),
+            (MONGO_MAX_POOL_SIZE_ENV, "50"),
+            (MONGO_MIN_POOL_SIZE_ENV, "0"),
+            (MONGO_CONNECT_TIMEOUT_MS_ENV, " 2500 "),
+            (MONGO_SERVER_SELECTION_TIMEOUT_MS_ENV, "3000"),
+        ])
+        .unwrap();
+        assert_eq!(
+            read,
+            MongoSettings {
+                app_name: Some("catalog-eu-1".to_string()),
+                max_pool_size: Some(50),
+                min_pool_size: Some(0),
+                connect_timeout: Some(Duration::from_millis(2500)),
+                server_selection_timeout: Some(Duration::from_secs(3)),
+            }
+        );
+    }
+
+    #[test]
+    fn zero_garbage_and_inverted_pools_are_rejected() {
+        for vars in [
+            [(MONGO_SERVER_SELECTION_TIMEOUT_MS_ENV, "0")],
+            [(MONGO_CONNECT_TIMEOUT_MS_ENV, "soon")],
+            [(MONGO_MAX_POOL_SIZE_ENV, "0")],
+            [(MONGO_MAX_POOL_SIZE_ENV, "-4")],
+            [(MONGO_MAX_POOL_SIZE_ENV, "5000000000")],
+        ] {
+            assert!(
+                matches!(
+                    settings(&vars),
+                    Err(ConfigError::InvalidVariable(msg)) if msg.contains(vars[0].0)
+                ),
+                "{:?}",
+                vars
+            );
+        }
+        assert!(matches!(
+            settings(&[(MONGO_MIN_POOL_SIZE_ENV, "20"), (MONGO_MAX_POOL_SIZE_ENV, "10")]),
+            Err(ConfigError::InvalidVariable(msg)) if msg.contains(MONGO_MIN_POOL_SIZE_ENV)
+        ));
+    }
+
+    #[tokio::test]
+    async fn settings_override_the_uri_and_selection_fails_fast_by_default() {
+        let uri = "mongodb://localhost:27017/?maxPoolSize=20&serverSelectionTimeoutMS=1000";
+        let mut options = ClientOptions::parse(uri).await.unwrap();
+        settings(&[(MONGO_MAX_POOL_SIZE_ENV, "50")])
+            .unwrap()
+            .apply(&mut options);
+        assert_eq!(options.max_pool_size, Some(50));
+        assert_eq!(
+            options.server_selection_timeout,
+            Some(Duration::from_secs(1)),
+            "the URI's own timeout stays"
+        );
+        assert_eq!(options.app_name.as_deref(), Some("product-catalog-service"));
+
+        let mut options = ClientOptions::parse("mongodb://localhost:27017")
+            .await
+            .unwrap();
+        MongoSettings::default().apply(&mut options);
+        assert_eq!(options.max_pool_size, None);
+        assert_eq!(options.app_name, None);
+        assert_eq!(
+            options.server_selection_timeout,
+            Some(DEFAULT_SERVER_SELECTION_TIMEOUT)
+        );
+    }
+}
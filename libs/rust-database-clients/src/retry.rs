@@ -0,0 +1,135 @@
+use rand::Rng;
+use std::{future::Future, time::Duration};
+
+/// How often, and how far apart, [`retry`] tries an operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Tries in all, the first one included; `1` (or `0`) never retries.
+    pub attempts: u32,
+    /// Longest delay before the first retry, doubled for every following one.
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: 3,
+            base_delay: Duration::from_millis(100),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The pause after failed attempt `attempt` (counting from 1): between half and all of
+    /// `base_delay * 2^(attempt - 1)`, picked at random so that callers which failed
+    /// together don't all retry at the same moment.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let ceiling = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)));
+        ceiling.mul_f64(rand::rng().random_range(0.5..=1.0))
+    }
+}
+
+/// Runs `operation` until it succeeds, fails with an error `is_retryable` turns down, or
+/// has been tried `policy.attempts` times, sleeping [`RetryPolicy::delay`] in between.
+/// `operation` is handed the number of the attempt, counting from 1; the last error is
+/// returned.
+pub async fn retry<T, E, F, Fut>(
+    policy: RetryPolicy,
+    is_retryable: impl Fn(&E) -> bool,
+    mut operation: F,
+) -> Result<T, E>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut attempt = 1;
+    loop {
+        match operation(attempt).await {
+            Err(error) if attempt < policy.attempts && is_retryable(&error) => {
+                tokio::time::sleep(policy.delay(attempt)).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    const IMMEDIATE: RetryPolicy = RetryPolicy {
+        attempts: 3,
+        base_delay: Duration::ZERO,
+    };
+
+    #[derive(Debug, PartialEq)]
+    enum Failure {
+        Transient,
+        Permanent,
+    }
+
+    fn transient(failure: &Failure) -> bool {
+        *failure == Failure::Transient
+    }
+
+    #[tokio::test]
+    async fn retries_transient_failures_until_one_succeeds() {
+        let calls = AtomicU32::new(0);
+        let result = retry(IMMEDIATE, transient, |attempt| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if attempt < 2 {
+                    Err(Failure::Transient)
+                } else {
+                    Ok(attempt)
+                }
+            }
+        })
+        .await;
+        assert_eq!(result, Ok(2));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_the_last_attempt() {
+        let calls = AtomicU32::new(0);
+        let result: Result<(), _> = retry(IMMEDIATE, transient, |_| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async { Err(Failure::Transient) }
+        })
+        .await;
+        assert_eq!(result, Err(Failure::Transient));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn other_failures_are_returned_at_once() {
+        let calls = AtomicU32::new(0);
+        let result: Result<(), _> = retry(IMMEDIATE, transient, |_| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async { Err(Failure::Permanent) }
+        })
+        .await;
+        assert_eq!(result, Err(Failure::Permanent));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn delays_double_with_up_to_half_taken_off() {
+        let policy = RetryPolicy {
+            attempts: 3,
+            base_delay: Duration::from_millis(100),
+        };
+        for _ in 0..100 {
+            let first = policy.delay(1);
+            assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+            let second = policy.delay(2);
+            assert!(second >= Duration::from_millis(100) && second <= Duration::from_millis(200));
+        }
+        assert_eq!(IMMEDIATE.delay(5), Duration::ZERO);
+    }
+}
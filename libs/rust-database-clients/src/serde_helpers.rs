@@ -0,0 +1,378 @@
+use bson::Bson;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tracing::debug;
+
+/// (De)serializes a `chrono::DateTime<Utc>` as an RFC 3339 string for human-readable
+/// formats (JSON responses, Redis cache) and as a native BSON datetime otherwise.
+///
+/// The MongoDB driver uses the raw (non human-readable) BSON serializer, so documents
+/// stored through this helper are byte-identical to `chrono_datetime_as_bson_datetime`.
+/// Use it with `#[serde(with = "rust_database_clients::serde_helpers::chrono_datetime_as_rfc3339_or_bson")]`.
+pub mod chrono_datetime_as_rfc3339_or_bson {
+    use super::*;
+
+    /// JSON inputs we accept: the RFC 3339 string we emit today, plus the extended-JSON
+    /// `{"$date": ...}` shape that older cache entries were written in.
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum HumanReadableDateTime {
+        Rfc3339(String),
+        Extended(bson::DateTime),
+    }
+
+    pub fn serialize<S>(value: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            value
+                .to_rfc3339_opts(SecondsFormat::Millis, true)
+                .serialize(serializer)
+        } else {
+            bson::DateTime::from_chrono(*value).serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            match HumanReadableDateTime::deserialize(deserializer)? {
+                HumanReadableDateTime::Rfc3339(s) => DateTime::parse_from_rfc3339(&s)
+                    .map(|dt| dt.with_timezone(&Utc))
+                    .map_err(serde::de::Error::custom),
+                HumanReadableDateTime::Extended(dt) => Ok(dt.to_chrono()),
+            }
+        } else {
+            bson::DateTime::deserialize(deserializer).map(|dt| dt.to_chrono())
+        }
+    }
+}
+
+/// Like [`chrono_datetime_as_rfc3339_or_bson`], but reads whatever datetime documents
+/// written by other tools hold: BSON datetimes, RFC 3339 strings, extended-JSON
+/// `{"$date": ...}` and Unix seconds as a number or a string, as OpenFoodFacts dumps
+/// write them. Anything else reads as the Unix epoch instead of failing the document.
+/// Use it with `#[serde(default, with = "rust_database_clients::serde_helpers::lenient_datetime")]`.
+pub mod lenient_datetime {
+    use super::*;
+
+    pub use super::chrono_datetime_as_rfc3339_or_bson::serialize;
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = Bson::deserialize(deserializer)?;
+        let unix = |seconds: f64| {
+            debug!(seconds, "Lenient read: Unix timestamp as a datetime");
+            DateTime::from_timestamp_millis((seconds * 1000.0).round() as i64)
+        };
+        let datetime = match &value {
+            Bson::DateTime(dt) => Some(dt.to_chrono()),
+            Bson::String(s) => DateTime::parse_from_rfc3339(s.trim())
+                .map(|dt| dt.with_timezone(&Utc))
+                .ok()
+                .or_else(|| s.trim().parse().ok().and_then(unix)),
+            Bson::Int32(n) => unix(f64::from(*n)),
+            Bson::Int64(n) => unix(*n as f64),
+            Bson::Double(n) if n.is_finite() => unix(*n),
+            _ => None,
+        };
+        Ok(datetime.unwrap_or_else(|| {
+            debug!(value = %value, "Lenient read: unreadable datetime as the Unix epoch");
+            DateTime::UNIX_EPOCH
+        }))
+    }
+}
+
+/// Reads a string that documents written by other tools may hold as a number, such as
+/// OpenFoodFacts' `quantity`: numbers become their decimal text, and anything else that
+/// isn't a string reads as `None` instead of failing the document. Pair it with
+/// `#[serde(default)]`:
+/// `#[serde(default, deserialize_with = "rust_database_clients::serde_helpers::string_or_number")]`.
+pub fn string_or_number<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let number = match Bson::deserialize(deserializer)? {
+        Bson::Null => return Ok(None),
+        Bson::String(s) => return Ok(Some(s)),
+        Bson::Int32(n) => n.to_string(),
+        Bson::Int64(n) => n.to_string(),
+        Bson::Double(n) if n.is_finite() => n.to_string(),
+        other => {
+            debug!(value = %other, "Lenient read: dropped a non-string value");
+            return Ok(None);
+        }
+    };
+    debug!(value = %number, "Lenient read: number as a string");
+    Ok(Some(number))
+}
+
+/// Reads a tag list that documents written by other tools may hold as one string, such
+/// as OpenFoodFacts' comma-separated `"en:milk,en:nuts"`: the string is split on commas,
+/// elements that aren't strings are dropped, and anything else reads as the default
+/// (`None`, or an empty list) instead of failing the document. Pair it with
+/// `#[serde(default)]`:
+/// `#[serde(default, deserialize_with = "rust_database_clients::serde_helpers::string_or_vec")]`.
+pub fn string_or_vec<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + From<Vec<String>>,
+{
+    let tags: Vec<String> = match Bson::deserialize(deserializer)? {
+        Bson::Null => return Ok(T::default()),
+        Bson::Array(values) => values
+            .into_iter()
+            .filter_map(|value| match value {
+                Bson::String(s) => Some(s),
+                other => {
+                    debug!(value = %other, "Lenient read: dropped a non-string tag");
+                    None
+                }
+            })
+            .collect(),
+        Bson::String(s) => {
+            debug!(value = %s, "Lenient read: comma-separated string as a tag list");
+            s.split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_string)
+                .collect()
+        }
+        other => {
+            debug!(value = %other, "Lenient read: dropped a tag list that isn't one");
+            return Ok(T::default());
+        }
+    };
+    Ok(T::from(tags))
+}
+
+/// Deserializes a field that may be absent, `null` or a value as `None`, `Some(None)` or
+/// `Some(Some(value))`, for patch bodies where `null` clears a field. Pair it with
+/// `#[serde(default)]` so that absent fields stay `None`:
+/// `#[serde(default, deserialize_with = "rust_database_clients::serde_helpers::double_option")]`.
+pub fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::{RawBsonRef, serde_helpers::chrono_datetime_as_bson_datetime};
+    use chrono::TimeZone;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Stamped {
+        #[serde(with = "chrono_datetime_as_rfc3339_or_bson")]
+        created_at: DateTime<Utc>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct LegacyStamped {
+        #[serde(with = "chrono_datetime_as_bson_datetime")]
+        created_at: DateTime<Utc>,
+    }
+
+    fn sample_time() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 1, 12, 30, 45).unwrap()
+            + chrono::Duration::milliseconds(123)
+    }
+
+    #[test]
+    fn json_uses_rfc3339_strings() {
+        let value = Stamped {
+            created_at: sample_time(),
+        };
+        let json = serde_json::to_value(&value).unwrap();
+        assert_eq!(json["created_at"], "2024-06-01T12:30:45.123Z");
+
+        let back: Stamped = serde_json::from_value(json).unwrap();
+        assert_eq!(back, value);
+    }
+
+    #[test]
+    fn bson_uses_native_datetimes() {
+        let value = Stamped {
+            created_at: sample_time(),
+        };
+        let raw = bson::to_raw_document_buf(&value).unwrap();
+        match raw.get("created_at").unwrap() {
+            Some(RawBsonRef::DateTime(dt)) => assert_eq!(dt.to_chrono(), sample_time()),
+            other => panic!("Expected a BSON datetime, got {:?}", other),
+        }
+
+        let back: Stamped = bson::from_slice(raw.as_bytes()).unwrap();
+        assert_eq!(back, value);
+    }
+
+    #[test]
+    fn bson_bytes_match_legacy_helper() {
+        let new_bytes = bson::to_vec(&Stamped {
+            created_at: sample_time(),
+        })
+        .unwrap();
+        let legacy_bytes = bson::to_vec(&LegacyStamped {
+            created_at: sample_time(),
+        })
+        .unwrap();
+        assert_eq!(new_bytes, legacy_bytes);
+
+        let from_legacy: Stamped = bson::from_slice(&legacy_bytes).unwrap();
+        assert_eq!(from_legacy.created_at, sample_time());
+    }
+
+    #[test]
+    fn cache_round_trip_through_json_string() {
+        let value = Stamped {
+            created_at: sample_time(),
+        };
+        // Mirrors the Redis cache path: serde_json::to_string on write, from_str on read.
+        let cached = serde_json::to_string(&value).unwrap();
+        assert!(cached.contains("\"2024-06-01T12:30:45.123Z\""));
+        let back: Stamped = serde_json::from_str(&cached).unwrap();
+        assert_eq!(back, value);
+    }
+
+    #[test]
+    fn json_accepts_legacy_extended_json_cache_entries() {
+        let legacy_json = serde_json::to_string(&LegacyStamped {
+            created_at: sample_time(),
+        })
+        .unwrap();
+        assert!(legacy_json.contains("$date"));
+
+        let back: Stamped = serde_json::from_str(&legacy_json).unwrap();
+        assert_eq!(back.created_at, sample_time());
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Patch {
+        #[serde(default, deserialize_with = "double_option")]
+        name: Option<Option<String>>,
+    }
+
+    #[test]
+    fn double_option_tells_absent_from_null() {
+        let patch = |json: &str| serde_json::from_str::<Patch>(json).unwrap().name;
+        assert_eq!(patch("{}"), None);
+        assert_eq!(patch(r#"{"name":null}"#), Some(None));
+        assert_eq!(patch(r#"{"name":"oats"}"#), Some(Some("oats".to_string())));
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Messy {
+        #[serde(default, with = "lenient_datetime")]
+        created_at: DateTime<Utc>,
+        #[serde(default, deserialize_with = "string_or_number")]
+        quantity: Option<String>,
+        #[serde(default, deserialize_with = "string_or_vec")]
+        labels: Option<Vec<String>>,
+        #[serde(default, deserialize_with = "string_or_vec")]
+        allergens: Vec<String>,
+    }
+
+    fn messy(json: serde_json::Value) -> Messy {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn lenient_datetimes_read_every_shape_dumps_use() {
+        let at =
+            |json: serde_json::Value| messy(serde_json::json!({ "created_at": json })).created_at;
+        let unix = sample_time().timestamp();
+        let whole_seconds = DateTime::from_timestamp(unix, 0).unwrap();
+        assert_eq!(at("2024-06-01T12:30:45.123Z".into()), sample_time());
+        assert_eq!(at(unix.into()), whole_seconds);
+        assert_eq!(at(unix.to_string().into()), whole_seconds);
+        assert_eq!(at((unix as f64 + 0.123).into()), sample_time());
+        let extended = serde_json::to_value(LegacyStamped {
+            created_at: sample_time(),
+        })
+        .unwrap();
+        assert_eq!(at(extended["created_at"].clone()), sample_time());
+
+        for garbage in ["yesterday".into(), true.into(), serde_json::json!([1])] {
+            assert_eq!(at(garbage), DateTime::UNIX_EPOCH);
+        }
+        assert_eq!(
+            messy(serde_json::json!({})).created_at,
+            DateTime::UNIX_EPOCH
+        );
+    }
+
+    #[test]
+    fn lenient_datetimes_round_trip_through_bson() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Lenient {
+            #[serde(with = "lenient_datetime")]
+            created_at: DateTime<Utc>,
+        }
+        let value = Lenient {
+            created_at: sample_time(),
+        };
+        let raw = bson::to_raw_document_buf(&value).unwrap();
+        assert!(matches!(
+            raw.get("created_at").unwrap(),
+            Some(RawBsonRef::DateTime(_))
+        ));
+        assert_eq!(bson::from_slice::<Lenient>(raw.as_bytes()).unwrap(), value);
+
+        let unix: Lenient =
+            bson::from_document(bson::doc! { "created_at": sample_time().timestamp() }).unwrap();
+        assert_eq!(unix.created_at.timestamp(), sample_time().timestamp());
+    }
+
+    #[test]
+    fn numbers_read_as_strings() {
+        let quantity =
+            |json: serde_json::Value| messy(serde_json::json!({ "quantity": json })).quantity;
+        assert_eq!(quantity("500 g".into()), Some("500 g".to_string()));
+        assert_eq!(quantity(500.into()), Some("500".to_string()));
+        assert_eq!(quantity(1.5.into()), Some("1.5".to_string()));
+        assert_eq!(quantity(serde_json::Value::Null), None);
+        assert_eq!(quantity(serde_json::json!({"value": 500})), None);
+        assert_eq!(messy(serde_json::json!({})).quantity, None);
+    }
+
+    #[test]
+    fn single_strings_read_as_tag_lists() {
+        let tags = |json: serde_json::Value| {
+            let messy = messy(serde_json::json!({ "labels": json, "allergens": json }));
+            assert_eq!(messy.labels.clone().unwrap_or_default(), messy.allergens);
+            messy.labels
+        };
+        let strings = |tags: &[&str]| Some(tags.iter().map(|t| t.to_string()).collect());
+        assert_eq!(
+            tags(serde_json::json!(["en:organic"])),
+            strings(&["en:organic"])
+        );
+        assert_eq!(
+            tags("en:milk, en:nuts,".into()),
+            strings(&["en:milk", "en:nuts"])
+        );
+        assert_eq!(
+            tags(serde_json::json!(["en:vegan", 3, null])),
+            strings(&["en:vegan"])
+        );
+        assert_eq!(tags(serde_json::Value::Null), None);
+        assert_eq!(tags(42.into()), None);
+
+        let absent = messy(serde_json::json!({}));
+        assert_eq!(absent.labels, None);
+        assert!(absent.allergens.is_empty());
+    }
+
+    #[test]
+    fn json_rejects_garbage_strings() {
+        let result = serde_json::from_str::<Stamped>(r#"{"created_at":"yesterday"}"#);
+        assert!(result.is_err());
+    }
+}
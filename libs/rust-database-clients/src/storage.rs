@@ -0,0 +1,61 @@
+use crate::ConfigError;
+use std::{env, str::FromStr};
+
+/// Selects the services' storage backends at startup.
+pub const STORAGE_MODE_ENV: &str = "STORAGE_MODE";
+
+/// `external` (the default) talks to Mongo, Redis, Qdrant and Neo4j; `memory` keeps
+/// everything in process so a service runs without any of them. Memory mode is for
+/// local development only: nothing persists and nothing is shared between replicas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StorageMode {
+    #[default]
+    External,
+    Memory,
+}
+
+impl StorageMode {
+    /// Reads [`STORAGE_MODE_ENV`]; unset or empty means [`StorageMode::External`].
+    pub fn from_env() -> Result<Self, ConfigError> {
+        match env::var(STORAGE_MODE_ENV) {
+            Ok(value) if !value.trim().is_empty() => value.parse(),
+            _ => Ok(StorageMode::External),
+        }
+    }
+}
+
+impl FromStr for StorageMode {
+    type Err = ConfigError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "external" => Ok(StorageMode::External),
+            "memory" => Ok(StorageMode::Memory),
+            other => Err(ConfigError::InvalidVariable(format!(
+                "{} must be 'external' or 'memory', got '{}'",
+                STORAGE_MODE_ENV, other
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_both_modes_loosely() {
+        assert_eq!(
+            "external".parse::<StorageMode>().unwrap(),
+            StorageMode::External
+        );
+        assert_eq!(
+            " Memory ".parse::<StorageMode>().unwrap(),
+            StorageMode::Memory
+        );
+        assert!(matches!(
+            "sqlite".parse::<StorageMode>(),
+            Err(ConfigError::InvalidVariable(msg)) if msg.contains("'sqlite'")
+        ));
+    }
+}
@@ -0,0 +1,93 @@
+use std::{env, time::Duration};
+use thiserror::Error;
+
+const DEFAULT_DEV_SUBJECT: &str = "dev-user";
+const DEFAULT_JWKS_CACHE_TTL_SECONDS: u64 = 300;
+
+#[derive(Error, Debug)]
+pub enum AuthConfigError {
+    #[error("Missing authentication configuration: set JWT_HS256_SECRET, JWKS_URL or AUTH_DISABLED=true")]
+    MissingKeySource,
+    #[error("Invalid environment variable '{0}'")]
+    InvalidVariable(String),
+}
+
+#[derive(Debug, Clone)]
+pub enum AuthMode {
+    /// Development mode: every request is authenticated as `subject` with `roles`.
+    Disabled { subject: String, roles: Vec<String> },
+    Hs256 { secret: String },
+    Jwks { url: String, cache_ttl: Duration },
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthConfig {
+    pub mode: AuthMode,
+    pub audience: Option<String>,
+    pub issuer: Option<String>,
+}
+
+impl AuthConfig {
+    /// Reads `AUTH_DISABLED`, `AUTH_DEV_SUBJECT`, `AUTH_DEV_ROLES`, `JWT_HS256_SECRET`,
+    /// `JWKS_URL`, `JWKS_CACHE_TTL_SECONDS`, `JWT_AUDIENCE` and `JWT_ISSUER`.
+    pub fn from_env() -> Result<Self, AuthConfigError> {
+        let audience = non_empty_var("JWT_AUDIENCE");
+        let issuer = non_empty_var("JWT_ISSUER");
+
+        let disabled = match non_empty_var("AUTH_DISABLED") {
+            None => false,
+            Some(v) => v
+                .parse::<bool>()
+                .map_err(|_| AuthConfigError::InvalidVariable("AUTH_DISABLED".to_string()))?,
+        };
+
+        let mode = if disabled {
+            AuthMode::Disabled {
+                subject: non_empty_var("AUTH_DEV_SUBJECT")
+                    .unwrap_or_else(|| DEFAULT_DEV_SUBJECT.to_string()),
+                roles: non_empty_var("AUTH_DEV_ROLES")
+                    .map(|roles| {
+                        roles
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|r| !r.is_empty())
+                            .map(String::from)
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            }
+        } else if let Some(secret) = non_empty_var("JWT_HS256_SECRET") {
+            AuthMode::Hs256 { secret }
+        } else if let Some(url) = non_empty_var("JWKS_URL") {
+            let ttl_secs = match non_empty_var("JWKS_CACHE_TTL_SECONDS") {
+                None => DEFAULT_JWKS_CACHE_TTL_SECONDS,
+                Some(v) => v.parse::<u64>().map_err(|_| {
+                    AuthConfigError::InvalidVariable("JWKS_CACHE_TTL_SECONDS".to_string())
+                })?,
+            };
+            AuthMode::Jwks {
+                url,
+                cache_ttl: Duration::from_secs(ttl_secs),
+            }
+        } else {
+            return Err(AuthConfigError::MissingKeySource);
+        };
+
+        Ok(AuthConfig {
+            mode,
+            audience,
+            issuer,
+        })
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        matches!(self.mode, AuthMode::Disabled { .. })
+    }
+}
+
+fn non_empty_var(name: &str) -> Option<String> {
+    env::var(name)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
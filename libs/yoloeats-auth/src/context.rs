@@ -0,0 +1,35 @@
+use crate::error::AuthError;
+use axum::{extract::FromRequestParts, http::request::Parts};
+
+/// The authenticated principal, inserted into request extensions by `AuthLayer`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthContext {
+    pub subject: String,
+    pub roles: Vec<String>,
+}
+
+impl AuthContext {
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+}
+
+/// Extracts the `AuthContext`; rejects with 401 if the route is not behind `AuthLayer`.
+#[derive(Debug, Clone)]
+pub struct Authenticated(pub AuthContext);
+
+impl<S> FromRequestParts<S> for Authenticated
+where
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<AuthContext>()
+            .cloned()
+            .map(Authenticated)
+            .ok_or(AuthError::MissingToken)
+    }
+}
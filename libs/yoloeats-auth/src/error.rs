@@ -0,0 +1,77 @@
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use thiserror::Error;
+use tracing::{debug, error};
+use yoloeats_domain::{ErrorBody, ErrorCode};
+use yoloeats_tracing::current_request_id;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AuthError {
+    #[error("Missing bearer token")]
+    MissingToken,
+    #[error("Token has expired")]
+    Expired,
+    #[error("Invalid token: {0}")]
+    InvalidToken(String),
+    #[error("Token header has no 'kid'")]
+    MissingKid,
+    #[error("No signing key found for kid '{0}'")]
+    UnknownKid(String),
+    #[error("Signing keys are unavailable: {0}")]
+    KeysUnavailable(String),
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("Missing or invalid internal token")]
+    InvalidInternalToken,
+}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        let (status, code, message) = match &self {
+            AuthError::MissingToken => (
+                StatusCode::UNAUTHORIZED,
+                ErrorCode::Unauthorized,
+                "Missing or malformed Authorization header".to_string(),
+            ),
+            AuthError::Expired => (
+                StatusCode::UNAUTHORIZED,
+                ErrorCode::Unauthorized,
+                "Token has expired".to_string(),
+            ),
+            AuthError::InvalidToken(reason) => {
+                debug!("Rejected token: {}", reason);
+                (
+                    StatusCode::UNAUTHORIZED,
+                    ErrorCode::Unauthorized,
+                    "Invalid token".to_string(),
+                )
+            }
+            AuthError::MissingKid | AuthError::UnknownKid(_) => (
+                StatusCode::UNAUTHORIZED,
+                ErrorCode::Unauthorized,
+                "Invalid token".to_string(),
+            ),
+            AuthError::KeysUnavailable(reason) => {
+                error!("JWKS unavailable: {}", reason);
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    ErrorCode::AuthUnavailable,
+                    "Authentication temporarily unavailable".to_string(),
+                )
+            }
+            AuthError::Forbidden(msg) => (StatusCode::FORBIDDEN, ErrorCode::Forbidden, msg.clone()),
+            AuthError::InvalidInternalToken => (
+                StatusCode::UNAUTHORIZED,
+                ErrorCode::InvalidInternalToken,
+                self.to_string(),
+            ),
+        };
+
+        let body = ErrorBody::new(code, message)
+            .with_request_id(current_request_id().map(|id| id.to_string()));
+        (status, Json(body)).into_response()
+    }
+}
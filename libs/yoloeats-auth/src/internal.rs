@@ -0,0 +1,332 @@
+use crate::error::AuthError;
+use axum::{
+    body::Body,
+    http::{HeaderMap, HeaderValue, Request},
+    response::{IntoResponse, Response},
+};
+use std::{
+    env, fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use subtle::ConstantTimeEq;
+use tower::{Layer, Service};
+use tracing::{debug, warn};
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send + 'static>>;
+
+pub const INTERNAL_TOKEN_HEADER: &str = "x-internal-token";
+
+/// Path prefixes reserved for service-to-service and operator calls. Matching is by path
+/// segment, so `/internal` and `/internal/...` are protected but `/internalized` is not.
+/// `/health/details` lists dependency errors, so it is operator-only too; the liveness
+/// and readiness probes next to it stay open.
+pub const INTERNAL_PATH_PREFIXES: &[&str] = &["/internal", "/api/v1/admin", "/health/details"];
+
+/// The shared secrets for internal calls, read from `INTERNAL_TOKEN` and, while a rotation
+/// is in progress, `INTERNAL_TOKEN_PREVIOUS`. Both are accepted inbound; only the current
+/// one is sent on outbound calls. Cheap to clone.
+#[derive(Clone, Default)]
+pub struct InternalTokens {
+    current: Option<Arc<str>>,
+    previous: Option<Arc<str>>,
+}
+
+impl InternalTokens {
+    pub fn new(current: impl Into<String>, previous: Option<String>) -> Self {
+        InternalTokens {
+            current: Some(current.into().into()),
+            previous: previous.map(Into::into),
+        }
+    }
+
+    /// Without `INTERNAL_TOKEN` every internal route rejects every caller, which is the
+    /// safe failure for a misconfigured deployment.
+    pub fn from_env() -> Self {
+        let current = non_empty_var("INTERNAL_TOKEN");
+        let previous = non_empty_var("INTERNAL_TOKEN_PREVIOUS");
+        match current {
+            Some(current) => InternalTokens::new(current, previous),
+            None => {
+                warn!(
+                    "INTERNAL_TOKEN is not set: internal and admin routes will reject all calls."
+                );
+                InternalTokens::default()
+            }
+        }
+    }
+
+    /// Compares against every accepted token in constant time, without short-circuiting,
+    /// so timing reveals neither which token matched nor how much of it.
+    pub fn verify(&self, presented: &[u8]) -> bool {
+        let mut matched = subtle::Choice::from(0);
+        for token in [&self.current, &self.previous].into_iter().flatten() {
+            matched |= token.as_bytes().ct_eq(presented);
+        }
+        matched.into()
+    }
+
+    fn verify_headers(&self, headers: &HeaderMap) -> bool {
+        headers
+            .get(INTERNAL_TOKEN_HEADER)
+            .is_some_and(|value| self.verify(value.as_bytes()))
+    }
+
+    /// The value to send on outbound internal calls, if a token is configured.
+    pub fn header_value(&self) -> Option<HeaderValue> {
+        let current = self.current.as_deref()?;
+        let mut value = HeaderValue::from_str(current).ok()?;
+        value.set_sensitive(true);
+        Some(value)
+    }
+}
+
+impl fmt::Debug for InternalTokens {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InternalTokens")
+            .field("current", &self.current.as_ref().map(|_| "<redacted>"))
+            .field("previous", &self.previous.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+fn non_empty_var(name: &str) -> Option<String> {
+    env::var(name)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+pub fn is_internal_path(path: &str) -> bool {
+    INTERNAL_PATH_PREFIXES.iter().any(|prefix| {
+        path.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    })
+}
+
+/// Attaches the current internal token to an outbound request.
+pub trait InternalTokenExt {
+    fn internal_token(self, tokens: &InternalTokens) -> Self;
+}
+
+impl InternalTokenExt for reqwest::RequestBuilder {
+    fn internal_token(self, tokens: &InternalTokens) -> Self {
+        match tokens.header_value() {
+            Some(value) => self.header(INTERNAL_TOKEN_HEADER, value),
+            None => self,
+        }
+    }
+}
+
+impl InternalTokenExt for reqwest_middleware::RequestBuilder {
+    fn internal_token(self, tokens: &InternalTokens) -> Self {
+        match tokens.header_value() {
+            Some(value) => self.header(INTERNAL_TOKEN_HEADER, value),
+            None => self,
+        }
+    }
+}
+
+/// Tower layer requiring a valid `X-Internal-Token` on every request under
+/// [`INTERNAL_PATH_PREFIXES`]; other paths pass through untouched. Apply it to the whole
+/// router so routes added to those groups later are covered, and unknown internal paths
+/// answer 401 rather than 404.
+#[derive(Clone, Debug)]
+pub struct InternalTokenLayer {
+    tokens: InternalTokens,
+}
+
+impl InternalTokenLayer {
+    pub fn new(tokens: InternalTokens) -> Self {
+        InternalTokenLayer { tokens }
+    }
+}
+
+impl<S> Layer<S> for InternalTokenLayer {
+    type Service = InternalTokenService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        InternalTokenService {
+            inner,
+            tokens: self.tokens.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct InternalTokenService<S> {
+    inner: S,
+    tokens: InternalTokens,
+}
+
+impl<S> Service<Request<Body>> for InternalTokenService<S>
+where
+    S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        if is_internal_path(request.uri().path()) && !self.tokens.verify_headers(request.headers())
+        {
+            debug!(path = %request.uri().path(), "Rejected internal call without a valid token");
+            return Box::pin(async { Ok(AuthError::InvalidInternalToken.into_response()) });
+        }
+        Box::pin(self.inner.call(request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, http::StatusCode, routing::get};
+    use tower::ServiceExt;
+
+    fn app(tokens: InternalTokens) -> Router {
+        Router::new()
+            .route("/internal/v1/ping", get(|| async { "internal" }))
+            .route("/api/v1/admin/reindex", get(|| async { "admin" }))
+            .route("/api/v1/products", get(|| async { "public" }))
+            .layer(InternalTokenLayer::new(tokens))
+    }
+
+    async fn status(app: Router, path: &str, token: Option<&str>) -> StatusCode {
+        let mut request = Request::get(path);
+        if let Some(token) = token {
+            request = request.header(INTERNAL_TOKEN_HEADER, token);
+        }
+        app.oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn protects_internal_and_admin_groups_only() {
+        let tokens = InternalTokens::new("s3cret", None);
+        for path in ["/internal/v1/ping", "/api/v1/admin/reindex"] {
+            assert_eq!(
+                status(app(tokens.clone()), path, Some("s3cret")).await,
+                StatusCode::OK
+            );
+            assert_eq!(
+                status(app(tokens.clone()), path, None).await,
+                StatusCode::UNAUTHORIZED
+            );
+            assert_eq!(
+                status(app(tokens.clone()), path, Some("s3cret-but-longer")).await,
+                StatusCode::UNAUTHORIZED
+            );
+        }
+        assert_eq!(
+            status(app(tokens.clone()), "/api/v1/products", None).await,
+            StatusCode::OK
+        );
+        // Unknown internal paths don't reveal which routes exist.
+        assert_eq!(
+            status(app(tokens), "/internal/unknown", None).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[tokio::test]
+    async fn old_token_is_accepted_during_rotation() {
+        let rotating = InternalTokens::new("new-token", Some("old-token".to_string()));
+        for token in ["new-token", "old-token"] {
+            assert_eq!(
+                status(app(rotating.clone()), "/internal/v1/ping", Some(token)).await,
+                StatusCode::OK
+            );
+        }
+
+        // Once the previous token is dropped from config, it stops working.
+        let rotated = InternalTokens::new("new-token", None);
+        assert_eq!(
+            status(app(rotated.clone()), "/internal/v1/ping", Some("old-token")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status(app(rotated), "/internal/v1/ping", Some("new-token")).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn unconfigured_tokens_reject_everything() {
+        let tokens = InternalTokens::default();
+        assert!(!tokens.verify(b""));
+        assert_eq!(
+            status(app(tokens), "/internal/v1/ping", Some("")).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[tokio::test]
+    async fn rejection_uses_the_error_envelope() {
+        let response = app(InternalTokens::new("s3cret", None))
+            .oneshot(
+                Request::get("/internal/v1/ping")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "code": "invalid_internal_token",
+                "message": "Missing or invalid internal token",
+                "error": "Missing or invalid internal token"
+            })
+        );
+    }
+
+    #[test]
+    fn prefixes_match_whole_segments() {
+        assert!(is_internal_path("/internal"));
+        assert!(is_internal_path("/internal/v1/users/batch"));
+        assert!(is_internal_path("/api/v1/admin/reindex"));
+        assert!(!is_internal_path("/internalized"));
+        assert!(!is_internal_path("/api/v1/administrators"));
+        assert!(!is_internal_path("/api/v1/products"));
+        assert!(is_internal_path("/health/details"));
+        assert!(!is_internal_path("/health/ready"));
+        assert!(!is_internal_path("/health/live"));
+    }
+
+    #[test]
+    fn outbound_helper_sends_only_the_current_token() {
+        let tokens = InternalTokens::new("new-token", Some("old-token".to_string()));
+        let request = reqwest::Client::new()
+            .get("http://profile.internal/internal/v1/ping")
+            .internal_token(&tokens)
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()[INTERNAL_TOKEN_HEADER], "new-token");
+
+        let request = reqwest::Client::new()
+            .get("http://profile.internal/internal/v1/ping")
+            .internal_token(&InternalTokens::default())
+            .build()
+            .unwrap();
+        assert!(!request.headers().contains_key(INTERNAL_TOKEN_HEADER));
+    }
+
+    #[test]
+    fn debug_output_redacts_tokens() {
+        let rendered = format!("{:?}", InternalTokens::new("s3cret", None));
+        assert!(!rendered.contains("s3cret"));
+    }
+}
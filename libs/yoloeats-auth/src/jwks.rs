@@ -0,0 +1,93 @@
+use crate::error::AuthError;
+use jsonwebtoken::{
+    DecodingKey,
+    jwk::{Jwk, JwkSet},
+};
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{debug, info};
+
+/// An unknown `kid` triggers a refetch (keys may have rotated), but no more often than this.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+struct CachedKeys {
+    fetched_at: Instant,
+    keys: JwkSet,
+}
+
+pub(crate) struct JwksCache {
+    url: String,
+    ttl: Duration,
+    http_client: reqwest::Client,
+    cached: RwLock<Option<CachedKeys>>,
+}
+
+impl JwksCache {
+    pub(crate) fn new(url: String, ttl: Duration, http_client: reqwest::Client) -> Self {
+        JwksCache {
+            url,
+            ttl,
+            http_client,
+            cached: RwLock::new(None),
+        }
+    }
+
+    pub(crate) async fn decoding_key(&self, kid: &str) -> Result<DecodingKey, AuthError> {
+        {
+            let cached = self.cached.read().await;
+            if let Some(entry) = cached.as_ref() {
+                let fresh = entry.fetched_at.elapsed() < self.ttl;
+                match entry.keys.find(kid) {
+                    Some(jwk) if fresh => return to_decoding_key(jwk),
+                    None if entry.fetched_at.elapsed() < MIN_REFRESH_INTERVAL => {
+                        return Err(AuthError::UnknownKid(kid.to_string()));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut cached = self.cached.write().await;
+        // Another request may have refreshed while we waited for the write lock.
+        let refreshed_recently = cached
+            .as_ref()
+            .is_some_and(|entry| entry.fetched_at.elapsed() < MIN_REFRESH_INTERVAL);
+        if !refreshed_recently {
+            let keys = self.fetch().await?;
+            *cached = Some(CachedKeys {
+                fetched_at: Instant::now(),
+                keys,
+            });
+        }
+
+        let entry = cached
+            .as_ref()
+            .ok_or_else(|| AuthError::KeysUnavailable("JWKS cache is empty".to_string()))?;
+        entry
+            .keys
+            .find(kid)
+            .ok_or_else(|| AuthError::UnknownKid(kid.to_string()))
+            .and_then(to_decoding_key)
+    }
+
+    async fn fetch(&self) -> Result<JwkSet, AuthError> {
+        debug!("Fetching JWKS from {}", self.url);
+        let response = self
+            .http_client
+            .get(&self.url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| AuthError::KeysUnavailable(e.to_string()))?;
+        let keys = response
+            .json::<JwkSet>()
+            .await
+            .map_err(|e| AuthError::KeysUnavailable(e.to_string()))?;
+        info!("Loaded {} signing keys from JWKS", keys.keys.len());
+        Ok(keys)
+    }
+}
+
+fn to_decoding_key(jwk: &Jwk) -> Result<DecodingKey, AuthError> {
+    DecodingKey::from_jwk(jwk).map_err(|e| AuthError::InvalidToken(e.to_string()))
+}
@@ -0,0 +1,484 @@
+use crate::{
+    config::{AuthConfig, AuthMode},
+    context::AuthContext,
+    error::AuthError,
+    jwks::JwksCache,
+};
+use axum::{
+    body::Body,
+    extract::{FromRequestParts, RawPathParams},
+    http::{HeaderMap, Request, header::AUTHORIZATION},
+    response::{IntoResponse, Response},
+};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, decode_header, errors::ErrorKind};
+use serde::Deserialize;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tower::{Layer, Service};
+use tracing::{debug, warn};
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send + 'static>>;
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+    #[serde(default)]
+    roles: Vec<String>,
+}
+
+enum KeySource {
+    Disabled { subject: String, roles: Vec<String> },
+    Hs256(DecodingKey),
+    Jwks(JwksCache),
+}
+
+/// Validates bearer tokens according to an `AuthConfig`. Cheap to clone.
+#[derive(Clone)]
+pub struct Authenticator {
+    keys: Arc<KeySource>,
+    audience: Option<String>,
+    issuer: Option<String>,
+}
+
+impl Authenticator {
+    pub fn new(config: AuthConfig) -> Self {
+        let keys = match config.mode {
+            AuthMode::Disabled { subject, roles } => {
+                warn!(
+                    subject = %subject,
+                    "AUTH_DISABLED is set: all requests are authenticated as a fake subject."
+                );
+                KeySource::Disabled { subject, roles }
+            }
+            AuthMode::Hs256 { secret } => {
+                KeySource::Hs256(DecodingKey::from_secret(secret.as_bytes()))
+            }
+            AuthMode::Jwks { url, cache_ttl } => {
+                KeySource::Jwks(JwksCache::new(url, cache_ttl, reqwest::Client::new()))
+            }
+        };
+        Authenticator {
+            keys: Arc::new(keys),
+            audience: config.audience,
+            issuer: config.issuer,
+        }
+    }
+
+    pub async fn authenticate(&self, headers: &HeaderMap) -> Result<AuthContext, AuthError> {
+        if let KeySource::Disabled { subject, roles } = self.keys.as_ref() {
+            return Ok(AuthContext {
+                subject: subject.clone(),
+                roles: roles.clone(),
+            });
+        }
+
+        let token = bearer_token(headers).ok_or(AuthError::MissingToken)?;
+        let header = decode_header(token).map_err(|e| AuthError::InvalidToken(e.to_string()))?;
+
+        let (key, algorithm) = match self.keys.as_ref() {
+            KeySource::Hs256(key) => (key.clone(), Algorithm::HS256),
+            KeySource::Jwks(cache) => {
+                // Only asymmetric algorithms make sense with published keys; accepting
+                // HS* here would let a public key be used as an HMAC secret.
+                if matches!(
+                    header.alg,
+                    Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+                ) {
+                    return Err(AuthError::InvalidToken(format!(
+                        "algorithm {:?} not allowed with JWKS",
+                        header.alg
+                    )));
+                }
+                let kid = header.kid.as_deref().ok_or(AuthError::MissingKid)?;
+                (cache.decoding_key(kid).await?, header.alg)
+            }
+            KeySource::Disabled { .. } => unreachable!("handled above"),
+        };
+
+        // A configured audience or issuer must be claimed, not merely not contradicted.
+        let mut validation = Validation::new(algorithm);
+        let mut required = vec!["exp"];
+        match &self.audience {
+            Some(aud) => {
+                validation.set_audience(&[aud]);
+                required.push("aud");
+            }
+            None => validation.validate_aud = false,
+        }
+        if let Some(iss) = &self.issuer {
+            validation.set_issuer(&[iss]);
+            required.push("iss");
+        }
+        validation.set_required_spec_claims(&required);
+
+        let data = decode::<Claims>(token, &key, &validation).map_err(|e| match e.kind() {
+            ErrorKind::ExpiredSignature => AuthError::Expired,
+            _ => AuthError::InvalidToken(e.to_string()),
+        })?;
+        debug!(subject = %data.claims.sub, "Authenticated request");
+
+        Ok(AuthContext {
+            subject: data.claims.sub,
+            roles: data.claims.roles,
+        })
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return None;
+    }
+    let token = token.trim();
+    (!token.is_empty()).then_some(token)
+}
+
+/// Tower layer that authenticates every request and inserts an `AuthContext`.
+#[derive(Clone)]
+pub struct AuthLayer {
+    authenticator: Authenticator,
+}
+
+impl AuthLayer {
+    pub fn new(authenticator: Authenticator) -> Self {
+        AuthLayer { authenticator }
+    }
+}
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = AuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthService {
+            inner,
+            authenticator: self.authenticator.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AuthService<S> {
+    inner: S,
+    authenticator: Authenticator,
+}
+
+impl<S> Service<Request<Body>> for AuthService<S>
+where
+    S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<Body>) -> Self::Future {
+        // Take the service that was driven to readiness and leave a fresh clone behind.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let authenticator = self.authenticator.clone();
+
+        Box::pin(async move {
+            match authenticator.authenticate(request.headers()).await {
+                Ok(context) => {
+                    request.extensions_mut().insert(context);
+                    inner.call(request).await
+                }
+                Err(e) => Ok(e.into_response()),
+            }
+        })
+    }
+}
+
+/// Route layer that rejects with 403 unless the authenticated subject equals the path
+/// parameter `param` (e.g. `/users/{user_id}/profile`). Subjects with the `admin` role
+/// pass regardless. Must be applied with `route_layer` so path parameters are known.
+pub fn require_subject_matches_path(param: &'static str) -> SubjectMatchesPathLayer {
+    SubjectMatchesPathLayer { param }
+}
+
+#[derive(Clone, Copy)]
+pub struct SubjectMatchesPathLayer {
+    param: &'static str,
+}
+
+impl<S> Layer<S> for SubjectMatchesPathLayer {
+    type Service = SubjectMatchesPathService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SubjectMatchesPathService {
+            inner,
+            param: self.param,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SubjectMatchesPathService<S> {
+    inner: S,
+    param: &'static str,
+}
+
+impl<S> Service<Request<Body>> for SubjectMatchesPathService<S>
+where
+    S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let param = self.param;
+
+        Box::pin(async move {
+            let (mut parts, body) = request.into_parts();
+            let context = match parts.extensions.get::<AuthContext>() {
+                Some(context) => context.clone(),
+                None => return Ok(AuthError::MissingToken.into_response()),
+            };
+            let path_value = match RawPathParams::from_request_parts(&mut parts, &()).await {
+                Ok(params) => params
+                    .iter()
+                    .find(|(name, _)| *name == param)
+                    .map(|(_, value)| value.to_string()),
+                Err(rejection) => return Ok(rejection.into_response()),
+            };
+
+            match path_value {
+                Some(value) if value == context.subject || context.has_role("admin") => {
+                    inner.call(Request::from_parts(parts, body)).await
+                }
+                Some(_) => Ok(AuthError::Forbidden(format!(
+                    "Authenticated subject may not access this {}",
+                    param
+                ))
+                .into_response()),
+                None => {
+                    warn!(param, "Path parameter not present on route");
+                    Ok(AuthError::Forbidden("Access denied".to_string()).into_response())
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::Authenticated;
+    use axum::{Router, http::StatusCode, routing::get};
+    use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+    use jsonwebtoken::{EncodingKey, Header, encode};
+    use serde::Serialize;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use tower::ServiceExt;
+
+    const SECRET: &str = "test-secret";
+
+    #[derive(Serialize)]
+    struct TestClaims<'a> {
+        sub: &'a str,
+        roles: Vec<&'a str>,
+        exp: u64,
+    }
+
+    fn now_secs() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    fn token(secret: &str, sub: &str, exp: u64) -> String {
+        encode(
+            &Header::new(Algorithm::HS256),
+            &TestClaims {
+                sub,
+                roles: vec!["user"],
+                exp,
+            },
+            &EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    fn hs256() -> Authenticator {
+        Authenticator::new(AuthConfig {
+            mode: AuthMode::Hs256 {
+                secret: SECRET.to_string(),
+            },
+            audience: None,
+            issuer: None,
+        })
+    }
+
+    fn headers_with(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
+        headers
+    }
+
+    #[tokio::test]
+    async fn accepts_valid_token() {
+        let ctx = hs256()
+            .authenticate(&headers_with(&token(SECRET, "user-1", now_secs() + 600)))
+            .await
+            .unwrap();
+        assert_eq!(ctx.subject, "user-1");
+        assert_eq!(ctx.roles, vec!["user".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn rejects_expired_token() {
+        let err = hs256()
+            .authenticate(&headers_with(&token(SECRET, "user-1", now_secs() - 3600)))
+            .await
+            .unwrap_err();
+        assert_eq!(err, AuthError::Expired);
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_signature() {
+        let err = hs256()
+            .authenticate(&headers_with(&token("other-secret", "user-1", now_secs() + 600)))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AuthError::InvalidToken(_)));
+    }
+
+    #[tokio::test]
+    async fn rejects_wrong_audience() {
+        let authenticator = Authenticator::new(AuthConfig {
+            mode: AuthMode::Hs256 {
+                secret: SECRET.to_string(),
+            },
+            audience: Some("yoloeats".to_string()),
+            issuer: None,
+        });
+        let signed = |aud: &str| {
+            encode(
+                &Header::new(Algorithm::HS256),
+                &serde_json::json!({"sub": "user-1", "aud": aud, "exp": now_secs() + 600}),
+                &EncodingKey::from_secret(SECRET.as_bytes()),
+            )
+            .unwrap()
+        };
+
+        let ctx = authenticator
+            .authenticate(&headers_with(&signed("yoloeats")))
+            .await
+            .unwrap();
+        assert_eq!(ctx.subject, "user-1");
+        let err = authenticator
+            .authenticate(&headers_with(&signed("another-service")))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AuthError::InvalidToken(_)));
+        let err = authenticator
+            .authenticate(&headers_with(&token(SECRET, "user-1", now_secs() + 600)))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AuthError::InvalidToken(_)));
+    }
+
+    #[tokio::test]
+    async fn rejects_missing_header() {
+        let err = hs256().authenticate(&HeaderMap::new()).await.unwrap_err();
+        assert_eq!(err, AuthError::MissingToken);
+    }
+
+    #[tokio::test]
+    async fn jwks_mode_rejects_token_without_kid() {
+        let authenticator = Authenticator::new(AuthConfig {
+            mode: AuthMode::Jwks {
+                url: "http://127.0.0.1:9/jwks.json".to_string(),
+                cache_ttl: Duration::from_secs(60),
+            },
+            audience: None,
+            issuer: None,
+        });
+        // Signature is irrelevant: the header is inspected before any key lookup.
+        let header = Header::new(Algorithm::RS256);
+        let claims = serde_json::json!({"sub": "user-1", "exp": now_secs() + 600});
+        let rsa_style = format!(
+            "{}.{}.c2ln",
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header).unwrap()),
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).unwrap())
+        );
+        let err = authenticator
+            .authenticate(&headers_with(&rsa_style))
+            .await
+            .unwrap_err();
+        assert_eq!(err, AuthError::MissingKid);
+    }
+
+    #[tokio::test]
+    async fn disabled_mode_injects_fake_subject() {
+        let authenticator = Authenticator::new(AuthConfig {
+            mode: AuthMode::Disabled {
+                subject: "dev-user".to_string(),
+                roles: vec!["admin".to_string()],
+            },
+            audience: None,
+            issuer: None,
+        });
+        let ctx = authenticator.authenticate(&HeaderMap::new()).await.unwrap();
+        assert_eq!(ctx.subject, "dev-user");
+        assert!(ctx.has_role("admin"));
+    }
+
+    fn profile_router(authenticator: Authenticator) -> Router {
+        Router::new()
+            .route(
+                "/users/{user_id}/profile",
+                get(|Authenticated(ctx): Authenticated| async move { ctx.subject }),
+            )
+            .route_layer(require_subject_matches_path("user_id"))
+            .layer(AuthLayer::new(authenticator))
+    }
+
+    async fn status_for(router: Router, uri: &str, token: Option<String>) -> StatusCode {
+        let mut builder = Request::builder().uri(uri);
+        if let Some(token) = token {
+            builder = builder.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        router
+            .oneshot(builder.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn layer_enforces_token_and_subject_match() {
+        let valid = token(SECRET, "user-1", now_secs() + 600);
+        assert_eq!(
+            status_for(profile_router(hs256()), "/users/user-1/profile", Some(valid.clone())).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status_for(profile_router(hs256()), "/users/user-2/profile", Some(valid)).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status_for(profile_router(hs256()), "/users/user-1/profile", None).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+}
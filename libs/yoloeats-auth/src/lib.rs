@@ -0,0 +1,28 @@
+//! Bearer-token authentication shared by the YoloEats services.
+//!
+//! `AuthLayer` validates the `Authorization: Bearer ...` header (HS256 shared secret or
+//! a JWKS endpoint), and inserts an [`AuthContext`] into the request extensions.
+//! Handlers read it through the [`Authenticated`] extractor.
+//!
+//! Service-to-service calls use a shared secret instead: [`InternalTokenLayer`] guards
+//! `/internal/*` and `/api/v1/admin/*`, and [`InternalTokenExt`] attaches the token to
+//! outbound requests.
+
+mod config;
+mod context;
+mod error;
+mod internal;
+mod jwks;
+mod layer;
+
+pub use config::{AuthConfig, AuthConfigError, AuthMode};
+pub use context::{AuthContext, Authenticated};
+pub use error::AuthError;
+pub use internal::{
+    INTERNAL_PATH_PREFIXES, INTERNAL_TOKEN_HEADER, InternalTokenExt, InternalTokenLayer,
+    InternalTokenService, InternalTokens, is_internal_path,
+};
+pub use layer::{
+    AuthLayer, AuthService, Authenticator, SubjectMatchesPathLayer, SubjectMatchesPathService,
+    require_subject_matches_path,
+};
@@ -0,0 +1,298 @@
+use serde::{Serialize, Serializer, ser::SerializeStruct};
+use serde_json::Value;
+use std::fmt;
+
+/// Machine-readable error codes, the `code` of every error body. Clients branch on
+/// these instead of on messages, so a code never changes meaning once released; add a
+/// new variant instead, and list it in [`ErrorCode::ALL`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    InvalidRequest,
+    ValidationFailed,
+    UnsupportedMediaType,
+    InvalidData,
+    InvalidProductId,
+    InvalidBarcode,
+    PayloadTooLarge,
+    ProductNotFound,
+    ProfileNotFound,
+    ProductConflict,
+    ProfileConflict,
+    ResyncRequired,
+    Unauthorized,
+    InvalidInternalToken,
+    Forbidden,
+    AuthUnavailable,
+    UpstreamUnavailable,
+    Overloaded,
+    RateLimited,
+    DatabaseError,
+    CacheError,
+    ConfigurationError,
+    InternalError,
+}
+
+impl ErrorCode {
+    /// Every code, in declaration order; the OpenAPI schema of [`ErrorBody`] lists them.
+    pub const ALL: [ErrorCode; 23] = [
+        ErrorCode::InvalidRequest,
+        ErrorCode::ValidationFailed,
+        ErrorCode::UnsupportedMediaType,
+        ErrorCode::InvalidData,
+        ErrorCode::InvalidProductId,
+        ErrorCode::InvalidBarcode,
+        ErrorCode::PayloadTooLarge,
+        ErrorCode::ProductNotFound,
+        ErrorCode::ProfileNotFound,
+        ErrorCode::ProductConflict,
+        ErrorCode::ProfileConflict,
+        ErrorCode::ResyncRequired,
+        ErrorCode::Unauthorized,
+        ErrorCode::InvalidInternalToken,
+        ErrorCode::Forbidden,
+        ErrorCode::AuthUnavailable,
+        ErrorCode::UpstreamUnavailable,
+        ErrorCode::Overloaded,
+        ErrorCode::RateLimited,
+        ErrorCode::DatabaseError,
+        ErrorCode::CacheError,
+        ErrorCode::ConfigurationError,
+        ErrorCode::InternalError,
+    ];
+
+    /// The code as it appears on the wire, e.g. `product_not_found`.
+    pub const fn as_code(self) -> &'static str {
+        match self {
+            ErrorCode::InvalidRequest => "invalid_request",
+            ErrorCode::ValidationFailed => "validation_failed",
+            ErrorCode::UnsupportedMediaType => "unsupported_media_type",
+            ErrorCode::InvalidData => "invalid_data",
+            ErrorCode::InvalidProductId => "invalid_product_id",
+            ErrorCode::InvalidBarcode => "invalid_barcode",
+            ErrorCode::PayloadTooLarge => "payload_too_large",
+            ErrorCode::ProductNotFound => "product_not_found",
+            ErrorCode::ProfileNotFound => "profile_not_found",
+            ErrorCode::ProductConflict => "product_conflict",
+            ErrorCode::ProfileConflict => "profile_conflict",
+            ErrorCode::ResyncRequired => "resync_required",
+            ErrorCode::Unauthorized => "unauthorized",
+            ErrorCode::InvalidInternalToken => "invalid_internal_token",
+            ErrorCode::Forbidden => "forbidden",
+            ErrorCode::AuthUnavailable => "auth_unavailable",
+            ErrorCode::UpstreamUnavailable => "upstream_unavailable",
+            ErrorCode::Overloaded => "overloaded",
+            ErrorCode::RateLimited => "rate_limited",
+            ErrorCode::DatabaseError => "database_error",
+            ErrorCode::CacheError => "cache_error",
+            ErrorCode::ConfigurationError => "configuration_error",
+            ErrorCode::InternalError => "internal_error",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_code())
+    }
+}
+
+impl Serialize for ErrorCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_code())
+    }
+}
+
+/// The JSON body of every error response:
+/// `{"code", "message", "requestId"?, "details"?}`.
+///
+/// The legacy `error` field (a copy of `message`) is still written for clients that
+/// predate the envelope; it goes away in the next release.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorBody {
+    pub code: ErrorCode,
+    pub message: String,
+    pub request_id: Option<String>,
+    pub details: Option<Value>,
+}
+
+impl ErrorBody {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        ErrorBody {
+            code,
+            message: message.into(),
+            request_id: None,
+            details: None,
+        }
+    }
+
+    pub fn with_request_id(mut self, request_id: Option<String>) -> Self {
+        self.request_id = request_id;
+        self
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+}
+
+impl Serialize for ErrorBody {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut body = serializer.serialize_struct("ErrorBody", 5)?;
+        body.serialize_field("code", &self.code)?;
+        body.serialize_field("message", &self.message)?;
+        body.serialize_field("error", &self.message)?;
+        match &self.request_id {
+            Some(id) => body.serialize_field("requestId", id)?,
+            None => body.skip_field("requestId")?,
+        }
+        match &self.details {
+            Some(details) => body.serialize_field("details", details)?,
+            None => body.skip_field("details")?,
+        }
+        body.end()
+    }
+}
+
+/// Written by hand to match the hand-written [`Serialize`]: `requestId` and `details`
+/// are left out when unset, and `error` is marked deprecated.
+#[cfg(feature = "openapi")]
+impl utoipa::PartialSchema for ErrorBody {
+    fn schema() -> utoipa::openapi::RefOr<utoipa::openapi::schema::Schema> {
+        use utoipa::openapi::{
+            Deprecated, ObjectBuilder,
+            schema::{SchemaType, Type},
+        };
+
+        let string = |description: &str| {
+            ObjectBuilder::new()
+                .schema_type(Type::String)
+                .description(Some(description))
+        };
+        ObjectBuilder::new()
+            .property(
+                "code",
+                string("Machine-readable error code; more may be added later.")
+                    .enum_values(Some(ErrorCode::ALL.map(ErrorCode::as_code))),
+            )
+            .required("code")
+            .property("message", string("Human-readable description."))
+            .required("message")
+            .property(
+                "error",
+                string("Copy of `message` for clients that predate the envelope.")
+                    .deprecated(Some(Deprecated::True)),
+            )
+            .required("error")
+            .property(
+                "requestId",
+                string("The request's `X-Request-Id`, to quote when reporting it."),
+            )
+            .property(
+                "details",
+                ObjectBuilder::new()
+                    .schema_type(SchemaType::AnyValue)
+                    .description(Some("Code-specific detail, such as the invalid fields.")),
+            )
+            .into()
+    }
+}
+
+#[cfg(feature = "openapi")]
+impl utoipa::ToSchema for ErrorBody {
+    fn name() -> std::borrow::Cow<'static, str> {
+        std::borrow::Cow::Borrowed("ErrorBody")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn minimal_body_json_snapshot() {
+        let body = ErrorBody::new(
+            ErrorCode::ProductNotFound,
+            "Product with barcode 123 not found",
+        );
+        assert_eq!(
+            serde_json::to_value(&body).unwrap(),
+            json!({
+                "code": "product_not_found",
+                "message": "Product with barcode 123 not found",
+                "error": "Product with barcode 123 not found"
+            })
+        );
+    }
+
+    #[test]
+    fn full_body_json_snapshot() {
+        let body = ErrorBody::new(
+            ErrorCode::UpstreamUnavailable,
+            "Error communicating with svc",
+        )
+        .with_request_id(Some("req-1".to_string()))
+        .with_details(json!({ "service": "svc" }));
+        assert_eq!(
+            serde_json::to_value(&body).unwrap(),
+            json!({
+                "code": "upstream_unavailable",
+                "message": "Error communicating with svc",
+                "error": "Error communicating with svc",
+                "requestId": "req-1",
+                "details": { "service": "svc" }
+            })
+        );
+    }
+
+    #[test]
+    fn all_lists_every_code_once_in_order() {
+        // Stops compiling when a variant is added without a position here, as a
+        // reminder to add it to `ALL` too.
+        let position = |code: ErrorCode| match code {
+            ErrorCode::InvalidRequest => 0,
+            ErrorCode::ValidationFailed => 1,
+            ErrorCode::UnsupportedMediaType => 2,
+            ErrorCode::InvalidData => 3,
+            ErrorCode::InvalidProductId => 4,
+            ErrorCode::InvalidBarcode => 5,
+            ErrorCode::PayloadTooLarge => 6,
+            ErrorCode::ProductNotFound => 7,
+            ErrorCode::ProfileNotFound => 8,
+            ErrorCode::ProductConflict => 9,
+            ErrorCode::ProfileConflict => 10,
+            ErrorCode::ResyncRequired => 11,
+            ErrorCode::Unauthorized => 12,
+            ErrorCode::InvalidInternalToken => 13,
+            ErrorCode::Forbidden => 14,
+            ErrorCode::AuthUnavailable => 15,
+            ErrorCode::UpstreamUnavailable => 16,
+            ErrorCode::Overloaded => 17,
+            ErrorCode::RateLimited => 18,
+            ErrorCode::DatabaseError => 19,
+            ErrorCode::CacheError => 20,
+            ErrorCode::ConfigurationError => 21,
+            ErrorCode::InternalError => 22,
+        };
+        for (i, code) in ErrorCode::ALL.into_iter().enumerate() {
+            assert_eq!(position(code), i, "{:?}", code);
+        }
+    }
+
+    #[test]
+    fn codes_are_distinct_snake_case() {
+        let mut seen = std::collections::HashSet::new();
+        for code in ErrorCode::ALL {
+            let wire = code.as_code();
+            assert!(seen.insert(wire), "{} twice", wire);
+            assert!(
+                wire.chars().all(|c| c.is_ascii_lowercase() || c == '_'),
+                "{}",
+                wire
+            );
+            assert_eq!(serde_json::to_value(code).unwrap(), json!(wire));
+            assert_eq!(code.to_string(), wire);
+        }
+    }
+}
@@ -0,0 +1,449 @@
+//! Builders for test data, behind the `test-fixtures` feature.
+//!
+//! Products and profiles are persistence models owned by their services, so the builders
+//! here produce the JSON those models serialize to and [`build`](ProductFixture::build)
+//! deserializes it into whichever type the caller names: the catalog's `Product`, the
+//! profile service's `UserProfile`, or the shared [`ProductSummary`]/[`SafetyProfile`].
+//! The same [`json`](ProductFixture::json) is a ready-made wiremock body, and with
+//! `test-fixtures-mongo` [`insert_into`](ProductFixture::insert_into) stores the built
+//! value in a collection.
+//!
+//! What every fixture guarantees:
+//! - Ids are derived from the code or user id, so the same fixture always has the same
+//!   `_id` ([`fixture_object_id`]).
+//! - Every timestamp is [`FIXTURE_TIME`].
+//! - Tags are in the form the catalog stores: labels, brands, categories and countries
+//!   through [`normalize_tags`], allergens through [`extract_allergen_tags`], so
+//!   `with_allergens(["Milk"])` gives `en:milk` and unknown allergens are dropped.
+//! - Absent optional lists are `null`, as the catalog writes them; `allergens_tags` is
+//!   always a list.
+
+use crate::{
+    CheckResult, IngredientEntry, ProductSummary, RiskLevel, SafetyProfile, SafetyStatus,
+    tags::{extract_allergen_tags, normalize_tags},
+};
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+
+/// The creation and modification time of every fixture.
+pub const FIXTURE_TIME: &str = "2024-06-01T08:00:00.000Z";
+
+/// A stable 24-hex-digit ObjectId for `key`: FNV-1a of the key, zero-padded.
+pub fn fixture_object_id(key: &str) -> String {
+    let hash = key.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    format!("{:024x}", hash)
+}
+
+fn tags_or_null(tags: &[String]) -> Value {
+    if tags.is_empty() {
+        Value::Null
+    } else {
+        json!(tags)
+    }
+}
+
+fn strings<I, S>(values: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    values.into_iter().map(|v| v.as_ref().to_string()).collect()
+}
+
+fn deserialize<T: DeserializeOwned>(value: Value, what: &str) -> T {
+    serde_json::from_value(value)
+        .unwrap_or_else(|e| panic!("{} fixture does not fit the requested type: {}", what, e))
+}
+
+/// A catalog product, e.g.
+/// `ProductFixture::new("4000417025005").vegan().with_allergens(["en:milk"]).build()`.
+#[derive(Debug, Clone)]
+pub struct ProductFixture {
+    code: String,
+    name: Option<String>,
+    ingredients_text: Option<String>,
+    brands: Vec<String>,
+    categories: Vec<String>,
+    labels: Vec<String>,
+    allergens: Vec<String>,
+    traces: Vec<String>,
+    countries: Vec<String>,
+    nutriscore: Option<String>,
+    ingredients: Vec<IngredientEntry>,
+}
+
+impl ProductFixture {
+    /// A product named after its code, with no tags.
+    pub fn new(code: &str) -> Self {
+        ProductFixture {
+            code: code.to_string(),
+            name: Some(format!("Fixture product {}", code)),
+            ingredients_text: None,
+            brands: Vec::new(),
+            categories: Vec::new(),
+            labels: Vec::new(),
+            allergens: Vec::new(),
+            traces: Vec::new(),
+            countries: Vec::new(),
+            nutriscore: None,
+            ingredients: Vec::new(),
+        }
+    }
+
+    pub fn named(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    pub fn unnamed(mut self) -> Self {
+        self.name = None;
+        self
+    }
+
+    /// Comma-separated, as the checker parses it.
+    pub fn with_ingredients(mut self, text: &str) -> Self {
+        self.ingredients_text = Some(text.to_string());
+        self
+    }
+
+    /// OpenFoodFacts' structured ingredients, besides or instead of the text.
+    pub fn with_ingredient_entries<I: IntoIterator<Item = IngredientEntry>>(
+        mut self,
+        entries: I,
+    ) -> Self {
+        self.ingredients = entries.into_iter().collect();
+        self
+    }
+
+    pub fn with_brands<I: IntoIterator<Item = S>, S: AsRef<str>>(mut self, brands: I) -> Self {
+        self.brands = normalize_tags(brands);
+        self
+    }
+
+    pub fn with_categories<I: IntoIterator<Item = S>, S: AsRef<str>>(mut self, tags: I) -> Self {
+        self.categories = normalize_tags(tags);
+        self
+    }
+
+    /// Adds to the labels already set, so it combines with [`vegan`](Self::vegan).
+    pub fn with_labels<I: IntoIterator<Item = S>, S: AsRef<str>>(mut self, tags: I) -> Self {
+        let mut labels = std::mem::take(&mut self.labels);
+        labels.extend(strings(tags));
+        self.labels = normalize_tags(labels);
+        self
+    }
+
+    pub fn with_allergens<I: IntoIterator<Item = S>, S: AsRef<str>>(mut self, tags: I) -> Self {
+        self.allergens = extract_allergen_tags(tags);
+        self
+    }
+
+    pub fn with_traces<I: IntoIterator<Item = S>, S: AsRef<str>>(mut self, tags: I) -> Self {
+        self.traces = extract_allergen_tags(tags);
+        self
+    }
+
+    pub fn with_countries<I: IntoIterator<Item = S>, S: AsRef<str>>(mut self, tags: I) -> Self {
+        self.countries = normalize_tags(tags);
+        self
+    }
+
+    pub fn with_nutriscore(mut self, grade: &str) -> Self {
+        self.nutriscore = Some(grade.to_lowercase());
+        self
+    }
+
+    /// Labelled `en:vegan` and `en:vegetarian`, as OpenFoodFacts labels vegan products.
+    pub fn vegan(self) -> Self {
+        self.with_labels(["en:vegan", "en:vegetarian"])
+    }
+
+    pub fn vegetarian(self) -> Self {
+        self.with_labels(["en:vegetarian"])
+    }
+
+    /// The hex `_id` of this product.
+    pub fn id(&self) -> String {
+        fixture_object_id(&self.code)
+    }
+
+    /// The product as the catalog serializes it, e.g. as a wiremock body.
+    pub fn json(&self) -> Value {
+        json!({
+            "_id": { "$oid": self.id() },
+            "code": self.code,
+            "product_name": self.name,
+            "generic_name": null,
+            "brands_tags": tags_or_null(&self.brands),
+            "categories_tags": tags_or_null(&self.categories),
+            "main_category": self.categories.first(),
+            "labels_tags": tags_or_null(&self.labels),
+            "ingredients_text": self.ingredients_text,
+            "traces_tags": tags_or_null(&self.traces),
+            "allergens_tags": self.allergens,
+            "quantity": null,
+            "image_url": null,
+            "image_small_url": null,
+            "countries_tags": tags_or_null(&self.countries),
+            "nutrition_grade_fr": self.nutriscore,
+            "ingredients": (!self.ingredients.is_empty()).then_some(&self.ingredients),
+            "creator": "fixture",
+            "source": "fixture",
+            "created_datetime": FIXTURE_TIME,
+            "last_modified_datetime": FIXTURE_TIME,
+        })
+    }
+
+    /// The product as any type that reads the catalog's product JSON.
+    pub fn build<T: DeserializeOwned>(&self) -> T {
+        deserialize(self.json(), "Product")
+    }
+
+    pub fn summary(&self) -> ProductSummary {
+        self.build()
+    }
+
+    /// Builds the product as `T` and inserts it into `collection`.
+    #[cfg(feature = "test-fixtures-mongo")]
+    pub async fn insert_into<T>(&self, collection: &mongodb::Collection<T>) -> T
+    where
+        T: DeserializeOwned + serde::Serialize + Send + Sync,
+    {
+        let product: T = self.build();
+        collection
+            .insert_one(&product)
+            .await
+            .expect("insert product fixture");
+        product
+    }
+}
+
+/// A user profile, e.g.
+/// `UserProfileFixture::new("user-1").allergic_to(["peanuts"]).risk(RiskLevel::Low).build()`.
+#[derive(Debug, Clone)]
+pub struct UserProfileFixture {
+    user_id: String,
+    username: Option<String>,
+    email: Option<String>,
+    allergens: Vec<String>,
+    diets: Vec<String>,
+    risk: RiskLevel,
+}
+
+impl UserProfileFixture {
+    /// No allergens or diets, default risk tolerance.
+    pub fn new(user_id: &str) -> Self {
+        UserProfileFixture {
+            user_id: user_id.to_string(),
+            username: None,
+            email: None,
+            allergens: Vec::new(),
+            diets: Vec::new(),
+            risk: RiskLevel::default(),
+        }
+    }
+
+    pub fn named(mut self, username: &str) -> Self {
+        self.username = Some(username.to_string());
+        self
+    }
+
+    pub fn with_email(mut self, email: &str) -> Self {
+        self.email = Some(email.to_string());
+        self
+    }
+
+    /// Allergen ids as profiles store them (`milk`, not `en:milk`).
+    pub fn allergic_to<I: IntoIterator<Item = S>, S: AsRef<str>>(mut self, allergens: I) -> Self {
+        self.allergens = strings(allergens);
+        self
+    }
+
+    /// Diet names as profiles store them (`vegan`, `gluten_free`).
+    pub fn following<I: IntoIterator<Item = S>, S: AsRef<str>>(mut self, diets: I) -> Self {
+        self.diets = strings(diets);
+        self
+    }
+
+    pub fn risk(mut self, risk: RiskLevel) -> Self {
+        self.risk = risk;
+        self
+    }
+
+    /// The hex `_id` of this profile.
+    pub fn id(&self) -> String {
+        fixture_object_id(&self.user_id)
+    }
+
+    /// The profile as the profile service serializes it, e.g. as a wiremock body.
+    pub fn json(&self) -> Value {
+        let mut profile = json!({
+            "_id": { "$oid": self.id() },
+            "user_id": self.user_id,
+            "allergens": self.allergens,
+            "dietary_prefs": self.diets,
+            "risk_tolerance": self.risk,
+            "created_at": FIXTURE_TIME,
+            "updated_at": FIXTURE_TIME,
+        });
+        // The service leaves unset contact fields out rather than writing null.
+        if let Some(username) = &self.username {
+            profile["username"] = json!(username);
+        }
+        if let Some(email) = &self.email {
+            profile["email"] = json!(email);
+        }
+        profile
+    }
+
+    /// The profile as any type that reads the profile service's JSON.
+    pub fn build<T: DeserializeOwned>(&self) -> T {
+        deserialize(self.json(), "UserProfile")
+    }
+
+    pub fn safety_profile(&self) -> SafetyProfile {
+        self.build()
+    }
+
+    /// Builds the profile as `T` and inserts it into `collection`.
+    #[cfg(feature = "test-fixtures-mongo")]
+    pub async fn insert_into<T>(&self, collection: &mongodb::Collection<T>) -> T
+    where
+        T: DeserializeOwned + serde::Serialize + Send + Sync,
+    {
+        let profile: T = self.build();
+        collection
+            .insert_one(&profile)
+            .await
+            .expect("insert profile fixture");
+        profile
+    }
+}
+
+/// Check results as the checker returns them; all online.
+pub struct CheckResultFixture;
+
+impl CheckResultFixture {
+    pub fn safe() -> CheckResult {
+        CheckResult {
+            status: SafetyStatus::Safe,
+            conflicting_allergens: Vec::new(),
+            conflicting_diets: Vec::new(),
+            trace_allergens: Vec::new(),
+            is_offline_result: false,
+        }
+    }
+
+    pub fn unsafe_for<I: IntoIterator<Item = S>, S: AsRef<str>>(allergens: I) -> CheckResult {
+        CheckResult {
+            status: SafetyStatus::Unsafe,
+            conflicting_allergens: strings(allergens),
+            ..CheckResultFixture::safe()
+        }
+    }
+
+    pub fn unsafe_for_diets<I: IntoIterator<Item = S>, S: AsRef<str>>(diets: I) -> CheckResult {
+        CheckResult {
+            status: SafetyStatus::Unsafe,
+            conflicting_diets: strings(diets),
+            ..CheckResultFixture::safe()
+        }
+    }
+
+    /// Traces only, under the default `caution` trace policy.
+    pub fn caution_for_traces<I: IntoIterator<Item = S>, S: AsRef<str>>(traces: I) -> CheckResult {
+        CheckResult {
+            status: SafetyStatus::Caution,
+            trace_allergens: strings(traces),
+            ..CheckResultFixture::safe()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_are_stable_per_key() {
+        let id = fixture_object_id("4000417025005");
+        assert_eq!(id.len(), 24);
+        assert!(id.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(id, ProductFixture::new("4000417025005").id());
+        assert_ne!(id, fixture_object_id("4000417025006"));
+    }
+
+    #[test]
+    fn product_tags_are_normalized() {
+        let product = ProductFixture::new("1")
+            .vegan()
+            .with_labels(["EN:Organic", "en:vegan"])
+            .with_allergens(["Milk", "en:palm-oil"])
+            .with_brands(["Ritter Sport"])
+            .json();
+        assert_eq!(
+            product["labels_tags"],
+            json!(["en:vegan", "en:vegetarian", "en:organic"])
+        );
+        assert_eq!(product["allergens_tags"], json!(["en:milk"]));
+        assert_eq!(product["brands_tags"], json!(["ritter-sport"]));
+        assert_eq!(product["traces_tags"], Value::Null);
+        assert_eq!(product["created_datetime"], FIXTURE_TIME);
+    }
+
+    #[test]
+    fn products_read_as_summaries() {
+        let summary = ProductFixture::new("4000417025005")
+            .named("Ritter Sport")
+            .with_ingredients("sugar, whole milk powder")
+            .with_traces(["en:nuts"])
+            .summary();
+        assert_eq!(
+            summary,
+            ProductSummary {
+                code: "4000417025005".to_string(),
+                product_name: Some("Ritter Sport".to_string()),
+                ingredients_text: Some("sugar, whole milk powder".to_string()),
+                allergens_tags: vec![],
+                traces_tags: vec!["en:nuts".to_string()],
+                labels_tags: vec![],
+                ingredients: None,
+            }
+        );
+    }
+
+    #[test]
+    fn profiles_read_as_safety_profiles() {
+        let fixture = UserProfileFixture::new("user-1")
+            .allergic_to(["peanuts"])
+            .following(["vegan"])
+            .risk(RiskLevel::Low);
+        assert_eq!(
+            fixture.safety_profile(),
+            SafetyProfile {
+                user_id: "user-1".to_string(),
+                allergens: vec!["peanuts".to_string()],
+                dietary_prefs: vec!["vegan".to_string()],
+                risk_tolerance: RiskLevel::Low,
+            }
+        );
+        let json = fixture.json();
+        assert!(json.get("username").is_none());
+        assert_eq!(json["risk_tolerance"], "low");
+        assert_eq!(json["updated_at"], FIXTURE_TIME);
+    }
+
+    #[test]
+    fn check_results_fill_only_their_list() {
+        let result = CheckResultFixture::unsafe_for(["milk"]);
+        assert_eq!(result.status, SafetyStatus::Unsafe);
+        assert_eq!(result.conflicting_allergens, vec!["milk".to_string()]);
+        assert!(result.trace_allergens.is_empty() && !result.is_offline_result);
+        assert_eq!(
+            CheckResultFixture::caution_for_traces(["nuts"]).status,
+            SafetyStatus::Caution
+        );
+    }
+}
@@ -0,0 +1,39 @@
+//! The ingredient graph the allergy checker walks, as `(ingredient, target)` pairs per
+//! relationship. Names are lowercase, the way the checker splits ingredient text, and
+//! allergens and diets are named as in user profiles.
+//!
+//! The seed CLI loads these into Neo4j; the checker's in-memory storage starts from them.
+
+/// Ingredient -> allergen (`IS_ALLERGEN`).
+pub const INGREDIENT_ALLERGENS: &[(&str, &str)] = &[
+    ("whole milk powder", "milk"),
+    ("milk", "milk"),
+    ("hazelnuts", "nuts"),
+    ("roasted peanuts", "peanuts"),
+    ("whole wheat flour", "gluten"),
+    ("wheat flour", "gluten"),
+    ("wheat", "gluten"),
+    ("oats", "gluten"),
+    ("durum wheat semolina", "gluten"),
+    ("eggs", "eggs"),
+    ("cod", "fish"),
+    ("tahini", "sesame"),
+    ("soybeans", "soy"),
+];
+
+/// Trace tag -> allergen (`MAY_CONTAIN_TRACE`). The checker looks trace tags up as
+/// ingredient names, so the tag itself is the ingredient node.
+pub const TRACE_ALLERGENS: &[(&str, &str)] = &[
+    ("en:peanuts", "peanuts"),
+    ("en:sesame-seeds", "sesame"),
+    ("en:soybeans", "soy"),
+];
+
+/// Ingredient -> diet (`CONFLICTS_WITH_DIET`).
+pub const INGREDIENT_DIET_CONFLICTS: &[(&str, &str)] = &[
+    ("whole milk powder", "vegan"),
+    ("milk", "vegan"),
+    ("eggs", "vegan"),
+    ("cod", "vegan"),
+    ("cod", "vegetarian"),
+];
@@ -0,0 +1,31 @@
+//! Wire-format DTOs shared by the YoloEats services.
+//!
+//! These types describe what travels over HTTP between services and to clients.
+//! Persistence models (Mongo documents) stay in their owning service and convert
+//! into these types, so a field rename here is a reviewed change in one place.
+//! [`tags`] holds the tag normalization every product writer shares, [`ingredient_graph`]
+//! the seed ingredient graph; [`validation`] (feature `validation`) turns `validator`
+//! failures into the error envelope. [`fixtures`] (feature `test-fixtures`) builds test
+//! products, profiles and check results for the services' tests. With feature `openapi`
+//! the DTOs and [`ErrorBody`] are `utoipa` schemas for the services' OpenAPI documents.
+//! [`point`] (feature `point`) builds a product's Qdrant point as every writer of the
+//! product vectors does.
+
+mod error;
+#[cfg(feature = "test-fixtures")]
+pub mod fixtures;
+pub mod ingredient_graph;
+#[cfg(feature = "point")]
+pub mod point;
+mod product;
+mod profile;
+mod safety;
+mod serde_util;
+pub mod tags;
+#[cfg(feature = "validation")]
+pub mod validation;
+
+pub use error::{ErrorBody, ErrorCode};
+pub use product::{IngredientEntry, ProductSummary};
+pub use profile::{AllergenInfo, RiskLevel, SafetyProfile};
+pub use safety::{CheckResult, SafetyStatus};
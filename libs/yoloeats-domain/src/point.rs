@@ -0,0 +1,183 @@
+//! A catalog product as a Qdrant point: [`point_id`] and the [`ProductDoc`] its embedding
+//! text and payload are built from. The catalog-sync-worker and the catalog's own indexing
+//! both write these points, so they have to build them the same way.
+
+use bson::{Document, oid::ObjectId};
+use qdrant_client::Payload;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+/// Qdrant point id for a product: UUIDv5 (DNS namespace) of the Mongo `_id` hex string.
+/// Must stay identical to `vectorize_products.py` and the catalog's recommendations
+/// lookup, or synced points become orphans next to the script's.
+pub fn point_id(id: &ObjectId) -> String {
+    Uuid::new_v5(&Uuid::NAMESPACE_DNS, id.to_hex().as_bytes()).to_string()
+}
+
+/// The subset of a catalog product the embedding and payload are built from.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct ProductDoc {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub code: String,
+    pub product_name: Option<String>,
+    pub generic_name: Option<String>,
+    pub ingredients_text: Option<String>,
+    pub categories_tags: Option<Vec<String>>,
+    pub brands_tags: Option<Vec<String>>,
+    pub labels_tags: Option<Vec<String>>,
+    pub traces_tags: Option<Vec<String>>,
+    pub allergens_tags: Option<Vec<String>>,
+    pub countries_tags: Option<Vec<String>>,
+}
+
+impl ProductDoc {
+    /// Reads the fields of a stored product document, ignoring the rest.
+    pub fn from_document(document: Document) -> Result<Self, bson::de::Error> {
+        bson::from_document(document)
+    }
+
+    /// Same text as `create_embedding_text` in `vectorize_products.py`, so vectors from
+    /// the worker and the script are comparable.
+    pub fn embedding_text(&self) -> String {
+        let name = self
+            .product_name
+            .as_deref()
+            .filter(|n| !n.is_empty())
+            .or(self.generic_name.as_deref())
+            .unwrap_or_default();
+        let join = |tags: &Option<Vec<String>>| tags.as_deref().unwrap_or_default().join(" ");
+
+        let parts = [
+            ("Product", name.to_string()),
+            ("Categories", join(&self.categories_tags)),
+            ("Brands", join(&self.brands_tags)),
+            ("Labels", join(&self.labels_tags)),
+            (
+                "Ingredients",
+                self.ingredients_text.clone().unwrap_or_default(),
+            ),
+        ];
+        let text = parts
+            .iter()
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(label, value)| format!("{}: {}", label, value))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if text.is_empty() {
+            "product information unavailable".to_string()
+        } else {
+            text
+        }
+    }
+
+    /// The script's payload plus `countries_tags`, which the catalog's country filtering
+    /// excludes on. `allergens_tags` and `ingredients_text` stay null when the product has
+    /// none, which strict recommendations leave out as of unknown allergens.
+    pub fn payload(&self) -> Payload {
+        let name = self
+            .product_name
+            .clone()
+            .or_else(|| self.generic_name.clone())
+            .unwrap_or_else(|| "N/A".to_string());
+        let tags = |tags: &Option<Vec<String>>| tags.clone().unwrap_or_default();
+        Payload::try_from(json!({
+            "product_name": name,
+            "code": self.code,
+            "category_tags": tags(&self.categories_tags),
+            "brand_tags": tags(&self.brands_tags),
+            "traces_tags": tags(&self.traces_tags),
+            "labels_tags": tags(&self.labels_tags),
+            "allergens_tags": self.allergens_tags,
+            "ingredients_text": self
+                .ingredients_text
+                .as_deref()
+                .map(str::trim)
+                .filter(|text| !text.is_empty()),
+            "countries_tags": tags(&self.countries_tags),
+        }))
+        .expect("payload is a JSON object")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::doc;
+
+    fn oid() -> ObjectId {
+        ObjectId::parse_str("663a1f2e9b1e8a3f4c5d6e7f").unwrap()
+    }
+
+    #[test]
+    fn point_id_matches_the_embedding_script() {
+        // uuid.uuid5(uuid.NAMESPACE_DNS, "663a1f2e9b1e8a3f4c5d6e7f")
+        assert_eq!(
+            point_id(&oid()),
+            Uuid::new_v5(&Uuid::NAMESPACE_DNS, b"663a1f2e9b1e8a3f4c5d6e7f").to_string()
+        );
+    }
+
+    #[test]
+    fn embedding_text_mirrors_the_script() {
+        let product = ProductDoc::from_document(doc! {
+            "_id": oid(),
+            "code": "4000417025005",
+            "product_name": "Alpine milk chocolate",
+            "brands_tags": ["ritter-sport"],
+            "labels_tags": ["en:vegetarian", "en:fair-trade"],
+            "ingredients_text": "Sugar, whole milk powder",
+            "categories_tags": null,
+        })
+        .unwrap();
+        assert_eq!(
+            product.embedding_text(),
+            "Product: Alpine milk chocolate Brands: ritter-sport \
+             Labels: en:vegetarian en:fair-trade Ingredients: Sugar, whole milk powder"
+        );
+
+        let bare = ProductDoc::from_document(doc! { "_id": oid(), "code": "1" }).unwrap();
+        assert_eq!(bare.embedding_text(), "product information unavailable");
+    }
+
+    #[test]
+    fn payload_carries_code_labels_allergens_and_countries() {
+        let product = ProductDoc::from_document(doc! {
+            "_id": oid(),
+            "code": "4000417025005",
+            "generic_name": "Chocolate",
+            "labels_tags": ["en:vegan"],
+            "allergens_tags": ["en:soybeans"],
+            "countries_tags": ["en:germany"],
+        })
+        .unwrap();
+        let payload: serde_json::Value = product.payload().into();
+        assert_eq!(payload["code"], "4000417025005");
+        assert_eq!(payload["product_name"], "Chocolate");
+        assert_eq!(payload["labels_tags"], json!(["en:vegan"]));
+        assert_eq!(payload["allergens_tags"], json!(["en:soybeans"]));
+        assert_eq!(payload["countries_tags"], json!(["en:germany"]));
+        assert_eq!(payload["traces_tags"], json!([]));
+        assert_eq!(payload["ingredients_text"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn payload_keeps_unknown_allergens_null() {
+        let product = |document: Document| {
+            let product = ProductDoc::from_document(document).unwrap();
+            serde_json::Value::from(product.payload())
+        };
+        let unknown = product(doc! { "_id": oid(), "code": "1", "ingredients_text": " " });
+        assert_eq!(unknown["allergens_tags"], serde_json::Value::Null);
+        assert_eq!(unknown["ingredients_text"], serde_json::Value::Null);
+        let known = product(doc! {
+            "_id": oid(),
+            "code": "1",
+            "ingredients_text": "Sugar, cocoa",
+            "allergens_tags": [],
+        });
+        assert_eq!(known["allergens_tags"], json!([]));
+        assert_eq!(known["ingredients_text"], "Sugar, cocoa");
+    }
+}
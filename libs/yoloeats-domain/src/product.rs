@@ -0,0 +1,151 @@
+use crate::serde_util::{lenient_number, null_as_default};
+use serde::{Deserialize, Serialize};
+
+/// The subset of a catalog product other services rely on. Field names match the
+/// catalog's `Product` JSON, so a full product response deserializes into this.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ProductSummary {
+    pub code: String,
+    #[serde(default)]
+    pub product_name: Option<String>,
+    #[serde(default)]
+    pub ingredients_text: Option<String>,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub allergens_tags: Vec<String>,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub traces_tags: Vec<String>,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub labels_tags: Vec<String>,
+    /// OpenFoodFacts' structured ingredients, when the product has them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ingredients: Option<Vec<IngredientEntry>>,
+}
+
+/// One entry of OpenFoodFacts' structured `ingredients` list. Dumps write
+/// `percent_estimate` as a number or a string, and `vegan`/`vegetarian` as `yes`, `no`
+/// or `maybe`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct IngredientEntry {
+    /// The taxonomy id, e.g. `en:whole-milk-powder`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// As printed on the package.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "lenient_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub percent_estimate: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vegan: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vegetarian: Option<String>,
+}
+
+impl IngredientEntry {
+    /// The ingredient's English name: its `en:` id as words (`en:whole-milk-powder` is
+    /// `whole milk powder`), or else its lowercased text.
+    pub fn name(&self) -> Option<String> {
+        let from_id = self
+            .id
+            .as_deref()
+            .and_then(|id| id.strip_prefix("en:"))
+            .map(|id| id.replace('-', " "));
+        let from_text = || self.text.as_deref().map(|t| t.trim().to_lowercase());
+        from_id.or_else(from_text).filter(|name| !name.is_empty())
+    }
+
+    /// Whether OpenFoodFacts says the ingredient is not vegan.
+    pub fn not_vegan(&self) -> bool {
+        self.vegan.as_deref() == Some("no")
+    }
+
+    /// Whether OpenFoodFacts says the ingredient is not vegetarian.
+    pub fn not_vegetarian(&self) -> bool {
+        self.vegetarian.as_deref() == Some("no")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample() -> ProductSummary {
+        ProductSummary {
+            code: "4000417025005".to_string(),
+            product_name: Some("Ritter Sport Alpenmilch".to_string()),
+            ingredients_text: Some("sugar, cocoa butter, whole milk powder".to_string()),
+            allergens_tags: vec!["en:milk".to_string()],
+            traces_tags: vec!["en:nuts".to_string()],
+            labels_tags: vec!["en:vegetarian".to_string()],
+            ingredients: None,
+        }
+    }
+
+    #[test]
+    fn json_snapshot() {
+        assert_eq!(
+            serde_json::to_value(sample()).unwrap(),
+            json!({
+                "code": "4000417025005",
+                "product_name": "Ritter Sport Alpenmilch",
+                "ingredients_text": "sugar, cocoa butter, whole milk powder",
+                "allergens_tags": ["en:milk"],
+                "traces_tags": ["en:nuts"],
+                "labels_tags": ["en:vegetarian"]
+            })
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let json = serde_json::to_string(&sample()).unwrap();
+        assert_eq!(serde_json::from_str::<ProductSummary>(&json).unwrap(), sample());
+    }
+
+    #[test]
+    fn tolerates_nulls_missing_fields_and_extra_catalog_fields() {
+        let summary: ProductSummary = serde_json::from_value(json!({
+            "_id": {"$oid": "663a1f2e9b1e8a3f4c5d6e7f"},
+            "code": "123",
+            "traces_tags": null,
+            "brands_tags": ["ferrero"],
+            "created_datetime": "2024-06-01T08:00:00.000Z"
+        }))
+        .unwrap();
+        assert_eq!(summary.code, "123");
+        assert!(summary.traces_tags.is_empty());
+        assert!(summary.allergens_tags.is_empty());
+        assert_eq!(summary.product_name, None);
+    }
+
+    #[test]
+    fn ingredients_read_leniently() {
+        let summary: ProductSummary = serde_json::from_value(json!({
+            "code": "4000417025005",
+            "ingredients": [
+                { "id": "en:sugar", "text": "Zucker", "percent_estimate": 51.2, "vegan": "yes" },
+                { "id": "en:whole-milk-powder", "percent_estimate": "14,5", "vegan": "no", "vegetarian": "yes", "rank": 2 },
+                { "text": " Kakaobutter ", "percent_estimate": "unknown" },
+                { "percent_estimate": {"min": 1} }
+            ]
+        }))
+        .unwrap();
+        let ingredients = summary.ingredients.unwrap();
+        assert_eq!(ingredients[0].percent_estimate, Some(51.2));
+        assert_eq!(ingredients[1].percent_estimate, Some(14.5));
+        assert_eq!(ingredients[2].percent_estimate, None);
+        assert_eq!(ingredients[3].percent_estimate, None);
+
+        assert_eq!(ingredients[1].name().as_deref(), Some("whole milk powder"));
+        assert_eq!(ingredients[2].name().as_deref(), Some("kakaobutter"));
+        assert_eq!(ingredients[3].name(), None);
+        assert!(ingredients[1].not_vegan() && !ingredients[1].not_vegetarian());
+        assert!(!ingredients[2].not_vegan());
+    }
+}
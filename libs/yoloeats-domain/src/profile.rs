@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum RiskLevel {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+/// The safety-relevant part of a user profile, as served by the user-profile-service.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SafetyProfile {
+    pub user_id: String,
+    #[serde(default)]
+    pub allergens: Vec<String>,
+    #[serde(default)]
+    pub dietary_prefs: Vec<String>,
+    #[serde(default)]
+    pub risk_tolerance: RiskLevel,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AllergenInfo {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn safety_profile_json_snapshot() {
+        let profile = SafetyProfile {
+            user_id: "user-1".to_string(),
+            allergens: vec!["peanuts".to_string()],
+            dietary_prefs: vec!["vegan".to_string()],
+            risk_tolerance: RiskLevel::Low,
+        };
+        let value = serde_json::to_value(&profile).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "user_id": "user-1",
+                "allergens": ["peanuts"],
+                "dietary_prefs": ["vegan"],
+                "risk_tolerance": "low"
+            })
+        );
+        assert_eq!(serde_json::from_value::<SafetyProfile>(value).unwrap(), profile);
+    }
+
+    #[test]
+    fn safety_profile_defaults_missing_lists() {
+        let profile: SafetyProfile = serde_json::from_value(json!({
+            "user_id": "user-2",
+            "username": "ignored",
+            "created_at": "2024-06-01T08:00:00.000Z"
+        }))
+        .unwrap();
+        assert!(profile.allergens.is_empty());
+        assert!(profile.dietary_prefs.is_empty());
+        assert_eq!(profile.risk_tolerance, RiskLevel::Medium);
+    }
+
+    #[test]
+    fn allergen_info_json_snapshot() {
+        let info = AllergenInfo {
+            id: "milk".to_string(),
+            name: "Milk".to_string(),
+            description: None,
+        };
+        let value = serde_json::to_value(&info).unwrap();
+        assert_eq!(value, json!({"id": "milk", "name": "Milk", "description": null}));
+        assert_eq!(serde_json::from_value::<AllergenInfo>(value).unwrap(), info);
+    }
+}
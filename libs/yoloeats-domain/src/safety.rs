@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum SafetyStatus {
+    Safe,
+    Unsafe,
+    Caution,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct CheckResult {
+    pub status: SafetyStatus,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub conflicting_allergens: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub conflicting_diets: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub trace_allergens: Vec<String>,
+    pub is_offline_result: bool, // Indicate if result was based on cached/offline data (TODO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn check_result_json_snapshot() {
+        let result = CheckResult {
+            status: SafetyStatus::Unsafe,
+            conflicting_allergens: vec!["milk".to_string()],
+            conflicting_diets: vec![],
+            trace_allergens: vec!["nuts".to_string()],
+            is_offline_result: false,
+        };
+        let value = serde_json::to_value(&result).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "status": "unsafe",
+                "conflictingAllergens": ["milk"],
+                "traceAllergens": ["nuts"],
+                "isOfflineResult": false
+            })
+        );
+        assert_eq!(serde_json::from_value::<CheckResult>(value).unwrap(), result);
+    }
+
+    #[test]
+    fn safety_status_values() {
+        for (status, text) in [
+            (SafetyStatus::Safe, "safe"),
+            (SafetyStatus::Unsafe, "unsafe"),
+            (SafetyStatus::Caution, "caution"),
+        ] {
+            assert_eq!(serde_json::to_value(&status).unwrap(), json!(text));
+        }
+    }
+}
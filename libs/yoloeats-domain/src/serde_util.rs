@@ -0,0 +1,33 @@
+use serde::{Deserialize, Deserializer};
+
+/// Treats an explicit JSON `null` like a missing field. Producers serialize
+/// `Option<Vec<_>>` as `null`, consumers want an empty collection.
+pub(crate) fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrText {
+    Number(f64),
+    Text(String),
+    Other(serde::de::IgnoredAny),
+}
+
+/// A number, or a string holding one (`"12.5"`, `"12,5"`). Anything else, including a
+/// string that isn't a number, reads as `None` rather than failing the whole document.
+pub(crate) fn lenient_number<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let number = match Option::<NumberOrText>::deserialize(deserializer)? {
+        Some(NumberOrText::Number(n)) => Some(n),
+        Some(NumberOrText::Text(text)) => text.trim().replace(',', ".").parse().ok(),
+        Some(NumberOrText::Other(_)) | None => None,
+    };
+    Ok(number.filter(|n: &f64| n.is_finite()))
+}
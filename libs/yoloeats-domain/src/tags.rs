@@ -0,0 +1,141 @@
+//! OpenFoodFacts-style taxonomy tags (`en:milk`, `en:sesame-seeds`, `ritter-sport`).
+//!
+//! Catalog queries match tags exactly, so everything that writes products (the catalog
+//! API, the dump importer) normalizes through here.
+
+/// The EU's fourteen declarable allergens, as OpenFoodFacts tags them.
+pub const KNOWN_ALLERGENS: &[&str] = &[
+    "en:celery",
+    "en:crustaceans",
+    "en:eggs",
+    "en:fish",
+    "en:gluten",
+    "en:lupin",
+    "en:milk",
+    "en:molluscs",
+    "en:mustard",
+    "en:nuts",
+    "en:peanuts",
+    "en:sesame-seeds",
+    "en:soybeans",
+    "en:sulphur-dioxide-and-sulphites",
+];
+
+/// Lowercases `raw` and joins its words with single hyphens, keeping a two-letter
+/// language prefix: `" EN:Sesame Seeds "` becomes `en:sesame-seeds`. `None` if nothing
+/// but punctuation is left.
+pub fn normalize_tag(raw: &str) -> Option<String> {
+    let raw = raw.trim();
+    let (prefix, body) = match raw.split_once(':') {
+        Some((lang, body)) if lang.len() == 2 && lang.chars().all(|c| c.is_ascii_alphabetic()) => {
+            (Some(lang.to_ascii_lowercase()), body)
+        }
+        _ => (None, raw),
+    };
+
+    let mut slug = String::with_capacity(body.len());
+    for c in body.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        return None;
+    }
+    Some(match prefix {
+        Some(lang) => format!("{}:{}", lang, slug),
+        None => slug.to_string(),
+    })
+}
+
+/// [`normalize_tag`] over a list, dropping empties and duplicates but keeping order.
+pub fn normalize_tags<I, S>(raw: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut tags: Vec<String> = Vec::new();
+    for tag in raw.into_iter().filter_map(|t| normalize_tag(t.as_ref())) {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// The recognised allergens among `raw`, as `en:` tags. Bare names (`milk`) get the
+/// `en:` prefix; anything not in [`KNOWN_ALLERGENS`] is dropped, because allergen
+/// filters only know those.
+pub fn extract_allergen_tags<I, S>(raw: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut allergens: Vec<String> = Vec::new();
+    for tag in normalize_tags(raw) {
+        let tag = if tag.contains(':') {
+            tag
+        } else {
+            format!("en:{}", tag)
+        };
+        if KNOWN_ALLERGENS.contains(&tag.as_str()) && !allergens.contains(&tag) {
+            allergens.push(tag);
+        }
+    }
+    allergens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_case_spacing_and_punctuation() {
+        assert_eq!(
+            normalize_tag(" EN:Sesame Seeds ").as_deref(),
+            Some("en:sesame-seeds")
+        );
+        assert_eq!(
+            normalize_tag("Ritter Sport").as_deref(),
+            Some("ritter-sport")
+        );
+        assert_eq!(
+            normalize_tag("de:Bio--Produkte!").as_deref(),
+            Some("de:bio-produkte")
+        );
+        assert_eq!(normalize_tag("en:müsli").as_deref(), Some("en:müsli"));
+        assert_eq!(normalize_tag("en:vegan").as_deref(), Some("en:vegan"));
+    }
+
+    #[test]
+    fn only_two_letter_prefixes_are_languages() {
+        assert_eq!(normalize_tag("E:number").as_deref(), Some("e-number"));
+        assert_eq!(normalize_tag("abc:def").as_deref(), Some("abc-def"));
+    }
+
+    #[test]
+    fn empty_tags_are_dropped() {
+        assert_eq!(normalize_tag("  "), None);
+        assert_eq!(normalize_tag("en: - "), None);
+        assert_eq!(
+            normalize_tags(["en:Vegan", "", "en:vegan", "en:organic"]),
+            vec!["en:vegan".to_string(), "en:organic".to_string()]
+        );
+    }
+
+    #[test]
+    fn allergen_extraction_keeps_known_allergens_only() {
+        assert_eq!(
+            extract_allergen_tags(["en:milk", "Milk", "en:Nuts", "en:palm-oil", "sesame seeds"]),
+            vec![
+                "en:milk".to_string(),
+                "en:nuts".to_string(),
+                "en:sesame-seeds".to_string()
+            ]
+        );
+        assert!(extract_allergen_tags(["fr:lait"]).is_empty());
+    }
+}
@@ -0,0 +1,260 @@
+//! `validator::ValidationErrors` in the error envelope (feature `validation`).
+//!
+//! Every payload-validating handler answers the same way: a 422 `validation_failed`
+//! whose `message` lists the failures for humans and whose `details` is one
+//! `{"field", "code", "message", "params"}` entry per failure for clients to map onto
+//! their form fields. Nested fields are dotted paths, list entries are indexed:
+//! `items[0].name`. Query parameters that fail validation stay a 400 `invalid_request`
+//! with the same `details`.
+
+use crate::error::{ErrorBody, ErrorCode};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fmt::Write;
+use validator::{ValidationErrors, ValidationErrorsKind};
+
+/// One failed rule on one field.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    /// The rule's code: `length`, `email`, or a custom validator's.
+    pub code: String,
+    pub message: String,
+    /// The rule's arguments, e.g. `{"min": 3}`. The rejected value that `validator`
+    /// adds as `value` is left out: the client already has it, and logs of error
+    /// bodies should not collect it.
+    pub params: Map<String, Value>,
+}
+
+/// Flattens `errors` in a stable order: fields sorted by name, list entries by index,
+/// a field's failures in the order its rules are declared.
+pub fn field_errors(errors: &ValidationErrors) -> Vec<FieldError> {
+    let mut out = Vec::new();
+    collect(errors, "", &mut out);
+    out
+}
+
+fn collect(errors: &ValidationErrors, prefix: &str, out: &mut Vec<FieldError>) {
+    let mut fields: Vec<_> = errors.errors().iter().collect();
+    fields.sort_by_key(|(field, _)| *field);
+
+    for (field, kind) in fields {
+        let path = if prefix.is_empty() {
+            field.to_string()
+        } else {
+            format!("{}.{}", prefix, field)
+        };
+        match kind {
+            ValidationErrorsKind::Field(failures) => {
+                out.extend(failures.iter().map(|failure| {
+                    FieldError {
+                        field: path.clone(),
+                        code: failure.code.to_string(),
+                        message: failure
+                            .message
+                            .as_deref()
+                            .map(str::to_string)
+                            .unwrap_or_else(|| format!("Invalid value ({})", failure.code)),
+                        params: failure
+                            .params
+                            .iter()
+                            .filter(|(name, _)| *name != "value")
+                            .map(|(name, value)| (name.to_string(), value.clone()))
+                            .collect(),
+                    }
+                }));
+            }
+            ValidationErrorsKind::Struct(nested) => collect(nested, &path, out),
+            ValidationErrorsKind::List(entries) => {
+                for (index, nested) in entries {
+                    collect(nested, &format!("{}[{}]", path, index), out);
+                }
+            }
+        }
+    }
+}
+
+/// The `details` array for the error envelope.
+pub fn validation_details(errors: &ValidationErrors) -> Value {
+    serde_json::to_value(field_errors(errors)).expect("field errors serialize")
+}
+
+/// `field: message; other.field: message`, for the envelope's `message` and for logs.
+pub fn validation_summary(errors: &ValidationErrors) -> String {
+    let mut summary = String::new();
+    for (i, failure) in field_errors(errors).iter().enumerate() {
+        if i > 0 {
+            summary.push_str("; ");
+        }
+        let _ = write!(summary, "{}: {}", failure.field, failure.message);
+    }
+    summary
+}
+
+impl ErrorBody {
+    /// `validation_failed` with the summary as `message` and [`validation_details`].
+    pub fn validation(errors: &ValidationErrors) -> Self {
+        ErrorBody::new(
+            ErrorCode::ValidationFailed,
+            format!("Input validation failed: {}", validation_summary(errors)),
+        )
+        .with_details(validation_details(errors))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use validator::Validate;
+
+    #[derive(Validate)]
+    struct Ingredient {
+        #[validate(length(min = 1, message = "Name is required"))]
+        name: String,
+        #[validate(range(min = 0.0, max = 100.0, message = "Percent must be 0-100"))]
+        percent: f64,
+    }
+
+    #[derive(Validate)]
+    struct Address {
+        #[validate(length(min = 2, max = 2))]
+        country: String,
+    }
+
+    #[derive(Validate)]
+    struct Payload {
+        #[validate(length(min = 3, message = "Username must be at least 3 characters long"))]
+        username: String,
+        #[validate(nested)]
+        address: Address,
+        #[validate(nested)]
+        items: Vec<Ingredient>,
+    }
+
+    fn valid() -> Payload {
+        Payload {
+            username: "alice".to_string(),
+            address: Address {
+                country: "DE".to_string(),
+            },
+            items: vec![Ingredient {
+                name: "milk".to_string(),
+                percent: 10.0,
+            }],
+        }
+    }
+
+    #[test]
+    fn top_level_field_errors_keep_their_params_but_not_the_value() {
+        let payload = Payload {
+            username: "al".to_string(),
+            ..valid()
+        };
+        let errors = payload.validate().unwrap_err();
+        assert_eq!(
+            validation_details(&errors),
+            json!([{
+                "field": "username",
+                "code": "length",
+                "message": "Username must be at least 3 characters long",
+                "params": { "min": 3 }
+            }])
+        );
+    }
+
+    #[test]
+    fn nested_struct_errors_use_dotted_paths_and_a_default_message() {
+        let payload = Payload {
+            address: Address {
+                country: "Germany".to_string(),
+            },
+            ..valid()
+        };
+        let errors = payload.validate().unwrap_err();
+        let fields = field_errors(&errors);
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].field, "address.country");
+        assert_eq!(fields[0].code, "length");
+        assert_eq!(fields[0].message, "Invalid value (length)");
+        assert_eq!(fields[0].params.get("max"), Some(&json!(2)));
+    }
+
+    #[test]
+    fn list_errors_are_indexed_and_ordered() {
+        let payload = Payload {
+            items: vec![
+                Ingredient {
+                    name: "milk".to_string(),
+                    percent: 10.0,
+                },
+                Ingredient {
+                    name: String::new(),
+                    percent: 150.0,
+                },
+                Ingredient {
+                    name: String::new(),
+                    percent: 5.0,
+                },
+            ],
+            ..valid()
+        };
+        let errors = payload.validate().unwrap_err();
+        let fields: Vec<_> = field_errors(&errors)
+            .into_iter()
+            .map(|f| (f.field, f.code))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("items[1].name".to_string(), "length".to_string()),
+                ("items[1].percent".to_string(), "range".to_string()),
+                ("items[2].name".to_string(), "length".to_string()),
+            ]
+        );
+    }
+
+    #[derive(Validate)]
+    struct German {
+        #[validate(length(
+            min = 3,
+            message = "Der Benutzername muss mindestens 3 Zeichen lang sein – „ü“"
+        ))]
+        benutzername: String,
+    }
+
+    #[test]
+    fn unicode_messages_pass_through_untouched() {
+        let errors = German {
+            benutzername: "ä".to_string(),
+        }
+        .validate()
+        .unwrap_err();
+        let body = serde_json::to_value(ErrorBody::validation(&errors)).unwrap();
+        assert_eq!(body["code"], "validation_failed");
+        assert_eq!(
+            body["message"],
+            "Input validation failed: benutzername: Der Benutzername muss mindestens 3 Zeichen lang sein – „ü“"
+        );
+        assert_eq!(
+            body["details"][0]["message"],
+            "Der Benutzername muss mindestens 3 Zeichen lang sein – „ü“"
+        );
+    }
+
+    #[test]
+    fn summary_joins_every_failure() {
+        let payload = Payload {
+            username: "al".to_string(),
+            address: Address {
+                country: "D".to_string(),
+            },
+            ..valid()
+        };
+        let errors = payload.validate().unwrap_err();
+        assert_eq!(
+            validation_summary(&errors),
+            "address.country: Invalid value (length); username: Username must be at least 3 characters long"
+        );
+    }
+}
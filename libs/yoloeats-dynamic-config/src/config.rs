@@ -0,0 +1,433 @@
+use crate::error::ConfigError;
+use crate::store::OverrideStore;
+use crate::tunable::{Definition, Erased, Tunable, TunableValue};
+use arc_swap::ArcSwap;
+use serde::Serialize;
+use std::{
+    any::type_name,
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    task::JoinHandle,
+    time::{MissedTickBehavior, interval},
+};
+use tracing::{info, warn};
+
+/// How often [`DynamicConfig::spawn_refresh`] re-reads the overrides in production.
+pub const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The values handlers read, replaced as a whole on every refresh.
+#[derive(Default)]
+struct Snapshot {
+    values: HashMap<&'static str, Erased>,
+    /// Raw override behind each value that came from the store.
+    overrides: HashMap<&'static str, String>,
+}
+
+/// One tunable as shown by `GET /internal/v1/config`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TunableEntry {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub value: String,
+    pub default: String,
+    pub overridden: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConfigView {
+    pub service: &'static str,
+    pub tunables: Vec<TunableEntry>,
+}
+
+pub struct DynamicConfigBuilder {
+    service: &'static str,
+    definitions: Vec<Definition>,
+    errors: BTreeMap<String, String>,
+}
+
+impl DynamicConfigBuilder {
+    pub fn register<T: TunableValue>(
+        self,
+        tunable: Tunable<T>,
+        default: T,
+        description: &'static str,
+    ) -> Self {
+        self.register_validated(tunable, default, description, |_| Ok(()))
+    }
+
+    /// Like [`DynamicConfigBuilder::register`], but overrides (and the default) must also
+    /// pass `validate`; see [`in_range`](crate::in_range).
+    pub fn register_validated<T, V>(
+        mut self,
+        tunable: Tunable<T>,
+        default: T,
+        description: &'static str,
+        validate: V,
+    ) -> Self
+    where
+        T: TunableValue,
+        V: Fn(&T) -> Result<(), String> + Send + Sync + 'static,
+    {
+        match Definition::new(tunable, default, description, validate) {
+            Ok(definition) => self.definitions.push(definition),
+            Err(reason) => {
+                self.errors.insert(tunable.name().to_string(), reason);
+            }
+        }
+        self
+    }
+
+    /// Fails if a default doesn't pass its validator. The config starts out with the
+    /// defaults; overrides arrive with the first refresh.
+    pub fn build(self, store: impl OverrideStore + 'static) -> Result<DynamicConfig, ConfigError> {
+        if !self.errors.is_empty() {
+            return Err(ConfigError::Invalid(self.errors));
+        }
+        let snapshot = Snapshot {
+            values: self
+                .definitions
+                .iter()
+                .map(|d| (d.name, d.default.clone()))
+                .collect(),
+            overrides: HashMap::new(),
+        };
+        Ok(DynamicConfig {
+            inner: Arc::new(Inner {
+                service: self.service,
+                definitions: self.definitions,
+                snapshot: ArcSwap::from_pointee(snapshot),
+                store: Box::new(store),
+            }),
+        })
+    }
+}
+
+struct Inner {
+    service: &'static str,
+    definitions: Vec<Definition>,
+    snapshot: ArcSwap<Snapshot>,
+    store: Box<dyn OverrideStore>,
+}
+
+/// A service's tunables and their current values. Cheap to clone.
+#[derive(Clone)]
+pub struct DynamicConfig {
+    inner: Arc<Inner>,
+}
+
+impl DynamicConfig {
+    pub fn builder(service: &'static str) -> DynamicConfigBuilder {
+        DynamicConfigBuilder {
+            service,
+            definitions: Vec::new(),
+            errors: BTreeMap::new(),
+        }
+    }
+
+    pub fn service(&self) -> &'static str {
+        self.inner.service
+    }
+
+    fn definition(&self, name: &str) -> Option<&Definition> {
+        self.inner.definitions.iter().find(|d| d.name == name)
+    }
+
+    /// The current value. Panics if `tunable` was never registered, or registered with
+    /// another type: both are wiring bugs a service's tests should catch.
+    pub fn get<T: TunableValue>(&self, tunable: Tunable<T>) -> T {
+        self.inner
+            .snapshot
+            .load()
+            .values
+            .get(tunable.name())
+            .and_then(Erased::downcast::<T>)
+            .unwrap_or_else(|| {
+                panic!(
+                    "tunable '{}' is not registered as {} for {}",
+                    tunable.name(),
+                    type_name::<T>(),
+                    self.inner.service
+                )
+            })
+    }
+
+    pub fn view(&self) -> ConfigView {
+        let snapshot = self.inner.snapshot.load();
+        ConfigView {
+            service: self.inner.service,
+            tunables: self
+                .inner
+                .definitions
+                .iter()
+                .map(|d| TunableEntry {
+                    name: d.name,
+                    description: d.description,
+                    value: snapshot.values[d.name].rendered().to_string(),
+                    default: d.default.rendered().to_string(),
+                    overridden: snapshot.overrides.contains_key(d.name),
+                })
+                .collect(),
+        }
+    }
+
+    /// Swaps in a snapshot built from `overrides`. Tunables without an override go back
+    /// to their default; an invalid override is ignored and the tunable keeps the value
+    /// it had, so a typo in Redis can't take a setting somewhere unexpected.
+    fn apply(&self, overrides: &HashMap<String, String>) {
+        let current = self.inner.snapshot.load();
+        let mut next = Snapshot::default();
+        for definition in &self.inner.definitions {
+            let name = definition.name;
+            let (value, raw) = match overrides.get(name) {
+                None => (definition.default.clone(), None),
+                Some(raw) => match definition.parse(raw) {
+                    Ok(value) => (value, Some(raw.clone())),
+                    Err(reason) => {
+                        warn!(
+                            service = self.inner.service,
+                            tunable = name,
+                            "Ignoring invalid override: {}",
+                            reason
+                        );
+                        (
+                            current.values[name].clone(),
+                            current.overrides.get(name).cloned(),
+                        )
+                    }
+                },
+            };
+            if value.rendered() != current.values[name].rendered() {
+                info!(
+                    service = self.inner.service,
+                    tunable = name,
+                    "Tunable changed: {} -> {}",
+                    current.values[name].rendered(),
+                    value.rendered()
+                );
+            }
+            next.values.insert(name, value);
+            if let Some(raw) = raw {
+                next.overrides.insert(name, raw);
+            }
+        }
+        for name in overrides.keys() {
+            if self.definition(name).is_none() {
+                warn!(
+                    service = self.inner.service,
+                    "Ignoring override for unknown tunable '{}'", name
+                );
+            }
+        }
+        self.inner.snapshot.store(Arc::new(next));
+    }
+
+    /// Re-reads the overrides from the store. On error the current values stay.
+    pub async fn refresh(&self) -> Result<(), ConfigError> {
+        let overrides = self.inner.store.load().await?;
+        self.apply(&overrides);
+        Ok(())
+    }
+
+    /// Validates every change first and writes nothing if any is refused. `None` removes
+    /// the override. This replica sees the result immediately, the others within one
+    /// refresh interval.
+    pub async fn update(
+        &self,
+        changes: BTreeMap<String, Option<String>>,
+    ) -> Result<(), ConfigError> {
+        let mut errors = BTreeMap::new();
+        let mut set = Vec::new();
+        let mut remove = Vec::new();
+        for (name, value) in changes {
+            let Some(definition) = self.definition(&name) else {
+                errors.insert(name, "unknown tunable".to_string());
+                continue;
+            };
+            match value {
+                Some(raw) => match definition.parse(&raw) {
+                    Ok(_) => set.push((name, raw.trim().to_string())),
+                    Err(reason) => {
+                        errors.insert(name, reason);
+                    }
+                },
+                None => remove.push(name),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(ConfigError::Invalid(errors));
+        }
+
+        info!(
+            service = self.inner.service,
+            "Updating config overrides: set {:?}, removed {:?}", set, remove
+        );
+        self.inner.store.save(&set, &remove).await?;
+        self.refresh().await
+    }
+
+    /// Refreshes every `every`, starting now. Store errors are logged and retried on the
+    /// next tick.
+    pub fn spawn_refresh(&self, every: Duration) -> JoinHandle<()> {
+        let config = self.clone();
+        tokio::spawn(async move {
+            let mut ticks = interval(every);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticks.tick().await;
+                if let Err(e) = config.refresh().await {
+                    warn!(
+                        service = config.inner.service,
+                        "Could not refresh config overrides, keeping current values: {}", e
+                    );
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+    use crate::tunable::in_range;
+
+    pub(crate) const TTL: Tunable<u64> = Tunable::new("cache_ttl_secs");
+    pub(crate) const GREETING: Tunable<String> = Tunable::new("greeting");
+
+    pub(crate) fn config(store: &MemoryStore) -> DynamicConfig {
+        DynamicConfig::builder("svc")
+            .register_validated(TTL, 300, "Cache TTL", in_range(1, 3600))
+            .register(GREETING, "hello".to_string(), "Greeting")
+            .build(store.clone())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn starts_with_defaults_and_applies_overrides_on_refresh() {
+        let store = MemoryStore::default();
+        let config = config(&store);
+        assert_eq!(config.get(TTL), 300);
+
+        store.set("cache_ttl_secs", "600");
+        assert_eq!(config.get(TTL), 300, "nothing changes until a refresh");
+        config.refresh().await.unwrap();
+        assert_eq!(config.get(TTL), 600);
+        assert_eq!(config.get(GREETING), "hello");
+
+        store.remove("cache_ttl_secs");
+        config.refresh().await.unwrap();
+        assert_eq!(
+            config.get(TTL),
+            300,
+            "a removed override falls back to the default"
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_overrides_are_ignored() {
+        let store = MemoryStore::default();
+        let config = config(&store);
+        store.set("cache_ttl_secs", "600");
+        config.refresh().await.unwrap();
+
+        for bad in ["0", "forever", "-5"] {
+            store.set("cache_ttl_secs", bad);
+            store.set("no_such_tunable", "1");
+            config.refresh().await.unwrap();
+            assert_eq!(config.get(TTL), 600, "{} keeps the previous value", bad);
+        }
+        let entry = &config.view().tunables[0];
+        assert_eq!(entry.value, "600");
+        assert!(entry.overridden);
+
+        // Starting from defaults, an invalid override leaves the default in place.
+        let fresh = DynamicConfig::builder("svc")
+            .register_validated(TTL, 300, "", in_range(1, 3600))
+            .build(store.clone())
+            .unwrap();
+        fresh.refresh().await.unwrap();
+        assert_eq!(fresh.get(TTL), 300);
+        assert!(!fresh.view().tunables[0].overridden);
+    }
+
+    #[tokio::test]
+    async fn updates_are_validated_as_a_whole() {
+        let store = MemoryStore::default();
+        let config = config(&store);
+
+        let err = config
+            .update(BTreeMap::from([
+                ("cache_ttl_secs".to_string(), Some("60".to_string())),
+                ("greeting".to_string(), Some("hi".to_string())),
+                ("nope".to_string(), Some("1".to_string())),
+            ]))
+            .await
+            .unwrap_err();
+        match err {
+            ConfigError::Invalid(errors) => {
+                assert_eq!(errors.keys().collect::<Vec<_>>(), ["nope"]);
+            }
+            other => panic!("expected Invalid, got {:?}", other),
+        }
+        assert_eq!(config.get(TTL), 300, "nothing applied");
+        assert!(store.load().await.unwrap().is_empty(), "nothing stored");
+
+        config
+            .update(BTreeMap::from([(
+                "cache_ttl_secs".to_string(),
+                Some(" 60 ".to_string()),
+            )]))
+            .await
+            .unwrap();
+        assert_eq!(config.get(TTL), 60, "applied locally without waiting");
+        assert_eq!(store.load().await.unwrap()["cache_ttl_secs"], "60");
+
+        config
+            .update(BTreeMap::from([("cache_ttl_secs".to_string(), None)]))
+            .await
+            .unwrap();
+        assert_eq!(config.get(TTL), 300);
+        assert!(store.load().await.unwrap().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn overrides_propagate_within_one_refresh_interval() {
+        let store = MemoryStore::default();
+        let config = config(&store);
+        let every = Duration::from_secs(30);
+        let task = config.spawn_refresh(every);
+        tokio::time::sleep(Duration::from_millis(1)).await;
+
+        // Written just after a refresh: the worst case.
+        store.set("cache_ttl_secs", "900");
+        tokio::time::sleep(every - Duration::from_secs(1)).await;
+        assert_eq!(config.get(TTL), 300);
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert_eq!(config.get(TTL), 900);
+
+        task.abort();
+    }
+
+    #[test]
+    fn invalid_defaults_fail_the_build() {
+        let err = DynamicConfig::builder("svc")
+            .register_validated(TTL, 0, "", in_range(1, 3600))
+            .build(MemoryStore::default())
+            .err()
+            .unwrap();
+        assert!(
+            matches!(err, ConfigError::Invalid(errors) if errors.contains_key("cache_ttl_secs"))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "tunable 'cache_ttl_secs' is not registered as u32")]
+    fn reading_with_the_wrong_type_panics() {
+        let config = config(&MemoryStore::default());
+        config.get(Tunable::<u32>::new("cache_ttl_secs"));
+    }
+}
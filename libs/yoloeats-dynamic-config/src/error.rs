@@ -0,0 +1,45 @@
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+use std::collections::BTreeMap;
+use thiserror::Error;
+use tracing::error;
+use yoloeats_domain::{ErrorBody, ErrorCode};
+use yoloeats_tracing::current_request_id;
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Redis error: {0}")]
+    Redis(#[from] redis::RedisError),
+
+    /// Tunable name -> why its value (or default) was refused.
+    #[error("Invalid tunable values: {0:?}")]
+    Invalid(BTreeMap<String, String>),
+}
+
+impl IntoResponse for ConfigError {
+    fn into_response(self) -> Response {
+        let (status, body) = match &self {
+            ConfigError::Redis(e) => {
+                error!("Config override store error: {}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorBody::new(ErrorCode::CacheError, "Config override store unavailable"),
+                )
+            }
+            ConfigError::Invalid(errors) => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new(ErrorCode::InvalidRequest, "Invalid tunable values")
+                    .with_details(json!(errors)),
+            ),
+        };
+        (
+            status,
+            Json(body.with_request_id(current_request_id().map(|id| id.to_string()))),
+        )
+            .into_response()
+    }
+}
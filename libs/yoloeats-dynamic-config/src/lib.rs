@@ -0,0 +1,25 @@
+//! Runtime-tunable settings shared by the YoloEats services.
+//!
+//! A service declares each setting as a typed [`Tunable`] constant, registers it with a
+//! default (usually read from the environment via [`env_default`]) and reads the current
+//! value with [`DynamicConfig::get`]. Overrides live in the Redis hash `config:{service}`:
+//! a background task ([`DynamicConfig::spawn_refresh`]) polls it, validates every field
+//! and swaps the new values in atomically, so a handler always sees one consistent
+//! snapshot. Invalid overrides are logged and ignored; the previous value stays.
+//!
+//! [`config_router`] serves `GET`/`PUT /internal/v1/config` to inspect and change the
+//! overrides. It sits under `/internal`, so the services' `InternalTokenLayer` guards it.
+
+mod config;
+mod error;
+mod router;
+mod store;
+mod tunable;
+
+pub use config::{
+    ConfigView, DEFAULT_REFRESH_INTERVAL, DynamicConfig, DynamicConfigBuilder, TunableEntry,
+};
+pub use error::ConfigError;
+pub use router::{CONFIG_PATH, config_router};
+pub use store::{MemoryStore, OverrideStore, RedisStore, redis_key};
+pub use tunable::{Tunable, TunableValue, env_default, in_range};
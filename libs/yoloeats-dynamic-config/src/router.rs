@@ -0,0 +1,154 @@
+use crate::config::{ConfigView, DynamicConfig};
+use crate::error::ConfigError;
+use axum::{Json, Router, extract::State, routing::get};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use tracing::{info, instrument};
+
+pub const CONFIG_PATH: &str = "/internal/v1/config";
+
+#[instrument(skip_all, fields(service = config.service()))]
+async fn show_config(State(config): State<DynamicConfig>) -> Json<ConfigView> {
+    info!("Listing tunables");
+    Json(config.view())
+}
+
+/// Body: `{"name": value, ...}`. Strings, numbers and booleans set an override, `null`
+/// removes it; tunables not mentioned are left alone.
+#[instrument(skip_all, fields(service = config.service()))]
+async fn update_config(
+    State(config): State<DynamicConfig>,
+    Json(body): Json<Map<String, Value>>,
+) -> Result<Json<ConfigView>, ConfigError> {
+    let mut changes = BTreeMap::new();
+    let mut errors = BTreeMap::new();
+    for (name, value) in body {
+        let value = match value {
+            Value::Null => None,
+            Value::String(s) => Some(s),
+            Value::Number(n) => Some(n.to_string()),
+            Value::Bool(b) => Some(b.to_string()),
+            Value::Array(_) | Value::Object(_) => {
+                errors.insert(
+                    name,
+                    "must be a string, number, boolean or null".to_string(),
+                );
+                continue;
+            }
+        };
+        changes.insert(name, value);
+    }
+    if !errors.is_empty() {
+        return Err(ConfigError::Invalid(errors));
+    }
+    config.update(changes).await?;
+    Ok(Json(config.view()))
+}
+
+/// `GET`/`PUT` [`CONFIG_PATH`], ready to `merge` into a service router of any state type.
+pub fn config_router<S>(config: DynamicConfig) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route(CONFIG_PATH, get(show_config).put(update_config))
+        .with_state(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::tests::{TTL, config};
+    use crate::store::{MemoryStore, OverrideStore};
+    use axum::body::{Body, to_bytes};
+    use axum::http::{Request, StatusCode, header};
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    async fn send(router: &Router, method: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let request = Request::builder().method(method).uri(CONFIG_PATH);
+        let request = match body {
+            Some(body) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        };
+        let response = router.clone().oneshot(request.unwrap()).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn lists_tunables_with_defaults() {
+        let router = config_router(config(&MemoryStore::default()));
+        let (status, body) = send(&router, "GET", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["service"], "svc");
+        assert_eq!(
+            body["tunables"][0],
+            json!({
+                "name": "cache_ttl_secs",
+                "description": "Cache TTL",
+                "value": "300",
+                "default": "300",
+                "overridden": false,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn put_applies_valid_changes() {
+        let config = config(&MemoryStore::default());
+        let router = config_router(config.clone());
+
+        let (status, body) = send(
+            &router,
+            "PUT",
+            Some(json!({ "cache_ttl_secs": 120, "greeting": "moin" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["tunables"][0]["value"], "120");
+        assert_eq!(body["tunables"][0]["overridden"], true);
+        assert_eq!(config.get(TTL), 120);
+
+        let (_, body) = send(&router, "PUT", Some(json!({ "cache_ttl_secs": null }))).await;
+        assert_eq!(body["tunables"][0]["value"], "300");
+        assert_eq!(body["tunables"][1]["value"], "moin", "untouched");
+    }
+
+    #[tokio::test]
+    async fn put_rejects_invalid_values_without_applying_any() {
+        let store = MemoryStore::default();
+        let config = config(&store);
+        let router = config_router(config.clone());
+
+        let (status, body) = send(
+            &router,
+            "PUT",
+            Some(json!({ "cache_ttl_secs": 99999, "greeting": ["a"] })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "invalid_request");
+        assert_eq!(
+            body["details"]["greeting"],
+            "must be a string, number, boolean or null"
+        );
+
+        let (status, body) = send(
+            &router,
+            "PUT",
+            Some(json!({ "cache_ttl_secs": 99999, "greeting": "hi" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body["details"]["cache_ttl_secs"],
+            "'99999' must be between 1 and 3600"
+        );
+        assert_eq!(config.get(TTL), 300);
+        assert!(store.load().await.unwrap().is_empty());
+    }
+}
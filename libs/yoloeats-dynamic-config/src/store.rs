@@ -0,0 +1,96 @@
+use crate::error::ConfigError;
+use async_trait::async_trait;
+use redis::AsyncCommands;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// Where overrides are kept: tunable name -> raw value.
+#[async_trait]
+pub trait OverrideStore: Send + Sync {
+    async fn load(&self) -> Result<HashMap<String, String>, ConfigError>;
+
+    /// Applies `set` and `remove` together.
+    async fn save(&self, set: &[(String, String)], remove: &[String]) -> Result<(), ConfigError>;
+}
+
+/// The hash holding a service's overrides.
+pub fn redis_key(service: &str) -> String {
+    format!("config:{}", service)
+}
+
+/// Overrides in the Redis hash `config:{service}`, shared by every replica.
+pub struct RedisStore {
+    client: redis::Client,
+    key: String,
+}
+
+impl RedisStore {
+    pub fn new(client: redis::Client, service: &str) -> Self {
+        RedisStore {
+            client,
+            key: redis_key(service),
+        }
+    }
+}
+
+#[async_trait]
+impl OverrideStore for RedisStore {
+    async fn load(&self) -> Result<HashMap<String, String>, ConfigError> {
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        Ok(conn.hgetall(&self.key).await?)
+    }
+
+    async fn save(&self, set: &[(String, String)], remove: &[String]) -> Result<(), ConfigError> {
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        if !set.is_empty() {
+            pipe.hset_multiple(&self.key, set).ignore();
+        }
+        if !remove.is_empty() {
+            pipe.hdel(&self.key, remove).ignore();
+        }
+        pipe.query_async::<()>(&mut conn).await?;
+        Ok(())
+    }
+}
+
+/// Process-local overrides, for tests. Clones share the same map.
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    overrides: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl MemoryStore {
+    /// Writes an override directly, the way an operator editing Redis would.
+    pub fn set(&self, name: &str, value: &str) {
+        self.overrides
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), value.to_string());
+    }
+
+    pub fn remove(&self, name: &str) {
+        self.overrides.lock().unwrap().remove(name);
+    }
+}
+
+#[async_trait]
+impl OverrideStore for MemoryStore {
+    async fn load(&self) -> Result<HashMap<String, String>, ConfigError> {
+        Ok(self.overrides.lock().unwrap().clone())
+    }
+
+    async fn save(&self, set: &[(String, String)], remove: &[String]) -> Result<(), ConfigError> {
+        let mut overrides = self.overrides.lock().unwrap();
+        for (name, value) in set {
+            overrides.insert(name.clone(), value.clone());
+        }
+        for name in remove {
+            overrides.remove(name);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,170 @@
+use std::{any::Any, env, fmt::Display, marker::PhantomData, str::FromStr, sync::Arc};
+use tracing::warn;
+
+/// A type a tunable can hold. Values travel as strings (Redis fields, the config
+/// endpoint), so they must round-trip through `FromStr` and `Display`.
+pub trait TunableValue: FromStr<Err: Display> + Display + Clone + Send + Sync + 'static {}
+
+impl<T> TunableValue for T where T: FromStr<Err: Display> + Display + Clone + Send + Sync + 'static {}
+
+/// A typed handle to a registered setting. Declare it as a `const` next to the code that
+/// reads it; the name is the Redis hash field and the key in the config endpoint.
+pub struct Tunable<T> {
+    name: &'static str,
+    _value: PhantomData<fn() -> T>,
+}
+
+impl<T> Tunable<T> {
+    pub const fn new(name: &'static str) -> Self {
+        Tunable {
+            name,
+            _value: PhantomData,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<T> Clone for Tunable<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Tunable<T> {}
+
+/// `var` parsed as `T`, or `fallback` if it is unset. A value that doesn't parse is
+/// logged and replaced by `fallback` rather than stopping the service.
+pub fn env_default<T: TunableValue>(var: &str, fallback: T) -> T {
+    match env::var(var) {
+        Ok(raw) => raw.trim().parse().unwrap_or_else(|e| {
+            warn!("Ignoring {}='{}' ({}), using {}", var, raw, e, fallback);
+            fallback
+        }),
+        Err(_) => fallback,
+    }
+}
+
+/// A validator accepting `min..=max`.
+pub fn in_range<T>(min: T, max: T) -> impl Fn(&T) -> Result<(), String> + Send + Sync + 'static
+where
+    T: PartialOrd + Display + Send + Sync + 'static,
+{
+    move |value| {
+        if *value < min || *value > max {
+            Err(format!("must be between {} and {}", min, max))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// A parsed value with its rendering, type-erased so tunables of different types share
+/// one snapshot.
+#[derive(Clone)]
+pub(crate) struct Erased {
+    value: Arc<dyn Any + Send + Sync>,
+    rendered: String,
+}
+
+impl Erased {
+    fn new<T: TunableValue>(value: T) -> Self {
+        Erased {
+            rendered: value.to_string(),
+            value: Arc::new(value),
+        }
+    }
+
+    pub(crate) fn downcast<T: Clone + 'static>(&self) -> Option<T> {
+        self.value.downcast_ref::<T>().cloned()
+    }
+
+    pub(crate) fn rendered(&self) -> &str {
+        &self.rendered
+    }
+}
+
+type Parser = Box<dyn Fn(&str) -> Result<Erased, String> + Send + Sync>;
+
+/// A registered tunable: its default and how to turn an override string into a
+/// validated value.
+pub(crate) struct Definition {
+    pub(crate) name: &'static str,
+    pub(crate) description: &'static str,
+    pub(crate) default: Erased,
+    parse: Parser,
+}
+
+impl Definition {
+    pub(crate) fn new<T, V>(
+        tunable: Tunable<T>,
+        default: T,
+        description: &'static str,
+        validate: V,
+    ) -> Result<Self, String>
+    where
+        T: TunableValue,
+        V: Fn(&T) -> Result<(), String> + Send + Sync + 'static,
+    {
+        validate(&default).map_err(|reason| format!("default {}: {}", default, reason))?;
+        Ok(Definition {
+            name: tunable.name(),
+            description,
+            default: Erased::new(default),
+            parse: Box::new(move |raw| {
+                let value = raw
+                    .trim()
+                    .parse::<T>()
+                    .map_err(|e| format!("'{}' is not valid: {}", raw, e))?;
+                validate(&value).map_err(|reason| format!("'{}' {}", raw, reason))?;
+                Ok(Erased::new(value))
+            }),
+        })
+    }
+
+    /// The validated value for an override, or why it was refused.
+    pub(crate) fn parse(&self, raw: &str) -> Result<Erased, String> {
+        (self.parse)(raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TTL: Tunable<u64> = Tunable::new("ttl_secs");
+
+    #[test]
+    fn overrides_are_parsed_then_validated() {
+        let definition = Definition::new(TTL, 300, "", in_range(1, 3600)).unwrap();
+        let value = definition.parse(" 600 ").unwrap();
+        assert_eq!(value.downcast::<u64>(), Some(600));
+        assert_eq!(value.rendered(), "600");
+        assert_eq!(
+            value.downcast::<u32>(),
+            None,
+            "the type is part of the handle"
+        );
+
+        assert_eq!(
+            definition.parse("ten").err().unwrap(),
+            "'ten' is not valid: invalid digit found in string"
+        );
+        assert_eq!(
+            definition.parse("0").err().unwrap(),
+            "'0' must be between 1 and 3600"
+        );
+    }
+
+    #[test]
+    fn an_invalid_default_is_refused() {
+        assert_eq!(
+            Definition::new(TTL, 0, "", in_range(1, 3600))
+                .err()
+                .unwrap(),
+            "default 0: must be between 1 and 3600"
+        );
+    }
+}
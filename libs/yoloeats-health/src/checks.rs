@@ -0,0 +1,124 @@
+//! Checks for the clients the services share, each behind the feature of the same name
+//! (`http` for [`HttpCheck`]).
+
+#[cfg(any(
+    feature = "mongo",
+    feature = "redis",
+    feature = "neo4j",
+    feature = "qdrant",
+    feature = "http"
+))]
+use crate::registry::HealthCheckable;
+#[cfg(any(
+    feature = "mongo",
+    feature = "redis",
+    feature = "neo4j",
+    feature = "qdrant",
+    feature = "http"
+))]
+use async_trait::async_trait;
+
+/// `ping` against the database.
+#[cfg(feature = "mongo")]
+pub struct MongoCheck(pub mongodb::Database);
+
+#[cfg(feature = "mongo")]
+#[async_trait]
+impl HealthCheckable for MongoCheck {
+    async fn check_health(&self) -> Result<(), String> {
+        self.0
+            .run_command(mongodb::bson::doc! { "ping": 1 })
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// `PING` on a fresh multiplexed connection.
+#[cfg(feature = "redis")]
+pub struct RedisCheck(pub redis::Client);
+
+#[cfg(feature = "redis")]
+#[async_trait]
+impl HealthCheckable for RedisCheck {
+    async fn check_health(&self) -> Result<(), String> {
+        let mut conn = self
+            .0
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(|e| e.to_string())?;
+        redis::cmd("PING")
+            .query_async::<String>(&mut conn)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// `RETURN 1` through the pool.
+#[cfg(feature = "neo4j")]
+pub struct Neo4jCheck(pub neo4rs::Graph);
+
+#[cfg(feature = "neo4j")]
+#[async_trait]
+impl HealthCheckable for Neo4jCheck {
+    async fn check_health(&self) -> Result<(), String> {
+        self.0
+            .run(neo4rs::query("RETURN 1"))
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Qdrant's own health endpoint.
+#[cfg(feature = "qdrant")]
+pub struct QdrantCheck(pub std::sync::Arc<qdrant_client::Qdrant>);
+
+#[cfg(feature = "qdrant")]
+#[async_trait]
+impl HealthCheckable for QdrantCheck {
+    async fn check_health(&self) -> Result<(), String> {
+        self.0
+            .health_check()
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Another service's liveness endpoint. Only the process is asked about, not its own
+/// dependencies, so one unhealthy database doesn't cascade through every caller.
+#[cfg(feature = "http")]
+pub struct HttpCheck {
+    pub client: reqwest::Client,
+    pub url: String,
+}
+
+#[cfg(feature = "http")]
+impl HttpCheck {
+    /// Probes `{base_url}/health/live`.
+    pub fn live(client: reqwest::Client, base_url: &str) -> Self {
+        HttpCheck {
+            client,
+            url: format!("{}{}", base_url.trim_end_matches('/'), crate::LIVE_PATH),
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+#[async_trait]
+impl HealthCheckable for HttpCheck {
+    async fn check_health(&self) -> Result<(), String> {
+        let response = self
+            .client
+            .get(&self.url)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("{} returned {}", self.url, response.status()))
+        }
+    }
+}
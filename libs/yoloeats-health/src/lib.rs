@@ -0,0 +1,27 @@
+//! Health probes shared by the YoloEats services.
+//!
+//! Each service builds a [`HealthRegistry`] of named [`HealthCheckable`] dependency
+//! checks, each either [`Criticality::Critical`] (the service can't serve without it) or
+//! [`Criticality::Informational`] (degraded, but still useful), and merges
+//! [`health_router`] into its router:
+//!
+//! - `GET /health/live`: the process answers HTTP. Runs no checks, so a slow database
+//!   never gets a healthy pod restarted.
+//! - `GET /health/ready`: the critical checks, 503 if any is down. Cached briefly so
+//!   probes from every replica and load balancer don't hammer the dependencies.
+//! - `GET /health/details`: every check with its error and timing, uncached. Guarded by
+//!   the internal token (see `yoloeats_auth::INTERNAL_PATH_PREFIXES`).
+//!
+//! The `mongo`, `redis`, `neo4j` and `qdrant` features add ready-made checks for those
+//! clients in [`checks`]; `http` adds one for a peer service's liveness endpoint.
+
+pub mod checks;
+mod registry;
+mod router;
+
+pub use async_trait::async_trait;
+pub use registry::{
+    CheckReport, Criticality, DEFAULT_CHECK_TIMEOUT, DEFAULT_READY_TTL, HealthCheckable,
+    HealthRegistry, HealthReport, Status,
+};
+pub use router::{DETAILS_PATH, LIVE_PATH, READY_PATH, health_router};
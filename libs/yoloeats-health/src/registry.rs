@@ -0,0 +1,309 @@
+use async_trait::async_trait;
+use futures::future::join_all;
+use serde::Serialize;
+use std::time::Duration;
+use tokio::{sync::Mutex, time::Instant};
+use tracing::{debug, warn};
+
+/// How long one check may take before it counts as down.
+pub const DEFAULT_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long a readiness answer is reused.
+pub const DEFAULT_READY_TTL: Duration = Duration::from_secs(2);
+
+/// Something a service depends on that can say whether it is usable right now.
+#[async_trait]
+pub trait HealthCheckable: Send + Sync {
+    /// `Err` carries a short reason for operators, e.g. the driver's error message.
+    async fn check_health(&self) -> Result<(), String>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Criticality {
+    /// Down means the service can't do its job: readiness fails.
+    Critical,
+    /// Down means degraded (no cache, no recommendations); only reported.
+    Informational,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckReport {
+    pub name: &'static str,
+    pub criticality: Criticality,
+    pub status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// `status` is down only if a critical check is down.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealthReport {
+    pub service: &'static str,
+    pub status: Status,
+    pub checks: Vec<CheckReport>,
+}
+
+struct Registered {
+    name: &'static str,
+    criticality: Criticality,
+    check: Box<dyn HealthCheckable>,
+}
+
+/// A service's dependency checks. Build it once at startup and share it in an `Arc`.
+pub struct HealthRegistry {
+    service: &'static str,
+    checks: Vec<Registered>,
+    check_timeout: Duration,
+    ready_ttl: Duration,
+    ready_cache: Mutex<Option<(Instant, HealthReport)>>,
+}
+
+impl HealthRegistry {
+    pub fn new(service: &'static str) -> Self {
+        HealthRegistry {
+            service,
+            checks: Vec::new(),
+            check_timeout: DEFAULT_CHECK_TIMEOUT,
+            ready_ttl: DEFAULT_READY_TTL,
+            ready_cache: Mutex::new(None),
+        }
+    }
+
+    pub fn register(
+        mut self,
+        name: &'static str,
+        criticality: Criticality,
+        check: impl HealthCheckable + 'static,
+    ) -> Self {
+        self.checks.push(Registered {
+            name,
+            criticality,
+            check: Box::new(check),
+        });
+        self
+    }
+
+    pub fn check_timeout(mut self, timeout: Duration) -> Self {
+        self.check_timeout = timeout;
+        self
+    }
+
+    pub fn ready_ttl(mut self, ttl: Duration) -> Self {
+        self.ready_ttl = ttl;
+        self
+    }
+
+    async fn run(&self, include: impl Fn(Criticality) -> bool) -> HealthReport {
+        let checks = join_all(
+            self.checks
+                .iter()
+                .filter(|registered| include(registered.criticality))
+                .map(|registered| self.run_one(registered)),
+        )
+        .await;
+        let status = if checks
+            .iter()
+            .any(|c| c.criticality == Criticality::Critical && c.status == Status::Down)
+        {
+            Status::Down
+        } else {
+            Status::Up
+        };
+        HealthReport {
+            service: self.service,
+            status,
+            checks,
+        }
+    }
+
+    async fn run_one(&self, registered: &Registered) -> CheckReport {
+        let started = Instant::now();
+        let outcome = tokio::time::timeout(self.check_timeout, registered.check.check_health())
+            .await
+            .unwrap_or_else(|_| Err(format!("timed out after {:?}", self.check_timeout)));
+        let duration_ms = started.elapsed().as_millis() as u64;
+        let (status, error) = match outcome {
+            Ok(()) => (Status::Up, None),
+            Err(reason) => {
+                warn!(
+                    check = registered.name,
+                    criticality = ?registered.criticality,
+                    "Health check failed: {}",
+                    reason
+                );
+                (Status::Down, Some(reason))
+            }
+        };
+        CheckReport {
+            name: registered.name,
+            criticality: registered.criticality,
+            status,
+            error,
+            duration_ms,
+        }
+    }
+
+    /// The critical checks. A result younger than the TTL is returned as is, and
+    /// concurrent callers wait for the one evaluation in flight instead of starting
+    /// their own.
+    pub async fn readiness(&self) -> HealthReport {
+        let mut cache = self.ready_cache.lock().await;
+        let fresh = cache
+            .as_ref()
+            .filter(|(evaluated_at, _)| evaluated_at.elapsed() < self.ready_ttl);
+        if let Some((_, report)) = fresh {
+            debug!("Serving cached readiness ({:?})", report.status);
+            return report.clone();
+        }
+        let report = self.run(|c| c == Criticality::Critical).await;
+        *cache = Some((Instant::now(), report.clone()));
+        report
+    }
+
+    /// Every check, evaluated now.
+    pub async fn details(&self) -> HealthReport {
+        self.run(|_| true).await
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU32, Ordering},
+    };
+
+    /// Counts its calls; `down` can be flipped while registered.
+    #[derive(Clone, Default)]
+    pub(crate) struct FakeCheck {
+        pub calls: Arc<AtomicU32>,
+        pub down: Arc<AtomicBool>,
+    }
+
+    impl FakeCheck {
+        pub fn down() -> Self {
+            let check = FakeCheck::default();
+            check.down.store(true, Ordering::SeqCst);
+            check
+        }
+
+        pub fn calls(&self) -> u32 {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait]
+    impl HealthCheckable for FakeCheck {
+        async fn check_health(&self) -> Result<(), String> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.down.load(Ordering::SeqCst) {
+                Err("connection refused".to_string())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    struct Hangs;
+
+    #[async_trait]
+    impl HealthCheckable for Hangs {
+        async fn check_health(&self) -> Result<(), String> {
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn informational_failures_do_not_affect_status() {
+        let registry = HealthRegistry::new("svc")
+            .register("mongo", Criticality::Critical, FakeCheck::default())
+            .register("redis", Criticality::Informational, FakeCheck::down());
+
+        let ready = registry.readiness().await;
+        assert_eq!(ready.status, Status::Up);
+        assert_eq!(ready.checks.len(), 1, "readiness runs critical checks only");
+        assert_eq!(ready.checks[0].name, "mongo");
+
+        let details = registry.details().await;
+        assert_eq!(details.status, Status::Up);
+        assert_eq!(details.checks.len(), 2);
+        assert_eq!(details.checks[1].status, Status::Down);
+        assert_eq!(
+            details.checks[1].error.as_deref(),
+            Some("connection refused")
+        );
+    }
+
+    #[tokio::test]
+    async fn a_critical_failure_makes_the_service_unready() {
+        let registry = HealthRegistry::new("svc")
+            .register("mongo", Criticality::Critical, FakeCheck::down())
+            .register("redis", Criticality::Informational, FakeCheck::default());
+
+        assert_eq!(registry.readiness().await.status, Status::Down);
+        assert_eq!(registry.details().await.status, Status::Down);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn readiness_is_cached_for_the_ttl() {
+        let mongo = FakeCheck::default();
+        let registry = HealthRegistry::new("svc")
+            .register("mongo", Criticality::Critical, mongo.clone())
+            .ready_ttl(Duration::from_secs(2));
+
+        assert_eq!(registry.readiness().await.status, Status::Up);
+        mongo.down.store(true, Ordering::SeqCst);
+        tokio::time::advance(Duration::from_millis(1500)).await;
+        assert_eq!(
+            registry.readiness().await.status,
+            Status::Up,
+            "still inside the window"
+        );
+        assert_eq!(mongo.calls(), 1);
+
+        tokio::time::advance(Duration::from_millis(600)).await;
+        assert_eq!(registry.readiness().await.status, Status::Down);
+        assert_eq!(mongo.calls(), 2);
+
+        // Details always re-evaluate and don't refresh the readiness cache.
+        registry.details().await;
+        assert_eq!(mongo.calls(), 3);
+        registry.readiness().await;
+        assert_eq!(mongo.calls(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn concurrent_probes_share_one_evaluation() {
+        let mongo = FakeCheck::default();
+        let registry =
+            HealthRegistry::new("svc").register("mongo", Criticality::Critical, mongo.clone());
+
+        let reports = join_all((0..10).map(|_| registry.readiness())).await;
+        assert!(reports.iter().all(|r| r.status == Status::Up));
+        assert_eq!(mongo.calls(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn hanging_checks_time_out_as_down() {
+        let registry = HealthRegistry::new("svc")
+            .register("neo4j", Criticality::Critical, Hangs)
+            .check_timeout(Duration::from_millis(500));
+
+        let report = registry.readiness().await;
+        assert_eq!(report.status, Status::Down);
+        assert_eq!(
+            report.checks[0].error.as_deref(),
+            Some("timed out after 500ms")
+        );
+    }
+}
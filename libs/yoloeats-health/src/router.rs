@@ -0,0 +1,122 @@
+use crate::registry::{HealthRegistry, HealthReport, Status};
+use axum::{
+    Json, Router,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use serde::Serialize;
+use std::sync::Arc;
+
+pub const LIVE_PATH: &str = "/health/live";
+pub const READY_PATH: &str = "/health/ready";
+pub const DETAILS_PATH: &str = "/health/details";
+
+#[derive(Serialize)]
+struct Live {
+    status: Status,
+}
+
+async fn live() -> Json<Live> {
+    Json(Live { status: Status::Up })
+}
+
+fn respond(report: HealthReport) -> Response {
+    let code = match report.status {
+        Status::Up => StatusCode::OK,
+        Status::Down => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    (code, Json(report)).into_response()
+}
+
+async fn ready(State(registry): State<Arc<HealthRegistry>>) -> Response {
+    respond(registry.readiness().await)
+}
+
+async fn details(State(registry): State<Arc<HealthRegistry>>) -> Response {
+    respond(registry.details().await)
+}
+
+/// The three probe routes, ready to `merge` into a service router of any state type.
+pub fn health_router<S>(registry: Arc<HealthRegistry>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route(LIVE_PATH, get(live))
+        .route(READY_PATH, get(ready))
+        .route(DETAILS_PATH, get(details))
+        .with_state(registry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::{Criticality, tests::FakeCheck};
+    use axum::body::{Body, to_bytes};
+    use axum::http::Request;
+    use serde_json::{Value, json};
+    use tower::ServiceExt;
+
+    async fn get(router: &Router, path: &str) -> (StatusCode, Value) {
+        let response = router
+            .clone()
+            .oneshot(Request::get(path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn liveness_runs_no_checks() {
+        let mongo = FakeCheck::down();
+        let router = health_router(Arc::new(HealthRegistry::new("svc").register(
+            "mongo",
+            Criticality::Critical,
+            mongo.clone(),
+        )));
+
+        let (status, body) = get(&router, LIVE_PATH).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({ "status": "up" }));
+        assert_eq!(mongo.calls(), 0);
+    }
+
+    #[tokio::test]
+    async fn readiness_is_503_when_a_critical_check_is_down() {
+        let router = health_router(Arc::new(
+            HealthRegistry::new("svc")
+                .register("mongo", Criticality::Critical, FakeCheck::down())
+                .register("redis", Criticality::Informational, FakeCheck::default()),
+        ));
+
+        let (status, body) = get(&router, READY_PATH).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "down");
+        assert_eq!(body["service"], "svc");
+        assert_eq!(body["checks"].as_array().unwrap().len(), 1);
+        assert_eq!(body["checks"][0]["error"], "connection refused");
+    }
+
+    #[tokio::test]
+    async fn details_report_every_check() {
+        let router = health_router(Arc::new(
+            HealthRegistry::new("svc")
+                .register("mongo", Criticality::Critical, FakeCheck::default())
+                .register("redis", Criticality::Informational, FakeCheck::down()),
+        ));
+
+        let (status, body) = get(&router, DETAILS_PATH).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "up");
+        let redis = &body["checks"][1];
+        assert_eq!(redis["name"], "redis");
+        assert_eq!(redis["criticality"], "informational");
+        assert_eq!(redis["status"], "down");
+        assert!(redis["durationMs"].is_u64());
+        assert!(body["checks"][0].get("error").is_none());
+    }
+}
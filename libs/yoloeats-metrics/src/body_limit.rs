@@ -0,0 +1,354 @@
+use axum::{
+    Json,
+    body::Body,
+    http::{
+        Request, StatusCode,
+        header::{CONTENT_LENGTH, CONTENT_TYPE},
+    },
+    response::{IntoResponse, Response},
+};
+use http_body_util::Limited;
+use serde_json::json;
+use std::{
+    env,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use thiserror::Error;
+use tower::{Layer, Service};
+use tracing::debug;
+use yoloeats_domain::{ErrorBody, ErrorCode};
+use yoloeats_tracing::current_request_id;
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send + 'static>>;
+
+/// Limit for every route outside a configured group.
+pub const MAX_BODY_BYTES_ENV: &str = "MAX_BODY_BYTES";
+pub const DEFAULT_MAX_BODY_BYTES: usize = 256 * 1024;
+
+#[derive(Debug, Error)]
+#[error("Invalid {var} '{value}': expected a positive number of bytes")]
+pub struct BodyLimitConfigError {
+    pub var: &'static str,
+    pub value: String,
+}
+
+/// Request body limits: one default plus larger (or smaller) limits for route groups,
+/// matched by path prefix at segment boundaries; the longest matching prefix wins.
+///
+/// A limit above axum's 2 MB buffering default only helps routes that stream the body;
+/// `Json` and `Bytes` extractors stop at 2 MB unless the route also lifts
+/// `DefaultBodyLimit`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BodyLimits {
+    default: usize,
+    groups: Vec<(&'static str, usize)>,
+}
+
+impl Default for BodyLimits {
+    fn default() -> Self {
+        BodyLimits::new(DEFAULT_MAX_BODY_BYTES)
+    }
+}
+
+impl BodyLimits {
+    pub fn new(default: usize) -> Self {
+        BodyLimits {
+            default,
+            groups: Vec::new(),
+        }
+    }
+
+    pub fn group(mut self, prefix: &'static str, limit: usize) -> Self {
+        self.groups.push((prefix, limit));
+        self
+    }
+
+    /// [`DEFAULT_MAX_BODY_BYTES`], or [`MAX_BODY_BYTES_ENV`] where set.
+    pub fn from_env() -> Result<Self, BodyLimitConfigError> {
+        Ok(BodyLimits::new(
+            bytes_var(MAX_BODY_BYTES_ENV)?.unwrap_or(DEFAULT_MAX_BODY_BYTES),
+        ))
+    }
+
+    /// Adds a group whose limit comes from `var`, falling back to `fallback`.
+    pub fn group_from_env(
+        self,
+        prefix: &'static str,
+        var: &'static str,
+        fallback: usize,
+    ) -> Result<Self, BodyLimitConfigError> {
+        let limit = bytes_var(var)?.unwrap_or(fallback);
+        Ok(self.group(prefix, limit))
+    }
+
+    pub fn limit_for(&self, path: &str) -> usize {
+        self.groups
+            .iter()
+            .filter(|(prefix, _)| {
+                path.strip_prefix(prefix)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.default, |(_, limit)| *limit)
+    }
+}
+
+fn bytes_var(var: &'static str) -> Result<Option<usize>, BodyLimitConfigError> {
+    match env::var(var) {
+        Ok(value) if !value.trim().is_empty() => match value.trim().parse::<usize>() {
+            Ok(parsed) if parsed > 0 => Ok(Some(parsed)),
+            _ => Err(BodyLimitConfigError { var, value }),
+        },
+        _ => Ok(None),
+    }
+}
+
+fn human_size(bytes: usize) -> String {
+    const MB: usize = 1024 * 1024;
+    const KB: usize = 1024;
+    if bytes.is_multiple_of(MB) {
+        format!("{} MB", bytes / MB)
+    } else if bytes.is_multiple_of(KB) {
+        format!("{} KB", bytes / KB)
+    } else {
+        format!("{} bytes", bytes)
+    }
+}
+
+fn too_large(limit: usize) -> Response {
+    let body = ErrorBody::new(
+        ErrorCode::PayloadTooLarge,
+        format!("Request body exceeds the {} limit", human_size(limit)),
+    )
+    .with_request_id(current_request_id().map(|id| id.to_string()))
+    .with_details(json!({ "limitBytes": limit }));
+    (StatusCode::PAYLOAD_TOO_LARGE, Json(body)).into_response()
+}
+
+/// Tower layer enforcing [`BodyLimits`]. A declared `Content-Length` over the limit is
+/// rejected before the handler runs; otherwise the body is cut off at the limit as it is
+/// read, so chunked uploads can't get around it. Either way the client gets 413 with the
+/// error envelope, never axum's plain-text rejection.
+#[derive(Clone, Debug)]
+pub struct BodyLimitLayer {
+    limits: Arc<BodyLimits>,
+}
+
+impl BodyLimitLayer {
+    pub fn new(limits: BodyLimits) -> Self {
+        BodyLimitLayer {
+            limits: Arc::new(limits),
+        }
+    }
+}
+
+impl<S> Layer<S> for BodyLimitLayer {
+    type Service = BodyLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        BodyLimitService {
+            inner,
+            limits: self.limits.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BodyLimitService<S> {
+    inner: S,
+    limits: Arc<BodyLimits>,
+}
+
+impl<S> Service<Request<Body>> for BodyLimitService<S>
+where
+    S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let limit = self.limits.limit_for(request.uri().path());
+        let declared = request
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        if declared.is_some_and(|length| length > limit as u64) {
+            debug!(path = %request.uri().path(), ?declared, limit, "Rejected oversized body");
+            return Box::pin(async move { Ok(too_large(limit)) });
+        }
+
+        let request = request.map(|body| Body::new(Limited::new(body, limit)));
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let response = response.await?;
+            // Extractors report a body cut off by `Limited` as a plain-text 413.
+            let is_json = response
+                .headers()
+                .get(CONTENT_TYPE)
+                .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+            if response.status() == StatusCode::PAYLOAD_TOO_LARGE && !is_json {
+                return Ok(too_large(limit));
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, routing::post};
+    use futures::stream;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    const IMPORT: &str = "/api/v1/products/import";
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/api/v1/products",
+                post(|Json(body): Json<Value>| async move { Json(body) }),
+            )
+            // Streams the body chunk by chunk like an NDJSON import, never buffering it.
+            .route(
+                IMPORT,
+                post(|body: Body| async move {
+                    let mut lines = 0usize;
+                    let mut stream = body.into_data_stream();
+                    while let Some(chunk) = futures::StreamExt::next(&mut stream).await {
+                        match chunk {
+                            Ok(chunk) => lines += chunk.iter().filter(|b| **b == b'\n').count(),
+                            Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+                        }
+                    }
+                    lines.to_string().into_response()
+                }),
+            )
+            .layer(BodyLimitLayer::new(
+                BodyLimits::new(1024).group(IMPORT, 8 * 1024 * 1024),
+            ))
+    }
+
+    fn json_body(bytes: usize) -> String {
+        format!(r#"{{"name": "{}"}}"#, "x".repeat(bytes))
+    }
+
+    async fn json_of(response: Response) -> Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn declared_oversized_bodies_are_rejected_with_the_envelope() {
+        let body = json_body(2048);
+        let response = app()
+            .oneshot(
+                Request::post("/api/v1/products")
+                    .header(CONTENT_TYPE, "application/json")
+                    .header(CONTENT_LENGTH, body.len())
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(
+            json_of(response).await,
+            json!({
+                "code": "payload_too_large",
+                "message": "Request body exceeds the 1 KB limit",
+                "error": "Request body exceeds the 1 KB limit",
+                "details": { "limitBytes": 1024 }
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn undeclared_oversized_bodies_are_cut_off_while_read() {
+        let chunks = (0..4).map(|_| Ok::<_, std::io::Error>(json_body(500)));
+        let response = app()
+            .oneshot(
+                Request::post("/api/v1/products")
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(Body::from_stream(stream::iter(chunks)))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(json_of(response).await["code"], "payload_too_large");
+    }
+
+    #[tokio::test]
+    async fn bodies_within_the_limit_reach_the_handler() {
+        let response = app()
+            .oneshot(
+                Request::post("/api/v1/products")
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(Body::from(json_body(100)))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn streaming_routes_use_their_group_limit() {
+        let line = format!("{}\n", json_body(1000));
+        let chunks = (0..3000).map(move |_| Ok::<_, std::io::Error>(line.clone()));
+        let response = app()
+            .oneshot(
+                Request::post(IMPORT)
+                    .body(Body::from_stream(stream::iter(chunks)))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.status(),
+            StatusCode::OK,
+            "3 MB is under the 8 MB group limit"
+        );
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&bytes[..], b"3000");
+    }
+
+    #[test]
+    fn the_longest_matching_group_wins() {
+        let limits = BodyLimits::new(10)
+            .group("/api/v1/products", 20)
+            .group("/api/v1/products/import", 30);
+        assert_eq!(limits.limit_for("/api/v1/users/u1/profile"), 10);
+        assert_eq!(limits.limit_for("/api/v1/products"), 20);
+        assert_eq!(limits.limit_for("/api/v1/products/abc"), 20);
+        assert_eq!(limits.limit_for("/api/v1/products/import"), 30);
+        assert_eq!(limits.limit_for("/api/v1/products/imports"), 20);
+    }
+
+    #[test]
+    fn sizes_are_described_in_the_largest_whole_unit() {
+        assert_eq!(human_size(256 * 1024), "256 KB");
+        assert_eq!(human_size(10 * 1024 * 1024), "10 MB");
+        assert_eq!(human_size(1500), "1500 bytes");
+    }
+}
@@ -0,0 +1,175 @@
+use tower_http::compression::{
+    CompressionLayer,
+    predicate::{NotForContentType, Predicate, SizeAbove},
+};
+
+/// Responses smaller than this go out as they are: compressing them saves less than the
+/// `Content-Encoding` header costs.
+pub const MIN_COMPRESSED_BYTES: u16 = 1024;
+
+/// Compresses response bodies with gzip or brotli, whichever the request's
+/// `Accept-Encoding` prefers; without one, or for clients that accept neither, nothing
+/// changes. Bodies under [`MIN_COMPRESSED_BYTES`] are left alone, as are content types
+/// that are compressed already (images, archives) or must reach the client a message at
+/// a time (gRPC, server-sent events). Streamed bodies, whose size isn't known up front,
+/// are compressed as they are produced rather than buffered whole.
+pub fn compression_layer() -> CompressionLayer<impl Predicate> {
+    CompressionLayer::new().compress_when(
+        SizeAbove::new(MIN_COMPRESSED_BYTES)
+            .and(NotForContentType::GRPC)
+            .and(NotForContentType::IMAGES)
+            .and(NotForContentType::SSE)
+            .and(NotForContentType::const_new("application/gzip"))
+            .and(NotForContentType::const_new("application/zip"))
+            .and(NotForContentType::const_new("application/zstd")),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Router,
+        body::{Body, Bytes},
+        http::{
+            Request, Response,
+            header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE},
+        },
+        routing::get,
+    };
+    use futures::SinkExt;
+    use http_body_util::BodyExt;
+    use std::{
+        convert::Infallible,
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+    };
+    use tower::{Layer, ServiceExt, service_fn};
+
+    fn app() -> Router {
+        let json = |len: usize| {
+            let body = format!("[{}]", vec!["\"en:hazelnuts\""; len].join(","));
+            ([(CONTENT_TYPE, "application/json")], body)
+        };
+        Router::new()
+            .route("/big", get(move || async move { json(500) }))
+            .route("/small", get(move || async move { json(5) }))
+            .route(
+                "/archive",
+                get(|| async { ([(CONTENT_TYPE, "application/zip")], vec![b'P'; 8192]) }),
+            )
+            .route(
+                "/image",
+                get(|| async { ([(CONTENT_TYPE, "image/png")], vec![0u8; 8192]) }),
+            )
+            .layer(compression_layer())
+    }
+
+    async fn encoding(path: &str, accept: Option<&str>) -> Option<String> {
+        let mut request = Request::get(path);
+        if let Some(accept) = accept {
+            request = request.header(ACCEPT_ENCODING, accept);
+        }
+        let response = app()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        response
+            .headers()
+            .get(CONTENT_ENCODING)
+            .map(|value| value.to_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn large_bodies_are_compressed_as_the_client_accepts() {
+        assert_eq!(
+            encoding("/big", Some("gzip")).await.as_deref(),
+            Some("gzip")
+        );
+        assert_eq!(encoding("/big", Some("br")).await.as_deref(), Some("br"));
+        assert_eq!(
+            encoding("/big", Some("gzip;q=0.5, br")).await.as_deref(),
+            Some("br")
+        );
+        assert_eq!(encoding("/big", Some("identity")).await, None);
+        assert_eq!(encoding("/big", None).await, None);
+    }
+
+    #[tokio::test]
+    async fn small_and_compressed_bodies_are_left_alone() {
+        assert_eq!(encoding("/small", Some("gzip, br")).await, None);
+        assert_eq!(encoding("/archive", Some("gzip, br")).await, None);
+        assert_eq!(encoding("/image", Some("gzip, br")).await, None);
+    }
+
+    #[tokio::test]
+    async fn streams_are_compressed_as_they_are_produced() {
+        const CHUNK: usize = 64 * 1024;
+        const CHUNKS: usize = 64;
+        const TOTAL: usize = CHUNK * CHUNKS;
+
+        // The producer can only run a chunk ahead of what the compression has taken, so
+        // a layer that buffered the whole body would see all of it before answering.
+        let (mut tx, rx) = futures::channel::mpsc::channel::<Result<Bytes, Infallible>>(1);
+        let sent = Arc::new(AtomicUsize::new(0));
+        let producer = tokio::spawn({
+            let sent = sent.clone();
+            async move {
+                let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+                for _ in 0..CHUNKS {
+                    let chunk: Vec<u8> = (0..CHUNK)
+                        .map(|_| {
+                            seed ^= seed << 13;
+                            seed ^= seed >> 7;
+                            seed ^= seed << 17;
+                            b'a' + (seed % 26) as u8
+                        })
+                        .collect();
+                    tx.send(Ok(Bytes::from(chunk))).await.unwrap();
+                    sent.fetch_add(CHUNK, Ordering::SeqCst);
+                }
+            }
+        });
+
+        let mut rx = Some(rx);
+        let export = compression_layer().layer(service_fn(move |_: Request<Body>| {
+            let body = Body::from_stream(rx.take().expect("one request"));
+            async move {
+                Ok::<_, Infallible>(
+                    Response::builder()
+                        .header(CONTENT_TYPE, "application/x-ndjson")
+                        .body(body)
+                        .unwrap(),
+                )
+            }
+        }));
+        let request = Request::get("/export")
+            .header(ACCEPT_ENCODING, "gzip")
+            .body(Body::empty())
+            .unwrap();
+        let response = export.oneshot(request).await.unwrap();
+        assert_eq!(response.headers()[CONTENT_ENCODING], "gzip");
+
+        let mut body = response.into_body();
+        let mut sent_at_first_frame = None;
+        let mut compressed = 0;
+        while let Some(frame) = body.frame().await {
+            if let Ok(data) = frame.unwrap().into_data() {
+                sent_at_first_frame.get_or_insert(sent.load(Ordering::SeqCst));
+                compressed += data.len();
+            }
+        }
+        producer.await.unwrap();
+
+        let sent_at_first_frame = sent_at_first_frame.unwrap();
+        assert!(
+            sent_at_first_frame <= TOTAL / 4,
+            "first compressed bytes only after {} of {} bytes",
+            sent_at_first_frame,
+            TOTAL
+        );
+        assert!(compressed > 0 && compressed < TOTAL);
+    }
+}
@@ -0,0 +1,27 @@
+use crate::layer::HTTP_REQUEST_DURATION_SECONDS;
+use axum::{Router, routing::get};
+use metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder, PrometheusHandle};
+
+/// Request latencies span cache hits (sub-millisecond) to vector searches (seconds).
+const DURATION_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Installs the global Prometheus recorder. Call once, early in `main`; a second call
+/// fails because the `metrics` facade only accepts one recorder per process.
+pub fn install_recorder() -> Result<PrometheusHandle, BuildError> {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(HTTP_REQUEST_DURATION_SECONDS.to_string()),
+            DURATION_BUCKETS,
+        )?
+        .install_recorder()
+}
+
+/// `GET /metrics` in the Prometheus text format, ready to `merge` into a service router.
+pub fn metrics_router<S>(handle: PrometheusHandle) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new().route("/metrics", get(move || async move { handle.render() }))
+}
@@ -0,0 +1,315 @@
+use axum::{
+    Json,
+    body::Bytes,
+    extract::{FromRequest, Request, rejection::BytesRejection},
+    http::{HeaderMap, StatusCode, header::CONTENT_TYPE},
+    response::{IntoResponse, Response},
+};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, error::Category, json};
+use std::ops::{Deref, DerefMut};
+use thiserror::Error;
+use tracing::debug;
+use validator::{Validate, ValidationErrors};
+use yoloeats_domain::{
+    ErrorBody, ErrorCode,
+    validation::{FieldError, validation_summary},
+};
+use yoloeats_tracing::current_request_id;
+
+/// The `code` of a [`FieldError`] for a value that couldn't be read as its field's type,
+/// or a field that is missing altogether.
+pub const JSON_FIELD_ERROR_CODE: &str = "json";
+
+type PathError = serde_path_to_error::Error<serde_json::Error>;
+
+/// A JSON request body, deserialized and then validated.
+///
+/// Stands in for axum's `Json` on every payload-taking route so that a body the handler
+/// can't use is always answered in the error envelope, with the offending field's path
+/// in `details`:
+///
+/// - a value of the wrong type, a missing field or a failed `validator` rule: 422
+///   `validation_failed`;
+/// - a body that isn't JSON at all: 400 `invalid_request`, the path showing where the
+///   parser gave up;
+/// - no `application/json` (or `+json`) `Content-Type`: 415 `unsupported_media_type`.
+///
+/// Bodies over the route's limit are left to [`BodyLimitLayer`](crate::BodyLimitLayer).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidJson<T>(pub T);
+
+impl<T> Deref for ValidJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for ValidJson<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum JsonBodyRejection {
+    #[error("Expected a JSON body with Content-Type: application/json")]
+    UnsupportedMediaType,
+
+    #[error(transparent)]
+    Body(#[from] BytesRejection),
+
+    #[error("Malformed JSON body: {}", .0.inner())]
+    Syntax(PathError),
+
+    #[error("Input validation failed: {}: {}", field_path(.0), field_message(.0))]
+    Data(PathError),
+
+    #[error("Input validation failed: {}", validation_summary(.0))]
+    Invalid(ValidationErrors),
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    let Some(mime) = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+    else {
+        return false;
+    };
+    let mime = mime.trim().to_ascii_lowercase();
+    mime == "application/json" || (mime.starts_with("application/") && mime.ends_with("+json"))
+}
+
+/// `items[1].name`, as `validator` paths read; empty for the body as a whole.
+fn field_path(error: &PathError) -> String {
+    let path = error.path().to_string();
+    // A body cut off inside an object ends its path with the key it never got, `?`.
+    match path.strip_suffix(".?").unwrap_or(&path) {
+        "." | "?" => String::new(),
+        path => path.to_string(),
+    }
+}
+
+/// The parser's message without its `at line 1 column 20`, which `details` has no use for.
+fn field_message(error: &PathError) -> String {
+    let inner = error.inner();
+    let message = inner.to_string();
+    let position = format!(" at line {} column {}", inner.line(), inner.column());
+    message
+        .strip_suffix(&position)
+        .map(str::to_string)
+        .unwrap_or(message)
+}
+
+fn field_error(error: &PathError) -> FieldError {
+    FieldError {
+        field: field_path(error),
+        code: JSON_FIELD_ERROR_CODE.to_string(),
+        message: field_message(error),
+        params: Map::new(),
+    }
+}
+
+impl<T, S> FromRequest<S> for ValidJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = JsonBodyRejection;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        if !is_json(request.headers()) {
+            return Err(JsonBodyRejection::UnsupportedMediaType);
+        }
+        let bytes = Bytes::from_request(request, state).await?;
+        let deserializer = &mut serde_json::Deserializer::from_slice(&bytes);
+        let value: T =
+            serde_path_to_error::deserialize(deserializer).map_err(|error| {
+                match error.inner().classify() {
+                    Category::Data => JsonBodyRejection::Data(error),
+                    Category::Syntax | Category::Eof | Category::Io => {
+                        JsonBodyRejection::Syntax(error)
+                    }
+                }
+            })?;
+        value.validate().map_err(JsonBodyRejection::Invalid)?;
+        Ok(ValidJson(value))
+    }
+}
+
+impl IntoResponse for JsonBodyRejection {
+    fn into_response(self) -> Response {
+        debug!("Rejected request body: {}", self);
+        let message = self.to_string();
+        let (status, body) = match self {
+            // Keeps axum's answer; `BodyLimitLayer` turns its plain-text 413 into the
+            // envelope.
+            JsonBodyRejection::Body(rejection) => return rejection.into_response(),
+            JsonBodyRejection::UnsupportedMediaType => (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                ErrorBody::new(ErrorCode::UnsupportedMediaType, message),
+            ),
+            JsonBodyRejection::Syntax(error) => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new(ErrorCode::InvalidRequest, message)
+                    .with_details(json!([field_error(&error)])),
+            ),
+            JsonBodyRejection::Data(error) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorBody::new(ErrorCode::ValidationFailed, message)
+                    .with_details(json!([field_error(&error)])),
+            ),
+            JsonBodyRejection::Invalid(errors) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorBody::validation(&errors),
+            ),
+        };
+        let body = body.with_request_id(current_request_id().map(|id| id.to_string()));
+        (status, Json(body)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Body, http::Request, routing::post};
+    use serde::Deserialize;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    #[derive(Debug, Deserialize, Validate)]
+    struct Ingredient {
+        #[validate(length(min = 1, message = "Name is required"))]
+        name: String,
+        percent: Option<f64>,
+    }
+
+    #[derive(Debug, Deserialize, Validate)]
+    struct Payload {
+        #[validate(email(message = "Invalid email format"))]
+        email: String,
+        #[validate(nested)]
+        #[serde(default)]
+        ingredients: Vec<Ingredient>,
+    }
+
+    fn app() -> Router {
+        Router::new().route(
+            "/profile",
+            post(|ValidJson(payload): ValidJson<Payload>| async move {
+                let percent: f64 = payload.ingredients.iter().filter_map(|i| i.percent).sum();
+                Json(json!({ "email": payload.email, "percent": percent }))
+            }),
+        )
+    }
+
+    async fn post_body(content_type: Option<&str>, body: &str) -> (StatusCode, Value) {
+        let mut request = Request::post("/profile");
+        if let Some(content_type) = content_type {
+            request = request.header(CONTENT_TYPE, content_type);
+        }
+        let response = app()
+            .oneshot(request.body(Body::from(body.to_string())).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    async fn post_json(body: &str) -> (StatusCode, Value) {
+        post_body(Some("application/json"), body).await
+    }
+
+    #[tokio::test]
+    async fn valid_bodies_reach_the_handler() {
+        let (status, body) = post_json(r#"{"email": "a@example.com"}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({ "email": "a@example.com", "percent": 0.0 }));
+
+        let (status, _) = post_body(
+            Some("application/merge-patch+json"),
+            r#"{"email": "a@example.com"}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn failed_rules_are_listed_per_field() {
+        let (status, body) =
+            post_json(r#"{"email": "nope", "ingredients": [{"name": "milk"}, {"name": ""}]}"#)
+                .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "validation_failed");
+        let fields: Vec<&str> = body["details"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|failure| failure["field"].as_str().unwrap())
+            .collect();
+        assert_eq!(fields, ["email", "ingredients[1].name"]);
+        assert_eq!(body["details"][0]["message"], "Invalid email format");
+    }
+
+    #[tokio::test]
+    async fn values_of_the_wrong_type_name_their_path() {
+        let (status, body) = post_json(
+            r#"{"email": "a@example.com", "ingredients": [{"name": "milk", "percent": "lots"}]}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "validation_failed");
+        assert_eq!(
+            body["details"],
+            json!([{
+                "field": "ingredients[0].percent",
+                "code": "json",
+                "message": "invalid type: string \"lots\", expected f64",
+                "params": {}
+            }])
+        );
+        assert_eq!(
+            body["message"],
+            "Input validation failed: ingredients[0].percent: invalid type: string \"lots\", expected f64"
+        );
+
+        let (status, body) = post_json(r#"{"ingredients": []}"#).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["details"][0]["field"], "");
+        assert_eq!(body["details"][0]["message"], "missing field `email`");
+    }
+
+    #[tokio::test]
+    async fn unparseable_bodies_are_bad_requests() {
+        let (status, body) = post_json(r#"{"email": "a@example.com", "ingredients": [{"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "invalid_request");
+        assert_eq!(body["details"][0]["field"], "ingredients[0]");
+        assert!(
+            body["message"]
+                .as_str()
+                .unwrap()
+                .starts_with("Malformed JSON body: EOF while parsing")
+        );
+    }
+
+    #[tokio::test]
+    async fn other_content_types_are_unsupported() {
+        for content_type in [None, Some("text/plain"), Some("application/jsonp")] {
+            let (status, body) = post_body(content_type, r#"{"email": "a@example.com"}"#).await;
+            assert_eq!(
+                status,
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "{:?}",
+                content_type
+            );
+            assert_eq!(body["code"], "unsupported_media_type");
+        }
+    }
+}
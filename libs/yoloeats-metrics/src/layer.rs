@@ -0,0 +1,261 @@
+use axum::extract::MatchedPath;
+use http::{Request, Response, StatusCode};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Instant,
+};
+use tower::{Layer, Service};
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send + 'static>>;
+
+/// The request counter's name after the service's [`metric_namespace`]:
+/// `product_catalog_http_requests_total`.
+pub const HTTP_REQUESTS_TOTAL: &str = "http_requests_total";
+/// The latency histogram's name after the service's [`metric_namespace`].
+pub const HTTP_REQUEST_DURATION_SECONDS: &str = "http_request_duration_seconds";
+
+/// Route label for requests no route matched, so probing for random paths can't mint
+/// new series.
+pub const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// The prefix of a service's metric names: its name without the `-service` suffix, in
+/// snake case, so `product-catalog-service` has `product_catalog`.
+pub fn metric_namespace(service: &str) -> String {
+    service
+        .strip_suffix("-service")
+        .unwrap_or(service)
+        .replace('-', "_")
+}
+
+/// The names a service's HTTP metrics are recorded under.
+#[derive(Debug)]
+struct MetricNames {
+    requests_total: String,
+    request_duration_seconds: String,
+}
+
+/// Tower layer recording request count and latency, under names namespaced by service
+/// (`product_catalog_http_requests_total`). Add it with `Router::layer` so the route
+/// template from [`MatchedPath`] is available; ids and barcodes never become labels.
+#[derive(Clone, Debug)]
+pub struct HttpMetricsLayer {
+    service: &'static str,
+    names: Arc<MetricNames>,
+}
+
+impl HttpMetricsLayer {
+    pub fn new(service: &'static str) -> Self {
+        let namespace = metric_namespace(service);
+        HttpMetricsLayer {
+            service,
+            names: Arc::new(MetricNames {
+                requests_total: format!("{}_{}", namespace, HTTP_REQUESTS_TOTAL),
+                request_duration_seconds: format!(
+                    "{}_{}",
+                    namespace, HTTP_REQUEST_DURATION_SECONDS
+                ),
+            }),
+        }
+    }
+}
+
+impl<S> Layer<S> for HttpMetricsLayer {
+    type Service = HttpMetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HttpMetricsService {
+            inner,
+            service: self.service,
+            names: self.names.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct HttpMetricsService<S> {
+    inner: S,
+    service: &'static str,
+    names: Arc<MetricNames>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for HttpMetricsService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: 'static,
+    ResBody: 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<Result<Response<ResBody>, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let service = self.service;
+        let names = self.names.clone();
+        let method = request.method().to_string();
+        let route = request
+            .extensions()
+            .get::<MatchedPath>()
+            .map(|path| path.as_str().to_string())
+            .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+        let start = Instant::now();
+        let response = self.inner.call(request);
+
+        Box::pin(async move {
+            let response = response.await?;
+            let labels = [
+                ("service", service.to_string()),
+                ("method", method),
+                ("route", route),
+                ("status", status_class(response.status()).to_string()),
+            ];
+            metrics::counter!(names.requests_total.clone(), &labels).increment(1);
+            metrics::histogram!(names.request_duration_seconds.clone(), &labels)
+                .record(start.elapsed().as_secs_f64());
+            Ok(response)
+        })
+    }
+}
+
+fn status_class(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        100..=199 => "1xx",
+        200..=299 => "2xx",
+        300..=399 => "3xx",
+        400..=499 => "4xx",
+        _ => "5xx",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Body, routing::get};
+    use metrics_exporter_prometheus::PrometheusBuilder;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    fn routes() -> Router {
+        Router::new().nest(
+            "/api/v1/products",
+            Router::new()
+                .route("/{id}", get(|| async { "product" }))
+                .route("/barcode/{code}", get(|| async { StatusCode::NOT_FOUND })),
+        )
+    }
+
+    async fn send(app: &Router, path: &str) {
+        let request = Request::get(path).body(Body::empty()).unwrap();
+        app.clone().oneshot(request).await.unwrap();
+    }
+
+    /// Runs `scenario` on a current-thread runtime with a private recorder and returns its
+    /// output with the rendered exposition, so tests don't fight over the global recorder.
+    fn record<Fut: Future>(scenario: Fut) -> (Fut::Output, String) {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let output = metrics::with_local_recorder(&recorder, || runtime.block_on(scenario));
+        (output, handle.render())
+    }
+
+    #[test]
+    fn labels_use_the_route_template_not_the_raw_path() {
+        let ((), rendered) = record(async {
+            let app = routes().layer(HttpMetricsLayer::new("product-catalog-service"));
+            send(&app, "/api/v1/products/663a1f0c2b4e5d6f7a8b9c0d").await;
+            send(&app, "/api/v1/products/663a1f0c2b4e5d6f7a8b9c0e").await;
+            send(&app, "/api/v1/products/barcode/4000417025005").await;
+        });
+
+        assert!(rendered.contains(
+            r#"product_catalog_http_requests_total{service="product-catalog-service",method="GET",route="/api/v1/products/{id}",status="2xx"} 2"#
+        ), "{}", rendered);
+        assert!(rendered.contains(
+            r#"product_catalog_http_requests_total{service="product-catalog-service",method="GET",route="/api/v1/products/barcode/{code}",status="4xx"} 1"#
+        ), "{}", rendered);
+        assert!(rendered.contains("product_catalog_http_request_duration_seconds"));
+        assert!(!rendered.contains("663a1f0c"));
+        assert!(!rendered.contains("4000417025005"));
+    }
+
+    #[test]
+    fn names_are_namespaced_per_service() {
+        assert_eq!(
+            metric_namespace("product-catalog-service"),
+            "product_catalog"
+        );
+        assert_eq!(metric_namespace("user-profile-service"), "user_profile");
+        assert_eq!(metric_namespace("gateway"), "gateway");
+
+        let ((), rendered) = record(async {
+            let app = routes().layer(HttpMetricsLayer::new("user-profile-service"));
+            send(&app, "/api/v1/products/663a1f0c2b4e5d6f7a8b9c0d").await;
+        });
+        assert!(
+            rendered.contains("user_profile_http_requests_total{"),
+            "{}",
+            rendered
+        );
+        assert!(!rendered.contains("\nhttp_requests_total"), "{}", rendered);
+    }
+
+    #[test]
+    fn unmatched_paths_share_one_series() {
+        let ((), rendered) = record(async {
+            let app = routes().layer(HttpMetricsLayer::new("product-catalog-service"));
+            send(&app, "/wp-admin/setup.php").await;
+            send(&app, "/.env").await;
+        });
+
+        assert!(
+            rendered.contains(r#"route="unmatched",status="4xx"} 2"#),
+            "{}",
+            rendered
+        );
+        assert!(!rendered.contains("wp-admin"));
+    }
+
+    #[test]
+    fn happy_path_overhead_is_negligible() {
+        const REQUESTS: u32 = 2_000;
+
+        async fn time(app: Router) -> Duration {
+            // Warm up allocations and the metric handles before measuring.
+            for _ in 0..100 {
+                send(&app, "/api/v1/products/663a1f0c2b4e5d6f7a8b9c0d").await;
+            }
+            let start = Instant::now();
+            for _ in 0..REQUESTS {
+                send(&app, "/api/v1/products/663a1f0c2b4e5d6f7a8b9c0d").await;
+            }
+            start.elapsed()
+        }
+
+        let ((bare, measured), _) = record(async {
+            let bare = time(routes()).await;
+            let measured = time(routes().layer(HttpMetricsLayer::new("bench"))).await;
+            (bare, measured)
+        });
+
+        // A generous absolute bound keeps this stable on slow CI machines while still
+        // catching anything like a lock or allocation storm per request.
+        let per_request = measured.saturating_sub(bare) / REQUESTS;
+        assert!(
+            per_request < Duration::from_micros(50),
+            "metrics layer added {:?} per request (bare {:?}, measured {:?})",
+            per_request,
+            bare,
+            measured
+        );
+    }
+}
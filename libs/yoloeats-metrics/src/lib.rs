@@ -0,0 +1,55 @@
+//! HTTP metrics shared by the YoloEats services.
+//!
+//! [`HttpMetricsLayer`] records `{service}_http_requests_total` and
+//! `{service}_http_request_duration_seconds` for every request, namespaced by
+//! [`metric_namespace`] (`product_catalog_http_requests_total`) and labelled by service,
+//! method, matched route template and status class. [`install_recorder`] sets up the
+//! process-wide Prometheus recorder once at startup, and [`metrics_router`] serves what
+//! it collected on `GET /metrics`. Anything else recorded through the `metrics` macros
+//! in the same process (cache or vector-store counters, say) is served there too.
+//!
+//! [`LoadShedLayer`] protects a service from overload: it caps concurrent requests and
+//! rejects with 503 and `Retry-After` what can't be served in time, instead of letting
+//! requests pile up behind a slow database. [`BodyLimitLayer`] caps request bodies per
+//! route group and answers 413 in the error envelope. [`RateLimitLayer`] caps how often
+//! each client may call a route group, answering 429 and `Retry-After`; the counters
+//! live in Redis with the `redis` feature, so every replica sees the same budget, and
+//! behind the gateway a client is told by the `X-Forwarded-For` of [`TrustedProxies`].
+//! [`compression_layer`] gzips or brotli-compresses the larger responses for clients
+//! that accept it. [`ValidJson`] reads and validates a JSON request body, answering
+//! whatever it can't use in the error envelope with the offending field's path.
+
+mod body_limit;
+mod compression;
+mod exporter;
+mod json_body;
+mod layer;
+mod load_shed;
+mod rate_limit;
+
+pub use body_limit::{
+    BodyLimitConfigError, BodyLimitLayer, BodyLimitService, BodyLimits, DEFAULT_MAX_BODY_BYTES,
+    MAX_BODY_BYTES_ENV,
+};
+pub use compression::{MIN_COMPRESSED_BYTES, compression_layer};
+pub use exporter::{install_recorder, metrics_router};
+pub use json_body::{JSON_FIELD_ERROR_CODE, JsonBodyRejection, ValidJson};
+pub use layer::{
+    HTTP_REQUEST_DURATION_SECONDS, HTTP_REQUESTS_TOTAL, HttpMetricsLayer, HttpMetricsService,
+    UNMATCHED_ROUTE, metric_namespace,
+};
+pub use load_shed::{
+    HTTP_REQUESTS_IN_FLIGHT, HTTP_REQUESTS_SHED_TOTAL, LOAD_SHED_BYPASS_PREFIXES, LoadShedConfig,
+    LoadShedConfigError, LoadShedLayer, LoadShedService, MAX_CONCURRENT_REQUESTS_ENV,
+    MAX_IN_FLIGHT_REQUESTS_ENV, REQUEST_QUEUE_TIMEOUT_MS_ENV,
+};
+pub use metrics_exporter_prometheus::{BuildError, PrometheusHandle};
+#[cfg(feature = "redis")]
+pub use rate_limit::RedisRateLimitStore;
+pub use rate_limit::{
+    ClientAddress, Clock, Counts, HTTP_REQUESTS_RATE_LIMITED_TOTAL, Hit, MemoryRateLimitStore,
+    RATE_LIMIT_STORE_TIMEOUT, RATE_LIMIT_WINDOW, RateLimitConfigError, RateLimitLayer,
+    RateLimitService, RateLimitStore, RateLimitStoreError, RateLimiter, RateLimits, RouteLimit,
+    SystemClock, TRUSTED_PROXIES_ENV, TrustedProxies, TrustedProxiesError, rate_limit_client,
+    retry_after_secs,
+};
@@ -0,0 +1,502 @@
+use axum::{
+    Json,
+    body::Body,
+    http::{HeaderValue, Request, StatusCode, header::RETRY_AFTER},
+    response::{IntoResponse, Response},
+};
+use std::{
+    env,
+    future::Future,
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+use thiserror::Error;
+use tokio::sync::Semaphore;
+use tower::{Layer, Service};
+use tracing::warn;
+use yoloeats_domain::{ErrorBody, ErrorCode};
+use yoloeats_tracing::current_request_id;
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send + 'static>>;
+
+pub const HTTP_REQUESTS_IN_FLIGHT: &str = "http_requests_in_flight";
+pub const HTTP_REQUESTS_SHED_TOTAL: &str = "http_requests_shed_total";
+
+/// Never shed: probes must keep answering while the service is busy, or the orchestrator
+/// restarts a pod that is merely overloaded. Matched by whole path segment.
+pub const LOAD_SHED_BYPASS_PREFIXES: &[&str] = &["/health", "/metrics"];
+
+pub const MAX_CONCURRENT_REQUESTS_ENV: &str = "MAX_CONCURRENT_REQUESTS";
+pub const MAX_IN_FLIGHT_REQUESTS_ENV: &str = "MAX_IN_FLIGHT_REQUESTS";
+pub const REQUEST_QUEUE_TIMEOUT_MS_ENV: &str = "REQUEST_QUEUE_TIMEOUT_MS";
+
+#[derive(Debug, Error)]
+#[error("Invalid {var} '{value}': expected a positive integer")]
+pub struct LoadShedConfigError {
+    pub var: &'static str,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadShedConfig {
+    /// Requests handled at once; the rest wait for a slot.
+    pub max_concurrency: usize,
+    /// Handled plus waiting. Beyond this a request is rejected without queueing.
+    pub max_in_flight: usize,
+    /// How long a request may wait for a slot before it is rejected.
+    pub queue_timeout: Duration,
+    /// Sent as `Retry-After` on every rejection.
+    pub retry_after: Duration,
+}
+
+impl Default for LoadShedConfig {
+    fn default() -> Self {
+        LoadShedConfig {
+            max_concurrency: 256,
+            max_in_flight: 1024,
+            queue_timeout: Duration::from_millis(500),
+            retry_after: Duration::from_secs(1),
+        }
+    }
+}
+
+impl LoadShedConfig {
+    /// The defaults, overridden by [`MAX_CONCURRENT_REQUESTS_ENV`],
+    /// [`MAX_IN_FLIGHT_REQUESTS_ENV`] and [`REQUEST_QUEUE_TIMEOUT_MS_ENV`] where set.
+    pub fn from_env() -> Result<Self, LoadShedConfigError> {
+        let defaults = LoadShedConfig::default();
+        let max_concurrency =
+            positive_var(MAX_CONCURRENT_REQUESTS_ENV)?.unwrap_or(defaults.max_concurrency as u64);
+        let max_in_flight = positive_var(MAX_IN_FLIGHT_REQUESTS_ENV)?
+            .unwrap_or(defaults.max_in_flight.max(max_concurrency as usize) as u64);
+        let queue_timeout = positive_var(REQUEST_QUEUE_TIMEOUT_MS_ENV)?
+            .map(Duration::from_millis)
+            .unwrap_or(defaults.queue_timeout);
+        Ok(LoadShedConfig {
+            max_concurrency: max_concurrency as usize,
+            max_in_flight: max_in_flight as usize,
+            queue_timeout,
+            ..defaults
+        })
+    }
+}
+
+fn positive_var(var: &'static str) -> Result<Option<u64>, LoadShedConfigError> {
+    match env::var(var) {
+        Ok(value) if !value.trim().is_empty() => match value.trim().parse::<u64>() {
+            Ok(parsed) if parsed > 0 => Ok(Some(parsed)),
+            _ => Err(LoadShedConfigError { var, value }),
+        },
+        _ => Ok(None),
+    }
+}
+
+fn bypasses(path: &str) -> bool {
+    LOAD_SHED_BYPASS_PREFIXES.iter().any(|prefix| {
+        path.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ShedReason {
+    InFlightLimit,
+    QueueTimeout,
+}
+
+impl ShedReason {
+    fn as_str(self) -> &'static str {
+        match self {
+            ShedReason::InFlightLimit => "in_flight_limit",
+            ShedReason::QueueTimeout => "queue_timeout",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Shared {
+    service: &'static str,
+    config: LoadShedConfig,
+    slots: Arc<Semaphore>,
+    in_flight: AtomicUsize,
+}
+
+impl Shared {
+    fn enter(self: &Arc<Self>) -> Option<InFlight> {
+        let previous = self.in_flight.fetch_add(1, Ordering::SeqCst);
+        let ticket = InFlight(self.clone());
+        if previous >= self.config.max_in_flight {
+            // Dropping the ticket undoes the increment.
+            return None;
+        }
+        self.record_in_flight(previous + 1);
+        Some(ticket)
+    }
+
+    fn record_in_flight(&self, count: usize) {
+        metrics::gauge!(HTTP_REQUESTS_IN_FLIGHT, "service" => self.service).set(count as f64);
+    }
+
+    fn shed(&self, reason: ShedReason) -> Response {
+        warn!(
+            service = self.service,
+            reason = reason.as_str(),
+            "Shedding request under load"
+        );
+        metrics::counter!(
+            HTTP_REQUESTS_SHED_TOTAL,
+            "service" => self.service,
+            "reason" => reason.as_str()
+        )
+        .increment(1);
+
+        let body = ErrorBody::new(
+            ErrorCode::Overloaded,
+            "Service is overloaded, retry after a short wait",
+        )
+        .with_request_id(current_request_id().map(|id| id.to_string()));
+        let retry_after = HeaderValue::from(self.config.retry_after.as_secs().max(1));
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(RETRY_AFTER, retry_after)],
+            Json(body),
+        )
+            .into_response()
+    }
+}
+
+/// Counts one request as in flight until dropped, whether it finished, was shed or the
+/// client went away.
+struct InFlight(Arc<Shared>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let previous = self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+        self.0.record_in_flight(previous - 1);
+    }
+}
+
+/// Tower layer rejecting work the service can't get to in time, with 503, the error
+/// envelope and `Retry-After`: at most `max_concurrency` requests run, up to
+/// `max_in_flight` in total may wait for a turn, and none waits longer than
+/// `queue_timeout`. Paths under [`LOAD_SHED_BYPASS_PREFIXES`] skip it.
+///
+/// The limits are shared by every clone, so build one per router and add it with
+/// `Router::layer`; [`HTTP_REQUESTS_IN_FLIGHT`] and [`HTTP_REQUESTS_SHED_TOTAL`] report
+/// what it is doing.
+#[derive(Clone, Debug)]
+pub struct LoadShedLayer {
+    shared: Arc<Shared>,
+}
+
+impl LoadShedLayer {
+    pub fn new(service: &'static str, config: LoadShedConfig) -> Self {
+        LoadShedLayer {
+            shared: Arc::new(Shared {
+                service,
+                config,
+                slots: Arc::new(Semaphore::new(config.max_concurrency)),
+                in_flight: AtomicUsize::new(0),
+            }),
+        }
+    }
+}
+
+impl<S> Layer<S> for LoadShedLayer {
+    type Service = LoadShedService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        LoadShedService {
+            inner,
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LoadShedService<S> {
+    inner: S,
+    shared: Arc<Shared>,
+}
+
+impl<S> Service<Request<Body>> for LoadShedService<S>
+where
+    S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        if bypasses(request.uri().path()) {
+            return Box::pin(self.inner.call(request));
+        }
+        let shared = self.shared.clone();
+        let Some(ticket) = shared.enter() else {
+            return Box::pin(async move { Ok(shared.shed(ShedReason::InFlightLimit)) });
+        };
+        // The clone isn't the instance `poll_ready` readied; keep that one for this call.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            let slot = tokio::time::timeout(
+                shared.config.queue_timeout,
+                shared.slots.clone().acquire_owned(),
+            )
+            .await;
+            let Ok(Ok(_slot)) = slot else {
+                return Ok(shared.shed(ShedReason::QueueTimeout));
+            };
+            let response = inner.call(request).await;
+            drop(ticket);
+            response
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, routing::get};
+    use metrics_exporter_prometheus::PrometheusBuilder;
+    use std::time::Instant;
+    use tokio::sync::watch;
+    use tower::ServiceExt;
+
+    /// A deliberately slow handler: every call parks until the gate opens.
+    #[derive(Clone)]
+    struct Gate {
+        entered: Arc<AtomicUsize>,
+        open: Arc<watch::Sender<bool>>,
+    }
+
+    impl Gate {
+        fn new() -> Self {
+            Gate {
+                entered: Arc::default(),
+                open: Arc::new(watch::channel(false).0),
+            }
+        }
+
+        async fn pass(self) -> &'static str {
+            self.entered.fetch_add(1, Ordering::SeqCst);
+            let mut open = self.open.subscribe();
+            open.wait_for(|open| *open).await.unwrap();
+            "done"
+        }
+
+        async fn wait_entered(&self, count: usize) {
+            while self.entered.load(Ordering::SeqCst) < count {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        }
+    }
+
+    fn app(gate: &Gate, config: LoadShedConfig) -> Router {
+        let slow = gate.clone();
+        Router::new()
+            .route("/api/v1/products", get(move || slow.clone().pass()))
+            .route("/health/ready", get(|| async { "up" }))
+            .layer(LoadShedLayer::new("svc", config))
+    }
+
+    fn config(
+        max_concurrency: usize,
+        max_in_flight: usize,
+        queue_timeout_ms: u64,
+    ) -> LoadShedConfig {
+        LoadShedConfig {
+            max_concurrency,
+            max_in_flight,
+            queue_timeout: Duration::from_millis(queue_timeout_ms),
+            ..LoadShedConfig::default()
+        }
+    }
+
+    /// Fires `count` requests at once; each reports its status and how long it took.
+    fn burst(
+        app: &Router,
+        path: &'static str,
+        count: usize,
+    ) -> Vec<tokio::task::JoinHandle<(StatusCode, Duration)>> {
+        (0..count)
+            .map(|_| {
+                let app = app.clone();
+                tokio::spawn(async move {
+                    let started = Instant::now();
+                    let request = Request::get(path).body(Body::empty()).unwrap();
+                    let response = app.oneshot(request).await.unwrap();
+                    (response.status(), started.elapsed())
+                })
+            })
+            .collect()
+    }
+
+    /// Runs `scenario` on a current-thread runtime with a private recorder, like the
+    /// metrics layer tests, and returns the rendered exposition too.
+    fn record<Fut: Future>(scenario: Fut) -> (Fut::Output, String) {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let output = metrics::with_local_recorder(&recorder, || runtime.block_on(scenario));
+        (output, handle.render())
+    }
+
+    #[test]
+    fn excess_requests_are_shed_at_once_instead_of_queueing() {
+        const HOLD: Duration = Duration::from_millis(200);
+
+        let (results, rendered) = record(async {
+            let gate = Gate::new();
+            let app = app(&gate, config(2, 4, 10_000));
+            let requests = burst(&app, "/api/v1/products", 10);
+            gate.wait_entered(2).await;
+            tokio::time::sleep(HOLD).await;
+            gate.open.send_replace(true);
+
+            let mut results = Vec::new();
+            for request in requests {
+                results.push(request.await.unwrap());
+            }
+            results
+        });
+
+        let (served, shed): (Vec<_>, Vec<_>) = results
+            .iter()
+            .partition(|(status, _)| *status == StatusCode::OK);
+        assert_eq!(served.len(), 4, "two ran, two waited for a slot");
+        assert_eq!(shed.len(), 6);
+        assert!(
+            shed.iter()
+                .all(|(status, _)| *status == StatusCode::SERVICE_UNAVAILABLE)
+        );
+        assert!(
+            shed.iter().all(|(_, elapsed)| *elapsed < HOLD / 2),
+            "shed requests must not wait for the slow ones: {:?}",
+            shed
+        );
+        assert!(served.iter().all(|(_, elapsed)| *elapsed >= HOLD));
+
+        assert!(
+            rendered
+                .contains(r#"http_requests_shed_total{service="svc",reason="in_flight_limit"} 6"#),
+            "{}",
+            rendered
+        );
+        assert!(
+            rendered.contains(r#"http_requests_in_flight{service="svc"} 0"#),
+            "{}",
+            rendered
+        );
+    }
+
+    #[test]
+    fn requests_waiting_past_the_deadline_are_shed() {
+        let (results, rendered) = record(async {
+            let gate = Gate::new();
+            let app = app(&gate, config(1, 100, 50));
+            let requests = burst(&app, "/api/v1/products", 3);
+            gate.wait_entered(1).await;
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            gate.open.send_replace(true);
+
+            let mut results = Vec::new();
+            for request in requests {
+                results.push(request.await.unwrap());
+            }
+            results
+        });
+
+        let shed: Vec<_> = results
+            .iter()
+            .filter(|(status, _)| *status == StatusCode::SERVICE_UNAVAILABLE)
+            .collect();
+        assert_eq!(shed.len(), 2);
+        assert!(
+            shed.iter()
+                .all(|(_, elapsed)| *elapsed >= Duration::from_millis(50)
+                    && *elapsed < Duration::from_millis(300)),
+            "{:?}",
+            shed
+        );
+        assert!(
+            rendered
+                .contains(r#"http_requests_shed_total{service="svc",reason="queue_timeout"} 2"#),
+            "{}",
+            rendered
+        );
+    }
+
+    #[tokio::test]
+    async fn rejections_carry_retry_after_and_the_error_envelope() {
+        let gate = Gate::new();
+        let app = app(&gate, config(1, 1, 10_000));
+        let busy = burst(&app, "/api/v1/products", 1);
+        gate.wait_entered(1).await;
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get("/api/v1/products")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[RETRY_AFTER], "1");
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["code"], "overloaded");
+
+        gate.open.send_replace(true);
+        for request in busy {
+            assert_eq!(request.await.unwrap().0, StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn health_probes_bypass_the_limits() {
+        let gate = Gate::new();
+        let app = app(&gate, config(1, 1, 10_000));
+        let busy = burst(&app, "/api/v1/products", 1);
+        gate.wait_entered(1).await;
+
+        let probe = app
+            .clone()
+            .oneshot(Request::get("/health/ready").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(probe.status(), StatusCode::OK);
+
+        gate.open.send_replace(true);
+        for request in busy {
+            request.await.unwrap();
+        }
+    }
+
+    #[test]
+    fn bypass_matches_whole_segments() {
+        assert!(bypasses("/health"));
+        assert!(bypasses("/health/live"));
+        assert!(bypasses("/metrics"));
+        assert!(!bypasses("/healthy-recipes"));
+        assert!(!bypasses("/api/v1/products"));
+    }
+}
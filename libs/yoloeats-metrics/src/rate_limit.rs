@@ -0,0 +1,1021 @@
+use async_trait::async_trait;
+use axum::{
+    Json,
+    body::Body,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{HeaderMap, HeaderValue, Request, StatusCode, header::RETRY_AFTER, request::Parts},
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    env,
+    future::Future,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use thiserror::Error;
+use tower::{Layer, Service};
+use tracing::{debug, warn};
+use yoloeats_auth::AuthContext;
+use yoloeats_domain::{ErrorBody, ErrorCode};
+use yoloeats_tracing::current_request_id;
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send + 'static>>;
+
+pub const HTTP_REQUESTS_RATE_LIMITED_TOTAL: &str = "http_requests_rate_limited_total";
+
+/// Limits are per minute; the window slides, see [`RateLimitStore`].
+pub const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Longest wait for the store before the request is let through anyway.
+pub const RATE_LIMIT_STORE_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Comma-separated IP addresses of the proxies in front of a service, like the
+/// api-gateway, whose `X-Forwarded-For` names the client; see [`TrustedProxies`].
+pub const TRUSTED_PROXIES_ENV: &str = "TRUSTED_PROXIES";
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+#[derive(Debug, Error)]
+#[error("Invalid {var} '{value}': expected a positive number of requests per minute")]
+pub struct RateLimitConfigError {
+    pub var: &'static str,
+    pub value: String,
+}
+
+#[derive(Debug, Error)]
+#[error("Invalid {TRUSTED_PROXIES_ENV} entry '{0}': expected an IP address")]
+pub struct TrustedProxiesError(pub String);
+
+/// One limited route group: paths under `prefix`, at segment boundaries, get
+/// `per_minute` requests per client. Routes sharing a `group` share the budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RouteLimit {
+    pub group: &'static str,
+    pub prefix: &'static str,
+    pub per_minute: u64,
+}
+
+/// Per-client request limits by route group; the longest matching prefix wins, and
+/// routes outside every group are not limited.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RateLimits {
+    routes: Vec<RouteLimit>,
+}
+
+impl RateLimits {
+    pub fn route(mut self, group: &'static str, prefix: &'static str, per_minute: u64) -> Self {
+        self.routes.push(RouteLimit {
+            group,
+            prefix,
+            per_minute,
+        });
+        self
+    }
+
+    /// Adds a route whose limit comes from `var`, falling back to `fallback`.
+    pub fn route_from_env(
+        self,
+        group: &'static str,
+        prefix: &'static str,
+        var: &'static str,
+        fallback: u64,
+    ) -> Result<Self, RateLimitConfigError> {
+        let per_minute = per_minute_var(var)?.unwrap_or(fallback);
+        Ok(self.route(group, prefix, per_minute))
+    }
+
+    pub fn limit_for(&self, path: &str) -> Option<RouteLimit> {
+        self.routes
+            .iter()
+            .filter(|route| {
+                path.strip_prefix(route.prefix)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+            .max_by_key(|route| route.prefix.len())
+            .copied()
+    }
+}
+
+fn per_minute_var(var: &'static str) -> Result<Option<u64>, RateLimitConfigError> {
+    match env::var(var) {
+        Ok(value) if !value.trim().is_empty() => match value.trim().parse::<u64>() {
+            Ok(parsed) if parsed > 0 => Ok(Some(parsed)),
+            _ => Err(RateLimitConfigError { var, value }),
+        },
+        _ => Ok(None),
+    }
+}
+
+/// Time since the Unix epoch. Windows are counted from it so that every replica sharing
+/// a store agrees on them.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Duration;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Rate limit store failed: {0}")]
+pub struct RateLimitStoreError(pub String);
+
+/// One request against a client's budget for a route group.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hit<'a> {
+    /// The client and route group; the store adds the window.
+    pub key: &'a str,
+    /// Windows since the epoch.
+    pub window: u64,
+    /// How much of the previous window still counts: 1 as the window starts, falling to
+    /// 0 as it ends.
+    pub previous_weight: f64,
+    pub limit: u64,
+}
+
+/// What a store made of a [`Hit`]: the requests counted in the current and previous
+/// windows, this one included if it was allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Counts {
+    pub allowed: bool,
+    pub current: u64,
+    pub previous: u64,
+}
+
+/// Request counters by client, route group and window.
+///
+/// A request is allowed while the previous window's count, weighted by how much of it
+/// still overlaps the last minute, plus the current window's count stays within the
+/// limit; rejected requests are not counted, so a client that backs off for the
+/// `Retry-After` it was given gets in.
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Counts `hit` if it is allowed, atomically, so that replicas can't both take the
+    /// last request of a budget.
+    async fn hit(&self, hit: &Hit<'_>) -> Result<Counts, RateLimitStoreError>;
+}
+
+fn admits(current: u64, previous: u64, previous_weight: f64, limit: u64) -> bool {
+    previous as f64 * previous_weight + current as f64 + 1.0 <= limit as f64
+}
+
+#[cfg(feature = "redis")]
+fn window_key(key: &str, window: u64) -> String {
+    format!("{}:{}", key, window)
+}
+
+/// Counters in Redis, shared by every replica: `{key}:{window}`, each expiring once it
+/// can no longer be the previous window.
+#[cfg(feature = "redis")]
+pub struct RedisRateLimitStore {
+    client: redis::Client,
+    script: redis::Script,
+}
+
+#[cfg(feature = "redis")]
+const HIT_SCRIPT: &str = r"
+local current = tonumber(redis.call('GET', KEYS[1]) or '0')
+local previous = tonumber(redis.call('GET', KEYS[2]) or '0')
+if previous * tonumber(ARGV[1]) + current + 1 > tonumber(ARGV[2]) then
+    return {0, current, previous}
+end
+current = redis.call('INCR', KEYS[1])
+redis.call('EXPIRE', KEYS[1], ARGV[3])
+return {1, current, previous}
+";
+
+#[cfg(feature = "redis")]
+impl RedisRateLimitStore {
+    pub fn new(client: redis::Client) -> Self {
+        RedisRateLimitStore {
+            client,
+            script: redis::Script::new(HIT_SCRIPT),
+        }
+    }
+}
+
+#[cfg(feature = "redis")]
+#[async_trait]
+impl RateLimitStore for RedisRateLimitStore {
+    async fn hit(&self, hit: &Hit<'_>) -> Result<Counts, RateLimitStoreError> {
+        let store_error = |e: redis::RedisError| RateLimitStoreError(e.to_string());
+        let mut conn = self
+            .client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(store_error)?;
+        let (allowed, current, previous): (u8, u64, u64) = self
+            .script
+            .key(window_key(hit.key, hit.window))
+            .key(window_key(hit.key, hit.window.saturating_sub(1)))
+            .arg(hit.previous_weight)
+            .arg(hit.limit)
+            .arg(2 * RATE_LIMIT_WINDOW.as_secs())
+            .invoke_async(&mut conn)
+            .await
+            .map_err(store_error)?;
+        Ok(Counts {
+            allowed: allowed == 1,
+            current,
+            previous,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Windows {
+    window: u64,
+    current: u64,
+    previous: u64,
+}
+
+/// Process-local counters, for `STORAGE_MODE=memory` and tests. Clones share them.
+#[derive(Clone, Default)]
+pub struct MemoryRateLimitStore {
+    counters: Arc<Mutex<HashMap<String, Windows>>>,
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryRateLimitStore {
+    async fn hit(&self, hit: &Hit<'_>) -> Result<Counts, RateLimitStoreError> {
+        let mut counters = self.counters.lock().unwrap();
+        let windows = counters.entry(hit.key.to_string()).or_default();
+        *windows = match hit.window.checked_sub(windows.window) {
+            Some(0) => *windows,
+            Some(1) => Windows {
+                window: hit.window,
+                current: 0,
+                previous: windows.current,
+            },
+            _ => Windows {
+                window: hit.window,
+                ..Windows::default()
+            },
+        };
+        let allowed = admits(
+            windows.current,
+            windows.previous,
+            hit.previous_weight,
+            hit.limit,
+        );
+        if allowed {
+            windows.current += 1;
+        }
+        Ok(Counts {
+            allowed,
+            current: windows.current,
+            previous: windows.previous,
+        })
+    }
+}
+
+/// How long until a request rejected with `counts`, `elapsed` into its window, would
+/// be allowed, if the client sends nothing in between.
+fn retry_after(counts: Counts, elapsed: Duration, limit: u64) -> Duration {
+    let window = RATE_LIMIT_WINDOW.as_secs_f64();
+    let elapsed = elapsed.as_secs_f64();
+    let (current, previous, limit) = (counts.current as f64, counts.previous as f64, limit as f64);
+    let wait = if current + 1.0 <= limit {
+        // Room in this window once enough of the previous one has slid out.
+        window * (1.0 - (limit - current - 1.0) / previous) - elapsed
+    } else {
+        // This window is full: wait for the next, where it counts as the previous one.
+        (window - elapsed) + window * (1.0 - (limit - 1.0) / current)
+    };
+    Duration::from_secs_f64(wait.max(0.0))
+}
+
+/// The proxies whose `X-Forwarded-For` is believed, read from [`TRUSTED_PROXIES_ENV`].
+/// Behind the gateway every request comes from its address; the header it adds names
+/// the client, and the client's own requests can't pass a made-up one off as a proxy's.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrustedProxies(Arc<[IpAddr]>);
+
+impl TrustedProxies {
+    pub fn new(addresses: impl IntoIterator<Item = IpAddr>) -> Self {
+        TrustedProxies(addresses.into_iter().collect())
+    }
+
+    /// Trusts no proxy when [`TRUSTED_PROXIES_ENV`] is unset or blank.
+    pub fn from_env() -> Result<Self, TrustedProxiesError> {
+        TrustedProxies::parse(&env::var(TRUSTED_PROXIES_ENV).unwrap_or_default())
+    }
+
+    pub fn parse(addresses: &str) -> Result<Self, TrustedProxiesError> {
+        addresses
+            .split(',')
+            .map(str::trim)
+            .filter(|address| !address.is_empty())
+            .map(|address| {
+                address
+                    .parse()
+                    .map_err(|_| TrustedProxiesError(address.to_string()))
+            })
+            .collect::<Result<Vec<IpAddr>, _>>()
+            .map(TrustedProxies::new)
+    }
+
+    pub fn contains(&self, address: IpAddr) -> bool {
+        self.0.contains(&address)
+    }
+
+    /// The client behind a request from `peer` with `headers`: the peer itself, unless
+    /// it is a trusted proxy. Then `X-Forwarded-For` is read from the right, past the
+    /// proxies that added to it, up to the first address that isn't one; what is left
+    /// of it was written by the client and is ignored.
+    pub fn client(&self, peer: Option<SocketAddr>, headers: &HeaderMap) -> Option<IpAddr> {
+        let mut client = peer?.ip();
+        let forwarded: Vec<&str> = headers
+            .get_all(X_FORWARDED_FOR)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect();
+        for hop in forwarded.into_iter().rev() {
+            if !self.contains(client) {
+                break;
+            }
+            match hop.parse() {
+                Ok(address) => client = address,
+                Err(_) => break,
+            }
+        }
+        Some(client)
+    }
+}
+
+/// The address a request is counted by: its peer, or the client a trusted proxy
+/// forwarded it for. [`RateLimitLayer`] puts it on every request, so handlers counting
+/// limits of their own see the same clients; without the layer it is the peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientAddress(pub Option<IpAddr>);
+
+impl<S> FromRequestParts<S> for ClientAddress
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<ClientAddress>()
+            .copied()
+            .unwrap_or_else(|| {
+                ClientAddress(
+                    parts
+                        .extensions
+                        .get::<ConnectInfo<SocketAddr>>()
+                        .map(|info| info.0.ip()),
+                )
+            }))
+    }
+}
+
+/// The client a request counts against; see [`rate_limit_client`]. Its subject is that
+/// of the [`AuthContext`] an `AuthLayer` in front of this one put on the request.
+/// Without `ConnectInfo` (the server wasn't started with
+/// `into_make_service_with_connect_info`) every request shares the `unknown` address.
+fn client_key(request: &Request<Body>, address: ClientAddress) -> String {
+    rate_limit_client(
+        address.0,
+        request
+            .extensions()
+            .get::<AuthContext>()
+            .map(|context| context.subject.as_str()),
+    )
+}
+
+/// A client as limits count it: its [`ClientAddress`], plus the authenticated `subject`
+/// when there is one, so the users of one address (a NAT) get their own budgets.
+/// Anything the client says about itself, like an `X-User-Id` header or an
+/// `X-Forwarded-For` not from a trusted proxy, is left out: it would give each made-up
+/// id a budget of its own.
+pub fn rate_limit_client(address: Option<IpAddr>, subject: Option<&str>) -> String {
+    let address = address.map_or_else(|| "unknown".to_string(), |address| address.to_string());
+    match subject {
+        Some(subject) => format!("{}:{}", address, subject),
+        None => address,
+    }
+}
+
+/// Whole seconds of `wait`, at least one: the `Retry-After` of a rejected request.
+pub fn retry_after_secs(wait: Duration) -> u64 {
+    (wait.as_millis() as u64).div_ceil(1000).max(1)
+}
+
+/// Counts clients' requests against [`RouteLimit`]s in a [`RateLimitStore`]. The
+/// [`RateLimitLayer`] checks every request of a limited prefix with one; handlers use one
+/// for limits a prefix can't express, like one method of a path with an id in it.
+pub struct RateLimiter {
+    service: &'static str,
+    store: Arc<dyn RateLimitStore>,
+    clock: Arc<dyn Clock>,
+}
+
+impl RateLimiter {
+    pub fn new(service: &'static str, store: Arc<dyn RateLimitStore>) -> Self {
+        RateLimiter::with_clock(service, store, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(
+        service: &'static str,
+        store: Arc<dyn RateLimitStore>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        RateLimiter {
+            service,
+            store,
+            clock,
+        }
+    }
+
+    /// `Some(wait)` when `client` is over `route`'s limit, counted in
+    /// [`HTTP_REQUESTS_RATE_LIMITED_TOTAL`]; `wait` is how long until it would be
+    /// allowed. A store that fails or is slow lets the request through: losing the limit
+    /// beats losing the service.
+    pub async fn check(&self, route: RouteLimit, client: &str) -> Option<Duration> {
+        let now = self.clock.now();
+        let window_ms = RATE_LIMIT_WINDOW.as_millis();
+        let elapsed = Duration::from_millis((now.as_millis() % window_ms) as u64);
+        let key = format!("ratelimit:{}:{}", route.group, client);
+        let hit = Hit {
+            key: &key,
+            window: (now.as_millis() / window_ms) as u64,
+            previous_weight: 1.0 - elapsed.as_secs_f64() / RATE_LIMIT_WINDOW.as_secs_f64(),
+            limit: route.per_minute,
+        };
+        match tokio::time::timeout(RATE_LIMIT_STORE_TIMEOUT, self.store.hit(&hit)).await {
+            Ok(Ok(counts)) if counts.allowed => None,
+            Ok(Ok(counts)) => {
+                let wait = retry_after(counts, elapsed, route.per_minute);
+                debug!(
+                    service = self.service,
+                    group = route.group,
+                    ?wait,
+                    "Rate limited request"
+                );
+                metrics::counter!(
+                    HTTP_REQUESTS_RATE_LIMITED_TOTAL,
+                    "service" => self.service,
+                    "group" => route.group
+                )
+                .increment(1);
+                Some(wait)
+            }
+            Ok(Err(e)) => {
+                warn!(
+                    service = self.service,
+                    group = route.group,
+                    error = %e,
+                    "Rate limit store failed; request not limited"
+                );
+                None
+            }
+            Err(_) => {
+                warn!(
+                    service = self.service,
+                    group = route.group,
+                    timeout = ?RATE_LIMIT_STORE_TIMEOUT,
+                    "Rate limit store timed out; request not limited"
+                );
+                None
+            }
+        }
+    }
+}
+
+struct Limiter {
+    limits: RateLimits,
+    rate_limiter: RateLimiter,
+}
+
+/// The 429 of a request over `route`'s limit, allowed again after `wait`.
+fn reject(route: RouteLimit, wait: Duration) -> Response {
+    let retry_after_secs = retry_after_secs(wait);
+    let body = ErrorBody::new(
+        ErrorCode::RateLimited,
+        format!(
+            "Too many requests: at most {} per minute, retry in {} s",
+            route.per_minute, retry_after_secs
+        ),
+    )
+    .with_request_id(current_request_id().map(|id| id.to_string()))
+    .with_details(json!({ "limitPerMinute": route.per_minute }));
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(RETRY_AFTER, HeaderValue::from(retry_after_secs))],
+        Json(body),
+    )
+        .into_response()
+}
+
+/// Tower layer enforcing [`RateLimits`] per client, counted in a [`RateLimitStore`].
+/// A request over its limit gets 429 with the error envelope and `Retry-After`, the
+/// seconds until it would be allowed; [`HTTP_REQUESTS_RATE_LIMITED_TOTAL`] counts them.
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    limiter: Arc<Limiter>,
+    proxies: TrustedProxies,
+}
+
+impl RateLimitLayer {
+    pub fn new(service: &'static str, limits: RateLimits, store: Arc<dyn RateLimitStore>) -> Self {
+        RateLimitLayer::with_clock(service, limits, store, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(
+        service: &'static str,
+        limits: RateLimits,
+        store: Arc<dyn RateLimitStore>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        RateLimitLayer {
+            limiter: Arc::new(Limiter {
+                limits,
+                rate_limiter: RateLimiter::with_clock(service, store, clock),
+            }),
+            proxies: TrustedProxies::default(),
+        }
+    }
+
+    /// Counts the requests of `proxies` by the client they forward for.
+    pub fn trusting(self, proxies: TrustedProxies) -> Self {
+        RateLimitLayer { proxies, ..self }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService {
+            inner,
+            limiter: self.limiter.clone(),
+            proxies: self.proxies.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitService<S> {
+    inner: S,
+    limiter: Arc<Limiter>,
+    proxies: TrustedProxies,
+}
+
+impl<S> Service<Request<Body>> for RateLimitService<S>
+where
+    S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<Body>) -> Self::Future {
+        let peer = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|info| info.0);
+        let address = ClientAddress(self.proxies.client(peer, request.headers()));
+        request.extensions_mut().insert(address);
+        let Some(route) = self.limiter.limits.limit_for(request.uri().path()) else {
+            return Box::pin(self.inner.call(request));
+        };
+        let client = client_key(&request, address);
+        let limiter = self.limiter.clone();
+        // The clone isn't the instance `poll_ready` readied; keep that one for this call.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            if let Some(wait) = limiter.rate_limiter.check(route, &client).await {
+                return Ok(reject(route, wait));
+            }
+            inner.call(request).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, routing::get};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use tower::ServiceExt;
+
+    /// Starts on a window boundary and only moves when told to.
+    #[derive(Default)]
+    struct FakeClock(AtomicU64);
+
+    impl FakeClock {
+        fn at(seconds: u64) -> Arc<Self> {
+            Arc::new(FakeClock(AtomicU64::new(seconds * 1000)))
+        }
+
+        fn advance(&self, by: Duration) {
+            self.0.fetch_add(by.as_millis() as u64, Ordering::SeqCst);
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Duration {
+            Duration::from_millis(self.0.load(Ordering::SeqCst))
+        }
+    }
+
+    struct DownStore;
+
+    #[async_trait]
+    impl RateLimitStore for DownStore {
+        async fn hit(&self, _: &Hit<'_>) -> Result<Counts, RateLimitStoreError> {
+            Err(RateLimitStoreError("connection refused".to_string()))
+        }
+    }
+
+    struct HangingStore;
+
+    #[async_trait]
+    impl RateLimitStore for HangingStore {
+        async fn hit(&self, _: &Hit<'_>) -> Result<Counts, RateLimitStoreError> {
+            std::future::pending().await
+        }
+    }
+
+    const T0: u64 = 1_700_000_040;
+
+    fn limits() -> RateLimits {
+        RateLimits::default()
+            .route("search", "/api/v1/products/search", 2)
+            .route("search", "/api/v2/products/search", 2)
+    }
+
+    fn app(store: Arc<dyn RateLimitStore>, clock: Arc<FakeClock>) -> Router {
+        Router::new()
+            .route("/api/v1/products/search", get(|| async { "found" }))
+            .route("/api/v2/products/search", get(|| async { "found" }))
+            .route("/api/v1/products/{id}", get(|| async { "product" }))
+            .layer(RateLimitLayer::with_clock("svc", limits(), store, clock))
+    }
+
+    async fn status(app: &Router, path: &str, subject: Option<&str>) -> StatusCode {
+        send(app, path, subject).await.status()
+    }
+
+    /// A request from one address, authenticated as `subject` if given.
+    async fn send(app: &Router, path: &str, subject: Option<&str>) -> Response {
+        let mut request = Request::get(path).body(Body::empty()).unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([203, 0, 113, 7], 40000))));
+        if let Some(subject) = subject {
+            request.extensions_mut().insert(AuthContext {
+                subject: subject.to_string(),
+                roles: Vec::new(),
+            });
+        }
+        app.clone().oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn requests_over_the_limit_get_429_with_retry_after() {
+        let app = app(Arc::new(MemoryRateLimitStore::default()), FakeClock::at(T0));
+
+        assert_eq!(
+            status(&app, "/api/v1/products/search", None).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(&app, "/api/v2/products/search", None).await,
+            StatusCode::OK
+        );
+        let response = send(&app, "/api/v1/products/search", None).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        // Both in this window: it has to end, and half the next go by before the two
+        // weigh only one.
+        assert_eq!(response.headers()[RETRY_AFTER], "90");
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["code"], "rate_limited");
+        assert_eq!(body["details"]["limitPerMinute"], 2);
+
+        // Other routes aren't limited.
+        assert_eq!(
+            status(&app, "/api/v1/products/42", None).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn the_window_slides_instead_of_resetting() {
+        let clock = FakeClock::at(T0);
+        let app = app(Arc::new(MemoryRateLimitStore::default()), clock.clone());
+        for _ in 0..2 {
+            assert_eq!(
+                status(&app, "/api/v1/products/search", None).await,
+                StatusCode::OK
+            );
+        }
+
+        // A new window, but the last one still weighs 2 * 0.9.
+        clock.advance(Duration::from_secs(66));
+        let response = send(&app, "/api/v1/products/search", None).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[RETRY_AFTER], "24");
+
+        clock.advance(Duration::from_secs(23));
+        assert_eq!(
+            status(&app, "/api/v1/products/search", None).await,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(
+            status(&app, "/api/v1/products/search", None).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn rejections_do_not_count_against_the_client() {
+        let clock = FakeClock::at(T0);
+        let app = app(Arc::new(MemoryRateLimitStore::default()), clock.clone());
+        for _ in 0..2 {
+            send(&app, "/api/v1/products/search", None).await;
+        }
+        for _ in 0..10 {
+            assert_eq!(
+                status(&app, "/api/v1/products/search", None).await,
+                StatusCode::TOO_MANY_REQUESTS
+            );
+        }
+
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(
+            status(&app, "/api/v1/products/search", None).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn authenticated_users_behind_one_address_have_their_own_budget() {
+        let clock = FakeClock::at(T0);
+        let app = app(Arc::new(MemoryRateLimitStore::default()), clock);
+        for _ in 0..2 {
+            send(&app, "/api/v1/products/search", Some("alice")).await;
+        }
+        assert_eq!(
+            status(&app, "/api/v1/products/search", Some("alice")).await,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(
+            status(&app, "/api/v1/products/search", Some("bob")).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(&app, "/api/v1/products/search", None).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn a_claimed_user_id_shares_the_address_budget() {
+        let app = app(Arc::new(MemoryRateLimitStore::default()), FakeClock::at(T0));
+        for _ in 0..2 {
+            send(&app, "/api/v1/products/search", None).await;
+        }
+        let mut request = Request::get("/api/v1/products/search")
+            .header("x-user-id", "someone-else")
+            .body(Body::empty())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([203, 0, 113, 7], 40000))));
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    /// The gateway's address in these tests.
+    const GATEWAY: [u8; 4] = [10, 0, 0, 2];
+
+    fn app_behind_the_gateway() -> Router {
+        let layer = RateLimitLayer::with_clock(
+            "svc",
+            limits(),
+            Arc::new(MemoryRateLimitStore::default()),
+            FakeClock::at(T0),
+        )
+        .trusting(TrustedProxies::new([IpAddr::from(GATEWAY)]));
+        Router::new()
+            .route(
+                "/api/v1/products/search",
+                get(|ClientAddress(address): ClientAddress| async move {
+                    address
+                        .map(|address| address.to_string())
+                        .unwrap_or_default()
+                }),
+            )
+            .layer(layer)
+    }
+
+    /// A search from `peer`, with `forwarded` as its `X-Forwarded-For` if given.
+    async fn search_from(app: &Router, peer: [u8; 4], forwarded: Option<&str>) -> Response {
+        let mut request = Request::get("/api/v1/products/search");
+        if let Some(forwarded) = forwarded {
+            request = request.header(X_FORWARDED_FOR, forwarded);
+        }
+        let mut request = request.body(Body::empty()).unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from((peer, 40000))));
+        app.clone().oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn clients_forwarded_by_a_trusted_proxy_have_their_own_budget() {
+        let app = app_behind_the_gateway();
+        for _ in 0..2 {
+            let response = search_from(&app, GATEWAY, Some("198.51.100.1")).await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        let response = search_from(&app, GATEWAY, Some("198.51.100.1")).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // Another client of the gateway, who handlers see as such too.
+        let response = search_from(&app, GATEWAY, Some("198.51.100.2")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(bytes, "198.51.100.2");
+    }
+
+    #[tokio::test]
+    async fn only_trusted_proxies_are_believed() {
+        let app = app_behind_the_gateway();
+        let client = [203, 0, 113, 7];
+        for forwarded in ["198.51.100.1", "198.51.100.2"] {
+            let response = search_from(&app, client, Some(forwarded)).await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        let response = search_from(&app, client, Some("198.51.100.3")).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // What the client put in front of the gateway's entry is not believed either.
+        for _ in 0..2 {
+            search_from(&app, GATEWAY, Some("198.51.100.9, 203.0.113.8")).await;
+        }
+        let response = search_from(&app, GATEWAY, Some("198.51.100.10, 203.0.113.8")).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[test]
+    fn trusted_proxies_name_the_client() {
+        let proxies = TrustedProxies::parse(" 10.0.0.2, ::1 ,").unwrap();
+        assert!(proxies.contains(IpAddr::from(GATEWAY)));
+        assert!(proxies.contains("::1".parse().unwrap()));
+        assert!(TrustedProxies::parse("10.0.0.0/8").is_err());
+        assert_eq!(
+            TrustedProxies::parse("").unwrap(),
+            TrustedProxies::default()
+        );
+
+        let client = |peer: [u8; 4], forwarded: &[&'static str]| {
+            let mut headers = HeaderMap::new();
+            for value in forwarded {
+                headers.append(X_FORWARDED_FOR, HeaderValue::from_static(value));
+            }
+            proxies
+                .client(Some(SocketAddr::from((peer, 1))), &headers)
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(client(GATEWAY, &[]), "10.0.0.2");
+        assert_eq!(client(GATEWAY, &["198.51.100.1"]), "198.51.100.1");
+        // Past every trusted hop, whichever header line it came in.
+        assert_eq!(
+            client(GATEWAY, &["198.51.100.9, 198.51.100.1", "10.0.0.2"]),
+            "198.51.100.1"
+        );
+        assert_eq!(client(GATEWAY, &["junk"]), "10.0.0.2");
+        assert_eq!(client([203, 0, 113, 7], &["198.51.100.1"]), "203.0.113.7");
+        assert_eq!(proxies.client(None, &HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn clients_are_an_address_and_an_authenticated_subject() {
+        let address = IpAddr::from([10, 0, 0, 7]);
+        assert_eq!(rate_limit_client(Some(address), None), "10.0.0.7");
+        assert_eq!(
+            rate_limit_client(Some(address), Some("u-1")),
+            "10.0.0.7:u-1"
+        );
+        assert_eq!(rate_limit_client(None, Some("u-1")), "unknown:u-1");
+    }
+
+    #[tokio::test]
+    async fn handlers_can_limit_with_a_rate_limiter() {
+        let limiter = RateLimiter::with_clock(
+            "svc",
+            Arc::new(MemoryRateLimitStore::default()),
+            FakeClock::at(T0),
+        );
+        let route = RouteLimit {
+            group: "reports",
+            prefix: "/api/v1/products",
+            per_minute: 1,
+        };
+        assert_eq!(limiter.check(route, "alice").await, None);
+        let wait = limiter.check(route, "alice").await.unwrap();
+        assert_eq!(wait, Duration::from_secs(120));
+        assert_eq!(retry_after_secs(wait), 120);
+        assert_eq!(limiter.check(route, "bob").await, None);
+        assert_eq!(retry_after_secs(Duration::from_millis(1500)), 2);
+        assert_eq!(retry_after_secs(Duration::ZERO), 1);
+    }
+
+    #[tokio::test]
+    async fn an_unavailable_store_lets_requests_through() {
+        let app = app(Arc::new(DownStore), FakeClock::at(T0));
+        for _ in 0..5 {
+            assert_eq!(
+                status(&app, "/api/v1/products/search", None).await,
+                StatusCode::OK
+            );
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_slow_store_lets_requests_through() {
+        let app = app(Arc::new(HangingStore), FakeClock::at(T0));
+        assert_eq!(
+            status(&app, "/api/v1/products/search", None).await,
+            StatusCode::OK
+        );
+    }
+
+    #[test]
+    fn routes_match_by_longest_prefix_at_segment_boundaries() {
+        let limits = RateLimits::default()
+            .route("products", "/api/v1/products", 100)
+            .route("search", "/api/v1/products/search", 10);
+        assert_eq!(
+            limits.limit_for("/api/v1/products/search").unwrap().group,
+            "search"
+        );
+        assert_eq!(
+            limits
+                .limit_for("/api/v1/products/search/semantic")
+                .unwrap()
+                .per_minute,
+            10
+        );
+        assert_eq!(
+            limits
+                .limit_for("/api/v1/products/searchable")
+                .unwrap()
+                .group,
+            "products"
+        );
+        assert_eq!(limits.limit_for("/health"), None);
+    }
+
+    #[test]
+    fn retry_after_is_when_the_next_request_fits() {
+        let counts = |current, previous| Counts {
+            allowed: false,
+            current,
+            previous,
+        };
+        // Half way, 1 + 8 * 0.5 + 1 is over 5; 7.5 s on, 1 + 8 * 0.375 + 1 fits.
+        assert_eq!(
+            retry_after(counts(1, 8), Duration::from_secs(30), 5),
+            Duration::from_millis(7500)
+        );
+        // A full window: the 45 s left of it, then 15 s until the 4 weigh 3.
+        assert_eq!(
+            retry_after(counts(4, 0), Duration::from_secs(15), 4),
+            Duration::from_secs(45 + 15)
+        );
+    }
+}
@@ -0,0 +1,203 @@
+//! OpenAPI documents for the YoloEats HTTP APIs.
+//!
+//! Each service derives its document with `utoipa`, `#[derive(OpenApi)]` over its
+//! `#[utoipa::path]`-annotated handlers, and merges [`openapi_router`] into its router,
+//! which serves the document at [`OPENAPI_JSON_PATH`] and Swagger UI over it at
+//! [`DOCS_PATH`]. Schemas are derived from the types the handlers serialize, so serde
+//! renames show in the document as they are on the wire.
+//!
+//! The handlers' annotations list the errors their `IntoResponse` impls answer with;
+//! [`MiddlewareResponses`] adds those of the shared middleware, which answers before any
+//! handler runs. Operations behind `yoloeats_auth::AuthLayer` name `security(("bearer" =
+//! []))`, the scheme [`BearerAuth`] declares.
+
+use axum::Router;
+use utoipa::{
+    Modify, PartialSchema,
+    openapi::{
+        ContentBuilder, OpenApi, Ref, Response, ResponseBuilder,
+        path::{Operation, PathItem},
+        security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    },
+};
+use utoipa_swagger_ui::SwaggerUi;
+use yoloeats_domain::ErrorBody;
+
+pub const OPENAPI_JSON_PATH: &str = "/api-docs/openapi.json";
+pub const DOCS_PATH: &str = "/docs";
+
+/// The document and Swagger UI routes, ready to `merge` into a service router of any
+/// state type.
+pub fn openapi_router<S>(spec: OpenApi) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new().merge(SwaggerUi::new(DOCS_PATH).url(OPENAPI_JSON_PATH, spec))
+}
+
+/// An error response in the shared envelope, [`ErrorBody`].
+pub fn error_response(description: &str) -> Response {
+    ResponseBuilder::new()
+        .description(description)
+        .content(
+            "application/json",
+            ContentBuilder::new()
+                .schema(Some(Ref::from_schema_name("ErrorBody")))
+                .build(),
+        )
+        .build()
+}
+
+/// Adds what `yoloeats_metrics` answers for every service: `503 overloaded` from the
+/// load shedder on every operation, and `413 payload_too_large` from the body limit on
+/// those that take a body. An operation documenting its own response for the status
+/// keeps it. Also registers the [`ErrorBody`] schema the handlers' errors refer to.
+pub struct MiddlewareResponses;
+
+impl Modify for MiddlewareResponses {
+    fn modify(&self, openapi: &mut OpenApi) {
+        openapi
+            .components
+            .get_or_insert_with(Default::default)
+            .schemas
+            .entry("ErrorBody".to_string())
+            .or_insert_with(ErrorBody::schema);
+        for item in openapi.paths.paths.values_mut() {
+            for operation in operations_mut(item) {
+                let takes_body = operation.request_body.is_some();
+                let responses = &mut operation.responses.responses;
+                responses.entry("503".to_string()).or_insert_with(|| {
+                    error_response("Too many requests in flight; retry after `Retry-After`.").into()
+                });
+                if takes_body {
+                    responses.entry("413".to_string()).or_insert_with(|| {
+                        error_response("The body is over the route's size limit.").into()
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Declares the `bearer` security scheme: a JWT in `Authorization: Bearer ...`.
+pub struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut OpenApi) {
+        openapi
+            .components
+            .get_or_insert_with(Default::default)
+            .add_security_scheme(
+                "bearer",
+                SecurityScheme::Http(
+                    HttpBuilder::new()
+                        .scheme(HttpAuthScheme::Bearer)
+                        .bearer_format("JWT")
+                        .build(),
+                ),
+            );
+    }
+}
+
+fn operations_mut(item: &mut PathItem) -> impl Iterator<Item = &mut Operation> {
+    [
+        &mut item.get,
+        &mut item.put,
+        &mut item.post,
+        &mut item.delete,
+        &mut item.patch,
+    ]
+    .into_iter()
+    .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::{Body, to_bytes},
+        http::{Request, StatusCode},
+        routing::get,
+    };
+    use serde_json::Value;
+    use tower::ServiceExt;
+    use utoipa::OpenApi as _;
+
+    #[utoipa::path(get, path = "/items", responses((status = 200, description = "The items")))]
+    async fn list() -> &'static str {
+        "[]"
+    }
+
+    #[utoipa::path(
+        post,
+        path = "/items",
+        request_body = String,
+        responses(
+            (status = 201, description = "Created"),
+            (status = 503, description = "The item store is down", body = ErrorBody),
+        )
+    )]
+    async fn create(_body: String) -> StatusCode {
+        StatusCode::CREATED
+    }
+
+    #[derive(utoipa::OpenApi)]
+    #[openapi(paths(list, create), modifiers(&MiddlewareResponses))]
+    struct Doc;
+
+    fn app() -> Router {
+        Router::new()
+            .route("/items", get(list).post(create))
+            .merge(openapi_router(Doc::openapi()))
+    }
+
+    async fn get_path(path: &str) -> (StatusCode, Vec<u8>) {
+        let response = app()
+            .oneshot(Request::get(path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, body.to_vec())
+    }
+
+    #[tokio::test]
+    async fn serves_the_document_and_swagger_ui() {
+        let (status, body) = get_path(OPENAPI_JSON_PATH).await;
+        assert_eq!(status, StatusCode::OK);
+        let spec: Value = serde_json::from_slice(&body).unwrap();
+        assert!(spec["openapi"].as_str().unwrap().starts_with("3."));
+        assert!(spec["paths"]["/items"]["get"].is_object());
+
+        let (status, body) = get_path(&format!("{}/", DOCS_PATH)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(String::from_utf8(body).unwrap().contains("swagger-ui"));
+    }
+
+    #[test]
+    fn middleware_errors_are_added_without_overriding() {
+        let spec = serde_json::to_value(Doc::openapi()).unwrap();
+        let items = &spec["paths"]["/items"];
+
+        let shed = &items["get"]["responses"]["503"];
+        assert_eq!(
+            shed["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/ErrorBody"
+        );
+        assert!(items["get"]["responses"]["413"].is_null());
+        assert!(items["post"]["responses"]["413"].is_object());
+        assert_eq!(
+            items["post"]["responses"]["503"]["description"],
+            "The item store is down"
+        );
+
+        let error_body = &spec["components"]["schemas"]["ErrorBody"];
+        for field in ["code", "message", "error", "requestId", "details"] {
+            assert!(error_body["properties"][field].is_object(), "{}", field);
+        }
+        assert_eq!(
+            error_body["required"],
+            serde_json::json!(["code", "message", "error"])
+        );
+    }
+}
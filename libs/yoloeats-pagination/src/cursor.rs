@@ -0,0 +1,176 @@
+use crate::error::PaginationError;
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use serde::{Serialize, de::DeserializeOwned};
+use sha2::Sha256;
+use std::{env, fmt, sync::Arc};
+use tracing::{debug, warn};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Key for signing cursors. Every replica of a service must share it, or a cursor issued
+/// by one is refused by the next.
+pub const CURSOR_SECRET_ENV: &str = "CURSOR_SECRET";
+
+/// Turns a position into `base64url(json).base64url(hmac)` and back. Cheap to clone.
+#[derive(Clone)]
+pub struct CursorCodec {
+    key: Arc<[u8]>,
+}
+
+impl CursorCodec {
+    pub fn new(secret: impl AsRef<[u8]>) -> Self {
+        CursorCodec {
+            key: secret.as_ref().into(),
+        }
+    }
+
+    /// Uses [`CURSOR_SECRET_ENV`]. Without it the key is random per process: fine for a
+    /// single local instance, broken behind a load balancer, hence the warning.
+    pub fn from_env() -> Self {
+        match env::var(CURSOR_SECRET_ENV) {
+            Ok(secret) if !secret.trim().is_empty() => CursorCodec::new(secret.trim()),
+            _ => {
+                warn!(
+                    "{} is not set: page cursors are only valid on this instance.",
+                    CURSOR_SECRET_ENV
+                );
+                let mut key = uuid::Uuid::new_v4().as_bytes().to_vec();
+                key.extend_from_slice(uuid::Uuid::new_v4().as_bytes());
+                CursorCodec::new(key)
+            }
+        }
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(&self.key).expect("HMAC accepts keys of any length")
+    }
+
+    pub fn encode<T: Serialize>(&self, position: &T) -> String {
+        let payload = serde_json::to_vec(position).expect("cursor positions serialize");
+        let mut mac = self.mac();
+        mac.update(&payload);
+        let signature = mac.finalize().into_bytes();
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(&payload),
+            URL_SAFE_NO_PAD.encode(signature)
+        )
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, cursor: &str) -> Result<T, PaginationError> {
+        let (payload, signature) = cursor
+            .split_once('.')
+            .ok_or(PaginationError::InvalidCursor)?;
+        let payload = URL_SAFE_NO_PAD
+            .decode(payload)
+            .map_err(|_| PaginationError::InvalidCursor)?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| PaginationError::InvalidCursor)?;
+
+        let mut mac = self.mac();
+        mac.update(&payload);
+        // Constant-time comparison.
+        mac.verify_slice(&signature).map_err(|_| {
+            debug!("Rejected cursor with a bad signature");
+            PaginationError::InvalidCursor
+        })?;
+        serde_json::from_slice(&payload).map_err(|_| PaginationError::InvalidCursor)
+    }
+}
+
+impl fmt::Debug for CursorCodec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CursorCodec")
+            .field("key", &"<redacted>")
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Position {
+        offset: u64,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct OtherEndpoint {
+        last_id: String,
+    }
+
+    #[test]
+    fn round_trips_a_position() {
+        let codec = CursorCodec::new("s3cret");
+        let cursor = codec.encode(&Position { offset: 40 });
+        assert!(
+            !cursor.contains('{') && !cursor.contains('='),
+            "opaque and URL-safe: {}",
+            cursor
+        );
+        assert_eq!(
+            codec.decode::<Position>(&cursor).unwrap(),
+            Position { offset: 40 }
+        );
+    }
+
+    #[test]
+    fn tampered_payloads_are_refused() {
+        let codec = CursorCodec::new("s3cret");
+        let cursor = codec.encode(&Position { offset: 40 });
+        let (_, signature) = cursor.split_once('.').unwrap();
+
+        // Same signature, payload rewritten to jump ahead.
+        let forged = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(br#"{"offset":100000}"#),
+            signature
+        );
+        assert_eq!(
+            codec.decode::<Position>(&forged),
+            Err(PaginationError::InvalidCursor)
+        );
+
+        let mut flipped = cursor.into_bytes();
+        flipped[2] ^= 0x01;
+        let flipped = String::from_utf8(flipped).unwrap();
+        assert_eq!(
+            codec.decode::<Position>(&flipped),
+            Err(PaginationError::InvalidCursor)
+        );
+    }
+
+    #[test]
+    fn cursors_signed_with_another_key_are_refused() {
+        let cursor = CursorCodec::new("old-secret").encode(&Position { offset: 40 });
+        assert_eq!(
+            CursorCodec::new("new-secret").decode::<Position>(&cursor),
+            Err(PaginationError::InvalidCursor)
+        );
+    }
+
+    #[test]
+    fn garbage_and_foreign_cursors_are_refused() {
+        let codec = CursorCodec::new("s3cret");
+        for garbage in ["", "no-dot", "!!!.???", "e30.e30"] {
+            assert_eq!(
+                codec.decode::<Position>(garbage),
+                Err(PaginationError::InvalidCursor),
+                "{}",
+                garbage
+            );
+        }
+        let cursor = codec.encode(&Position { offset: 40 });
+        assert!(codec.decode::<OtherEndpoint>(&cursor).is_err());
+    }
+
+    #[test]
+    fn debug_output_redacts_the_key() {
+        assert!(!format!("{:?}", CursorCodec::new("s3cret")).contains("s3cret"));
+    }
+}
@@ -0,0 +1,33 @@
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use thiserror::Error;
+use yoloeats_domain::{ErrorBody, ErrorCode};
+use yoloeats_tracing::current_request_id;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PaginationError {
+    #[error("Invalid limit '{0}': expected a positive integer")]
+    InvalidLimit(String),
+
+    #[error("Invalid offset '{0}': expected a non-negative integer")]
+    InvalidOffset(String),
+
+    #[error("Invalid query string: {0}")]
+    InvalidQuery(String),
+
+    /// Malformed, tampered with, signed with another key or meant for another endpoint.
+    /// Deliberately says no more than that.
+    #[error("Invalid cursor")]
+    InvalidCursor,
+}
+
+impl IntoResponse for PaginationError {
+    fn into_response(self) -> Response {
+        let body = ErrorBody::new(ErrorCode::InvalidRequest, self.to_string())
+            .with_request_id(current_request_id().map(|id| id.to_string()));
+        (StatusCode::BAD_REQUEST, Json(body)).into_response()
+    }
+}
@@ -0,0 +1,25 @@
+//! Pagination shared by the YoloEats list endpoints.
+//!
+//! Handlers take [`PageParams`] as an extractor: it reads `limit`, `offset` and `cursor`
+//! from the query string, rejects nonsense with a 400 in the error envelope and clamps
+//! `limit` to the endpoint's [`PageLimit`]. They answer with [`Page`], serialized as
+//! `{"items", "total", "nextCursor", "hasMore"}`. Endpoints fetch one item more than
+//! the page holds and build it with [`Page::overfetched`], so a page that ends exactly
+//! at the last item says so.
+//!
+//! Cursors are opaque to clients: [`CursorCodec`] serializes whatever position the
+//! endpoint needs (an offset, the last id seen) and signs it with HMAC-SHA256, so a
+//! tampered or forged cursor is refused instead of steering the query.
+//!
+//! With feature `openapi`, [`Page`] is a `utoipa` schema and [`PageParams`] lists its
+//! query parameters for the endpoints' OpenAPI operations.
+
+mod cursor;
+mod error;
+mod page;
+mod params;
+
+pub use cursor::{CURSOR_SECRET_ENV, CursorCodec};
+pub use error::PaginationError;
+pub use page::Page;
+pub use params::{DefaultPageLimit, PageLimit, PageParams};
@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+
+/// One page of a list response. `total` is `None` where counting would cost a second
+/// query; `next_cursor` is `None` on the last page. Both are always present on the wire,
+/// as `null` when unset, so clients can rely on the shape. `has_more` says whether
+/// `next_cursor` leads to another page, so clients needn't ask for an empty one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: Option<u64>,
+    pub next_cursor: Option<String>,
+    #[serde(default)]
+    pub has_more: bool,
+}
+
+impl<T> Page<T> {
+    pub fn new(items: Vec<T>) -> Self {
+        Page {
+            items,
+            total: None,
+            next_cursor: None,
+            has_more: false,
+        }
+    }
+
+    /// The first `limit` of `fetched`, which the endpoint asked for `limit + 1` of: an
+    /// item past the page is how it knows another page follows. `cursor` makes that
+    /// page's cursor from this one's last item; it isn't asked on the last page.
+    pub fn overfetched(
+        mut fetched: Vec<T>,
+        limit: u64,
+        cursor: impl FnOnce(&T) -> Option<String>,
+    ) -> Self {
+        let more = fetched.len() as u64 > limit;
+        fetched.truncate(limit as usize);
+        let next_cursor = fetched.last().filter(|_| more).and_then(cursor);
+        Page::new(fetched).with_next_cursor(next_cursor)
+    }
+
+    pub fn with_total(mut self, total: u64) -> Self {
+        self.total = Some(total);
+        self
+    }
+
+    /// Sets the cursor to the next page; there is more exactly when there is one.
+    pub fn with_next_cursor(mut self, cursor: Option<String>) -> Self {
+        self.has_more = cursor.is_some();
+        self.next_cursor = cursor;
+        self
+    }
+
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            total: self.total,
+            next_cursor: self.next_cursor,
+            has_more: self.has_more,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn serializes_as_the_shared_envelope() {
+        let page = Page::new(vec!["a", "b"])
+            .with_total(7)
+            .with_next_cursor(Some("eyJvZmZzZXQiOjJ9.sig".to_string()));
+        assert_eq!(
+            serde_json::to_value(&page).unwrap(),
+            json!({
+                "items": ["a", "b"],
+                "total": 7,
+                "nextCursor": "eyJvZmZzZXQiOjJ9.sig",
+                "hasMore": true,
+            })
+        );
+    }
+
+    #[test]
+    fn unset_fields_are_null_not_missing() {
+        let page: Page<u32> = Page::new(vec![]);
+        assert_eq!(
+            serde_json::to_value(&page).unwrap(),
+            json!({ "items": [], "total": null, "nextCursor": null, "hasMore": false })
+        );
+    }
+
+    fn cursor_after(last: &u32) -> Option<String> {
+        Some(format!("after:{}", last))
+    }
+
+    #[test]
+    fn an_item_past_the_limit_means_another_page() {
+        let page = Page::overfetched(vec![1, 2, 3], 2, cursor_after);
+        assert_eq!(page.items, [1, 2]);
+        assert_eq!(page.next_cursor.as_deref(), Some("after:2"));
+        assert!(page.has_more);
+    }
+
+    #[test]
+    fn a_page_of_exactly_the_limit_is_the_last() {
+        let page = Page::overfetched(vec![1, 2], 2, cursor_after);
+        assert_eq!(page.items, [1, 2]);
+        assert_eq!(page.next_cursor, None);
+        assert!(!page.has_more);
+
+        let short = Page::overfetched(vec![1], 2, cursor_after);
+        assert_eq!(short.items, [1]);
+        assert!(!short.has_more);
+        let empty = Page::overfetched(Vec::new(), 2, cursor_after);
+        assert!(empty.items.is_empty());
+        assert!(!empty.has_more);
+    }
+
+    #[test]
+    fn no_cursor_for_the_next_page_means_no_more() {
+        let page = Page::overfetched(vec![1, 2, 3], 2, |_| None);
+        assert_eq!(page.items, [1, 2]);
+        assert!(!page.has_more);
+    }
+
+    #[test]
+    fn pages_without_has_more_still_read() {
+        let page: Page<u32> =
+            serde_json::from_value(json!({ "items": [1], "total": 1, "nextCursor": null }))
+                .unwrap();
+        assert!(!page.has_more);
+    }
+
+    #[test]
+    fn map_keeps_the_paging_fields() {
+        let page = Page::new(vec![1, 2])
+            .with_total(4)
+            .with_next_cursor(Some("c".to_string()))
+            .map(|n| n.to_string());
+        assert_eq!(page.items, vec!["1".to_string(), "2".to_string()]);
+        assert_eq!(page.total, Some(4));
+        assert!(page.has_more);
+    }
+}
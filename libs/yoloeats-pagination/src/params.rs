@@ -0,0 +1,280 @@
+use crate::{cursor::CursorCodec, error::PaginationError};
+use axum::{extract::FromRequestParts, http::request::Parts};
+use serde::{Deserialize, de::DeserializeOwned};
+use std::{fmt, marker::PhantomData};
+
+/// An endpoint's page sizes: `DEFAULT` when the client sends no `limit`, and `MAX`,
+/// which larger requests are clamped to rather than refused.
+pub trait PageLimit: Send + Sync + 'static {
+    const DEFAULT: u64;
+    const MAX: u64;
+}
+
+/// 20 per page, at most 100.
+pub struct DefaultPageLimit;
+
+impl PageLimit for DefaultPageLimit {
+    const DEFAULT: u64 = 20;
+    const MAX: u64 = 100;
+}
+
+/// `?limit=&offset=&cursor=`, validated. Other query parameters are left for the
+/// handler's own `Query` extractor.
+///
+/// When a cursor is present it supersedes `offset`; decode it with
+/// [`PageParams::position`].
+pub struct PageParams<L: PageLimit = DefaultPageLimit> {
+    pub limit: u64,
+    pub offset: u64,
+    pub cursor: Option<String>,
+    _limit: PhantomData<L>,
+}
+
+impl<L: PageLimit> PageParams<L> {
+    /// Parses the raw query values; what the extractor does after reading the query.
+    pub fn parse(
+        limit: Option<&str>,
+        offset: Option<&str>,
+        cursor: Option<&str>,
+    ) -> Result<Self, PaginationError> {
+        let limit = match limit.map(str::trim).filter(|v| !v.is_empty()) {
+            None => L::DEFAULT,
+            Some(raw) => match raw.parse::<u64>() {
+                Ok(0) | Err(_) => return Err(PaginationError::InvalidLimit(raw.to_string())),
+                Ok(limit) => limit.min(L::MAX),
+            },
+        };
+        let offset = match offset.map(str::trim).filter(|v| !v.is_empty()) {
+            None => 0,
+            Some(raw) => raw
+                .parse::<u64>()
+                .map_err(|_| PaginationError::InvalidOffset(raw.to_string()))?,
+        };
+        Ok(PageParams {
+            limit,
+            offset,
+            cursor: cursor
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string),
+            _limit: PhantomData,
+        })
+    }
+
+    /// The decoded cursor, if the client sent one.
+    pub fn position<T: DeserializeOwned>(
+        &self,
+        codec: &CursorCodec,
+    ) -> Result<Option<T>, PaginationError> {
+        self.cursor
+            .as_deref()
+            .map(|cursor| codec.decode(cursor))
+            .transpose()
+    }
+}
+
+impl<L: PageLimit> fmt::Debug for PageParams<L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PageParams")
+            .field("limit", &self.limit)
+            .field("offset", &self.offset)
+            .field("cursor", &self.cursor.as_ref().map(|_| "<cursor>"))
+            .finish()
+    }
+}
+
+/// Taken as strings so a bad value gets our message, not serde's.
+#[derive(Deserialize)]
+struct RawPageQuery {
+    limit: Option<String>,
+    offset: Option<String>,
+    cursor: Option<String>,
+}
+
+impl<S, L> FromRequestParts<S> for PageParams<L>
+where
+    S: Send + Sync,
+    L: PageLimit,
+{
+    type Rejection = PaginationError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let raw: RawPageQuery = serde_urlencoded::from_str(parts.uri.query().unwrap_or_default())
+            .map_err(|e| PaginationError::InvalidQuery(e.to_string()))?;
+        PageParams::parse(
+            raw.limit.as_deref(),
+            raw.offset.as_deref(),
+            raw.cursor.as_deref(),
+        )
+    }
+}
+
+/// The three query parameters, with the endpoint's `DEFAULT` and `MAX` as the limit's
+/// default and maximum.
+#[cfg(feature = "openapi")]
+impl<L: PageLimit> utoipa::IntoParams for PageParams<L> {
+    fn into_params(
+        _parameter_in_provider: impl Fn() -> Option<utoipa::openapi::path::ParameterIn>,
+    ) -> Vec<utoipa::openapi::path::Parameter> {
+        use utoipa::openapi::{
+            ObjectBuilder, Required,
+            path::{ParameterBuilder, ParameterIn},
+            schema::Type,
+        };
+
+        let param = |name: &str, description: &str, schema: ObjectBuilder| {
+            ParameterBuilder::new()
+                .name(name)
+                .parameter_in(ParameterIn::Query)
+                .required(Required::False)
+                .description(Some(description))
+                .schema(Some(schema))
+                .build()
+        };
+        vec![
+            param(
+                "limit",
+                "Items per page; larger values are clamped to the maximum.",
+                ObjectBuilder::new()
+                    .schema_type(Type::Integer)
+                    .minimum(Some(1))
+                    .maximum(Some(L::MAX))
+                    .default(Some(L::DEFAULT.into())),
+            ),
+            param(
+                "offset",
+                "Items to skip; ignored when a cursor is sent.",
+                ObjectBuilder::new()
+                    .schema_type(Type::Integer)
+                    .minimum(Some(0))
+                    .default(Some(0.into())),
+            ),
+            param(
+                "cursor",
+                "The previous page's `nextCursor`.",
+                ObjectBuilder::new().schema_type(Type::String),
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Router,
+        body::Body,
+        http::{Request, StatusCode},
+        routing::get,
+    };
+    use serde::Serialize;
+    use tower::ServiceExt;
+
+    struct Small;
+
+    impl PageLimit for Small {
+        const DEFAULT: u64 = 5;
+        const MAX: u64 = 10;
+    }
+
+    #[test]
+    fn limits_default_and_clamp_to_the_endpoint_max() {
+        let params = PageParams::<Small>::parse(None, None, None).unwrap();
+        assert_eq!((params.limit, params.offset), (5, 0));
+
+        assert_eq!(
+            PageParams::<Small>::parse(Some("7"), None, None)
+                .unwrap()
+                .limit,
+            7
+        );
+        assert_eq!(
+            PageParams::<Small>::parse(Some("500"), None, None)
+                .unwrap()
+                .limit,
+            10
+        );
+        assert_eq!(
+            PageParams::<DefaultPageLimit>::parse(Some("500"), None, None)
+                .unwrap()
+                .limit,
+            100
+        );
+    }
+
+    #[test]
+    fn nonsense_values_are_rejected() {
+        assert_eq!(
+            PageParams::<Small>::parse(Some("0"), None, None).unwrap_err(),
+            PaginationError::InvalidLimit("0".to_string())
+        );
+        assert_eq!(
+            PageParams::<Small>::parse(Some("-3"), None, None).unwrap_err(),
+            PaginationError::InvalidLimit("-3".to_string())
+        );
+        assert_eq!(
+            PageParams::<Small>::parse(None, Some("ten"), None).unwrap_err(),
+            PaginationError::InvalidOffset("ten".to_string())
+        );
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Position {
+        offset: u64,
+    }
+
+    #[test]
+    fn position_decodes_a_signed_cursor() {
+        let codec = CursorCodec::new("s3cret");
+        let cursor = codec.encode(&Position { offset: 30 });
+        let params = PageParams::<Small>::parse(None, None, Some(&cursor)).unwrap();
+        assert_eq!(
+            params.position::<Position>(&codec).unwrap(),
+            Some(Position { offset: 30 })
+        );
+
+        let none = PageParams::<Small>::parse(None, None, Some("  ")).unwrap();
+        assert_eq!(none.position::<Position>(&codec).unwrap(), None);
+    }
+
+    async fn list(page: PageParams<Small>) -> String {
+        format!("{} {} {:?}", page.limit, page.offset, page.cursor)
+    }
+
+    async fn get_body(uri: &str) -> (StatusCode, String) {
+        let response = Router::new()
+            .route("/items", get(list))
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn extracts_from_the_query_next_to_other_parameters() {
+        assert_eq!(
+            get_body("/items?q=milk&limit=50&offset=20&cursor=abc.def").await,
+            (StatusCode::OK, r#"10 20 Some("abc.def")"#.to_string())
+        );
+        assert_eq!(
+            get_body("/items").await,
+            (StatusCode::OK, "5 0 None".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn rejections_use_the_error_envelope() {
+        let (status, body) = get_body("/items?limit=lots").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["code"], "invalid_request");
+        assert_eq!(
+            body["message"],
+            "Invalid limit 'lots': expected a positive integer"
+        );
+    }
+}
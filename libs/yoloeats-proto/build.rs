@@ -0,0 +1,12 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Use the vendored protoc so builds don't depend on a system install.
+    // SAFETY: build scripts are single-threaded at this point.
+    unsafe {
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    }
+    tonic_build::configure().compile_protos(
+        &["proto/yoloeats/internal/v1/internal.proto"],
+        &["proto"],
+    )?;
+    Ok(())
+}
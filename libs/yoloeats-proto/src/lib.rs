@@ -0,0 +1,186 @@
+//! Generated gRPC types for internal service-to-service calls.
+//!
+//! The messages mirror the `yoloeats-domain` DTOs, and the `From` impls here are the
+//! only place the two shapes meet, so servers and clients keep working with the
+//! domain types on either side of the wire.
+
+pub mod v1 {
+    tonic::include_proto!("yoloeats.internal.v1");
+}
+
+use yoloeats_domain::{IngredientEntry, ProductSummary, RiskLevel, SafetyProfile};
+
+impl From<RiskLevel> for v1::RiskLevel {
+    fn from(level: RiskLevel) -> Self {
+        match level {
+            RiskLevel::Low => v1::RiskLevel::Low,
+            RiskLevel::Medium => v1::RiskLevel::Medium,
+            RiskLevel::High => v1::RiskLevel::High,
+        }
+    }
+}
+
+impl From<v1::RiskLevel> for RiskLevel {
+    fn from(level: v1::RiskLevel) -> Self {
+        match level {
+            v1::RiskLevel::Low => RiskLevel::Low,
+            v1::RiskLevel::Medium => RiskLevel::Medium,
+            v1::RiskLevel::High => RiskLevel::High,
+            // Same fallback as a profile JSON without `risk_tolerance`.
+            v1::RiskLevel::Unspecified => RiskLevel::default(),
+        }
+    }
+}
+
+impl From<SafetyProfile> for v1::SafetyProfile {
+    fn from(profile: SafetyProfile) -> Self {
+        v1::SafetyProfile {
+            user_id: profile.user_id,
+            allergens: profile.allergens,
+            dietary_prefs: profile.dietary_prefs,
+            risk_tolerance: v1::RiskLevel::from(profile.risk_tolerance).into(),
+        }
+    }
+}
+
+impl From<v1::SafetyProfile> for SafetyProfile {
+    fn from(profile: v1::SafetyProfile) -> Self {
+        SafetyProfile {
+            // Unknown enum values decode as `Unspecified` through the accessor.
+            risk_tolerance: profile.risk_tolerance().into(),
+            user_id: profile.user_id,
+            allergens: profile.allergens,
+            dietary_prefs: profile.dietary_prefs,
+        }
+    }
+}
+
+impl From<ProductSummary> for v1::ProductSummary {
+    fn from(product: ProductSummary) -> Self {
+        v1::ProductSummary {
+            code: product.code,
+            product_name: product.product_name,
+            ingredients_text: product.ingredients_text,
+            allergens_tags: product.allergens_tags,
+            traces_tags: product.traces_tags,
+            labels_tags: product.labels_tags,
+            ingredients: product
+                .ingredients
+                .unwrap_or_default()
+                .into_iter()
+                .map(v1::Ingredient::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<v1::ProductSummary> for ProductSummary {
+    fn from(product: v1::ProductSummary) -> Self {
+        let ingredients: Vec<IngredientEntry> = product
+            .ingredients
+            .into_iter()
+            .map(IngredientEntry::from)
+            .collect();
+        ProductSummary {
+            code: product.code,
+            product_name: product.product_name,
+            ingredients_text: product.ingredients_text,
+            allergens_tags: product.allergens_tags,
+            traces_tags: product.traces_tags,
+            labels_tags: product.labels_tags,
+            // The wire has no absent list; an empty one is the JSON's missing field.
+            ingredients: Some(ingredients).filter(|i| !i.is_empty()),
+        }
+    }
+}
+
+impl From<IngredientEntry> for v1::Ingredient {
+    fn from(entry: IngredientEntry) -> Self {
+        v1::Ingredient {
+            id: entry.id,
+            text: entry.text,
+            percent_estimate: entry.percent_estimate,
+            vegan: entry.vegan,
+            vegetarian: entry.vegetarian,
+        }
+    }
+}
+
+impl From<v1::Ingredient> for IngredientEntry {
+    fn from(entry: v1::Ingredient) -> Self {
+        IngredientEntry {
+            id: entry.id,
+            text: entry.text,
+            percent_estimate: entry.percent_estimate,
+            vegan: entry.vegan,
+            vegetarian: entry.vegetarian,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+
+    #[test]
+    fn safety_profile_round_trips_through_the_wire_format() {
+        for risk_tolerance in [RiskLevel::Low, RiskLevel::Medium, RiskLevel::High] {
+            let profile = SafetyProfile {
+                user_id: "user-1".to_string(),
+                allergens: vec!["peanuts".to_string(), "milk".to_string()],
+                dietary_prefs: vec!["vegan".to_string()],
+                risk_tolerance,
+            };
+            let bytes = v1::SafetyProfile::from(profile.clone()).encode_to_vec();
+            let decoded = v1::SafetyProfile::decode(bytes.as_slice()).unwrap();
+            assert_eq!(SafetyProfile::from(decoded), profile);
+        }
+    }
+
+    #[test]
+    fn unspecified_or_unknown_risk_level_falls_back_to_default() {
+        let mut message = v1::SafetyProfile {
+            user_id: "user-2".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(SafetyProfile::from(message.clone()).risk_tolerance, RiskLevel::Medium);
+
+        message.risk_tolerance = 42;
+        assert_eq!(SafetyProfile::from(message).risk_tolerance, RiskLevel::Medium);
+    }
+
+    #[test]
+    fn product_summary_keeps_absent_optionals_absent() {
+        let product = ProductSummary {
+            code: "4000417025005".to_string(),
+            product_name: Some("Ritter Sport Alpenmilch".to_string()),
+            ingredients_text: None,
+            allergens_tags: vec!["en:milk".to_string()],
+            traces_tags: vec![],
+            labels_tags: vec!["en:vegetarian".to_string()],
+            ingredients: None,
+        };
+        let bytes = v1::ProductSummary::from(product.clone()).encode_to_vec();
+        let decoded = v1::ProductSummary::decode(bytes.as_slice()).unwrap();
+        assert_eq!(decoded.ingredients_text, None);
+        assert_eq!(ProductSummary::from(decoded), product);
+    }
+
+    #[test]
+    fn product_summary_carries_structured_ingredients() {
+        let product = ProductSummary {
+            code: "4000417025005".to_string(),
+            ingredients: Some(vec![IngredientEntry {
+                id: Some("en:whole-milk-powder".to_string()),
+                percent_estimate: Some(14.5),
+                vegan: Some("no".to_string()),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+        let bytes = v1::ProductSummary::from(product.clone()).encode_to_vec();
+        let decoded = v1::ProductSummary::decode(bytes.as_slice()).unwrap();
+        assert_eq!(ProductSummary::from(decoded), product);
+    }
+}
@@ -0,0 +1,307 @@
+//! Graceful shutdown for the YoloEats services.
+//!
+//! On Ctrl+C or SIGTERM ([`shutdown_signal`]) a service stops accepting connections,
+//! lets the requests in flight finish and waits for the background work it spawned
+//! through [`Shutdown::tasks`], for at most the drain timeout in all, then exits. Without
+//! this, a write whose cache invalidation or Qdrant indexing was still running is cut off
+//! half done.
+//!
+//! ```ignore
+//! let shutdown = Shutdown::new(drain_timeout_from_env()?);
+//! shutdown.trigger_on(shutdown_signal());
+//! let server = axum::serve(listener, app).with_graceful_shutdown(shutdown.signalled());
+//! shutdown.run(server).await?;
+//! ```
+
+use std::{
+    env,
+    future::{Future, IntoFuture},
+    time::Duration,
+};
+use thiserror::Error;
+use tokio::time::{Instant, timeout_at};
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
+use tracing::{info, warn};
+
+pub use tokio_util::task::TaskTracker;
+
+pub const SHUTDOWN_DRAIN_TIMEOUT_SECS_ENV: &str = "SHUTDOWN_DRAIN_TIMEOUT_SECS";
+
+/// Under Docker's default 10-second stop grace period, after which it kills the process.
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(8);
+
+#[derive(Debug, Error)]
+#[error("Invalid {SHUTDOWN_DRAIN_TIMEOUT_SECS_ENV} '{value}': expected a positive integer")]
+pub struct DrainTimeoutError {
+    pub value: String,
+}
+
+/// [`SHUTDOWN_DRAIN_TIMEOUT_SECS_ENV`] if set, else [`DEFAULT_DRAIN_TIMEOUT`].
+pub fn drain_timeout_from_env() -> Result<Duration, DrainTimeoutError> {
+    parse_drain_timeout(env::var(SHUTDOWN_DRAIN_TIMEOUT_SECS_ENV).ok())
+}
+
+fn parse_drain_timeout(value: Option<String>) -> Result<Duration, DrainTimeoutError> {
+    match value {
+        Some(value) if !value.trim().is_empty() => match value.trim().parse::<u64>() {
+            Ok(secs) if secs > 0 => Ok(Duration::from_secs(secs)),
+            _ => Err(DrainTimeoutError { value }),
+        },
+        _ => Ok(DEFAULT_DRAIN_TIMEOUT),
+    }
+}
+
+/// Resolves on Ctrl+C or, on Unix, SIGTERM (what orchestrators send on shutdown).
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("Received Ctrl+C."),
+        _ = terminate => info!("Received SIGTERM."),
+    }
+}
+
+/// How a shutdown went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Drain {
+    /// Every request and background task finished.
+    Complete,
+    /// The drain timeout passed first; what was still running was dropped.
+    TimedOut,
+}
+
+/// Shared by a service's servers and the background work it spawns. Clones share the
+/// same shutdown.
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    token: CancellationToken,
+    tasks: TaskTracker,
+    drain_timeout: Duration,
+}
+
+impl Shutdown {
+    pub fn new(drain_timeout: Duration) -> Self {
+        Shutdown {
+            token: CancellationToken::new(),
+            tasks: TaskTracker::new(),
+            drain_timeout,
+        }
+    }
+
+    /// Starts the shutdown once `signal` resolves.
+    pub fn trigger_on<F>(&self, signal: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let token = self.token.clone();
+        tokio::spawn(async move {
+            signal.await;
+            token.cancel();
+        });
+    }
+
+    /// Starts the shutdown now.
+    pub fn trigger(&self) {
+        self.token.cancel();
+    }
+
+    /// Resolves once the shutdown has started; what the servers' `with_graceful_shutdown`
+    /// and `serve_with_shutdown` take.
+    pub fn signalled(&self) -> WaitForCancellationFutureOwned {
+        self.token.clone().cancelled_owned()
+    }
+
+    /// Where to spawn background work the shutdown should wait for.
+    pub fn tasks(&self) -> TaskTracker {
+        self.tasks.clone()
+    }
+
+    /// Runs `server`, which should stop on [`Shutdown::signalled`], and once the shutdown
+    /// starts waits for it to drain and then for the tracked tasks, up to the drain
+    /// timeout in all. A server error is returned as soon as it happens.
+    pub async fn run<E>(
+        &self,
+        server: impl IntoFuture<Output = Result<(), E>>,
+    ) -> Result<Drain, E> {
+        let server = server.into_future();
+        tokio::pin!(server);
+        tokio::select! {
+            result = &mut server => {
+                result?;
+                // Stopped on its own; still let the tracked tasks finish.
+                self.token.cancel();
+                let drain = self.wait_for_tasks(Instant::now() + self.drain_timeout).await;
+                return Ok(drain);
+            }
+            _ = self.token.cancelled() => {}
+        }
+
+        let deadline = Instant::now() + self.drain_timeout;
+        info!(
+            "Shutting down: refusing new connections, draining in-flight requests for up to {:?}.",
+            self.drain_timeout
+        );
+        let requests = match timeout_at(deadline, &mut server).await {
+            Ok(result) => {
+                result?;
+                info!("In-flight requests drained.");
+                Drain::Complete
+            }
+            Err(_) => {
+                warn!("Drain timeout reached with requests in flight; closing their connections.");
+                Drain::TimedOut
+            }
+        };
+        let tasks = self.wait_for_tasks(deadline).await;
+
+        if requests == Drain::Complete && tasks == Drain::Complete {
+            info!("Shutdown complete.");
+            Ok(Drain::Complete)
+        } else {
+            warn!(
+                "Forced exit after the {:?} drain timeout.",
+                self.drain_timeout
+            );
+            Ok(Drain::TimedOut)
+        }
+    }
+
+    async fn wait_for_tasks(&self, deadline: Instant) -> Drain {
+        self.tasks.close();
+        if self.tasks.is_empty() {
+            return Drain::Complete;
+        }
+        info!("Waiting for {} background tasks.", self.tasks.len());
+        match timeout_at(deadline, self.tasks.wait()).await {
+            Ok(()) => {
+                info!("Background tasks finished.");
+                Drain::Complete
+            }
+            Err(_) => {
+                warn!(
+                    "{} background tasks still running at the drain timeout; exiting without them.",
+                    self.tasks.len()
+                );
+                Drain::TimedOut
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, routing::get};
+    use std::sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    };
+    use tokio::{net::TcpListener, sync::Notify, task::JoinHandle};
+
+    /// Serves a `/slow` route taking `delay`, notifying `started` when a request comes in.
+    async fn serve_slow(
+        shutdown: &Shutdown,
+        delay: Duration,
+        started: Arc<Notify>,
+    ) -> (String, JoinHandle<std::io::Result<Drain>>) {
+        let app = Router::new().route(
+            "/slow",
+            get(move || async move {
+                started.notify_one();
+                tokio::time::sleep(delay).await;
+                "done"
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/slow", listener.local_addr().unwrap());
+        let server = axum::serve(listener, app).with_graceful_shutdown(shutdown.signalled());
+        let shutdown = shutdown.clone();
+        (url, tokio::spawn(async move { shutdown.run(server).await }))
+    }
+
+    #[tokio::test]
+    async fn an_in_flight_request_completes_during_shutdown() {
+        let shutdown = Shutdown::new(Duration::from_secs(5));
+        let started = Arc::new(Notify::new());
+        let (url, server) =
+            serve_slow(&shutdown, Duration::from_millis(300), started.clone()).await;
+
+        let request = tokio::spawn(reqwest::get(url.clone()));
+        started.notified().await;
+        shutdown.trigger();
+
+        let response = request.await.unwrap().unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.text().await.unwrap(), "done");
+        assert_eq!(server.await.unwrap().unwrap(), Drain::Complete);
+        assert!(reqwest::get(url).await.is_err(), "accepted after shutdown");
+    }
+
+    #[tokio::test]
+    async fn tracked_tasks_are_awaited_before_exit() {
+        let shutdown = Shutdown::new(Duration::from_secs(5));
+        let (_, server) = serve_slow(&shutdown, Duration::ZERO, Arc::new(Notify::new())).await;
+        let finished = Arc::new(AtomicBool::new(false));
+        let flag = finished.clone();
+        shutdown.tasks().spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            flag.store(true, Ordering::SeqCst);
+        });
+
+        shutdown.trigger();
+        assert_eq!(server.await.unwrap().unwrap(), Drain::Complete);
+        assert!(finished.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn the_drain_timeout_forces_exit() {
+        let shutdown = Shutdown::new(Duration::from_millis(200));
+        let started = Arc::new(Notify::new());
+        let (url, server) = serve_slow(&shutdown, Duration::from_secs(60), started.clone()).await;
+
+        let request = tokio::spawn(reqwest::get(url));
+        started.notified().await;
+        let begun = Instant::now();
+        shutdown.trigger();
+
+        assert_eq!(server.await.unwrap().unwrap(), Drain::TimedOut);
+        assert!(begun.elapsed() < Duration::from_secs(5));
+        request.abort();
+    }
+
+    #[test]
+    fn drain_timeout_defaults_and_rejects_nonsense() {
+        assert_eq!(parse_drain_timeout(None).unwrap(), DEFAULT_DRAIN_TIMEOUT);
+        assert_eq!(
+            parse_drain_timeout(Some(" ".to_string())).unwrap(),
+            DEFAULT_DRAIN_TIMEOUT
+        );
+        for (value, expected) in [
+            ("20", Some(20)),
+            (" 3 ", Some(3)),
+            ("0", None),
+            ("soon", None),
+        ] {
+            assert_eq!(
+                parse_drain_timeout(Some(value.to_string())).ok(),
+                expected.map(Duration::from_secs),
+                "{}",
+                value
+            );
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The diets a profile can declare, named as in `dietary_prefs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Diet {
+    Vegan,
+    Vegetarian,
+    GlutenFree,
+    LactoseFree,
+}
+
+impl Diet {
+    pub const ALL: [Diet; 4] = [
+        Diet::Vegan,
+        Diet::Vegetarian,
+        Diet::GlutenFree,
+        Diet::LactoseFree,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Diet::Vegan => "vegan",
+            Diet::Vegetarian => "vegetarian",
+            Diet::GlutenFree => "gluten_free",
+            Diet::LactoseFree => "lactose_free",
+        }
+    }
+
+    /// `None` for names no table knows, which callers skip.
+    pub fn parse(name: &str) -> Option<Diet> {
+        Diet::ALL.into_iter().find(|diet| diet.as_str() == name)
+    }
+
+    /// The known diets among `names`, in order, unknown names dropped.
+    pub fn parse_all<I, S>(names: I) -> Vec<Diet>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        names
+            .into_iter()
+            .filter_map(|name| Diet::parse(name.as_ref()))
+            .collect()
+    }
+}
+
+impl fmt::Display for Diet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip_and_match_serde() {
+        for diet in Diet::ALL {
+            assert_eq!(Diet::parse(diet.as_str()), Some(diet));
+            assert_eq!(
+                serde_json::to_value(diet).unwrap(),
+                serde_json::json!(diet.as_str())
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_names_are_skipped() {
+        assert_eq!(Diet::parse("Vegan"), None);
+        assert_eq!(
+            Diet::parse_all(["keto", "vegan", "lactose_free"]),
+            vec![Diet::Vegan, Diet::LactoseFree]
+        );
+    }
+}
@@ -0,0 +1,59 @@
+//! The food taxonomy the services share: which label tags each diet rules out, which
+//! `allergens_tags` each profile allergen means, and the ingredient synonyms the allergy
+//! checker folds onto ingredient-graph names.
+//!
+//! The tables ship in `data/taxonomy.json`. A deployment can replace them with its own
+//! file by setting [`TAXONOMY_PATH_ENV`] and calling [`load_from_env`] at startup, which
+//! rejects a file whose tags the catalog wouldn't store as written. Until then, or if it
+//! is never called, the free functions answer from the bundled tables.
+
+mod diet;
+mod taxonomy;
+
+pub use diet::Diet;
+pub use taxonomy::{Taxonomy, TaxonomyError};
+
+use std::{env, fs, path::PathBuf, sync::OnceLock};
+
+/// Path of a taxonomy file replacing the bundled one.
+pub const TAXONOMY_PATH_ENV: &str = "TAXONOMY_PATH";
+
+static TAXONOMY: OnceLock<Taxonomy> = OnceLock::new();
+
+/// Installs the taxonomy from [`TAXONOMY_PATH_ENV`], or the bundled one where it is unset.
+/// Once a taxonomy is installed, later calls return it without reading anything.
+pub fn load_from_env() -> Result<&'static Taxonomy, TaxonomyError> {
+    if let Some(taxonomy) = TAXONOMY.get() {
+        return Ok(taxonomy);
+    }
+    let loaded = match env::var(TAXONOMY_PATH_ENV) {
+        Ok(path) if !path.trim().is_empty() => {
+            let path = PathBuf::from(path);
+            let json =
+                fs::read_to_string(&path).map_err(|source| TaxonomyError::Read { path, source })?;
+            Taxonomy::parse(&json)?
+        }
+        _ => Taxonomy::bundled(),
+    };
+    Ok(TAXONOMY.get_or_init(|| loaded))
+}
+
+/// The installed taxonomy; the bundled one if [`load_from_env`] hasn't run.
+pub fn taxonomy() -> &'static Taxonomy {
+    TAXONOMY.get_or_init(Taxonomy::bundled)
+}
+
+/// See [`Taxonomy::conflicting_tags_for_diets`].
+pub fn conflicting_tags_for_diets(diets: &[Diet]) -> Vec<String> {
+    taxonomy().conflicting_tags_for_diets(diets)
+}
+
+/// See [`Taxonomy::allergen_to_tags`].
+pub fn allergen_to_tags(allergen_id: &str) -> &'static [String] {
+    taxonomy().allergen_to_tags(allergen_id)
+}
+
+/// See [`Taxonomy::canonical_ingredient`].
+pub fn canonical_ingredient(name: &str) -> &str {
+    taxonomy().canonical_ingredient(name)
+}
@@ -0,0 +1,343 @@
+use crate::Diet;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    path::PathBuf,
+};
+use thiserror::Error;
+use yoloeats_domain::tags::normalize_tag;
+
+#[derive(Debug, Error)]
+pub enum TaxonomyError {
+    #[error("Failed to read taxonomy file {path}: {source}")]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Invalid taxonomy data: {0}")]
+    Parse(#[from] serde_json::Error),
+
+    #[error("Unknown diet '{0}' in taxonomy")]
+    UnknownDiet(String),
+
+    #[error("Taxonomy has no tags for diet '{0}'")]
+    MissingDiet(Diet),
+
+    #[error("Taxonomy tag '{tag}' is not normalized (expected '{expected}')")]
+    UnnormalizedTag { tag: String, expected: String },
+
+    #[error("Ingredient synonym '{name}' is not a lowercase, trimmed name")]
+    InvalidSynonym { name: String },
+}
+
+/// The file's shape; [`Taxonomy::parse`] checks it before anything uses it.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaxonomyFile {
+    diets: BTreeMap<String, Vec<String>>,
+    allergens: BTreeMap<String, Vec<String>>,
+    ingredient_synonyms: BTreeMap<String, String>,
+}
+
+/// Diet and allergen tags plus the ingredient synonyms. Every tag is already in the form
+/// the catalog stores (see [`normalize_tag`]), so it can be matched against products as is.
+#[derive(Debug, Clone)]
+pub struct Taxonomy {
+    diets: HashMap<Diet, Vec<String>>,
+    allergens: HashMap<String, Vec<String>>,
+    ingredient_synonyms: HashMap<String, String>,
+}
+
+impl Taxonomy {
+    /// The tables shipped in `data/taxonomy.json`.
+    pub fn bundled() -> Taxonomy {
+        Taxonomy::parse(include_str!("../data/taxonomy.json")).expect("bundled taxonomy is valid")
+    }
+
+    pub fn parse(json: &str) -> Result<Taxonomy, TaxonomyError> {
+        let file: TaxonomyFile = serde_json::from_str(json)?;
+
+        let mut diets = HashMap::new();
+        for (name, tags) in file.diets {
+            let diet = Diet::parse(&name).ok_or(TaxonomyError::UnknownDiet(name))?;
+            diets.insert(diet, checked_tags(tags)?);
+        }
+        if let Some(missing) = Diet::ALL.into_iter().find(|d| !diets.contains_key(d)) {
+            return Err(TaxonomyError::MissingDiet(missing));
+        }
+
+        let allergens = file
+            .allergens
+            .into_iter()
+            .map(|(id, tags)| Ok((id, checked_tags(tags)?)))
+            .collect::<Result<_, TaxonomyError>>()?;
+
+        for (name, canonical) in &file.ingredient_synonyms {
+            for name in [name, canonical] {
+                if name.trim() != name || name.to_lowercase() != *name || name.is_empty() {
+                    return Err(TaxonomyError::InvalidSynonym { name: name.clone() });
+                }
+            }
+        }
+
+        Ok(Taxonomy {
+            diets,
+            allergens,
+            ingredient_synonyms: file.ingredient_synonyms.into_iter().collect(),
+        })
+    }
+
+    /// Label tags a product following any of `diets` must not carry, sorted and deduplicated.
+    pub fn conflicting_tags_for_diets(&self, diets: &[Diet]) -> Vec<String> {
+        let mut tags: Vec<String> = diets
+            .iter()
+            .flat_map(|diet| self.diets[diet].iter().cloned())
+            .collect();
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+
+    /// The `allergens_tags` values for a profile allergen id (`milk` -> `en:milk`); empty
+    /// for ids the taxonomy doesn't know.
+    pub fn allergen_to_tags(&self, allergen_id: &str) -> &[String] {
+        self.allergens
+            .get(allergen_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// The ingredient-graph name for a lowercase ingredient, or `name` itself when it has
+    /// no synonym.
+    pub fn canonical_ingredient<'a>(&'a self, name: &'a str) -> &'a str {
+        self.ingredient_synonyms
+            .get(name)
+            .map(String::as_str)
+            .unwrap_or(name)
+    }
+
+    /// Every profile allergen id the taxonomy maps.
+    pub fn allergen_ids(&self) -> impl Iterator<Item = &str> {
+        self.allergens.keys().map(String::as_str)
+    }
+
+    /// Every `(name, canonical)` synonym pair.
+    pub fn ingredient_synonyms(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.ingredient_synonyms
+            .iter()
+            .map(|(name, canonical)| (name.as_str(), canonical.as_str()))
+    }
+}
+
+fn checked_tags(tags: Vec<String>) -> Result<Vec<String>, TaxonomyError> {
+    for tag in &tags {
+        let expected = normalize_tag(tag).unwrap_or_default();
+        if expected != *tag {
+            return Err(TaxonomyError::UnnormalizedTag {
+                tag: tag.clone(),
+                expected,
+            });
+        }
+    }
+    Ok(tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yoloeats_domain::{
+        ingredient_graph::{INGREDIENT_ALLERGENS, INGREDIENT_DIET_CONFLICTS},
+        tags::KNOWN_ALLERGENS,
+    };
+
+    /// The ids of the profile service's allergen list.
+    const PROFILE_ALLERGEN_IDS: [&str; 14] = [
+        "gluten",
+        "crustaceans",
+        "eggs",
+        "fish",
+        "peanuts",
+        "soybeans",
+        "milk",
+        "nuts",
+        "celery",
+        "mustard",
+        "sesame",
+        "sulphites",
+        "lupin",
+        "molluscs",
+    ];
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn every_diet_has_its_conflicting_tags() {
+        let taxonomy = Taxonomy::bundled();
+        let cases: [(Diet, &[&str]); 4] = [
+            (
+                Diet::Vegan,
+                &[
+                    "en:contains-eggs",
+                    "en:contains-fish",
+                    "en:contains-honey",
+                    "en:contains-meat",
+                    "en:contains-milk",
+                    "en:dairy",
+                    "en:eggs",
+                    "en:fish",
+                    "en:honey",
+                    "en:meat",
+                    "en:non-vegan",
+                    "en:non-vegetarian",
+                    "en:vegetarian-status-unknown",
+                ],
+            ),
+            (
+                Diet::Vegetarian,
+                &[
+                    "en:contains-fish",
+                    "en:contains-meat",
+                    "en:fish",
+                    "en:meat",
+                    "en:non-vegetarian",
+                    "en:vegetarian-status-unknown",
+                ],
+            ),
+            (Diet::GlutenFree, &["en:contains-gluten", "en:gluten"]),
+            (Diet::LactoseFree, &["en:contains-milk", "en:dairy"]),
+        ];
+        for (diet, expected) in cases {
+            assert_eq!(
+                taxonomy.conflicting_tags_for_diets(&[diet]),
+                strings(expected),
+                "{}",
+                diet
+            );
+        }
+    }
+
+    #[test]
+    fn combined_diets_are_merged_sorted_and_deduplicated() {
+        let taxonomy = Taxonomy::bundled();
+        assert_eq!(
+            taxonomy.conflicting_tags_for_diets(&[Diet::LactoseFree, Diet::GlutenFree]),
+            strings(&[
+                "en:contains-gluten",
+                "en:contains-milk",
+                "en:dairy",
+                "en:gluten"
+            ])
+        );
+        // Everything a vegetarian avoids, a vegan avoids too.
+        assert_eq!(
+            taxonomy.conflicting_tags_for_diets(&[Diet::Vegan, Diet::Vegetarian]),
+            taxonomy.conflicting_tags_for_diets(&[Diet::Vegan])
+        );
+        assert!(taxonomy.conflicting_tags_for_diets(&[]).is_empty());
+    }
+
+    #[test]
+    fn every_profile_allergen_maps_to_known_allergen_tags() {
+        let taxonomy = Taxonomy::bundled();
+        let expected = [
+            ("gluten", "en:gluten"),
+            ("crustaceans", "en:crustaceans"),
+            ("eggs", "en:eggs"),
+            ("fish", "en:fish"),
+            ("peanuts", "en:peanuts"),
+            ("soybeans", "en:soybeans"),
+            ("milk", "en:milk"),
+            ("nuts", "en:nuts"),
+            ("celery", "en:celery"),
+            ("mustard", "en:mustard"),
+            ("sesame", "en:sesame-seeds"),
+            ("sulphites", "en:sulphur-dioxide-and-sulphites"),
+            ("lupin", "en:lupin"),
+            ("molluscs", "en:molluscs"),
+        ];
+        for (id, tag) in expected {
+            assert_eq!(taxonomy.allergen_to_tags(id), [tag.to_string()], "{}", id);
+            assert!(KNOWN_ALLERGENS.contains(&tag), "{}", tag);
+        }
+        let mut ids: Vec<&str> = taxonomy.allergen_ids().collect();
+        ids.sort();
+        let mut profile_ids = PROFILE_ALLERGEN_IDS.to_vec();
+        profile_ids.sort();
+        assert_eq!(ids, profile_ids);
+        assert!(taxonomy.allergen_to_tags("en:milk").is_empty());
+    }
+
+    #[test]
+    fn every_emitted_tag_is_in_the_catalogs_normal_form() {
+        let taxonomy = Taxonomy::bundled();
+        let tags = taxonomy
+            .conflicting_tags_for_diets(&Diet::ALL)
+            .into_iter()
+            .chain(
+                taxonomy
+                    .allergen_ids()
+                    .flat_map(|id| taxonomy.allergen_to_tags(id).to_vec()),
+            );
+        for tag in tags {
+            assert_eq!(normalize_tag(&tag).as_deref(), Some(tag.as_str()));
+        }
+    }
+
+    #[test]
+    fn synonyms_point_at_ingredient_graph_nodes() {
+        let taxonomy = Taxonomy::bundled();
+        let nodes: Vec<&str> = INGREDIENT_ALLERGENS
+            .iter()
+            .chain(INGREDIENT_DIET_CONFLICTS)
+            .map(|(ingredient, _)| *ingredient)
+            .collect();
+        for (name, canonical) in taxonomy.ingredient_synonyms() {
+            assert!(nodes.contains(&canonical), "{} -> {}", name, canonical);
+            assert!(!nodes.contains(&name), "{} is itself a node", name);
+        }
+        assert_eq!(taxonomy.canonical_ingredient("skimmed milk"), "milk");
+        assert_eq!(taxonomy.canonical_ingredient("sugar"), "sugar");
+    }
+
+    #[test]
+    fn invalid_data_is_rejected() {
+        let parse = |diets: &str, allergens: &str, synonyms: &str| {
+            Taxonomy::parse(&format!(
+                r#"{{"diets": {}, "allergens": {}, "ingredient_synonyms": {}}}"#,
+                diets, allergens, synonyms
+            ))
+        };
+        let diets = r#"{"vegan": [], "vegetarian": [], "gluten_free": [], "lactose_free": []}"#;
+        assert!(parse(diets, "{}", "{}").is_ok());
+
+        assert!(matches!(
+            parse(r#"{"vegan": []}"#, "{}", "{}"),
+            Err(TaxonomyError::MissingDiet(Diet::Vegetarian))
+        ));
+        assert!(matches!(
+            parse(
+                r#"{"vegan": [], "vegetarian": [], "gluten_free": [], "lactose_free": [], "keto": []}"#,
+                "{}",
+                "{}"
+            ),
+            Err(TaxonomyError::UnknownDiet(name)) if name == "keto"
+        ));
+        assert!(matches!(
+            parse(diets, r#"{"milk": ["en:Milk"]}"#, "{}"),
+            Err(TaxonomyError::UnnormalizedTag { expected, .. }) if expected == "en:milk"
+        ));
+        assert!(matches!(
+            parse(diets, "{}", r#"{"Whole Milk": "milk"}"#),
+            Err(TaxonomyError::InvalidSynonym { .. })
+        ));
+        assert!(matches!(
+            Taxonomy::parse("{}"),
+            Err(TaxonomyError::Parse(_))
+        ));
+    }
+}
@@ -0,0 +1,197 @@
+use crate::request_id::{REQUEST_ID_HEADER, RequestId, with_request_id};
+use http::{Request, Response};
+use opentelemetry::{global, trace::TraceContextExt};
+use opentelemetry_http::HeaderExtractor;
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tower::{Layer, Service};
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send + 'static>>;
+
+/// Tower layer assigning every request a [`RequestId`]. The id is written back into the
+/// request headers and extensions, scoped as the task's current id, recorded on a
+/// `request` span, and set on the response. An incoming W3C `traceparent` becomes the
+/// span's parent, and the span's `trace_id` field names the trace it belongs to: the
+/// caller's, or a new one when the call brought none.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RequestIdLayer;
+
+impl<S> Layer<S> for RequestIdLayer {
+    type Service = RequestIdService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestIdService { inner }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RequestIdService<S> {
+    inner: S,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for RequestIdService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: 'static,
+    ResBody: 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<Result<Response<ResBody>, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<ReqBody>) -> Self::Future {
+        let id = RequestId::from_headers(request.headers());
+        request
+            .headers_mut()
+            .insert(REQUEST_ID_HEADER, id.header_value());
+        request.extensions_mut().insert(id.clone());
+
+        let span = tracing::info_span!(
+            "request",
+            request_id = %id,
+            method = %request.method(),
+            path = %request.uri().path(),
+            trace_id = tracing::field::Empty,
+        );
+        let parent = global::get_text_map_propagator(|propagator| {
+            propagator.extract(&HeaderExtractor(request.headers()))
+        });
+        let incoming = parent.span().span_context().clone();
+        span.set_parent(parent);
+        let trace = if incoming.is_valid() {
+            incoming
+        } else {
+            span.context().span().span_context().clone()
+        };
+        if trace.is_valid() {
+            span.record("trace_id", tracing::field::display(trace.trace_id()));
+        }
+        let response = self.inner.call(request);
+
+        Box::pin(with_request_id(
+            id.clone(),
+            async move {
+                let mut response = response.await?;
+                response
+                    .headers_mut()
+                    .insert(REQUEST_ID_HEADER, id.header_value());
+                Ok(response)
+            }
+            .instrument(span),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{logging::tests::Captured, request_id::current_request_id};
+    use axum::{Router, body::Body, extract::Extension, routing::get};
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use tower::ServiceExt;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/",
+                get(|Extension(id): Extension<RequestId>| async move {
+                    // The extension and the task-local agree.
+                    assert_eq!(current_request_id(), Some(id.clone()));
+                    id.to_string()
+                }),
+            )
+            .layer(RequestIdLayer)
+    }
+
+    async fn call(request: Request<Body>) -> (String, String) {
+        let response = app().oneshot(request).await.unwrap();
+        let header = response.headers()[REQUEST_ID_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (header, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn generates_an_id_when_absent() {
+        let (header, seen) = call(Request::get("/").body(Body::empty()).unwrap()).await;
+        assert_eq!(header, seen);
+        assert!(uuid::Uuid::parse_str(&header).is_ok());
+    }
+
+    #[tokio::test]
+    async fn passes_a_valid_id_through() {
+        let request = Request::get("/")
+            .header(REQUEST_ID_HEADER, "client-abc-123")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(
+            call(request).await,
+            ("client-abc-123".to_string(), "client-abc-123".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn replaces_a_hostile_id() {
+        let hostile = format!("{}\"><script>", "x".repeat(300));
+        let request = Request::get("/")
+            .header(REQUEST_ID_HEADER, hostile.as_str())
+            .body(Body::empty())
+            .unwrap();
+        let (header, seen) = call(request).await;
+        assert_ne!(header, hostile);
+        assert_eq!(header, seen);
+        assert!(uuid::Uuid::parse_str(&header).is_ok());
+    }
+
+    #[tokio::test]
+    async fn an_incoming_traceparent_names_the_trace_of_the_request_span() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let captured = Captured::default();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(captured.clone()),
+        );
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        let app = Router::new()
+            .route(
+                "/",
+                get(|| async {
+                    tracing::info!("Handling the request");
+                }),
+            )
+            .layer(RequestIdLayer);
+        let request = Request::get("/")
+            .header(
+                "traceparent",
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            )
+            .body(Body::empty())
+            .unwrap();
+        app.oneshot(request).await.unwrap();
+
+        let logs = captured.text();
+        assert!(
+            logs.contains("trace_id=4bf92f3577b34da6a3ce929d0e0e4736")
+                && logs.contains("Handling the request"),
+            "{}",
+            logs
+        );
+    }
+}
@@ -0,0 +1,28 @@
+//! Correlation ids and tracing setup shared by the YoloEats services.
+//!
+//! [`RequestIdLayer`] reads (or creates) `X-Request-Id` on every inbound request, makes it
+//! available through [`current_request_id`] and the tracing span, and echoes it on the
+//! response. Clients built with [`http_client`] stamp the current id onto outbound calls,
+//! so handlers never have to forward it by hand.
+//!
+//! [`init_tracing`] installs the subscriber every service `main` uses. `LOG_FORMAT=json`
+//! (the default inside Kubernetes) switches stdout to one [`JsonFormat`] object per line,
+//! tagged with the service, its version and the current request id. The layer and client
+//! above also carry W3C trace context across service calls, and with
+//! `OTEL_EXPORTER_OTLP_ENDPOINT` set the spans are exported over OTLP.
+
+mod layer;
+mod logging;
+mod outbound;
+mod request_id;
+mod telemetry;
+
+pub use layer::{RequestIdLayer, RequestIdService};
+pub use logging::{JsonFormat, LOG_FORMAT_ENV, LogFormat};
+pub use outbound::{PropagateRequestId, PropagateTraceContext, http_client};
+pub use request_id::{
+    MAX_REQUEST_ID_LEN, REQUEST_ID_HEADER, RequestId, current_request_id, with_request_id,
+};
+pub use telemetry::{
+    OTLP_ENDPOINT_ENV, SAMPLE_RATIO_ENV, TelemetryError, TelemetryGuard, init_tracing,
+};
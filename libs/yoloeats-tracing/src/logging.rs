@@ -0,0 +1,290 @@
+use crate::request_id::current_request_id;
+use crate::telemetry::TelemetryError;
+use chrono::{SecondsFormat, Utc};
+use serde_json::{Map, Value};
+use std::{env, fmt, str::FromStr};
+use tracing::{
+    Event, Subscriber,
+    field::{Field, Visit},
+};
+use tracing_subscriber::{
+    fmt::{FmtContext, FormatEvent, FormatFields, format::Writer},
+    registry::LookupSpan,
+};
+
+/// `json` or `pretty`. Unset means json inside Kubernetes, pretty everywhere else.
+pub const LOG_FORMAT_ENV: &str = "LOG_FORMAT";
+/// Set by Kubernetes in every pod.
+const KUBERNETES_ENV: &str = "KUBERNETES_SERVICE_HOST";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// `tracing_subscriber`'s human-readable lines.
+    Pretty,
+    /// One [`JsonFormat`] object per line, for log aggregation.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = TelemetryError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(TelemetryError::InvalidLogFormat(value.to_string())),
+        }
+    }
+}
+
+impl LogFormat {
+    pub fn from_env() -> Result<Self, TelemetryError> {
+        Self::resolve(
+            env::var(LOG_FORMAT_ENV).ok().as_deref(),
+            env::var_os(KUBERNETES_ENV).is_some(),
+        )
+    }
+
+    fn resolve(configured: Option<&str>, in_kubernetes: bool) -> Result<Self, TelemetryError> {
+        match configured {
+            Some(value) if !value.trim().is_empty() => value.parse(),
+            _ if in_kubernetes => Ok(LogFormat::Json),
+            _ => Ok(LogFormat::Pretty),
+        }
+    }
+}
+
+/// Event fields as JSON values; numbers and booleans keep their type.
+struct JsonFields(Map<String, Value>);
+
+impl Visit for JsonFields {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(
+            field.name().to_string(),
+            Value::from(format!("{:?}", value)),
+        );
+    }
+}
+
+fn now_rfc3339() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+/// One JSON object per event, with the event's fields flattened to the top level next to
+/// `timestamp` (RFC 3339, UTC), `level`, `target`, `service`, `version`, the innermost
+/// `span` name and, inside a request, its `request_id`.
+///
+/// `fmt::layer().json()` can't add the static service fields, and only sees the request
+/// id when the request span happens to be the innermost one, hence the custom format.
+pub struct JsonFormat {
+    service: &'static str,
+    version: &'static str,
+    clock: fn() -> String,
+}
+
+impl JsonFormat {
+    pub fn new(service: &'static str, version: &'static str) -> Self {
+        JsonFormat {
+            service,
+            version,
+            clock: now_rfc3339,
+        }
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut fields = JsonFields(Map::new());
+        event.record(&mut fields);
+        let mut line = fields.0;
+
+        // Written last so an event field can't shadow them.
+        let metadata = event.metadata();
+        line.insert("timestamp".into(), Value::from((self.clock)()));
+        line.insert("level".into(), Value::from(metadata.level().as_str()));
+        line.insert("target".into(), Value::from(metadata.target()));
+        line.insert("service".into(), Value::from(self.service));
+        line.insert("version".into(), Value::from(self.version));
+        if let Some(span) = ctx.lookup_current() {
+            line.insert("span".into(), Value::from(span.name()));
+        }
+        if let Some(id) = current_request_id() {
+            line.insert("request_id".into(), Value::from(id.as_str()));
+        }
+
+        let json = serde_json::to_string(&line).map_err(|_| fmt::Error)?;
+        writeln!(writer, "{}", json)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::request_id::{RequestId, with_request_id};
+    use serde_json::json;
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+    use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt};
+
+    #[test]
+    fn format_follows_the_environment() {
+        assert_eq!(LogFormat::resolve(None, false).unwrap(), LogFormat::Pretty);
+        assert_eq!(LogFormat::resolve(None, true).unwrap(), LogFormat::Json);
+        assert_eq!(LogFormat::resolve(Some(""), true).unwrap(), LogFormat::Json);
+        assert_eq!(
+            LogFormat::resolve(Some("pretty"), true).unwrap(),
+            LogFormat::Pretty,
+            "explicit wins"
+        );
+        assert_eq!(
+            LogFormat::resolve(Some(" JSON "), false).unwrap(),
+            LogFormat::Json
+        );
+        assert!(matches!(
+            LogFormat::resolve(Some("xml"), false),
+            Err(TelemetryError::InvalidLogFormat(value)) if value == "xml"
+        ));
+    }
+
+    /// Everything written to it, for asserting on log output.
+    #[derive(Clone, Default)]
+    pub(crate) struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Captured {
+        type Writer = Captured;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    impl Captured {
+        pub(crate) fn text(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+
+        fn lines(&self) -> Vec<Value> {
+            self.text()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        }
+    }
+
+    fn subscriber(captured: &Captured) -> impl Subscriber + Send + Sync {
+        let format = JsonFormat {
+            clock: || "2025-05-01T12:00:00.000000Z".to_string(),
+            ..JsonFormat::new("product-catalog-service", "1.2.3")
+        };
+        tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .event_format(format)
+                .with_writer(captured.clone()),
+        )
+    }
+
+    #[tokio::test]
+    async fn events_in_a_request_carry_its_id_and_flattened_fields() {
+        let captured = Captured::default();
+        let _default = tracing::subscriber::set_default(subscriber(&captured));
+
+        let id = RequestId::parse("req-7f3a").unwrap();
+        with_request_id(id, async {
+            tracing::info_span!("get_product_by_barcode").in_scope(|| {
+                tracing::info!(
+                    code = "4000417025005",
+                    cached = false,
+                    elapsed_ms = 12u64,
+                    "Product found in DB by barcode"
+                );
+            });
+        })
+        .await;
+
+        assert_eq!(
+            captured.lines(),
+            vec![json!({
+                "timestamp": "2025-05-01T12:00:00.000000Z",
+                "level": "INFO",
+                "target": "yoloeats_tracing::logging::tests",
+                "service": "product-catalog-service",
+                "version": "1.2.3",
+                "span": "get_product_by_barcode",
+                "request_id": "req-7f3a",
+                "message": "Product found in DB by barcode",
+                "code": "4000417025005",
+                "cached": false,
+                "elapsed_ms": 12,
+            })]
+        );
+    }
+
+    #[test]
+    fn events_outside_requests_have_no_request_fields() {
+        let captured = Captured::default();
+        let _default = tracing::subscriber::set_default(subscriber(&captured));
+
+        tracing::warn!(service = "spoofed", error = %"connection refused", "Startup check failed");
+
+        assert_eq!(
+            captured.lines(),
+            vec![json!({
+                "timestamp": "2025-05-01T12:00:00.000000Z",
+                "level": "WARN",
+                "target": "yoloeats_tracing::logging::tests",
+                "service": "product-catalog-service",
+                "version": "1.2.3",
+                "message": "Startup check failed",
+                "error": "connection refused",
+            })]
+        );
+    }
+
+    #[test]
+    fn real_timestamps_are_rfc3339_utc() {
+        let timestamp = now_rfc3339();
+        assert!(timestamp.ends_with('Z'), "{}", timestamp);
+        assert!(chrono::DateTime::parse_from_rfc3339(&timestamp).is_ok());
+    }
+}
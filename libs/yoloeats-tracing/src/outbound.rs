@@ -0,0 +1,178 @@
+use crate::request_id::{REQUEST_ID_HEADER, current_request_id};
+use http::Extensions;
+use opentelemetry::global;
+use opentelemetry_http::HeaderInjector;
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware, Middleware, Next};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// `reqwest` middleware stamping the current request id onto outbound calls. An
+/// explicitly set `X-Request-Id` on the outgoing request is left untouched.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PropagateRequestId;
+
+#[async_trait::async_trait]
+impl Middleware for PropagateRequestId {
+    async fn handle(
+        &self,
+        mut request: reqwest::Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<reqwest::Response> {
+        if let Some(id) = current_request_id() {
+            request
+                .headers_mut()
+                .entry(REQUEST_ID_HEADER)
+                .or_insert_with(|| id.header_value());
+        }
+        next.run(request, extensions).await
+    }
+}
+
+/// `reqwest` middleware injecting the current span as a W3C `traceparent`, so the callee's
+/// spans join the caller's trace. A no-op outside a span, or before
+/// [`init_tracing`](crate::init_tracing) has installed the propagator.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PropagateTraceContext;
+
+#[async_trait::async_trait]
+impl Middleware for PropagateTraceContext {
+    async fn handle(
+        &self,
+        mut request: reqwest::Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<reqwest::Response> {
+        let context = tracing::Span::current().context();
+        global::get_text_map_propagator(|propagator| {
+            propagator.inject_context(&context, &mut HeaderInjector(request.headers_mut()))
+        });
+        next.run(request, extensions).await
+    }
+}
+
+/// Wraps `client` so every call made through it carries the current request id and trace
+/// context.
+pub fn http_client(client: reqwest::Client) -> ClientWithMiddleware {
+    ClientBuilder::new(client)
+        .with(PropagateRequestId)
+        .with(PropagateTraceContext)
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        request_id::{RequestId, with_request_id},
+        telemetry::local_provider,
+    };
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use std::collections::HashMap;
+    use tracing::Instrument;
+    use tracing_subscriber::layer::SubscriberExt;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{header, method},
+    };
+
+    #[tokio::test]
+    async fn stamps_current_id_on_outbound_calls() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(header(REQUEST_ID_HEADER, "outbound-1"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = http_client(reqwest::Client::new());
+        let id = RequestId::parse("outbound-1").unwrap();
+        let response = with_request_id(id, client.get(server.uri()).send())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn leaves_calls_outside_a_request_alone() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        http_client(reqwest::Client::new())
+            .get(server.uri())
+            .send()
+            .await
+            .unwrap();
+        let received = server.received_requests().await.unwrap();
+        assert!(!received[0].headers.contains_key(REQUEST_ID_HEADER));
+    }
+
+    #[tokio::test]
+    async fn explicit_header_wins() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(header(REQUEST_ID_HEADER, "explicit"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = http_client(reqwest::Client::new());
+        let id = RequestId::parse("ambient").unwrap();
+        with_request_id(
+            id,
+            client
+                .get(server.uri())
+                .header(REQUEST_ID_HEADER, "explicit")
+                .send(),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn outbound_calls_carry_the_current_trace() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(local_provider().tracer("test")));
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        let incoming = HashMap::from([(
+            "traceparent".to_string(),
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".to_string(),
+        )]);
+        let span = tracing::info_span!("request");
+        span.set_parent(global::get_text_map_propagator(|propagator| {
+            propagator.extract(&incoming)
+        }));
+        http_client(reqwest::Client::new())
+            .get(server.uri())
+            .send()
+            .instrument(span)
+            .await
+            .unwrap();
+
+        let received = server.received_requests().await.unwrap();
+        let traceparent = received[0].headers["traceparent"].to_str().unwrap();
+        let parts: Vec<&str> = traceparent.split('-').collect();
+        assert_eq!(
+            parts[1], "4bf92f3577b34da6a3ce929d0e0e4736",
+            "{}",
+            traceparent
+        );
+        assert_ne!(
+            parts[2], "00f067aa0ba902b7",
+            "the caller's own span is the parent"
+        );
+        assert_eq!(parts[3], "01", "the caller's sampling decision is kept");
+    }
+}
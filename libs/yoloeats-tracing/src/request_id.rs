@@ -0,0 +1,122 @@
+use http::{HeaderMap, HeaderValue};
+use std::{fmt, future::Future, sync::Arc};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longer incoming ids are replaced; 128 fits any UUID/ULID/trace-id style value.
+pub const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static CURRENT_REQUEST_ID: RequestId;
+}
+
+/// A validated correlation id. Only contains `[A-Za-z0-9._:-]`, so it is always a valid
+/// header value and safe to log.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RequestId(Arc<str>);
+
+impl RequestId {
+    pub fn generate() -> Self {
+        RequestId(Uuid::new_v4().to_string().into())
+    }
+
+    /// Accepts `value` if it is non-empty, at most [`MAX_REQUEST_ID_LEN`] bytes and made of
+    /// safe characters.
+    pub fn parse(value: &str) -> Option<Self> {
+        let valid = !value.is_empty()
+            && value.len() <= MAX_REQUEST_ID_LEN
+            && value
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b':'));
+        valid.then(|| RequestId(value.into()))
+    }
+
+    /// The incoming id when it is acceptable, otherwise a fresh one.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        match headers.get(REQUEST_ID_HEADER) {
+            None => RequestId::generate(),
+            Some(value) => match value.to_str().ok().and_then(RequestId::parse) {
+                Some(id) => id,
+                None => {
+                    // Don't echo the rejected value into logs; it is attacker-controlled.
+                    tracing::warn!(
+                        len = value.len(),
+                        "Ignoring malformed X-Request-Id header, generating a new id"
+                    );
+                    RequestId::generate()
+                }
+            },
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn header_value(&self) -> HeaderValue {
+        HeaderValue::from_str(&self.0).expect("request ids only contain header-safe characters")
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The id of the request being handled by the current task, if any.
+///
+/// Task-local: work moved onto `tokio::spawn` must be wrapped in [`with_request_id`] to keep it.
+pub fn current_request_id() -> Option<RequestId> {
+    CURRENT_REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Runs `future` with `id` as the current request id.
+pub async fn with_request_id<F: Future>(id: RequestId, future: F) -> F::Output {
+    CURRENT_REQUEST_ID.scope(id, future).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_common_id_formats() {
+        for id in [
+            "3f2b8c1e-4d5a-4e6f-8a9b-0c1d2e3f4a5b",
+            "01HZX5Q6M8K9N0P1R2S3T4V5W6",
+            "req_123.attempt:2",
+        ] {
+            assert_eq!(RequestId::parse(id).unwrap().as_str(), id);
+        }
+    }
+
+    #[test]
+    fn rejects_hostile_or_absurd_values() {
+        assert!(RequestId::parse("").is_none());
+        assert!(RequestId::parse("abc\r\nset-cookie: x=1").is_none());
+        assert!(RequestId::parse("id with spaces").is_none());
+        assert!(RequestId::parse("<script>").is_none());
+        assert!(RequestId::parse(&"a".repeat(MAX_REQUEST_ID_LEN + 1)).is_none());
+        assert!(RequestId::parse(&"a".repeat(MAX_REQUEST_ID_LEN)).is_some());
+    }
+
+    #[test]
+    fn from_headers_replaces_malformed_values() {
+        let mut headers = HeaderMap::new();
+        headers.insert(REQUEST_ID_HEADER, HeaderValue::from_static("not valid!"));
+        let id = RequestId::from_headers(&headers);
+        assert_ne!(id.as_str(), "not valid!");
+        assert!(Uuid::parse_str(id.as_str()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn current_id_is_scoped_to_the_task() {
+        assert_eq!(current_request_id(), None);
+        let id = RequestId::parse("scoped-1").unwrap();
+        let seen = with_request_id(id.clone(), async { current_request_id() }).await;
+        assert_eq!(seen, Some(id));
+        assert_eq!(current_request_id(), None);
+    }
+}
@@ -0,0 +1,257 @@
+use crate::logging::{JsonFormat, LOG_FORMAT_ENV, LogFormat};
+use opentelemetry::{global, trace::TracerProvider as _};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{
+    Resource,
+    propagation::TraceContextPropagator,
+    trace::{Sampler, SdkTracerProvider},
+};
+use std::env;
+use thiserror::Error;
+use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt};
+
+/// Export is enabled exactly when this is set, e.g. `http://tempo:4317`.
+pub const OTLP_ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+/// Fraction of new traces to sample, `0.0..=1.0`; defaults to `1.0`. Calls that arrive
+/// with a sampled `traceparent` are always kept so traces don't break mid-flight.
+pub const SAMPLE_RATIO_ENV: &str = "OTEL_TRACES_SAMPLER_ARG";
+
+#[derive(Error, Debug)]
+pub enum TelemetryError {
+    #[error("Invalid {SAMPLE_RATIO_ENV} '{0}': expected a number between 0 and 1")]
+    InvalidSampleRatio(String),
+
+    #[error("Invalid {LOG_FORMAT_ENV} '{0}': expected 'json' or 'pretty'")]
+    InvalidLogFormat(String),
+
+    #[error("Failed to build OTLP span exporter: {0}")]
+    Exporter(#[from] opentelemetry_otlp::ExporterBuildError),
+
+    #[error("Failed to install tracing subscriber: {0}")]
+    Subscriber(#[from] tracing_subscriber::util::TryInitError),
+}
+
+/// Keeps the trace pipeline alive; dropping it flushes and shuts down the exporter.
+/// Hold it in `main` for the lifetime of the process.
+#[must_use = "dropping the guard shuts down trace export"]
+pub struct TelemetryGuard {
+    provider: Option<SdkTracerProvider>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        let Some(provider) = self.provider.take() else {
+            return;
+        };
+        if let Err(e) = provider.shutdown() {
+            eprintln!("Failed to flush OTLP spans on shutdown: {}", e);
+        }
+    }
+}
+
+/// Installs the global subscriber: `RUST_LOG`-filtered stdout logs (default `info`) in the
+/// [`LogFormat`] chosen by [`LOG_FORMAT_ENV`], and W3C `traceparent` propagation for
+/// [`RequestIdLayer`](crate::RequestIdLayer) and [`http_client`](crate::http_client).
+/// When [`OTLP_ENDPOINT_ENV`] is set, spans are also exported over OTLP, tagged with
+/// `service.name`; without it traces are still continued and passed on, just never
+/// sampled here.
+///
+/// Pass `env!("CARGO_PKG_VERSION")` as `version`; JSON logs carry it on every line.
+pub fn init_tracing(
+    service_name: &'static str,
+    version: &'static str,
+) -> Result<TelemetryGuard, TelemetryError> {
+    let log_format = LogFormat::from_env()?;
+    let endpoint = env::var(OTLP_ENDPOINT_ENV)
+        .ok()
+        .filter(|endpoint| !endpoint.trim().is_empty());
+    let provider = match &endpoint {
+        Some(endpoint) => otlp_provider(service_name, endpoint.trim(), sample_ratio()?)?,
+        None => local_provider(),
+    };
+    let otel_layer = tracing_opentelemetry::layer().with_tracer(provider.tracer(service_name));
+
+    tracing_subscriber::registry()
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()))
+        .with((log_format == LogFormat::Pretty).then(fmt::layer))
+        .with(
+            (log_format == LogFormat::Json)
+                .then(|| fmt::layer().event_format(JsonFormat::new(service_name, version))),
+        )
+        .with(otel_layer)
+        .try_init()?;
+
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    global::set_tracer_provider(provider.clone());
+    if let Some(endpoint) = &endpoint {
+        tracing::info!(
+            service = service_name,
+            endpoint = %endpoint.trim(),
+            "OTLP trace export enabled"
+        );
+    }
+
+    Ok(TelemetryGuard {
+        provider: Some(provider),
+    })
+}
+
+fn sample_ratio() -> Result<f64, TelemetryError> {
+    match env::var(SAMPLE_RATIO_ENV) {
+        Err(_) => Ok(1.0),
+        Ok(raw) => parse_sample_ratio(&raw),
+    }
+}
+
+fn parse_sample_ratio(raw: &str) -> Result<f64, TelemetryError> {
+    raw.trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|ratio| (0.0..=1.0).contains(ratio))
+        .ok_or_else(|| TelemetryError::InvalidSampleRatio(raw.to_string()))
+}
+
+fn otlp_provider(
+    service_name: &'static str,
+    endpoint: &str,
+    ratio: f64,
+) -> Result<SdkTracerProvider, TelemetryError> {
+    let exporter = SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()?;
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            ratio,
+        ))))
+        .with_resource(Resource::builder().with_service_name(service_name).build())
+        .build())
+}
+
+/// Gives spans trace ids, the caller's or new ones, so they can be logged and forwarded,
+/// without recording anything: a trace the caller sampled stays sampled downstream, but
+/// nothing is started here that no one would ever export.
+pub(crate) fn local_provider() -> SdkTracerProvider {
+    SdkTracerProvider::builder()
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::AlwaysOff)))
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RequestIdLayer, http_client};
+    use axum::{Router, body::Body, routing::get};
+    use http::Request;
+    use opentelemetry::trace::SpanId;
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SpanData};
+    use std::time::Duration;
+    use tower::ServiceExt;
+    use tracing::Instrument;
+
+    #[test]
+    fn sample_ratio_must_be_a_fraction() {
+        assert_eq!(parse_sample_ratio("0.25").unwrap(), 0.25);
+        assert_eq!(parse_sample_ratio(" 1 ").unwrap(), 1.0);
+        assert!(parse_sample_ratio("1.5").is_err());
+        assert!(parse_sample_ratio("-0.1").is_err());
+        assert!(parse_sample_ratio("half").is_err());
+    }
+
+    async fn serve(router: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    /// The downstream spans close on the server task, possibly after the caller already
+    /// has its response.
+    async fn finished_spans(exporter: &InMemorySpanExporter, expected: usize) -> Vec<SpanData> {
+        for _ in 0..50 {
+            let spans = exporter.get_finished_spans().unwrap();
+            if spans.len() >= expected {
+                return spans;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        exporter.get_finished_spans().unwrap()
+    }
+
+    fn span<'a>(spans: &'a [SpanData], name: &str, parent: SpanId) -> &'a SpanData {
+        spans
+            .iter()
+            .find(|s| s.name == name && s.parent_span_id == parent)
+            .unwrap_or_else(|| panic!("no '{}' span under {:?} in {:#?}", name, parent, spans))
+    }
+
+    #[tokio::test]
+    async fn trace_context_crosses_service_boundaries() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        // Stand-in for the profile service.
+        let downstream_url = serve(
+            Router::new()
+                .route(
+                    "/profile",
+                    get(|| async { tracing::info_span!("profile.handler").in_scope(|| "ok") }),
+                )
+                .layer(RequestIdLayer),
+        )
+        .await;
+
+        // Stand-in for the catalog, calling the profile service while handling a request.
+        let client = http_client(reqwest::Client::new());
+        let upstream = Router::new()
+            .route(
+                "/recommendations",
+                get(move || async move {
+                    client
+                        .get(format!("{}/profile", downstream_url))
+                        .send()
+                        .instrument(tracing::info_span!("catalog.fetch_profile"))
+                        .await
+                        .unwrap()
+                        .status()
+                }),
+            )
+            .layer(RequestIdLayer);
+
+        let response = upstream
+            .oneshot(
+                Request::get("/recommendations")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        let spans = finished_spans(&exporter, 4).await;
+        let catalog_request = span(&spans, "request", SpanId::INVALID);
+        let fetch = span(
+            &spans,
+            "catalog.fetch_profile",
+            catalog_request.span_context.span_id(),
+        );
+        let profile_request = span(&spans, "request", fetch.span_context.span_id());
+        let profile_handler = span(
+            &spans,
+            "profile.handler",
+            profile_request.span_context.span_id(),
+        );
+
+        let trace_id = catalog_request.span_context.trace_id();
+        for s in [fetch, profile_request, profile_handler] {
+            assert_eq!(s.span_context.trace_id(), trace_id);
+        }
+    }
+}
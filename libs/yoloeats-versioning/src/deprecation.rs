@@ -0,0 +1,253 @@
+use axum::{
+    body::Body,
+    http::{HeaderName, HeaderValue, Request},
+    response::Response,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use std::{
+    env,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use thiserror::Error;
+use tower::{Layer, Service};
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send + 'static>>;
+
+/// When `/api/v1` was deprecated; unset means it isn't.
+pub const API_V1_DEPRECATED_AT_ENV: &str = "API_V1_DEPRECATED_AT";
+/// When `/api/v1` stops being served; unset means no date has been set.
+pub const API_V1_SUNSET_AT_ENV: &str = "API_V1_SUNSET_AT";
+
+const DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+const SUNSET: HeaderName = HeaderName::from_static("sunset");
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DeprecationConfigError {
+    #[error("Invalid {var} '{value}': expected an RFC 3339 date-time or a YYYY-MM-DD date")]
+    InvalidDate { var: &'static str, value: String },
+
+    #[error("The sunset ({sunset_at}) must not come before the deprecation ({deprecated_at})")]
+    SunsetBeforeDeprecation {
+        deprecated_at: DateTime<Utc>,
+        sunset_at: DateTime<Utc>,
+    },
+}
+
+/// A version's retirement dates. The default announces nothing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Deprecation {
+    pub deprecated_at: Option<DateTime<Utc>>,
+    pub sunset_at: Option<DateTime<Utc>>,
+}
+
+impl Deprecation {
+    pub fn new(
+        deprecated_at: Option<DateTime<Utc>>,
+        sunset_at: Option<DateTime<Utc>>,
+    ) -> Result<Self, DeprecationConfigError> {
+        match (deprecated_at, sunset_at) {
+            (Some(deprecated_at), Some(sunset_at)) if sunset_at < deprecated_at => {
+                Err(DeprecationConfigError::SunsetBeforeDeprecation {
+                    deprecated_at,
+                    sunset_at,
+                })
+            }
+            _ => Ok(Deprecation {
+                deprecated_at,
+                sunset_at,
+            }),
+        }
+    }
+
+    /// Reads both dates from the environment, e.g. [`API_V1_DEPRECATED_AT_ENV`] and
+    /// [`API_V1_SUNSET_AT_ENV`]. A bare date means midnight UTC.
+    pub fn from_env(
+        deprecated_var: &'static str,
+        sunset_var: &'static str,
+    ) -> Result<Self, DeprecationConfigError> {
+        let read = |var| match env::var(var) {
+            Ok(value) if !value.trim().is_empty() => parse_date(var, &value).map(Some),
+            _ => Ok(None),
+        };
+        Deprecation::new(read(deprecated_var)?, read(sunset_var)?)
+    }
+
+    fn headers(&self) -> Vec<(HeaderName, HeaderValue)> {
+        let mut headers = Vec::new();
+        if let Some(deprecated_at) = self.deprecated_at {
+            // RFC 9745: a structured-field date, seconds since the epoch.
+            let value = format!("@{}", deprecated_at.timestamp());
+            headers.push((DEPRECATION, HeaderValue::from_str(&value).expect("ASCII")));
+        }
+        if let Some(sunset_at) = self.sunset_at {
+            // RFC 8594: an HTTP-date.
+            let value = sunset_at.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+            headers.push((SUNSET, HeaderValue::from_str(&value).expect("ASCII")));
+        }
+        headers
+    }
+}
+
+fn parse_date(var: &'static str, value: &str) -> Result<DateTime<Utc>, DeprecationConfigError> {
+    let value = value.trim();
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.with_timezone(&Utc))
+        .or_else(|_| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map(|date| date.and_hms_opt(0, 0, 0).expect("midnight").and_utc())
+        })
+        .map_err(|_| DeprecationConfigError::InvalidDate {
+            var,
+            value: value.to_string(),
+        })
+}
+
+/// Tower layer adding the [`Deprecation`] headers to every response of the router it
+/// wraps, errors included. With nothing announced it passes responses through untouched.
+#[derive(Clone, Debug)]
+pub struct DeprecationLayer {
+    headers: Arc<Vec<(HeaderName, HeaderValue)>>,
+}
+
+impl DeprecationLayer {
+    pub fn new(deprecation: Deprecation) -> Self {
+        DeprecationLayer {
+            headers: Arc::new(deprecation.headers()),
+        }
+    }
+}
+
+impl<S> Layer<S> for DeprecationLayer {
+    type Service = DeprecationService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        DeprecationService {
+            inner,
+            headers: self.headers.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DeprecationService<S> {
+    inner: S,
+    headers: Arc<Vec<(HeaderName, HeaderValue)>>,
+}
+
+impl<S> Service<Request<Body>> for DeprecationService<S>
+where
+    S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let response = self.inner.call(request);
+        let headers = self.headers.clone();
+        Box::pin(async move {
+            let mut response = response.await?;
+            for (name, value) in headers.iter() {
+                response.headers_mut().insert(name.clone(), value.clone());
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, http::StatusCode, routing::get};
+    use chrono::TimeZone;
+    use tower::ServiceExt;
+
+    fn app(deprecation: Deprecation) -> Router {
+        let v1 = Router::new()
+            .route("/api/v1/ping", get(|| async { "v1" }))
+            .route(
+                "/api/v1/broken",
+                get(|| async { StatusCode::INTERNAL_SERVER_ERROR }),
+            )
+            .layer(DeprecationLayer::new(deprecation));
+        Router::new()
+            .merge(v1)
+            .route("/api/v2/ping", get(|| async { "v2" }))
+    }
+
+    async fn get_response(deprecation: Deprecation, uri: &str) -> Response {
+        app(deprecation)
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    fn announced() -> Deprecation {
+        Deprecation::new(
+            Some(Utc.with_ymd_and_hms(2025, 7, 1, 0, 0, 0).unwrap()),
+            Some(Utc.with_ymd_and_hms(2026, 1, 31, 23, 59, 59).unwrap()),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn wrapped_routes_announce_their_retirement() {
+        for uri in ["/api/v1/ping", "/api/v1/broken"] {
+            let response = get_response(announced(), uri).await;
+            assert_eq!(response.headers()["deprecation"], "@1751328000", "{}", uri);
+            assert_eq!(
+                response.headers()["sunset"],
+                "Sat, 31 Jan 2026 23:59:59 GMT",
+                "{}",
+                uri
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn other_routes_and_unannounced_versions_are_untouched() {
+        let response = get_response(announced(), "/api/v2/ping").await;
+        assert!(response.headers().get("deprecation").is_none());
+        assert!(response.headers().get("sunset").is_none());
+
+        let response = get_response(Deprecation::default(), "/api/v1/ping").await;
+        assert!(response.headers().get("deprecation").is_none());
+        assert!(response.headers().get("sunset").is_none());
+    }
+
+    #[test]
+    fn dates_parse_as_rfc3339_or_bare_days() {
+        assert_eq!(
+            parse_date("VAR", "2025-07-01T12:00:00+02:00").unwrap(),
+            Utc.with_ymd_and_hms(2025, 7, 1, 10, 0, 0).unwrap()
+        );
+        assert_eq!(
+            parse_date("VAR", " 2025-07-01 ").unwrap(),
+            Utc.with_ymd_and_hms(2025, 7, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            parse_date("VAR", "next summer").unwrap_err(),
+            DeprecationConfigError::InvalidDate {
+                var: "VAR",
+                value: "next summer".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn sunset_cannot_precede_deprecation() {
+        let early = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let late = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+        assert!(Deprecation::new(Some(late), Some(early)).is_err());
+        assert!(Deprecation::new(Some(early), Some(late)).is_ok());
+        assert!(Deprecation::new(None, Some(early)).is_ok());
+    }
+}
@@ -0,0 +1,38 @@
+//! API versioning shared by the YoloEats services.
+//!
+//! Services mount one router per major version under `/api/v1`, `/api/v2`, ... A
+//! released version is frozen: its handlers share logic with the newer ones but keep
+//! their own response types, so reshaping a v2 response can't change v1 output.
+//!
+//! [`DeprecationLayer`] goes on a frozen version's router and announces its retirement
+//! with `Deprecation` (RFC 9745) and `Sunset` (RFC 8594) headers, dated by a
+//! [`Deprecation`] read from the environment. [`timestamp`] is the date format of v2
+//! responses.
+
+mod deprecation;
+
+pub use deprecation::{
+    API_V1_DEPRECATED_AT_ENV, API_V1_SUNSET_AT_ENV, Deprecation, DeprecationConfigError,
+    DeprecationLayer, DeprecationService,
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+/// A v2 timestamp: RFC 3339 in UTC to the second, `2025-01-31T09:30:00Z`. v1 responses
+/// keep their millisecond form.
+pub fn timestamp(value: &DateTime<Utc>) -> String {
+    value.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn timestamps_are_utc_to_the_second() {
+        let value = Utc.with_ymd_and_hms(2025, 1, 31, 9, 30, 0).unwrap()
+            + chrono::Duration::milliseconds(789);
+        assert_eq!(timestamp(&value), "2025-01-31T09:30:00Z");
+    }
+}
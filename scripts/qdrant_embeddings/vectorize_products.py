@@ -99,7 +99,7 @@ def setup_qdrant_collection(client: QdrantClient):
             logging.info(f"Collection '{collection_name}' created successfully.")
 
         filterable_fields = [
-            "category_tags", "brand_tags", "traces_tags", "labels_tags", "code"
+            "category_tags", "brand_tags", "traces_tags", "labels_tags", "allergens_tags", "code"
         ]
         logging.info(f"Ensuring payload indexes exist for fields: {', '.join(filterable_fields)}")
         for field in filterable_fields:
@@ -172,7 +172,7 @@ def main():
         projection = {
             "_id": 1, "code": 1, "product_name": 1, "generic_name": 1,
             "ingredients_text": 1, "categories_tags": 1, "brands_tags": 1,
-            "traces_tags": 1, "labels_tags": 1,
+            "traces_tags": 1, "labels_tags": 1, "allergens_tags": 1,
         }
 
         try:
@@ -224,9 +224,13 @@ def main():
                         "brand_tags": product.get('brands_tags', []) or [],
                         "traces_tags": product.get('traces_tags', []) or [],
                         "labels_tags": product.get('labels_tags', []) or [],
+                        # None when missing, so strict recommendations can tell unknown allergens from none.
+                        "allergens_tags": product.get('allergens_tags'),
+                        "ingredients_text": (product.get('ingredients_text') or '').strip() or None,
                     }
-                    for key in ["category_tags", "brand_tags", "traces_tags", "labels_tags"]:
-                        payload[key] = [str(item) for item in payload[key] if item is not None]
+                    for key in ["category_tags", "brand_tags", "traces_tags", "labels_tags", "allergens_tags"]:
+                        if payload[key] is not None:
+                            payload[key] = [str(item) for item in payload[key] if item is not None]
 
                     point = PointStruct(
                         id=point_id,
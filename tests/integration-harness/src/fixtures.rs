@@ -0,0 +1,207 @@
+//! Builders for the persistence models the scenarios seed. Defaults are minimal but
+//! valid; set only what a scenario cares about.
+
+use bson::oid::ObjectId;
+use chrono::{DateTime, Utc};
+use product_catalog_service::models::{CreateProductPayload, Nutriments, Product};
+use std::collections::HashMap;
+use user_profile_service::models::{RiskLevel, UserProfile};
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
+pub struct ProductBuilder {
+    product: Product,
+}
+
+impl ProductBuilder {
+    pub fn new(code: &str) -> Self {
+        let now = Utc::now();
+        ProductBuilder {
+            product: Product {
+                id: Some(ObjectId::new()),
+                code: code.to_string(),
+                product_name: Some(format!("Test product {}", code)),
+                product_name_langs: None,
+                generic_name: None,
+                brands: None,
+                categories: None,
+                main_category: None,
+                labels: None,
+                ingredients_text: None,
+                ingredients: None,
+                traces_tags: None,
+                allergens_tags: Vec::new(),
+                quantity: None,
+                image_url: None,
+                image_small_url: None,
+                image_thumb_url: None,
+                countries: None,
+                nutrition_grade_fr: None,
+                nutrition_grade_source: None,
+                ecoscore_grade: None,
+                ecoscore_score: None,
+                nutriments: None,
+                creator: Some("integration-harness".to_string()),
+                source: Some("integration-harness".to_string()),
+                scan_count: None,
+                created_at: now,
+                last_modified_at: now,
+            },
+        }
+    }
+
+    pub fn id(mut self, id: ObjectId) -> Self {
+        self.product.id = Some(id);
+        self
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.product.product_name = Some(name.to_string());
+        self
+    }
+
+    /// Names the product in `language` too, as OpenFoodFacts' `product_name_de` does.
+    pub fn name_in(mut self, language: &str, name: &str) -> Self {
+        self.product
+            .product_name_langs
+            .get_or_insert_with(HashMap::new)
+            .insert(language.to_string(), name.to_string());
+        self
+    }
+
+    /// Comma-separated, as the checker parses it.
+    pub fn ingredients(mut self, text: &str) -> Self {
+        self.product.ingredients_text = Some(text.to_string());
+        self
+    }
+
+    pub fn allergens(mut self, tags: &[&str]) -> Self {
+        self.product.allergens_tags = strings(tags);
+        self
+    }
+
+    pub fn traces(mut self, tags: &[&str]) -> Self {
+        self.product.traces_tags = Some(strings(tags));
+        self
+    }
+
+    pub fn labels(mut self, tags: &[&str]) -> Self {
+        self.product.labels = Some(strings(tags));
+        self
+    }
+
+    pub fn brands(mut self, tags: &[&str]) -> Self {
+        self.product.brands = Some(strings(tags));
+        self
+    }
+
+    pub fn nutriments(mut self, nutriments: Nutriments) -> Self {
+        self.product.nutriments = Some(nutriments);
+        self
+    }
+
+    pub fn nutriscore(mut self, grade: &str) -> Self {
+        self.product.nutrition_grade_fr = Some(grade.to_string());
+        self
+    }
+
+    pub fn ecoscore(mut self, grade: &str) -> Self {
+        self.product.ecoscore_grade = Some(grade.to_string());
+        self
+    }
+
+    pub fn quantity(mut self, quantity: &str) -> Self {
+        self.product.quantity = Some(quantity.to_string());
+        self
+    }
+
+    pub fn image_url(mut self, url: &str) -> Self {
+        self.product.image_url = Some(url.to_string());
+        self
+    }
+
+    pub fn timestamps(
+        mut self,
+        created_at: DateTime<Utc>,
+        last_modified_at: DateTime<Utc>,
+    ) -> Self {
+        self.product.created_at = created_at;
+        self.product.last_modified_at = last_modified_at;
+        self
+    }
+
+    pub fn build(self) -> Product {
+        self.product
+    }
+
+    /// The same product as a `POST /api/v1/products` body.
+    pub fn create_payload(self) -> CreateProductPayload {
+        CreateProductPayload {
+            code: self.product.code,
+            product_name: self.product.product_name,
+            ingredients_text: self.product.ingredients_text,
+            ingredients: self.product.ingredients,
+            brands: self.product.brands,
+            categories: self.product.categories,
+            image_url: self.product.image_url,
+            ecoscore_grade: self.product.ecoscore_grade,
+            ecoscore_score: self.product.ecoscore_score,
+            nutriments: self.product.nutriments,
+        }
+    }
+}
+
+pub struct UserProfileBuilder {
+    profile: UserProfile,
+}
+
+impl UserProfileBuilder {
+    pub fn new(user_id: &str) -> Self {
+        let now = Utc::now();
+        UserProfileBuilder {
+            profile: UserProfile {
+                id: None,
+                user_id: user_id.to_string(),
+                username: None,
+                email: None,
+                allergens: Vec::new(),
+                dietary_prefs: Vec::new(),
+                risk_tolerance: RiskLevel::default(),
+                created_at: now,
+                updated_at: now,
+            },
+        }
+    }
+
+    pub fn allergens(mut self, allergens: &[&str]) -> Self {
+        self.profile.allergens = strings(allergens);
+        self
+    }
+
+    pub fn diets(mut self, diets: &[&str]) -> Self {
+        self.profile.dietary_prefs = strings(diets);
+        self
+    }
+
+    pub fn risk_tolerance(mut self, level: RiskLevel) -> Self {
+        self.profile.risk_tolerance = level;
+        self
+    }
+
+    pub fn email(mut self, email: &str) -> Self {
+        self.profile.email = Some(email.to_string());
+        self
+    }
+
+    pub fn timestamps(mut self, created_at: DateTime<Utc>, updated_at: DateTime<Utc>) -> Self {
+        self.profile.created_at = created_at;
+        self.profile.updated_at = updated_at;
+        self
+    }
+
+    pub fn build(self) -> UserProfile {
+        self.profile
+    }
+}
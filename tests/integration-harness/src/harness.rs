@@ -0,0 +1,370 @@
+use crate::infra::{Infra, NEO4J_PASSWORD, NEO4J_USER};
+use allergy_checker_service::graph::Neo4jGraph;
+use axum::{
+    Json, Router,
+    extract::Path,
+    http::StatusCode,
+    routing::{get, post},
+};
+use mongodb::Database;
+use neo4rs::{Graph, query};
+use product_catalog_service::{
+    audit::MongoAuditLog,
+    cascade::ExternalCopies,
+    categories::GraphTaxonomy,
+    embedding::HttpEmbeddings,
+    images::ImageHosts,
+    models::Product,
+    popularity::RedisScanCounts,
+    qdrant_setup::{CollectionConfig, ensure_qdrant_setup},
+    reports::MongoReportStore,
+    repository::MongoProducts,
+    webhooks::{MongoWebhookStore, Webhooks},
+};
+use qdrant_client::{
+    Payload, Qdrant,
+    qdrant::{Distance, PointStruct, UpsertPointsBuilder},
+};
+use rust_database_clients::{
+    RedisCache, create_mongo_client, create_redis_client,
+    http_resilience::{ResilienceConfig, ResilientClient},
+};
+use serde_json::{Value, json};
+use std::{sync::Arc, time::Duration};
+use user_profile_service::{models::UserProfile, repository::MongoProfiles};
+use uuid::Uuid;
+use yoloeats_auth::{AuthConfig, AuthMode, Authenticator, InternalTokens};
+use yoloeats_dynamic_config::RedisStore;
+use yoloeats_metrics::{
+    BodyLimits, LoadShedConfig, MemoryRateLimitStore, RateLimits, TrustedProxies,
+};
+use yoloeats_pagination::CursorCodec;
+use yoloeats_shutdown::TaskTracker;
+use yoloeats_versioning::Deprecation;
+
+pub const CATALOG_DB: &str = "openfoods";
+pub const PROFILE_DB: &str = "yoloeats_user_profile";
+
+pub const QDRANT_COLLECTION: &str = "product_vectors";
+/// Scenario vectors are hand-written, so a tiny dimension keeps them readable.
+pub const VECTOR_SIZE: u64 = 4;
+/// Shared by all three services; send it as `X-Internal-Token` to reach internal routes.
+pub const INTERNAL_TOKEN: &str = "integration-internal-token";
+/// The one barcode the stand-in for the OpenFoodFacts API knows.
+pub const OFF_API_CODE: &str = "4008400402222";
+/// How often the services re-read their runtime config overrides; 30s in production.
+pub const CONFIG_REFRESH_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Running infrastructure plus the three services, reachable at `*_url`.
+pub struct Harness {
+    pub profile_url: String,
+    pub catalog_url: String,
+    pub checker_url: String,
+    pub http: reqwest::Client,
+    pub catalog_db: Database,
+    pub profile_db: Database,
+    pub redis: redis::Client,
+    pub neo4j: Graph,
+    pub qdrant: Arc<Qdrant>,
+    _infra: Infra,
+}
+
+impl Harness {
+    pub async fn start() -> Self {
+        let infra = Infra::start().await;
+
+        let mongo = create_mongo_client(&infra.mongo_uri)
+            .await
+            .expect("connect to Mongo");
+        let redis = create_redis_client(&infra.redis_uri).expect("connect to Redis");
+        let neo4j = Graph::new(&infra.neo4j_uri, NEO4J_USER, NEO4J_PASSWORD)
+            .await
+            .expect("connect to Neo4j");
+        let qdrant = Arc::new(
+            Qdrant::from_url(&infra.qdrant_uri)
+                .build()
+                .expect("connect to Qdrant"),
+        );
+        let catalog_db = mongo.database(CATALOG_DB);
+        let profile_db = mongo.database(PROFILE_DB);
+
+        let authenticator = admin_authenticator();
+        let internal_tokens = InternalTokens::new(INTERNAL_TOKEN, None);
+        let profile_config = user_profile_service::tunables::config(RedisStore::new(
+            redis.clone(),
+            user_profile_service::tunables::SERVICE,
+        ))
+        .expect("profile tunables");
+        profile_config.spawn_refresh(CONFIG_REFRESH_INTERVAL);
+        let profile_url = serve(user_profile_service::router(
+            Arc::new(user_profile_service::state::AppState {
+                profiles: Arc::new(MongoProfiles::new(&profile_db)),
+                cache: Arc::new(RedisCache::new(redis.clone())),
+                clients: Some(user_profile_service::state::Clients {
+                    mongo_db: profile_db.clone(),
+                    redis_client: redis.clone(),
+                }),
+                internal_tokens: internal_tokens.clone(),
+                config: profile_config,
+                load_shed: LoadShedConfig::default(),
+                body_limits: BodyLimits::default(),
+                api_v1_deprecation: Deprecation::default(),
+            }),
+            authenticator,
+        ))
+        .await;
+
+        let http_client = reqwest::Client::new();
+        let embedding_url = serve(Router::new().route("/embed", post(fake_embed))).await;
+        let off_api_url =
+            serve(Router::new().route("/api/v2/product/{code}", get(fake_off_product))).await;
+        let catalog_config = product_catalog_service::tunables::config(RedisStore::new(
+            redis.clone(),
+            product_catalog_service::tunables::SERVICE,
+        ))
+        .expect("catalog tunables");
+        catalog_config.spawn_refresh(CONFIG_REFRESH_INTERVAL);
+        let upstream_client = ResilientClient::new(
+            yoloeats_tracing::http_client(http_client.clone()),
+            ResilienceConfig::default(),
+        );
+        let catalog_url = serve(product_catalog_service::router(
+            Arc::new(product_catalog_service::state::AppState {
+                products: Arc::new(MongoProducts::new(&catalog_db)),
+                audit: Arc::new(MongoAuditLog::new(&catalog_db)),
+                reports: Arc::new(MongoReportStore::new(&catalog_db)),
+                copies: Arc::new(ExternalCopies::new(qdrant.clone(), neo4j.clone())),
+                categories: Arc::new(GraphTaxonomy::new(neo4j.clone())),
+                scans: Arc::new(RedisScanCounts::new(redis.clone())),
+                webhooks: Webhooks::new(Arc::new(MongoWebhookStore::new(&catalog_db))),
+                cache: Arc::new(RedisCache::new(redis.clone())),
+                clients: Some(product_catalog_service::state::Clients {
+                    mongo_db: catalog_db.clone(),
+                    redis_client: redis.clone(),
+                    qdrant_client: qdrant.clone(),
+                    neo4j_client: neo4j.clone(),
+                }),
+                http_client: http_client.clone(),
+                image_hosts: ImageHosts::default(),
+                upstream_client: upstream_client.clone(),
+                user_profile_service_url: profile_url.clone(),
+                embeddings: Some(Arc::new(HttpEmbeddings::new(
+                    &embedding_url,
+                    upstream_client,
+                    catalog_config.clone(),
+                    VECTOR_SIZE,
+                ))),
+                off_api_url,
+                internal_tokens: internal_tokens.clone(),
+                config: catalog_config,
+                load_shed: LoadShedConfig::default(),
+                body_limits: BodyLimits::default(),
+                rate_limits: RateLimits::default(),
+                rate_limit_store: Arc::new(MemoryRateLimitStore::default()),
+                trusted_proxies: TrustedProxies::default(),
+                cursor_codec: CursorCodec::new("integration-cursor-secret"),
+                api_v1_deprecation: Deprecation::default(),
+                default_country: None,
+                public_reads: true,
+                tasks: TaskTracker::new(),
+            }),
+            admin_authenticator(),
+        ))
+        .await;
+
+        let checker_config = allergy_checker_service::tunables::config(RedisStore::new(
+            redis.clone(),
+            allergy_checker_service::tunables::SERVICE,
+        ))
+        .expect("checker tunables");
+        checker_config.spawn_refresh(CONFIG_REFRESH_INTERVAL);
+        let checker_url = serve(allergy_checker_service::router(Arc::new(
+            allergy_checker_service::state::AppState {
+                graph: Arc::new(Neo4jGraph::new(neo4j.clone())),
+                upstreams: allergy_checker_service::upstream::Upstreams::Http {
+                    client: yoloeats_tracing::http_client(http_client.clone()),
+                    user_profile_service_url: profile_url.clone(),
+                    product_catalog_service_url: catalog_url.clone(),
+                },
+                clients: Some(allergy_checker_service::state::Clients {
+                    neo4j_client: neo4j.clone(),
+                    redis_client: redis.clone(),
+                }),
+                internal_tokens: internal_tokens.clone(),
+                config: checker_config,
+                load_shed: LoadShedConfig::default(),
+                body_limits: BodyLimits::default(),
+            },
+        )))
+        .await;
+
+        Harness {
+            profile_url,
+            catalog_url,
+            checker_url,
+            http: http_client,
+            catalog_db,
+            profile_db,
+            redis,
+            neo4j,
+            qdrant,
+            _infra: infra,
+        }
+    }
+
+    pub async fn seed_product(&self, product: &Product) {
+        self.catalog_db
+            .collection::<Product>("products")
+            .insert_one(product)
+            .await
+            .expect("seed product");
+    }
+
+    pub async fn seed_profile(&self, profile: &UserProfile) {
+        self.profile_db
+            .collection::<UserProfile>("user_profiles")
+            .insert_one(profile)
+            .await
+            .expect("seed profile");
+    }
+
+    /// `(:Ingredient {name})-[:IS_ALLERGEN]->(:Allergen {name})`, as the checker queries it.
+    pub async fn seed_ingredient_allergen(&self, ingredient: &str, allergen: &str) {
+        self.neo4j
+            .run(
+                query(
+                    "MERGE (i:Ingredient {name: $ingredient}) \
+                     MERGE (a:Allergen {name: $allergen}) \
+                     MERGE (i)-[:IS_ALLERGEN]->(a)",
+                )
+                .param("ingredient", ingredient)
+                .param("allergen", allergen),
+            )
+            .await
+            .expect("seed ingredient graph");
+    }
+
+    /// `(:Ingredient {name})-[:MAY_CONTAIN_TRACE]->(:Allergen {name})`.
+    pub async fn seed_ingredient_trace(&self, ingredient: &str, allergen: &str) {
+        self.neo4j
+            .run(
+                query(
+                    "MERGE (i:Ingredient {name: $ingredient}) \
+                     MERGE (a:Allergen {name: $allergen}) \
+                     MERGE (i)-[:MAY_CONTAIN_TRACE]->(a)",
+                )
+                .param("ingredient", ingredient)
+                .param("allergen", allergen),
+            )
+            .await
+            .expect("seed ingredient graph");
+    }
+
+    /// Sets up the Qdrant collection the catalog indexes and searches, as the catalog
+    /// does at startup, if need be.
+    pub async fn ensure_vector_collection(&self) {
+        let config = CollectionConfig {
+            vector_size: VECTOR_SIZE,
+            distance: Distance::Cosine,
+        };
+        ensure_qdrant_setup(&self.qdrant, &config)
+            .await
+            .expect("set up Qdrant collection");
+    }
+
+    /// Indexes `product` in Qdrant the way the embedding script does: point id derived
+    /// from the Mongo id, `code`, `labels_tags`, `allergens_tags` and `ingredients_text` in
+    /// the payload.
+    pub async fn index_product_vector(&self, product: &Product, vector: [f32; VECTOR_SIZE as usize]) {
+        self.ensure_vector_collection().await;
+
+        let oid = product.id.expect("indexed products need a Mongo id").to_hex();
+        let point_id = Uuid::new_v5(&Uuid::NAMESPACE_DNS, oid.as_bytes()).to_string();
+        let payload = Payload::try_from(json!({
+            "code": product.code,
+            "labels_tags": product.labels.clone().unwrap_or_default(),
+            "allergens_tags": product.allergens_tags,
+            "ingredients_text": product.ingredients_text,
+        }))
+        .expect("payload is a JSON object");
+        self.qdrant
+            .upsert_points(
+                UpsertPointsBuilder::new(
+                    QDRANT_COLLECTION,
+                    vec![PointStruct::new(point_id, vector.to_vec(), payload)],
+                )
+                .wait(true),
+            )
+            .await
+            .expect("index product vector");
+    }
+}
+
+/// Auth is exercised by the auth crate's own tests; here every caller is an admin so
+/// service-to-service calls without a token still reach the profile routes.
+pub(crate) fn admin_authenticator() -> Authenticator {
+    Authenticator::new(AuthConfig {
+        mode: AuthMode::Disabled {
+            subject: "integration-admin".to_string(),
+            roles: vec!["admin".to_string()],
+        },
+        audience: None,
+        issuer: None,
+    })
+}
+
+/// Stands in for the embedding service: `{"texts": [...]}` to deterministic
+/// [`VECTOR_SIZE`] vectors, equal for equal texts.
+async fn fake_embed(Json(body): Json<Value>) -> Json<Value> {
+    let vectors: Vec<Vec<f32>> = body["texts"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .map(|text| {
+            let text = text.as_str().unwrap_or_default();
+            (0..VECTOR_SIZE as usize)
+                .map(|i| 1.0 + text.bytes().skip(i).step_by(4).map(f32::from).sum::<f32>())
+                .collect()
+        })
+        .collect();
+    Json(json!({ "vectors": vectors }))
+}
+
+/// Answers like the OpenFoodFacts API: [`OFF_API_CODE`] is found, anything else is not.
+async fn fake_off_product(Path(code): Path<String>) -> (StatusCode, Json<Value>) {
+    if code != OFF_API_CODE {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "code": code, "status": 0, "status_verbose": "product not found" })),
+        );
+    }
+    let product = json!({
+        "code": code,
+        "product_name": "Kinder Schokolade",
+        "brands": "Kinder, Ferrero",
+        "allergens_tags": ["en:milk", "en:soybeans"],
+        "traces": "en:nuts",
+        "countries_tags": ["en:germany"],
+        "created_t": 1365601187,
+    });
+    (
+        StatusCode::OK,
+        Json(
+            json!({ "code": code, "product": product, "status": 1, "status_verbose": "product found" }),
+        ),
+    )
+}
+
+pub(crate) async fn serve(router: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind ephemeral port");
+    let addr = listener.local_addr().expect("local address");
+    tokio::spawn(async move {
+        axum::serve(listener, router.into_make_service())
+            .await
+            .expect("serve router");
+    });
+    format!("http://{}", addr)
+}
@@ -0,0 +1,129 @@
+use bson::doc;
+use std::time::Duration;
+use testcontainers::{
+    ContainerAsync, ContainerRequest, GenericImage, Image, ImageExt,
+    core::{IntoContainerPort, WaitFor},
+    runners::AsyncRunner,
+};
+
+pub(crate) const NEO4J_USER: &str = "neo4j";
+pub(crate) const NEO4J_PASSWORD: &str = "integration-pass";
+
+/// The backing stores, one fresh container each. Dropping `Infra` stops them.
+pub struct Infra {
+    pub mongo_uri: String,
+    pub redis_uri: String,
+    pub neo4j_uri: String,
+    /// Qdrant's gRPC endpoint, which is what `qdrant-client` talks to.
+    pub qdrant_uri: String,
+    _containers: Vec<ContainerAsync<GenericImage>>,
+}
+
+impl Infra {
+    pub async fn start() -> Self {
+        let neo4j_auth = format!("{}/{}", NEO4J_USER, NEO4J_PASSWORD);
+        let neo4j_env = [("NEO4J_AUTH", neo4j_auth.as_str())];
+        let (mongo, redis, neo4j, qdrant) = tokio::join!(
+            start(
+                // Change streams (the catalog sync worker) need a replica set, and the
+                // catalog's bulk import a client-level bulkWrite, i.e. MongoDB 8.
+                GenericImage::new("mongo", "8")
+                    .with_exposed_port(27017.tcp())
+                    .with_wait_for(WaitFor::message_on_stdout("Waiting for connections"))
+                    .with_cmd(["--replSet", "rs0", "--bind_ip_all"]),
+                &[],
+            ),
+            start(
+                GenericImage::new("redis", "7-alpine")
+                    .with_exposed_port(6379.tcp())
+                    .with_wait_for(WaitFor::message_on_stdout("Ready to accept connections")),
+                &[],
+            ),
+            start(
+                GenericImage::new("neo4j", "5")
+                    .with_exposed_port(7687.tcp())
+                    .with_wait_for(WaitFor::message_on_stdout("Started.")),
+                &neo4j_env,
+            ),
+            start(
+                GenericImage::new("qdrant/qdrant", "v1.14.0")
+                    .with_exposed_port(6334.tcp())
+                    .with_wait_for(WaitFor::message_on_stdout("gRPC listening")),
+                &[],
+            ),
+        );
+
+        // The member is registered under its in-container address, so connect directly
+        // instead of through replica set discovery.
+        let mongo_uri = format!(
+            "mongodb://{}/?directConnection=true",
+            address(&mongo, 27017).await
+        );
+        initiate_replica_set(&mongo_uri).await;
+        let redis_uri = format!("redis://{}", address(&redis, 6379).await);
+        let neo4j_uri = format!("bolt://{}", address(&neo4j, 7687).await);
+        let qdrant_uri = format!("http://{}", address(&qdrant, 6334).await);
+
+        Infra {
+            mongo_uri,
+            redis_uri,
+            neo4j_uri,
+            qdrant_uri,
+            _containers: vec![mongo, redis, neo4j, qdrant],
+        }
+    }
+}
+
+async fn start(
+    request: impl Into<ContainerRequest<GenericImage>>,
+    env: &[(&str, &str)],
+) -> ContainerAsync<GenericImage> {
+    let mut request = request.into().with_startup_timeout(Duration::from_secs(120));
+    let name = request.image().name().to_string();
+    for (key, value) in env {
+        request = request.with_env_var(*key, *value);
+    }
+    request
+        .start()
+        .await
+        .unwrap_or_else(|e| panic!("failed to start {} container (is Docker running?): {}", name, e))
+}
+
+async fn address(container: &ContainerAsync<GenericImage>, port: u16) -> String {
+    let host = container.get_host().await.expect("container host");
+    let port = container
+        .get_host_port_ipv4(port.tcp())
+        .await
+        .expect("mapped container port");
+    format!("{}:{}", host, port)
+}
+
+/// Turns the fresh `mongod` into a single-member replica set and waits for it to become
+/// primary.
+async fn initiate_replica_set(mongo_uri: &str) {
+    let client = mongodb::Client::with_uri_str(mongo_uri)
+        .await
+        .expect("connect to Mongo");
+    let admin = client.database("admin");
+    admin
+        .run_command(doc! {
+            "replSetInitiate": {
+                "_id": "rs0",
+                "members": [{ "_id": 0, "host": "localhost:27017" }],
+            }
+        })
+        .await
+        .expect("initiate Mongo replica set");
+
+    for _ in 0..100 {
+        let hello = admin
+            .run_command(doc! { "hello": 1 })
+            .await
+            .expect("query Mongo replica set state");
+        if hello.get_bool("isWritablePrimary").unwrap_or(false) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("Mongo replica set never elected a primary");
+}
@@ -0,0 +1,20 @@
+//! End-to-end harness for the YoloEats services.
+//!
+//! [`Harness::start`] brings up Mongo, Redis, Neo4j and Qdrant in containers and serves
+//! the user-profile, product-catalog and allergy-checker routers in-process on ephemeral
+//! ports, wired to each other exactly as in production. Scenarios live in `tests/` and are
+//! `#[ignore]`d by default; run them with `cargo integration` from the repository root.
+//! [`MemoryHarness::start`] serves the same routers on their in-memory backends and
+//! needs nothing running.
+
+pub mod fixtures;
+mod harness;
+mod infra;
+mod memory;
+
+pub use harness::{
+    CATALOG_DB, CONFIG_REFRESH_INTERVAL, Harness, INTERNAL_TOKEN, OFF_API_CODE, PROFILE_DB,
+    QDRANT_COLLECTION, VECTOR_SIZE,
+};
+pub use infra::Infra;
+pub use memory::MemoryHarness;
@@ -0,0 +1,187 @@
+use crate::harness::{INTERNAL_TOKEN, admin_authenticator, serve};
+use allergy_checker_service::graph::MemoryGraph;
+use chrono::{TimeZone, Utc};
+use product_catalog_service::{
+    audit::MemoryAuditLog,
+    cascade::NoCopies,
+    categories::NoTaxonomy,
+    images::ImageHosts,
+    models::Product,
+    off_fallback::DEFAULT_OFF_API_URL,
+    popularity::MemoryScanCounts,
+    reports::MemoryReportStore,
+    repository::MemoryProducts,
+    webhooks::{DeliveryPolicy, MemoryWebhookStore, Webhooks},
+};
+use rust_database_clients::{
+    Cache, MemoryCache,
+    http_resilience::{ResilienceConfig, ResilientClient},
+};
+use std::{sync::Arc, time::Duration};
+use user_profile_service::{models::UserProfile, repository::MemoryProfiles};
+use yoloeats_auth::{Authenticator, InternalTokens};
+use yoloeats_dynamic_config::MemoryStore;
+use yoloeats_metrics::{
+    BodyLimits, LoadShedConfig, MemoryRateLimitStore, RateLimits, TrustedProxies,
+};
+use yoloeats_pagination::CursorCodec;
+use yoloeats_shutdown::TaskTracker;
+use yoloeats_versioning::Deprecation;
+
+/// The three services on `STORAGE_MODE=memory` backends, as `main.rs` builds them:
+/// nothing external, the checker's graph seeded from the domain tables. `/api/v1` is
+/// dated by [`MemoryHarness::api_v1_deprecation`], and webhook deliveries retry within
+/// a fraction of a second.
+pub struct MemoryHarness {
+    pub profile_url: String,
+    pub catalog_url: String,
+    pub checker_url: String,
+    pub http: reqwest::Client,
+    products: MemoryProducts,
+    profiles: MemoryProfiles,
+}
+
+impl MemoryHarness {
+    /// Deprecated 2025-07-01, sunset at the end of January 2026.
+    pub fn api_v1_deprecation() -> Deprecation {
+        Deprecation::new(
+            Some(Utc.with_ymd_and_hms(2025, 7, 1, 0, 0, 0).unwrap()),
+            Some(Utc.with_ymd_and_hms(2026, 1, 31, 23, 59, 59).unwrap()),
+        )
+        .expect("sunset after deprecation")
+    }
+
+    pub async fn start() -> Self {
+        Self::start_with_catalog_cache(Arc::new(MemoryCache::default())).await
+    }
+
+    /// [`start`](Self::start), with `catalog_cache` in front of the catalog's products;
+    /// for pointing it at a Redis that isn't there.
+    pub async fn start_with_catalog_cache(catalog_cache: Arc<dyn Cache>) -> Self {
+        Self::start_with(catalog_cache, None, admin_authenticator()).await
+    }
+
+    /// [`start`](Self::start), with the catalog scoping the requests that name no country
+    /// to `country`, as `DEFAULT_COUNTRY_TAG` does.
+    pub async fn start_with_default_country(country: &str) -> Self {
+        Self::start_with(
+            Arc::new(MemoryCache::default()),
+            Some(country.to_string()),
+            admin_authenticator(),
+        )
+        .await
+    }
+
+    /// [`start`](Self::start), with the profile and catalog routes checking tokens with
+    /// `authenticator` instead of taking every caller for an admin.
+    pub async fn start_with_authenticator(authenticator: Authenticator) -> Self {
+        Self::start_with(Arc::new(MemoryCache::default()), None, authenticator).await
+    }
+
+    async fn start_with(
+        catalog_cache: Arc<dyn Cache>,
+        default_country: Option<String>,
+        authenticator: Authenticator,
+    ) -> Self {
+        let internal_tokens = InternalTokens::new(INTERNAL_TOKEN, None);
+        let products = MemoryProducts::default();
+        let profiles = MemoryProfiles::default();
+
+        let profile_url = serve(user_profile_service::router(
+            Arc::new(user_profile_service::state::AppState {
+                profiles: Arc::new(profiles.clone()),
+                cache: Arc::new(MemoryCache::default()),
+                clients: None,
+                internal_tokens: internal_tokens.clone(),
+                config: user_profile_service::tunables::config(MemoryStore::default())
+                    .expect("profile tunables"),
+                load_shed: LoadShedConfig::default(),
+                body_limits: BodyLimits::default(),
+                api_v1_deprecation: Self::api_v1_deprecation(),
+            }),
+            authenticator.clone(),
+        ))
+        .await;
+
+        let http_client = reqwest::Client::new();
+        let catalog_url = serve(product_catalog_service::router(
+            Arc::new(product_catalog_service::state::AppState {
+                products: Arc::new(products.clone()),
+                audit: Arc::new(MemoryAuditLog::default()),
+                reports: Arc::new(MemoryReportStore::default()),
+                copies: Arc::new(NoCopies),
+                categories: Arc::new(NoTaxonomy),
+                scans: Arc::new(MemoryScanCounts::default()),
+                webhooks: Webhooks::new(Arc::new(MemoryWebhookStore::default())).with_policy(
+                    DeliveryPolicy {
+                        max_attempts: 3,
+                        initial_backoff: Duration::from_millis(20),
+                        timeout: Duration::from_secs(2),
+                    },
+                ),
+                cache: catalog_cache,
+                clients: None,
+                http_client: http_client.clone(),
+                image_hosts: ImageHosts::default(),
+                upstream_client: ResilientClient::new(
+                    yoloeats_tracing::http_client(http_client.clone()),
+                    ResilienceConfig::default(),
+                ),
+                user_profile_service_url: profile_url.clone(),
+                embeddings: None,
+                off_api_url: DEFAULT_OFF_API_URL.to_string(),
+                internal_tokens: internal_tokens.clone(),
+                config: product_catalog_service::tunables::config(MemoryStore::default())
+                    .expect("catalog tunables"),
+                load_shed: LoadShedConfig::default(),
+                body_limits: BodyLimits::default(),
+                rate_limits: RateLimits::default(),
+                rate_limit_store: Arc::new(MemoryRateLimitStore::default()),
+                trusted_proxies: TrustedProxies::default(),
+                cursor_codec: CursorCodec::new("integration-cursor-secret"),
+                api_v1_deprecation: Self::api_v1_deprecation(),
+                default_country,
+                public_reads: true,
+                tasks: TaskTracker::new(),
+            }),
+            authenticator,
+        ))
+        .await;
+
+        let checker_url = serve(allergy_checker_service::router(Arc::new(
+            allergy_checker_service::state::AppState {
+                graph: Arc::new(MemoryGraph::seeded()),
+                upstreams: allergy_checker_service::upstream::Upstreams::Http {
+                    client: yoloeats_tracing::http_client(http_client.clone()),
+                    user_profile_service_url: profile_url.clone(),
+                    product_catalog_service_url: catalog_url.clone(),
+                },
+                clients: None,
+                internal_tokens,
+                config: allergy_checker_service::tunables::config(MemoryStore::default())
+                    .expect("checker tunables"),
+                load_shed: LoadShedConfig::default(),
+                body_limits: BodyLimits::default(),
+            },
+        )))
+        .await;
+
+        MemoryHarness {
+            profile_url,
+            catalog_url,
+            checker_url,
+            http: http_client,
+            products,
+            profiles,
+        }
+    }
+
+    /// Stores `product` as is, id and timestamps included.
+    pub fn seed_product(&self, product: &Product) {
+        self.products.seed(product.clone());
+    }
+
+    pub fn seed_profile(&self, profile: &UserProfile) {
+        self.profiles.seed(profile.clone());
+    }
+}
@@ -0,0 +1,329 @@
+//! The JSON of `/api/v1` and `/api/v2`, pinned side by side. v1 is frozen: a change to
+//! the shared handler logic that alters a v1 body fails here. The in-memory tests run
+//! with plain `cargo test`; the recommendations one needs Qdrant and is ignored.
+
+use bson::oid::ObjectId;
+use chrono::{DateTime, TimeZone, Utc};
+use integration_harness::{
+    Harness, MemoryHarness,
+    fixtures::{ProductBuilder, UserProfileBuilder},
+};
+use reqwest::{Response, StatusCode};
+use serde_json::{Value, json};
+
+const PRODUCT_OID: &str = "663a1f2e9b1e8a3f4c5d6e7f";
+const PRODUCT_CODE: &str = "4000417025005";
+
+fn created_at() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 6, 1, 8, 0, 0).unwrap() + chrono::Duration::milliseconds(123)
+}
+
+fn updated_at() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 6, 2, 9, 30, 0).unwrap()
+}
+
+fn product() -> ProductBuilder {
+    ProductBuilder::new(PRODUCT_CODE)
+        .id(ObjectId::parse_str(PRODUCT_OID).unwrap())
+        .name("Alpine milk chocolate")
+        .ingredients("Sugar, cocoa butter, whole milk powder")
+        .brands(&["alpine-dairy"])
+        .labels(&["en:vegetarian"])
+        .allergens(&["en:milk"])
+        .traces(&["en:nuts"])
+        .timestamps(created_at(), updated_at())
+}
+
+fn v1_product() -> Value {
+    json!({
+        "_id": { "$oid": PRODUCT_OID },
+        "code": PRODUCT_CODE,
+        "product_name": "Alpine milk chocolate",
+        "generic_name": null,
+        "brands_tags": ["alpine-dairy"],
+        "categories_tags": null,
+        "main_category": null,
+        "labels_tags": ["en:vegetarian"],
+        "ingredients_text": "Sugar, cocoa butter, whole milk powder",
+        "traces_tags": ["en:nuts"],
+        "allergens_tags": ["en:milk"],
+        "quantity": null,
+        "image_url": null,
+        "image_small_url": null,
+        "countries_tags": null,
+        "nutrition_grade_fr": null,
+        "creator": "integration-harness",
+        "source": "integration-harness",
+        "created_datetime": "2024-06-01T08:00:00.123Z",
+        "last_modified_datetime": "2024-06-02T09:30:00.000Z",
+    })
+}
+
+fn v2_product() -> Value {
+    json!({
+        "id": PRODUCT_OID,
+        "code": PRODUCT_CODE,
+        "name": "Alpine milk chocolate",
+        "nameLangs": {},
+        "displayName": "Alpine milk chocolate",
+        "genericName": null,
+        "brands": ["alpine-dairy"],
+        "categories": [],
+        "mainCategory": null,
+        "labels": ["en:vegetarian"],
+        "ingredientsText": "Sugar, cocoa butter, whole milk powder",
+        "allergens": ["en:milk"],
+        "traces": ["en:nuts"],
+        "quantity": null,
+        "imageUrl": null,
+        "imageSmallUrl": null,
+        "countries": [],
+        "nutriscore": null,
+        "createdAt": "2024-06-01T08:00:00Z",
+        "updatedAt": "2024-06-02T09:30:00Z",
+    })
+}
+
+async fn get(harness: &MemoryHarness, url: String) -> Response {
+    harness.http.get(url).send().await.unwrap()
+}
+
+fn assert_deprecated(response: &Response) {
+    assert_eq!(response.headers()["deprecation"], "@1751328000");
+    assert_eq!(
+        response.headers()["sunset"],
+        "Sat, 31 Jan 2026 23:59:59 GMT"
+    );
+}
+
+fn assert_current(response: &Response) {
+    assert!(response.headers().get("deprecation").is_none());
+    assert!(response.headers().get("sunset").is_none());
+}
+
+/// Only v2 names products in the reader's language.
+fn assert_varies_by_language(response: &Response, varies: bool) {
+    let vary = response.headers().get_all("vary").iter();
+    let mut names = vary.flat_map(|vary| vary.to_str().unwrap().split(", "));
+    assert_eq!(names.any(|name| name == "accept-language"), varies);
+}
+
+async fn json_ok(response: Response) -> Value {
+    assert_eq!(response.status(), StatusCode::OK);
+    response.json().await.unwrap()
+}
+
+#[tokio::test]
+async fn product_reads_keep_v1_and_serve_v2() {
+    let harness = MemoryHarness::start().await;
+    harness.seed_product(&product().build());
+
+    for path in [
+        format!("products/{}", PRODUCT_OID),
+        format!("products/barcode/{}", PRODUCT_CODE),
+    ] {
+        let response = get(&harness, format!("{}/api/v1/{}", harness.catalog_url, path)).await;
+        assert_deprecated(&response);
+        assert_varies_by_language(&response, false);
+        assert_eq!(json_ok(response).await, v1_product(), "v1 {}", path);
+
+        let response = get(&harness, format!("{}/api/v2/{}", harness.catalog_url, path)).await;
+        assert_current(&response);
+        assert_varies_by_language(&response, true);
+        assert_eq!(json_ok(response).await, v2_product(), "v2 {}", path);
+    }
+}
+
+#[tokio::test]
+async fn product_search_keeps_v1_and_serves_v2() {
+    let harness = MemoryHarness::start().await;
+    harness.seed_product(&product().build());
+
+    let response = get(
+        &harness,
+        format!("{}/api/v1/products/search?q=chocolate", harness.catalog_url),
+    )
+    .await;
+    assert_deprecated(&response);
+    assert_varies_by_language(&response, false);
+    assert_eq!(
+        json_ok(response).await,
+        json!({ "items": [v1_product()], "total": 1, "nextCursor": null, "hasMore": false })
+    );
+
+    let response = get(
+        &harness,
+        format!("{}/api/v2/products/search?q=chocolate", harness.catalog_url),
+    )
+    .await;
+    assert_current(&response);
+    assert_varies_by_language(&response, true);
+    assert_eq!(
+        json_ok(response).await,
+        json!({ "items": [v2_product()], "total": 1, "nextCursor": null, "hasMore": false })
+    );
+}
+
+#[tokio::test]
+async fn v1_errors_are_deprecated_too() {
+    let harness = MemoryHarness::start().await;
+    let missing = ObjectId::new().to_hex();
+
+    let response = get(
+        &harness,
+        format!("{}/api/v1/products/{}", harness.catalog_url, missing),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_deprecated(&response);
+
+    let response = get(
+        &harness,
+        format!("{}/api/v2/products/{}", harness.catalog_url, missing),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_current(&response);
+}
+
+#[tokio::test]
+async fn profile_reads_keep_v1_and_serve_v2() {
+    let harness = MemoryHarness::start().await;
+    harness.seed_profile(
+        &UserProfileBuilder::new("alice")
+            .email("alice@example.com")
+            .allergens(&["milk"])
+            .diets(&["vegetarian"])
+            .timestamps(created_at(), updated_at())
+            .build(),
+    );
+
+    let response = get(
+        &harness,
+        format!("{}/api/v1/users/alice/profile", harness.profile_url),
+    )
+    .await;
+    assert_deprecated(&response);
+    assert_eq!(
+        json_ok(response).await,
+        json!({
+            "user_id": "alice",
+            "email": "alice@example.com",
+            "allergens": ["milk"],
+            "dietary_prefs": ["vegetarian"],
+            "risk_tolerance": "medium",
+            "created_at": "2024-06-01T08:00:00.123Z",
+            "updated_at": "2024-06-02T09:30:00.000Z",
+        })
+    );
+
+    let response = get(
+        &harness,
+        format!("{}/api/v2/users/alice/profile", harness.profile_url),
+    )
+    .await;
+    assert_current(&response);
+    assert_eq!(
+        json_ok(response).await,
+        json!({
+            "userId": "alice",
+            "username": null,
+            "email": "alice@example.com",
+            "allergens": ["milk"],
+            "dietaryPreferences": ["vegetarian"],
+            "riskTolerance": "medium",
+            "createdAt": "2024-06-01T08:00:00Z",
+            "updatedAt": "2024-06-02T09:30:00Z",
+        })
+    );
+}
+
+#[tokio::test]
+async fn allergen_list_is_paged_only_on_v2() {
+    let harness = MemoryHarness::start().await;
+
+    let response = get(
+        &harness,
+        format!("{}/api/v1/allergens", harness.profile_url),
+    )
+    .await;
+    assert_deprecated(&response);
+    let v1 = json_ok(response).await;
+    let allergens = v1.as_array().expect("v1 allergens are a bare array");
+    assert_eq!(allergens.len(), 14);
+    assert_eq!(
+        allergens[0],
+        json!({
+            "id": "gluten",
+            "name": "Cereals containing gluten",
+            "description": "Includes wheat (such as spelt and khorasan wheat), rye, barley, oats.",
+        })
+    );
+
+    let response = get(
+        &harness,
+        format!("{}/api/v2/allergens", harness.profile_url),
+    )
+    .await;
+    assert_current(&response);
+    assert_eq!(
+        json_ok(response).await,
+        json!({ "items": v1, "total": 14, "nextCursor": null, "hasMore": false })
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn recommendations_are_a_list_on_v1_and_structured_on_v2() {
+    let harness = Harness::start().await;
+
+    let source = product().build();
+    let similar = ProductBuilder::new("1000000000002").build();
+    for (product, vector) in [
+        (&source, [1.0, 0.0, 0.0, 0.0]),
+        (&similar, [0.9, 0.1, 0.0, 0.0]),
+    ] {
+        harness.seed_product(product).await;
+        harness.index_product_vector(product, vector).await;
+    }
+    harness
+        .seed_profile(&UserProfileBuilder::new("recommendation-user").build())
+        .await;
+    let similar_oid = similar.id.unwrap().to_hex();
+
+    let response = harness
+        .http
+        .get(format!(
+            "{}/api/v1/products/{}/recommendations",
+            harness.catalog_url, PRODUCT_OID
+        ))
+        .header("x-user-id", "recommendation-user")
+        .send()
+        .await
+        .unwrap();
+    let v1 = json_ok(response).await;
+    let items = v1.as_array().expect("v1 recommendations are a bare array");
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["_id"], json!({ "$oid": similar_oid }));
+
+    let response = harness
+        .http
+        .get(format!(
+            "{}/api/v2/products/{}/recommendations",
+            harness.catalog_url, PRODUCT_OID
+        ))
+        .header("x-user-id", "recommendation-user")
+        .send()
+        .await
+        .unwrap();
+    let v2 = json_ok(response).await;
+    assert_eq!(v2["sourceId"], PRODUCT_OID);
+    assert_eq!(v2["personalized"], true);
+    assert_eq!(v2["items"].as_array().unwrap().len(), 1);
+    assert_eq!(v2["items"][0]["id"], similar_oid);
+    assert_eq!(v2["items"][0]["code"], "1000000000002");
+    let score = v2["items"][0]["score"]
+        .as_f64()
+        .expect("v2 items carry their score");
+    assert!(score > 0.9 && score <= 1.0, "score {}", score);
+}
@@ -0,0 +1,66 @@
+//! Oversized request bodies are refused with 413 in the error envelope.
+//! Ignored by default: run `cargo integration` from the repository root.
+
+use integration_harness::{Harness, fixtures::ProductBuilder};
+use reqwest::{RequestBuilder, StatusCode};
+use serde_json::{Value, json};
+use yoloeats_metrics::DEFAULT_MAX_BODY_BYTES;
+
+/// Comfortably over the 256 KB default.
+fn oversized_text() -> String {
+    "x".repeat(DEFAULT_MAX_BODY_BYTES + 1024)
+}
+
+async fn assert_too_large(request: RequestBuilder) {
+    let response = request.send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "payload_too_large");
+    assert_eq!(body["message"], "Request body exceeds the 256 KB limit");
+    assert_eq!(body["details"]["limitBytes"], DEFAULT_MAX_BODY_BYTES);
+    assert!(body["requestId"].is_string(), "{}", body);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn oversized_product_creation_is_rejected() {
+    let harness = Harness::start().await;
+    let payload = ProductBuilder::new("4000417025005")
+        .name(&oversized_text())
+        .create_payload();
+
+    assert_too_large(
+        harness
+            .http
+            .post(format!("{}/api/v1/products", harness.catalog_url))
+            .json(&payload),
+    )
+    .await;
+
+    // A normal-sized product still goes through.
+    let response = harness
+        .http
+        .post(format!("{}/api/v1/products", harness.catalog_url))
+        .json(&ProductBuilder::new("4000417025005").create_payload())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn oversized_profile_update_is_rejected() {
+    let harness = Harness::start().await;
+
+    assert_too_large(
+        harness
+            .http
+            .put(format!(
+                "{}/api/v1/users/big-spender/profile",
+                harness.profile_url
+            ))
+            .json(&json!({ "allergens": ["milk"], "diets": [oversized_text()] })),
+    )
+    .await;
+}
@@ -0,0 +1,274 @@
+//! The catalog sync worker against real Mongo, Redis and Qdrant, with a fake embedding
+//! service. Ignored by default: run `cargo integration` from the repository root.
+
+use bson::{Document, doc, oid::ObjectId};
+use catalog_sync_worker::{
+    Checkpoints, DEFAULT_KEY_PREFIX, Embedder, RunMode, SyncError, Worker, WorkerConfig, point_id,
+};
+use integration_harness::{Harness, QDRANT_COLLECTION, VECTOR_SIZE, fixtures::ProductBuilder};
+use qdrant_client::qdrant::{GetPointsBuilder, RetrievedPoint};
+use std::{future::Future, time::Duration};
+use tokio::{sync::oneshot, task::JoinHandle};
+
+/// Deterministic vectors derived from the text; rejects anything mentioning "POISON"
+/// the way a real service rejects input it can't embed.
+struct FakeEmbedder;
+
+impl Embedder for FakeEmbedder {
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, SyncError> {
+        if let Some(text) = texts.iter().find(|t| t.contains("POISON")) {
+            return Err(SyncError::EmbeddingRejected(format!(
+                "cannot embed '{}'",
+                text
+            )));
+        }
+        Ok(texts
+            .iter()
+            .map(|text| {
+                (0..VECTOR_SIZE as usize)
+                    .map(|i| 1.0 + text.bytes().skip(i).step_by(4).map(f32::from).sum::<f32>())
+                    .collect()
+            })
+            .collect())
+    }
+}
+
+struct RunningWorker {
+    shutdown: oneshot::Sender<()>,
+    handle: JoinHandle<Result<(), SyncError>>,
+}
+
+impl RunningWorker {
+    async fn stop(self) {
+        self.shutdown.send(()).unwrap();
+        self.handle.await.unwrap().expect("worker stops cleanly");
+    }
+}
+
+fn start_worker(harness: &Harness, mode: RunMode) -> RunningWorker {
+    let worker = Worker::new(
+        harness.catalog_db.collection::<Document>("products"),
+        harness.qdrant.clone(),
+        Checkpoints::new(harness.redis.clone(), DEFAULT_KEY_PREFIX),
+        FakeEmbedder,
+        WorkerConfig {
+            vector_dimension: VECTOR_SIZE,
+            flush_interval: Duration::from_millis(100),
+            retry_base_delay: Duration::from_millis(10),
+            ..WorkerConfig::default()
+        },
+    );
+    let (shutdown, stopped) = oneshot::channel::<()>();
+    let handle = tokio::spawn(async move {
+        worker
+            .run(mode, async {
+                stopped.await.ok();
+            })
+            .await
+    });
+    RunningWorker { shutdown, handle }
+}
+
+async fn point(harness: &Harness, id: &ObjectId) -> Option<RetrievedPoint> {
+    harness
+        .qdrant
+        .get_points(
+            GetPointsBuilder::new(QDRANT_COLLECTION, vec![point_id(id).into()]).with_payload(true),
+        )
+        .await
+        .ok()?
+        .result
+        .into_iter()
+        .next()
+}
+
+async fn payload_field(harness: &Harness, id: &ObjectId, field: &str) -> Option<serde_json::Value> {
+    let point = point(harness, id).await?;
+    let payload: serde_json::Value = qdrant_client::Payload::from(point.payload).into();
+    Some(payload[field].clone())
+}
+
+/// Polls `check` until it holds; the worker applies changes asynchronously.
+async fn eventually<F, Fut>(what: &str, mut check: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = bool>,
+{
+    for _ in 0..100 {
+        if check().await {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("timed out waiting until {}", what);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn inserts_updates_and_deletes_reach_qdrant() {
+    let harness = Harness::start().await;
+    let worker = start_worker(&harness, RunMode::Stream);
+    // Give the change stream a moment to open before writing.
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let product = ProductBuilder::new("4000417025005")
+        .labels(&["en:vegan"])
+        .build();
+    let id = product.id.unwrap();
+    harness.seed_product(&product).await;
+    eventually("the inserted product is indexed", || async {
+        payload_field(&harness, &id, "code").await == Some("4000417025005".into())
+    })
+    .await;
+
+    harness
+        .catalog_db
+        .collection::<Document>("products")
+        .update_one(
+            doc! { "_id": id },
+            doc! { "$set": { "labels_tags": ["en:vegan", "en:organic"], "countries_tags": ["en:germany"] } },
+        )
+        .await
+        .unwrap();
+    eventually("the update is reflected in the payload", || async {
+        payload_field(&harness, &id, "labels_tags").await
+            == Some(serde_json::json!(["en:vegan", "en:organic"]))
+            && payload_field(&harness, &id, "countries_tags").await
+                == Some(serde_json::json!(["en:germany"]))
+    })
+    .await;
+
+    harness
+        .catalog_db
+        .collection::<Document>("products")
+        .delete_one(doc! { "_id": id })
+        .await
+        .unwrap();
+    eventually("the deleted product's point is removed", || async {
+        point(&harness, &id).await.is_none()
+    })
+    .await;
+
+    worker.stop().await;
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn poison_documents_are_dead_lettered_without_blocking_others() {
+    let harness = Harness::start().await;
+    let worker = start_worker(&harness, RunMode::Stream);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let products = harness.catalog_db.collection::<Document>("products");
+    let malformed = ObjectId::new();
+    let unembeddable = ProductBuilder::new("4000000000001")
+        .name("POISON pill")
+        .build();
+    let healthy = ProductBuilder::new("4000000000002")
+        .name("Oat drink")
+        .build();
+    products
+        .insert_one(doc! { "_id": malformed, "code": 4000000000000_i64 })
+        .await
+        .unwrap();
+    harness.seed_product(&unembeddable).await;
+    harness.seed_product(&healthy).await;
+
+    let healthy_id = healthy.id.unwrap();
+    eventually("the healthy product is indexed", || async {
+        point(&harness, &healthy_id).await.is_some()
+    })
+    .await;
+
+    let checkpoints = Checkpoints::new(harness.redis.clone(), DEFAULT_KEY_PREFIX);
+    eventually("both poison documents are dead-lettered", || async {
+        checkpoints.dead_letters().await.unwrap().len() == 2
+    })
+    .await;
+    let mut dead: Vec<String> = checkpoints
+        .dead_letters()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.product_id)
+        .collect();
+    dead.sort();
+    let mut expected = vec![malformed.to_hex(), unembeddable.id.unwrap().to_hex()];
+    expected.sort();
+    assert_eq!(dead, expected);
+    assert!(point(&harness, &unembeddable.id.unwrap()).await.is_none());
+
+    worker.stop().await;
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn backfill_then_resume_after_restart() {
+    let harness = Harness::start().await;
+    let existing = ProductBuilder::new("4000000000010").build();
+    harness.seed_product(&existing).await;
+
+    let worker = start_worker(&harness, RunMode::Backfill);
+    let existing_id = existing.id.unwrap();
+    eventually("the pre-existing product is backfilled", || async {
+        point(&harness, &existing_id).await.is_some()
+    })
+    .await;
+    worker.stop().await;
+
+    let checkpoints = Checkpoints::new(harness.redis.clone(), DEFAULT_KEY_PREFIX);
+    assert!(
+        checkpoints.load_resume_token().await.unwrap().is_some(),
+        "graceful shutdown saves the resume token"
+    );
+
+    // Written while the worker is down; only the saved token can find it.
+    let missed = ProductBuilder::new("4000000000011").build();
+    harness.seed_product(&missed).await;
+
+    let worker = start_worker(&harness, RunMode::Stream);
+    let missed_id = missed.id.unwrap();
+    eventually("the change made during the outage is applied", || async {
+        point(&harness, &missed_id).await.is_some()
+    })
+    .await;
+    worker.stop().await;
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn synced_points_drive_recommendations() {
+    let harness = Harness::start().await;
+    let worker = start_worker(&harness, RunMode::Stream);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let source = ProductBuilder::new("4000000000020")
+        .name("Dark chocolate")
+        .build();
+    let similar = ProductBuilder::new("4000000000021")
+        .name("Dark chocolate")
+        .build();
+    harness.seed_product(&source).await;
+    harness.seed_product(&similar).await;
+    let ids = [source.id.unwrap(), similar.id.unwrap()];
+    eventually("both products are indexed", || async {
+        point(&harness, &ids[0]).await.is_some() && point(&harness, &ids[1]).await.is_some()
+    })
+    .await;
+    worker.stop().await;
+
+    let response = harness
+        .http
+        .get(format!(
+            "{}/api/v1/products/{}/recommendations",
+            harness.catalog_url,
+            ids[0].to_hex()
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let products: Vec<serde_json::Value> = response.json().await.unwrap();
+    assert_eq!(products.len(), 1);
+    assert_eq!(products[0]["code"], "4000000000021");
+}
@@ -0,0 +1,149 @@
+//! Runtime config overrides: written to Redis or through `/internal/v1/config`, picked up
+//! by the running services. Ignored by default: run `cargo integration` from the
+//! repository root.
+
+use integration_harness::{
+    CONFIG_REFRESH_INTERVAL, Harness, INTERNAL_TOKEN,
+    fixtures::{ProductBuilder, UserProfileBuilder},
+};
+use redis::AsyncCommands;
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use std::time::{Duration, Instant};
+use yoloeats_auth::INTERNAL_TOKEN_HEADER;
+use yoloeats_domain::{CheckResult, SafetyStatus};
+use yoloeats_dynamic_config::redis_key;
+
+const CODE: &str = "4008400401621";
+
+async fn check_status(harness: &Harness) -> SafetyStatus {
+    let response = harness
+        .http
+        .post(format!("{}/api/v1/check", harness.checker_url))
+        .json(&json!({ "productIdentifier": CODE, "userId": "nut-allergic" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    response.json::<CheckResult>().await.unwrap().status
+}
+
+async fn set_override(harness: &Harness, service: &str, name: &str, value: &str) {
+    let mut conn = harness
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .unwrap();
+    let _: () = conn.hset(redis_key(service), name, value).await.unwrap();
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn trace_policy_overrides_reach_the_checker_within_one_refresh() {
+    let harness = Harness::start().await;
+    harness.seed_ingredient_trace("hazelnut", "nuts").await;
+    harness
+        .seed_profile(
+            &UserProfileBuilder::new("nut-allergic")
+                .allergens(&["nuts"])
+                .build(),
+        )
+        .await;
+    harness
+        .seed_product(
+            &ProductBuilder::new(CODE)
+                .ingredients("Sugar, cocoa butter")
+                .traces(&["hazelnut"])
+                .build(),
+        )
+        .await;
+    assert_eq!(check_status(&harness).await, SafetyStatus::Caution);
+
+    set_override(
+        &harness,
+        "allergy-checker-service",
+        "trace_policy",
+        "unsafe",
+    )
+    .await;
+    let written = Instant::now();
+    while check_status(&harness).await != SafetyStatus::Unsafe {
+        assert!(
+            written.elapsed() < CONFIG_REFRESH_INTERVAL * 3,
+            "override not picked up after {:?}",
+            written.elapsed()
+        );
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    // A bad value is ignored; the last valid one stays in force.
+    set_override(
+        &harness,
+        "allergy-checker-service",
+        "trace_policy",
+        "strict",
+    )
+    .await;
+    tokio::time::sleep(CONFIG_REFRESH_INTERVAL * 3).await;
+    assert_eq!(check_status(&harness).await, SafetyStatus::Unsafe);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn config_endpoint_rejects_invalid_values_and_stores_valid_ones() {
+    let harness = Harness::start().await;
+    let url = format!("{}/internal/v1/config", harness.catalog_url);
+
+    let response = harness.http.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = harness
+        .http
+        .put(&url)
+        .header(INTERNAL_TOKEN_HEADER, INTERNAL_TOKEN)
+        .json(&json!({ "product_cache_ttl_secs": 0, "recommendation_limit": 5 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "invalid_request");
+    assert!(body["details"]["product_cache_ttl_secs"].is_string());
+
+    let mut conn = harness
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .unwrap();
+    let stored: std::collections::HashMap<String, String> = conn
+        .hgetall(redis_key("product-catalog-service"))
+        .await
+        .unwrap();
+    assert!(stored.is_empty(), "nothing written: {:?}", stored);
+
+    let response = harness
+        .http
+        .put(&url)
+        .header(INTERNAL_TOKEN_HEADER, INTERNAL_TOKEN)
+        .json(&json!({ "recommendation_limit": 5 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await.unwrap();
+    let limit = body["tunables"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|t| t["name"] == "recommendation_limit")
+        .unwrap();
+    assert_eq!(limit["value"], "5");
+    assert_eq!(limit["default"], "10");
+    assert_eq!(limit["overridden"], true);
+
+    let stored: Option<String> = conn
+        .hget(redis_key("product-catalog-service"), "recommendation_limit")
+        .await
+        .unwrap();
+    assert_eq!(stored.as_deref(), Some("5"));
+}
@@ -0,0 +1,867 @@
+//! Cross-service scenarios. They need Docker, so they are ignored by default:
+//! run `cargo integration` from the repository root.
+
+use bson::{Document, doc};
+use catalog_sync_worker::point_id;
+use futures::{Stream, StreamExt};
+use integration_harness::{
+    Harness, INTERNAL_TOKEN, QDRANT_COLLECTION, VECTOR_SIZE,
+    fixtures::{ProductBuilder, UserProfileBuilder},
+};
+use mongodb::{IndexModel, options::IndexOptions};
+use neo4rs::query;
+use product_catalog_service::{
+    audit::AuditAction,
+    db_setup::create_indexes,
+    errors::ServiceError,
+    events::{self, ProductEvent},
+    qdrant_setup::{INDEXED_PAYLOAD_FIELDS, check_vector_size},
+    repository::{PRODUCTS_COLLECTION, TOMBSTONES_COLLECTION},
+};
+use qdrant_client::qdrant::GetPointsBuilder;
+use redis::AsyncCommands;
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use yoloeats_auth::INTERNAL_TOKEN_HEADER;
+use yoloeats_domain::{CheckResult, SafetyStatus};
+
+/// Recommendations are personalized for the user named in `x-user-id`.
+const RECOMMENDATION_USER: &str = "recommendation-user";
+
+async fn check(harness: &Harness, code: &str, user_id: &str) -> CheckResult {
+    let response = harness
+        .http
+        .post(format!("{}/api/v1/check", harness.checker_url))
+        .json(&json!({ "productIdentifier": code, "userId": user_id }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    response.json().await.unwrap()
+}
+
+async fn recommended_codes(harness: &Harness, source_oid: &str) -> Vec<String> {
+    let response = harness
+        .http
+        .get(format!(
+            "{}/api/v1/products/{}/recommendations",
+            harness.catalog_url, source_oid
+        ))
+        .header("x-user-id", RECOMMENDATION_USER)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let products: Vec<serde_json::Value> = response.json().await.unwrap();
+    let mut codes: Vec<String> = products
+        .iter()
+        .map(|p| p["code"].as_str().unwrap().to_string())
+        .collect();
+    codes.sort();
+    codes
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn created_product_is_unsafe_for_allergic_user() {
+    let harness = Harness::start().await;
+    harness
+        .seed_ingredient_allergen("whole milk powder", "milk")
+        .await;
+    harness
+        .seed_profile(&UserProfileBuilder::new("allergic").allergens(&["milk"]).build())
+        .await;
+    harness
+        .seed_profile(&UserProfileBuilder::new("not-allergic").allergens(&["peanuts"]).build())
+        .await;
+
+    let payload = ProductBuilder::new("4000417025005")
+        .name("Alpine milk chocolate")
+        .ingredients("Sugar, cocoa butter, whole milk powder")
+        .create_payload();
+    let response = harness
+        .http
+        .post(format!("{}/api/v1/products", harness.catalog_url))
+        .json(&payload)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    let result = check(&harness, "4000417025005", "allergic").await;
+    assert_eq!(result.status, SafetyStatus::Unsafe);
+    assert_eq!(result.conflicting_allergens, vec!["milk".to_string()]);
+    assert!(!result.is_offline_result);
+
+    let result = check(&harness, "4000417025005", "not-allergic").await;
+    assert_eq!(result.status, SafetyStatus::Safe);
+    assert!(result.conflicting_allergens.is_empty());
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn updating_profile_changes_recommendation_filtering() {
+    let harness = Harness::start().await;
+
+    let source = ProductBuilder::new("1000000000001").build();
+    let with_milk_label = ProductBuilder::new("1000000000002").labels(&["milk"]).build();
+    let plain = ProductBuilder::new("1000000000003").build();
+    for (product, vector) in [
+        (&source, [1.0, 0.0, 0.0, 0.0]),
+        (&with_milk_label, [0.9, 0.1, 0.0, 0.0]),
+        (&plain, [0.8, 0.2, 0.0, 0.0]),
+    ] {
+        harness.seed_product(product).await;
+        harness.index_product_vector(product, vector).await;
+    }
+    harness
+        .seed_profile(&UserProfileBuilder::new(RECOMMENDATION_USER).build())
+        .await;
+    let source_oid = source.id.unwrap().to_hex();
+
+    assert_eq!(
+        recommended_codes(&harness, &source_oid).await,
+        vec!["1000000000002".to_string(), "1000000000003".to_string()]
+    );
+
+    // Goes through the profile API so the cached profile is invalidated as in production.
+    let response = harness
+        .http
+        .put(format!(
+            "{}/api/v1/users/{}/profile",
+            harness.profile_url, RECOMMENDATION_USER
+        ))
+        .json(&json!({ "allergens": ["milk"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    assert_eq!(
+        recommended_codes(&harness, &source_oid).await,
+        vec!["1000000000003".to_string()]
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn qdrant_setup_indexes_the_filtered_fields_and_can_rerun() {
+    let harness = Harness::start().await;
+    harness.ensure_vector_collection().await;
+    harness.ensure_vector_collection().await;
+
+    let info = harness
+        .qdrant
+        .collection_info(QDRANT_COLLECTION)
+        .await
+        .unwrap()
+        .result
+        .unwrap();
+    for field in INDEXED_PAYLOAD_FIELDS {
+        assert!(
+            info.payload_schema.contains_key(field),
+            "{} is indexed",
+            field
+        );
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn embeddings_must_have_the_collections_dimensions() {
+    let harness = Harness::start().await;
+    harness.ensure_vector_collection().await;
+
+    check_vector_size(&harness.qdrant, VECTOR_SIZE)
+        .await
+        .unwrap();
+    let other_model = check_vector_size(&harness.qdrant, VECTOR_SIZE * 2).await;
+    assert!(
+        matches!(other_model, Err(ServiceError::InvalidVariable(_))),
+        "{:?}",
+        other_model
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn mongo_indexes_are_created_on_the_products_collection() {
+    let harness = Harness::start().await;
+    let products = harness
+        .catalog_db
+        .collection::<Document>(PRODUCTS_COLLECTION);
+    // An index someone built by hand, differently, is kept.
+    products
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "labels_tags": 1 })
+                .options(IndexOptions::builder().sparse(true).build())
+                .build(),
+        )
+        .await
+        .unwrap();
+
+    create_indexes(&harness.catalog_db).await.unwrap();
+    create_indexes(&harness.catalog_db).await.unwrap();
+
+    let names = products.list_index_names().await.unwrap();
+    for name in [
+        "code_1",
+        "product_name_text_generic_name_text_ingredients_text_text_brands_tags_text",
+        "categories_tags_1",
+        "labels_tags_1",
+        "brands_tags_1",
+        "countries_tags_1",
+        "allergens_tags_1",
+        "traces_tags_1",
+        "nutrition_grade_fr_1",
+        "nutriments.sugars_100g_1",
+        "nutriments.salt_100g_1",
+        "nutriments.fat_100g_1",
+        "nutriments.proteins_100g_1",
+        "last_modified_datetime_1__id_1",
+        "created_datetime_-1__id_-1",
+        "last_modified_datetime_-1__id_-1",
+    ] {
+        assert!(names.iter().any(|n| n == name), "{} in {:?}", name, names);
+    }
+    let tombstone_names = harness
+        .catalog_db
+        .collection::<Document>(TOMBSTONES_COLLECTION)
+        .list_index_names()
+        .await
+        .unwrap();
+    assert!(
+        tombstone_names
+            .iter()
+            .any(|n| n == "deleted_datetime_1__id_1"),
+        "{:?}",
+        tombstone_names
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn text_search_ranks_by_score_once_the_text_index_exists() {
+    let harness = Harness::start().await;
+    for (code, name) in [
+        ("1000000000001", "Hazelnut spread"),
+        ("1000000000002", "Nutella hazelnut spread"),
+    ] {
+        harness
+            .seed_product(&ProductBuilder::new(code).name(name).build())
+            .await;
+    }
+    let search = |query: &str| {
+        harness
+            .http
+            .get(format!(
+                "{}/api/v1/products/search?q=nutella+hazelnut{}",
+                harness.catalog_url, query
+            ))
+            .send()
+    };
+
+    let missing = search("").await.unwrap();
+    assert_eq!(missing.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let body: Value = missing.json().await.unwrap();
+    assert_eq!(
+        body["message"],
+        "Text search is unavailable: text index missing"
+    );
+
+    create_indexes(&harness.catalog_db).await.unwrap();
+    let ranked: Value = search("&debug=true").await.unwrap().json().await.unwrap();
+    assert_eq!(ranked["items"][0]["code"], "1000000000002");
+    let score = |i: usize| ranked["items"][i]["_score"].as_f64().unwrap();
+    assert!(score(0) > score(1));
+
+    let by_id: Value = search("&sort=id").await.unwrap().json().await.unwrap();
+    assert_eq!(by_id["items"][0]["code"], "1000000000001");
+    assert!(by_id["items"][0].get("_score").is_none());
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn summary_search_reads_only_the_summary_fields_from_mongodb() {
+    let harness = Harness::start().await;
+    create_indexes(&harness.catalog_db).await.unwrap();
+    let ingredients = "cocoa mass, sugar, hazelnuts, whole milk powder, ".repeat(40);
+    for code in ["1000000000001", "1000000000002"] {
+        harness
+            .seed_product(
+                &ProductBuilder::new(code)
+                    .name("Hazelnut chocolate")
+                    .ingredients(&ingredients)
+                    .allergens(&["en:milk", "en:nuts"])
+                    .build(),
+            )
+            .await;
+    }
+    let search = |query: &str| {
+        harness
+            .http
+            .get(format!(
+                "{}/api/v1/products/search?q=hazelnut{}",
+                harness.catalog_url, query
+            ))
+            .send()
+    };
+
+    let full = search("&debug=true").await.unwrap().text().await.unwrap();
+    let summary = search("&debug=true&view=summary")
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(
+        summary.len() * 5 < full.len(),
+        "{} vs {}",
+        summary.len(),
+        full.len()
+    );
+
+    let summary: Value = serde_json::from_str(&summary).unwrap();
+    let first = &summary["items"][0];
+    assert!(first.get("ingredients_text").is_none());
+    assert!(first.get("created_datetime").is_none());
+    assert_eq!(first["allergens_tags"], json!(["en:milk", "en:nuts"]));
+    assert!(first["_score"].as_f64().is_some());
+    assert_eq!(summary["total"], 2);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn strict_allergen_mode_leaves_out_products_of_unknown_allergens() {
+    let harness = Harness::start().await;
+    // The seed fixtures all have ingredients and allergen tags, as complete imports do.
+    let fixtures = seed_cli::fixtures::products(20);
+    for product in &fixtures {
+        harness.seed_product(product).await;
+    }
+    let with_milk = fixtures
+        .iter()
+        .filter(|product| product.allergens_tags.contains(&"en:milk".to_string()))
+        .count();
+    // Documents as incomplete imports leave them: one without allergens, four unknown.
+    harness
+        .catalog_db
+        .collection::<Document>(PRODUCTS_COLLECTION)
+        .insert_many([
+            doc! { "code": "1000000000016", "ingredients_text": "Oats", "allergens_tags": [] },
+            doc! { "code": "1000000000023", "allergens_tags": [] },
+            doc! { "code": "1000000000030", "ingredients_text": "", "allergens_tags": [] },
+            doc! { "code": "1000000000047", "ingredients_text": "Milk chocolate" },
+            doc! { "code": "1000000000054", "ingredients_text": "Peanuts", "allergens_tags": null },
+        ])
+        .await
+        .unwrap();
+    let total = |query: &str| {
+        let request = harness.http.get(format!(
+            "{}/api/v1/products/search?limit=1{}",
+            harness.catalog_url, query
+        ));
+        async move {
+            let response = request.send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let page: Value = response.json().await.unwrap();
+            page["total"].as_u64().unwrap() as usize
+        }
+    };
+
+    assert_eq!(total("").await, 25);
+    assert_eq!(total("&allergen_mode=lenient").await, 25);
+    assert_eq!(total("&allergen_mode=strict").await, 21);
+    // Lenient takes the untagged milk chocolate as free of milk; strict doesn't.
+    assert_eq!(total("&allergens=milk").await, 25 - with_milk);
+    assert_eq!(
+        total("&allergens=milk&allergen_mode=strict").await,
+        21 - with_milk
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn random_products_are_sampled_by_mongodb() {
+    let harness = Harness::start().await;
+    for i in 0..20 {
+        harness
+            .seed_product(&ProductBuilder::new(&format!("10000000000{:02}", i)).build())
+            .await;
+    }
+    let mut blank = ProductBuilder::new("2000000000001").build();
+    blank.product_name = None;
+    harness.seed_product(&blank).await;
+
+    let mut seen = std::collections::HashSet::new();
+    for _ in 0..20 {
+        let response = harness
+            .http
+            .get(format!(
+                "{}/api/v1/products/random?count=3",
+                harness.catalog_url
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let products: Vec<Value> = response.json().await.unwrap();
+        assert_eq!(products.len(), 3);
+        for product in products {
+            let code = product["code"].as_str().unwrap().to_string();
+            assert_ne!(code, "2000000000001");
+            seen.insert(code);
+        }
+    }
+    assert!(seen.len() > 3, "{:?}", seen);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn changes_page_through_mongodb_products_and_tombstones() {
+    let harness = Harness::start().await;
+    let now = chrono::Utc::now();
+    let mut ids = Vec::new();
+    for (code, hours_ago) in [
+        ("1000000000001", 3),
+        ("1000000000002", 2),
+        ("1000000000003", 1),
+    ] {
+        let modified = now - chrono::Duration::hours(hours_ago);
+        let product = ProductBuilder::new(code)
+            .timestamps(modified, modified)
+            .build();
+        ids.push(product.id.unwrap().to_hex());
+        harness.seed_product(&product).await;
+    }
+    let response = harness
+        .http
+        .delete(format!(
+            "{}/api/v1/products/{}",
+            harness.catalog_url, ids[0]
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let since =
+        (now - chrono::Duration::days(1)).to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    let first_page = format!(
+        "{}/api/v1/products/changes?since={}&limit=2",
+        harness.catalog_url, since
+    );
+    let mut url = first_page.clone();
+    let mut entries = Vec::new();
+    loop {
+        let response = harness.http.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let page: Value = response.json().await.unwrap();
+        entries.extend(page["items"].as_array().unwrap().clone());
+        match page["nextCursor"].as_str() {
+            Some(cursor) => url = format!("{}&cursor={}", first_page, cursor),
+            None => break,
+        }
+    }
+    let listed: Vec<(String, String)> = entries
+        .iter()
+        .map(|entry| {
+            (
+                entry["change"].as_str().unwrap().to_string(),
+                entry["code"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        listed,
+        [
+            ("modified".to_string(), "1000000000002".to_string()),
+            ("modified".to_string(), "1000000000003".to_string()),
+            ("deleted".to_string(), "1000000000001".to_string()),
+        ]
+    );
+    assert_eq!(entries[2]["_id"]["$oid"], ids[0]);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn recent_products_follow_creates_and_updates_in_mongodb() {
+    let harness = Harness::start().await;
+    let mut ids = Vec::new();
+    for code in ["1000000000016", "1000000000023", "1000000000030"] {
+        let response = harness
+            .http
+            .post(format!("{}/api/v1/products", harness.catalog_url))
+            .json(&ProductBuilder::new(code).create_payload())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let created: Value = response.json().await.unwrap();
+        ids.push(created["_id"]["$oid"].as_str().unwrap().to_string());
+        // MongoDB keeps milliseconds; apart, the times order the products alone.
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    }
+    for id in [&ids[1], &ids[0]] {
+        let response = harness
+            .http
+            .patch(format!("{}/api/v1/products/{}", harness.catalog_url, id))
+            .json(&json!({ "product_name": "Renamed" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    }
+
+    let listed = |kind: &'static str| {
+        let first_page = format!(
+            "{}/api/v1/products/recent?kind={}&limit=2",
+            harness.catalog_url, kind
+        );
+        let harness = &harness;
+        async move {
+            let mut url = first_page.clone();
+            let mut codes = Vec::new();
+            loop {
+                let response = harness.http.get(&url).send().await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                let page: Value = response.json().await.unwrap();
+                for product in page["items"].as_array().unwrap() {
+                    codes.push(product["code"].as_str().unwrap().to_string());
+                }
+                match page["nextCursor"].as_str() {
+                    Some(cursor) => url = format!("{}&cursor={}", first_page, cursor),
+                    None => break,
+                }
+            }
+            codes
+        }
+    };
+    assert_eq!(
+        listed("created").await,
+        ["1000000000030", "1000000000023", "1000000000016"]
+    );
+    assert_eq!(
+        listed("updated").await,
+        ["1000000000016", "1000000000023", "1000000000030"]
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn created_products_are_indexed_for_recommendations() {
+    let harness = Harness::start().await;
+    harness.ensure_vector_collection().await;
+
+    let mut oids = Vec::new();
+    for code in ["1000000000016", "1000000000023"] {
+        let payload = ProductBuilder::new(code)
+            .name("Dark chocolate")
+            .ingredients("Cocoa mass, sugar, cocoa butter")
+            .create_payload();
+        let response = harness
+            .http
+            .post(format!("{}/api/v1/products", harness.catalog_url))
+            .json(&payload)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let created: Value = response.json().await.unwrap();
+        oids.push(created["_id"]["$oid"].as_str().unwrap().to_string());
+    }
+
+    // Indexing runs after the responses; until then the source has no vector.
+    let url = format!(
+        "{}/api/v1/products/{}/recommendations",
+        harness.catalog_url, oids[0]
+    );
+    for _ in 0..100 {
+        let response = harness.http.get(&url).send().await.unwrap();
+        if response.status() == StatusCode::OK {
+            let products: Vec<Value> = response.json().await.unwrap();
+            if !products.is_empty() {
+                assert_eq!(products.len(), 1);
+                assert_eq!(products[0]["code"], "1000000000023");
+                return;
+            }
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    panic!("created products were never recommended");
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn deleting_a_product_removes_its_vector_and_graph_node() {
+    let harness = Harness::start().await;
+    let product = ProductBuilder::new("1000000000021").build();
+    harness.seed_product(&product).await;
+    harness
+        .index_product_vector(&product, [1.0, 0.0, 0.0, 0.0])
+        .await;
+    harness
+        .neo4j
+        .run(query("CREATE (:Product {code: $code})").param("code", product.code.clone()))
+        .await
+        .unwrap();
+    let id = product.id.unwrap();
+
+    let response = harness
+        .http
+        .delete(format!(
+            "{}/api/v1/products/{}",
+            harness.catalog_url,
+            id.to_hex()
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let points = harness
+        .qdrant
+        .get_points(GetPointsBuilder::new(
+            QDRANT_COLLECTION,
+            vec![point_id(&id).into()],
+        ))
+        .await
+        .unwrap();
+    assert!(points.result.is_empty());
+
+    let mut rows = harness
+        .neo4j
+        .execute(
+            query("MATCH (p:Product {code: $code}) RETURN count(p) AS nodes")
+                .param("code", product.code.clone()),
+        )
+        .await
+        .unwrap();
+    let nodes: i64 = rows.next().await.unwrap().unwrap().get("nodes").unwrap();
+    assert_eq!(nodes, 0);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn patch_unsets_stored_fields_and_invalidates_the_cache() {
+    let harness = Harness::start().await;
+    let product = ProductBuilder::new("4000417025005")
+        .labels(&["en:vegan"])
+        .quantity("500 g")
+        .image_url("https://images.example/old.jpg")
+        .build();
+    harness.seed_product(&product).await;
+    let id = product.id.unwrap();
+    let url = format!("{}/api/v1/products/{}", harness.catalog_url, id.to_hex());
+    // Cached before the patch.
+    harness.http.get(&url).send().await.unwrap();
+
+    let response = harness
+        .http
+        .patch(&url)
+        .json(&json!({ "image_url": null, "labels_tags": ["en:organic"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let stored = harness
+        .catalog_db
+        .collection::<Document>("products")
+        .find_one(doc! { "_id": id })
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!stored.contains_key("image_url"));
+    assert_eq!(stored.get_array("labels_tags").unwrap().len(), 1);
+    assert_eq!(stored.get_str("quantity").unwrap(), "500 g");
+    assert!(
+        stored
+            .get_datetime("last_modified_datetime")
+            .unwrap()
+            .to_chrono()
+            > product.last_modified_at
+    );
+
+    let cached: Value = harness
+        .http
+        .get(&url)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(cached["image_url"], Value::Null);
+    assert_eq!(cached["labels_tags"], json!(["en:organic"]));
+}
+
+/// The next event on the channel about `code`, skipping any about other products.
+async fn next_event_for(
+    events: &mut (impl Stream<Item = ProductEvent> + Unpin),
+    code: &str,
+) -> ProductEvent {
+    tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        loop {
+            let event = events.next().await.expect("subscribed to product events");
+            if event.code == code {
+                return event;
+            }
+        }
+    })
+    .await
+    .expect("a product event within 5 seconds")
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn product_changes_are_published_to_redis() {
+    let harness = Harness::start().await;
+    let mut events = events::subscribe(&harness.redis).await.unwrap();
+
+    let products = format!("{}/api/v1/products", harness.catalog_url);
+    let created: Value = harness
+        .http
+        .post(&products)
+        .json(
+            &ProductBuilder::new("4000417025005")
+                .name("Alpine milk chocolate")
+                .create_payload(),
+        )
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let id = created["_id"]["$oid"].as_str().unwrap().to_string();
+    let event = next_event_for(&mut events, "4000417025005").await;
+    assert_eq!(event.event, AuditAction::Created);
+    assert_eq!(event.id, id);
+    assert!(event.changed_fields.contains(&"product_name".to_string()));
+
+    let by_id = format!("{}/{}", products, id);
+    let response = harness
+        .http
+        .patch(&by_id)
+        .json(&json!({ "quantity": "100 g" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let event = next_event_for(&mut events, "4000417025005").await;
+    assert_eq!(event.event, AuditAction::Updated);
+    assert_eq!(event.changed_fields, ["quantity"]);
+
+    let response = harness.http.delete(&by_id).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let event = next_event_for(&mut events, "4000417025005").await;
+    assert_eq!(event.event, AuditAction::Deleted);
+    assert_eq!(event.id, id);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn reindex_rebuilds_the_vectors_of_every_stored_product() {
+    let harness = Harness::start().await;
+    harness.ensure_vector_collection().await;
+    let products = [
+        ProductBuilder::new("4000417025005")
+            .name("Alpine milk chocolate")
+            .labels(&["en:fair-trade"])
+            .build(),
+        ProductBuilder::new("3017620422003").name("Nutella").build(),
+    ];
+    for product in &products {
+        harness.seed_product(product).await;
+    }
+    harness
+        .catalog_db
+        .collection::<Document>(PRODUCTS_COLLECTION)
+        .insert_one(doc! { "product_name": "No barcode" })
+        .await
+        .unwrap();
+    let reindex = format!("{}/api/v1/admin/reindex", harness.catalog_url);
+    let start = || {
+        harness
+            .http
+            .post(&reindex)
+            .header(INTERNAL_TOKEN_HEADER, INTERNAL_TOKEN)
+            .send()
+    };
+
+    // Another job holds the lock.
+    let mut redis = harness
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .unwrap();
+    let _: () = redis.set("reindex:lock", "another-job").await.unwrap();
+    let response = start().await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let _: () = redis.del("reindex:lock").await.unwrap();
+
+    let response = start().await.unwrap();
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    let job: Value = response.json().await.unwrap();
+    assert_eq!(job["status"], "running");
+    assert_eq!(job["total"], 3);
+    let job_url = format!("{}/{}", reindex, job["job_id"].as_str().unwrap());
+
+    let mut job = job;
+    for _ in 0..100 {
+        job = harness
+            .http
+            .get(&job_url)
+            .header(INTERNAL_TOKEN_HEADER, INTERNAL_TOKEN)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if job["status"] != "running" {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(job["status"], "completed", "{}", job);
+    assert_eq!(
+        (&job["processed"], &job["indexed"], &job["failed"]),
+        (&json!(3), &json!(2), &json!(1))
+    );
+    let locked: Option<String> = redis.get("reindex:lock").await.unwrap();
+    assert_eq!(locked, None);
+
+    for product in &products {
+        let points = harness
+            .qdrant
+            .get_points(
+                GetPointsBuilder::new(
+                    QDRANT_COLLECTION,
+                    vec![point_id(&product.id.unwrap()).into()],
+                )
+                .with_payload(true),
+            )
+            .await
+            .unwrap();
+        let point = points.result.into_iter().next().expect("reindexed point");
+        let payload: Value = qdrant_client::Payload::from(point.payload).into();
+        assert_eq!(payload["code"], product.code);
+        assert_eq!(
+            payload["labels_tags"],
+            json!(product.labels.clone().unwrap_or_default())
+        );
+    }
+
+    let response = harness
+        .http
+        .get(format!("{}/{}", reindex, "000000000000000000000000"))
+        .header(INTERNAL_TOKEN_HEADER, INTERNAL_TOKEN)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
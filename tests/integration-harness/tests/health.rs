@@ -0,0 +1,90 @@
+//! The shared health routes on all three services, against real dependencies.
+//! Ignored by default: run `cargo integration` from the repository root.
+
+use integration_harness::{Harness, INTERNAL_TOKEN};
+use reqwest::StatusCode;
+use serde_json::Value;
+use yoloeats_auth::INTERNAL_TOKEN_HEADER;
+
+async fn get(harness: &Harness, url: String, token: Option<&str>) -> (StatusCode, Value) {
+    let mut request = harness.http.get(url);
+    if let Some(token) = token {
+        request = request.header(INTERNAL_TOKEN_HEADER, token);
+    }
+    let response = request.send().await.unwrap();
+    let status = response.status();
+    (status, response.json().await.unwrap_or(Value::Null))
+}
+
+fn check_names(report: &Value) -> Vec<&str> {
+    report["checks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|check| check["name"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn every_service_is_live_and_ready() {
+    let harness = Harness::start().await;
+
+    for (base, critical) in [
+        (&harness.profile_url, vec!["mongodb"]),
+        (&harness.catalog_url, vec!["mongodb"]),
+        (&harness.checker_url, vec!["neo4j"]),
+    ] {
+        let (status, live) = get(&harness, format!("{}/health/live", base), None).await;
+        assert_eq!(status, StatusCode::OK, "{}", base);
+        assert_eq!(live["status"], "up");
+
+        let (status, ready) = get(&harness, format!("{}/health/ready", base), None).await;
+        assert_eq!(status, StatusCode::OK, "{}: {}", base, ready);
+        assert_eq!(ready["status"], "up");
+        assert_eq!(
+            check_names(&ready),
+            critical,
+            "readiness runs critical checks only"
+        );
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn details_need_the_internal_token_and_list_every_check() {
+    let harness = Harness::start().await;
+    let url = format!("{}/health/details", harness.catalog_url);
+
+    let (status, _) = get(&harness, url.clone(), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, details) = get(&harness, url, Some(INTERNAL_TOKEN)).await;
+    assert_eq!(status, StatusCode::OK, "{}", details);
+    assert_eq!(details["service"], "product-catalog-service");
+    assert_eq!(
+        check_names(&details),
+        [
+            "mongodb",
+            "redis",
+            "qdrant",
+            "neo4j",
+            "user-profile-service"
+        ]
+    );
+    for check in details["checks"].as_array().unwrap() {
+        assert_eq!(check["status"], "up", "{}", check);
+    }
+
+    let (status, details) = get(
+        &harness,
+        format!("{}/health/details", harness.checker_url),
+        Some(INTERNAL_TOKEN),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", details);
+    assert_eq!(
+        check_names(&details),
+        ["neo4j", "user-profile-service", "product-catalog-service"]
+    );
+}
@@ -0,0 +1,3188 @@
+//! The services on `STORAGE_MODE=memory`: no Docker needed, so this runs with plain
+//! `cargo test`.
+
+use axum::{Router, body::Bytes, http::HeaderMap, routing::post};
+use chrono::{Duration, SecondsFormat, Utc};
+use integration_harness::{
+    INTERNAL_TOKEN, MemoryHarness,
+    fixtures::{ProductBuilder, UserProfileBuilder},
+};
+use product_catalog_service::{
+    errors::ServiceError,
+    handlers::recommendation_profile,
+    models::Nutriments,
+    webhooks::{self, SIGNATURE_HEADER},
+};
+use reqwest::{StatusCode, header::AUTHORIZATION};
+use rust_database_clients::{
+    RedisCache,
+    http_resilience::{ResilienceConfig, ResilientClient, UpstreamErrorKind},
+};
+use serde_json::{Value, json};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::{
+    net::TcpListener,
+    sync::mpsc::{self, UnboundedReceiver},
+};
+use yoloeats_auth::{AuthConfig, AuthMode, Authenticator, INTERNAL_TOKEN_HEADER};
+use yoloeats_domain::{CheckResult, SafetyStatus};
+use yoloeats_tracing::REQUEST_ID_HEADER;
+
+#[tokio::test]
+async fn create_product_update_profile_and_check_in_memory() {
+    let harness = MemoryHarness::start().await;
+
+    let payload = ProductBuilder::new("4000417025005")
+        .name("Alpine milk chocolate")
+        .ingredients("Sugar, cocoa butter, whole milk powder")
+        .create_payload();
+    let response = harness
+        .http
+        .post(format!("{}/api/v1/products", harness.catalog_url))
+        .json(&payload)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    let response = harness
+        .http
+        .put(format!(
+            "{}/api/v1/users/allergic/profile",
+            harness.profile_url
+        ))
+        .json(&json!({ "allergens": ["milk"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = harness
+        .http
+        .post(format!("{}/api/v1/check", harness.checker_url))
+        .json(&json!({ "productIdentifier": "4000417025005", "userId": "allergic" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let result: CheckResult = response.json().await.unwrap();
+    assert_eq!(result.status, SafetyStatus::Unsafe);
+    assert_eq!(result.conflicting_allergens, vec!["milk".to_string()]);
+}
+
+#[tokio::test]
+async fn a_request_id_comes_back_on_responses_and_errors_in_memory() {
+    let harness = MemoryHarness::start().await;
+
+    let response = harness
+        .http
+        .get(format!("{}/api/v1/products/not-an-id", harness.catalog_url))
+        .header(REQUEST_ID_HEADER, "memory-trace-1")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(response.headers()[REQUEST_ID_HEADER], "memory-trace-1");
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["requestId"], "memory-trace-1");
+
+    // The checker fails on the profile it fetched under the same id.
+    let response = harness
+        .http
+        .post(format!("{}/api/v1/check", harness.checker_url))
+        .header(REQUEST_ID_HEADER, "memory-trace-2")
+        .json(&json!({ "productIdentifier": "4000417025005", "userId": "nobody" }))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_client_error(), "{}", response.status());
+    assert_eq!(response.headers()[REQUEST_ID_HEADER], "memory-trace-2");
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["requestId"], "memory-trace-2");
+
+    // Without one, a fresh id is made up and answered.
+    let response = harness
+        .http
+        .get(format!("{}/api/v1/products/not-an-id", harness.catalog_url))
+        .send()
+        .await
+        .unwrap();
+    let generated = response.headers()[REQUEST_ID_HEADER]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["requestId"], generated.as_str());
+}
+
+#[tokio::test]
+async fn invalid_barcodes_are_refused_unless_internal_codes_are_allowed_in_memory() {
+    let harness = MemoryHarness::start().await;
+    let create = |code: &str| {
+        harness
+            .http
+            .post(format!("{}/api/v1/products", harness.catalog_url))
+            .json(&ProductBuilder::new(code).create_payload())
+            .send()
+    };
+    let lookup = |code: &str| {
+        harness
+            .http
+            .get(format!(
+                "{}/api/v1/products/barcode/{}",
+                harness.catalog_url, code
+            ))
+            .send()
+    };
+
+    // The last digit of 4000417025005 mistyped.
+    let response = create("4000417025006").await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "invalid_barcode");
+    assert!(
+        body["message"]
+            .as_str()
+            .unwrap()
+            .contains("EAN-8, UPC-A or EAN-13")
+    );
+    assert_eq!(
+        lookup("4000417025006").await.unwrap().status(),
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        create("2012345678900").await.unwrap().status(),
+        StatusCode::BAD_REQUEST
+    );
+
+    let response = harness
+        .http
+        .put(format!("{}/internal/v1/config", harness.catalog_url))
+        .header(INTERNAL_TOKEN_HEADER, INTERNAL_TOKEN)
+        .json(&json!({ "allow_internal_codes": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        create("2012345678900").await.unwrap().status(),
+        StatusCode::CREATED
+    );
+    assert_eq!(
+        lookup("2012345678900").await.unwrap().status(),
+        StatusCode::OK
+    );
+    assert_eq!(
+        create("4000417025006").await.unwrap().status(),
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[tokio::test]
+async fn structured_ingredients_tag_created_products_in_memory() {
+    let harness = MemoryHarness::start().await;
+
+    let response = harness
+        .http
+        .post(format!("{}/api/v1/products", harness.catalog_url))
+        .json(&json!({
+            "code": "4000417025005",
+            "ingredients_text": "Zucker, Kakaobutter, Vollmilchpulver",
+            "ingredients": [
+                { "id": "en:sugar", "text": "Zucker", "vegan": "yes", "percent_estimate": "48,5" },
+                { "id": "en:milk", "text": "Vollmilchpulver", "vegan": "no", "vegetarian": "yes" }
+            ]
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let product: Value = response.json().await.unwrap();
+    assert_eq!(product["allergens_tags"], json!(["en:milk"]));
+    assert_eq!(product["labels_tags"], json!(["en:non-vegan"]));
+    assert_eq!(product["ingredients"][0]["percent_estimate"], 48.5);
+}
+
+#[tokio::test]
+async fn tags_are_stored_and_searched_in_one_form_in_memory() {
+    let harness = MemoryHarness::start().await;
+
+    let product: Value = harness
+        .http
+        .post(format!("{}/api/v1/products", harness.catalog_url))
+        .json(&json!({
+            "code": "4000417025005",
+            "brands": ["Ritter Sport"],
+            "categories": [" Chocolates ", "en:chocolates", "de:Schokolade"]
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(product["brands_tags"], json!(["ritter-sport"]));
+    assert_eq!(
+        product["categories_tags"],
+        json!(["en:chocolates", "de:schokolade"])
+    );
+
+    for query in [
+        "category=CHOCOLATES",
+        "category=en:chocolates",
+        "brand=Ritter Sport",
+    ] {
+        let page: Value = harness
+            .http
+            .get(format!(
+                "{}/api/v1/products/search?{}",
+                harness.catalog_url, query
+            ))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(page["items"].as_array().unwrap().len(), 1, "{}", query);
+    }
+}
+
+#[tokio::test]
+async fn categories_are_counted_without_a_taxonomy_in_memory() {
+    let harness = MemoryHarness::start().await;
+    for (code, categories) in [
+        ("1000000000016", json!(["Snacks", "Chocolates"])),
+        ("1000000000023", json!(["en:snacks", "en:chocolates"])),
+        ("1000000000030", json!(["en:snacks", "en:crisps"])),
+    ] {
+        let status = harness
+            .http
+            .post(format!("{}/api/v1/products", harness.catalog_url))
+            .json(&json!({ "code": code, "categories": categories }))
+            .send()
+            .await
+            .unwrap()
+            .status();
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    let listed = |query: &'static str| {
+        let harness = &harness;
+        async move {
+            let response = harness
+                .http
+                .get(format!(
+                    "{}/api/v1/categories?{}",
+                    harness.catalog_url, query
+                ))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{}", query);
+            response.json::<Value>().await.unwrap()
+        }
+    };
+    assert_eq!(
+        listed("").await,
+        json!([
+            { "tag": "en:snacks", "count": 3, "name": null, "parent": null },
+            { "tag": "en:chocolates", "count": 2, "name": null, "parent": null },
+            { "tag": "en:crisps", "count": 1, "name": null, "parent": null },
+        ])
+    );
+    assert_eq!(
+        listed("prefix=Choc&min_count=2").await,
+        json!([{ "tag": "en:chocolates", "count": 2, "name": null, "parent": null }])
+    );
+    assert_eq!(listed("parent=en:snacks").await, json!([]));
+
+    let status = harness
+        .http
+        .get(format!("{}/api/v1/categories?limit=0", harness.catalog_url))
+        .send()
+        .await
+        .unwrap()
+        .status();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn allergens_are_read_from_the_ingredients_text_in_memory() {
+    let harness = MemoryHarness::start().await;
+
+    let product: Value = harness
+        .http
+        .post(format!("{}/api/v1/products", harness.catalog_url))
+        .json(&json!({
+            "code": "4000417025005",
+            "ingredients_text": "Weizenmehl (45 %), Kokosmilch, Sojalecithin. Kann Spuren von Nüssen enthalten."
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        product["allergens_tags"],
+        json!(["en:gluten", "en:soybeans"])
+    );
+    let by_id = format!(
+        "{}/api/v1/products/{}",
+        harness.catalog_url,
+        product["_id"]["$oid"].as_str().unwrap()
+    );
+
+    let updated: Value = harness
+        .http
+        .patch(&by_id)
+        .json(&json!({ "ingredients_text": "Reismehl, Vollmilchpulver" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(updated["allergens_tags"], json!(["en:milk"]));
+
+    // Allergens the caller sets win over the text.
+    let updated: Value = harness
+        .http
+        .patch(&by_id)
+        .json(&json!({ "ingredients_text": "Erdnüsse", "allergens_tags": ["en:sesame-seeds"] }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(updated["allergens_tags"], json!(["en:sesame-seeds"]));
+}
+
+#[tokio::test]
+async fn products_without_a_grade_are_scored_from_their_nutriments_in_memory() {
+    let harness = MemoryHarness::start().await;
+
+    let response = harness
+        .http
+        .post(format!("{}/api/v1/products", harness.catalog_url))
+        .json(&json!({
+            "code": "3017620422003",
+            "categories": ["en:spreads"],
+            "nutriments": {
+                "energy-kcal_100g": 539,
+                "sugars_100g": 56.3,
+                "saturated-fat_100g": 10.6,
+                "salt_100g": 0.107,
+                "proteins_100g": 6.3
+            }
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let product: Value = response.json().await.unwrap();
+    assert_eq!(product["nutrition_grade_fr"], "e");
+    assert_eq!(product["nutrition_grade_source"], "computed");
+    let id = product["_id"]["$oid"].as_str().unwrap().to_string();
+
+    let breakdown: Value = harness
+        .http
+        .get(format!(
+            "{}/api/v1/products/{}/nutriscore",
+            harness.catalog_url, id
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(breakdown["grade"], "e");
+    assert_eq!(breakdown["score"], 26);
+    assert_eq!(breakdown["negative"][1]["nutrient"], "sugars");
+    assert_eq!(breakdown["negative"][1]["points"], 10);
+    assert_eq!(breakdown["positive"][2]["counted"], false);
+
+    // A declared grade replaces the computed one.
+    let updated: Value = harness
+        .http
+        .put(format!("{}/api/v1/products/{}", harness.catalog_url, id))
+        .json(&json!({ "nutrition_grade_fr": "d" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(updated["nutrition_grade_fr"], "d");
+    assert!(updated.get("nutrition_grade_source").is_none());
+}
+
+#[tokio::test]
+async fn health_reports_no_storage_checks_in_memory() {
+    let harness = MemoryHarness::start().await;
+
+    for url in [
+        &harness.profile_url,
+        &harness.catalog_url,
+        &harness.checker_url,
+    ] {
+        let response = harness
+            .http
+            .get(format!("{}/health/ready", url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{}", url);
+        let ready: Value = response.json().await.unwrap();
+        assert_eq!(ready["checks"], json!([]), "{}: {}", url, ready);
+    }
+
+    let details: Value = harness
+        .http
+        .get(format!("{}/health/details", harness.catalog_url))
+        .header(INTERNAL_TOKEN_HEADER, INTERNAL_TOKEN)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let names: Vec<&str> = details["checks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|check| check["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        ["user-profile-service", "user-profile-service-circuit"]
+    );
+}
+
+#[tokio::test]
+async fn every_service_serves_its_openapi_document_in_memory() {
+    let harness = MemoryHarness::start().await;
+
+    for (url, path) in [
+        (&harness.profile_url, "/api/v2/users/{user_id}/profile"),
+        (&harness.catalog_url, "/api/v1/products/search"),
+        (&harness.checker_url, "/api/v1/check"),
+    ] {
+        let response = harness
+            .http
+            .get(format!("{}/api-docs/openapi.json", url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{}", url);
+        let spec: Value = response.json().await.unwrap();
+        assert!(spec["paths"][path].is_object(), "{}: {}", url, path);
+
+        let docs = harness
+            .http
+            .get(format!("{}/docs/", url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(docs.status(), StatusCode::OK, "{}", url);
+    }
+}
+
+#[tokio::test]
+async fn batch_barcode_lookup_in_memory() {
+    let harness = MemoryHarness::start().await;
+    harness.seed_product(&ProductBuilder::new("4000417025005").build());
+    harness.seed_product(&ProductBuilder::new("3017620422003").build());
+    let url = format!("{}/api/v1/products/batch", harness.catalog_url);
+
+    // The second request is answered from the cache the first one filled.
+    for _ in 0..2 {
+        let response = harness
+            .http
+            .post(&url)
+            .json(&json!({
+                "codes": ["4000417025005", "0000000000000", "3017620422003", "4000417025005"]
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = response.json().await.unwrap();
+        let products = body["products"].as_object().unwrap();
+        assert_eq!(
+            products.keys().collect::<Vec<_>>(),
+            ["3017620422003", "4000417025005"]
+        );
+        assert_eq!(products["4000417025005"]["code"], "4000417025005");
+        assert_eq!(body["not_found"], json!(["0000000000000"]));
+    }
+
+    let too_many: Vec<String> = (0..101).map(|n| format!("{:013}", n)).collect();
+    for (codes, status) in [
+        (json!([]), StatusCode::BAD_REQUEST),
+        (json!(too_many), StatusCode::UNPROCESSABLE_ENTITY),
+    ] {
+        let response = harness
+            .http
+            .post(&url)
+            .json(&json!({ "codes": codes }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), status);
+    }
+}
+
+#[tokio::test]
+async fn products_are_named_in_the_readers_language_in_memory() {
+    let harness = MemoryHarness::start().await;
+    let product = ProductBuilder::new("4000417025005")
+        .name("Alpine milk chocolate")
+        .name_in("de", "Alpenmilch Schokolade")
+        .name_in("fr", "Chocolat au lait des Alpes")
+        .build();
+    let id = product.id.unwrap().to_hex();
+    harness.seed_product(&product);
+    let get = |path: String, accept_language: &'static str| {
+        let request = harness.http.get(format!("{}{}", harness.catalog_url, path));
+        async move {
+            let response = request
+                .header("Accept-Language", accept_language)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            response.json::<Value>().await.unwrap()
+        }
+    };
+
+    let german = get(format!("/api/v2/products/{}", id), "de-AT,en;q=0.5").await;
+    assert_eq!(german["displayName"], "Alpenmilch Schokolade");
+    assert_eq!(german["name"], "Alpine milk chocolate");
+    assert_eq!(german["nameLangs"]["fr"], "Chocolat au lait des Alpes");
+
+    let path = "/api/v2/products/barcode/4000417025005?lang=fr".to_string();
+    assert_eq!(
+        get(path, "de").await["displayName"],
+        "Chocolat au lait des Alpes"
+    );
+    // v1 is frozen and names nothing.
+    let path = "/api/v1/products/barcode/4000417025005?lang=fr".to_string();
+    assert!(get(path, "de").await.get("display_name").is_none());
+    let path = format!("/api/v2/products/{}?fields=code,displayName", id);
+    assert_eq!(
+        get(path, "es, it;q=0.5").await,
+        json!({ "code": "4000417025005", "displayName": "Alpine milk chocolate" })
+    );
+
+    let search = get("/api/v2/products/search?q=chocolate".to_string(), "de").await;
+    assert_eq!(search["items"][0]["displayName"], "Alpenmilch Schokolade");
+    let search = get("/api/v1/products/search?q=chocolate".to_string(), "de").await;
+    assert!(search["items"][0].get("display_name").is_none());
+    let summaries = get(
+        "/api/v2/products/search?q=chocolate&view=summary&lang=fr".to_string(),
+        "de",
+    )
+    .await;
+    assert_eq!(
+        summaries["items"][0]["displayName"],
+        "Chocolat au lait des Alpes"
+    );
+    // The cached page serves other languages too.
+    let again = get(
+        "/api/v2/products/search?q=chocolate&view=summary".to_string(),
+        "de",
+    )
+    .await;
+    assert_eq!(again["items"][0]["displayName"], "Alpenmilch Schokolade");
+}
+
+#[tokio::test]
+async fn search_pages_by_cursor_or_offset_in_memory() {
+    let harness = MemoryHarness::start().await;
+    for code in ["1000000000001", "1000000000002", "1000000000003"] {
+        harness.seed_product(&ProductBuilder::new(code).name("Oat biscuits").build());
+    }
+    let search = |query: &str| {
+        harness
+            .http
+            .get(format!(
+                "{}/api/v1/products/search?q=biscuits&limit=2{}",
+                harness.catalog_url, query
+            ))
+            .send()
+    };
+    let codes = |page: &Value| -> Vec<String> {
+        page["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["code"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let first: Value = search("").await.unwrap().json().await.unwrap();
+    assert_eq!(codes(&first), ["1000000000001", "1000000000002"]);
+    assert_eq!(first["total"], 3);
+    let cursor = first["nextCursor"]
+        .as_str()
+        .expect("a full page has a cursor");
+
+    let second: Value = search(&format!("&cursor={}", cursor))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(codes(&second), ["1000000000003"]);
+    assert_eq!(second["nextCursor"], Value::Null);
+
+    let by_offset: Value = search("&offset=2&include_total=false")
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(codes(&by_offset), ["1000000000003"]);
+    assert_eq!(by_offset["total"], Value::Null);
+
+    let garbled = search("&cursor=not-a-cursor").await.unwrap();
+    assert_eq!(garbled.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn search_pages_know_whether_another_follows_in_memory() {
+    let harness = MemoryHarness::start().await;
+    for code in [
+        "1000000000001",
+        "1000000000002",
+        "1000000000003",
+        "1000000000004",
+    ] {
+        harness.seed_product(&ProductBuilder::new(code).name("Rye crackers").build());
+    }
+    let search = |query: String| {
+        let request = harness.http.get(format!(
+            "{}/api/v1/products/search?q=crackers{}",
+            harness.catalog_url, query
+        ));
+        async move { request.send().await.unwrap().json::<Value>().await.unwrap() }
+    };
+    let more = |page: &Value| {
+        (
+            page["items"].as_array().unwrap().len(),
+            page["hasMore"].clone(),
+        )
+    };
+
+    // One product past the page.
+    let first = search("&limit=3".to_string()).await;
+    assert_eq!(more(&first), (3, json!(true)));
+    let cursor = first["nextCursor"].as_str().unwrap();
+    let rest = search(format!("&limit=3&cursor={}", cursor)).await;
+    assert_eq!(more(&rest), (1, json!(false)));
+    assert_eq!(rest["nextCursor"], Value::Null);
+
+    // Pages that end exactly at the last product.
+    let all = search("&limit=4".to_string()).await;
+    assert_eq!(more(&all), (4, json!(false)));
+    assert_eq!(all["nextCursor"], Value::Null);
+    let halves = search("&limit=2".to_string()).await;
+    assert_eq!(more(&halves), (2, json!(true)));
+    let cursor = halves["nextCursor"].as_str().unwrap();
+    let second_half = search(format!("&limit=2&cursor={}", cursor)).await;
+    assert_eq!(more(&second_half), (2, json!(false)));
+    let by_offset = search("&limit=2&offset=2&include_total=false".to_string()).await;
+    assert_eq!(more(&by_offset), (2, json!(false)));
+
+    // Summaries and v2 pages carry it too.
+    let summaries = search("&limit=3&view=summary".to_string()).await;
+    assert_eq!(more(&summaries), (3, json!(true)));
+    let v2: Value = harness
+        .http
+        .get(format!(
+            "{}/api/v2/products/search?q=crackers&limit=4",
+            harness.catalog_url
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(more(&v2), (4, json!(false)));
+}
+
+#[tokio::test]
+async fn large_responses_are_compressed_for_clients_that_accept_it_in_memory() {
+    let harness = MemoryHarness::start().await;
+    for i in 0..40 {
+        harness.seed_product(
+            &ProductBuilder::new(&format!("10000000000{:02}", i))
+                .name("Oat biscuits")
+                .ingredients("oat flakes, wholegrain wheat flour, cane sugar, sunflower oil")
+                .build(),
+        );
+    }
+    let get = |url: String, accept: &'static str| {
+        let request = harness.http.get(url).header("accept-encoding", accept);
+        async move { request.send().await.unwrap() }
+    };
+    let search = format!(
+        "{}/api/v1/products/search?q=biscuits&limit=40",
+        harness.catalog_url
+    );
+
+    for encoding in ["gzip", "br"] {
+        let response = get(search.clone(), encoding).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-encoding"], encoding);
+    }
+    let plain = harness.http.get(&search).send().await.unwrap();
+    assert!(!plain.headers().contains_key("content-encoding"));
+    let page: Value = plain.json().await.unwrap();
+    assert_eq!(page["items"].as_array().unwrap().len(), 40);
+
+    for url in [
+        &harness.catalog_url,
+        &harness.profile_url,
+        &harness.checker_url,
+    ] {
+        let health = get(format!("{}/health/live", url), "gzip, br").await;
+        assert_eq!(health.status(), StatusCode::OK);
+        assert!(!health.headers().contains_key("content-encoding"));
+    }
+}
+
+#[tokio::test]
+async fn search_lists_summaries_on_request_or_on_large_v2_pages_in_memory() {
+    let harness = MemoryHarness::start().await;
+    let ingredients = "oat flakes, wholegrain wheat flour, cane sugar, sunflower oil, ".repeat(30);
+    for code in ["1000000000001", "1000000000002", "1000000000003"] {
+        harness.seed_product(
+            &ProductBuilder::new(code)
+                .name("Oat biscuits")
+                .ingredients(&ingredients)
+                .build(),
+        );
+    }
+    let search = |version: &str, query: &str| {
+        let request = harness.http.get(format!(
+            "{}/api/{}/products/search?q=biscuits{}",
+            harness.catalog_url, version, query
+        ));
+        let query = query.to_string();
+        async move {
+            let response = request.send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{}", query);
+            response.text().await.unwrap()
+        }
+    };
+    let item_fields = |body: &str| -> Vec<String> {
+        let page: Value = serde_json::from_str(body).unwrap();
+        let mut fields: Vec<String> = page["items"][0]
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        fields.sort();
+        fields
+    };
+
+    // v1 lists full products unless asked, whatever the page size.
+    let full = search("v1", "").await;
+    assert_eq!(search("v1", "&view=full").await, full);
+    assert_eq!(search("v1", "&limit=100").await, full);
+    let summary = search("v1", "&view=summary").await;
+    assert!(
+        summary.len() * 5 < full.len(),
+        "{} vs {}",
+        summary.len(),
+        full.len()
+    );
+    assert_eq!(
+        item_fields(&summary),
+        [
+            "_id",
+            "allergens_tags",
+            "brands_tags",
+            "code",
+            "image_small_url",
+            "nutrition_grade_fr",
+            "product_name"
+        ]
+    );
+    let page: Value = serde_json::from_str(&summary).unwrap();
+    assert_eq!(page["total"], 3);
+
+    // v2 turns to summaries above 50 to a page.
+    let v2_summary = item_fields(&search("v2", "&limit=51").await);
+    assert_eq!(
+        v2_summary,
+        [
+            "allergens",
+            "brands",
+            "code",
+            "displayName",
+            "id",
+            "imageSmallUrl",
+            "name",
+            "nutriscore"
+        ]
+    );
+    assert_eq!(
+        item_fields(&search("v2", "&view=summary").await),
+        v2_summary
+    );
+    assert!(item_fields(&search("v2", "&limit=50").await).contains(&"ingredientsText".to_string()));
+    assert!(
+        item_fields(&search("v2", "&limit=100&view=full").await)
+            .contains(&"ingredientsText".to_string())
+    );
+
+    let response = harness
+        .http
+        .get(format!(
+            "{}/api/v1/products/search?view=compact",
+            harness.catalog_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn text_search_ranks_by_relevance_in_memory() {
+    let harness = MemoryHarness::start().await;
+    for (code, name) in [
+        ("1000000000001", "Hazelnut spread"),
+        ("1000000000002", "Nutella hazelnut spread"),
+        ("1000000000003", "Nutella biscuits"),
+    ] {
+        harness.seed_product(&ProductBuilder::new(code).name(name).build());
+    }
+    let search = |query: &str| {
+        harness
+            .http
+            .get(format!(
+                "{}/api/v1/products/search?q=nutella%20hazelnut&limit=2{}",
+                harness.catalog_url, query
+            ))
+            .send()
+    };
+
+    let first: Value = search("&debug=true").await.unwrap().json().await.unwrap();
+    assert_eq!(first["items"][0]["code"], "1000000000002");
+    assert_eq!(first["items"][0]["_score"], 2.0);
+    assert_eq!(first["items"][1]["_score"], 1.0);
+    let cursor = first["nextCursor"].as_str().unwrap();
+    let second: Value = search(&format!("&cursor={}", cursor))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(second["items"][0]["code"], "1000000000003");
+    assert!(second["items"][0].get("_score").is_none());
+
+    let by_id: Value = search("&sort=id").await.unwrap().json().await.unwrap();
+    assert_eq!(by_id["items"][0]["code"], "1000000000001");
+    let mixed = search(&format!("&sort=id&cursor={}", cursor))
+        .await
+        .unwrap();
+    assert_eq!(mixed.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn search_takes_repeated_or_comma_separated_brands_in_memory() {
+    let harness = MemoryHarness::start().await;
+    for (code, brand) in [
+        ("1000000000001", "milka"),
+        ("1000000000002", "lindt"),
+        ("1000000000003", "haribo"),
+    ] {
+        harness.seed_product(&ProductBuilder::new(code).brands(&[brand]).build());
+    }
+    let search = |query: &str| {
+        let request = harness.http.get(format!(
+            "{}/api/v1/products/search?{}",
+            harness.catalog_url, query
+        ));
+        async move {
+            let page: Value = request.send().await.unwrap().json().await.unwrap();
+            page["items"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|p| p["code"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        }
+    };
+
+    let milka_or_haribo = ["1000000000001", "1000000000003"];
+    assert_eq!(search("brand=milka&brand=Haribo").await, milka_or_haribo);
+    assert_eq!(search("brand=milka,%20haribo,").await, milka_or_haribo);
+    assert_eq!(search("brand=,").await.len(), 3);
+}
+
+#[tokio::test]
+async fn search_bounds_nutriments_in_memory() {
+    let harness = MemoryHarness::start().await;
+    let with_sugars = |code: &str, sugars: f64| {
+        ProductBuilder::new(code)
+            .nutriments(Nutriments {
+                sugars_100g: Some(sugars),
+                ..Default::default()
+            })
+            .build()
+    };
+    harness.seed_product(&with_sugars("1000000000001", 56.3));
+    harness.seed_product(&with_sugars("1000000000002", 4.0));
+    harness.seed_product(&ProductBuilder::new("1000000000003").build());
+    let search = |query: &str| {
+        harness
+            .http
+            .get(format!(
+                "{}/api/v1/products/search?{}",
+                harness.catalog_url, query
+            ))
+            .send()
+    };
+
+    let low_sugar: Value = search("max_sugar=5").await.unwrap().json().await.unwrap();
+    assert_eq!(low_sugar["total"], 1);
+    assert_eq!(low_sugar["items"][0]["code"], "1000000000002");
+    assert_eq!(low_sugar["items"][0]["nutriments"]["sugars_100g"], 4.0);
+
+    let negative = search("max_sugar=-1").await.unwrap();
+    assert_eq!(negative.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn search_counts_come_from_the_count_route_or_a_header_in_memory() {
+    let harness = MemoryHarness::start().await;
+    for (code, brand, country) in [
+        ("1000000000001", "milka", "en:germany"),
+        ("1000000000002", "milka", "en:france"),
+        ("1000000000003", "lindt", "en:germany"),
+    ] {
+        let mut product = ProductBuilder::new(code).brands(&[brand]).build();
+        product.countries = Some(vec![country.to_string()]);
+        harness.seed_product(&product);
+    }
+    let get = |path: String| {
+        let request = harness
+            .http
+            .get(format!("{}/api{}", harness.catalog_url, path));
+        async move { request.send().await.unwrap() }
+    };
+    async fn count(harness: &MemoryHarness, query: &str) -> u64 {
+        let response = harness
+            .http
+            .get(format!(
+                "{}/api/v1/products/count{}",
+                harness.catalog_url, query
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{}", query);
+        let body: Value = response.json().await.unwrap();
+        body["count"].as_u64().unwrap()
+    }
+
+    assert_eq!(count(&harness, "").await, 3);
+    assert_eq!(count(&harness, "?country=En:Germany").await, 2);
+    assert_eq!(count(&harness, "?brand=milka&country=en:germany").await, 1);
+    assert_eq!(
+        get("/v1/products/count?max_sugar=-1".to_string())
+            .await
+            .status(),
+        StatusCode::BAD_REQUEST
+    );
+
+    for version in ["v1", "v2"] {
+        let response = get(format!(
+            "/{}/products/search?include_count=true&include_total=false&limit=1",
+            version
+        ))
+        .await;
+        assert_eq!(response.headers()["x-total-count"], "3", "{}", version);
+        let page: Value = response.json().await.unwrap();
+        assert_eq!(page["total"], Value::Null, "{}", version);
+        assert_eq!(page["items"].as_array().unwrap().len(), 1, "{}", version);
+
+        let response = get(format!("/{}/products/search?brand=lindt", version)).await;
+        assert!(response.headers().get("x-total-count").is_none());
+        let page: Value = response.json().await.unwrap();
+        assert_eq!(page["total"], 1, "{}", version);
+    }
+
+    // Unfiltered and single-country counts are cached for a minute; others are not.
+    let mut late = ProductBuilder::new("1000000000004")
+        .brands(&["milka"])
+        .build();
+    late.countries = Some(vec!["en:germany".to_string()]);
+    harness.seed_product(&late);
+    assert_eq!(count(&harness, "").await, 3);
+    assert_eq!(count(&harness, "?country=en:germany").await, 2);
+    assert_eq!(count(&harness, "?brand=milka&country=en:germany").await, 2);
+}
+
+#[tokio::test]
+async fn search_pages_are_cached_per_query_until_turned_off_in_memory() {
+    let harness = MemoryHarness::start().await;
+    harness.seed_product(
+        &ProductBuilder::new("1000000000001")
+            .name("Milk chocolate")
+            .allergens(&["en:milk"])
+            .build(),
+    );
+    let codes = |query: &str| {
+        let request = harness.http.get(format!(
+            "{}/api/v1/products/search?include_total=false{}",
+            harness.catalog_url, query
+        ));
+        async move {
+            let page: Value = request.send().await.unwrap().json().await.unwrap();
+            page["items"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|item| item["code"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        }
+    };
+
+    assert_eq!(codes("").await, ["1000000000001"]);
+    // Seeding skips the API, but writes through it leave cached pages alone too.
+    harness.seed_product(
+        &ProductBuilder::new("1000000000002")
+            .name("Dark chocolate")
+            .build(),
+    );
+    assert_eq!(codes("").await, ["1000000000001"]);
+    assert_eq!(codes("&diets=vegan").await.len(), 2);
+    assert_eq!(codes("&allergens=milk").await, ["1000000000002"]);
+
+    let response = harness
+        .http
+        .put(format!("{}/internal/v1/config", harness.catalog_url))
+        .header(INTERNAL_TOKEN_HEADER, INTERNAL_TOKEN)
+        .json(&json!({ "search_cache_enabled": false }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(codes("").await, ["1000000000001", "1000000000002"]);
+}
+
+#[tokio::test]
+async fn random_products_vary_and_honor_the_filters_in_memory() {
+    let harness = MemoryHarness::start().await;
+    for i in 0..20 {
+        let mut product = ProductBuilder::new(&format!("10000000000{:02}", i)).build();
+        product.countries = Some(vec!["en:germany".to_string()]);
+        harness.seed_product(&product);
+    }
+    let mut peanuts = ProductBuilder::new("2000000000001")
+        .allergens(&["en:peanuts"])
+        .build();
+    peanuts.countries = Some(vec!["en:germany".to_string()]);
+    harness.seed_product(&peanuts);
+    let mut blank = ProductBuilder::new("2000000000002").build();
+    blank.product_name = None;
+    blank.countries = Some(vec!["en:germany".to_string()]);
+    harness.seed_product(&blank);
+
+    let random = |query: &str| {
+        let request = harness.http.get(format!(
+            "{}/api/v1/products/random{}",
+            harness.catalog_url, query
+        ));
+        async move {
+            let response = request.send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let products: Vec<Value> = response.json().await.unwrap();
+            products
+                .iter()
+                .map(|p| p["code"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        }
+    };
+
+    let mut seen = HashSet::new();
+    for _ in 0..20 {
+        let codes = random("?country=en:germany&allergens=peanuts").await;
+        assert_eq!(codes.len(), 1);
+        assert!(codes[0].starts_with("10000000000"), "{:?}", codes);
+        seen.insert(codes[0].clone());
+    }
+    assert!(seen.len() > 1, "the same product 20 times: {:?}", seen);
+
+    let five = random("?count=5&country=en:germany").await;
+    assert_eq!(five.len(), 5);
+    assert_eq!(five.iter().collect::<HashSet<_>>().len(), 5);
+    // Nor is the product without a name or an image, however often they are drawn.
+    for _ in 0..10 {
+        let codes = random("?count=10").await;
+        assert_eq!(codes.len(), 10);
+        assert!(!codes.contains(&"2000000000002".to_string()), "{:?}", codes);
+    }
+    assert_eq!(random("?country=Atlantis").await, Vec::<String>::new());
+
+    for query in ["?count=0", "?count=11", "?max_sugar=-1"] {
+        let response = harness
+            .http
+            .get(format!(
+                "{}/api/v1/products/random{}",
+                harness.catalog_url, query
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", query);
+    }
+}
+
+#[tokio::test]
+async fn changes_list_modified_products_and_tombstones_in_memory() {
+    let harness = MemoryHarness::start().await;
+    let now = Utc::now();
+    let mut ids = Vec::new();
+    for (code, hours_ago) in [
+        ("1000000000001", 2),
+        ("1000000000002", 1),
+        ("1000000000003", 72),
+    ] {
+        let modified = now - Duration::hours(hours_ago);
+        let product = ProductBuilder::new(code)
+            .timestamps(modified, modified)
+            .build();
+        ids.push(product.id.unwrap().to_hex());
+        harness.seed_product(&product);
+    }
+    let response = harness
+        .http
+        .delete(format!(
+            "{}/api/v1/products/{}",
+            harness.catalog_url, ids[1]
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let changes = |query: String| {
+        let request = harness.http.get(format!(
+            "{}/api/v1/products/changes{}",
+            harness.catalog_url, query
+        ));
+        async move { request.send().await.unwrap() }
+    };
+    let since = (now - Duration::days(1)).to_rfc3339_opts(SecondsFormat::Secs, true);
+    let first: Value = changes(format!("?since={}&limit=1", since))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(first["items"].as_array().unwrap().len(), 1);
+    assert_eq!(first["items"][0]["change"], "modified");
+    assert_eq!(first["items"][0]["code"], "1000000000001");
+
+    let cursor = first["nextCursor"].as_str().unwrap();
+    let second: Value = changes(format!("?since={}&limit=1&cursor={}", since, cursor))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(second["items"].as_array().unwrap().len(), 1);
+    let tombstone = &second["items"][0];
+    assert_eq!(tombstone["change"], "deleted");
+    assert_eq!(tombstone["code"], "1000000000002");
+    assert_eq!(tombstone["_id"]["$oid"], ids[1]);
+    assert!(tombstone["deleted_datetime"].is_string());
+    assert_eq!(second["nextCursor"], Value::Null);
+    assert_eq!(second["hasMore"], false);
+
+    let too_old = (now - Duration::days(31)).to_rfc3339_opts(SecondsFormat::Secs, true);
+    let response = changes(format!("?since={}", too_old)).await;
+    assert_eq!(response.status(), StatusCode::GONE);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "resync_required");
+
+    for query in ["", "?since=yesterday", "?since=2024-06-01"] {
+        let response = changes(query.to_string()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", query);
+    }
+}
+
+#[tokio::test]
+async fn recent_products_follow_creates_and_updates_in_memory() {
+    let harness = MemoryHarness::start().await;
+    let mut ids = Vec::new();
+    for (code, country) in [
+        ("1000000000016", "Germany"),
+        ("1000000000023", "France"),
+        ("1000000000030", "Germany"),
+    ] {
+        let created: Value = harness
+            .http
+            .post(format!("{}/api/v1/products", harness.catalog_url))
+            .json(&json!({ "code": code }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let id = created["_id"]["$oid"].as_str().unwrap().to_string();
+        // Creates take no countries; setting them leaves the update order the creation order.
+        let response = harness
+            .http
+            .patch(format!("{}/api/v1/products/{}", harness.catalog_url, id))
+            .json(&json!({ "countries_tags": [country] }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        ids.push(id);
+    }
+    for id in [&ids[1], &ids[0]] {
+        let response = harness
+            .http
+            .patch(format!("{}/api/v1/products/{}", harness.catalog_url, id))
+            .json(&json!({ "product_name": "Renamed" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let recent = |query: String| {
+        let request = harness.http.get(format!(
+            "{}/api/v1/products/recent{}",
+            harness.catalog_url, query
+        ));
+        async move {
+            let response = request.send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            response.json::<Value>().await.unwrap()
+        }
+    };
+    let codes = |page: &Value| -> Vec<String> {
+        page["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|product| product["code"].as_str().unwrap().to_string())
+            .collect()
+    };
+    assert_eq!(
+        codes(&recent(String::new()).await),
+        ["1000000000030", "1000000000023", "1000000000016"]
+    );
+    assert_eq!(
+        codes(&recent("?kind=updated".to_string()).await),
+        ["1000000000016", "1000000000023", "1000000000030"]
+    );
+    assert_eq!(
+        codes(&recent("?kind=created&country=germany".to_string()).await),
+        ["1000000000030", "1000000000016"]
+    );
+
+    let first = recent("?kind=updated&limit=2".to_string()).await;
+    assert_eq!(codes(&first), ["1000000000016", "1000000000023"]);
+    assert!(
+        first["items"][0].get("ingredients_text").is_none(),
+        "a summary"
+    );
+    let cursor = first["nextCursor"].as_str().unwrap();
+    let second = recent(format!("?kind=updated&limit=2&cursor={}", cursor)).await;
+    assert_eq!(codes(&second), ["1000000000030"]);
+    assert_eq!(second["hasMore"], false);
+
+    let response = harness
+        .http
+        .get(format!(
+            "{}/api/v1/products/recent?kind=newest",
+            harness.catalog_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn popular_products_are_the_most_scanned_in_memory() {
+    let harness = MemoryHarness::start().await;
+    for (code, country, scans) in [
+        ("1000000000016", "en:germany", 1),
+        ("1000000000023", "en:france", 3),
+        ("1000000000030", "en:germany", 2),
+    ] {
+        let mut product = ProductBuilder::new(code).build();
+        product.countries = Some(vec![country.to_string()]);
+        harness.seed_product(&product);
+        for version in ["v1", "v2"].iter().cycle().take(scans) {
+            let response = harness
+                .http
+                .get(format!(
+                    "{}/api/{}/products/barcode/{}",
+                    harness.catalog_url, version, code
+                ))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+
+    let popular = |query: &str| {
+        let request = harness.http.get(format!(
+            "{}/api/v1/products/popular{}",
+            harness.catalog_url, query
+        ));
+        async move {
+            let response = request.send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let products: Value = response.json().await.unwrap();
+            products
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|product| {
+                    let code = product["code"].as_str().unwrap().to_string();
+                    (code, product["scans"].as_u64().unwrap())
+                })
+                .collect::<Vec<_>>()
+        }
+    };
+    // Scans are counted after the lookups have answered.
+    let mut listed = Vec::new();
+    for _ in 0..50 {
+        listed = popular("").await;
+        if listed.iter().map(|(_, scans)| scans).sum::<u64>() == 6 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(
+        listed,
+        [
+            ("1000000000023".to_string(), 3),
+            ("1000000000030".to_string(), 2),
+            ("1000000000016".to_string(), 1),
+        ]
+    );
+    assert_eq!(
+        popular("?window=1d&limit=1").await,
+        [("1000000000023".to_string(), 3)]
+    );
+    assert_eq!(
+        popular("?country=germany").await,
+        [
+            ("1000000000030".to_string(), 2),
+            ("1000000000016".to_string(), 1),
+        ]
+    );
+
+    for query in ["?window=31d", "?window=week", "?limit=0"] {
+        let response = harness
+            .http
+            .get(format!(
+                "{}/api/v1/products/popular{}",
+                harness.catalog_url, query
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", query);
+    }
+}
+
+#[tokio::test]
+async fn semantic_search_finds_nothing_without_an_index_in_memory() {
+    let harness = MemoryHarness::start().await;
+    harness.seed_product(&ProductBuilder::new("4000417025005").build());
+
+    for version in ["v1", "v2"] {
+        let url = format!(
+            "{}/api/{}/products/search/semantic",
+            harness.catalog_url, version
+        );
+        let response = harness
+            .http
+            .get(format!("{}?q=chocolate&diets=vegan", url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.json::<Value>().await.unwrap(), json!([]));
+
+        let missing_q = harness.http.get(&url).send().await.unwrap();
+        assert_eq!(missing_q.status(), StatusCode::BAD_REQUEST);
+
+        let both = harness
+            .http
+            .post(&url)
+            .json(&json!({ "q": "chocolate", "vector": [0.1, 0.2] }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(both.status(), StatusCode::BAD_REQUEST);
+    }
+}
+
+#[tokio::test]
+async fn recommendation_params_are_checked_before_the_index_in_memory() {
+    let harness = MemoryHarness::start().await;
+    let product = ProductBuilder::new("4000417025005").build();
+    harness.seed_product(&product);
+    let id = product.id.unwrap().to_hex();
+
+    for version in ["v1", "v2"] {
+        let url = format!(
+            "{}/api/{}/products/{}/recommendations",
+            harness.catalog_url, version, id
+        );
+        for query in ["limit=0", "min_score=1.5", "min_score=-0.2"] {
+            let response = harness
+                .http
+                .get(format!("{}?{}", url, query))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", query);
+        }
+
+        // Sensible values, a limit over the cap included, get as far as the missing index.
+        let response = harness
+            .http
+            .get(format!("{}?limit=500&min_score=0.5", url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
+
+/// Signs the bearer tokens of [`token_harness`].
+const AUTH_SECRET: &str = "memory-auth-secret";
+
+/// A harness whose services take real HS256 bearer tokens, as [`bearer`] makes them.
+async fn token_harness() -> MemoryHarness {
+    MemoryHarness::start_with_authenticator(Authenticator::new(AuthConfig {
+        mode: AuthMode::Hs256 {
+            secret: AUTH_SECRET.to_string(),
+        },
+        audience: None,
+        issuer: None,
+    }))
+    .await
+}
+
+/// An `Authorization` value for `subject` that [`token_harness`] accepts.
+fn bearer(subject: &str) -> reqwest::header::HeaderValue {
+    let token = jsonwebtoken::encode(
+        &jsonwebtoken::Header::default(),
+        &json!({ "sub": subject, "exp": (Utc::now() + Duration::minutes(5)).timestamp() }),
+        &jsonwebtoken::EncodingKey::from_secret(AUTH_SECRET.as_bytes()),
+    )
+    .unwrap();
+    reqwest::header::HeaderValue::from_str(&format!("Bearer {}", token)).unwrap()
+}
+
+#[tokio::test]
+async fn recommendations_read_the_profile_with_the_callers_token_in_memory() {
+    let harness = token_harness().await;
+    harness.seed_profile(
+        &UserProfileBuilder::new("alice")
+            .allergens(&["milk"])
+            .build(),
+    );
+    let client = ResilientClient::new(reqwest::Client::new(), ResilienceConfig::default());
+    let profile = |user: &'static str, auth: Option<reqwest::header::HeaderValue>| {
+        let client = client.clone();
+        let url = harness.profile_url.clone();
+        async move { recommendation_profile(&client, &url, Some(user), auth.as_ref()).await }
+    };
+
+    let own = profile("alice", Some(bearer("alice"))).await.unwrap();
+    assert_eq!(own.expect("alice's profile").allergens, ["milk"]);
+    assert!(
+        profile("nobody", Some(bearer("nobody")))
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    // The profile route serves only the token's own subject: anything else is refused,
+    // and refused is not the same as anonymous.
+    for (authorization, status) in [(None, 401), (Some(bearer("mallory")), 403)] {
+        match profile("alice", authorization).await {
+            Err(ServiceError::Upstream(e)) => {
+                assert_eq!(e.kind, UpstreamErrorKind::Status(status))
+            }
+            other => panic!("{}: {:?}", status, other),
+        }
+    }
+}
+
+#[tokio::test]
+async fn patch_sets_unsets_and_leaves_fields_in_memory() {
+    let harness = MemoryHarness::start().await;
+    let product = ProductBuilder::new("4000417025005")
+        .labels(&["en:vegan"])
+        .quantity("500 g")
+        .image_url("https://images.example/old.jpg")
+        .timestamps(
+            "2024-01-01T00:00:00Z".parse().unwrap(),
+            "2024-01-01T00:00:00Z".parse().unwrap(),
+        )
+        .build();
+    harness.seed_product(&product);
+    let by_id = format!(
+        "{}/api/v1/products/{}",
+        harness.catalog_url,
+        product.id.unwrap().to_hex()
+    );
+    let by_code = format!(
+        "{}/api/v1/products/barcode/4000417025005",
+        harness.catalog_url
+    );
+    let get = |url: &str| {
+        let request = harness.http.get(url).send();
+        async move { request.await.unwrap().json::<Value>().await.unwrap() }
+    };
+    let patch = |body: Value| harness.http.patch(&by_id).json(&body).send();
+    // Both cache entries are filled before patching.
+    get(&by_id).await;
+    get(&by_code).await;
+
+    let response = patch(json!({ "image_url": null, "labels_tags": ["en:organic"] }))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let patched: Value = response.json().await.unwrap();
+    assert_eq!(patched["image_url"], Value::Null);
+    assert_eq!(patched["labels_tags"], json!(["en:organic"]));
+    assert_eq!(patched["quantity"], "500 g");
+    assert_ne!(
+        patched["last_modified_datetime"],
+        "2024-01-01T00:00:00.000Z"
+    );
+    assert_eq!(get(&by_id).await, patched);
+    assert_eq!(get(&by_code).await, patched);
+
+    let front =
+        "https://images.openfoodfacts.org/images/products/400/041/702/5005/front_de.400.jpg";
+    let patched: Value = patch(json!({
+        "image_url": front,
+        "labels_tags": null,
+        "quantity": null
+    }))
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    assert_eq!(patched["image_url"], front);
+    assert_eq!(
+        patched["image_small_url"],
+        front.replace(".400.jpg", ".200.jpg")
+    );
+    assert_eq!(
+        patched["image_thumb_url"],
+        front.replace(".400.jpg", ".100.jpg")
+    );
+    assert_eq!(patched["labels_tags"], Value::Null);
+    assert_eq!(patched["quantity"], Value::Null);
+    assert_eq!(get(&by_code).await, patched);
+
+    let not_a_url = patch(json!({ "image_url": "not a url" })).await.unwrap();
+    assert_eq!(not_a_url.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let elsewhere = patch(json!({ "image_url": "https://images.example/new.jpg" }))
+        .await
+        .unwrap();
+    assert_eq!(elsewhere.status(), StatusCode::BAD_REQUEST);
+    let missing = harness
+        .http
+        .patch(format!(
+            "{}/api/v1/products/{}",
+            harness.catalog_url,
+            bson::oid::ObjectId::new().to_hex()
+        ))
+        .json(&json!({ "quantity": null }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn created_products_take_images_only_from_allowed_hosts_in_memory() {
+    let harness = MemoryHarness::start().await;
+    let create = |code: &str, image_url: &str| {
+        harness
+            .http
+            .post(format!("{}/api/v1/products", harness.catalog_url))
+            .json(
+                &ProductBuilder::new(code)
+                    .image_url(image_url)
+                    .create_payload(),
+            )
+            .send()
+    };
+
+    let refused = create("1000000000016", "https://images.example/front.jpg")
+        .await
+        .unwrap();
+    assert_eq!(refused.status(), StatusCode::BAD_REQUEST);
+
+    let front =
+        "https://images.openfoodfacts.org/images/products/400/041/702/5005/front_de.full.jpg";
+    let response = create("4000417025005", front).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let created: Value = response.json().await.unwrap();
+    assert_eq!(created["image_url"], front);
+    assert_eq!(
+        created["image_small_url"],
+        front.replace(".full.jpg", ".200.jpg")
+    );
+    assert_eq!(
+        created["image_thumb_url"],
+        front.replace(".full.jpg", ".100.jpg")
+    );
+}
+
+#[tokio::test]
+async fn ecoscore_filters_alongside_nutriscore_in_memory() {
+    let harness = MemoryHarness::start().await;
+    for (code, nutriscore, ecoscore) in [
+        ("1000000000016", "a", "b"),
+        ("1000000000023", "a", "c"),
+        ("1000000000030", "c", "b"),
+    ] {
+        harness.seed_product(
+            &ProductBuilder::new(code)
+                .nutriscore(nutriscore)
+                .ecoscore(ecoscore)
+                .build(),
+        );
+    }
+    let search = |query: &'static str| {
+        harness
+            .http
+            .get(format!(
+                "{}/api/v1/products/search?{}",
+                harness.catalog_url, query
+            ))
+            .send()
+    };
+    let codes = |page: &Value| -> Vec<String> {
+        page["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["code"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let page: Value = search("nutriscore=a&ecoscore=B&view=summary")
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(codes(&page), ["1000000000016"]);
+    assert_eq!(page["items"][0]["ecoscore_grade"], "b");
+    let page: Value = search("ecoscore=b").await.unwrap().json().await.unwrap();
+    assert_eq!(codes(&page), ["1000000000016", "1000000000030"]);
+    let unknown = search("ecoscore=unknown").await.unwrap();
+    assert_eq!(unknown.status(), StatusCode::BAD_REQUEST);
+
+    let create = |grade: &str| {
+        harness
+            .http
+            .post(format!("{}/api/v1/products", harness.catalog_url))
+            .json(&json!({
+                "code": "4000417025005",
+                "ecoscore_grade": grade,
+                "ecoscore_score": 71.5
+            }))
+            .send()
+    };
+    let refused = create("f").await.unwrap();
+    assert_eq!(refused.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let response = create("A").await.unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let created: Value = response.json().await.unwrap();
+    assert_eq!(created["ecoscore_grade"], "a");
+    assert_eq!(created["ecoscore_score"], 71.5);
+}
+
+#[tokio::test]
+async fn unusable_bodies_name_the_offending_field_in_memory() {
+    let harness = MemoryHarness::start().await;
+    let product = ProductBuilder::new("4000417025005").build();
+    harness.seed_product(&product);
+    let profile = format!("{}/api/v1/users/alice/profile", harness.profile_url);
+    let by_id = format!(
+        "{}/api/v1/products/{}",
+        harness.catalog_url,
+        product.id.unwrap().to_hex()
+    );
+    let fields = |body: &Value| -> Vec<(String, String)> {
+        body["details"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|failure| {
+                let text = |key: &str| failure[key].as_str().unwrap().to_string();
+                (text("field"), text("message"))
+            })
+            .collect()
+    };
+
+    let response = harness
+        .http
+        .put(&profile)
+        .json(&json!({ "username": "al", "email": "not-an-email" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "validation_failed");
+    assert_eq!(
+        fields(&body),
+        [
+            ("email".to_string(), "Invalid email format".to_string()),
+            (
+                "username".to_string(),
+                "Username must be at least 3 characters long".to_string()
+            ),
+        ]
+    );
+
+    for body in [
+        json!({ "brands": "milka" }),
+        json!({ "brands": ["milka", 42] }),
+    ] {
+        let response = harness.http.put(&by_id).json(&body).send().await.unwrap();
+        assert_eq!(
+            response.status(),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "{}",
+            body
+        );
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["code"], "validation_failed");
+        assert_eq!(body["details"][0]["code"], "json");
+        assert!(
+            body["details"][0]["field"]
+                .as_str()
+                .unwrap()
+                .starts_with("brands"),
+            "{}",
+            body
+        );
+    }
+
+    let response = harness
+        .http
+        .put(&by_id)
+        .header("content-type", "application/json")
+        .body(r#"{"product_name": "Milk"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "invalid_request");
+    assert_eq!(body["details"][0]["field"], "product_name");
+
+    let response = harness
+        .http
+        .put(&profile)
+        .body(r#"{"username": "alice"}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "unsupported_media_type");
+}
+
+#[tokio::test]
+async fn errors_carry_a_stable_code_in_memory() {
+    let harness = MemoryHarness::start().await;
+    let create = || {
+        let payload = ProductBuilder::new("4000417025005").create_payload();
+        harness
+            .http
+            .post(format!("{}/api/v1/products", harness.catalog_url))
+            .json(&payload)
+            .send()
+    };
+    let code_of = |response: reqwest::Response| async move {
+        let status = response.status();
+        let body: Value = response.json().await.unwrap();
+        (status, body["code"].as_str().unwrap().to_string())
+    };
+
+    assert_eq!(create().await.unwrap().status(), StatusCode::CREATED);
+    assert_eq!(
+        code_of(create().await.unwrap()).await,
+        (StatusCode::CONFLICT, "product_conflict".to_string())
+    );
+
+    let missing = [
+        (
+            format!(
+                "{}/api/v1/products/barcode/4000417025012",
+                harness.catalog_url
+            ),
+            "product_not_found",
+        ),
+        (
+            format!(
+                "{}/api/v1/products/{}",
+                harness.catalog_url,
+                bson::oid::ObjectId::new().to_hex()
+            ),
+            "product_not_found",
+        ),
+        (
+            format!("{}/api/v1/products/not-an-id", harness.catalog_url),
+            "invalid_product_id",
+        ),
+        (
+            format!("{}/api/v1/users/nobody/profile", harness.profile_url),
+            "profile_not_found",
+        ),
+    ];
+    for (url, code) in missing {
+        let response = harness.http.get(&url).send().await.unwrap();
+        assert_eq!(code_of(response).await.1, code, "{}", url);
+    }
+}
+
+#[tokio::test]
+async fn import_reports_inserted_updated_and_skipped_lines_in_memory() {
+    let harness = MemoryHarness::start().await;
+    harness.seed_product(
+        &ProductBuilder::new("4000417025005")
+            .name("Old name")
+            .build(),
+    );
+    let by_code = format!(
+        "{}/api/v1/products/barcode/4000417025005",
+        harness.catalog_url
+    );
+    harness.http.get(&by_code).send().await.unwrap();
+
+    let dump = [
+        json!({ "code": "4000417025005", "product_name": "Alpenmilch" }).to_string(),
+        json!({ "code": "3017620422003", "product_name": "Nutella" }).to_string(),
+        String::new(),
+        "{\"code\": ".to_string(),
+        json!({ "code": "n/a" }).to_string(),
+        "[1, 2]".to_string(),
+        json!({ "code": "20724696", "brands_tags": ["Ritter Sport"] }).to_string(),
+    ]
+    .join("\n");
+    let response = harness
+        .http
+        .post(format!("{}/api/v2/products/import", harness.catalog_url))
+        .body(dump)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let report: Value = response.json().await.unwrap();
+    assert_eq!(report["inserted"], 2);
+    assert_eq!(report["updated"], 1);
+    assert_eq!(report["skipped"], 3);
+    let lines: Vec<&Value> = report["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| &e["line"])
+        .collect();
+    assert_eq!(lines, [4, 5, 6]);
+    assert_eq!(report["errors"][1]["message"], "code 'n/a' is not numeric");
+
+    let updated: Value = harness
+        .http
+        .get(&by_code)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(updated["product_name"], "Alpenmilch");
+}
+
+#[tokio::test]
+async fn product_history_records_who_changed_what_in_memory() {
+    let harness = token_harness().await;
+    let products = format!("{}/api/v1/products", harness.catalog_url);
+    let payload = ProductBuilder::new("4000417025005")
+        .name("Alpine milk chocolate")
+        .create_payload();
+    let created: Value = harness
+        .http
+        .post(&products)
+        .header(AUTHORIZATION, bearer("editor-1"))
+        .json(&payload)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let id = created["_id"]["$oid"].as_str().unwrap().to_string();
+    let by_id = format!("{}/{}", products, id);
+
+    let response = harness
+        .http
+        .put(&by_id)
+        .header(AUTHORIZATION, bearer("editor-2"))
+        .json(&json!({ "product_name": "Alpine dark chocolate" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    // Setting the name it already has changes nothing worth recording.
+    harness
+        .http
+        .put(&by_id)
+        .header(AUTHORIZATION, bearer("editor-2"))
+        .json(&json!({ "product_name": "Alpine dark chocolate" }))
+        .send()
+        .await
+        .unwrap();
+    // The actor is the token's subject, whatever the caller claims to be.
+    let response = harness
+        .http
+        .delete(&by_id)
+        .header(AUTHORIZATION, bearer("editor-3"))
+        .header("x-user-id", "editor-1")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let history: Value = harness
+        .http
+        .get(format!("{}/history?limit=2", by_id))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(history["total"], 3);
+    let entries = history["items"].as_array().unwrap();
+    assert_eq!(entries[0]["action"], "deleted");
+    assert_eq!(entries[0]["actor"], "editor-3");
+    assert_eq!(entries[1]["action"], "updated");
+    assert_eq!(entries[1]["actor"], "editor-2");
+    assert_eq!(
+        entries[1]["changes"],
+        json!([{
+            "field": "product_name",
+            "old": "Alpine milk chocolate",
+            "new": "Alpine dark chocolate"
+        }])
+    );
+
+    let cursor = history["nextCursor"].as_str().unwrap();
+    let rest: Value = harness
+        .http
+        .get(format!(
+            "{}/api/v2/products/{}/history?limit=2&cursor={}",
+            harness.catalog_url, id, cursor
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let entries = rest["items"].as_array().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["action"], "created");
+    assert_eq!(entries[0]["actor"], "editor-1");
+    assert_eq!(entries[0]["productId"], id.as_str());
+    assert_eq!(rest["nextCursor"], Value::Null);
+}
+
+#[tokio::test]
+async fn conditional_product_gets_until_an_update_in_memory() {
+    let harness = MemoryHarness::start().await;
+    let product = ProductBuilder::new("4000417025005")
+        .name("Alpine milk chocolate")
+        .build();
+    harness.seed_product(&product);
+    let id = product.id.unwrap().to_hex();
+    let by_id = format!("{}/api/v1/products/{}", harness.catalog_url, id);
+    let urls = [
+        by_id.clone(),
+        format!(
+            "{}/api/v1/products/barcode/4000417025005",
+            harness.catalog_url
+        ),
+        format!("{}/api/v2/products/{}", harness.catalog_url, id),
+    ];
+    let get = |url: &str, etag: Option<&str>| {
+        let mut request = harness.http.get(url);
+        if let Some(etag) = etag {
+            request = request.header("If-None-Match", etag);
+        }
+        async move { request.send().await.unwrap() }
+    };
+    let etag_of =
+        |response: &reqwest::Response| response.headers()["etag"].to_str().unwrap().to_string();
+
+    let first = get(&by_id, None).await;
+    assert_eq!(first.status(), StatusCode::OK);
+    let etag = etag_of(&first);
+    assert!(etag.starts_with("W/\""), "{}", etag);
+    // The first round fills the cache, the second is answered from it.
+    for _ in 0..2 {
+        for url in &urls {
+            let response = get(url, Some(&etag)).await;
+            assert_eq!(response.status(), StatusCode::NOT_MODIFIED, "{}", url);
+            assert_eq!(etag_of(&response), etag);
+            assert!(response.bytes().await.unwrap().is_empty());
+        }
+    }
+
+    let response = harness
+        .http
+        .put(&by_id)
+        .json(&json!({
+            "product_name": "Alpine dark chocolate",
+            "allergens_tags": ["Milk", "en:Peanuts", "soya", "unheard-of"],
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    for url in &urls {
+        let response = get(url, Some(&etag)).await;
+        assert_eq!(response.status(), StatusCode::OK, "{}", url);
+        let new_etag = etag_of(&response);
+        assert_ne!(new_etag, etag);
+        let body: Value = response.json().await.unwrap();
+        assert!(body.to_string().contains("Alpine dark chocolate"));
+        let allergens = body.get("allergens_tags").unwrap_or(&body["allergens"]);
+        assert_eq!(
+            allergens,
+            &json!(["en:milk", "en:peanuts", "en:soybeans"]),
+            "{}",
+            url
+        );
+        assert_eq!(
+            get(url, Some(&new_etag)).await.status(),
+            StatusCode::NOT_MODIFIED
+        );
+    }
+}
+
+#[tokio::test]
+async fn product_gets_project_to_the_requested_fields_in_memory() {
+    let harness = MemoryHarness::start().await;
+    let product = ProductBuilder::new("4000417025005")
+        .name("Alpine milk chocolate")
+        .build();
+    harness.seed_product(&product);
+    let get = |path: String| {
+        let request = harness.http.get(format!("{}{}", harness.catalog_url, path));
+        async move { request.send().await.unwrap() }
+    };
+
+    // The first lookup caches the whole product; the second is projected from the cache.
+    for _ in 0..2 {
+        let response = get(
+            "/api/v1/products/barcode/4000417025005?fields=product_name,image_small_url"
+                .to_string(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = response.json().await.unwrap();
+        let mut fields: Vec<&String> = body.as_object().unwrap().keys().collect();
+        fields.sort();
+        assert_eq!(fields, ["code", "image_small_url", "product_name"]);
+        assert_eq!(body["product_name"], "Alpine milk chocolate");
+    }
+
+    let id = product.id.unwrap().to_hex();
+    let body: Value = get(format!("/api/v2/products/{}?fields=name", id))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        body,
+        json!({ "code": "4000417025005", "name": "Alpine milk chocolate" })
+    );
+
+    let whole: Value = get(format!("/api/v1/products/{}", id))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert!(whole.get("created_datetime").is_some());
+
+    let response = get(format!("/api/v1/products/{}?fields=code,secret_sauce", id)).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "invalid_request");
+}
+
+#[tokio::test]
+async fn curators_list_the_least_complete_products_in_memory() {
+    let harness = MemoryHarness::start().await;
+    let sparse = ProductBuilder::new("1000000000001").build();
+    harness.seed_product(&sparse);
+    harness.seed_product(
+        &ProductBuilder::new("1000000000002")
+            .name("Alpine milk chocolate")
+            .ingredients("sugar, cocoa butter, milk powder")
+            .allergens(&["en:milk"])
+            .image_url("https://images.example/chocolate.jpg")
+            .quantity("100 g")
+            .build(),
+    );
+    let get = |path: &str| {
+        let request = harness
+            .http
+            .get(format!("{}/api/v1/products{}", harness.catalog_url, path));
+        async move { request.send().await.unwrap() }
+    };
+    let listed = |page: &Value| -> Vec<(String, u64)> {
+        page["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| {
+                (
+                    p["code"].as_str().unwrap().to_string(),
+                    p["completeness"].as_u64().unwrap(),
+                )
+            })
+            .collect()
+    };
+
+    let all: Value = get("/curation/incomplete").await.json().await.unwrap();
+    let scores = listed(&all);
+    assert_eq!(scores.len(), 2);
+    assert_eq!(scores[0].0, "1000000000001");
+    assert!(scores[0].1 < scores[1].1, "{:?}", scores);
+
+    let first: Value = get("/curation/incomplete?limit=1")
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(listed(&first), scores[..1]);
+    assert_eq!(first["hasMore"], true);
+    let cursor = first["nextCursor"].as_str().unwrap();
+    let second: Value = get(&format!("/curation/incomplete?limit=1&cursor={}", cursor))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(listed(&second), scores[1..]);
+    // The second page ends with the last product, and knows it.
+    assert_eq!(second["hasMore"], false);
+    assert_eq!(second["nextCursor"], Value::Null);
+
+    let below: Value = get(&format!("/curation/incomplete?max_score={}", scores[0].1))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(listed(&below), scores[..1]);
+    let elsewhere: Value = get("/curation/incomplete?country=Atlantis")
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert!(listed(&elsewhere).is_empty());
+    assert_eq!(
+        get("/curation/incomplete?max_score=101").await.status(),
+        StatusCode::BAD_REQUEST
+    );
+
+    // The product GETs give the same score when asked.
+    let id = sparse.id.unwrap().to_hex();
+    let product: Value = get(&format!("/{}?include_completeness=true", id))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(product["completeness"].as_u64(), Some(scores[0].1));
+    let product: Value = get(&format!("/{}", id)).await.json().await.unwrap();
+    assert!(product.get("completeness").is_none());
+}
+
+#[tokio::test]
+async fn duplicates_are_matched_by_name_without_an_index_in_memory() {
+    let harness = MemoryHarness::start().await;
+    let source = ProductBuilder::new("1000000000001")
+        .name("Ritter Sport Alpenmilch")
+        .build();
+    harness.seed_product(&source);
+    for (code, name) in [
+        ("1000000000002", "RITTER SPORT - alpenmilch"),
+        ("1000000000003", "Ritter Sport Alpenmilch Nuss"),
+        ("1000000000004", "Milka Alpenmilch"),
+    ] {
+        harness.seed_product(&ProductBuilder::new(code).name(name).build());
+    }
+    let url = format!(
+        "{}/api/v1/products/{}/duplicates",
+        harness.catalog_url,
+        source.id.unwrap().to_hex()
+    );
+
+    let response = harness.http.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let duplicates: Value = response.json().await.unwrap();
+    assert_eq!(duplicates["matched_by"], "name");
+    let candidates = duplicates["candidates"].as_array().unwrap();
+    assert_eq!(candidates.len(), 1);
+    assert_eq!(candidates[0]["code"], "1000000000002");
+    assert_eq!(candidates[0]["score"], Value::Null);
+    assert_eq!(candidates[0]["name_overlap"], 1.0);
+
+    for query in ["min_score=1.5", "limit=0"] {
+        let response = harness
+            .http
+            .get(format!("{}?{}", url, query))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", query);
+    }
+    let missing = harness
+        .http
+        .get(format!(
+            "{}/api/v1/products/65f0c0ffee0000000000beef/duplicates",
+            harness.catalog_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn missed_lookups_are_cached_until_the_product_is_created_in_memory() {
+    let harness = MemoryHarness::start().await;
+    let by_code = |code: &str| format!("{}/api/v1/products/barcode/{}", harness.catalog_url, code);
+    let status = |url: String| {
+        let request = harness.http.get(url).send();
+        async move { request.await.unwrap().status() }
+    };
+
+    // Seeding skips the API, so only the cached miss can explain the 404s that follow.
+    assert_eq!(
+        status(by_code("4000417025005")).await,
+        StatusCode::NOT_FOUND
+    );
+    harness.seed_product(&ProductBuilder::new("4000417025005").build());
+    assert_eq!(
+        status(by_code("4000417025005")).await,
+        StatusCode::NOT_FOUND
+    );
+    let batch: Value = harness
+        .http
+        .post(format!("{}/api/v1/products/batch", harness.catalog_url))
+        .json(&json!({ "codes": ["4000417025005"] }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(batch["not_found"], json!(["4000417025005"]));
+
+    // Creating through the API drops the cached miss.
+    assert_eq!(
+        status(by_code("7622210449283")).await,
+        StatusCode::NOT_FOUND
+    );
+    let response = harness
+        .http
+        .post(format!("{}/api/v1/products", harness.catalog_url))
+        .json(&ProductBuilder::new("7622210449283").create_payload())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(status(by_code("7622210449283")).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn product_reads_and_writes_work_without_redis_in_memory() {
+    // Nothing listens on port 1: every connection attempt is refused.
+    let dead_redis = redis::Client::open("redis://127.0.0.1:1/").unwrap();
+    let harness =
+        MemoryHarness::start_with_catalog_cache(Arc::new(RedisCache::new(dead_redis))).await;
+    let product = ProductBuilder::new("4000417025005")
+        .name("Alpine milk chocolate")
+        .build();
+    harness.seed_product(&product);
+    let by_id = format!(
+        "{}/api/v1/products/{}",
+        harness.catalog_url,
+        product.id.unwrap().to_hex()
+    );
+
+    for url in [
+        by_id.clone(),
+        format!(
+            "{}/api/v1/products/barcode/4000417025005",
+            harness.catalog_url
+        ),
+    ] {
+        let response = harness.http.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{}", url);
+    }
+    let batch = harness
+        .http
+        .post(format!("{}/api/v1/products/batch", harness.catalog_url))
+        .json(&json!({ "codes": ["4000417025005", "0000000000000"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(batch.status(), StatusCode::OK);
+    let batch: Value = batch.json().await.unwrap();
+    assert_eq!(batch["not_found"], json!(["0000000000000"]));
+
+    let response = harness
+        .http
+        .put(&by_id)
+        .json(&json!({ "product_name": "Alpine dark chocolate" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = harness.http.delete(&by_id).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = harness.http.get(&by_id).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+/// The next event the receiver got, once its signature is checked.
+async fn next_event(received: &mut UnboundedReceiver<(HeaderMap, Bytes)>) -> Value {
+    let (headers, body) = tokio::time::timeout(std::time::Duration::from_secs(5), received.recv())
+        .await
+        .expect("a webhook within 5 seconds")
+        .unwrap();
+    assert_eq!(
+        headers[SIGNATURE_HEADER],
+        webhooks::sign("partner-shared-secret", &body).as_str()
+    );
+    serde_json::from_slice(&body).unwrap()
+}
+
+#[tokio::test]
+async fn webhooks_get_signed_events_for_their_product_changes_in_memory() {
+    let harness = MemoryHarness::start().await;
+    let (sender, mut received) = mpsc::unbounded_channel();
+    let receiver = Router::new().route(
+        "/hook",
+        post(move |headers: HeaderMap, body: Bytes| {
+            let sender = sender.clone();
+            async move {
+                sender.send((headers, body)).unwrap();
+                StatusCode::NO_CONTENT
+            }
+        }),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let hook_url = format!("http://{}/hook", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, receiver).await.unwrap() });
+    let admin = format!("{}/api/v1/admin/webhooks", harness.catalog_url);
+    let registration = json!({
+        "url": hook_url,
+        "secret": "partner-shared-secret",
+        "events": ["product.created", "product.deleted"],
+    });
+    let response = harness
+        .http
+        .post(&admin)
+        .json(&registration)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = harness
+        .http
+        .post(&admin)
+        .header(INTERNAL_TOKEN_HEADER, INTERNAL_TOKEN)
+        .json(&json!({
+            "url": "ftp://partner.example/hook",
+            "secret": "partner-shared-secret",
+            "events": ["product.created"],
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = harness
+        .http
+        .post(&admin)
+        .header(INTERNAL_TOKEN_HEADER, INTERNAL_TOKEN)
+        .json(&registration)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let subscription: Value = response.json().await.unwrap();
+    assert!(subscription.get("secret").is_none());
+    let subscription_id = subscription["id"].as_str().unwrap().to_string();
+
+    let products = format!("{}/api/v1/products", harness.catalog_url);
+    let created: Value = harness
+        .http
+        .post(&products)
+        .json(
+            &ProductBuilder::new("4000417025005")
+                .name("Alpine milk chocolate")
+                .create_payload(),
+        )
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let id = created["_id"]["$oid"].as_str().unwrap().to_string();
+    let event = next_event(&mut received).await;
+    assert_eq!(event["type"], "product.created");
+    assert_eq!(event["product_id"], id.as_str());
+    assert_eq!(event["code"], "4000417025005");
+    assert_eq!(event["product"]["product_name"], "Alpine milk chocolate");
+
+    // Not subscribed to updates: the next event is the delete.
+    let by_id = format!("{}/{}", products, id);
+    let response = harness
+        .http
+        .put(&by_id)
+        .json(&json!({ "product_name": "Alpine dark chocolate" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = harness.http.delete(&by_id).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let event = next_event(&mut received).await;
+    assert_eq!(event["type"], "product.deleted");
+    assert_eq!(event["product_id"], id.as_str());
+    assert_eq!(event["product"], Value::Null);
+
+    // The outcome is recorded once the receiver has answered.
+    let mut listed = Value::Null;
+    for _ in 0..50 {
+        listed = harness
+            .http
+            .get(&admin)
+            .header(INTERNAL_TOKEN_HEADER, INTERNAL_TOKEN)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if listed[0]["last_delivery"]["event_id"] == event["id"] {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(listed[0]["last_delivery"]["event_id"], event["id"]);
+    assert_eq!(listed[0]["last_delivery"]["status"], 204);
+    assert_eq!(listed[0]["last_failure"], Value::Null);
+    assert_eq!(listed[0]["failed_deliveries"], 0);
+
+    let subscription_url = format!("{}/{}", admin, subscription_id);
+    for expected in [StatusCode::NO_CONTENT, StatusCode::NOT_FOUND] {
+        let response = harness
+            .http
+            .delete(&subscription_url)
+            .header(INTERNAL_TOKEN_HEADER, INTERNAL_TOKEN)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), expected);
+    }
+}
+
+#[tokio::test]
+async fn reindexing_needs_qdrant_in_memory() {
+    let harness = MemoryHarness::start().await;
+    let reindex = format!("{}/api/v1/admin/reindex", harness.catalog_url);
+
+    let response = harness.http.post(&reindex).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    for request in [
+        harness.http.post(&reindex),
+        harness.http.get(format!("{}/{}", reindex, "665f1c")),
+    ] {
+        let response = request
+            .header(INTERNAL_TOKEN_HEADER, INTERNAL_TOKEN)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
+
+#[tokio::test]
+async fn requests_naming_no_country_are_scoped_to_the_default_in_memory() {
+    let harness = MemoryHarness::start_with_default_country("en:germany").await;
+    for (code, country) in [
+        ("1000000000016", "en:germany"),
+        ("1000000000023", "en:france"),
+        ("1000000000030", "en:germany"),
+    ] {
+        let mut product = ProductBuilder::new(code).build();
+        product.countries = Some(vec![country.to_string()]);
+        product.categories = Some(vec!["en:snacks".to_string()]);
+        harness.seed_product(&product);
+    }
+
+    let get = |path: String| {
+        let request = harness.http.get(format!("{}{}", harness.catalog_url, path));
+        async move {
+            let response = request.send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{}", path);
+            let default_country = response
+                .headers()
+                .get("x-default-country")
+                .map(|value| value.to_str().unwrap().to_string());
+            let body: Value = response.json().await.unwrap();
+            (default_country, body)
+        }
+    };
+    let codes = |items: &Value| {
+        let mut codes: Vec<String> = items
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["code"].as_str().unwrap().to_string())
+            .collect();
+        codes.sort();
+        codes
+    };
+    let germany = Some("en:germany".to_string());
+
+    for search in ["/api/v1/products/search", "/api/v2/products/search"] {
+        let (default_country, page) = get(search.to_string()).await;
+        assert_eq!(default_country, germany, "{}", search);
+        assert_eq!(codes(&page["items"]), ["1000000000016", "1000000000030"]);
+
+        let (default_country, page) = get(format!("{}?country=all", search)).await;
+        assert_eq!(default_country, None, "{}", search);
+        assert_eq!(codes(&page["items"]).len(), 3);
+
+        let (default_country, page) = get(format!("{}?country=France", search)).await;
+        assert_eq!(default_country, None, "{}", search);
+        assert_eq!(codes(&page["items"]), ["1000000000023"]);
+    }
+
+    for (query, expected_country, count) in [
+        ("", germany.clone(), 2),
+        ("?country=all", None, 3),
+        ("?country=en:france", None, 1),
+    ] {
+        let (default_country, counted) = get(format!("/api/v1/products/count{}", query)).await;
+        assert_eq!(default_country, expected_country, "{}", query);
+        assert_eq!(counted, json!({"count": count}), "{}", query);
+
+        let (default_country, categories) = get(format!("/api/v1/categories{}", query)).await;
+        assert_eq!(default_country, expected_country, "{}", query);
+        assert_eq!(categories[0]["count"], json!(count), "{}", query);
+
+        let (default_country, recent) = get(format!("/api/v1/products/recent{}", query)).await;
+        assert_eq!(default_country, expected_country, "{}", query);
+        assert_eq!(codes(&recent["items"]).len(), count, "{}", query);
+
+        let (default_country, _) = get(format!("/api/v1/products/popular{}", query)).await;
+        assert_eq!(default_country, expected_country, "{}", query);
+    }
+}
+
+#[tokio::test]
+async fn searches_mark_the_query_in_their_results_on_request_in_memory() {
+    let harness = MemoryHarness::start().await;
+    harness.seed_product(
+        &ProductBuilder::new("4000417025005")
+            .name("Alpine milk chocolate")
+            .brands(&["milka"])
+            .ingredients("Sugar, cocoa butter, skimmed milk powder, hazelnut paste")
+            .build(),
+    );
+
+    let search = |query: &str| {
+        let request = harness
+            .http
+            .get(format!("{}/api/{}", harness.catalog_url, query));
+        async move {
+            let response = request.send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let page: Value = response.json().await.unwrap();
+            page["items"][0].clone()
+        }
+    };
+
+    let plain = search("v1/products/search?q=milk").await;
+    assert_eq!(plain["code"], "4000417025005");
+    assert!(plain.get("highlights").is_none());
+
+    let full = search("v1/products/search?q=milk&highlight=true").await;
+    assert_eq!(
+        full["highlights"],
+        json!([
+            {"field": "product_name", "snippet": "Alpine <em>milk</em> chocolate"},
+            {"field": "brands_tags", "snippet": "<em>milk</em>a"},
+            {
+                "field": "ingredients_text",
+                "snippet": "Sugar, cocoa butter, skimmed <em>milk</em> powder, hazelnut paste"
+            },
+        ])
+    );
+
+    let summary = search("v2/products/search?q=chocolate&highlight=true&view=summary").await;
+    assert_eq!(
+        summary["highlights"],
+        json!([{"field": "name", "snippet": "Alpine milk <em>chocolate</em>"}])
+    );
+
+    let without_q = search("v1/products/search?highlight=true").await;
+    assert!(without_q.get("highlights").is_none());
+}
+
+#[tokio::test]
+async fn searches_that_find_nothing_suggest_what_they_may_have_meant_in_memory() {
+    let harness = MemoryHarness::start().await;
+    for (code, name) in [
+        ("1000000000016", "Nutella hazelnut spread"),
+        ("1000000000023", "Nutella biscuits"),
+        ("1000000000030", "Greek yoghurt"),
+    ] {
+        harness.seed_product(
+            &ProductBuilder::new(code)
+                .name(name)
+                .brands(&["ferrero"])
+                .build(),
+        );
+    }
+
+    let search = |query: &str| {
+        let request = harness
+            .http
+            .get(format!("{}/api/{}", harness.catalog_url, query));
+        async move {
+            let response = request.send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            response.json::<Value>().await.unwrap()
+        }
+    };
+
+    let misspelt = search("v1/products/search?q=nutela").await;
+    assert_eq!(misspelt["items"], json!([]));
+    assert_eq!(misspelt["did_you_mean"], json!(["nutella"]));
+
+    let v2 = search("v2/products/search?q=Joghurt").await;
+    assert_eq!(v2["items"], json!([]));
+    assert_eq!(v2["didYouMean"], json!(["yoghurt"]));
+
+    let found = search("v1/products/search?q=nutella").await;
+    assert_eq!(found["items"].as_array().unwrap().len(), 2);
+    assert!(found.get("did_you_mean").is_none());
+
+    let unknown = search("v1/products/search?q=xyzzy").await;
+    assert_eq!(unknown["items"], json!([]));
+    assert!(unknown.get("did_you_mean").is_none());
+}
+
+#[tokio::test]
+async fn hybrid_search_ranks_by_text_score_without_an_index_in_memory() {
+    let harness = MemoryHarness::start().await;
+    for (code, name) in [
+        ("1000000000016", "Dark chocolate"),
+        ("1000000000023", "Oat milk chocolate"),
+        ("1000000000030", "Milk chocolate"),
+        ("1000000000047", "Salted crisps"),
+    ] {
+        harness.seed_product(&ProductBuilder::new(code).name(name).build());
+    }
+    let url = format!("{}/api/v1/products/search/hybrid", harness.catalog_url);
+
+    let response = harness
+        .http
+        .get(format!(
+            "{}?q=oat%20milk%20chocolate&semantic_weight=0.9",
+            url
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let hits: Value = response.json().await.unwrap();
+    let ranked: Vec<(&str, f64, f64)> = hits
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|hit| {
+            assert!(hit.get("similarity").is_none());
+            (
+                hit["code"].as_str().unwrap(),
+                hit["text_score"].as_f64().unwrap(),
+                hit["score"].as_f64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(ranked.len(), 3);
+    assert_eq!(
+        ranked.iter().map(|(code, ..)| *code).collect::<Vec<_>>(),
+        ["1000000000023", "1000000000030", "1000000000016"]
+    );
+    assert_eq!(ranked[0].1, 3.0);
+    assert_eq!(ranked[0].2, 1.0);
+    assert!((ranked[2].2 - 1.0 / 3.0).abs() < 1e-9);
+
+    let limited = harness
+        .http
+        .post(&url)
+        .json(&json!({ "q": "chocolate", "vector": [0.1, 0.2], "limit": 2 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(limited.status(), StatusCode::OK);
+    assert_eq!(
+        limited
+            .json::<Value>()
+            .await
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .len(),
+        2
+    );
+
+    for query in [
+        "",
+        "?q=%20",
+        "?q=milk&semantic_weight=1.5",
+        "?q=milk&limit=0",
+    ] {
+        let response = harness
+            .http
+            .get(format!("{}{}", url, query))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", query);
+    }
+}
+
+#[tokio::test]
+async fn product_reports_are_validated_filed_and_listed_in_memory() {
+    let harness = MemoryHarness::start().await;
+    let products = format!("{}/api/v1/products", harness.catalog_url);
+    let admin_reports = format!("{}/api/v1/admin/reports", harness.catalog_url);
+    let mut ids = Vec::new();
+    for (code, name) in [
+        ("4000417025005", "Alpine milk chocolate"),
+        ("1000000000016", "Oat crackers"),
+    ] {
+        let created: Value = harness
+            .http
+            .post(&products)
+            .json(&ProductBuilder::new(code).name(name).create_payload())
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        ids.push(created["_id"]["$oid"].as_str().unwrap().to_string());
+    }
+    let chocolate_reports = format!("{}/{}/reports", products, ids[0]);
+
+    for (body, status) in [
+        (
+            json!({ "reason": "tastes_bad", "comment": "Too sweet" }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ),
+        (
+            json!({ "reason": "other", "comment": "   " }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ),
+        (
+            json!({ "reason": "other", "comment": "x".repeat(1001) }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ),
+    ] {
+        let response = harness
+            .http
+            .post(&chocolate_reports)
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), status, "{}", body);
+    }
+    let valid = json!({ "reason": "other", "comment": "Wrong brand" });
+    for (url, status) in [
+        (
+            format!("{}/{}/reports", products, "000000000000000000000000"),
+            StatusCode::NOT_FOUND,
+        ),
+        (
+            format!("{}/not-an-id/reports", products),
+            StatusCode::BAD_REQUEST,
+        ),
+    ] {
+        let response = harness.http.post(&url).json(&valid).send().await.unwrap();
+        assert_eq!(response.status(), status, "{}", url);
+    }
+
+    for (id, reason, reporter) in [
+        (&ids[0], "wrong_allergens", "shopper-1"),
+        (&ids[1], "wrong_image", "shopper-2"),
+        (&ids[0], "wrong_ingredients", "shopper-3"),
+    ] {
+        let response = harness
+            .http
+            .post(format!("{}/{}/reports", products, id))
+            .json(&json!({
+                "reason": reason,
+                "comment": " Label lists milk ",
+                "reporter_id": reporter
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let report: Value = response.json().await.unwrap();
+        assert_eq!(report["status"], "open");
+        assert_eq!(report["comment"], "Label lists milk");
+        assert!(report["_id"]["$oid"].is_string());
+    }
+    // The report keeps the product as it was.
+    let response = harness
+        .http
+        .delete(format!("{}/{}", products, ids[0]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let page: Value = harness
+        .http
+        .get(format!("{}?limit=1", chocolate_reports))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(page["total"], 2);
+    assert_eq!(page["items"][0]["reason"], "wrong_ingredients");
+    assert_eq!(page["items"][0]["code"], "4000417025005");
+    assert_eq!(page["items"][0]["product_name"], "Alpine milk chocolate");
+    let cursor = page["nextCursor"].as_str().unwrap();
+    let page: Value = harness
+        .http
+        .get(format!("{}?cursor={}", chocolate_reports, cursor))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let reasons: Vec<&Value> = page["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|report| &report["reason"])
+        .collect();
+    assert_eq!(reasons, [&json!("wrong_allergens")]);
+
+    let response = harness.http.get(&admin_reports).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    for (status, total) in [("open", 3), ("resolved", 0)] {
+        let page: Value = harness
+            .http
+            .get(format!("{}?status={}", admin_reports, status))
+            .header(INTERNAL_TOKEN_HEADER, INTERNAL_TOKEN)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(page["total"], total, "{}", status);
+    }
+    let response = harness
+        .http
+        .get(format!("{}?status=closed", admin_reports))
+        .header(INTERNAL_TOKEN_HEADER, INTERNAL_TOKEN)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn filing_reports_is_rate_limited_per_reporter_in_memory() {
+    let harness = MemoryHarness::start().await;
+    let created: Value = harness
+        .http
+        .post(format!("{}/api/v1/products", harness.catalog_url))
+        .json(&ProductBuilder::new("4000417025005").create_payload())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let reports = format!(
+        "{}/api/v1/products/{}/reports",
+        harness.catalog_url,
+        created["_id"]["$oid"].as_str().unwrap()
+    );
+    let file = |reporter: &'static str| {
+        harness
+            .http
+            .post(&reports)
+            .json(&json!({ "reason": "other", "comment": "Wrong", "reporter_id": reporter }))
+            .send()
+    };
+
+    // `REPORTS_PER_MINUTE` is 5 by default.
+    for _ in 0..5 {
+        assert_eq!(file("spammer").await.unwrap().status(), StatusCode::CREATED);
+    }
+    let response = file("spammer").await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().contains_key("retry-after"));
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "rate_limited");
+    assert_eq!(body["details"]["limitPerMinute"], 5);
+
+    // A reporter id is the client's say-so, not another client.
+    assert_eq!(
+        file("someone-else").await.unwrap().status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+}
+
+#[tokio::test]
+async fn moderators_assign_and_resolve_reports_in_memory() {
+    let harness = token_harness().await;
+    let products = format!("{}/api/v1/products", harness.catalog_url);
+    let admin_reports = format!("{}/api/v1/admin/reports", harness.catalog_url);
+    let created: Value = harness
+        .http
+        .post(&products)
+        .header(AUTHORIZATION, bearer("editor-1"))
+        .json(
+            &ProductBuilder::new("4000417025005")
+                .name("Alpine milk chocolate")
+                .create_payload(),
+        )
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let product_id = created["_id"]["$oid"].as_str().unwrap().to_string();
+    let mut report_ids = Vec::new();
+    for reason in ["wrong_allergens", "wrong_image", "wrong_allergens"] {
+        let report: Value = harness
+            .http
+            .post(format!("{}/{}/reports", products, product_id))
+            .json(&json!({ "reason": reason, "comment": "Misses the milk" }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        report_ids.push(report["_id"]["$oid"].as_str().unwrap().to_string());
+    }
+    let admin_post = |path: String, body: Value| {
+        harness
+            .http
+            .post(format!("{}/{}", admin_reports, path))
+            .header(INTERNAL_TOKEN_HEADER, INTERNAL_TOKEN)
+            .header(AUTHORIZATION, bearer("lead-curator"))
+            .json(&body)
+            .send()
+    };
+
+    let response = admin_post(
+        format!("{}/assign", report_ids[0]),
+        json!({ "assignee": "curator-1" }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let assigned: Value = response.json().await.unwrap();
+    assert_eq!(assigned["status"], "open");
+    assert_eq!(assigned["assignment"]["assignee"], "curator-1");
+    assert_eq!(assigned["assignment"]["assigned_by"], "lead-curator");
+
+    // A correction goes with `fixed` only, and must pass the product rules.
+    for (body, status) in [
+        (
+            json!({ "action": "dismissed", "product": { "product_name": "Milk chocolate" } }),
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            json!({ "action": "fixed", "product": { "product_name": "  " } }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ),
+        (
+            json!({ "action": "ignored" }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ),
+    ] {
+        let response = admin_post(format!("{}/resolve", report_ids[0]), body.clone())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), status, "{}", body);
+    }
+    // The correction that failed handed its report back open.
+    let open: Value = harness
+        .http
+        .get(format!("{}?status=open", admin_reports))
+        .header(INTERNAL_TOKEN_HEADER, INTERNAL_TOKEN)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(open["total"], 3);
+
+    let response = admin_post(
+        format!("{}/resolve", report_ids[0]),
+        json!({
+            "action": "fixed",
+            "product": { "product_name": "Alpine milk chocolate with hazelnuts" }
+        }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let resolved: Value = response.json().await.unwrap();
+    assert_eq!(resolved["status"], "resolved");
+    assert_eq!(resolved["resolution"]["action"], "fixed");
+    assert_eq!(resolved["resolution"]["resolved_by"], "lead-curator");
+    // The report keeps the name it was filed against.
+    assert_eq!(resolved["product_name"], "Alpine milk chocolate");
+    let product: Value = harness
+        .http
+        .get(format!("{}/{}", products, product_id))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        product["product_name"],
+        "Alpine milk chocolate with hazelnuts"
+    );
+    let history: Value = harness
+        .http
+        .get(format!("{}/{}/history", products, product_id))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(history["items"][0]["actor"], "lead-curator");
+
+    let response = admin_post(
+        format!("{}/resolve", report_ids[1]),
+        json!({ "action": "dismissed" }),
+    )
+    .await
+    .unwrap();
+    let dismissed: Value = response.json().await.unwrap();
+    assert_eq!(dismissed["status"], "dismissed");
+
+    // Closed reports stay closed.
+    for (path, body) in [
+        (
+            format!("{}/resolve", report_ids[0]),
+            json!({ "action": "dismissed" }),
+        ),
+        (
+            format!("{}/assign", report_ids[1]),
+            json!({ "assignee": "curator-2" }),
+        ),
+    ] {
+        let response = admin_post(path.clone(), body).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT, "{}", path);
+    }
+    for (path, status) in [
+        (
+            "000000000000000000000000/resolve".to_string(),
+            StatusCode::NOT_FOUND,
+        ),
+        ("not-an-id/resolve".to_string(), StatusCode::BAD_REQUEST),
+    ] {
+        let response = admin_post(path.clone(), json!({ "action": "dismissed" }))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), status, "{}", path);
+    }
+
+    let stats: Value = harness
+        .http
+        .get(format!("{}/stats", admin_reports))
+        .header(INTERNAL_TOKEN_HEADER, INTERNAL_TOKEN)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        stats,
+        json!({
+            "open": 1,
+            "open_by_reason": {
+                "wrong_allergens": 1,
+                "wrong_ingredients": 0,
+                "wrong_image": 0,
+                "other": 0
+            }
+        })
+    );
+}
@@ -0,0 +1,294 @@
+//! The OpenFoodFacts importer against a real Mongo, fed a generated 1k-line dump.
+//! Ignored by default: run `cargo integration` from the repository root.
+
+use bson::doc;
+use integration_harness::{CONFIG_REFRESH_INTERVAL, Harness, INTERNAL_TOKEN, OFF_API_CODE};
+use off_import::{
+    Filter, Format, ImportConfig, ImportStats, OffsetFile, RejectsWriter, import, source,
+};
+use product_catalog_service::models::Product;
+use reqwest::StatusCode;
+use serde_json::json;
+use std::path::PathBuf;
+use yoloeats_auth::INTERNAL_TOKEN_HEADER;
+
+const LINES: usize = 1000;
+
+#[derive(Default)]
+struct Expected {
+    imported: u64,
+    skipped_country: u64,
+    skipped_incomplete: u64,
+    rejected: u64,
+}
+
+/// A dump mixing good German rows with every kind of row the importer must not load.
+fn write_dump(path: &PathBuf) -> Expected {
+    let mut expected = Expected::default();
+    let mut lines = Vec::with_capacity(LINES);
+    for i in 0..LINES {
+        let code = format!("400{:010}", i);
+        let line = if i % 50 == 7 {
+            expected.rejected += 1;
+            format!("{{\"code\": \"{}\", \"product_name\": ", code)
+        } else if i % 50 == 13 {
+            expected.rejected += 1;
+            json!({ "code": "n/a", "countries_tags": ["en:germany"], "completeness": 0.9 })
+                .to_string()
+        } else if i % 20 == 3 {
+            expected.skipped_country += 1;
+            json!({ "code": code, "countries_tags": ["en:france"], "completeness": 0.9 })
+                .to_string()
+        } else if i % 25 == 11 {
+            expected.skipped_incomplete += 1;
+            json!({ "code": code, "countries_tags": ["en:germany"], "completeness": 0.1 })
+                .to_string()
+        } else {
+            expected.imported += 1;
+            json!({
+                "code": code,
+                "product_name": format!("Produkt {}", i),
+                "brands": "Testmarke",
+                "countries_tags": ["en:germany", "en:austria"],
+                "allergens_tags": if i % 2 == 0 { json!(["en:milk"]) } else { json!([]) },
+                "labels_tags": ["en:Vegetarian"],
+                "completeness": 0.75,
+                "created_t": 1_600_000_000 + i,
+                "last_modified_t": 1_700_000_000 + i,
+            })
+            .to_string()
+        };
+        lines.push(line);
+    }
+    std::fs::write(path, lines.join("\n") + "\n").unwrap();
+    expected
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("off-import-it-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+struct Run {
+    dump: PathBuf,
+    offsets: OffsetFile,
+    rejects: PathBuf,
+}
+
+impl Run {
+    async fn import(&self, harness: &Harness) -> ImportStats {
+        let config = ImportConfig {
+            filter: Filter {
+                min_completeness: 0.5,
+                ..Filter::default()
+            },
+            batch_size: 100,
+            ..ImportConfig::default()
+        };
+        let reader = source::open(self.dump.to_str().unwrap(), &reqwest::Client::new())
+            .await
+            .unwrap();
+        let mut rejects = RejectsWriter::open(&self.rejects).await.unwrap();
+        import(
+            reader,
+            Format::Jsonl,
+            &harness.catalog_db.collection::<Product>("products"),
+            &self.offsets,
+            &mut rejects,
+            &config,
+        )
+        .await
+        .expect("import succeeds")
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn imports_a_1k_line_dump_and_resumes_from_the_offset() {
+    let harness = Harness::start().await;
+    let run = Run {
+        dump: temp_path("dump.jsonl"),
+        offsets: OffsetFile::new(temp_path("offset")),
+        rejects: temp_path("rejects.jsonl"),
+    };
+    let expected = write_dump(&run.dump);
+
+    let stats = run.import(&harness).await;
+    assert_eq!(stats.lines, LINES as u64);
+    assert_eq!(stats.inserted, expected.imported);
+    assert_eq!(stats.skipped_country, expected.skipped_country);
+    assert_eq!(stats.skipped_incomplete, expected.skipped_incomplete);
+    assert_eq!(stats.rejected, expected.rejected);
+
+    let products = harness.catalog_db.collection::<Product>("products");
+    assert_eq!(
+        products.count_documents(doc! {}).await.unwrap(),
+        expected.imported
+    );
+    let first = products
+        .find_one(doc! { "code": "4000000000000" })
+        .await
+        .unwrap()
+        .expect("row 0 is imported");
+    assert_eq!(first.product_name.as_deref(), Some("Produkt 0"));
+    assert_eq!(first.brands, Some(vec!["testmarke".to_string()]));
+    assert_eq!(first.labels, Some(vec!["en:vegetarian".to_string()]));
+    assert_eq!(first.allergens_tags, vec!["en:milk".to_string()]);
+    assert_eq!(first.source.as_deref(), Some("openfoodfacts"));
+    assert_eq!(first.created_at.timestamp(), 1_600_000_000);
+
+    let rejects: Vec<serde_json::Value> = std::fs::read_to_string(&run.rejects)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(rejects.len() as u64, expected.rejected);
+    assert_eq!(rejects[0]["line"], 8);
+    assert!(
+        rejects[0]["reason"]
+            .as_str()
+            .unwrap()
+            .starts_with("invalid JSON")
+    );
+    assert_eq!(rejects[1]["reason"], "code 'n/a' is not numeric");
+    assert_eq!(run.offsets.load().await.unwrap(), LINES as u64);
+
+    // A finished import has nothing left to do.
+    let stats = run.import(&harness).await;
+    assert_eq!(stats, ImportStats::default());
+
+    // Pretend the first run died after line 500: the rest is re-read, already present.
+    run.offsets.save(500).await.unwrap();
+    let stats = run.import(&harness).await;
+    assert_eq!(stats.lines, 500);
+    assert_eq!(stats.inserted, 0);
+    assert_eq!(stats.updated, 0);
+    assert!(stats.unchanged > 0);
+    assert_eq!(
+        products.count_documents(doc! {}).await.unwrap(),
+        expected.imported
+    );
+
+    for path in [&run.dump, &run.rejects, &run.offsets.path().to_path_buf()] {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn import_endpoint_upserts_in_batches_and_invalidates_the_cache() {
+    let harness = Harness::start().await;
+    // A product the dump updates, cached by barcode before the import.
+    let url = format!("{}/api/v1/products", harness.catalog_url);
+    let response = harness
+        .http
+        .post(&url)
+        .json(&json!({ "code": "4000000000006", "product_name": "Altes Produkt" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let by_code = format!("{}/barcode/4000000000006", url);
+    harness.http.get(&by_code).send().await.unwrap();
+
+    // More lines than one batch, with the bad ones spread across both.
+    let lines: Vec<String> = (0..1200)
+        .map(|i| {
+            if i % 400 == 7 {
+                "{\"code\": ".to_string()
+            } else {
+                json!({
+                    "code": format!("400{:010}", i),
+                    "product_name": format!("Produkt {}", i),
+                })
+                .to_string()
+            }
+        })
+        .collect();
+    let response = harness
+        .http
+        .post(format!("{}/import", url))
+        .body(lines.join("\n"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["inserted"], 1196);
+    assert_eq!(report["updated"], 1);
+    assert_eq!(report["skipped"], 3);
+    assert_eq!(report["errors"][0]["line"], 8);
+    assert_eq!(report["errors"][2]["line"], 808);
+
+    let products = harness.catalog_db.collection::<Product>("products");
+    assert_eq!(products.count_documents(doc! {}).await.unwrap(), 1197);
+    let cached: serde_json::Value = harness
+        .http
+        .get(&by_code)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(cached["product_name"], "Produkt 6");
+}
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn unknown_barcodes_are_fetched_from_openfoodfacts_when_enabled() {
+    let harness = Harness::start().await;
+    let response = harness
+        .http
+        .put(format!("{}/internal/v1/config", harness.catalog_url))
+        .header(INTERNAL_TOKEN_HEADER, INTERNAL_TOKEN)
+        .json(&json!({ "off_fallback_enabled": true }))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+    tokio::time::sleep(CONFIG_REFRESH_INTERVAL * 3).await;
+
+    let url = format!(
+        "{}/api/v1/products/barcode/{}",
+        harness.catalog_url, OFF_API_CODE
+    );
+    let response = harness.http.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["x-fetched-from"], "openfoodfacts");
+    let fetched: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(fetched["product_name"], "Kinder Schokolade");
+    assert_eq!(fetched["source"], "openfoodfacts_live");
+    assert_eq!(fetched["allergens_tags"], json!(["en:milk", "en:soybeans"]));
+
+    let stored = harness
+        .catalog_db
+        .collection::<Product>("products")
+        .find_one(doc! { "code": OFF_API_CODE })
+        .await
+        .unwrap()
+        .expect("the fetched product is stored");
+    assert_eq!(stored.source.as_deref(), Some("openfoodfacts_live"));
+
+    // From now on the catalog has it.
+    let response = harness.http.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!response.headers().contains_key("x-fetched-from"));
+    let v2 = format!(
+        "{}/api/v2/products/barcode/{}",
+        harness.catalog_url, OFF_API_CODE
+    );
+    let response = harness.http.get(&v2).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!response.headers().contains_key("x-fetched-from"));
+
+    // Barcodes OpenFoodFacts doesn't know either are the usual 404.
+    let unknown = format!(
+        "{}/api/v1/products/barcode/4006381333931",
+        harness.catalog_url
+    );
+    let response = harness.http.get(&unknown).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert!(!response.headers().contains_key("x-fetched-from"));
+}
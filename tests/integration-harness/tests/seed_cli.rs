@@ -0,0 +1,67 @@
+//! `seed all` against fresh containers must leave a stack the checker can answer from.
+//! Ignored by default: run `cargo integration` from the repository root.
+
+use integration_harness::Harness;
+use reqwest::StatusCode;
+use seed_cli::{Counts, barcode, seed_all};
+use serde_json::json;
+use yoloeats_domain::{CheckResult, SafetyStatus};
+
+#[tokio::test]
+#[ignore = "needs Docker; run with `cargo integration`"]
+async fn seeded_chocolate_is_unsafe_for_the_seeded_milk_allergic_user() {
+    let harness = Harness::start().await;
+    let users = vec!["alice".to_string(), "bob".to_string()];
+
+    let summary = seed_all(
+        &harness.catalog_db,
+        &harness.profile_db,
+        &harness.neo4j,
+        10,
+        &users,
+    )
+    .await
+    .expect("seed all");
+    assert_eq!(summary.rows[0].1.inserted, 10);
+    assert_eq!(summary.rows[1].1.inserted, 2);
+
+    // Fixture 0 is the milk chocolate; the first user is allergic to milk.
+    let response = harness
+        .http
+        .post(format!("{}/api/v1/check", harness.checker_url))
+        .json(&json!({ "productIdentifier": barcode(0), "userId": "alice" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let result: CheckResult = response.json().await.unwrap();
+    assert_eq!(result.status, SafetyStatus::Unsafe);
+    assert!(result.conflicting_allergens.contains(&"milk".to_string()));
+
+    let rerun = seed_all(
+        &harness.catalog_db,
+        &harness.profile_db,
+        &harness.neo4j,
+        10,
+        &users,
+    )
+    .await
+    .expect("seed all again");
+    for (target, counts) in &rerun.rows {
+        assert_eq!(
+            (counts.inserted, counts.updated),
+            (0, 0),
+            "reseeding {} changed data: {:?}",
+            target,
+            counts
+        );
+    }
+    assert_eq!(
+        rerun.rows[0].1,
+        Counts {
+            inserted: 0,
+            updated: 0,
+            unchanged: 10
+        }
+    );
+}